@@ -1,3 +1,4 @@
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -8,6 +9,12 @@ use walkdir::WalkDir;
 use sha2::{Digest, Sha256};
 use hex;
 use anyhow::Result;
+use aios_cancel::CancellationToken;
+use aios_plugins::Hook;
+use aios_store::{Migration, Namespace, Store, Value as StoreValue};
+#[cfg(feature = "python")]
+use aios_config::AiosConfig;
+use aios_config::BackupConfig;
 
 /*
  * AIOS Backup Core - Rust Implementation
@@ -23,20 +30,34 @@ use anyhow::Result;
 /// 
 /// Returned to Python layer after backup operations
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all))]
 pub struct BackupResult {
-    #[pyo3(get)]
     pub success: bool,
-    #[pyo3(get)]
     pub files_processed: u32,
-    #[pyo3(get)]
     pub files_changed: u32,
-    #[pyo3(get)]
     pub time_taken_ms: u64,
-    #[pyo3(get)]
     pub backup_path: String,
-    #[pyo3(get)]
     pub error_message: Option<String>,
+    /// True when the backup stopped early through its cancellation token;
+    /// checksums are then left as they were, so the next backup redoes it
+    pub cancelled: bool,
+}
+
+/// Result of restoring files from a backup
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct RestoreResult {
+    pub success: bool,
+    /// Files copied (or, in a dry run, that would be copied)
+    pub files_restored: u32,
+    /// Files already identical to the backed-up version
+    pub files_unchanged: u32,
+    pub time_taken_ms: u64,
+    pub restore_path: String,
+    /// Restored paths, relative to `restore_path`
+    pub restored_files: Vec<String>,
+    pub error_message: Option<String>,
+    pub cancelled: bool,
 }
 
 /// File metadata for tracking changes
@@ -48,6 +69,17 @@ pub struct FileMetadata {
     pub modified_time: u64,
 }
 
+/// Tables of the backup core's store namespace; rows are per backup directory
+const STORE_MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    sql: "CREATE TABLE backup_checksums (
+              backup_dir TEXT NOT NULL,
+              path TEXT NOT NULL,
+              checksum TEXT NOT NULL,
+              PRIMARY KEY (backup_dir, path)
+          );",
+}];
+
 /// Rust implementation of AIOS Backup Core
 /// 
 /// Provides high-performance backup operations with:
@@ -63,11 +95,14 @@ pub struct RustBackupCore {
     archive_backup_dir: PathBuf,
     file_checksums: HashMap<String, String>,
     last_backup_timestamp: u64,
+    config: BackupConfig,
+    /// Replaces the JSON tracking files once attached
+    store: Option<Namespace>,
 }
 
 impl RustBackupCore {
     /// Initialize the Rust backup core
-    pub fn new(backup_dir: &str) -> Result<Self> {
+    pub fn new(backup_dir: &str, config: BackupConfig) -> Result<Self> {
         let backup_path = PathBuf::from(backup_dir);
         let active_backup = backup_path.join("active_backup");
         let archive_backup = backup_path.join("archive_backup");
@@ -103,37 +138,116 @@ impl RustBackupCore {
             archive_backup_dir: archive_backup,
             file_checksums,
             last_backup_timestamp,
+            config,
+            store: None,
         })
     }
 
+    /// Keep checksums and the backup timestamp in `store` instead of the
+    /// JSON files in the backup directory. On first use the state loaded
+    /// from those files is copied into the store.
+    pub fn attach_store(&mut self, store: &Store) -> Result<()> {
+        let namespace = store.namespace("backup", STORE_MIGRATIONS)?;
+        let backup_dir = self.backup_dir.to_string_lossy().to_string();
+        let tracking_key = format!("tracking:{}", backup_dir);
+        match namespace.get::<serde_json::Value>(&tracking_key)? {
+            Some(tracking) => {
+                self.last_backup_timestamp = tracking.get("last_backup_timestamp").and_then(|v| v.as_u64()).unwrap_or(0);
+                let rows = namespace.query(
+                    "SELECT path, checksum FROM backup_checksums WHERE backup_dir = ?",
+                    &[backup_dir.into()],
+                )?;
+                self.file_checksums = rows
+                    .into_iter()
+                    .filter_map(|row| match (row.first(), row.get(1)) {
+                        (Some(StoreValue::Text(path)), Some(StoreValue::Text(checksum))) => Some((path.clone(), checksum.clone())),
+                        _ => None,
+                    })
+                    .collect();
+                self.store = Some(namespace);
+            }
+            None => {
+                self.store = Some(namespace);
+                self.save_checksums()?;
+                self.save_tracking()?;
+            }
+        }
+        Ok(())
+    }
+
     /// Create/update backup with Git-like incremental behavior
     pub fn create_backup(
         &mut self,
         include_data: bool,
         include_logs: bool,
         include_config: bool,
+        cancel: &CancellationToken,
     ) -> Result<BackupResult> {
         let start_time = SystemTime::now();
 
         // Get files to backup
         let files_to_backup = self.get_files_to_backup(include_data, include_logs, include_config)?;
+        aios_events::event!("backup.progress", phase = "scanning", files_total = files_to_backup.len());
+        if aios_plugins::active(Hook::PreBackup) {
+            let backup = serde_json::json!({
+                "include_data": include_data,
+                "include_logs": include_logs,
+                "include_config": include_config,
+                "backup_dir": self.active_backup_dir.to_string_lossy(),
+                "files_total": files_to_backup.len(),
+            });
+            if let Err(rejection) = aios_plugins::run(Hook::PreBackup, backup, |_| false) {
+                return Ok(BackupResult {
+                    success: false,
+                    files_processed: 0,
+                    files_changed: 0,
+                    time_taken_ms: start_time.elapsed()?.as_millis() as u64,
+                    backup_path: self.active_backup_dir.to_string_lossy().to_string(),
+                    error_message: Some(format!("Backup was vetoed by plugin {}", rejection.plugin)),
+                    cancelled: false,
+                });
+            }
+        }
         
         // Get changed files
-        let changed_files = self.get_changed_files(&files_to_backup)?;
+        let Some(changed_files) = self.get_changed_files(&files_to_backup, cancel)? else {
+            return self.cancelled_result(files_to_backup.len(), 0, start_time);
+        };
         
         // Archive changed files (Git-like: clear archive and create fresh)
-        if !changed_files.is_empty() {
-            self.archive_changed_files(&changed_files)?;
+        aios_events::event!(
+            "backup.progress",
+            phase = "archiving",
+            files_total = files_to_backup.len(),
+            files_changed = changed_files.len(),
+        );
+        if !changed_files.is_empty() && !self.archive_changed_files(&changed_files, cancel)? {
+            return self.cancelled_result(files_to_backup.len(), changed_files.len(), start_time);
         }
 
         // Update active backup
-        self.update_active_backup(&files_to_backup)?;
+        aios_events::event!(
+            "backup.progress",
+            phase = "updating",
+            files_total = files_to_backup.len(),
+            files_changed = changed_files.len(),
+        );
+        if !self.update_active_backup(&files_to_backup, cancel)? {
+            return self.cancelled_result(files_to_backup.len(), changed_files.len(), start_time);
+        }
 
         // Update checksums and tracking
         self.update_file_checksums(&files_to_backup)?;
         self.update_backup_timestamp()?;
 
         let elapsed = start_time.elapsed()?.as_millis() as u64;
+        aios_events::event!(
+            "backup.progress",
+            phase = "completed",
+            files_total = files_to_backup.len(),
+            files_changed = changed_files.len(),
+            time_taken_ms = elapsed,
+        );
 
         Ok(BackupResult {
             success: true,
@@ -142,9 +256,81 @@ impl RustBackupCore {
             time_taken_ms: elapsed,
             backup_path: self.active_backup_dir.to_string_lossy().to_string(),
             error_message: None,
+            cancelled: false,
         })
     }
 
+    fn cancelled_result(&self, files_processed: usize, files_changed: usize, start_time: SystemTime) -> Result<BackupResult> {
+        aios_events::event!("backup.progress", phase = "cancelled", files_total = files_processed, files_changed = files_changed);
+        Ok(BackupResult {
+            success: false,
+            files_processed: files_processed as u32,
+            files_changed: files_changed as u32,
+            time_taken_ms: start_time.elapsed()?.as_millis() as u64,
+            backup_path: self.active_backup_dir.to_string_lossy().to_string(),
+            error_message: Some("Backup was cancelled".to_string()),
+            cancelled: true,
+        })
+    }
+
+    /// Copy backed-up files into `target_dir`, keeping their relative paths.
+    ///
+    /// Restores the active backup, or with `from_archive` the archived
+    /// versions the last backup replaced. Files whose content already
+    /// matches are left alone; a dry run only reports what would be copied.
+    pub fn restore_backup(
+        &self,
+        target_dir: &Path,
+        from_archive: bool,
+        dry_run: bool,
+        cancel: &CancellationToken,
+    ) -> Result<RestoreResult> {
+        let start_time = SystemTime::now();
+        let source_dir = if from_archive { &self.archive_backup_dir } else { &self.active_backup_dir };
+        let mut result = RestoreResult {
+            success: true,
+            files_restored: 0,
+            files_unchanged: 0,
+            time_taken_ms: 0,
+            restore_path: target_dir.to_string_lossy().to_string(),
+            restored_files: Vec::new(),
+            error_message: None,
+            cancelled: false,
+        };
+
+        for entry in WalkDir::new(source_dir).sort_by_file_name() {
+            if cancel.is_cancelled() {
+                result.success = false;
+                result.cancelled = true;
+                result.error_message = Some("Restore was cancelled".to_string());
+                break;
+            }
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative_path = entry.path().strip_prefix(source_dir)?;
+            let target_path = target_dir.join(relative_path);
+            if target_path.is_file()
+                && self.calculate_file_checksum(&target_path)? == self.calculate_file_checksum(entry.path())?
+            {
+                result.files_unchanged += 1;
+                continue;
+            }
+            if !dry_run {
+                if let Some(parent) = target_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(entry.path(), &target_path)?;
+            }
+            result.files_restored += 1;
+            result.restored_files.push(relative_path.to_string_lossy().to_string());
+        }
+
+        result.time_taken_ms = start_time.elapsed()?.as_millis() as u64;
+        Ok(result)
+    }
+
     /// Get list of files to backup
     fn get_files_to_backup(
         &self,
@@ -156,19 +342,7 @@ impl RustBackupCore {
         let current_dir = std::env::current_dir()?;
 
         // Core directories to backup (exclude backup_core to avoid recursion)
-        let core_dirs = vec![
-            "carma_core",
-            "data_core", 
-            "dream_core",
-            "enterprise_core",
-            "luna_core",
-            "streamlit_core",
-            "support_core",
-            "utils_core",
-        ];
-
-        // Add core files
-        for core_dir in core_dirs {
+        for core_dir in &self.config.roots {
             let dir_path = current_dir.join(core_dir);
             if dir_path.exists() {
                 for entry in WalkDir::new(&dir_path) {
@@ -177,9 +351,8 @@ impl RustBackupCore {
                         let path = entry.path();
                         // Skip problematic directories
                         if path.components().any(|c| {
-                            matches!(c, std::path::Component::Normal(s) if 
-                                s == ".git" || s == "__pycache__" || 
-                                s == ".pytest_cache" || s == "node_modules")
+                            matches!(c, std::path::Component::Normal(s) if
+                                self.config.exclude_dirs.iter().any(|excluded| s == excluded.as_str()))
                         }) {
                             continue;
                         }
@@ -190,8 +363,7 @@ impl RustBackupCore {
         }
 
         // Add main files
-        let main_files = vec!["main.py", "requirements.txt", "README.md"];
-        for main_file in main_files {
+        for main_file in &self.config.files {
             let file_path = current_dir.join(main_file);
             if file_path.exists() {
                 files.push(file_path);
@@ -200,7 +372,7 @@ impl RustBackupCore {
 
         // Add conditional directories
         if include_config {
-            let config_dir = current_dir.join("config");
+            let config_dir = current_dir.join(&self.config.config_dir);
             if config_dir.exists() {
                 for entry in WalkDir::new(&config_dir) {
                     let entry = entry?;
@@ -212,7 +384,7 @@ impl RustBackupCore {
         }
 
         if include_logs {
-            let log_dir = current_dir.join("log");
+            let log_dir = current_dir.join(&self.config.log_dir);
             if log_dir.exists() {
                 for entry in WalkDir::new(&log_dir) {
                     let entry = entry?;
@@ -226,11 +398,14 @@ impl RustBackupCore {
         Ok(files)
     }
 
-    /// Get list of changed files
-    fn get_changed_files(&self, files_to_backup: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    /// Get list of changed files (None if cancelled)
+    fn get_changed_files(&self, files_to_backup: &[PathBuf], cancel: &CancellationToken) -> Result<Option<Vec<PathBuf>>> {
         let mut changed_files = Vec::new();
 
         for file_path in files_to_backup {
+            if cancel.is_cancelled() {
+                return Ok(None);
+            }
             let current_checksum = self.calculate_file_checksum(file_path)?;
             let path_str = file_path.to_string_lossy().to_string();
             let stored_checksum = self.file_checksums.get(&path_str);
@@ -240,11 +415,12 @@ impl RustBackupCore {
             }
         }
 
-        Ok(changed_files)
+        Ok(Some(changed_files))
     }
 
-    /// Archive changed files (Git-like: clear and recreate archive)
-    fn archive_changed_files(&self, changed_files: &[PathBuf]) -> Result<()> {
+    /// Archive changed files (Git-like: clear and recreate archive).
+    /// Returns false if cancelled.
+    fn archive_changed_files(&self, changed_files: &[PathBuf], cancel: &CancellationToken) -> Result<bool> {
         // Clear existing archive (Git-like behavior)
         if self.archive_backup_dir.exists() {
             fs::remove_dir_all(&self.archive_backup_dir)?;
@@ -254,6 +430,9 @@ impl RustBackupCore {
         let current_dir = std::env::current_dir()?;
 
         for file_path in changed_files {
+            if cancel.is_cancelled() {
+                return Ok(false);
+            }
             // Get relative path
             let relative_path = match file_path.strip_prefix(&current_dir) {
                 Ok(rel) => rel,
@@ -274,14 +453,17 @@ impl RustBackupCore {
             }
         }
 
-        Ok(())
+        Ok(true)
     }
 
-    /// Update active backup with current files
-    fn update_active_backup(&self, files_to_backup: &[PathBuf]) -> Result<()> {
+    /// Update active backup with current files; returns false if cancelled
+    fn update_active_backup(&self, files_to_backup: &[PathBuf], cancel: &CancellationToken) -> Result<bool> {
         let current_dir = std::env::current_dir()?;
 
         for file_path in files_to_backup {
+            if cancel.is_cancelled() {
+                return Ok(false);
+            }
             // Get relative path
             let relative_path = match file_path.strip_prefix(&current_dir) {
                 Ok(rel) => rel,
@@ -299,7 +481,7 @@ impl RustBackupCore {
             fs::copy(file_path, &backup_file_path)?;
         }
 
-        Ok(())
+        Ok(true)
     }
 
     /// Calculate SHA256 checksum of a file
@@ -318,11 +500,26 @@ impl RustBackupCore {
             self.file_checksums.insert(path_str, checksum);
         }
 
-        // Save checksums to file
-        let checksums_file = self.backup_dir.join("file_checksums.json");
-        let content = serde_json::to_string_pretty(&self.file_checksums)?;
-        fs::write(checksums_file, content)?;
+        self.save_checksums()
+    }
 
+    fn save_checksums(&self) -> Result<()> {
+        let Some(store) = &self.store else {
+            let checksums_file = self.backup_dir.join("file_checksums.json");
+            let content = serde_json::to_string_pretty(&self.file_checksums)?;
+            fs::write(checksums_file, content)?;
+            return Ok(());
+        };
+        let backup_dir = self.backup_dir.to_string_lossy().to_string();
+        store.transaction(|connection| {
+            connection.execute("DELETE FROM backup_checksums WHERE backup_dir = ?", &[backup_dir.as_str().into()])?;
+            let mut insert =
+                connection.prepare("INSERT INTO backup_checksums (backup_dir, path, checksum) VALUES (?, ?, ?)")?;
+            for (path, checksum) in &self.file_checksums {
+                insert.execute(&[backup_dir.as_str().into(), path.as_str().into(), checksum.as_str().into()])?;
+            }
+            Ok(())
+        })?;
         Ok(())
     }
 
@@ -331,26 +528,69 @@ impl RustBackupCore {
         self.last_backup_timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)?
             .as_secs();
+        self.save_tracking()
+    }
 
+    fn save_tracking(&self) -> Result<()> {
         let tracking_data = serde_json::json!({
             "last_backup_timestamp": self.last_backup_timestamp,
             "backup_count": self.file_checksums.len()
         });
 
-        let tracking_file = self.backup_dir.join("backup_tracking.json");
-        let content = serde_json::to_string_pretty(&tracking_data)?;
-        fs::write(tracking_file, content)?;
-
+        match &self.store {
+            Some(store) => store.set(&format!("tracking:{}", self.backup_dir.to_string_lossy()), &tracking_data)?,
+            None => {
+                let tracking_file = self.backup_dir.join("backup_tracking.json");
+                let content = serde_json::to_string_pretty(&tracking_data)?;
+                fs::write(tracking_file, content)?;
+            }
+        }
         Ok(())
     }
 }
 
+#[cfg(feature = "python")]
+mod errors {
+    aios_errors::python_exceptions!(aios_backup_rust);
+}
+
+#[cfg(feature = "python")]
+mod tracing {
+    aios_trace::python_tracing!();
+}
+
+#[cfg(feature = "python")]
+mod awaitable {
+    aios_async::python_awaitables!();
+}
+
+#[cfg(feature = "python")]
+mod cancel {
+    aios_cancel::python_cancellation!();
+}
+
+#[cfg(feature = "python")]
+mod threads {
+    aios_threads::python_thread_pool!();
+}
+
+#[cfg(feature = "python")]
+mod events {
+    aios_events::python_events!();
+}
+
+#[cfg(feature = "python")]
+mod plugins {
+    aios_plugins::python_plugins!(PreBackup);
+}
+
 /// Python module interface
 /// 
 /// Exports Rust backup functionality to Python via PyO3
 /// 
 /// Available classes:
 /// - BackupResult: Result of backup operations
+/// - RestoreResult: Result of restore operations
 /// - PyRustBackupCore: Main backup interface
 /// 
 /// Future enhancements planned:
@@ -358,37 +598,102 @@ impl RustBackupCore {
 /// - Branching support
 /// - Staging area
 /// - Diff engine
+#[cfg(feature = "python")]
 #[pymodule]
-fn aios_backup_rust(_py: Python, m: &PyModule) -> PyResult<()> {
+fn aios_backup_rust(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<BackupResult>()?;
+    m.add_class::<RestoreResult>()?;
     m.add_class::<PyRustBackupCore>()?;
+    errors::register(py, m)?;
+    tracing::register(m)?;
+    cancel::register(m)?;
+    threads::register(m)?;
+    events::register(m)?;
+    plugins::register(m)?;
     Ok(())
 }
 
 /// Python wrapper for RustBackupCore
+///
+/// Backups run with the GIL released.
+#[cfg(feature = "python")]
 #[pyclass]
 pub struct PyRustBackupCore {
     core: RustBackupCore,
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl PyRustBackupCore {
+    /// Backup roots and exclusions come from the `[backup]` section of
+    /// `config_path`, `$AIOS_CONFIG` or `./aios.toml` (defaults if none exist).
+    /// With `[store] path` set, checksums are tracked in that database.
     #[new]
-    fn new(backup_dir: &str) -> PyResult<Self> {
-        let core = RustBackupCore::new(backup_dir)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to initialize backup core: {}", e)))?;
+    #[pyo3(signature = (backup_dir, config_path=None))]
+    fn new(backup_dir: &str, config_path: Option<&str>) -> PyResult<Self> {
+        let config = AiosConfig::load(config_path).map_err(errors::to_pyerr)?;
+        let mut core = RustBackupCore::new(backup_dir, config.backup)
+            .map_err(|e| errors::io(format!("Failed to initialize backup core: {}", e)))?;
+        if !config.store.path.is_empty() {
+            let store = Store::open(&config.store.path).map_err(errors::to_pyerr)?;
+            core.attach_store(&store).map_err(|e| errors::io(format!("Failed to attach store: {}", e)))?;
+        }
         Ok(Self { core })
     }
 
+    /// Back up changed files. A cancelled backup returns `cancelled=True`;
+    /// one vetoed by a `pre_backup` plugin returns `success=False`.
+    #[pyo3(signature = (include_data, include_logs, include_config, cancel_token=None))]
     fn create_backup(
         &mut self,
+        py: Python<'_>,
         include_data: bool,
         include_logs: bool,
         include_config: bool,
+        cancel_token: Option<PyObject>,
     ) -> PyResult<BackupResult> {
-        match self.core.create_backup(include_data, include_logs, include_config) {
+        let _span = aios_trace::span!("PyRustBackupCore.create_backup", include_data = include_data, include_logs = include_logs, include_config = include_config);
+        let cancel = cancel::token(py, cancel_token)?;
+        match py.allow_threads(|| {
+            aios_threads::install(|| self.core.create_backup(include_data, include_logs, include_config, &cancel))
+        }) {
             Ok(result) => Ok(result),
-            Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Backup failed: {}", e)))
+            Err(e) => Err(errors::io(format!("Backup failed: {}", e)))
         }
     }
+
+    /// Copy backed-up files into `target_dir` (the working directory by
+    /// default); `from_archive` restores the versions the last backup replaced.
+    /// A cancelled restore returns `cancelled=True` with the files copied so far.
+    #[pyo3(signature = (target_dir=".", from_archive=false, dry_run=false, cancel_token=None))]
+    fn restore_backup(
+        &self,
+        py: Python<'_>,
+        target_dir: &str,
+        from_archive: bool,
+        dry_run: bool,
+        cancel_token: Option<PyObject>,
+    ) -> PyResult<RestoreResult> {
+        let _span = aios_trace::span!("PyRustBackupCore.restore_backup", target_dir = target_dir, from_archive = from_archive, dry_run = dry_run);
+        let cancel = cancel::token(py, cancel_token)?;
+        py.allow_threads(|| aios_threads::install(|| self.core.restore_backup(Path::new(target_dir), from_archive, dry_run, &cancel)))
+            .map_err(|e| errors::io(format!("Restore failed: {}", e)))
+    }
+
+    /// Awaitable `create_backup`; the backup runs on a background thread.
+    /// Other calls on this core raise RuntimeError until it completes.
+    #[pyo3(signature = (include_data, include_logs, include_config, cancel_token=None))]
+    fn create_backup_async(
+        slf: Py<Self>,
+        py: Python<'_>,
+        include_data: bool,
+        include_logs: bool,
+        include_config: bool,
+        cancel_token: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("PyRustBackupCore.create_backup_async", include_data = include_data, include_logs = include_logs, include_config = include_config);
+        awaitable::spawn(py, move || {
+            Python::with_gil(|py| slf.try_borrow_mut(py)?.create_backup(py, include_data, include_logs, include_config, cancel_token))
+        })
+    }
 }
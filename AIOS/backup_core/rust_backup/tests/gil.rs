@@ -0,0 +1,75 @@
+//! Long calls release the GIL, so other Python threads keep running
+
+#![cfg(feature = "python")]
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use aios_backup_rust::PyRustBackupCore;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// A Python thread counting every millisecond until `running` is cleared
+const TICKER: &str = r#"
+import threading, time
+ticks = 0
+running = True
+def tick():
+    global ticks
+    while running:
+        ticks += 1
+        time.sleep(0.001)
+ticker = threading.Thread(target=tick, daemon=True)
+ticker.start()
+"#;
+
+/// Ticks counted while `call` is repeated, holding the GIL in between, for
+/// `span`
+fn ticks_during(globals: &PyDict, span: Duration, mut call: impl FnMut() -> PyResult<()>) -> PyResult<u64> {
+    let ticks = || -> PyResult<u64> { globals.get_item("ticks")?.expect("ticker started").extract() };
+    let start = ticks()?;
+    let deadline = Instant::now() + span;
+    while Instant::now() < deadline {
+        call()?;
+    }
+    Ok(ticks()? - start)
+}
+
+#[test]
+fn test_backups_release_gil() -> PyResult<()> {
+    let root = std::env::temp_dir().join(format!("aios_backup_gil_{}", std::process::id()));
+    let sources = root.join("sources");
+    for dir in 0..10 {
+        fs::create_dir_all(sources.join(dir.to_string())).unwrap();
+        for file in 0..20 {
+            fs::write(sources.join(dir.to_string()).join(format!("{}.txt", file)), vec![b'x'; 16 * 1024]).unwrap();
+        }
+    }
+    let config = root.join("aios.toml");
+    let roots = format!("[backup]\nroots = [{:?}]\nfiles = []\n", sources.to_str().unwrap());
+    fs::write(&config, roots).unwrap();
+
+    pyo3::prepare_freethreaded_python();
+    let result = Python::with_gil(|py| {
+        let globals = PyDict::new(py);
+        py.run(TICKER, Some(globals), None)?;
+        // The ticker stalls while the GIL is held...
+        let stalled = ticks_during(globals, Duration::from_millis(200), || {
+            std::thread::sleep(Duration::from_millis(10));
+            Ok(())
+        })?;
+        // ...but not during calls that release it
+        let backup_dir = root.join("backups");
+        let core = py.get_type::<PyRustBackupCore>().call1((backup_dir.to_str().unwrap(), config.to_str().unwrap()))?;
+        let backing_up = ticks_during(globals, Duration::from_millis(200), || {
+            core.call_method1("create_backup", (false, false, false)).map(drop)
+        })?;
+        globals.set_item("running", false)?;
+        py.run("ticker.join()", Some(globals), None)?;
+        assert!(stalled <= 2, "ticked {} times with the GIL held", stalled);
+        assert!(backing_up >= 20, "ticked only {} times during backups", backing_up);
+        Ok(())
+    });
+    let _ = fs::remove_dir_all(&root);
+    result
+}
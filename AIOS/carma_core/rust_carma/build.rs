@@ -0,0 +1,17 @@
+//! Links libpython into the integration tests, which embed an interpreter;
+//! the extension module itself is loaded into one and must not link it.
+
+fn main() {
+    // PyO3 links it on Windows either way
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("windows") {
+        return;
+    }
+    let config = pyo3_build_config::get();
+    if let Some(dir) = &config.lib_dir {
+        println!("cargo:rustc-link-arg-tests=-L{}", dir);
+        println!("cargo:rustc-link-arg-tests=-Wl,-rpath,{}", dir);
+    }
+    if let Some(name) = &config.lib_name {
+        println!("cargo:rustc-link-arg-tests=-l{}", name);
+    }
+}
@@ -0,0 +1,135 @@
+//! Rolling-window retrieval analytics reported by `get_stats`
+
+use std::collections::VecDeque;
+
+/// A query slower than this many times the window's median latency is a spike...
+const SPIKE_FACTOR: f64 = 5.0;
+/// ...if it also took at least this long (seconds), so jitter on fast queries is not one
+const SPIKE_FLOOR: f64 = 0.05;
+/// Queries in the window before spikes are reported
+const SPIKE_MIN_SAMPLES: usize = 20;
+
+/// Retrieval path a query took
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryClass {
+    Semantic,
+    Diverse,
+    Reranked,
+    Keyword,
+    Hybrid,
+}
+
+impl QueryClass {
+    pub const ALL: [QueryClass; 5] = [
+        QueryClass::Semantic,
+        QueryClass::Diverse,
+        QueryClass::Reranked,
+        QueryClass::Keyword,
+        QueryClass::Hybrid,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            QueryClass::Semantic => "semantic",
+            QueryClass::Diverse => "diverse",
+            QueryClass::Reranked => "reranked",
+            QueryClass::Keyword => "keyword",
+            QueryClass::Hybrid => "hybrid",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct QuerySample {
+    class: QueryClass,
+    /// Seconds
+    latency: f64,
+    /// Size of the candidate set the final ranking was taken from
+    candidates: usize,
+}
+
+/// The most recent `window` queries
+#[derive(Debug, Clone)]
+pub struct RetrievalAnalytics {
+    window: usize,
+    samples: VecDeque<QuerySample>,
+}
+
+impl RetrievalAnalytics {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            samples: VecDeque::with_capacity(window.min(4096)),
+        }
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Resize the window, dropping the oldest samples if it shrinks
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window;
+        while self.samples.len() > window {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn record(&mut self, class: QueryClass, latency: f64, candidates: usize) {
+        if self.window == 0 {
+            return;
+        }
+        if self.samples.len() >= self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(QuerySample { class, latency, candidates });
+    }
+
+    /// The window's median latency when `latency` is a spike against it
+    pub fn spike_baseline(&self, latency: f64) -> Option<f64> {
+        if latency < SPIKE_FLOOR || self.samples.len() < SPIKE_MIN_SAMPLES {
+            return None;
+        }
+        let median = self.latency_percentiles(&[50.0])[0];
+        (latency > median * SPIKE_FACTOR).then_some(median)
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Nearest-rank latency percentiles in seconds (0 when empty)
+    pub fn latency_percentiles(&self, percentiles: &[f64]) -> Vec<f64> {
+        let mut latencies: Vec<f64> = self.samples.iter().map(|s| s.latency).collect();
+        latencies.sort_by(|a, b| a.total_cmp(b));
+        percentiles
+            .iter()
+            .map(|&p| {
+                if latencies.is_empty() {
+                    return 0.0;
+                }
+                let rank = (p / 100.0 * latencies.len() as f64).ceil() as usize;
+                latencies[rank.clamp(1, latencies.len()) - 1]
+            })
+            .collect()
+    }
+
+    pub fn mean_candidates(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().map(|s| s.candidates as f64).sum::<f64>() / self.samples.len() as f64
+    }
+
+    /// Query count per class, including classes with no queries
+    pub fn class_counts(&self) -> Vec<(&'static str, usize)> {
+        QueryClass::ALL
+            .iter()
+            .map(|class| (class.name(), self.samples.iter().filter(|s| s.class == *class).count()))
+            .collect()
+    }
+}
@@ -0,0 +1,195 @@
+//! Split raw documents into fragment-sized chunks
+//!
+//! Tokens are approximated as whitespace-separated words. Chunks are packed
+//! from whole sentences where possible; a sentence longer than the target is
+//! split on word boundaries. Chunk text is a slice of the original document,
+//! so spacing and punctuation are preserved.
+
+/// Chunking parameters
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkParams {
+    /// Preferred chunk length in tokens
+    pub target_tokens: usize,
+    /// Tokens repeated from the end of one chunk at the start of the next
+    pub overlap_tokens: usize,
+    /// Pack whole sentences; otherwise pack individual words
+    pub respect_sentences: bool,
+}
+
+/// A chunk as a byte range into the source text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    pub start: usize,
+    pub end: usize,
+    pub tokens: usize,
+}
+
+/// Contiguous run of text packed as a unit: (start, end, tokens)
+type Unit = (usize, usize, usize);
+
+/// Split `text` into chunks of about `target_tokens` tokens
+pub fn split_document(text: &str, params: ChunkParams) -> Vec<Chunk> {
+    let target = params.target_tokens.max(1);
+    let overlap = params.overlap_tokens.min(target - 1);
+
+    let units: Vec<Unit> = if params.respect_sentences {
+        sentence_spans(text)
+            .into_iter()
+            .flat_map(|(start, end)| {
+                let words = word_spans(text, start, end);
+                if words.len() <= target {
+                    vec![(start, end, words.len())]
+                } else {
+                    words.chunks(target).map(|w| (w[0].0, w[w.len() - 1].1, w.len())).collect()
+                }
+            })
+            .collect()
+    } else {
+        word_spans(text, 0, text.len()).into_iter().map(|(start, end)| (start, end, 1)).collect()
+    };
+
+    let mut chunks = Vec::new();
+    let mut first = 0;
+    while first < units.len() {
+        // Greedily take units up to the target, always at least one
+        let mut last = first;
+        let mut tokens = units[first].2;
+        while last + 1 < units.len() && tokens + units[last + 1].2 <= target {
+            last += 1;
+            tokens += units[last].2;
+        }
+        chunks.push(Chunk {
+            start: units[first].0,
+            end: units[last].1,
+            tokens,
+        });
+        if last + 1 == units.len() {
+            break;
+        }
+
+        // Step back over trailing units that fit in the overlap, as long as the
+        // next chunk still has room for new text
+        let mut next = last + 1;
+        let mut carried = 0;
+        let fresh = units[last + 1].2;
+        while next - 1 > first {
+            let with_unit = carried + units[next - 1].2;
+            if with_unit > overlap || with_unit + fresh > target {
+                break;
+            }
+            next -= 1;
+            carried += units[next].2;
+        }
+        first = next;
+    }
+    chunks
+}
+
+/// Trimmed sentence spans. A sentence ends at `.`, `!` or `?` followed by
+/// whitespace, or at a line break.
+fn sentence_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let boundary = match c {
+            '\n' => true,
+            '.' | '!' | '?' => chars.peek().is_none_or(|&(_, next)| next.is_whitespace()),
+            _ => false,
+        };
+        if boundary {
+            push_trimmed(text, start, i + c.len_utf8(), &mut spans);
+            start = i + c.len_utf8();
+        }
+    }
+    push_trimmed(text, start, text.len(), &mut spans);
+    spans
+}
+
+fn push_trimmed(text: &str, start: usize, end: usize, spans: &mut Vec<(usize, usize)>) {
+    let slice = &text[start..end];
+    let trimmed = slice.trim();
+    if !trimmed.is_empty() {
+        let offset = start + (slice.len() - slice.trim_start().len());
+        spans.push((offset, offset + trimmed.len()));
+    }
+}
+
+/// Spans of whitespace-separated words within `text[start..end]`
+fn word_spans(text: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut word_start = None;
+    for (i, c) in text[start..end].char_indices() {
+        match (c.is_whitespace(), word_start) {
+            (true, Some(s)) => {
+                spans.push((start + s, start + i));
+                word_start = None;
+            }
+            (false, None) => word_start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = word_start {
+        spans.push((start + s, end));
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(text: &str, target_tokens: usize, overlap_tokens: usize, respect_sentences: bool) -> Vec<&str> {
+        let params = ChunkParams { target_tokens, overlap_tokens, respect_sentences };
+        split_document(text, params).iter().map(|chunk| &text[chunk.start..chunk.end]).collect()
+    }
+
+    const TEXT: &str = "One two three. Four five six!  Seven eight nine? Ten eleven twelve.";
+
+    #[test]
+    fn test_packs_whole_sentences() {
+        assert_eq!(
+            texts(TEXT, 6, 0, true),
+            vec!["One two three. Four five six!", "Seven eight nine? Ten eleven twelve."]
+        );
+        // A sentence is never split to fill a chunk
+        assert_eq!(
+            texts(TEXT, 7, 0, true),
+            vec!["One two three. Four five six!", "Seven eight nine? Ten eleven twelve."]
+        );
+        let chunks = split_document(TEXT, ChunkParams { target_tokens: 6, overlap_tokens: 0, respect_sentences: true });
+        assert!(chunks.iter().all(|chunk| chunk.tokens == 6));
+    }
+
+    #[test]
+    fn test_overlap_repeats_trailing_sentences() {
+        assert_eq!(
+            texts(TEXT, 6, 3, true),
+            vec![
+                "One two three. Four five six!",
+                "Four five six!  Seven eight nine?",
+                "Seven eight nine? Ten eleven twelve.",
+            ]
+        );
+        // Overlap too small for a whole sentence carries nothing
+        assert_eq!(texts(TEXT, 6, 2, true).len(), 2);
+        assert_eq!(texts("a b c d e", 3, 1, false), vec!["a b c", "c d e"]);
+        assert_eq!(texts("a b c d e f g", 3, 0, false), vec!["a b c", "d e f", "g"]);
+        // Overlap is capped below the target, so chunks always advance
+        assert_eq!(texts("a b c d", 2, 5, false), vec!["a b", "b c", "c d"]);
+    }
+
+    #[test]
+    fn test_sentence_boundaries() {
+        let text = "Pi is 3.14 roughly! Yes?\nA line without a stop\nLast";
+        assert_eq!(
+            sentence_spans(text).iter().map(|&(s, e)| &text[s..e]).collect::<Vec<_>>(),
+            vec!["Pi is 3.14 roughly!", "Yes?", "A line without a stop", "Last"]
+        );
+        // Long sentences are split on word boundaries
+        assert_eq!(texts("a b c d e f g h i j.", 4, 0, true), vec!["a b c d", "e f g h", "i j."]);
+        assert_eq!(texts("Ünïcode wörds here. Ok.", 3, 0, true), vec!["Ünïcode wörds here.", "Ok."]);
+        assert!(texts("", 10, 0, true).is_empty());
+        assert!(texts(" \n\t ", 10, 0, false).is_empty());
+    }
+}
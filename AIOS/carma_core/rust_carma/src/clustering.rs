@@ -0,0 +1,724 @@
+//! Clustering algorithms over fragment embeddings
+
+use aios_cancel::CancellationToken;
+use rand::Rng;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// Output of a k-means run
+#[derive(Debug, Clone)]
+pub struct KMeansOutput {
+    pub assignments: Vec<i32>,
+    pub centroids: Vec<Vec<f32>>,
+    pub inertia: f64,
+    pub iterations: usize,
+    pub converged: bool,
+}
+
+/// k-means run parameters
+#[derive(Debug, Clone, Copy)]
+pub struct KMeansParams {
+    pub k: usize,
+    pub max_iterations: usize,
+    pub tolerance: f64,
+    /// Mini-batch size; `None` runs full-batch Lloyd iterations
+    pub batch_size: Option<usize>,
+}
+
+/// k-means with k-means++ seeding, full-batch or mini-batch.
+/// Iterations stop early (unconverged) once `cancel` is cancelled.
+pub fn kmeans(features: &[&[f32]], params: KMeansParams, cancel: &CancellationToken) -> KMeansOutput {
+    if features.is_empty() || params.k == 0 {
+        return KMeansOutput {
+            assignments: Vec::new(),
+            centroids: Vec::new(),
+            inertia: 0.0,
+            iterations: 0,
+            converged: true,
+        };
+    }
+
+    let k = params.k.min(features.len());
+    let mut centroids = kmeans_plus_plus(features, k);
+
+    let (iterations, converged) = match params.batch_size {
+        Some(batch_size) => mini_batch_iterations(features, &mut centroids, batch_size.max(1), params, cancel),
+        None => lloyd_iterations(features, &mut centroids, params, cancel),
+    };
+
+    let assignments: Vec<i32> = features
+        .par_iter()
+        .map(|f| nearest_centroid(f, &centroids).0 as i32)
+        .collect();
+    let inertia = compute_inertia(features, &centroids, &assignments);
+
+    KMeansOutput {
+        assignments,
+        centroids,
+        inertia,
+        iterations,
+        converged,
+    }
+}
+
+/// k-means++ seeding: each new centroid is drawn with probability proportional
+/// to its squared distance from the closest centroid chosen so far
+pub fn kmeans_plus_plus(features: &[&[f32]], k: usize) -> Vec<Vec<f32>> {
+    let mut rng = aios_rng::rng("carma.kmeans");
+    let mut centroids: Vec<Vec<f32>> = Vec::with_capacity(k);
+    centroids.push(features[rng.gen_range(0..features.len())].to_vec());
+
+    let mut min_dist: Vec<f64> = features
+        .iter()
+        .map(|f| squared_distance(f, &centroids[0]) as f64)
+        .collect();
+
+    while centroids.len() < k {
+        let total: f64 = min_dist.iter().sum();
+        let next = if total <= 0.0 {
+            // All remaining points coincide with a centroid
+            rng.gen_range(0..features.len())
+        } else {
+            let mut target = rng.gen::<f64>() * total;
+            let mut chosen = features.len() - 1;
+            for (i, d) in min_dist.iter().enumerate() {
+                target -= d;
+                if target <= 0.0 {
+                    chosen = i;
+                    break;
+                }
+            }
+            chosen
+        };
+
+        let centroid = features[next].to_vec();
+        for (i, f) in features.iter().enumerate() {
+            let d = squared_distance(f, &centroid) as f64;
+            if d < min_dist[i] {
+                min_dist[i] = d;
+            }
+        }
+        centroids.push(centroid);
+    }
+
+    centroids
+}
+
+/// Full-batch Lloyd iterations; returns (iterations, converged)
+fn lloyd_iterations(
+    features: &[&[f32]],
+    centroids: &mut [Vec<f32>],
+    params: KMeansParams,
+    cancel: &CancellationToken,
+) -> (usize, bool) {
+    let dim = centroids[0].len();
+    let mut assignments = vec![usize::MAX; features.len()];
+
+    for iteration in 1..=params.max_iterations {
+        if cancel.is_cancelled() {
+            return (iteration - 1, false);
+        }
+        // The assignment step dominates, so run it in parallel
+        let nearest: Vec<usize> = features.par_iter().map(|f| nearest_centroid(f, centroids).0).collect();
+        let changed = nearest != assignments;
+        assignments = nearest;
+
+        let mut sums = vec![vec![0.0f64; dim]; centroids.len()];
+        let mut counts = vec![0usize; centroids.len()];
+        for (feature, &cluster) in features.iter().zip(assignments.iter()) {
+            counts[cluster] += 1;
+            for (s, &x) in sums[cluster].iter_mut().zip(feature.iter()) {
+                *s += x as f64;
+            }
+        }
+
+        let mut max_shift = 0.0f64;
+        for (j, centroid) in centroids.iter_mut().enumerate() {
+            if counts[j] == 0 {
+                continue;
+            }
+            let updated: Vec<f32> = sums[j].iter().map(|s| (s / counts[j] as f64) as f32).collect();
+            max_shift = max_shift.max(squared_distance(centroid, &updated).sqrt() as f64);
+            *centroid = updated;
+        }
+
+        if !changed || max_shift <= params.tolerance {
+            return (iteration, true);
+        }
+    }
+
+    (params.max_iterations, false)
+}
+
+/// Mini-batch k-means (Sculley 2010) with per-centroid learning rates
+fn mini_batch_iterations(
+    features: &[&[f32]],
+    centroids: &mut [Vec<f32>],
+    batch_size: usize,
+    params: KMeansParams,
+    cancel: &CancellationToken,
+) -> (usize, bool) {
+    let mut rng = aios_rng::rng("carma.kmeans_batches");
+    let mut counts = vec![0usize; centroids.len()];
+
+    for iteration in 1..=params.max_iterations {
+        if cancel.is_cancelled() {
+            return (iteration - 1, false);
+        }
+        let batch: Vec<usize> = (0..batch_size.min(features.len()))
+            .map(|_| rng.gen_range(0..features.len()))
+            .collect();
+        let nearest: Vec<usize> = batch
+            .iter()
+            .map(|&i| nearest_centroid(features[i], centroids).0)
+            .collect();
+
+        let previous: Vec<Vec<f32>> = centroids.to_vec();
+        for (&i, &cluster) in batch.iter().zip(nearest.iter()) {
+            counts[cluster] += 1;
+            let eta = 1.0 / counts[cluster] as f32;
+            for (c, &x) in centroids[cluster].iter_mut().zip(features[i].iter()) {
+                *c = (1.0 - eta) * *c + eta * x;
+            }
+        }
+
+        let max_shift = previous
+            .iter()
+            .zip(centroids.iter())
+            .map(|(old, new)| squared_distance(old, new).sqrt() as f64)
+            .fold(0.0, f64::max);
+        if max_shift <= params.tolerance {
+            return (iteration, true);
+        }
+    }
+
+    (params.max_iterations, false)
+}
+
+/// Index and squared distance of the closest centroid
+pub fn nearest_centroid(feature: &[f32], centroids: &[Vec<f32>]) -> (usize, f32) {
+    let mut best = (0, f32::INFINITY);
+    for (j, centroid) in centroids.iter().enumerate() {
+        let d = squared_distance(feature, centroid);
+        if d < best.1 {
+            best = (j, d);
+        }
+    }
+    best
+}
+
+/// Sum of squared distances from each point to its assigned centroid
+pub fn compute_inertia(features: &[&[f32]], centroids: &[Vec<f32>], assignments: &[i32]) -> f64 {
+    features
+        .par_iter()
+        .zip(assignments.par_iter())
+        .filter(|(_, &c)| c >= 0)
+        .map(|(f, &c)| squared_distance(f, &centroids[c as usize]) as f64)
+        .sum()
+}
+
+/// Squared Euclidean distance (infinite for mismatched dimensions)
+pub fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return f32::INFINITY;
+    }
+
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y).powi(2)).sum()
+}
+
+/// Criterion for choosing k automatically
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KSelection {
+    /// Point of maximum curvature on the inertia curve
+    Elbow,
+    /// Highest mean silhouette coefficient
+    Silhouette,
+}
+
+impl KSelection {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "elbow" => Some(Self::Elbow),
+            "silhouette" => Some(Self::Silhouette),
+            _ => None,
+        }
+    }
+}
+
+/// Mean silhouette coefficient with Euclidean distance.
+///
+/// Scored over at most `max_samples` evenly strided points (each against the
+/// full set), so the cost is O(max_samples * n). Singleton clusters score 0.
+pub fn silhouette_score(features: &[&[f32]], labels: &[i32], max_samples: usize) -> f64 {
+    let num_clusters = labels.iter().copied().max().map_or(0, |m| (m + 1).max(0) as usize);
+    if num_clusters < 2 || features.is_empty() {
+        return 0.0;
+    }
+
+    let stride = features.len().div_ceil(max_samples.max(1));
+    let samples: Vec<usize> = (0..features.len()).step_by(stride).filter(|&i| labels[i] >= 0).collect();
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    let total: f64 = samples
+        .par_iter()
+        .map(|&i| {
+            let mut sums = vec![0.0f64; num_clusters];
+            let mut counts = vec![0usize; num_clusters];
+            for (j, &label) in labels.iter().enumerate() {
+                if j == i || label < 0 {
+                    continue;
+                }
+                sums[label as usize] += (squared_distance(features[i], features[j]) as f64).sqrt();
+                counts[label as usize] += 1;
+            }
+
+            let own = labels[i] as usize;
+            if counts[own] == 0 {
+                return 0.0;
+            }
+            let a = sums[own] / counts[own] as f64;
+            let b = (0..num_clusters)
+                .filter(|&c| c != own && counts[c] > 0)
+                .map(|c| sums[c] / counts[c] as f64)
+                .fold(f64::INFINITY, f64::min);
+            if !b.is_finite() || a.max(b) == 0.0 {
+                0.0
+            } else {
+                (b - a) / a.max(b)
+            }
+        })
+        .sum();
+
+    total / samples.len() as f64
+}
+
+/// Index of the elbow on a decreasing curve: the point farthest below the
+/// chord joining the first and last points, after scaling both axes to [0, 1]
+pub fn elbow_index(xs: &[f64], ys: &[f64]) -> usize {
+    if xs.len() < 3 {
+        return 0;
+    }
+
+    let (x0, x1) = (xs[0], xs[xs.len() - 1]);
+    let y_max = ys.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let y_min = ys.iter().copied().fold(f64::INFINITY, f64::min);
+    if x1 == x0 || y_max == y_min {
+        return 0;
+    }
+
+    let scale = |x: f64, y: f64| ((x - x0) / (x1 - x0), (y - y_min) / (y_max - y_min));
+    let (_, first) = scale(x0, ys[0]);
+    let (_, last) = scale(x1, ys[ys.len() - 1]);
+
+    let mut best = (0, 0.0);
+    for (i, (&x, &y)) in xs.iter().zip(ys.iter()).enumerate() {
+        let (x, y) = scale(x, y);
+        let gap = first + (last - first) * x - y;
+        if gap > best.1 {
+            best = (i, gap);
+        }
+    }
+    best.0
+}
+
+/// Label assigned to DBSCAN noise points
+pub const NOISE: i32 = -1;
+
+/// DBSCAN over a precomputed distance function.
+///
+/// Returns a cluster label per point (0-based), or `NOISE` for points that
+/// are neither core points nor within `eps` of one. Once `cancel` is
+/// cancelled the labels are incomplete; callers must check it.
+pub fn dbscan<D>(n: usize, eps: f32, min_samples: usize, cancel: &CancellationToken, distance: D) -> Vec<i32>
+where
+    D: Fn(usize, usize) -> f32 + Sync,
+{
+    // Neighbourhoods are the expensive part, so compute them in parallel
+    let neighborhoods: Vec<Vec<usize>> = (0..n)
+        .into_par_iter()
+        .map(|i| {
+            if cancel.is_cancelled() {
+                return Vec::new();
+            }
+            (0..n).filter(|&j| distance(i, j) <= eps).collect()
+        })
+        .collect();
+    if cancel.is_cancelled() {
+        return vec![NOISE; n];
+    }
+
+    let mut labels = vec![None::<i32>; n];
+    let mut next_cluster = 0;
+
+    for i in 0..n {
+        if labels[i].is_some() {
+            continue;
+        }
+        if neighborhoods[i].len() < min_samples {
+            labels[i] = Some(NOISE);
+            continue;
+        }
+
+        let cluster = next_cluster;
+        next_cluster += 1;
+        labels[i] = Some(cluster);
+
+        let mut queue: Vec<usize> = neighborhoods[i].clone();
+        while let Some(j) = queue.pop() {
+            match labels[j] {
+                // Border point previously marked as noise joins the cluster
+                Some(NOISE) => labels[j] = Some(cluster),
+                Some(_) => continue,
+                None => {
+                    labels[j] = Some(cluster);
+                    if neighborhoods[j].len() >= min_samples {
+                        queue.extend(neighborhoods[j].iter().copied());
+                    }
+                }
+            }
+        }
+    }
+
+    labels.into_iter().map(|l| l.unwrap_or(NOISE)).collect()
+}
+
+/// Mean vector of every labelled cluster, indexed by label
+pub fn cluster_means(features: &[&[f32]], labels: &[i32]) -> Vec<Vec<f32>> {
+    let num_clusters = labels.iter().copied().max().map_or(0, |m| (m + 1).max(0) as usize);
+    let dim = features.first().map_or(0, |f| f.len());
+    let mut sums = vec![vec![0.0f64; dim]; num_clusters];
+    let mut counts = vec![0usize; num_clusters];
+
+    for (feature, &label) in features.iter().zip(labels.iter()) {
+        if label < 0 || feature.len() != dim {
+            continue;
+        }
+        counts[label as usize] += 1;
+        for (s, &x) in sums[label as usize].iter_mut().zip(feature.iter()) {
+            *s += x as f64;
+        }
+    }
+
+    sums.into_iter()
+        .zip(counts)
+        .map(|(sum, count)| sum.into_iter().map(|s| (s / count.max(1) as f64) as f32).collect())
+        .collect()
+}
+
+/// Linkage criterion for agglomerative clustering
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Linkage {
+    Single,
+    Complete,
+    Average,
+    Ward,
+}
+
+impl Linkage {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "single" => Some(Linkage::Single),
+            "complete" => Some(Linkage::Complete),
+            "average" => Some(Linkage::Average),
+            "ward" => Some(Linkage::Ward),
+            _ => None,
+        }
+    }
+
+    /// Lance-Williams update for the distance from merged cluster (a ∪ b) to k
+    fn update(self, d_ak: f32, d_bk: f32, d_ab: f32, na: usize, nb: usize, nk: usize) -> f32 {
+        match self {
+            Linkage::Single => d_ak.min(d_bk),
+            Linkage::Complete => d_ak.max(d_bk),
+            Linkage::Average => (na as f32 * d_ak + nb as f32 * d_bk) / (na + nb) as f32,
+            Linkage::Ward => {
+                let total = (na + nb + nk) as f32;
+                ((na + nk) as f32 * d_ak + (nb + nk) as f32 * d_bk - nk as f32 * d_ab) / total
+            }
+        }
+    }
+}
+
+/// One dendrogram merge in SciPy linkage-matrix convention: leaves are
+/// `0..n`, the cluster created by merge `i` is `n + i`
+#[derive(Debug, Clone, Copy)]
+pub struct Merge {
+    pub left: usize,
+    pub right: usize,
+    pub distance: f32,
+    pub size: usize,
+}
+
+/// Agglomerative clustering with the nearest-neighbour-chain algorithm
+/// (O(n²) time and memory for the supported reducible linkages).
+///
+/// `distance(i, j)` is the base dissimilarity between points; for Ward it
+/// should be the squared Euclidean distance. Once `cancel` is cancelled the
+/// merges are incomplete; callers must check it.
+pub fn agglomerative<D>(n: usize, linkage: Linkage, cancel: &CancellationToken, distance: D) -> Vec<Merge>
+where
+    D: Fn(usize, usize) -> f32 + Sync,
+{
+    if n < 2 {
+        return Vec::new();
+    }
+
+    let mut dist: Vec<f32> = (0..n)
+        .into_par_iter()
+        .flat_map_iter(|i| (0..n).map(move |j| (i, j)))
+        .map(|(i, j)| if i == j { 0.0 } else { distance(i, j) })
+        .collect();
+    let mut size = vec![1usize; n];
+    let mut active = vec![true; n];
+    let mut remaining = n;
+    let mut chain: Vec<usize> = Vec::new();
+    // (leaf representative a, leaf representative b, distance)
+    let mut raw_merges: Vec<(usize, usize, f32)> = Vec::with_capacity(n - 1);
+
+    while remaining > 1 {
+        if cancel.is_cancelled() {
+            break;
+        }
+        if chain.is_empty() {
+            chain.push(active.iter().position(|&a| a).unwrap_or(0));
+        }
+
+        let a = chain[chain.len() - 1];
+        let previous = if chain.len() >= 2 { Some(chain[chain.len() - 2]) } else { None };
+
+        // Nearest active neighbour of a, preferring the previous chain element on ties
+        let mut best = previous.unwrap_or(usize::MAX);
+        let mut best_dist = previous.map_or(f32::INFINITY, |p| dist[a * n + p]);
+        for k in 0..n {
+            if k == a || !active[k] {
+                continue;
+            }
+            let d = dist[a * n + k];
+            if d < best_dist {
+                best = k;
+                best_dist = d;
+            }
+        }
+
+        if Some(best) != previous {
+            chain.push(best);
+            continue;
+        }
+
+        // Reciprocal nearest neighbours: merge b into a's slot
+        chain.pop();
+        chain.pop();
+        let b = best;
+        let d_ab = best_dist;
+        for k in 0..n {
+            if !active[k] || k == a || k == b {
+                continue;
+            }
+            let updated = linkage.update(dist[a * n + k], dist[b * n + k], d_ab, size[a], size[b], size[k]);
+            dist[a * n + k] = updated;
+            dist[k * n + a] = updated;
+        }
+        size[a] += size[b];
+        active[b] = false;
+        remaining -= 1;
+        raw_merges.push((a, b, d_ab));
+    }
+
+    // NN-chain finds merges out of order; sort and relabel via union-find
+    raw_merges.sort_by(|x, y| x.2.total_cmp(&y.2));
+    let mut parent: Vec<usize> = (0..n).collect();
+    let mut label: Vec<usize> = (0..n).collect();
+    let mut cluster_size = vec![1usize; n];
+    let mut merges = Vec::with_capacity(raw_merges.len());
+
+    for (i, (a, b, d)) in raw_merges.into_iter().enumerate() {
+        let root_a = find_root(&mut parent, a);
+        let root_b = find_root(&mut parent, b);
+        let (left, right) = (label[root_a].min(label[root_b]), label[root_a].max(label[root_b]));
+        let merged_size = cluster_size[root_a] + cluster_size[root_b];
+
+        parent[root_b] = root_a;
+        label[root_a] = n + i;
+        cluster_size[root_a] = merged_size;
+
+        merges.push(Merge {
+            left,
+            right,
+            distance: if linkage == Linkage::Ward { d.max(0.0).sqrt() } else { d },
+            size: merged_size,
+        });
+    }
+
+    merges
+}
+
+/// Flat cluster labels after applying the first `num_merges` merges
+pub fn cut_tree(n: usize, merges: &[Merge], num_merges: usize) -> Vec<i32> {
+    let mut parent: Vec<usize> = (0..n + merges.len()).collect();
+    for (i, merge) in merges.iter().take(num_merges).enumerate() {
+        parent[merge.left] = n + i;
+        parent[merge.right] = n + i;
+    }
+
+    let mut roots: HashMap<usize, i32> = HashMap::new();
+    (0..n)
+        .map(|leaf| {
+            let root = find_root(&mut parent, leaf);
+            let next = roots.len() as i32;
+            *roots.entry(root).or_insert(next)
+        })
+        .collect()
+}
+
+fn find_root(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CENTERS: [[f32; 2]; 3] = [[0.0, 0.0], [10.0, 0.0], [0.0, 10.0]];
+
+    /// 30 points within 1 of each center, in center order
+    fn blobs() -> Vec<Vec<f32>> {
+        CENTERS
+            .iter()
+            .flat_map(|center| {
+                (0..30).map(move |i| {
+                    let angle = i as f32 * 2.4;
+                    let radius = (i % 7) as f32 / 7.0;
+                    vec![center[0] + radius * angle.cos(), center[1] + radius * angle.sin()]
+                })
+            })
+            .collect()
+    }
+
+    /// Every blob has one label of its own
+    fn assert_recovers_blobs(labels: &[i32]) {
+        let blob_labels: Vec<i32> = labels.chunks(30).map(|blob| blob[0]).collect();
+        for (blob, &label) in labels.chunks(30).zip(&blob_labels) {
+            assert!(blob.iter().all(|&l| l == label), "split blob: {:?}", labels);
+        }
+        let mut distinct = blob_labels.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 3, "merged blobs: {:?}", labels);
+    }
+
+    fn params(k: usize, batch_size: Option<usize>) -> KMeansParams {
+        KMeansParams { k, max_iterations: 100, tolerance: 1e-6, batch_size }
+    }
+
+    #[test]
+    fn test_kmeans_recovers_blobs() {
+        aios_rng::set_seed(Some(11)).unwrap();
+        let points = blobs();
+        let features: Vec<&[f32]> = points.iter().map(Vec::as_slice).collect();
+        let output = kmeans(&features, params(3, None), &CancellationToken::new());
+        assert!(output.converged);
+        assert_recovers_blobs(&output.assignments);
+        for center in CENTERS {
+            let (_, d) = nearest_centroid(&center, &output.centroids);
+            assert!(d < 0.1, "no centroid near {:?}", center);
+        }
+        assert!((output.inertia - compute_inertia(&features, &output.centroids, &output.assignments)).abs() < 1e-6);
+
+        let output = kmeans(&features, params(3, Some(16)), &CancellationToken::new());
+        assert_recovers_blobs(&output.assignments);
+
+        // k-means++ seeds land in distinct blobs
+        let seeds = kmeans_plus_plus(&features, 3);
+        let mut seeded: Vec<usize> = seeds.iter().map(|s| nearest_centroid(s, &CENTERS.map(|c| c.to_vec())).0).collect();
+        seeded.sort();
+        assert_eq!(seeded, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_kmeans_edge_cases() {
+        let points = blobs();
+        let features: Vec<&[f32]> = points.iter().map(Vec::as_slice).collect();
+        assert!(kmeans(&[], params(3, None), &CancellationToken::new()).assignments.is_empty());
+        assert!(kmeans(&features, params(0, None), &CancellationToken::new()).centroids.is_empty());
+        // k is capped at the number of points
+        assert_eq!(kmeans(&features[..2], params(5, None), &CancellationToken::new()).centroids.len(), 2);
+
+        let cancelled = CancellationToken::new();
+        cancelled.cancel();
+        let output = kmeans(&features, params(3, None), &cancelled);
+        assert_eq!((output.iterations, output.converged, output.assignments.len()), (0, false, 90));
+    }
+
+    #[test]
+    fn test_dbscan_labels_outliers_as_noise() {
+        let mut points = blobs();
+        points.truncate(60);
+        points.push(vec![50.0, 50.0]);
+        points.push(vec![-40.0, 5.0]);
+        let distance = |i: usize, j: usize| squared_distance(&points[i], &points[j]).sqrt();
+        let labels = dbscan(points.len(), 1.5, 4, &CancellationToken::new(), distance);
+        assert_eq!(&labels[60..], &[NOISE, NOISE]);
+        assert!(labels[..30].iter().all(|&l| l == 0));
+        assert!(labels[30..60].iter().all(|&l| l == 1));
+
+        // Too few neighbours anywhere: all noise
+        assert!(dbscan(points.len(), 1.5, 100, &CancellationToken::new(), distance).iter().all(|&l| l == NOISE));
+        let means = cluster_means(&points.iter().map(Vec::as_slice).collect::<Vec<_>>(), &labels);
+        assert_eq!(means.len(), 2);
+        assert!(squared_distance(&means[1], &CENTERS[1]) < 0.1);
+    }
+
+    #[test]
+    fn test_cut_tree_gives_k_clusters() {
+        let points = blobs();
+        let n = points.len();
+        for linkage in [Linkage::Single, Linkage::Complete, Linkage::Average, Linkage::Ward] {
+            let merges = agglomerative(n, linkage, &CancellationToken::new(), |i, j| match linkage {
+                Linkage::Ward => squared_distance(&points[i], &points[j]),
+                _ => squared_distance(&points[i], &points[j]).sqrt(),
+            });
+            assert_eq!(merges.len(), n - 1);
+            assert!(merges.windows(2).all(|w| w[0].distance <= w[1].distance), "{:?}", linkage);
+            assert_eq!(merges[n - 2].size, n);
+            for merge in &merges {
+                assert!(merge.left < merge.right && merge.right < n + n - 1);
+            }
+
+            assert_recovers_blobs(&cut_tree(n, &merges, n - 3));
+            for k in [1, 2, 5, n] {
+                let mut labels = cut_tree(n, &merges, n - k);
+                labels.sort();
+                labels.dedup();
+                assert_eq!(labels.len(), k, "{:?} cut to {}", linkage, k);
+            }
+        }
+        assert!(agglomerative(1, Linkage::Average, &CancellationToken::new(), |_, _| 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_silhouette_and_elbow_pick_true_k() {
+        aios_rng::set_seed(Some(5)).unwrap();
+        let points = blobs();
+        let features: Vec<&[f32]> = points.iter().map(Vec::as_slice).collect();
+        let runs: Vec<KMeansOutput> =
+            (1..=8).map(|k| kmeans(&features, params(k, None), &CancellationToken::new())).collect();
+
+        let silhouettes: Vec<f64> = runs.iter().map(|run| silhouette_score(&features, &run.assignments, 1000)).collect();
+        let best = (0..silhouettes.len()).max_by(|&a, &b| silhouettes[a].total_cmp(&silhouettes[b])).unwrap();
+        assert_eq!(best + 1, 3, "silhouettes {:?}", silhouettes);
+        assert!(silhouettes[2] > 0.8);
+        assert_eq!(silhouettes[0], 0.0);
+
+        let ks: Vec<f64> = (1..=8).map(|k| k as f64).collect();
+        let inertias: Vec<f64> = runs.iter().map(|run| run.inertia).collect();
+        assert_eq!(elbow_index(&ks, &inertias) + 1, 3, "inertias {:?}", inertias);
+        assert_eq!(elbow_index(&ks[..2], &inertias[..2]), 0);
+        assert_eq!(elbow_index(&ks, &[1.0; 8]), 0);
+    }
+}
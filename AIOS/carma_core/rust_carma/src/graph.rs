@@ -0,0 +1,220 @@
+//! HNSW graph over the fragment store, surviving evictions
+//!
+//! Graph nodes are numbered in insertion order and mapped to fragment
+//! positions, which shift whenever fragments are removed. A removed fragment
+//! stays in the graph as a tombstone: its embedding is kept for routing but
+//! it is left out of results. Once tombstones would pass
+//! `TOMBSTONE_REBUILD_SHARE` of the nodes, `retire` declines and the caller
+//! rebuilds the graph from the live fragments.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use aios_hnsw::HnswIndex;
+
+use crate::{cosine_similarity, retain_positions, stored_embedding, stored_similarity, MemoryFragment, QuantizedStore};
+
+/// Share of graph nodes that may be tombstones before a rebuild
+const TOMBSTONE_REBUILD_SHARE: f64 = 0.25;
+
+/// Extra search width at most for skipping tombstones
+const MAX_TOMBSTONE_BEAM: usize = 256;
+
+pub struct FragmentGraph {
+    index: HnswIndex,
+    /// Fragment position of each node; None for tombstones
+    node_positions: Vec<Option<usize>>,
+    /// Node of each fragment position
+    position_nodes: Vec<usize>,
+    /// Embeddings of tombstones, by node
+    tombstones: HashMap<usize, Vec<f32>>,
+}
+
+impl FragmentGraph {
+    pub fn new(m: usize, ef_construction: usize, ef_search: usize) -> Self {
+        Self {
+            index: HnswIndex::new("carma.hnsw", m, ef_construction, ef_search),
+            node_positions: Vec::new(),
+            position_nodes: Vec::new(),
+            tombstones: HashMap::new(),
+        }
+    }
+
+    /// Nodes, tombstones included
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn tombstones(&self) -> usize {
+        self.tombstones.len()
+    }
+
+    /// Every one of `fragments` fragments has a node
+    pub fn covers(&self, fragments: usize) -> bool {
+        self.position_nodes.len() == fragments
+    }
+
+    pub fn ef_search(&self) -> usize {
+        self.index.ef_search()
+    }
+
+    pub fn set_ef_search(&mut self, ef_search: usize) {
+        self.index.set_ef_search(ef_search);
+    }
+
+    pub fn clear(&mut self) {
+        self.index.clear();
+        self.node_positions.clear();
+        self.position_nodes.clear();
+        self.tombstones.clear();
+    }
+
+    /// Add the fragment at `position`, the next one without a node
+    pub fn insert(&mut self, fragments: &[MemoryFragment], quantized: Option<&QuantizedStore>, position: usize) {
+        // Mapped first, as linking measures distances to the new node too
+        self.node_positions.push(Some(position));
+        self.position_nodes.push(self.node_positions.len() - 1);
+        let nodes = Nodes { fragments, quantized, positions: &self.node_positions, tombstones: &self.tombstones };
+        let query = stored_embedding(fragments, quantized, position);
+        let query_norm = query.iter().map(|x| x * x).sum::<f32>().sqrt();
+        self.index.insert(
+            |node| 1.0 - nodes.similarity(&query, query_norm, node),
+            |a, b| {
+                let embedding = nodes.embedding(a);
+                let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+                1.0 - nodes.similarity(&embedding, norm, b)
+            },
+        );
+    }
+
+    /// Approximate top-k (position, cosine similarity) pairs, best first
+    pub fn search(
+        &self,
+        fragments: &[MemoryFragment],
+        quantized: Option<&QuantizedStore>,
+        query: &[f32],
+        query_norm: f32,
+        topk: usize,
+    ) -> Vec<(usize, f32)> {
+        let nodes = Nodes { fragments, quantized, positions: &self.node_positions, tombstones: &self.tombstones };
+        let extra = self.tombstones.len().min(MAX_TOMBSTONE_BEAM);
+        self.index
+            .search(topk.saturating_add(extra), |node| 1.0 - nodes.similarity(query, query_norm, node))
+            .into_iter()
+            .filter_map(|(node, distance)| Some((self.node_positions[node]?, 1.0 - distance)))
+            .take(topk)
+            .collect()
+    }
+
+    /// Turn the fragments at `positions` into tombstones, before they are
+    /// removed from `fragments`; false, changing nothing, when the graph
+    /// should be rebuilt instead
+    pub fn retire(&mut self, fragments: &[MemoryFragment], quantized: Option<&QuantizedStore>, positions: &HashSet<usize>) -> bool {
+        let tombstones = self.tombstones.len() + positions.len();
+        if !self.covers(fragments.len()) || tombstones as f64 > self.len() as f64 * TOMBSTONE_REBUILD_SHARE {
+            return false;
+        }
+        for &position in positions {
+            let node = self.position_nodes[position];
+            self.tombstones.insert(node, stored_embedding(fragments, quantized, position).into_owned());
+            self.node_positions[node] = None;
+        }
+        retain_positions(&mut self.position_nodes, positions);
+        for (position, &node) in self.position_nodes.iter().enumerate() {
+            self.node_positions[node] = Some(position);
+        }
+        true
+    }
+}
+
+/// Where the nodes' embeddings are
+struct Nodes<'a> {
+    fragments: &'a [MemoryFragment],
+    quantized: Option<&'a QuantizedStore>,
+    positions: &'a [Option<usize>],
+    tombstones: &'a HashMap<usize, Vec<f32>>,
+}
+
+impl Nodes<'_> {
+    fn similarity(&self, query: &[f32], query_norm: f32, node: usize) -> f32 {
+        match self.positions.get(node).copied().flatten() {
+            Some(position) => stored_similarity(self.fragments, self.quantized, query, query_norm, position),
+            None => cosine_similarity(query, &self.tombstones[&node]),
+        }
+    }
+
+    fn embedding(&self, node: usize) -> Cow<'_, [f32]> {
+        match self.positions.get(node).copied().flatten() {
+            Some(position) => stored_embedding(self.fragments, self.quantized, position),
+            None => Cow::Borrowed(&self.tombstones[&node]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragments(count: usize) -> Vec<MemoryFragment> {
+        (0..count)
+            .map(|i| {
+                let embedding = (0..8).map(|j| ((i * 8 + j) as f32 * 0.77).sin()).collect();
+                MemoryFragment::new(format!("f{}", i), String::new(), embedding)
+            })
+            .collect()
+    }
+
+    fn graph(fragments: &[MemoryFragment]) -> FragmentGraph {
+        let mut graph = FragmentGraph::new(8, 100, 50);
+        for position in 0..fragments.len() {
+            graph.insert(fragments, None, position);
+        }
+        graph
+    }
+
+    /// Id of the best hit for each fragment's own embedding
+    fn self_hits(graph: &FragmentGraph, fragments: &[MemoryFragment]) -> Vec<String> {
+        fragments
+            .iter()
+            .map(|fragment| {
+                let norm = fragment.embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+                let (position, _) = graph.search(fragments, None, &fragment.embedding, norm, 1)[0];
+                fragments[position].id.clone()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tombstones_keep_positions_mapped() {
+        let mut fragments = fragments(20);
+        let mut graph = graph(&fragments);
+        assert!(graph.covers(20));
+
+        let removed: HashSet<usize> = [2, 9, 15].into_iter().collect();
+        assert!(graph.retire(&fragments, None, &removed));
+        retain_positions(&mut fragments, &removed);
+        assert_eq!((graph.len(), graph.tombstones()), (20, 3));
+        assert!(graph.covers(17));
+
+        // Results map to the shifted positions and never include tombstones
+        let ids: Vec<String> = fragments.iter().map(|f| f.id.clone()).collect();
+        assert_eq!(self_hits(&graph, &fragments), ids);
+        let norm = fragments[0].embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let all = graph.search(&fragments, None, &fragments[0].embedding, norm, 100);
+        assert_eq!(all.len(), 17);
+    }
+
+    #[test]
+    fn test_retire_declines_past_rebuild_share() {
+        let fragments = fragments(20);
+        let mut graph = graph(&fragments);
+        // 6 of 20 nodes would pass the 25% share
+        let too_many: HashSet<usize> = (0..6).collect();
+        assert!(!graph.retire(&fragments, None, &too_many));
+        assert_eq!(graph.tombstones(), 0);
+        assert!(graph.covers(20));
+        let allowed: HashSet<usize> = (0..5).collect();
+        assert!(graph.retire(&fragments, None, &allowed));
+        assert_eq!(graph.tombstones(), 5);
+    }
+}
@@ -1,10 +1,94 @@
+use pyo3::create_exception;
+use parking_lot::{Mutex, RwLock, RwLockUpgradableReadGuard};
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList};
+use numpy::PyArray2;
+use pyo3::types::{PyBytes, PyDict, PyList};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::time::SystemTime;
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime};
 use uuid::Uuid;
 
+mod analytics;
+mod arrays;
+mod chunking;
+mod clustering;
+mod graph;
+mod parquet;
+mod persistence;
+mod quantization;
+mod topk;
+
+use aios_cancel::CancellationToken;
+use aios_plugins::Hook;
+use analytics::{QueryClass, RetrievalAnalytics};
+use arrays::{to_ndarray, Matrix, Vector};
+use chunking::ChunkParams;
+use graph::FragmentGraph;
+use clustering::{
+    agglomerative, cluster_means, cut_tree, dbscan, elbow_index, kmeans, nearest_centroid, silhouette_score,
+    squared_distance, KMeansOutput, KMeansParams, KSelection, Linkage, Merge, NOISE,
+};
+use aios_carma::keyword::Bm25Index;
+use persistence::{
+    decode_snapshot, encode_snapshot, read_snapshot, write_snapshot, FragmentRecord, SnapshotHeader, FORMAT_VERSION,
+};
+use quantization::{Int8Code, PqCode, ProductQuantizer};
+use topk::top_k;
+
+mod errors {
+    aios_errors::python_exceptions!(aios_carma_rust);
+}
+
+mod tracing {
+    aios_trace::python_tracing!();
+}
+
+mod awaitable {
+    aios_async::python_awaitables!();
+}
+
+mod cancel {
+    aios_cancel::python_cancellation!();
+}
+
+mod threads {
+    aios_threads::python_thread_pool!();
+}
+
+mod bus {
+    aios_bus::python_bus!();
+}
+
+mod state {
+    aios_state::python_state!();
+}
+
+mod events {
+    aios_events::python_events!();
+}
+
+mod plugins {
+    aios_plugins::python_plugins!(PreRetrieval);
+}
+
+mod rng {
+    aios_rng::python_rng!();
+}
+
+create_exception!(
+    aios_carma_rust,
+    EmbeddingError,
+    errors::ValidationError,
+    "Embedding has the wrong dimension or contains NaN/inf values."
+);
+
 /// Represents a memory fragment for CARMA processing
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[pyclass]
@@ -48,6 +132,9 @@ pub struct ClusterResult {
     pub metadata: HashMap<String, f64>,
     #[pyo3(get)]
     pub num_clusters: usize,
+    /// Fragments left unclustered by density-based modes
+    #[pyo3(get)]
+    pub noise: Vec<MemoryFragment>,
 }
 
 #[pymethods]
@@ -59,242 +146,2634 @@ impl ClusterResult {
             clusters,
             metadata,
             num_clusters,
+            noise: Vec::new(),
         }
     }
 }
 
-/// Main CARMA Rust implementation
+/// Agglomerative clustering merge tree.
+///
+/// `merges` follows the SciPy linkage-matrix convention: each entry is
+/// `(left, right, distance, size)`, leaves are `0..n` (indices into
+/// `fragment_ids`) and the cluster created by merge `i` has id `n + i`.
+#[derive(Debug, Clone)]
 #[pyclass]
-pub struct RustCarmaCore {
+pub struct Dendrogram {
+    #[pyo3(get)]
+    pub fragment_ids: Vec<String>,
+    #[pyo3(get)]
+    pub linkage: String,
+    merges: Vec<Merge>,
+}
+
+#[pymethods]
+impl Dendrogram {
+    /// Merge list as (left, right, distance, size) tuples
+    #[getter]
+    fn merges(&self) -> Vec<(usize, usize, f32, usize)> {
+        self.merges.iter().map(|m| (m.left, m.right, m.distance, m.size)).collect()
+    }
+
+    /// Cut the tree into (at most) `num_clusters` flat clusters of fragment ids
+    fn cut(&self, num_clusters: usize) -> HashMap<i32, Vec<String>> {
+        let n = self.fragment_ids.len();
+        let num_merges = n.saturating_sub(num_clusters.max(1)).min(self.merges.len());
+        self.group(&cut_tree(n, &self.merges, num_merges))
+    }
+
+    /// Cut the tree at a merge distance, keeping merges with distance <= threshold
+    fn cut_at_distance(&self, threshold: f32) -> HashMap<i32, Vec<String>> {
+        let n = self.fragment_ids.len();
+        let num_merges = self.merges.iter().take_while(|m| m.distance <= threshold).count();
+        self.group(&cut_tree(n, &self.merges, num_merges))
+    }
+
+    fn __len__(&self) -> usize {
+        self.merges.len()
+    }
+}
+
+impl Dendrogram {
+    fn group(&self, labels: &[i32]) -> HashMap<i32, Vec<String>> {
+        let mut clusters: HashMap<i32, Vec<String>> = HashMap::new();
+        for (id, &label) in self.fragment_ids.iter().zip(labels.iter()) {
+            clusters.entry(label).or_default().push(id.clone());
+        }
+        clusters
+    }
+}
+
+/// Options for `split_document`. Token counts are whitespace-separated words.
+#[derive(Debug, Clone)]
+#[pyclass]
+pub struct ChunkOptions {
+    #[pyo3(get, set)]
+    pub target_tokens: usize,
+    #[pyo3(get, set)]
+    pub overlap_tokens: usize,
+    #[pyo3(get, set)]
+    pub respect_sentences: bool,
+}
+
+#[pymethods]
+impl ChunkOptions {
+    #[new]
+    #[pyo3(signature = (target_tokens=200, overlap_tokens=20, respect_sentences=true))]
+    fn new(target_tokens: usize, overlap_tokens: usize, respect_sentences: bool) -> Self {
+        Self {
+            target_tokens,
+            overlap_tokens,
+            respect_sentences,
+        }
+    }
+}
+
+/// Which fragments to drop when the store is at capacity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EvictionPolicy {
+    /// Least recently retrieved first
+    Lru,
+    /// Lowest cumulative retrieval similarity first
+    LowestRelevance,
+    /// Oldest creation timestamp first
+    Oldest,
+}
+
+impl EvictionPolicy {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "lru" => Some(EvictionPolicy::Lru),
+            "lowest_relevance" => Some(EvictionPolicy::LowestRelevance),
+            "oldest" => Some(EvictionPolicy::Oldest),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            EvictionPolicy::Lru => "lru",
+            EvictionPolicy::LowestRelevance => "lowest_relevance",
+            EvictionPolicy::Oldest => "oldest",
+        }
+    }
+}
+
+/// What add_fragment does with a near-duplicate of an existing fragment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DedupMode {
+    Off,
+    /// Drop the new fragment and return the existing id
+    Reject,
+    /// Fold the new fragment into the existing one and return its id
+    Merge,
+}
+
+impl DedupMode {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(DedupMode::Off),
+            "reject" => Some(DedupMode::Reject),
+            "merge" => Some(DedupMode::Merge),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DedupMode::Off => "off",
+            DedupMode::Reject => "reject",
+            DedupMode::Merge => "merge",
+        }
+    }
+}
+
+/// Near-duplicate detection settings and counters
+#[derive(Debug, Clone)]
+struct DedupConfig {
+    mode: DedupMode,
+    similarity_threshold: f32,
+    overlap_threshold: f64,
+    rejected: u64,
+    merged: u64,
+}
+
+/// Checks applied to embeddings on add and query
+#[derive(Debug, Clone, Default)]
+struct EmbeddingPolicy {
+    /// Required dimension; taken from the first fragment when unset
+    dimension: Option<usize>,
+    /// L2-normalize embeddings before storing them
+    normalize: bool,
+}
+
+/// Reference point for detecting centroid drift after incremental assignment
+#[derive(Debug, Clone, Default)]
+struct DriftBaseline {
+    /// Centroids as of the last full clustering run
+    centroids: Vec<Vec<f32>>,
+    /// Mean member-to-centroid distance at that time
+    scale: f32,
+    assigned_since_fit: usize,
+}
+
+/// Per-fragment retrieval bookkeeping, parallel to `fragments`
+#[derive(Debug, Clone, Default)]
+struct FragmentUsage {
+    last_access: f64,
+    relevance: f64,
+    hit_count: u64,
+}
+
+/// Ranking boost for frequently and recently retrieved fragments
+#[derive(Debug, Clone, Default)]
+struct Reinforcement {
+    /// Score bonus per unit of ln(1 + hit_count); 0 disables reinforcement
+    weight: f32,
+    /// Seconds for the bonus to halve since the last access (None = no decay)
+    half_life: Option<f64>,
+}
+
+/// Eviction counters reported in get_stats
+#[derive(Debug, Clone, Default)]
+struct EvictionStats {
+    total_evicted: u64,
+    eviction_runs: u64,
+    last_eviction: f64,
+}
+
+/// How hybrid search combines keyword and embedding rankings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HybridFusion {
+    /// alpha * embedding + (1 - alpha) * keyword, both min-max normalized
+    Weighted,
+    /// Reciprocal-rank fusion: sum of 1 / (rrf_k + rank)
+    Rrf,
+}
+
+impl HybridFusion {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "weighted" => Some(HybridFusion::Weighted),
+            "rrf" => Some(HybridFusion::Rrf),
+            _ => None,
+        }
+    }
+}
+
+/// File format for fragment export/import
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat {
+    Jsonl,
+    Parquet,
+}
+
+impl ExportFormat {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "jsonl" => Some(ExportFormat::Jsonl),
+            "parquet" => Some(ExportFormat::Parquet),
+            _ => None,
+        }
+    }
+
+    /// Explicit format name, or one inferred from the file extension
+    fn resolve(format: Option<&str>, path: &str) -> PyResult<Self> {
+        let name = match format {
+            Some(name) => name,
+            None => match Path::new(path).extension().and_then(|e| e.to_str()) {
+                Some("parquet") => "parquet",
+                _ => "jsonl",
+            },
+        };
+        Self::parse(name).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown export format: {}", name))
+        })
+    }
+}
+
+/// How stored embeddings are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QuantizationMode {
+    /// Per-vector int8 scalar codes
+    Int8,
+    /// Product-quantized codes for scanning, int8 codes for re-ranking
+    Product,
+}
+
+impl QuantizationMode {
+    fn name(self) -> &'static str {
+        match self {
+            QuantizationMode::Int8 => "int8",
+            QuantizationMode::Product => "pq",
+        }
+    }
+}
+
+/// Compressed embeddings, parallel to `fragments`
+#[derive(Debug, Clone)]
+struct QuantizedStore {
+    mode: QuantizationMode,
+    int8: Vec<Int8Code>,
+    /// Trained quantizer and per-fragment codes (None for mismatched dimensions)
+    pq: Option<(ProductQuantizer, Vec<Option<PqCode>>)>,
+    pq_subspaces: usize,
+    rerank_candidates: usize,
+    keep_full_precision: bool,
+}
+
+/// Cached ranking for one query
+#[derive(Debug, Clone)]
+struct CachedQuery {
+    embedding: Vec<f32>,
+    norm: f32,
+    topk: usize,
+    mmr_lambda: Option<f32>,
+    hits: Vec<(usize, f32)>,
+    last_used: u64,
+}
+
+/// LRU cache of query rankings, cleared whenever stored fragments change
+#[derive(Debug, Clone)]
+struct QueryCache {
+    capacity: usize,
+    /// Queries with cosine similarity >= 1 - tolerance share an entry
+    tolerance: f32,
+    entries: HashMap<u64, CachedQuery>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl QueryCache {
+    fn new(capacity: usize, tolerance: f32) -> Self {
+        Self {
+            capacity,
+            tolerance,
+            entries: HashMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn key(embedding: &[f32], topk: usize, mmr_lambda: Option<f32>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for x in embedding {
+            x.to_bits().hash(&mut hasher);
+        }
+        topk.hash(&mut hasher);
+        mmr_lambda.map(f32::to_bits).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn get(&mut self, key: u64, embedding: &[f32], topk: usize, mmr_lambda: Option<f32>) -> Option<Vec<(usize, f32)>> {
+        if self.capacity == 0 {
+            return None;
+        }
+        self.tick += 1;
+
+        let found = match self.entries.get(&key) {
+            Some(entry) if entry.embedding == embedding => Some(key),
+            _ if self.tolerance > 0.0 => {
+                let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+                self.entries
+                    .iter()
+                    .filter(|(_, e)| e.topk == topk && e.mmr_lambda == mmr_lambda && e.embedding.len() == embedding.len())
+                    .find(|(_, e)| {
+                        let dot: f32 = e.embedding.iter().zip(embedding.iter()).map(|(a, b)| a * b).sum();
+                        norm > 0.0 && e.norm > 0.0 && dot / (norm * e.norm) >= 1.0 - self.tolerance
+                    })
+                    .map(|(&k, _)| k)
+            }
+            _ => None,
+        };
+
+        match found.and_then(|k| self.entries.get_mut(&k)) {
+            Some(entry) => {
+                entry.last_used = self.tick;
+                self.hits += 1;
+                Some(entry.hits.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, key: u64, embedding: &[f32], topk: usize, mmr_lambda: Option<f32>, hits: &[(usize, f32)]) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            if let Some(oldest) = self.entries.iter().min_by_key(|(_, e)| e.last_used).map(|(&k, _)| k) {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(
+            key,
+            CachedQuery {
+                embedding: embedding.to_vec(),
+                norm: embedding.iter().map(|x| x * x).sum::<f32>().sqrt(),
+                topk,
+                mmr_lambda,
+                hits: hits.to_vec(),
+                last_used: self.tick,
+            },
+        );
+    }
+}
+
+/// Fragment store behind the `RustCarmaCore` lock
+struct CarmaStore {
     fragments: Vec<MemoryFragment>,
     clusters: HashMap<i32, Vec<MemoryFragment>>,
+    centroids: Vec<Vec<f32>>,
     total_queries: u64,
+    index: FragmentGraph,
+    exact_threshold: usize,
+    usage: Vec<FragmentUsage>,
+    max_fragments: Option<usize>,
+    eviction_policy: EvictionPolicy,
+    eviction_stats: EvictionStats,
+    dedup: DedupConfig,
+    drift: DriftBaseline,
+    quantized: Option<QuantizedStore>,
+    keywords: Bm25Index,
+    reinforcement: Reinforcement,
+    embedding_policy: EmbeddingPolicy,
+    query_cache: Mutex<QueryCache>,
+    analytics: Mutex<RetrievalAnalytics>,
+}
+
+/// Main CARMA Rust implementation.
+///
+/// Safe to share across Python threads: the store sits behind a read-write
+/// lock that is always taken with the GIL released, so searches run
+/// concurrently with each other and with Python code, and writers (ingestion,
+/// clustering) only block other store access while they hold the lock.
+///
+/// Embedding arguments accept float32/float64 numpy arrays (one row per
+/// embedding for batches) as well as lists.
+#[pyclass]
+pub struct RustCarmaCore {
+    store: RwLock<CarmaStore>,
 }
 
+/// Fragments inserted per write-lock acquisition in `add_fragments`
+const INGEST_CHUNK: usize = 256;
+
+aios_stream::python_iterator!(
+    /// Iterator over a core's fragments, fetched in chunks; see `iter_fragments`
+    FragmentIterator, RustCarmaCore, MemoryFragment,
+    |py, core, offset, limit| Ok(py.allow_threads(|| core.store.read().fragment_window(offset, limit)))
+);
+
 #[pymethods]
 impl RustCarmaCore {
+    /// Create a core with an HNSW index (`hnsw_m` links per node, `ef_*` beam widths).
+    /// Collections up to `exact_threshold` fragments are searched exactly.
     #[new]
-    fn new() -> Self {
+    #[pyo3(signature = (hnsw_m=16, ef_construction=200, ef_search=64, exact_threshold=1000))]
+    fn new(hnsw_m: usize, ef_construction: usize, ef_search: usize, exact_threshold: usize) -> Self {
         Self {
-            fragments: Vec::new(),
-            clusters: HashMap::new(),
-            total_queries: 0,
+            store: RwLock::new(CarmaStore::new(hnsw_m, ef_construction, ef_search, exact_threshold)),
         }
     }
 
-    /// Add a memory fragment
-    fn add_fragment(&mut self, content: String, embedding: Vec<f32>) -> String {
-        let id = Uuid::new_v4().to_string();
-        let fragment = MemoryFragment::new(id.clone(), content, embedding);
-        self.fragments.push(fragment);
-        id
+    /// Add a memory fragment.
+    ///
+    /// With near-duplicate detection enabled, returns the id of an existing
+    /// fragment instead when the new one is too similar to it. Raises
+    /// `EmbeddingError` for a wrong dimension or non-finite values.
+    fn add_fragment(&self, py: Python<'_>, content: String, embedding: Vector<'_>) -> PyResult<String> {
+        let _span = aios_trace::span!("RustCarmaCore.add_fragment", content_len = content.len(), dimension = embedding.len());
+        let embedding = embedding.into_vec();
+        py.allow_threads(|| self.store.write().add_fragment(content, embedding))
     }
 
-    /// Find relevant fragments using cosine similarity
-    fn find_relevant_fragments(&self, query_embedding: Vec<f32>, topk: usize) -> Vec<MemoryFragment> {
-        if self.fragments.is_empty() {
-            return Vec::new();
+    /// Add many fragments, returning their ids in order.
+    ///
+    /// Embeddings are validated and normalized in parallel before anything is
+    /// inserted; insertion then takes the write lock in chunks so queries from
+    /// other threads are served in between.
+    fn add_fragments(&self, py: Python<'_>, contents: Vec<String>, embeddings: Matrix<'_>) -> PyResult<Vec<String>> {
+        let _span = aios_trace::span!("RustCarmaCore.add_fragments", count = contents.len());
+        if contents.len() != embeddings.len() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Got {} contents but {} embeddings",
+                contents.len(),
+                embeddings.len()
+            )));
         }
 
-        let mut similarities: Vec<(usize, f32)> = self
-            .fragments
-            .iter()
-            .enumerate()
-            .map(|(i, fragment)| {
-                let similarity = cosine_similarity(&query_embedding, &fragment.embedding);
-                (i, similarity)
-            })
-            .collect();
+        let embeddings = embeddings.into_rows();
+        py.allow_threads(|| {
+            let embeddings = self.store.read().prepare_embeddings(embeddings)?;
+            let mut ids = Vec::with_capacity(contents.len());
+            let mut pending = contents.into_iter().zip(embeddings).peekable();
+            while pending.peek().is_some() {
+                let mut store = self.store.write();
+                for (content, embedding) in pending.by_ref().take(INGEST_CHUNK) {
+                    ids.push(store.insert_fragment(content, embedding)?);
+                }
+            }
+            Ok(ids)
+        })
+    }
 
-        // Sort by similarity (descending)
-        similarities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    /// Modify a stored fragment; arguments left as None are unchanged.
+    ///
+    /// `metadata` replaces the whole metadata dict. A new embedding is
+    /// validated like in `add_fragment` and triggers an index rebuild.
+    /// Cluster assignments are kept. Raises KeyError for an unknown id.
+    #[pyo3(signature = (fragment_id, content=None, embedding=None, metadata=None))]
+    fn update_fragment(
+        &self,
+        py: Python<'_>,
+        fragment_id: &str,
+        content: Option<String>,
+        embedding: Option<Vector<'_>>,
+        metadata: Option<HashMap<String, String>>,
+    ) -> PyResult<()> {
+        let _span = aios_trace::span!("RustCarmaCore.update_fragment", fragment_id = fragment_id, content = content.is_some(), embedding = embedding.is_some(), metadata = metadata.is_some());
+        let embedding = embedding.map(Vector::into_vec);
+        py.allow_threads(|| {
+            self.store
+                .write()
+                .update_fragment(fragment_id, content, embedding, metadata)
+        })
+    }
 
-        // Return top k fragments
-        similarities
-            .iter()
-            .take(topk)
-            .map(|(i, _)| self.fragments[*i].clone())
-            .collect()
+    /// Remove fragments by id from the store, index and clusters.
+    /// Unknown ids are ignored; returns the number removed.
+    fn delete_fragments(&self, py: Python<'_>, fragment_ids: Vec<String>) -> usize {
+        let _span = aios_trace::span!("RustCarmaCore.delete_fragments", count = fragment_ids.len());
+        py.allow_threads(|| self.store.write().delete_fragments(&fragment_ids))
     }
 
-    /// Cluster fragments using simple k-means
-    fn cluster_fragments(&mut self, num_clusters: usize) -> ClusterResult {
-        if self.fragments.len() < 2 {
-            let mut clusters = HashMap::new();
-            clusters.insert(0, self.fragments.clone());
-            let metadata = HashMap::new();
-            return ClusterResult::new(clusters, metadata);
-        }
+    /// Configure embedding validation.
+    ///
+    /// `dimension` is enforced on every added fragment and query; when None it
+    /// is taken from the first fragment added. `normalize=True` L2-normalizes
+    /// embeddings of fragments added from now on.
+    #[pyo3(signature = (dimension=None, normalize=false))]
+    fn configure_embeddings(&self, py: Python<'_>, dimension: Option<usize>, normalize: bool) -> PyResult<()> {
+        let _span = aios_trace::span!("RustCarmaCore.configure_embeddings", dimension = dimension, normalize = normalize);
+        py.allow_threads(|| self.store.write().configure_embeddings(dimension, normalize))
+    }
 
-        // Extract features (embeddings)
-        let features: Vec<&Vec<f32>> = self.fragments.iter().map(|f| &f.embedding).collect();
-        
-        // Simple k-means clustering
-        let cluster_assignments = kmeans_clustering(&features, num_clusters);
-        
-        // Group fragments by cluster
-        let mut clusters: HashMap<i32, Vec<MemoryFragment>> = HashMap::new();
-        for (i, cluster_id) in cluster_assignments.iter().enumerate() {
-            clusters
-                .entry(*cluster_id)
-                .or_insert_with(Vec::new)
-                .push(self.fragments[i].clone());
-        }
+    /// Configure near-duplicate detection on add_fragment.
+    ///
+    /// A new fragment is a duplicate when its cosine similarity to an existing
+    /// fragment is at least `similarity_threshold` and the word-set Jaccard
+    /// overlap of their contents is at least `overlap_threshold`. `mode` is
+    /// "off", "reject" (keep the existing fragment unchanged) or "merge" (keep
+    /// the longer content and count the duplicate on the existing fragment).
+    #[pyo3(signature = (mode="reject", similarity_threshold=0.95, overlap_threshold=0.8))]
+    fn set_dedup_policy(
+        &self,
+        py: Python<'_>,
+        mode: &str,
+        similarity_threshold: f32,
+        overlap_threshold: f64,
+    ) -> PyResult<()> {
+        let _span = aios_trace::span!("RustCarmaCore.set_dedup_policy", mode = mode, similarity_threshold = similarity_threshold, overlap_threshold = overlap_threshold);
+        py.allow_threads(|| self.store.write().set_dedup_policy(mode, similarity_threshold, overlap_threshold))
+    }
 
-        // Calculate metadata
-        let metadata = calculate_cluster_metadata(&clusters);
-        
-        self.clusters = clusters.clone();
-        
-        ClusterResult::new(clusters, metadata)
+    /// Bound the number of stored fragments.
+    ///
+    /// `policy` is "lru" (least recently retrieved), "lowest_relevance" or
+    /// "oldest". When the store is full, roughly 5% of capacity is evicted at
+    /// once so the index is rebuilt rarely. Pass `None` to remove the limit.
+    #[pyo3(signature = (max_fragments, policy="lru"))]
+    fn set_capacity(&self, py: Python<'_>, max_fragments: Option<usize>, policy: &str) -> PyResult<()> {
+        let _span = aios_trace::span!("RustCarmaCore.set_capacity", max_fragments = max_fragments, policy = policy);
+        py.allow_threads(|| self.store.write().set_capacity(max_fragments, policy))
     }
 
-    /// Process a query and return relevant fragments
-    fn process_query(&mut self, query: String, query_embedding: Vec<f32>, topk: usize) -> PyResult<PyObject> {
-        self.total_queries += 1;
-        
-        let relevant_fragments = self.find_relevant_fragments(query_embedding, topk);
-        
-        // Create response dictionary
-        Python::with_gil(|py| {
-            let result = PyDict::new(py);
-            result.set_item("query", query)?;
-            result.set_item("total_queries", self.total_queries)?;
-            result.set_item("fragments_found", relevant_fragments.len())?;
-            // Convert fragments to Python objects
-            let fragment_list = PyList::empty(py);
-            for fragment in relevant_fragments {
-                let py_fragment = Py::new(py, fragment)?;
-                fragment_list.append(py_fragment)?;
-            }
-            result.set_item("fragments", fragment_list)?;
-            Ok(result.into())
-        })
+    /// Set the search beam width of the HNSW index
+    fn set_ef_search(&self, py: Python<'_>, ef_search: usize) {
+        let _span = aios_trace::span!("RustCarmaCore.set_ef_search", ef_search = ef_search);
+        py.allow_threads(|| self.store.write().set_ef_search(ef_search))
     }
 
-    /// Get system statistics
-    fn get_stats(&self) -> PyResult<PyObject> {
-        Python::with_gil(|py| {
-            let stats = PyDict::new(py);
-            stats.set_item("total_fragments", self.fragments.len())?;
-            stats.set_item("total_queries", self.total_queries)?;
-            stats.set_item("num_clusters", self.clusters.len())?;
-            Ok(stats.into())
+    /// Compress stored embeddings.
+    ///
+    /// `mode="int8"` keeps one int8 code per dimension (about 4x smaller);
+    /// `mode="pq"` additionally scans with product-quantized codes of
+    /// `pq_subspaces` bytes. Searches score the compressed codes against the
+    /// full-precision query and re-rank the best `rerank_candidates`, exactly
+    /// when `keep_full_precision=True`. Without full precision, returned
+    /// fragments carry decoded embeddings and cluster copies carry none.
+    /// `mode="none"` restores (decoded) embeddings and drops the codes.
+    #[pyo3(signature = (mode="int8", rerank_candidates=50, pq_subspaces=8, keep_full_precision=false))]
+    fn enable_quantization(
+        &self,
+        py: Python<'_>,
+        mode: &str,
+        rerank_candidates: usize,
+        pq_subspaces: usize,
+        keep_full_precision: bool,
+    ) -> PyResult<()> {
+        let _span = aios_trace::span!("RustCarmaCore.enable_quantization", mode = mode, rerank_candidates = rerank_candidates, pq_subspaces = pq_subspaces, keep_full_precision = keep_full_precision);
+        py.allow_threads(|| {
+            self.store
+                .write()
+                .enable_quantization(mode, rerank_candidates, pq_subspaces, keep_full_precision)
         })
     }
 
-    /// Get all fragments
-    fn get_all_fragments(&self) -> Vec<MemoryFragment> {
-        self.fragments.clone()
+    /// Configure the query result cache.
+    ///
+    /// Up to `capacity` rankings are kept in LRU order (0 disables caching).
+    /// With `tolerance > 0`, a query whose cosine similarity to a cached query
+    /// is at least `1 - tolerance` reuses its results. The cache is cleared
+    /// whenever fragments change and is bypassed while reinforcement is on.
+    #[pyo3(signature = (capacity=256, tolerance=0.0))]
+    fn configure_query_cache(&self, py: Python<'_>, capacity: usize, tolerance: f32) {
+        let _span = aios_trace::span!("RustCarmaCore.configure_query_cache", capacity = capacity, tolerance = tolerance);
+        py.allow_threads(|| *self.store.write().query_cache.get_mut() = QueryCache::new(capacity, tolerance.max(0.0)))
     }
 
-    /// Clear all data
-    fn clear_all(&mut self) {
-        self.fragments.clear();
-        self.clusters.clear();
-        self.total_queries = 0;
+    /// Keep retrieval analytics for the last `window` queries (0 disables).
+    ///
+    /// `get_stats` reports latency percentiles, the mean candidate-set size and
+    /// query counts per retrieval class over this window. Queries in a
+    /// `process_queries` batch each record an equal share of the batch time.
+    #[pyo3(signature = (window=1000))]
+    fn set_analytics_window(&self, py: Python<'_>, window: usize) {
+        let _span = aios_trace::span!("RustCarmaCore.set_analytics_window", window = window);
+        py.allow_threads(|| self.store.read().analytics.lock().set_window(window))
     }
-}
 
-/// Calculate cosine similarity between two vectors
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    if a.len() != b.len() {
-        return 0.0;
+    /// Find relevant fragments using cosine similarity
+    fn find_relevant_fragments(
+        &self,
+        py: Python<'_>,
+        query_embedding: Vector<'_>,
+        topk: usize,
+    ) -> PyResult<Vec<MemoryFragment>> {
+        let _span = aios_trace::span!("RustCarmaCore.find_relevant_fragments", dimension = query_embedding.len(), topk = topk);
+        let query_embedding = query_embedding.into_vec();
+        py.allow_threads(|| self.store.read().find_relevant_fragments(query_embedding, topk))
     }
 
-    let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-    let magnitude_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let magnitude_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    /// Let retrieval history influence ranking.
+    ///
+    /// Results are ordered by `similarity + weight * ln(1 + hit_count)`, with the
+    /// bonus halving every `half_life` seconds since the fragment was last
+    /// retrieved. `weight=0` restores pure similarity ranking.
+    #[pyo3(signature = (weight=0.05, half_life=None))]
+    fn set_reinforcement(&self, py: Python<'_>, weight: f32, half_life: Option<f64>) -> PyResult<()> {
+        let _span = aios_trace::span!("RustCarmaCore.set_reinforcement", weight = weight, half_life = half_life);
+        py.allow_threads(|| self.store.write().set_reinforcement(weight, half_life))
+    }
 
-    if magnitude_a == 0.0 || magnitude_b == 0.0 {
-        0.0
-    } else {
-        dot_product / (magnitude_a * magnitude_b)
+    /// Per-fragment access stats: hit_count, last_access and cumulative relevance.
+    ///
+    /// Returns stats for `fragment_id`, or a dict of all fragments keyed by id.
+    #[pyo3(signature = (fragment_id=None))]
+    fn get_access_stats(&self, py: Python<'_>, fragment_id: Option<&str>) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustCarmaCore.get_access_stats", fragment_id = fragment_id);
+        py.allow_threads(|| self.store.read().get_access_stats(fragment_id))
     }
-}
 
-/// Simple k-means clustering implementation
-fn kmeans_clustering(features: &[&Vec<f32>], k: usize) -> Vec<i32> {
-    if features.is_empty() || k == 0 {
-        return Vec::new();
+    /// Top-k fragments re-ranked with maximal marginal relevance.
+    ///
+    /// Each pick maximizes `lambda_ * sim(query, d) - (1 - lambda_) * max sim(d, picked)`
+    /// over the best `candidates` fragments (default 4 * topk). `lambda_=1` is
+    /// plain relevance ranking, lower values favour diversity.
+    #[pyo3(signature = (query_embedding, topk, lambda_=0.5, candidates=None))]
+    fn find_diverse_fragments(
+        &self,
+        py: Python<'_>,
+        query_embedding: Vector<'_>,
+        topk: usize,
+        lambda_: f32,
+        candidates: Option<usize>,
+    ) -> PyResult<Vec<MemoryFragment>> {
+        let _span = aios_trace::span!("RustCarmaCore.find_diverse_fragments", dimension = query_embedding.len(), topk = topk, lambda = lambda_, candidates = candidates);
+        let query_embedding = query_embedding.into_vec();
+        py.allow_threads(|| {
+            self.store
+                .read()
+                .find_diverse_fragments(query_embedding, topk, lambda_, candidates)
+        })
     }
 
-    let n = features.len();
-    let dim = features[0].len();
-    
-    // Initialize centroids randomly
-    let mut centroids: Vec<Vec<f32>> = (0..k)
-        .map(|_| {
-            (0..dim)
-                .map(|_| rand::random::<f32>() * 2.0 - 1.0)
-                .collect()
+    /// Top-k fragments by BM25 keyword score over their content
+    fn keyword_search(&self, py: Python<'_>, query: &str, topk: usize) -> Vec<MemoryFragment> {
+        let _span = aios_trace::span!("RustCarmaCore.keyword_search", query_len = query.len(), topk = topk);
+        py.allow_threads(|| match filter_query(query) {
+            Some(query) => self.store.read().keyword_search(&query, topk),
+            None => Vec::new(),
+        })
+    }
+
+    /// Combine BM25 keyword and embedding retrieval.
+    ///
+    /// The best `candidates` fragments of each ranking are fused with
+    /// `fusion="rrf"` (reciprocal-rank fusion with constant `rrf_k`) or
+    /// `fusion="weighted"` (`alpha` * embedding + (1 - `alpha`) * keyword score,
+    /// each min-max normalized over the candidates).
+    #[pyo3(signature = (query, query_embedding, topk, fusion="rrf", alpha=0.5, rrf_k=60.0, candidates=50))]
+    #[allow(clippy::too_many_arguments)]
+    fn hybrid_search(
+        &self,
+        py: Python<'_>,
+        query: &str,
+        query_embedding: Vector<'_>,
+        topk: usize,
+        fusion: &str,
+        alpha: f32,
+        rrf_k: f32,
+        candidates: usize,
+    ) -> PyResult<Vec<MemoryFragment>> {
+        let _span = aios_trace::span!("RustCarmaCore.hybrid_search", query_len = query.len(), topk = topk, fusion = fusion, candidates = candidates);
+        let query_embedding = query_embedding.into_vec();
+        py.allow_threads(|| match filter_query(query) {
+            Some(query) => self.store.read().hybrid_search(&query, query_embedding, topk, fusion, alpha, rrf_k, candidates),
+            None => Ok(Vec::new()),
         })
-        .collect();
+    }
 
-    let mut assignments = vec![0; n];
-    let max_iterations = 100;
+    /// Cluster fragments.
+    ///
+    /// `mode="kmeans"` runs k-means with k-means++ seeding (pass `batch_size` for
+    /// mini-batch k-means). `mode="dbscan"` runs density-based clustering with
+    /// cosine distance `eps` and `min_samples`; unclustered fragments are returned
+    /// in `ClusterResult.noise`.
+    ///
+    /// `mode="auto"` runs k-means for every k from `k_min` to `num_clusters` and
+    /// keeps the best by `criterion` ("silhouette" or "elbow" on inertia). The
+    /// chosen k is reported as `chosen_k`, and the curve as `inertia_k{k}` and
+    /// `silhouette_k{k}` metadata entries.
+    ///
+    /// Raises `CancelledError` if `cancel_token` is cancelled before the
+    /// clustering finishes; existing clusters are then left unchanged.
+    #[pyo3(signature = (num_clusters=8, batch_size=None, tolerance=1e-4, max_iterations=100, mode="kmeans", eps=0.3, min_samples=5, k_min=2, criterion="silhouette", cancel_token=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn cluster_fragments(
+        &self,
+        py: Python<'_>,
+        num_clusters: usize,
+        batch_size: Option<usize>,
+        tolerance: f64,
+        max_iterations: usize,
+        mode: &str,
+        eps: f32,
+        min_samples: usize,
+        k_min: usize,
+        criterion: &str,
+        cancel_token: Option<PyObject>,
+    ) -> PyResult<ClusterResult> {
+        let _span = aios_trace::span!("RustCarmaCore.cluster_fragments", num_clusters = num_clusters, mode = mode, batch_size = batch_size, k_min = k_min, criterion = criterion);
+        let cancel = cancel::token(py, cancel_token)?;
+        py.allow_threads(|| {
+            aios_threads::install(|| {
+                self.store.write().cluster_fragments(
+                    num_clusters,
+                    batch_size,
+                    tolerance,
+                    max_iterations,
+                    mode,
+                    eps,
+                    min_samples,
+                    k_min,
+                    criterion,
+                    &cancel,
+                )
+            })
+        })
+    }
 
-    for _ in 0..max_iterations {
-        let mut changed = false;
+    /// Place fragments into the existing clusters by nearest centroid.
+    ///
+    /// `fragment_ids` defaults to every fragment not yet in a cluster. Centroids
+    /// are updated as running means; `max_centroid_drift` is the largest centroid
+    /// movement since the last full clustering, relative to the mean member
+    /// distance at that time. When it exceeds `drift_threshold` the result
+    /// reports `needs_recluster`, and `auto_recluster=True` re-runs k-means.
+    #[pyo3(signature = (fragment_ids=None, drift_threshold=0.25, auto_recluster=false))]
+    fn assign_to_clusters(
+        &self,
+        py: Python<'_>,
+        fragment_ids: Option<Vec<String>>,
+        drift_threshold: f32,
+        auto_recluster: bool,
+    ) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustCarmaCore.assign_to_clusters", count = fragment_ids.as_ref().map(Vec::len), drift_threshold = drift_threshold, auto_recluster = auto_recluster);
+        py.allow_threads(|| {
+            aios_threads::install(|| {
+                self.store
+                    .write()
+                    .assign_to_clusters(fragment_ids, drift_threshold, auto_recluster)
+            })
+        })
+    }
 
-        // Assign points to closest centroid
-        for (i, feature) in features.iter().enumerate() {
-            let mut best_cluster = 0;
-            let mut best_distance = f32::INFINITY;
+    /// Agglomerative clustering of all fragments into a dendrogram.
+    ///
+    /// `linkage` is one of "single", "complete", "average" (cosine distance)
+    /// or "ward" (Euclidean distance). Raises `CancelledError` if
+    /// `cancel_token` is cancelled first.
+    #[pyo3(signature = (linkage="average", cancel_token=None))]
+    fn hierarchical_cluster(&self, py: Python<'_>, linkage: &str, cancel_token: Option<PyObject>) -> PyResult<Dendrogram> {
+        let _span = aios_trace::span!("RustCarmaCore.hierarchical_cluster", linkage = linkage);
+        let cancel = cancel::token(py, cancel_token)?;
+        py.allow_threads(|| aios_threads::install(|| self.store.read().hierarchical_cluster(linkage, &cancel)))
+    }
 
-            for (j, centroid) in centroids.iter().enumerate() {
-                let distance = euclidean_distance(feature, centroid);
-                if distance < best_distance {
-                    best_distance = distance;
-                    best_cluster = j;
-                }
-            }
+    /// Process a query and return relevant fragments.
+    ///
+    /// Pass `mmr_lambda` to diversify the results with maximal marginal relevance.
+    ///
+    /// `reranker(query, fragments)` is called with the top `rerank_candidates`
+    /// (default `4 * topk`) and must return one score per fragment; the best
+    /// `topk` by that score are returned. It runs without holding the store's
+    /// lock, so it may call back into the core; candidates removed while it
+    /// runs are left out of the results.
+    ///
+    /// `pre_retrieval` plugins may rewrite `query` or reject it; a rejected
+    /// query returns no fragments.
+    #[pyo3(signature = (query, query_embedding, topk, mmr_lambda=None, reranker=None, rerank_candidates=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn process_query(
+        &self,
+        py: Python<'_>,
+        query: String,
+        query_embedding: Vector<'_>,
+        topk: usize,
+        mmr_lambda: Option<f32>,
+        reranker: Option<PyObject>,
+        rerank_candidates: Option<usize>,
+    ) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustCarmaCore.process_query", topk = topk, mmr_lambda = mmr_lambda, reranker = reranker.is_some());
+        let mut results = self.run_queries(
+            py,
+            vec![query],
+            vec![query_embedding.into_vec()],
+            topk,
+            mmr_lambda,
+            reranker.as_ref(),
+            rerank_candidates,
+        )?;
+        Ok(results.remove(0))
+    }
 
-            if assignments[i] != best_cluster as i32 {
-                assignments[i] = best_cluster as i32;
-                changed = true;
-            }
+    /// Process many queries concurrently, releasing the GIL while searching.
+    ///
+    /// Returns one result dict per query, in the same shape as `process_query`.
+    /// `reranker` is called once per query, as in `process_query`.
+    #[pyo3(signature = (queries, query_embeddings, topk, mmr_lambda=None, reranker=None, rerank_candidates=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn process_queries(
+        &self,
+        py: Python<'_>,
+        queries: Vec<String>,
+        query_embeddings: Matrix<'_>,
+        topk: usize,
+        mmr_lambda: Option<f32>,
+        reranker: Option<PyObject>,
+        rerank_candidates: Option<usize>,
+    ) -> PyResult<Vec<PyObject>> {
+        let _span = aios_trace::span!("RustCarmaCore.process_queries", count = queries.len(), topk = topk, mmr_lambda = mmr_lambda, reranker = reranker.is_some());
+        if queries.len() != query_embeddings.len() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Got {} queries but {} embeddings",
+                queries.len(),
+                query_embeddings.len()
+            )));
         }
+        self.run_queries(
+            py,
+            queries,
+            query_embeddings.into_rows(),
+            topk,
+            mmr_lambda,
+            reranker.as_ref(),
+            rerank_candidates,
+        )
+    }
 
-        if !changed {
-            break;
+    /// Awaitable `process_queries`; the batch runs on a background thread, so
+    /// other calls on this core are served while it is in flight.
+    #[pyo3(signature = (queries, query_embeddings, topk, mmr_lambda=None, reranker=None, rerank_candidates=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn process_queries_async(
+        slf: Py<Self>,
+        py: Python<'_>,
+        queries: Vec<String>,
+        query_embeddings: Matrix<'_>,
+        topk: usize,
+        mmr_lambda: Option<f32>,
+        reranker: Option<PyObject>,
+        rerank_candidates: Option<usize>,
+    ) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustCarmaCore.process_queries_async", count = queries.len(), topk = topk, mmr_lambda = mmr_lambda, reranker = reranker.is_some());
+        if queries.len() != query_embeddings.len() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Got {} queries but {} embeddings",
+                queries.len(),
+                query_embeddings.len()
+            )));
         }
+        let query_embeddings = query_embeddings.into_rows();
+        awaitable::spawn(py, move || {
+            Python::with_gil(|py| {
+                slf.borrow(py).run_queries(
+                    py,
+                    queries,
+                    query_embeddings,
+                    topk,
+                    mmr_lambda,
+                    reranker.as_ref(),
+                    rerank_candidates,
+                )
+            })
+        })
+    }
 
-        // Update centroids
-        for (j, centroid) in centroids.iter_mut().enumerate() {
-            let cluster_points: Vec<&Vec<f32>> = features
-                .iter()
-                .zip(assignments.iter())
-                .filter(|(_, &cluster)| cluster == j as i32)
-                .map(|(point, _)| *point)
-                .collect();
-
-            if !cluster_points.is_empty() {
-                for (i, component) in centroid.iter_mut().enumerate() {
-                    *component = cluster_points.iter().map(|point| point[i]).sum::<f32>()
-                        / cluster_points.len() as f32;
+    /// Write every fragment to `path` as JSON Lines or Parquet.
+    ///
+    /// `format` is "jsonl" or "parquet" (default: from the file extension).
+    /// Parquet files hold one row per fragment with `embedding` as a list of
+    /// floats and `metadata` as a JSON string. Returns the number written.
+    #[pyo3(signature = (path, format=None))]
+    fn export_fragments(&self, py: Python<'_>, path: &str, format: Option<&str>) -> PyResult<usize> {
+        let _span = aios_trace::span!("RustCarmaCore.export_fragments", path = path, format = format);
+        let format = ExportFormat::resolve(format, path)?;
+        py.allow_threads(|| self.store.read().export_fragments(path, format))
+    }
+
+    /// Add fragments from a file written by `export_fragments`.
+    ///
+    /// Ids, timestamps and metadata are kept; fragments whose id is already
+    /// stored are skipped and dedup is not applied. Returns the number added.
+    #[pyo3(signature = (path, format=None))]
+    fn import_fragments(&self, py: Python<'_>, path: &str, format: Option<&str>) -> PyResult<usize> {
+        let _span = aios_trace::span!("RustCarmaCore.import_fragments", path = path, format = format);
+        let format = ExportFormat::resolve(format, path)?;
+        py.allow_threads(|| {
+            let fragments = read_fragment_file(path, format)
+                .map_err(|e| errors::io(format!("Failed to import fragments: {}", e)))?;
+            let embeddings = self
+                .store
+                .read()
+                .prepare_embeddings(fragments.iter().map(|f| f.embedding.clone()).collect())?;
+            let mut store = self.store.write();
+            let mut imported = 0;
+            for (mut fragment, embedding) in fragments.into_iter().zip(embeddings) {
+                fragment.embedding = embedding;
+                if store.import_fragment(fragment)? {
+                    imported += 1;
                 }
             }
-        }
+            Ok(imported)
+        })
     }
 
-    assignments
-}
+    /// Add fragments from an Arrow IPC stream or file, returning their ids.
+    ///
+    /// `source` is the IPC bytes or a path, e.g. the output of rust_data's
+    /// `export_fragment_candidates`. Each row's `content_column` (utf8) and
+    /// `features_column` (list of floats) become a fragment, inserted like
+    /// `add_fragments`; `path_column`, when present, is kept as the
+    /// `source_path` metadata entry. Raises ValueError for missing columns.
+    #[pyo3(signature = (source, content_column="content", features_column="features", path_column="path"))]
+    fn ingest_arrow(
+        &self,
+        py: Python<'_>,
+        source: ArrowSource<'_>,
+        content_column: &str,
+        features_column: &str,
+        path_column: &str,
+    ) -> PyResult<Vec<String>> {
+        let _span = aios_trace::span!("RustCarmaCore.ingest_arrow", content_column = content_column, features_column = features_column);
+        let bytes = match &source {
+            ArrowSource::Bytes(bytes) => Cow::Borrowed(bytes.as_bytes()),
+            ArrowSource::Path(path) => Cow::Owned(
+                py.allow_threads(|| fs::read(path))
+                    .map_err(|e| errors::io(format!("Failed to read {}: {}", path.display(), e)))?,
+            ),
+        };
+        py.allow_threads(|| {
+            let mut rows = Vec::new();
+            for mut batch in aios_arrow::read_stream(&bytes).map_err(errors::to_pyerr)? {
+                let contents = match batch.take_column(content_column) {
+                    Some(aios_arrow::Column::Utf8(contents)) => contents,
+                    other => return Err(arrow_column_error(content_column, "utf8", other)),
+                };
+                let features = match batch.take_column(features_column) {
+                    Some(aios_arrow::Column::FloatLists(features)) => features,
+                    other => return Err(arrow_column_error(features_column, "list<float>", other)),
+                };
+                let paths = match batch.take_column(path_column) {
+                    Some(aios_arrow::Column::Utf8(paths)) => paths.into_iter().map(Some).collect(),
+                    None => vec![None; contents.len()],
+                    other => return Err(arrow_column_error(path_column, "utf8", other)),
+                };
+                rows.extend(contents.into_iter().zip(features).zip(paths));
+            }
 
-/// Calculate Euclidean distance between two vectors
-fn euclidean_distance(a: &[f32], b: &[f32]) -> f32 {
-    if a.len() != b.len() {
-        return f32::INFINITY;
+            let (rows, features): (Vec<_>, Vec<_>) =
+                rows.into_iter().map(|((content, features), path)| ((content, path), features)).unzip();
+            let embeddings = self.store.read().prepare_embeddings(features)?;
+            let mut ids = Vec::with_capacity(rows.len());
+            let mut pending = rows.into_iter().zip(embeddings).peekable();
+            while pending.peek().is_some() {
+                let mut store = self.store.write();
+                for ((content, path), embedding) in pending.by_ref().take(INGEST_CHUNK) {
+                    let metadata = path.map(|path| HashMap::from([("source_path".to_string(), path)])).unwrap_or_default();
+                    ids.push(store.insert_fragment_with_metadata(content, embedding, metadata)?);
+                }
+            }
+            Ok(ids)
+        })
     }
 
-    a.iter()
-        .zip(b.iter())
-        .map(|(x, y)| (x - y).powi(2))
-        .sum::<f32>()
-        .sqrt()
-}
+    /// Get system statistics
+    fn get_stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustCarmaCore.get_stats");
+        py.allow_threads(|| self.store.read().get_stats())
+    }
 
-/// Calculate cluster metadata
+    /// Save fragments, embeddings, cluster assignments and stats to `path`
+    fn save(&self, py: Python<'_>, path: &str) -> PyResult<()> {
+        let _span = aios_trace::span!("RustCarmaCore.save", path = path);
+        py.allow_threads(|| self.store.read().save(path))
+    }
+
+    /// Replace the current state with a snapshot written by `save`
+    fn load(&self, py: Python<'_>, path: &str) -> PyResult<()> {
+        let _span = aios_trace::span!("RustCarmaCore.load", path = path);
+        py.allow_threads(|| self.store.write().load(path))
+    }
+
+    /// Section name in `save_state` archives
+    #[classattr]
+    const STATE_SECTION: &'static str = "carma";
+
+    /// Everything `save` writes, as `(version, bytes)` in the same format
+    fn export_state(&self, py: Python<'_>) -> PyResult<(u32, PyObject)> {
+        let _span = aios_trace::span!("RustCarmaCore.export_state");
+        let data = py.allow_threads(|| self.store.read().export_state())?;
+        Ok((FORMAT_VERSION, data.as_slice().into_py(py)))
+    }
+
+    /// Replace the current state with one from `export_state`, like `load`
+    fn import_state(&self, py: Python<'_>, version: u32, data: &[u8]) -> PyResult<()> {
+        let _span = aios_trace::span!("RustCarmaCore.import_state", version = version, bytes = data.len());
+        aios_state::check_version("carma", version, FORMAT_VERSION).map_err(errors::to_pyerr)?;
+        py.allow_threads(|| self.store.write().import_state(data))
+    }
+
+    /// Get the centroids from the last clustering run
+    fn get_centroids(&self, py: Python<'_>) -> Vec<Vec<f32>> {
+        let _span = aios_trace::span!("RustCarmaCore.get_centroids");
+        py.allow_threads(|| self.store.read().get_centroids())
+    }
+
+    /// Centroids from the last clustering run as a (k, dim) float32 numpy array
+    fn get_centroids_array<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let _span = aios_trace::span!("RustCarmaCore.get_centroids_array");
+        let centroids = py.allow_threads(|| self.store.read().get_centroids());
+        to_ndarray(py, &centroids, centroids.first().map_or(0, Vec::len))
+    }
+
+    /// Fragment ids and their embeddings as an (n, dim) float32 numpy array.
+    ///
+    /// Row `i` belongs to `ids[i]`; quantized embeddings are decoded.
+    fn get_embedding_matrix<'py>(&self, py: Python<'py>) -> PyResult<(Vec<String>, Bound<'py, PyArray2<f32>>)> {
+        let _span = aios_trace::span!("RustCarmaCore.get_embedding_matrix");
+        let (ids, rows) = py.allow_threads(|| self.store.read().embedding_matrix());
+        let matrix = to_ndarray(py, &rows, rows.first().map_or(0, Vec::len))?;
+        Ok((ids, matrix))
+    }
+
+    /// Get all fragments
+    fn get_all_fragments(&self, py: Python<'_>) -> Vec<MemoryFragment> {
+        let _span = aios_trace::span!("RustCarmaCore.get_all_fragments");
+        py.allow_threads(|| self.store.read().get_all_fragments())
+    }
+
+    /// Iterate over all fragments, `chunk_size` at a time, without copying
+    /// the whole collection into Python
+    #[pyo3(signature = (chunk_size=aios_stream::DEFAULT_CHUNK_SIZE))]
+    fn iter_fragments(slf: Py<Self>, chunk_size: usize) -> PyResult<FragmentIterator> {
+        let _span = aios_trace::span!("RustCarmaCore.iter_fragments", chunk_size = chunk_size);
+        FragmentIterator::new(slf, chunk_size)
+    }
+
+    /// Clear all data
+    fn clear_all(&self, py: Python<'_>) {
+        let _span = aios_trace::span!("RustCarmaCore.clear_all");
+        py.allow_threads(|| self.store.write().clear_all())
+    }
+}
+
+impl RustCarmaCore {
+    /// Search under a shared lock, then upgrade it to record hits. Only one
+    /// query batch holds the upgradable lock at a time, so positions found in
+    /// the search phase are still valid when they are recorded.
+    ///
+    /// A reranker runs with no lock held, so it may call back into the core
+    /// (and slow rerankers don't hold up other queries); its picks are looked
+    /// up again by id, dropping fragments removed in the meantime.
+    #[allow(clippy::too_many_arguments)]
+    fn run_queries(
+        &self,
+        py: Python<'_>,
+        queries: Vec<String>,
+        query_embeddings: Vec<Vec<f32>>,
+        topk: usize,
+        mmr_lambda: Option<f32>,
+        reranker: Option<&PyObject>,
+        rerank_candidates: Option<usize>,
+    ) -> PyResult<Vec<PyObject>> {
+        py.allow_threads(|| {
+            // Plugins run before the store is locked, so they may call back into the core
+            let (queries, rejected): (Vec<String>, Vec<bool>) = queries
+                .into_iter()
+                .map(|query| match filter_query(&query) {
+                    Some(filtered) => (filtered, false),
+                    None => (query, true),
+                })
+                .unzip();
+            let start = Instant::now();
+            let (store, mut all_hits, class, candidates) = match reranker {
+                Some(reranker) => {
+                    let pool = rerank_candidates.unwrap_or(topk.saturating_mul(4)).max(topk);
+                    let (fragments, scores): (Vec<Vec<MemoryFragment>>, Vec<Vec<f32>>) = {
+                        let store = self.store.read();
+                        store
+                            .search_queries(&query_embeddings, pool, mmr_lambda)?
+                            .into_iter()
+                            .map(|hits| hits.into_iter().map(|(i, score)| (store.fragment_out(i), score)).unzip())
+                            .unzip()
+                    };
+                    let sizes = fragments.iter().map(|f| f.len()).collect();
+                    let picks: Vec<Vec<(String, f32)>> = rerank(reranker, &queries, &fragments, topk)?
+                        .into_iter()
+                        .zip(fragments.iter().zip(&scores))
+                        .map(|(order, (fragments, scores))| {
+                            order.into_iter().map(|c| (fragments[c].id.clone(), scores[c])).collect()
+                        })
+                        .collect();
+                    let store = self.store.upgradable_read();
+                    let all_hits = store.locate_hits(picks);
+                    (store, all_hits, QueryClass::Reranked, sizes)
+                }
+                None => {
+                    let store = self.store.upgradable_read();
+                    let (class, pool) = match mmr_lambda {
+                        Some(_) => (QueryClass::Diverse, store.semantic_pool(topk.saturating_mul(4))),
+                        None => (QueryClass::Semantic, store.semantic_pool(topk)),
+                    };
+                    let all_hits = store.search_queries(&query_embeddings, topk, mmr_lambda)?;
+                    (store, all_hits, class, vec![pool; query_embeddings.len()])
+                }
+            };
+
+            for (hits, rejected) in all_hits.iter_mut().zip(rejected) {
+                if rejected {
+                    hits.clear();
+                }
+            }
+
+            let latency = start.elapsed().as_secs_f64() / queries.len().max(1) as f64;
+            let mut analytics = store.analytics.lock();
+            for candidates in candidates {
+                record_latency(&mut analytics, class, latency, candidates);
+            }
+            drop(analytics);
+            RwLockUpgradableReadGuard::upgrade(store).finish_queries(queries, all_hits)
+        })
+    }
+}
+
+impl CarmaStore {
+    fn new(hnsw_m: usize, ef_construction: usize, ef_search: usize, exact_threshold: usize) -> Self {
+        Self {
+            fragments: Vec::new(),
+            clusters: HashMap::new(),
+            centroids: Vec::new(),
+            total_queries: 0,
+            index: FragmentGraph::new(hnsw_m, ef_construction, ef_search),
+            exact_threshold,
+            usage: Vec::new(),
+            max_fragments: None,
+            eviction_policy: EvictionPolicy::Lru,
+            eviction_stats: EvictionStats::default(),
+            dedup: DedupConfig {
+                mode: DedupMode::Off,
+                similarity_threshold: 0.95,
+                overlap_threshold: 0.8,
+                rejected: 0,
+                merged: 0,
+            },
+            drift: DriftBaseline::default(),
+            quantized: None,
+            keywords: Bm25Index::new(1.2, 0.75),
+            reinforcement: Reinforcement::default(),
+            embedding_policy: EmbeddingPolicy::default(),
+            query_cache: Mutex::new(QueryCache::new(256, 0.0)),
+            analytics: Mutex::new(RetrievalAnalytics::new(1000)),
+        }
+    }
+
+    fn add_fragment(&mut self, content: String, embedding: Vec<f32>) -> PyResult<String> {
+        let embedding = self.prepare_embedding(embedding, self.embedding_policy.dimension)?;
+        self.insert_fragment(content, embedding)
+    }
+
+    /// Validate and (if configured) normalize embeddings in parallel.
+    /// Without a configured dimension, the first embedding sets it.
+    fn prepare_embeddings(&self, embeddings: Vec<Vec<f32>>) -> PyResult<Vec<Vec<f32>>> {
+        let dimension = self
+            .embedding_policy
+            .dimension
+            .or_else(|| embeddings.first().map(|e| e.len()));
+        aios_threads::install(|| {
+            embeddings
+                .into_par_iter()
+                .map(|embedding| self.prepare_embedding(embedding, dimension))
+                .collect()
+        })
+    }
+
+    /// Add a fragment whose embedding already went through `prepare_embedding`
+    fn insert_fragment(&mut self, content: String, embedding: Vec<f32>) -> PyResult<String> {
+        self.insert_fragment_with_metadata(content, embedding, HashMap::new())
+    }
+
+    /// `insert_fragment` with initial metadata; a duplicate keeps its own
+    fn insert_fragment_with_metadata(
+        &mut self,
+        content: String,
+        embedding: Vec<f32>,
+        metadata: HashMap<String, String>,
+    ) -> PyResult<String> {
+        // The dimension may have been fixed by another batch since preparation
+        let dimension = *self.embedding_policy.dimension.get_or_insert(embedding.len());
+        if embedding.len() != dimension {
+            return Err(dimension_error(dimension, embedding.len()));
+        }
+
+        if self.dedup.mode != DedupMode::Off {
+            if let Some(position) = self.find_duplicate(&content, &embedding) {
+                return Ok(self.absorb_duplicate(position, content));
+            }
+        }
+
+        let id = Uuid::new_v4().to_string();
+        let mut fragment = MemoryFragment::new(id.clone(), content, embedding);
+        fragment.metadata = metadata;
+        self.push_fragment(fragment);
+        Ok(id)
+    }
+
+    fn update_fragment(
+        &mut self,
+        fragment_id: &str,
+        content: Option<String>,
+        embedding: Option<Vec<f32>>,
+        metadata: Option<HashMap<String, String>>,
+    ) -> PyResult<()> {
+        let position = self
+            .fragments
+            .iter()
+            .position(|f| f.id == fragment_id)
+            .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!("Unknown fragment: {}", fragment_id)))?;
+        let embedding = embedding
+            .map(|e| self.prepare_embedding(e, self.embedding_policy.dimension))
+            .transpose()?;
+
+        let content_changed = content.is_some();
+        let fragment = &mut self.fragments[position];
+        if let Some(content) = content {
+            fragment.content = content;
+        }
+        if let Some(metadata) = metadata {
+            fragment.metadata = metadata;
+        }
+
+        let embedding_changed = embedding.is_some();
+        if let Some(mut embedding) = embedding {
+            if let Some(store) = &mut self.quantized {
+                store.int8[position] = Int8Code::encode(&embedding);
+                if let Some((pq, codes)) = &mut store.pq {
+                    codes[position] = pq.encode(&embedding);
+                }
+                if !store.keep_full_precision {
+                    embedding = Vec::new();
+                }
+            }
+            fragment.embedding = embedding;
+        }
+
+        let updated = self.fragments[position].clone();
+        for member in self.clusters.values_mut().flatten() {
+            if member.id == updated.id {
+                *member = updated.clone();
+            }
+        }
+        if content_changed {
+            self.rebuild_keywords();
+        }
+        if embedding_changed {
+            self.rebuild_index();
+        }
+        Ok(())
+    }
+
+    fn delete_fragments(&mut self, fragment_ids: &[String]) -> usize {
+        let ids: HashSet<&str> = fragment_ids.iter().map(|id| id.as_str()).collect();
+        let positions: HashSet<usize> = (0..self.fragments.len())
+            .filter(|&i| ids.contains(self.fragments[i].id.as_str()))
+            .collect();
+        if !positions.is_empty() {
+            self.remove_positions(&positions);
+        }
+        positions.len()
+    }
+
+    fn configure_embeddings(&mut self, dimension: Option<usize>, normalize: bool) -> PyResult<()> {
+        if let Some(dimension) = dimension {
+            let mismatched = (0..self.fragments.len())
+                .filter(|&i| stored_embedding(&self.fragments, self.quantized.as_ref(), i).len() != dimension)
+                .count();
+            if mismatched > 0 {
+                return Err(EmbeddingError::new_err(format!(
+                    "{} stored fragments do not have dimension {}",
+                    mismatched, dimension
+                )));
+            }
+        }
+        self.embedding_policy = EmbeddingPolicy { dimension, normalize };
+        Ok(())
+    }
+
+    fn set_dedup_policy(&mut self, mode: &str, similarity_threshold: f32, overlap_threshold: f64) -> PyResult<()> {
+        self.dedup.mode = DedupMode::parse(mode).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown dedup mode: {}", mode))
+        })?;
+        self.dedup.similarity_threshold = similarity_threshold;
+        self.dedup.overlap_threshold = overlap_threshold;
+        Ok(())
+    }
+
+    fn set_capacity(&mut self, max_fragments: Option<usize>, policy: &str) -> PyResult<()> {
+        self.eviction_policy = EvictionPolicy::parse(policy).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown eviction policy: {}", policy))
+        })?;
+        self.max_fragments = max_fragments.map(|max| max.max(1));
+
+        if let Some(max) = self.max_fragments {
+            if self.fragments.len() > max {
+                self.evict(self.fragments.len() - max);
+            }
+        }
+        Ok(())
+    }
+
+    fn set_ef_search(&mut self, ef_search: usize) {
+        self.index.set_ef_search(ef_search);
+        self.query_cache.get_mut().entries.clear();
+    }
+
+    fn enable_quantization(
+        &mut self,
+        mode: &str,
+        rerank_candidates: usize,
+        pq_subspaces: usize,
+        keep_full_precision: bool,
+    ) -> PyResult<()> {
+        let mode = match mode {
+            "none" => None,
+            "int8" => Some(QuantizationMode::Int8),
+            "pq" => Some(QuantizationMode::Product),
+            _ => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Unknown quantization mode: {}",
+                    mode
+                )))
+            }
+        };
+
+        self.restore_embeddings();
+        self.quantized = mode.map(|mode| QuantizedStore {
+            mode,
+            int8: Vec::new(),
+            pq: None,
+            pq_subspaces: pq_subspaces.max(1),
+            rerank_candidates,
+            keep_full_precision,
+        });
+        self.requantize();
+        Ok(())
+    }
+
+    fn find_relevant_fragments(&self, query_embedding: Vec<f32>, topk: usize) -> PyResult<Vec<MemoryFragment>> {
+        let start = Instant::now();
+        self.check_embedding(&query_embedding)?;
+        let fragments = self.top_fragments(&query_embedding, topk);
+        self.record_query(QueryClass::Semantic, start, self.semantic_pool(topk));
+        Ok(fragments)
+    }
+
+    fn set_reinforcement(&mut self, weight: f32, half_life: Option<f64>) -> PyResult<()> {
+        if weight < 0.0 || half_life.is_some_and(|h| h <= 0.0) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "weight must be >= 0 and half_life > 0",
+            ));
+        }
+        self.reinforcement = Reinforcement { weight, half_life };
+        Ok(())
+    }
+
+    fn get_access_stats(&self, fragment_id: Option<&str>) -> PyResult<PyObject> {
+        Python::with_gil(|py| {
+            let usage_dict = |position: usize| -> PyResult<&PyDict> {
+                let usage = &self.usage[position];
+                let entry = PyDict::new(py);
+                entry.set_item("hit_count", usage.hit_count)?;
+                entry.set_item("last_access", usage.last_access)?;
+                entry.set_item("relevance", usage.relevance)?;
+                Ok(entry)
+            };
+
+            match fragment_id {
+                Some(id) => {
+                    let position = self.fragments.iter().position(|f| f.id == id).ok_or_else(|| {
+                        PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!("Unknown fragment: {}", id))
+                    })?;
+                    Ok(usage_dict(position)?.into())
+                }
+                None => {
+                    let all = PyDict::new(py);
+                    for (position, fragment) in self.fragments.iter().enumerate() {
+                        all.set_item(&fragment.id, usage_dict(position)?)?;
+                    }
+                    Ok(all.into())
+                }
+            }
+        })
+    }
+
+    fn find_diverse_fragments(
+        &self,
+        query_embedding: Vec<f32>,
+        topk: usize,
+        lambda_: f32,
+        candidates: Option<usize>,
+    ) -> PyResult<Vec<MemoryFragment>> {
+        let start = Instant::now();
+        self.check_embedding(&query_embedding)?;
+        let pool = self.ranked_hits(&query_embedding, candidates.unwrap_or(topk * 4).max(topk));
+        let pool_size = pool.len();
+        let fragments = self
+            .mmr(pool, topk, lambda_)
+            .into_iter()
+            .map(|(i, _)| self.fragment_out(i))
+            .collect();
+        self.record_query(QueryClass::Diverse, start, pool_size);
+        Ok(fragments)
+    }
+
+    fn keyword_search(&self, query: &str, topk: usize) -> Vec<MemoryFragment> {
+        let start = Instant::now();
+        let scores = self.keywords.scores(query);
+        let matched = scores.len();
+        let fragments = top_k(scores, topk).into_iter().map(|(i, _)| self.fragment_out(i)).collect();
+        self.record_query(QueryClass::Keyword, start, matched);
+        fragments
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn hybrid_search(
+        &self,
+        query: &str,
+        query_embedding: Vec<f32>,
+        topk: usize,
+        fusion: &str,
+        alpha: f32,
+        rrf_k: f32,
+        candidates: usize,
+    ) -> PyResult<Vec<MemoryFragment>> {
+        let start = Instant::now();
+        let fusion = HybridFusion::parse(fusion).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown fusion mode: {}", fusion))
+        })?;
+        self.check_embedding(&query_embedding)?;
+
+        let pool = candidates.max(topk);
+        let semantic = self.search_scored(&query_embedding, pool);
+        let keyword_scores = self.keywords.scores(query);
+        let keyword = top_k(keyword_scores.iter().map(|(&i, &score)| (i, score)), pool);
+
+        let mut fused: HashMap<usize, f32> = HashMap::new();
+        match fusion {
+            HybridFusion::Rrf => {
+                for ranking in [&semantic, &keyword] {
+                    for (rank, &(i, _)) in ranking.iter().enumerate() {
+                        *fused.entry(i).or_insert(0.0) += 1.0 / (rrf_k + rank as f32 + 1.0);
+                    }
+                }
+            }
+            HybridFusion::Weighted => {
+                let query_norm = query_embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+                let pooled: HashSet<usize> = semantic.iter().chain(keyword.iter()).map(|&(i, _)| i).collect();
+                let semantic_scores: HashMap<usize, f32> = pooled
+                    .iter()
+                    .map(|&i| {
+                        let similarity =
+                            stored_similarity(&self.fragments, self.quantized.as_ref(), &query_embedding, query_norm, i);
+                        (i, similarity)
+                    })
+                    .collect();
+                let keyword_pool: HashMap<usize, f32> = pooled
+                    .iter()
+                    .map(|&i| (i, keyword_scores.get(&i).copied().unwrap_or(0.0)))
+                    .collect();
+                let semantic_norm = min_max_normalize(&semantic_scores);
+                let keyword_norm = min_max_normalize(&keyword_pool);
+                for &i in &pooled {
+                    fused.insert(i, alpha * semantic_norm[&i] + (1.0 - alpha) * keyword_norm[&i]);
+                }
+            }
+        }
+
+        let pooled = fused.len();
+        let fragments = top_k(fused, topk).into_iter().map(|(i, _)| self.fragment_out(i)).collect();
+        self.record_query(QueryClass::Hybrid, start, pooled);
+        Ok(fragments)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn cluster_fragments(
+        &mut self,
+        num_clusters: usize,
+        batch_size: Option<usize>,
+        tolerance: f64,
+        max_iterations: usize,
+        mode: &str,
+        eps: f32,
+        min_samples: usize,
+        k_min: usize,
+        criterion: &str,
+        cancel: &CancellationToken,
+    ) -> PyResult<ClusterResult> {
+        if self.fragments.len() < 2 {
+            let mut clusters = HashMap::new();
+            clusters.insert(0, self.fragments.clone());
+            let metadata = HashMap::new();
+            return Ok(ClusterResult::new(clusters, metadata));
+        }
+
+        let selection = match mode {
+            "kmeans" => None,
+            "auto" => Some(KSelection::parse(criterion).ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown k selection criterion: {}", criterion))
+            })?),
+            "dbscan" => return self.cluster_dbscan(eps, min_samples, cancel),
+            _ => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Unknown clustering mode: {}",
+                    mode
+                )))
+            }
+        };
+
+        // Extract features (embeddings)
+        let embeddings = self.embeddings();
+        let features: Vec<&[f32]> = embeddings.iter().map(|e| e.as_ref()).collect();
+        
+        let params = KMeansParams {
+            k: num_clusters,
+            max_iterations,
+            tolerance,
+            batch_size,
+        };
+        let (output, curve) = match selection {
+            Some(selection) => select_k(&features, params, k_min, selection, cancel),
+            None => (kmeans(&features, params, cancel), HashMap::new()),
+        };
+        cancel.check().map_err(errors::to_pyerr)?;
+        
+        // Group fragments by cluster
+        let mut clusters: HashMap<i32, Vec<MemoryFragment>> = HashMap::new();
+        for (i, cluster_id) in output.assignments.iter().enumerate() {
+            clusters
+                .entry(*cluster_id)
+                .or_insert_with(Vec::new)
+                .push(self.fragments[i].clone());
+        }
+
+        // Calculate metadata
+        let mut metadata = calculate_cluster_metadata(&clusters);
+        metadata.insert("inertia".to_string(), output.inertia);
+        metadata.insert("iterations".to_string(), output.iterations as f64);
+        metadata.insert("converged".to_string(), if output.converged { 1.0 } else { 0.0 });
+        metadata.extend(curve);
+        
+        self.clusters = clusters.clone();
+        self.centroids = output.centroids;
+        self.reset_drift_baseline();
+        
+        Ok(ClusterResult::new(clusters, metadata))
+    }
+
+    fn assign_to_clusters(
+        &mut self,
+        fragment_ids: Option<Vec<String>>,
+        drift_threshold: f32,
+        auto_recluster: bool,
+    ) -> PyResult<PyObject> {
+        if self.centroids.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                "No clusters yet; run cluster_fragments first",
+            ));
+        }
+
+        let clustered: HashSet<String> = self.clusters.values().flatten().map(|f| f.id.clone()).collect();
+        let targets: Vec<usize> = match &fragment_ids {
+            Some(ids) => {
+                let wanted: HashSet<&str> = ids.iter().map(|id| id.as_str()).collect();
+                (0..self.fragments.len())
+                    .filter(|&i| wanted.contains(self.fragments[i].id.as_str()))
+                    .collect()
+            }
+            None => (0..self.fragments.len()).collect(),
+        };
+
+        let mut assignments: HashMap<String, i32> = HashMap::new();
+        for position in targets {
+            let fragment = &self.fragments[position];
+            if clustered.contains(&fragment.id) {
+                continue;
+            }
+
+            let embedding = stored_embedding(&self.fragments, self.quantized.as_ref(), position);
+            let (cluster, _) = nearest_centroid(&embedding, &self.centroids);
+            let members = self.clusters.entry(cluster as i32).or_default();
+            let count = members.len() as f32;
+            for (c, &x) in self.centroids[cluster].iter_mut().zip(embedding.iter()) {
+                *c += (x - *c) / (count + 1.0);
+            }
+            members.push(fragment.clone());
+            assignments.insert(fragment.id.clone(), cluster as i32);
+        }
+        self.drift.assigned_since_fit += assignments.len();
+
+        let max_drift = self.max_centroid_drift();
+        let needs_recluster = max_drift > drift_threshold;
+        let reclustered = needs_recluster && auto_recluster;
+        if reclustered {
+            let cancel = CancellationToken::new();
+            self.cluster_fragments(self.centroids.len(), None, 1e-4, 100, "kmeans", 0.3, 5, 2, "silhouette", &cancel)?;
+        }
+
+        Python::with_gil(|py| {
+            let result = PyDict::new(py);
+            result.set_item("assigned", assignments.len())?;
+            result.set_item("assignments", assignments)?;
+            result.set_item("max_centroid_drift", max_drift)?;
+            result.set_item("assigned_since_fit", self.drift.assigned_since_fit)?;
+            result.set_item("needs_recluster", needs_recluster)?;
+            result.set_item("reclustered", reclustered)?;
+            Ok(result.into())
+        })
+    }
+
+    fn hierarchical_cluster(&self, linkage: &str, cancel: &CancellationToken) -> PyResult<Dendrogram> {
+        let method = Linkage::parse(linkage).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown linkage: {}", linkage))
+        })?;
+
+        let embeddings = self.embeddings();
+        let features: Vec<&[f32]> = embeddings.iter().map(|e| e.as_ref()).collect();
+        let merges = if method == Linkage::Ward {
+            agglomerative(features.len(), method, cancel, |i, j| squared_distance(features[i], features[j]))
+        } else {
+            agglomerative(features.len(), method, cancel, |i, j| 1.0 - cosine_similarity(features[i], features[j]))
+        };
+        cancel.check().map_err(errors::to_pyerr)?;
+
+        Ok(Dendrogram {
+            fragment_ids: self.fragments.iter().map(|f| f.id.clone()).collect(),
+            linkage: linkage.to_string(),
+            merges,
+        })
+    }
+
+    /// Search phase of process_query(ies); only needs shared access
+    fn search_queries(
+        &self,
+        query_embeddings: &[Vec<f32>],
+        topk: usize,
+        mmr_lambda: Option<f32>,
+    ) -> PyResult<Vec<Vec<(usize, f32)>>> {
+        for embedding in query_embeddings {
+            self.check_embedding(embedding)?;
+        }
+        Ok(aios_threads::install(|| {
+            query_embeddings
+                .par_iter()
+                .map(|embedding| self.retrieve(embedding, topk, mmr_lambda))
+                .collect()
+        }))
+    }
+
+    /// Current positions of reranked `(id, score)` hits; ids no longer
+    /// stored are dropped
+    fn locate_hits(&self, picks: Vec<Vec<(String, f32)>>) -> Vec<Vec<(usize, f32)>> {
+        let positions: HashMap<&str, usize> =
+            self.fragments.iter().enumerate().map(|(i, f)| (f.id.as_str(), i)).collect();
+        picks
+            .into_iter()
+            .map(|hits| {
+                hits.into_iter()
+                    .filter_map(|(id, score)| positions.get(id.as_str()).map(|&i| (i, score)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Record phase of process_query(ies): count queries and hits, build result dicts
+    fn finish_queries(&mut self, queries: Vec<String>, all_hits: Vec<Vec<(usize, f32)>>) -> PyResult<Vec<PyObject>> {
+        Python::with_gil(|py| {
+            let mut results = Vec::with_capacity(queries.len());
+            for (query, hits) in queries.into_iter().zip(all_hits) {
+                self.total_queries += 1;
+                self.record_hits(&hits);
+                let relevant_fragments: Vec<MemoryFragment> = hits.iter().map(|&(i, _)| self.fragment_out(i)).collect();
+
+                // Create response dictionary
+                let result = PyDict::new(py);
+                result.set_item("query", query)?;
+                result.set_item("total_queries", self.total_queries)?;
+                result.set_item("fragments_found", relevant_fragments.len())?;
+                // Convert fragments to Python objects
+                let fragment_list = PyList::empty(py);
+                for fragment in relevant_fragments {
+                    fragment_list.append(Py::new(py, fragment)?)?;
+                }
+                result.set_item("fragments", fragment_list)?;
+                results.push(result.into());
+            }
+            Ok(results)
+        })
+    }
+
+    fn export_fragments(&self, path: &str, format: ExportFormat) -> PyResult<usize> {
+        let fragments = self.get_all_fragments();
+        write_fragment_file(path, format, &fragments)
+            .map_err(|e| errors::io(format!("Failed to export fragments: {}", e)))?;
+        Ok(fragments.len())
+    }
+
+    /// Add an exported fragment unless its id is already stored
+    fn import_fragment(&mut self, fragment: MemoryFragment) -> PyResult<bool> {
+        if self.fragments.iter().any(|f| f.id == fragment.id) {
+            return Ok(false);
+        }
+        let dimension = *self.embedding_policy.dimension.get_or_insert(fragment.embedding.len());
+        if fragment.embedding.len() != dimension {
+            return Err(dimension_error(dimension, fragment.embedding.len()));
+        }
+        self.push_fragment(fragment);
+        Ok(true)
+    }
+
+    fn get_stats(&self) -> PyResult<PyObject> {
+        Python::with_gil(|py| {
+            let stats = PyDict::new(py);
+            stats.set_item("total_fragments", self.fragments.len())?;
+            stats.set_item("total_queries", self.total_queries)?;
+            stats.set_item("num_clusters", self.clusters.len())?;
+            stats.set_item("index_size", self.index.len())?;
+            stats.set_item("index_ef_search", self.index.ef_search())?;
+            stats.set_item("index_tombstones", self.index.tombstones())?;
+            stats.set_item("exact_threshold", self.exact_threshold)?;
+            stats.set_item("max_fragments", self.max_fragments)?;
+            stats.set_item("eviction_policy", self.eviction_policy.name())?;
+            stats.set_item("total_evicted", self.eviction_stats.total_evicted)?;
+            stats.set_item("eviction_runs", self.eviction_stats.eviction_runs)?;
+            stats.set_item("last_eviction", self.eviction_stats.last_eviction)?;
+            stats.set_item("dedup_mode", self.dedup.mode.name())?;
+            stats.set_item("duplicates_rejected", self.dedup.rejected)?;
+            stats.set_item("duplicates_merged", self.dedup.merged)?;
+            stats.set_item("quantization_mode", self.quantized.as_ref().map_or("none", |q| q.mode.name()))?;
+            stats.set_item("embedding_bytes", self.embedding_bytes())?;
+            stats.set_item("keyword_vocabulary", self.keywords.vocabulary_size())?;
+            stats.set_item("total_hits", self.usage.iter().map(|u| u.hit_count).sum::<u64>())?;
+            stats.set_item("fragments_never_hit", self.usage.iter().filter(|u| u.hit_count == 0).count())?;
+            stats.set_item("reinforcement_weight", self.reinforcement.weight)?;
+            stats.set_item("embedding_dimension", self.embedding_policy.dimension)?;
+            stats.set_item("normalize_embeddings", self.embedding_policy.normalize)?;
+            let cache = self.query_cache.lock();
+            let lookups = cache.hits + cache.misses;
+            stats.set_item("cache_size", cache.entries.len())?;
+            stats.set_item("cache_hits", cache.hits)?;
+            stats.set_item("cache_misses", cache.misses)?;
+            stats.set_item("cache_hit_rate", if lookups > 0 { cache.hits as f64 / lookups as f64 } else { 0.0 })?;
+            drop(cache);
+            let analytics = self.analytics.lock();
+            let percentiles = analytics.latency_percentiles(&[50.0, 95.0, 99.0]);
+            stats.set_item("analytics_window", analytics.window())?;
+            stats.set_item("window_queries", analytics.len())?;
+            stats.set_item("latency_p50_ms", percentiles[0] * 1000.0)?;
+            stats.set_item("latency_p95_ms", percentiles[1] * 1000.0)?;
+            stats.set_item("latency_p99_ms", percentiles[2] * 1000.0)?;
+            stats.set_item("avg_candidates", analytics.mean_candidates())?;
+            let by_class = PyDict::new(py);
+            for (class, count) in analytics.class_counts() {
+                by_class.set_item(class, count)?;
+            }
+            stats.set_item("queries_by_class", by_class)?;
+            Ok(stats.into())
+        })
+    }
+
+    /// Header and fragments in the `save` format, embeddings decoded
+    fn snapshot(&self) -> (SnapshotHeader, Cow<'_, [MemoryFragment]>) {
+        let fragments: Cow<[MemoryFragment]> = match &self.quantized {
+            Some(store) if !store.keep_full_precision => {
+                Cow::Owned((0..self.fragments.len()).map(|i| self.fragment_out(i)).collect())
+            }
+            _ => Cow::Borrowed(&self.fragments),
+        };
+        let header = SnapshotHeader {
+            version: FORMAT_VERSION,
+            fragments: fragments
+                .iter()
+                .map(|f| FragmentRecord {
+                    id: f.id.clone(),
+                    content: f.content.clone(),
+                    timestamp: f.timestamp,
+                    metadata: f.metadata.clone(),
+                    dim: f.embedding.len() as u32,
+                })
+                .collect(),
+            clusters: self
+                .clusters
+                .iter()
+                .map(|(cluster, members)| (*cluster, members.iter().map(|f| f.id.clone()).collect()))
+                .collect(),
+            centroids: self.centroids.clone(),
+            total_queries: self.total_queries,
+        };
+        (header, fragments)
+    }
+
+    fn save(&self, path: &str) -> PyResult<()> {
+        let (header, fragments) = self.snapshot();
+        write_snapshot(Path::new(path), &header, &fragments)
+            .map_err(|e| errors::io(format!("Failed to save CARMA state: {}", e)))
+    }
+
+    /// `save` file contents, for state archives
+    fn export_state(&self) -> PyResult<Vec<u8>> {
+        let (header, fragments) = self.snapshot();
+        encode_snapshot(&header, &fragments).map_err(|e| errors::io(format!("Failed to encode CARMA state: {}", e)))
+    }
+
+    fn load(&mut self, path: &str) -> PyResult<()> {
+        let (header, fragments) = read_snapshot(Path::new(path))
+            .map_err(|e| errors::io(format!("Failed to load CARMA state: {}", e)))?;
+        self.restore(header, fragments);
+        Ok(())
+    }
+
+    fn import_state(&mut self, data: &[u8]) -> PyResult<()> {
+        let (header, fragments) =
+            decode_snapshot(data).map_err(|e| errors::validation(format!("Invalid CARMA state: {}", e)))?;
+        self.restore(header, fragments);
+        Ok(())
+    }
+
+    /// Replace the current state with a decoded snapshot
+    fn restore(&mut self, header: SnapshotHeader, fragments: Vec<MemoryFragment>) {
+        let by_id: HashMap<&str, &MemoryFragment> = fragments.iter().map(|f| (f.id.as_str(), f)).collect();
+        self.clusters = header
+            .clusters
+            .iter()
+            .map(|(cluster, ids)| {
+                let members = ids.iter().filter_map(|id| by_id.get(id.as_str()).map(|f| (*f).clone())).collect();
+                (*cluster, members)
+            })
+            .collect();
+        self.centroids = header.centroids;
+        self.total_queries = header.total_queries;
+        self.usage = fragments
+            .iter()
+            .map(|f| FragmentUsage {
+                last_access: f.timestamp,
+                relevance: 0.0,
+                hit_count: 0,
+            })
+            .collect();
+        self.fragments = fragments;
+        self.requantize();
+        self.rebuild_index();
+        self.rebuild_keywords();
+        self.reset_drift_baseline();
+    }
+
+    fn get_centroids(&self) -> Vec<Vec<f32>> {
+        self.centroids.clone()
+    }
+
+    fn embedding_matrix(&self) -> (Vec<String>, Vec<Vec<f32>>) {
+        let ids = self.fragments.iter().map(|f| f.id.clone()).collect();
+        let rows = self.embeddings().into_iter().map(Cow::into_owned).collect();
+        (ids, rows)
+    }
+
+    fn get_all_fragments(&self) -> Vec<MemoryFragment> {
+        (0..self.fragments.len()).map(|i| self.fragment_out(i)).collect()
+    }
+
+    fn fragment_window(&self, offset: usize, limit: usize) -> Vec<MemoryFragment> {
+        let end = offset.saturating_add(limit).min(self.fragments.len());
+        (offset.min(end)..end).map(|i| self.fragment_out(i)).collect()
+    }
+
+    fn clear_all(&mut self) {
+        self.fragments.clear();
+        self.clusters.clear();
+        self.centroids.clear();
+        self.total_queries = 0;
+        self.index.clear();
+        self.keywords.clear();
+        self.usage.clear();
+        self.eviction_stats = EvictionStats::default();
+        self.drift = DriftBaseline::default();
+        self.query_cache.get_mut().entries.clear();
+        self.analytics.get_mut().clear();
+        if let Some(store) = &mut self.quantized {
+            store.int8.clear();
+            store.pq = None;
+        }
+    }
+}
+
+impl CarmaStore {
+    /// Reject embeddings with the wrong dimension or non-finite values
+    fn check_embedding(&self, embedding: &[f32]) -> PyResult<()> {
+        validate_embedding(embedding, self.embedding_policy.dimension)
+    }
+
+    /// Validate an embedding against `dimension` and normalize it if configured
+    fn prepare_embedding(&self, embedding: Vec<f32>, dimension: Option<usize>) -> PyResult<Vec<f32>> {
+        validate_embedding(&embedding, dimension)?;
+        Ok(if self.embedding_policy.normalize {
+            l2_normalize(embedding)
+        } else {
+            embedding
+        })
+    }
+
+    /// Top-k fragments by cosine similarity (HNSW above the exact threshold)
+    fn top_fragments(&self, query_embedding: &[f32], topk: usize) -> Vec<MemoryFragment> {
+        self.retrieve(query_embedding, topk, None)
+            .into_iter()
+            .map(|(i, _)| self.fragment_out(i))
+            .collect()
+    }
+
+    /// Clone of the fragment at `position` with its embedding filled in
+    fn fragment_out(&self, position: usize) -> MemoryFragment {
+        let mut fragment = self.fragments[position].clone();
+        if let Cow::Owned(decoded) = stored_embedding(&self.fragments, self.quantized.as_ref(), position) {
+            fragment.embedding = decoded;
+        }
+        fragment
+    }
+
+    /// Embeddings of every fragment, decoded where only codes are stored
+    fn embeddings(&self) -> Vec<Cow<'_, [f32]>> {
+        (0..self.fragments.len())
+            .map(|i| stored_embedding(&self.fragments, self.quantized.as_ref(), i))
+            .collect()
+    }
+
+    /// Ranked hits, diversified with MMR when `mmr_lambda` is given
+    fn retrieve(&self, query_embedding: &[f32], topk: usize, mmr_lambda: Option<f32>) -> Vec<(usize, f32)> {
+        // Reinforcement reorders results as hits accumulate, so skip the cache
+        let cacheable = self.reinforcement.weight <= 0.0;
+        let key = QueryCache::key(query_embedding, topk, mmr_lambda);
+        if cacheable {
+            if let Some(hits) = self.query_cache.lock().get(key, query_embedding, topk, mmr_lambda) {
+                return hits;
+            }
+        }
+
+        let hits = match mmr_lambda {
+            Some(lambda) => self.mmr(self.ranked_hits(query_embedding, topk * 4), topk, lambda),
+            None => self.ranked_hits(query_embedding, topk),
+        };
+        if cacheable {
+            self.query_cache.lock().insert(key, query_embedding, topk, mmr_lambda, &hits);
+        }
+        hits
+    }
+
+    /// Greedy maximal-marginal-relevance selection of `topk` from ranked candidates
+    fn mmr(&self, candidates: Vec<(usize, f32)>, topk: usize, lambda: f32) -> Vec<(usize, f32)> {
+        let embeddings: Vec<Cow<[f32]>> = candidates
+            .iter()
+            .map(|&(i, _)| stored_embedding(&self.fragments, self.quantized.as_ref(), i))
+            .collect();
+        // Highest similarity of each candidate to anything picked so far
+        let mut redundancy = vec![f32::NEG_INFINITY; candidates.len()];
+        let mut picked = vec![false; candidates.len()];
+        let mut selected = Vec::with_capacity(topk.min(candidates.len()));
+
+        while selected.len() < topk {
+            let best = (0..candidates.len())
+                .filter(|&c| !picked[c])
+                .map(|c| {
+                    let penalty = if selected.is_empty() { 0.0 } else { redundancy[c] };
+                    (c, lambda * candidates[c].1 - (1.0 - lambda) * penalty)
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            let Some((choice, _)) = best else { break };
+
+            picked[choice] = true;
+            selected.push(candidates[choice]);
+            for c in 0..candidates.len() {
+                if !picked[c] {
+                    redundancy[c] = redundancy[c].max(cosine_similarity(&embeddings[c], &embeddings[choice]));
+                }
+            }
+        }
+        selected
+    }
+
+    /// Top-k (position, cosine similarity) pairs ordered with the reinforcement bonus
+    fn ranked_hits(&self, query_embedding: &[f32], topk: usize) -> Vec<(usize, f32)> {
+        if self.reinforcement.weight <= 0.0 {
+            return self.search_scored(query_embedding, topk);
+        }
+
+        let now = now_secs();
+        let mut boosted: Vec<(usize, f32, f32)> = self
+            .search_scored(query_embedding, self.semantic_pool(topk))
+            .into_iter()
+            .map(|(i, similarity)| (i, similarity, similarity + self.reinforcement_bonus(i, now)))
+            .collect();
+        boosted.sort_by(|a, b| b.2.total_cmp(&a.2));
+        boosted.into_iter().take(topk).map(|(i, similarity, _)| (i, similarity)).collect()
+    }
+
+    /// Candidates `ranked_hits` considers for `topk` results
+    fn semantic_pool(&self, topk: usize) -> usize {
+        let pool = if self.reinforcement.weight > 0.0 { topk.saturating_mul(4).max(topk + 10) } else { topk };
+        pool.min(self.fragments.len())
+    }
+
+    fn record_query(&self, class: QueryClass, start: Instant, candidates: usize) {
+        record_latency(&mut self.analytics.lock(), class, start.elapsed().as_secs_f64(), candidates);
+    }
+
+    fn reinforcement_bonus(&self, position: usize, now: f64) -> f32 {
+        let usage = &self.usage[position];
+        let decay = self
+            .reinforcement
+            .half_life
+            .map_or(1.0, |half_life| 0.5f64.powf((now - usage.last_access).max(0.0) / half_life));
+        self.reinforcement.weight * (usage.hit_count as f32).ln_1p() * decay as f32
+    }
+
+    /// Top-k (position, cosine similarity) pairs, best first
+    fn search_scored(&self, query_embedding: &[f32], topk: usize) -> Vec<(usize, f32)> {
+        if self.fragments.is_empty() || topk == 0 {
+            return Vec::new();
+        }
+
+        let query_norm = query_embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let quantized = self.quantized.as_ref();
+        if self.fragments.len() > self.exact_threshold && self.index.covers(self.fragments.len()) {
+            return self.index.search(&self.fragments, quantized, query_embedding, query_norm, topk);
+        }
+
+        if let Some(store) = quantized {
+            return self.quantized_scan(store, query_embedding, query_norm, topk);
+        }
+
+        let similarities = self
+            .fragments
+            .iter()
+            .enumerate()
+            .map(|(i, fragment)| (i, cosine_similarity(query_embedding, &fragment.embedding)));
+        top_k(similarities, topk)
+    }
+
+    /// Brute-force scan over compressed codes, then re-rank the best candidates
+    fn quantized_scan(&self, store: &QuantizedStore, query: &[f32], query_norm: f32, topk: usize) -> Vec<(usize, f32)> {
+        let table = store.pq.as_ref().and_then(|(pq, _)| pq.query_table(query));
+        let scores = (0..self.fragments.len()).map(|i| {
+            let pq_code = store.pq.as_ref().and_then(|(_, codes)| codes.get(i)).and_then(|c| c.as_ref());
+            let score = match (&table, pq_code) {
+                (Some(table), Some(code)) => ProductQuantizer::cosine(table, code, query_norm),
+                _ => store.int8[i].cosine(query, query_norm),
+            };
+            (i, score)
+        });
+        let candidates = top_k(scores, store.rerank_candidates.max(topk));
+
+        let reranked = candidates
+            .into_iter()
+            .map(|(i, _)| (i, stored_similarity(&self.fragments, Some(store), query, query_norm, i)));
+        top_k(reranked, topk)
+    }
+
+    /// Re-encode every fragment with the current quantization settings,
+    /// dropping full-precision embeddings unless they are kept
+    fn requantize(&mut self) {
+        self.query_cache.get_mut().entries.clear();
+        let store = match &mut self.quantized {
+            Some(store) => store,
+            None => return,
+        };
+
+        store.int8 = self.fragments.iter().map(|f| Int8Code::encode(&f.embedding)).collect();
+        store.pq = None;
+        if store.mode == QuantizationMode::Product {
+            let vectors: Vec<&[f32]> = self.fragments.iter().map(|f| f.embedding.as_slice()).collect();
+            if let Some(pq) = ProductQuantizer::train(&vectors, store.pq_subspaces) {
+                let codes = vectors.iter().map(|v| pq.encode(v)).collect();
+                store.pq = Some((pq, codes));
+            }
+        }
+
+        if !store.keep_full_precision {
+            for fragment in self.fragments.iter_mut() {
+                fragment.embedding = Vec::new();
+            }
+        }
+    }
+
+    /// Put decoded embeddings back on fragments that only have codes
+    fn restore_embeddings(&mut self) {
+        if let Some(store) = &self.quantized {
+            for (fragment, code) in self.fragments.iter_mut().zip(store.int8.iter()) {
+                if fragment.embedding.is_empty() {
+                    fragment.embedding = code.decode();
+                }
+            }
+        }
+    }
+
+    /// Bytes held by stored embeddings and their codes
+    fn embedding_bytes(&self) -> usize {
+        let full: usize = self.fragments.iter().map(|f| f.embedding.len() * std::mem::size_of::<f32>()).sum();
+        let codes: usize = self.quantized.as_ref().map_or(0, |store| {
+            let int8: usize = store.int8.iter().map(|c| c.memory_bytes()).sum();
+            let pq: usize = store
+                .pq
+                .as_ref()
+                .map_or(0, |(_, codes)| codes.iter().flatten().map(|c| c.memory_bytes()).sum());
+            int8 + pq
+        });
+        full + codes
+    }
+
+    /// Record retrieval hits for LRU / relevance bookkeeping
+    fn record_hits(&mut self, hits: &[(usize, f32)]) {
+        let now = now_secs();
+        for &(position, similarity) in hits {
+            let usage = &mut self.usage[position];
+            usage.last_access = now;
+            usage.relevance += similarity as f64;
+            usage.hit_count += 1;
+        }
+    }
+
+    /// Position of an existing fragment that the new content/embedding duplicates
+    fn find_duplicate(&self, content: &str, embedding: &[f32]) -> Option<usize> {
+        self.search_scored(embedding, 5)
+            .into_iter()
+            .filter(|&(_, similarity)| similarity >= self.dedup.similarity_threshold)
+            .find(|&(i, _)| content_overlap(content, &self.fragments[i].content) >= self.dedup.overlap_threshold)
+            .map(|(i, _)| i)
+    }
+
+    /// Apply the dedup mode to a detected duplicate and return the existing id
+    fn absorb_duplicate(&mut self, position: usize, content: String) -> String {
+        match self.dedup.mode {
+            DedupMode::Merge => {
+                self.dedup.merged += 1;
+                if content.len() > self.fragments[position].content.len() {
+                    let old = std::mem::replace(&mut self.fragments[position].content, content);
+                    self.keywords.replace(position, &old, &self.fragments[position].content);
+                }
+                let fragment = &mut self.fragments[position];
+                let count = fragment
+                    .metadata
+                    .get("duplicate_count")
+                    .and_then(|c| c.parse::<u64>().ok())
+                    .unwrap_or(0);
+                fragment.metadata.insert("duplicate_count".to_string(), (count + 1).to_string());
+                self.usage[position].last_access = now_secs();
+            }
+            _ => self.dedup.rejected += 1,
+        }
+        self.fragments[position].id.clone()
+    }
+
+    /// Append a fragment with fresh usage stats, evicting first if at capacity
+    fn push_fragment(&mut self, fragment: MemoryFragment) {
+        if let Some(max) = self.max_fragments {
+            if self.fragments.len() >= max {
+                self.evict(self.fragments.len() + 1 - max + max / 20);
+            }
+        }
+
+        let mut fragment = fragment;
+        if let Some(store) = &mut self.quantized {
+            store.int8.push(Int8Code::encode(&fragment.embedding));
+            if let Some((pq, codes)) = &mut store.pq {
+                codes.push(pq.encode(&fragment.embedding));
+            }
+            if !store.keep_full_precision {
+                fragment.embedding = Vec::new();
+            }
+        }
+
+        self.usage.push(FragmentUsage {
+            last_access: fragment.timestamp,
+            relevance: 0.0,
+            hit_count: 0,
+        });
+        self.keywords.add(&fragment.content);
+        self.fragments.push(fragment);
+        self.index_fragment(self.fragments.len() - 1);
+        self.query_cache.get_mut().entries.clear();
+    }
+
+    /// Evict `count` fragments according to the eviction policy
+    fn evict(&mut self, count: usize) {
+        let count = count.min(self.fragments.len());
+        if count == 0 {
+            return;
+        }
+
+        let mut order: Vec<usize> = (0..self.fragments.len()).collect();
+        match self.eviction_policy {
+            EvictionPolicy::Lru => order.sort_by(|&a, &b| self.usage[a].last_access.total_cmp(&self.usage[b].last_access)),
+            EvictionPolicy::LowestRelevance => order.sort_by(|&a, &b| self.usage[a].relevance.total_cmp(&self.usage[b].relevance)),
+            EvictionPolicy::Oldest => order.sort_by(|&a, &b| self.fragments[a].timestamp.total_cmp(&self.fragments[b].timestamp)),
+        }
+
+        let victims: HashSet<usize> = order.into_iter().take(count).collect();
+        self.remove_positions(&victims);
+        self.eviction_stats.total_evicted += count as u64;
+        self.eviction_stats.eviction_runs += 1;
+        self.eviction_stats.last_eviction = now_secs();
+    }
+
+    /// Remove fragments at the given positions, keeping usage, clusters and
+    /// index in sync; the HNSW graph keeps them as tombstones until too many
+    /// have piled up, then is rebuilt
+    fn remove_positions(&mut self, positions: &HashSet<usize>) {
+        let removed_ids: HashSet<String> =
+            positions.iter().map(|&i| self.fragments[i].id.clone()).collect();
+        let retired = self.index.retire(&self.fragments, self.quantized.as_ref(), positions);
+
+        retain_positions(&mut self.fragments, positions);
+        retain_positions(&mut self.usage, positions);
+        if let Some(store) = &mut self.quantized {
+            retain_positions(&mut store.int8, positions);
+            if let Some((_, codes)) = &mut store.pq {
+                retain_positions(codes, positions);
+            }
+        }
+
+        for members in self.clusters.values_mut() {
+            members.retain(|f| !removed_ids.contains(&f.id));
+        }
+        self.clusters.retain(|_, members| !members.is_empty());
+        if retired {
+            self.query_cache.get_mut().entries.clear();
+        } else {
+            self.rebuild_index();
+        }
+        self.rebuild_keywords();
+    }
+
+    /// DBSCAN clustering with cosine distance
+    fn cluster_dbscan(&mut self, eps: f32, min_samples: usize, cancel: &CancellationToken) -> PyResult<ClusterResult> {
+        let embeddings = self.embeddings();
+        let features: Vec<&[f32]> = embeddings.iter().map(|e| e.as_ref()).collect();
+        let labels = dbscan(features.len(), eps, min_samples.max(1), cancel, |i, j| {
+            1.0 - cosine_similarity(features[i], features[j])
+        });
+        cancel.check().map_err(errors::to_pyerr)?;
+        let centroids = cluster_means(&features, &labels);
+        drop(embeddings);
+
+        let mut clusters: HashMap<i32, Vec<MemoryFragment>> = HashMap::new();
+        let mut noise = Vec::new();
+        for (i, &label) in labels.iter().enumerate() {
+            if label == NOISE {
+                noise.push(self.fragments[i].clone());
+            } else {
+                clusters.entry(label).or_default().push(self.fragments[i].clone());
+            }
+        }
+
+        let mut metadata = calculate_cluster_metadata(&clusters);
+        metadata.insert("noise_points".to_string(), noise.len() as f64);
+        metadata.insert("eps".to_string(), eps as f64);
+        metadata.insert("min_samples".to_string(), min_samples as f64);
+
+        self.clusters = clusters.clone();
+        self.centroids = centroids;
+        self.reset_drift_baseline();
+
+        let mut result = ClusterResult::new(clusters, metadata);
+        result.noise = noise;
+        Ok(result)
+    }
+
+    /// Snapshot the current centroids as the drift reference
+    fn reset_drift_baseline(&mut self) {
+        let positions: HashMap<&str, usize> =
+            self.fragments.iter().enumerate().map(|(i, f)| (f.id.as_str(), i)).collect();
+        let mut total = 0.0f32;
+        let mut count = 0usize;
+        for (cluster, members) in &self.clusters {
+            if let Some(centroid) = self.centroids.get(*cluster as usize) {
+                for position in members.iter().filter_map(|f| positions.get(f.id.as_str())) {
+                    let embedding = stored_embedding(&self.fragments, self.quantized.as_ref(), *position);
+                    total += squared_distance(&embedding, centroid).sqrt();
+                    count += 1;
+                }
+            }
+        }
+
+        self.drift = DriftBaseline {
+            centroids: self.centroids.clone(),
+            scale: if count > 0 { total / count as f32 } else { 0.0 },
+            assigned_since_fit: 0,
+        };
+    }
+
+    /// Largest centroid movement since the baseline, relative to its scale
+    fn max_centroid_drift(&self) -> f32 {
+        let scale = self.drift.scale.max(f32::EPSILON);
+        self.centroids
+            .iter()
+            .zip(self.drift.centroids.iter())
+            .map(|(now, then)| squared_distance(now, then).sqrt() / scale)
+            .fold(0.0, f32::max)
+    }
+
+    /// Rebuild the HNSW index from the current fragment list
+    fn rebuild_index(&mut self) {
+        self.query_cache.get_mut().entries.clear();
+        self.index.clear();
+        for position in 0..self.fragments.len() {
+            self.index_fragment(position);
+        }
+    }
+
+    /// Rebuild the keyword index from the current fragment contents
+    fn rebuild_keywords(&mut self) {
+        self.keywords.rebuild(self.fragments.iter().map(|f| f.content.as_str()));
+    }
+
+    /// Insert the fragment at `position` into the HNSW index
+    fn index_fragment(&mut self, position: usize) {
+        self.index.insert(&self.fragments, self.quantized.as_ref(), position);
+    }
+}
+
+/// Current UNIX time in seconds
+fn now_secs() -> f64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64()
+}
+
+/// Run the `pre_retrieval` plugins on `query`: the text to search for, or
+/// None when a plugin rejected it
+fn filter_query(query: &str) -> Option<String> {
+    if !aios_plugins::active(Hook::PreRetrieval) {
+        return Some(query.to_string());
+    }
+    match aios_plugins::run(Hook::PreRetrieval, serde_json::Value::from(query), serde_json::Value::is_string) {
+        Ok(serde_json::Value::String(filtered)) => Some(filtered),
+        Ok(_) => Some(query.to_string()),
+        Err(_) => None,
+    }
+}
+
+/// Add a query to the analytics, first reporting it if it is a latency spike
+fn record_latency(analytics: &mut RetrievalAnalytics, class: QueryClass, latency: f64, candidates: usize) {
+    if aios_events::enabled() {
+        if let Some(median) = analytics.spike_baseline(latency) {
+            aios_events::event!(
+                "retrieval.latency_spike",
+                query_class = class.name(),
+                latency_ms = latency * 1000.0,
+                median_ms = median * 1000.0,
+                candidates = candidates,
+            );
+        }
+    }
+    analytics.record(class, latency, candidates);
+}
+
+/// Indices of each query's candidates ordered by the scores returned from
+/// `reranker(query, fragments)`, best `topk` first
+fn rerank(
+    reranker: &PyObject,
+    queries: &[String],
+    all_candidates: &[Vec<MemoryFragment>],
+    topk: usize,
+) -> PyResult<Vec<Vec<usize>>> {
+    Python::with_gil(|py| {
+        queries
+            .iter()
+            .zip(all_candidates)
+            .map(|(query, candidates)| {
+                let scores: Vec<f32> = reranker.call1(py, (query.as_str(), candidates.to_vec()))?.extract(py)?;
+                if scores.len() != candidates.len() {
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "Reranker returned {} scores for {} candidates",
+                        scores.len(),
+                        candidates.len()
+                    )));
+                }
+                // Rank by candidate order so ties keep the retrieval order
+                Ok(top_k(scores.into_iter().enumerate(), topk).into_iter().map(|(c, _)| c).collect())
+            })
+            .collect()
+    })
+}
+
+/// Embedding at `position`, decoded from its int8 code if only that is stored
+fn stored_embedding<'a>(
+    fragments: &'a [MemoryFragment],
+    quantized: Option<&QuantizedStore>,
+    position: usize,
+) -> Cow<'a, [f32]> {
+    let embedding = &fragments[position].embedding;
+    match quantized {
+        Some(store) if embedding.is_empty() => Cow::Owned(store.int8[position].decode()),
+        _ => Cow::Borrowed(embedding),
+    }
+}
+
+/// Cosine similarity of a query to the fragment at `position`, scored against
+/// its int8 code if the full-precision embedding was dropped
+fn stored_similarity(
+    fragments: &[MemoryFragment],
+    quantized: Option<&QuantizedStore>,
+    query: &[f32],
+    query_norm: f32,
+    position: usize,
+) -> f32 {
+    let embedding = &fragments[position].embedding;
+    match quantized {
+        Some(store) if embedding.is_empty() => store.int8[position].cosine(query, query_norm),
+        _ => cosine_similarity(query, embedding),
+    }
+}
+
+/// Points scored per k when computing silhouettes
+const SILHOUETTE_SAMPLES: usize = 1000;
+
+/// Run k-means over `k_min..=params.k` and keep the run chosen by `selection`.
+///
+/// Returns that run plus `chosen_k`, `inertia_k{k}` and (for silhouette
+/// selection) `silhouette_k{k}` metadata entries. Stops after the current
+/// k once `cancel` is cancelled.
+fn select_k(
+    features: &[&[f32]],
+    params: KMeansParams,
+    k_min: usize,
+    selection: KSelection,
+    cancel: &CancellationToken,
+) -> (KMeansOutput, HashMap<String, f64>) {
+    let k_max = params.k.min(features.len()).max(1);
+    let k_min = k_min.clamp(1, k_max);
+    let mut curve = HashMap::new();
+    let mut runs = Vec::with_capacity(k_max - k_min + 1);
+    let mut silhouettes = Vec::with_capacity(k_max - k_min + 1);
+
+    for k in k_min..=k_max {
+        let output = kmeans(features, KMeansParams { k, ..params }, cancel);
+        curve.insert(format!("inertia_k{}", k), output.inertia);
+        if selection == KSelection::Silhouette {
+            let score = silhouette_score(features, &output.assignments, SILHOUETTE_SAMPLES);
+            curve.insert(format!("silhouette_k{}", k), score);
+            silhouettes.push(score);
+        }
+        runs.push(output);
+        if cancel.is_cancelled() {
+            break;
+        }
+    }
+
+    let best = match selection {
+        KSelection::Elbow => {
+            let ks: Vec<f64> = (k_min..k_min + runs.len()).map(|k| k as f64).collect();
+            let inertias: Vec<f64> = runs.iter().map(|run| run.inertia).collect();
+            elbow_index(&ks, &inertias)
+        }
+        // First k with the highest score
+        KSelection::Silhouette => silhouettes
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1).then(b.0.cmp(&a.0)))
+            .map_or(0, |(i, _)| i),
+    };
+    curve.insert("chosen_k".to_string(), (k_min + best) as f64);
+    (runs.swap_remove(best), curve)
+}
+
+/// Scale scores to [0, 1]; all-equal scores map to 1
+fn min_max_normalize(scores: &HashMap<usize, f32>) -> HashMap<usize, f32> {
+    let min = scores.values().copied().fold(f32::INFINITY, f32::min);
+    let max = scores.values().copied().fold(f32::NEG_INFINITY, f32::max);
+    let range = max - min;
+    scores
+        .iter()
+        .map(|(&i, &score)| (i, if range > 0.0 { (score - min) / range } else { 1.0 }))
+        .collect()
+}
+
+/// Write fragments as JSON Lines or Parquet
+fn write_fragment_file(path: &str, format: ExportFormat, fragments: &[MemoryFragment]) -> io::Result<()> {
+    match format {
+        ExportFormat::Jsonl => {
+            let mut writer = BufWriter::new(fs::File::create(path)?);
+            for fragment in fragments {
+                serde_json::to_writer(&mut writer, fragment)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()
+        }
+        ExportFormat::Parquet => parquet::write_fragments(Path::new(path), fragments),
+    }
+}
+
+/// Read fragments written by `write_fragment_file`
+fn read_fragment_file(path: &str, format: ExportFormat) -> io::Result<Vec<MemoryFragment>> {
+    match format {
+        ExportFormat::Jsonl => {
+            let reader = io::BufReader::new(fs::File::open(path)?);
+            let mut fragments = Vec::new();
+            for line in reader.lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    fragments.push(serde_json::from_str(&line)?);
+                }
+            }
+            Ok(fragments)
+        }
+        ExportFormat::Parquet => parquet::read_fragments(Path::new(path)),
+    }
+}
+
+/// Arrow IPC input for `ingest_arrow`: raw bytes or a file path
+#[derive(FromPyObject)]
+enum ArrowSource<'py> {
+    Bytes(Bound<'py, PyBytes>),
+    Path(std::path::PathBuf),
+}
+
+fn arrow_column_error(name: &str, expected: &str, found: Option<aios_arrow::Column>) -> PyErr {
+    let message = match found {
+        Some(column) => format!("Arrow column '{}' is {}, expected {}", name, column.type_name(), expected),
+        None => format!("Arrow data has no column '{}'", name),
+    };
+    PyErr::new::<pyo3::exceptions::PyValueError, _>(message)
+}
+
+/// Reject empty or non-finite embeddings and, if given, a wrong dimension
+fn validate_embedding(embedding: &[f32], dimension: Option<usize>) -> PyResult<()> {
+    if embedding.is_empty() {
+        return Err(EmbeddingError::new_err("Embedding is empty"));
+    }
+    if let Some(dimension) = dimension {
+        if embedding.len() != dimension {
+            return Err(dimension_error(dimension, embedding.len()));
+        }
+    }
+    if let Some(position) = embedding.iter().position(|x| !x.is_finite()) {
+        return Err(EmbeddingError::new_err(format!(
+            "Embedding has non-finite value {} at index {}",
+            embedding[position], position
+        )));
+    }
+    Ok(())
+}
+
+fn dimension_error(expected: usize, got: usize) -> PyErr {
+    EmbeddingError::new_err(format!("Expected embedding dimension {}, got {}", expected, got))
+}
+
+/// Scale a vector to unit L2 norm (zero vectors are returned unchanged)
+fn l2_normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for x in vector.iter_mut() {
+            *x /= norm;
+        }
+    }
+    vector
+}
+
+/// Drop the items at the given positions, preserving order
+fn retain_positions<T>(items: &mut Vec<T>, positions: &HashSet<usize>) {
+    let mut position = 0;
+    items.retain(|_| {
+        let keep = !positions.contains(&position);
+        position += 1;
+        keep
+    });
+}
+
+/// Jaccard overlap of the lowercase word sets of two texts
+fn content_overlap(a: &str, b: &str) -> f64 {
+    let words_a: HashSet<String> = a.split_whitespace().map(|w| w.to_lowercase()).collect();
+    let words_b: HashSet<String> = b.split_whitespace().map(|w| w.to_lowercase()).collect();
+    if words_a.is_empty() && words_b.is_empty() {
+        return 1.0;
+    }
+
+    let intersection = words_a.intersection(&words_b).count();
+    let union = words_a.len() + words_b.len() - intersection;
+    intersection as f64 / union as f64
+}
+
+/// Calculate cosine similarity between two vectors
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+    let magnitude_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let magnitude_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if magnitude_a == 0.0 || magnitude_b == 0.0 {
+        0.0
+    } else {
+        dot_product / (magnitude_a * magnitude_b)
+    }
+}
+
+/// Calculate cluster metadata
 fn calculate_cluster_metadata(clusters: &HashMap<i32, Vec<MemoryFragment>>) -> HashMap<String, f64> {
     let mut metadata = HashMap::new();
     
@@ -311,11 +2790,119 @@ fn calculate_cluster_metadata(clusters: &HashMap<i32, Vec<MemoryFragment>>) -> H
     metadata
 }
 
+/// Split a raw document into MemoryFragments of about `target_tokens` words.
+///
+/// Chunks keep whole sentences where possible and repeat up to
+/// `overlap_tokens` trailing words of context. Embeddings are left empty to be
+/// filled in before ingestion. Metadata records `chunk_index`, `token_count`,
+/// the byte range `byte_start`/`byte_end` and `source_id` when given.
+#[pyfunction]
+#[pyo3(signature = (text, options=None, source_id=None))]
+fn split_document(py: Python<'_>, text: &str, options: Option<ChunkOptions>, source_id: Option<String>) -> PyResult<Vec<MemoryFragment>> {
+    let _span = aios_trace::span!("split_document", text_len = text.len(), source_id = source_id);
+    let options = options.unwrap_or_else(|| ChunkOptions::new(200, 20, true));
+    if options.target_tokens == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("target_tokens must be positive"));
+    }
+    if options.overlap_tokens >= options.target_tokens {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "overlap_tokens ({}) must be smaller than target_tokens ({})",
+            options.overlap_tokens, options.target_tokens
+        )));
+    }
+
+    let params = ChunkParams {
+        target_tokens: options.target_tokens,
+        overlap_tokens: options.overlap_tokens,
+        respect_sentences: options.respect_sentences,
+    };
+    let chunks = py.allow_threads(|| chunking::split_document(text, params));
+    Ok(chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut fragment = MemoryFragment::new(
+                Uuid::new_v4().to_string(),
+                text[chunk.start..chunk.end].to_string(),
+                Vec::new(),
+            );
+            fragment.metadata.insert("chunk_index".to_string(), index.to_string());
+            fragment.metadata.insert("token_count".to_string(), chunk.tokens.to_string());
+            fragment.metadata.insert("byte_start".to_string(), chunk.start.to_string());
+            fragment.metadata.insert("byte_end".to_string(), chunk.end.to_string());
+            if let Some(source_id) = &source_id {
+                fragment.metadata.insert("source_id".to_string(), source_id.clone());
+            }
+            fragment
+        })
+        .collect())
+}
+
 /// Python module definition
 #[pymodule]
-fn aios_carma_rust(_py: Python, m: &PyModule) -> PyResult<()> {
+fn aios_carma_rust(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<MemoryFragment>()?;
     m.add_class::<ClusterResult>()?;
+    m.add_class::<Dendrogram>()?;
+    m.add_class::<ChunkOptions>()?;
     m.add_class::<RustCarmaCore>()?;
+    m.add_class::<FragmentIterator>()?;
+    m.add("EmbeddingError", py.get_type::<EmbeddingError>())?;
+    errors::register(py, m)?;
+    m.add_function(wrap_pyfunction!(split_document, m)?)?;
+    tracing::register(m)?;
+    cancel::register(m)?;
+    bus::register(m)?;
+    state::register(m)?;
+    threads::register(m)?;
+    events::register(m)?;
+    plugins::register(m)?;
+    rng::register(m)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_cache_tolerance() {
+        let query = vec![1.0, 0.0, 0.0];
+        let near = vec![1.0, 0.01, 0.0];
+        let far = vec![1.0, 0.5, 0.0];
+        let hits = vec![(3, 0.9), (1, 0.5)];
+
+        let mut exact = QueryCache::new(4, 0.0);
+        exact.insert(QueryCache::key(&query, 2, None), &query, 2, None, &hits);
+        assert_eq!(exact.get(QueryCache::key(&query, 2, None), &query, 2, None), Some(hits.clone()));
+        assert_eq!(exact.get(QueryCache::key(&near, 2, None), &near, 2, None), None);
+
+        // cos(query, near) is about 0.99995, cos(query, far) about 0.894
+        let mut tolerant = QueryCache::new(4, 0.001);
+        tolerant.insert(QueryCache::key(&query, 2, None), &query, 2, None, &hits);
+        assert_eq!(tolerant.get(QueryCache::key(&near, 2, None), &near, 2, None), Some(hits.clone()));
+        assert_eq!(tolerant.get(QueryCache::key(&far, 2, None), &far, 2, None), None);
+        // A near query only shares entries with the same topk and MMR setting
+        assert_eq!(tolerant.get(QueryCache::key(&near, 3, None), &near, 3, None), None);
+        assert_eq!(tolerant.get(QueryCache::key(&near, 2, Some(0.5)), &near, 2, Some(0.5)), None);
+        assert_eq!((tolerant.hits, tolerant.misses), (1, 3));
+
+        let mut disabled = QueryCache::new(0, 0.5);
+        disabled.insert(QueryCache::key(&query, 2, None), &query, 2, None, &hits);
+        assert_eq!(disabled.get(QueryCache::key(&query, 2, None), &query, 2, None), None);
+    }
+
+    #[test]
+    fn test_query_cache_evicts_least_recently_used() {
+        let mut cache = QueryCache::new(2, 0.0);
+        let queries: Vec<Vec<f32>> = (0..3).map(|i| vec![i as f32, 1.0]).collect();
+        let key = |i: usize| QueryCache::key(&queries[i], 1, None);
+        cache.insert(key(0), &queries[0], 1, None, &[(0, 1.0)]);
+        cache.insert(key(1), &queries[1], 1, None, &[(1, 1.0)]);
+        assert!(cache.get(key(0), &queries[0], 1, None).is_some());
+        cache.insert(key(2), &queries[2], 1, None, &[(2, 1.0)]);
+        assert!(cache.get(key(1), &queries[1], 1, None).is_none());
+        assert!(cache.get(key(0), &queries[0], 1, None).is_some());
+        assert!(cache.get(key(2), &queries[2], 1, None).is_some());
+    }
+}
@@ -0,0 +1,896 @@
+//! Minimal Parquet reader/writer for fragment export
+//!
+//! Writes row groups of up to `ROW_GROUP_ROWS` fragments, each column one
+//! uncompressed, PLAIN-encoded v1 data page, with the schema
+//!
+//! ```text
+//! id: string, content: string, timestamp: double, metadata: string (JSON),
+//! embedding: list<float>
+//! ```
+//!
+//! The reader accepts that layout from any writer (optional columns, bit-packed
+//! levels, float or double embeddings) but not compressed or dictionary-encoded
+//! pages.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::MemoryFragment;
+
+const MAGIC: &[u8; 4] = b"PAR1";
+
+/// Fragments per row group written
+const ROW_GROUP_ROWS: usize = 65536;
+
+// Physical types
+const TYPE_FLOAT: i32 = 4;
+const TYPE_DOUBLE: i32 = 5;
+const TYPE_BYTE_ARRAY: i32 = 6;
+
+// Repetition types
+const REQUIRED: i32 = 0;
+const OPTIONAL: i32 = 1;
+const REPEATED: i32 = 2;
+
+// Converted types
+const CONVERTED_UTF8: i32 = 0;
+const CONVERTED_LIST: i32 = 3;
+
+// Encodings, page types, codecs
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const PAGE_DATA: i32 = 0;
+const CODEC_UNCOMPRESSED: i32 = 0;
+
+// Thrift compact protocol type ids
+const CT_BOOL_TRUE: u8 = 1;
+const CT_BOOL_FALSE: u8 = 2;
+const CT_BYTE: u8 = 3;
+const CT_I16: u8 = 4;
+const CT_I32: u8 = 5;
+const CT_I64: u8 = 6;
+const CT_DOUBLE: u8 = 7;
+const CT_BINARY: u8 = 8;
+const CT_LIST: u8 = 9;
+const CT_SET: u8 = 10;
+const CT_MAP: u8 = 11;
+const CT_STRUCT: u8 = 12;
+
+/// Write fragments to a Parquet file
+pub fn write_fragments(path: &Path, fragments: &[MemoryFragment]) -> io::Result<()> {
+    fs::write(path, encode_fragments(fragments, ROW_GROUP_ROWS)?)
+}
+
+/// The Parquet file for `fragments`, `group_rows` per row group; no
+/// fragments still make one empty row group
+fn encode_fragments(fragments: &[MemoryFragment], group_rows: usize) -> io::Result<Vec<u8>> {
+    let groups: Vec<&[MemoryFragment]> = if fragments.is_empty() {
+        vec![fragments]
+    } else {
+        fragments.chunks(group_rows.max(1)).collect()
+    };
+
+    let mut file = MAGIC.to_vec();
+    // Columns of each row group, their pages moved into the file
+    let mut row_groups = Vec::with_capacity(groups.len());
+    for group in groups {
+        let mut columns = fragment_columns(group)?;
+        let mut chunks = Vec::with_capacity(columns.len());
+        for column in &mut columns {
+            let page = std::mem::take(&mut column.page);
+            let mut header = CompactWriter::new();
+            header.i32(1, PAGE_DATA);
+            header.i32(2, page.len() as i32);
+            header.i32(3, page.len() as i32);
+            header.begin_struct(5);
+            header.i32(1, column.num_values as i32);
+            header.i32(2, ENCODING_PLAIN);
+            header.i32(3, ENCODING_RLE);
+            header.i32(4, ENCODING_RLE);
+            header.end_struct();
+            let header = header.finish();
+
+            let offset = file.len() as i64;
+            file.extend_from_slice(&header);
+            file.extend_from_slice(&page);
+            chunks.push((offset, (header.len() + page.len()) as i64));
+        }
+        row_groups.push((group.len(), columns, chunks));
+    }
+
+    let mut meta = CompactWriter::new();
+    meta.i32(1, 1);
+    meta.list_header(2, CT_STRUCT, 8);
+    write_schema(&mut meta);
+    meta.i64(3, fragments.len() as i64);
+    meta.list_header(4, CT_STRUCT, row_groups.len());
+    for (rows, columns, chunks) in &row_groups {
+        meta.begin_element();
+        meta.list_header(1, CT_STRUCT, columns.len());
+        for (column, &(offset, size)) in columns.iter().zip(chunks.iter()) {
+            meta.begin_element();
+            meta.i64(2, offset);
+            meta.begin_struct(3);
+            meta.i32(1, column.physical);
+            meta.list_header(2, CT_I32, 2);
+            meta.raw_i64(ENCODING_PLAIN as i64);
+            meta.raw_i64(ENCODING_RLE as i64);
+            meta.list_header(3, CT_BINARY, column.path.len());
+            for part in &column.path {
+                meta.raw_binary(part.as_bytes());
+            }
+            meta.i32(4, CODEC_UNCOMPRESSED);
+            meta.i64(5, column.num_values as i64);
+            meta.i64(6, size);
+            meta.i64(7, size);
+            meta.i64(9, offset);
+            meta.end_struct();
+            meta.end_element();
+        }
+        let total: i64 = chunks.iter().map(|&(_, size)| size).sum();
+        meta.i64(2, total);
+        meta.i64(3, *rows as i64);
+        meta.end_element();
+    }
+    meta.binary(6, b"aios_carma_rust");
+    let meta = meta.finish();
+
+    file.extend_from_slice(&meta);
+    file.extend_from_slice(&(meta.len() as u32).to_le_bytes());
+    file.extend_from_slice(MAGIC);
+    Ok(file)
+}
+
+/// The export columns of `fragments`, in schema order
+fn fragment_columns(fragments: &[MemoryFragment]) -> io::Result<Vec<ColumnData>> {
+    let mut columns = vec![
+        flat_column("id", TYPE_BYTE_ARRAY, fragments.iter().map(|f| byte_array(f.id.as_bytes()))),
+        flat_column("content", TYPE_BYTE_ARRAY, fragments.iter().map(|f| byte_array(f.content.as_bytes()))),
+        flat_column("timestamp", TYPE_DOUBLE, fragments.iter().map(|f| f.timestamp.to_le_bytes().to_vec())),
+    ];
+    let mut metadata_values = Vec::with_capacity(fragments.len());
+    for fragment in fragments {
+        metadata_values.push(byte_array(&serde_json::to_vec(&fragment.metadata)?));
+    }
+    columns.push(flat_column("metadata", TYPE_BYTE_ARRAY, metadata_values.into_iter()));
+    columns.push(embedding_column(fragments));
+    Ok(columns)
+}
+
+/// Read fragments from a Parquet file with the export schema
+pub fn read_fragments(path: &Path) -> io::Result<Vec<MemoryFragment>> {
+    let data = fs::read(path)?;
+    if data.len() < 12 || &data[..4] != MAGIC || &data[data.len() - 4..] != MAGIC {
+        return Err(invalid("not a Parquet file"));
+    }
+    let meta_len = u32::from_le_bytes(data[data.len() - 8..data.len() - 4].try_into().unwrap()) as usize;
+    let meta_start = (data.len() - 8)
+        .checked_sub(meta_len)
+        .ok_or_else(|| invalid("truncated footer"))?;
+    let meta = CompactReader::new(&data[meta_start..data.len() - 8]).read_struct()?;
+
+    let schema = leaf_levels(meta.list(2)?)?;
+    let mut ids = Vec::new();
+    let mut contents = Vec::new();
+    let mut timestamps = Vec::new();
+    let mut metadata = Vec::new();
+    let mut embeddings = Vec::new();
+
+    for row_group in meta.list(4)? {
+        for chunk in row_group.as_struct()?.list(1)? {
+            let column = chunk.as_struct()?.field(3)?.as_struct()?;
+            let path: Vec<String> = column
+                .list(3)?
+                .iter()
+                .map(|p| p.as_str().map(|s| s.to_string()))
+                .collect::<io::Result<_>>()?;
+            let (max_def, max_rep) = schema.get(&path).copied().ok_or_else(|| invalid("column missing from schema"))?;
+            let leaf = read_column(&data, column, max_def, max_rep)?;
+
+            match path[0].as_str() {
+                "id" => ids.extend(leaf.strings()?),
+                "content" => contents.extend(leaf.strings()?),
+                "metadata" => metadata.extend(leaf.strings()?),
+                "timestamp" => timestamps.extend(leaf.doubles()?),
+                "embedding" => embeddings.extend(leaf.lists()?),
+                _ => {}
+            }
+        }
+    }
+
+    if contents.len() != ids.len() || embeddings.len() != ids.len() {
+        return Err(invalid("id, content and embedding columns have different lengths"));
+    }
+    let mut fragments = Vec::with_capacity(ids.len());
+    for (i, (id, content)) in ids.into_iter().zip(contents).enumerate() {
+        let metadata = match metadata.get(i) {
+            Some(Some(json)) => serde_json::from_str(json)?,
+            _ => HashMap::new(),
+        };
+        fragments.push(MemoryFragment {
+            id: id.ok_or_else(|| invalid("null fragment id"))?,
+            content: content.unwrap_or_default(),
+            embedding: embeddings[i].clone(),
+            timestamp: timestamps.get(i).copied().flatten().unwrap_or(0.0),
+            metadata,
+        });
+    }
+    Ok(fragments)
+}
+
+/// One column chunk ready to write: PLAIN values preceded by any levels
+struct ColumnData {
+    path: Vec<&'static str>,
+    physical: i32,
+    num_values: usize,
+    page: Vec<u8>,
+}
+
+fn flat_column(name: &'static str, physical: i32, values: impl Iterator<Item = Vec<u8>>) -> ColumnData {
+    let mut page = Vec::new();
+    let mut num_values = 0;
+    for value in values {
+        page.extend_from_slice(&value);
+        num_values += 1;
+    }
+    ColumnData {
+        path: vec![name],
+        physical,
+        num_values,
+        page,
+    }
+}
+
+/// `embedding.list.element`, max repetition and definition level 1
+fn embedding_column(fragments: &[MemoryFragment]) -> ColumnData {
+    let mut rep = Vec::new();
+    let mut def = Vec::new();
+    let mut values = Vec::new();
+    for fragment in fragments {
+        if fragment.embedding.is_empty() {
+            rep.push(0);
+            def.push(0);
+            continue;
+        }
+        for (j, x) in fragment.embedding.iter().enumerate() {
+            rep.push(if j == 0 { 0 } else { 1 });
+            def.push(1);
+            values.extend_from_slice(&x.to_le_bytes());
+        }
+    }
+
+    let mut page = Vec::new();
+    for levels in [&rep, &def] {
+        let encoded = encode_levels(levels);
+        page.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+        page.extend_from_slice(&encoded);
+    }
+    page.extend_from_slice(&values);
+    ColumnData {
+        path: vec!["embedding", "list", "element"],
+        physical: TYPE_FLOAT,
+        num_values: rep.len(),
+        page,
+    }
+}
+
+/// Depth-first schema: root, four flat columns, then embedding > list > element
+fn write_schema(meta: &mut CompactWriter) {
+    schema_element(meta, "schema", None, None, Some(5), None);
+    schema_element(meta, "id", Some(TYPE_BYTE_ARRAY), Some(REQUIRED), None, Some(CONVERTED_UTF8));
+    schema_element(meta, "content", Some(TYPE_BYTE_ARRAY), Some(REQUIRED), None, Some(CONVERTED_UTF8));
+    schema_element(meta, "timestamp", Some(TYPE_DOUBLE), Some(REQUIRED), None, None);
+    schema_element(meta, "metadata", Some(TYPE_BYTE_ARRAY), Some(REQUIRED), None, Some(CONVERTED_UTF8));
+    schema_element(meta, "embedding", None, Some(REQUIRED), Some(1), Some(CONVERTED_LIST));
+    schema_element(meta, "list", None, Some(REPEATED), Some(1), None);
+    schema_element(meta, "element", Some(TYPE_FLOAT), Some(REQUIRED), None, None);
+}
+
+fn schema_element(
+    meta: &mut CompactWriter,
+    name: &str,
+    physical: Option<i32>,
+    repetition: Option<i32>,
+    children: Option<i32>,
+    converted: Option<i32>,
+) {
+    meta.begin_element();
+    if let Some(physical) = physical {
+        meta.i32(1, physical);
+    }
+    if let Some(repetition) = repetition {
+        meta.i32(3, repetition);
+    }
+    meta.binary(4, name.as_bytes());
+    if let Some(children) = children {
+        meta.i32(5, children);
+    }
+    if let Some(converted) = converted {
+        meta.i32(6, converted);
+    }
+    meta.end_element();
+}
+
+fn byte_array(bytes: &[u8]) -> Vec<u8> {
+    let mut out = (bytes.len() as u32).to_le_bytes().to_vec();
+    out.extend_from_slice(bytes);
+    out
+}
+
+/// RLE-encode bit-width-1 levels as runs of equal values
+fn encode_levels(levels: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < levels.len() {
+        let value = levels[i];
+        let run = levels[i..].iter().take_while(|&&l| l == value).count();
+        write_varint(&mut out, (run as u64) << 1);
+        out.push(value);
+        i += run;
+    }
+    out
+}
+
+/// Decode an RLE/bit-packed hybrid run of `count` levels
+fn decode_levels(data: &[u8], bit_width: u32, count: usize) -> io::Result<Vec<u32>> {
+    let mut levels = Vec::with_capacity(count);
+    let mut pos = 0;
+    let value_bytes = bit_width.div_ceil(8) as usize;
+    while levels.len() < count {
+        let header = read_varint(data, &mut pos)?;
+        if header & 1 == 0 {
+            let run = (header >> 1) as usize;
+            let bytes = data.get(pos..pos + value_bytes).ok_or_else(|| invalid("truncated levels"))?;
+            let mut value = 0u32;
+            for (k, &b) in bytes.iter().enumerate() {
+                value |= (b as u32) << (8 * k);
+            }
+            pos += value_bytes;
+            levels.extend(std::iter::repeat_n(value, run));
+        } else {
+            let values = (header >> 1) as usize * 8;
+            let bytes = data
+                .get(pos..pos + values * bit_width as usize / 8)
+                .ok_or_else(|| invalid("truncated levels"))?;
+            for v in 0..values {
+                let mut value = 0u32;
+                for bit in 0..bit_width as usize {
+                    let index = v * bit_width as usize + bit;
+                    value |= (((bytes[index / 8] >> (index % 8)) & 1) as u32) << bit;
+                }
+                levels.push(value);
+            }
+            pos += bytes.len();
+        }
+    }
+    levels.truncate(count);
+    Ok(levels)
+}
+
+/// Max (definition, repetition) level of every leaf column, keyed by path
+fn leaf_levels(schema: &[Thrift]) -> io::Result<HashMap<Vec<String>, (u32, u32)>> {
+    fn walk(
+        schema: &[Thrift],
+        pos: &mut usize,
+        path: &mut Vec<String>,
+        def: u32,
+        rep: u32,
+        out: &mut HashMap<Vec<String>, (u32, u32)>,
+    ) -> io::Result<()> {
+        let element = schema.get(*pos).ok_or_else(|| invalid("truncated schema"))?.as_struct()?;
+        *pos += 1;
+        let (def, rep) = match element.get(3).map(|r| r.as_i64()).transpose()? {
+            Some(r) if r == OPTIONAL as i64 => (def + 1, rep),
+            Some(r) if r == REPEATED as i64 => (def + 1, rep + 1),
+            _ => (def, rep),
+        };
+        path.push(element.field(4)?.as_str()?.to_string());
+        match element.get(5).map(|c| c.as_i64()).transpose()? {
+            Some(children) => {
+                for _ in 0..children {
+                    walk(schema, pos, path, def, rep, out)?;
+                }
+            }
+            None => {
+                out.insert(path.clone(), (def, rep));
+            }
+        }
+        path.pop();
+        Ok(())
+    }
+
+    let root = schema.first().ok_or_else(|| invalid("empty schema"))?.as_struct()?;
+    let children = root.field(5)?.as_i64()?;
+    let mut out = HashMap::new();
+    let mut pos = 1;
+    for _ in 0..children {
+        walk(schema, &mut pos, &mut Vec::new(), 0, 0, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Decoded leaf column: levels plus the non-null values
+struct LeafColumn {
+    def: Vec<u32>,
+    rep: Vec<u32>,
+    max_def: u32,
+    values: LeafValues,
+}
+
+enum LeafValues {
+    Bytes(Vec<Vec<u8>>),
+    Doubles(Vec<f64>),
+    Floats(Vec<f32>),
+}
+
+impl LeafColumn {
+    /// Flat column values with nulls as None
+    fn flat<T: Clone>(&self, values: &[T]) -> Vec<Option<T>> {
+        let mut next = values.iter();
+        self.def
+            .iter()
+            .map(|&d| if d == self.max_def { next.next().cloned() } else { None })
+            .collect()
+    }
+
+    fn strings(&self) -> io::Result<Vec<Option<String>>> {
+        match &self.values {
+            LeafValues::Bytes(values) => self
+                .flat(values)
+                .into_iter()
+                .map(|v| v.map(|b| String::from_utf8(b).map_err(|_| invalid("invalid UTF-8"))).transpose())
+                .collect(),
+            _ => Err(invalid("expected a string column")),
+        }
+    }
+
+    fn doubles(&self) -> io::Result<Vec<Option<f64>>> {
+        match &self.values {
+            LeafValues::Doubles(values) => Ok(self.flat(values)),
+            LeafValues::Floats(values) => Ok(self.flat(values).into_iter().map(|v| v.map(|x| x as f64)).collect()),
+            _ => Err(invalid("expected a floating-point column")),
+        }
+    }
+
+    /// Repeated column regrouped into one list per row
+    fn lists(&self) -> io::Result<Vec<Vec<f32>>> {
+        let values: Vec<f32> = match &self.values {
+            LeafValues::Floats(values) => values.clone(),
+            LeafValues::Doubles(values) => values.iter().map(|&x| x as f32).collect(),
+            _ => return Err(invalid("expected a list<float> column")),
+        };
+
+        let mut rows: Vec<Vec<f32>> = Vec::new();
+        let mut next = values.into_iter();
+        for (&rep, &def) in self.rep.iter().zip(self.def.iter()) {
+            if rep == 0 {
+                rows.push(Vec::new());
+            }
+            if def == self.max_def {
+                let row = rows.last_mut().ok_or_else(|| invalid("list continues before first row"))?;
+                row.push(next.next().ok_or_else(|| invalid("missing list values"))?);
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// Read every data page of one column chunk
+fn read_column(data: &[u8], column: &ThriftStruct, max_def: u32, max_rep: u32) -> io::Result<LeafColumn> {
+    if column.field(4)?.as_i64()? != CODEC_UNCOMPRESSED as i64 {
+        return Err(invalid("compressed Parquet columns are not supported"));
+    }
+    let physical = column.field(1)?.as_i64()? as i32;
+    let total_values = column.field(5)?.as_i64()? as usize;
+    let mut pos = column.field(9)?.as_i64()? as usize;
+
+    let mut leaf = LeafColumn {
+        def: Vec::with_capacity(total_values),
+        rep: Vec::with_capacity(total_values),
+        max_def,
+        values: match physical {
+            TYPE_BYTE_ARRAY => LeafValues::Bytes(Vec::new()),
+            TYPE_DOUBLE => LeafValues::Doubles(Vec::new()),
+            TYPE_FLOAT => LeafValues::Floats(Vec::new()),
+            _ => return Err(invalid("unsupported Parquet physical type")),
+        },
+    };
+
+    while leaf.def.len() < total_values {
+        let mut reader = CompactReader::new(data.get(pos..).ok_or_else(|| invalid("truncated column"))?);
+        let header = reader.read_struct()?;
+        pos += reader.pos;
+        let size = header.field(3)?.as_i64()? as usize;
+        let page = data.get(pos..pos + size).ok_or_else(|| invalid("truncated page"))?;
+        pos += size;
+        if header.field(1)?.as_i64()? != PAGE_DATA as i64 {
+            return Err(invalid("only v1 data pages are supported"));
+        }
+
+        let page_header = header.field(5)?.as_struct()?;
+        if page_header.field(2)?.as_i64()? != ENCODING_PLAIN as i64 {
+            return Err(invalid("only PLAIN-encoded Parquet pages are supported"));
+        }
+        let num_values = page_header.field(1)?.as_i64()? as usize;
+        let mut offset = 0;
+        let mut levels = |max: u32| -> io::Result<Vec<u32>> {
+            if max == 0 {
+                return Ok(vec![0; num_values]);
+            }
+            let len = page
+                .get(offset..offset + 4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+                .ok_or_else(|| invalid("truncated levels"))?;
+            let encoded = page.get(offset + 4..offset + 4 + len).ok_or_else(|| invalid("truncated levels"))?;
+            offset += 4 + len;
+            decode_levels(encoded, 32 - max.leading_zeros(), num_values)
+        };
+        let rep = levels(max_rep)?;
+        let def = levels(max_def)?;
+        let present = def.iter().filter(|&&d| d == max_def).count();
+
+        let mut values = &page[offset..];
+        for _ in 0..present {
+            match &mut leaf.values {
+                LeafValues::Bytes(out) => {
+                    let len = values
+                        .get(..4)
+                        .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+                        .ok_or_else(|| invalid("truncated values"))?;
+                    out.push(values.get(4..4 + len).ok_or_else(|| invalid("truncated values"))?.to_vec());
+                    values = &values[4 + len..];
+                }
+                LeafValues::Doubles(out) => {
+                    let bytes = values.get(..8).ok_or_else(|| invalid("truncated values"))?;
+                    out.push(f64::from_le_bytes(bytes.try_into().unwrap()));
+                    values = &values[8..];
+                }
+                LeafValues::Floats(out) => {
+                    let bytes = values.get(..4).ok_or_else(|| invalid("truncated values"))?;
+                    out.push(f32::from_le_bytes(bytes.try_into().unwrap()));
+                    values = &values[4..];
+                }
+            }
+        }
+        leaf.rep.extend(rep);
+        leaf.def.extend(def);
+    }
+    Ok(leaf)
+}
+
+/// Thrift compact protocol writer for the structures above
+struct CompactWriter {
+    buf: Vec<u8>,
+    /// Last field id written in each open struct
+    last_field: Vec<i16>,
+}
+
+impl CompactWriter {
+    fn new() -> Self {
+        Self { buf: Vec::new(), last_field: vec![0] }
+    }
+
+    fn field(&mut self, id: i16, field_type: u8) {
+        let last = self.last_field.last_mut().unwrap();
+        let delta = id - *last;
+        if (1..=15).contains(&delta) {
+            self.buf.push(((delta as u8) << 4) | field_type);
+        } else {
+            self.buf.push(field_type);
+            write_varint(&mut self.buf, zigzag(id as i64));
+        }
+        *last = id;
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, CT_I32);
+        self.raw_i64(value as i64);
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, CT_I64);
+        self.raw_i64(value);
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, CT_BINARY);
+        self.raw_binary(value);
+    }
+
+    fn raw_i64(&mut self, value: i64) {
+        write_varint(&mut self.buf, zigzag(value));
+    }
+
+    fn raw_binary(&mut self, value: &[u8]) {
+        write_varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    fn list_header(&mut self, id: i16, element_type: u8, size: usize) {
+        self.field(id, CT_LIST);
+        if size < 15 {
+            self.buf.push(((size as u8) << 4) | element_type);
+        } else {
+            self.buf.push(0xF0 | element_type);
+            write_varint(&mut self.buf, size as u64);
+        }
+    }
+
+    fn begin_struct(&mut self, id: i16) {
+        self.field(id, CT_STRUCT);
+        self.last_field.push(0);
+    }
+
+    fn end_struct(&mut self) {
+        self.buf.push(0);
+        self.last_field.pop();
+    }
+
+    /// Start a struct that is a list element (no field header)
+    fn begin_element(&mut self) {
+        self.last_field.push(0);
+    }
+
+    fn end_element(&mut self) {
+        self.end_struct();
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.buf.push(0);
+        self.buf
+    }
+}
+
+/// Decoded Thrift value (all integer widths widened to i64; doubles, which
+/// only appear in statistics we ignore, are kept as raw bits)
+#[derive(Debug, Clone)]
+enum Thrift {
+    Int(i64),
+    Binary(Vec<u8>),
+    List(Vec<Thrift>),
+    Struct(ThriftStruct),
+}
+
+#[derive(Debug, Clone, Default)]
+struct ThriftStruct {
+    fields: HashMap<i16, Thrift>,
+}
+
+impl ThriftStruct {
+    fn get(&self, id: i16) -> Option<&Thrift> {
+        self.fields.get(&id)
+    }
+
+    fn field(&self, id: i16) -> io::Result<&Thrift> {
+        self.get(id).ok_or_else(|| invalid(&format!("missing Thrift field {}", id)))
+    }
+
+    fn list(&self, id: i16) -> io::Result<&[Thrift]> {
+        match self.field(id)? {
+            Thrift::List(items) => Ok(items),
+            _ => Err(invalid("expected a Thrift list")),
+        }
+    }
+}
+
+impl Thrift {
+    fn as_i64(&self) -> io::Result<i64> {
+        match self {
+            Thrift::Int(v) => Ok(*v),
+            _ => Err(invalid("expected a Thrift integer")),
+        }
+    }
+
+    fn as_str(&self) -> io::Result<&str> {
+        match self {
+            Thrift::Binary(bytes) => std::str::from_utf8(bytes).map_err(|_| invalid("invalid UTF-8 in Thrift string")),
+            _ => Err(invalid("expected a Thrift string")),
+        }
+    }
+
+    fn as_struct(&self) -> io::Result<&ThriftStruct> {
+        match self {
+            Thrift::Struct(s) => Ok(s),
+            _ => Err(invalid("expected a Thrift struct")),
+        }
+    }
+}
+
+/// Thrift compact protocol reader
+struct CompactReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> CompactReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        let b = *self.data.get(self.pos).ok_or_else(|| invalid("truncated Thrift data"))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn read_struct(&mut self) -> io::Result<ThriftStruct> {
+        let mut result = ThriftStruct::default();
+        let mut last: i16 = 0;
+        loop {
+            let header = self.byte()?;
+            if header == 0 {
+                return Ok(result);
+            }
+            let field_type = header & 0x0F;
+            let delta = (header >> 4) as i16;
+            let id = if delta == 0 {
+                unzigzag(read_varint(self.data, &mut self.pos)?) as i16
+            } else {
+                last + delta
+            };
+            last = id;
+            let value = self.read_value(field_type)?;
+            result.fields.insert(id, value);
+        }
+    }
+
+    fn read_value(&mut self, value_type: u8) -> io::Result<Thrift> {
+        Ok(match value_type {
+            CT_BOOL_TRUE => Thrift::Int(1),
+            CT_BOOL_FALSE => Thrift::Int(0),
+            CT_BYTE => Thrift::Int(self.byte()? as i8 as i64),
+            CT_I16 | CT_I32 | CT_I64 => Thrift::Int(unzigzag(read_varint(self.data, &mut self.pos)?)),
+            CT_DOUBLE => {
+                let bytes = self.data.get(self.pos..self.pos + 8).ok_or_else(|| invalid("truncated Thrift data"))?;
+                self.pos += 8;
+                Thrift::Int(i64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            CT_BINARY => {
+                let len = read_varint(self.data, &mut self.pos)? as usize;
+                let bytes = self
+                    .data
+                    .get(self.pos..self.pos + len)
+                    .ok_or_else(|| invalid("truncated Thrift data"))?;
+                self.pos += len;
+                Thrift::Binary(bytes.to_vec())
+            }
+            CT_LIST | CT_SET => {
+                let header = self.byte()?;
+                let element_type = header & 0x0F;
+                let size = match header >> 4 {
+                    15 => read_varint(self.data, &mut self.pos)? as usize,
+                    n => n as usize,
+                };
+                let mut items = Vec::with_capacity(size.min(1 << 16));
+                for _ in 0..size {
+                    // Booleans inside lists are a single byte each
+                    if element_type == CT_BOOL_TRUE || element_type == CT_BOOL_FALSE {
+                        items.push(Thrift::Int((self.byte()? == 1) as i64));
+                    } else {
+                        items.push(self.read_value(element_type)?);
+                    }
+                }
+                Thrift::List(items)
+            }
+            CT_MAP => {
+                let size = read_varint(self.data, &mut self.pos)? as usize;
+                if size > 0 {
+                    let types = self.byte()?;
+                    for _ in 0..size {
+                        self.read_value(types >> 4)?;
+                        self.read_value(types & 0x0F)?;
+                    }
+                }
+                Thrift::List(Vec::new())
+            }
+            CT_STRUCT => Thrift::Struct(self.read_struct()?),
+            _ => return Err(invalid("unknown Thrift type")),
+        })
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> io::Result<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let b = *data.get(*pos).ok_or_else(|| invalid("truncated varint"))?;
+        *pos += 1;
+        value |= ((b & 0x7F) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+        if shift >= 64 {
+            return Err(invalid("varint too long"));
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment(i: usize, embedding: Vec<f32>) -> MemoryFragment {
+        MemoryFragment {
+            id: format!("frag_{}", i),
+            content: format!("content {} é", i),
+            embedding,
+            timestamp: 1_700_000_000.5 + i as f64,
+            metadata: HashMap::from([("source".to_string(), format!("test {}", i))]),
+        }
+    }
+
+    fn round_trip(fragments: &[MemoryFragment], group_rows: usize) -> Vec<MemoryFragment> {
+        let path = std::env::temp_dir().join(format!("aios_carma_parquet_{}_{}.parquet", std::process::id(), group_rows));
+        fs::write(&path, encode_fragments(fragments, group_rows).unwrap()).unwrap();
+        let read = read_fragments(&path);
+        let _ = fs::remove_file(&path);
+        read.unwrap()
+    }
+
+    fn row_groups(file: &[u8]) -> usize {
+        let meta_len = u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap()) as usize;
+        let meta = CompactReader::new(&file[file.len() - 8 - meta_len..file.len() - 8]).read_struct().unwrap();
+        meta.list(4).unwrap().len()
+    }
+
+    fn assert_same(read: &[MemoryFragment], written: &[MemoryFragment]) {
+        assert_eq!(read.len(), written.len());
+        for (read, written) in read.iter().zip(written) {
+            assert_eq!(read.id, written.id);
+            assert_eq!(read.content, written.content);
+            assert_eq!(read.embedding, written.embedding);
+            assert_eq!(read.timestamp, written.timestamp);
+            assert_eq!(read.metadata, written.metadata);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let fragments: Vec<MemoryFragment> = (0..5).map(|i| fragment(i, vec![i as f32, -0.5, 1e-3])).collect();
+        assert_same(&round_trip(&fragments, ROW_GROUP_ROWS), &fragments);
+
+        // Empty embeddings between full ones
+        let ragged = vec![fragment(0, vec![1.0]), fragment(1, Vec::new()), fragment(2, vec![2.0, 3.0]), fragment(3, Vec::new())];
+        assert_same(&round_trip(&ragged, ROW_GROUP_ROWS), &ragged);
+    }
+
+    #[test]
+    fn test_empty_table() {
+        assert!(round_trip(&[], ROW_GROUP_ROWS).is_empty());
+        assert_eq!(row_groups(&encode_fragments(&[], ROW_GROUP_ROWS).unwrap()), 1);
+    }
+
+    #[test]
+    fn test_row_groups() {
+        let fragments: Vec<MemoryFragment> =
+            (0..7).map(|i| fragment(i, if i % 3 == 1 { Vec::new() } else { vec![i as f32; 1 + i % 2] })).collect();
+        for group_rows in [1, 2, 3, 7, 100] {
+            let file = encode_fragments(&fragments, group_rows).unwrap();
+            assert_eq!(row_groups(&file), fragments.len().div_ceil(group_rows));
+            assert_same(&round_trip(&fragments, group_rows), &fragments);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Snapshots of RustCarmaCore in the `aios_carma::snapshot` format
+
+use std::io;
+use std::path::Path;
+
+pub use aios_carma::snapshot::{FragmentRecord, SnapshotHeader, FORMAT_VERSION};
+
+use crate::MemoryFragment;
+
+/// Write fragments and header to `path` atomically (temp file + rename)
+pub fn write_snapshot(path: &Path, header: &SnapshotHeader, fragments: &[MemoryFragment]) -> io::Result<()> {
+    let embeddings: Vec<&[f32]> = fragments.iter().map(|fragment| fragment.embedding.as_slice()).collect();
+    aios_carma::snapshot::write_snapshot(path, header, &embeddings)
+}
+
+/// Snapshot file contents for the header and fragments
+pub fn encode_snapshot(header: &SnapshotHeader, fragments: &[MemoryFragment]) -> io::Result<Vec<u8>> {
+    let embeddings: Vec<&[f32]> = fragments.iter().map(|fragment| fragment.embedding.as_slice()).collect();
+    aios_carma::snapshot::encode_snapshot(header, &embeddings)
+}
+
+/// Read a snapshot, returning the header and fully materialized fragments
+pub fn read_snapshot(path: &Path) -> io::Result<(SnapshotHeader, Vec<MemoryFragment>)> {
+    let (header, embeddings) = aios_carma::snapshot::read_snapshot(path)?;
+    Ok(materialize(header, embeddings))
+}
+
+/// Parse snapshot file contents into the header and fragments
+pub fn decode_snapshot(data: &[u8]) -> io::Result<(SnapshotHeader, Vec<MemoryFragment>)> {
+    let (header, embeddings) = aios_carma::snapshot::decode_snapshot(data)?;
+    Ok(materialize(header, embeddings))
+}
+
+fn materialize(header: SnapshotHeader, embeddings: Vec<Vec<f32>>) -> (SnapshotHeader, Vec<MemoryFragment>) {
+    let fragments = header
+        .fragments
+        .iter()
+        .zip(embeddings)
+        .map(|(record, embedding)| MemoryFragment {
+            id: record.id.clone(),
+            content: record.content.clone(),
+            embedding,
+            timestamp: record.timestamp,
+            metadata: record.metadata.clone(),
+        })
+        .collect();
+    (header, fragments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn fragments() -> Vec<MemoryFragment> {
+        (0..5)
+            .map(|i| MemoryFragment {
+                id: format!("f{}", i),
+                content: format!("fragment {} ✓", i),
+                embedding: (0..4).map(|j| (i * 4 + j) as f32 / 7.0).collect(),
+                timestamp: 1_700_000_000.5 + i as f64,
+                metadata: HashMap::from([("source".to_string(), format!("doc{}", i % 2))]),
+            })
+            .collect()
+    }
+
+    fn snapshot_header(fragments: &[MemoryFragment]) -> SnapshotHeader {
+        SnapshotHeader {
+            version: FORMAT_VERSION,
+            fragments: fragments
+                .iter()
+                .map(|f| FragmentRecord {
+                    id: f.id.clone(),
+                    content: f.content.clone(),
+                    timestamp: f.timestamp,
+                    metadata: f.metadata.clone(),
+                    dim: f.embedding.len() as u32,
+                })
+                .collect(),
+            clusters: HashMap::from([(0, vec!["f0".to_string(), "f2".to_string()]), (1, vec!["f1".to_string()])]),
+            centroids: vec![vec![0.5; 4], vec![-0.25; 4]],
+            total_queries: 42,
+        }
+    }
+
+    fn assert_same(expected: &[MemoryFragment], found: &[MemoryFragment]) {
+        assert_eq!(expected.len(), found.len());
+        for (expected, found) in expected.iter().zip(found) {
+            assert_eq!(
+                (&expected.id, &expected.content, &expected.embedding, expected.timestamp, &expected.metadata),
+                (&found.id, &found.content, &found.embedding, found.timestamp, &found.metadata)
+            );
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let fragments = fragments();
+        let header = snapshot_header(&fragments);
+        let path = std::env::temp_dir().join(format!("aios_carma_snapshot_{}.carma", std::process::id()));
+        write_snapshot(&path, &header, &fragments).unwrap();
+        let (loaded, loaded_fragments) = read_snapshot(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_same(&fragments, &loaded_fragments);
+        assert_eq!((loaded.version, loaded.total_queries), (FORMAT_VERSION, 42));
+        assert_eq!(loaded.clusters, header.clusters);
+        assert_eq!(loaded.centroids, header.centroids);
+
+        let (_, decoded) = decode_snapshot(&encode_snapshot(&header, &fragments).unwrap()).unwrap();
+        assert_same(&fragments, &decoded);
+
+        let empty = snapshot_header(&[]);
+        let (_, decoded) = decode_snapshot(&encode_snapshot(&empty, &[]).unwrap()).unwrap();
+        assert!(decoded.is_empty());
+    }
+
+    #[test]
+    fn test_truncated_snapshot_rejected() {
+        let fragments = fragments();
+        let data = encode_snapshot(&snapshot_header(&fragments), &fragments).unwrap();
+        for len in [0, 8, 20, data.len() / 2, data.len() - 4, data.len() - 1] {
+            assert!(decode_snapshot(&data[..len]).is_err(), "accepted {} of {} bytes", len, data.len());
+        }
+        let mut corrupt = data.clone();
+        corrupt[..8].copy_from_slice(b"NOTCARMA");
+        assert!(decode_snapshot(&corrupt).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Compressed embedding storage: int8 scalar and product quantization
+//!
+//! Both quantizers score with asymmetric distance computation (ADC): the query
+//! stays in full precision and only the stored vectors are compressed.
+
+use aios_cancel::CancellationToken;
+use crate::clustering::{kmeans, nearest_centroid, KMeansParams};
+
+/// Per-vector int8 scalar code: x ≈ min + code * scale
+#[derive(Debug, Clone)]
+pub struct Int8Code {
+    codes: Vec<u8>,
+    min: f32,
+    scale: f32,
+    /// L2 norm of the reconstructed vector
+    norm: f32,
+}
+
+impl Int8Code {
+    pub fn encode(vector: &[f32]) -> Self {
+        let min = vector.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = vector.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let (min, scale) = if vector.is_empty() || !min.is_finite() || !max.is_finite() {
+            (0.0, 0.0)
+        } else {
+            (min, (max - min) / 255.0)
+        };
+
+        let codes: Vec<u8> = vector
+            .iter()
+            .map(|&x| if scale > 0.0 { ((x - min) / scale).round().clamp(0.0, 255.0) as u8 } else { 0 })
+            .collect();
+        let mut code = Self { codes, min, scale, norm: 0.0 };
+        code.norm = code.decode().iter().map(|x| x * x).sum::<f32>().sqrt();
+        code
+    }
+
+    pub fn decode(&self) -> Vec<f32> {
+        self.codes.iter().map(|&c| self.min + c as f32 * self.scale).collect()
+    }
+
+    /// Cosine similarity between a full-precision query and this code
+    pub fn cosine(&self, query: &[f32], query_norm: f32) -> f32 {
+        if query.len() != self.codes.len() || query_norm == 0.0 || self.norm == 0.0 {
+            return 0.0;
+        }
+
+        let query_sum: f32 = query.iter().sum();
+        let weighted: f32 = query.iter().zip(self.codes.iter()).map(|(q, &c)| q * c as f32).sum();
+        (self.min * query_sum + self.scale * weighted) / (query_norm * self.norm)
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.codes.len() + 3 * std::mem::size_of::<f32>()
+    }
+}
+
+/// Product quantizer: the vector is split into `m` subspaces, each encoded as
+/// the index of its nearest sub-centroid
+#[derive(Debug, Clone)]
+pub struct ProductQuantizer {
+    dim: usize,
+    /// Subspace boundaries, `m + 1` entries
+    bounds: Vec<usize>,
+    /// codebooks[j][c] is sub-centroid c of subspace j
+    codebooks: Vec<Vec<Vec<f32>>>,
+}
+
+/// Product-quantized vector with the norm of its reconstruction
+#[derive(Debug, Clone)]
+pub struct PqCode {
+    codes: Vec<u8>,
+    norm: f32,
+}
+
+impl ProductQuantizer {
+    /// Train codebooks with up to 256 centroids per subspace
+    pub fn train(vectors: &[&[f32]], subspaces: usize) -> Option<Self> {
+        let dim = vectors.first()?.len();
+        if dim == 0 || vectors.iter().any(|v| v.len() != dim) {
+            return None;
+        }
+
+        let m = subspaces.clamp(1, dim);
+        let bounds: Vec<usize> = (0..=m).map(|j| j * dim / m).collect();
+        let codebooks = (0..m)
+            .map(|j| {
+                let slices: Vec<&[f32]> = vectors.iter().map(|v| &v[bounds[j]..bounds[j + 1]]).collect();
+                let params = KMeansParams {
+                    k: 256.min(slices.len()),
+                    max_iterations: 25,
+                    tolerance: 1e-4,
+                    batch_size: if slices.len() > 10_000 { Some(4096) } else { None },
+                };
+                kmeans(&slices, params, &CancellationToken::new()).centroids
+            })
+            .collect();
+
+        Some(Self { dim, bounds, codebooks })
+    }
+
+    pub fn encode(&self, vector: &[f32]) -> Option<PqCode> {
+        if vector.len() != self.dim {
+            return None;
+        }
+
+        let mut norm_sq = 0.0f32;
+        let codes = self
+            .codebooks
+            .iter()
+            .enumerate()
+            .map(|(j, codebook)| {
+                let (code, _) = nearest_centroid(&vector[self.bounds[j]..self.bounds[j + 1]], codebook);
+                norm_sq += codebook[code].iter().map(|x| x * x).sum::<f32>();
+                code as u8
+            })
+            .collect();
+        Some(PqCode { codes, norm: norm_sq.sqrt() })
+    }
+
+    /// Per-subspace dot products of the query with every sub-centroid
+    pub fn query_table(&self, query: &[f32]) -> Option<Vec<Vec<f32>>> {
+        if query.len() != self.dim {
+            return None;
+        }
+
+        Some(
+            self.codebooks
+                .iter()
+                .enumerate()
+                .map(|(j, codebook)| {
+                    let sub = &query[self.bounds[j]..self.bounds[j + 1]];
+                    codebook
+                        .iter()
+                        .map(|c| c.iter().zip(sub.iter()).map(|(a, b)| a * b).sum())
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    /// Approximate cosine similarity using a precomputed query table
+    pub fn cosine(table: &[Vec<f32>], code: &PqCode, query_norm: f32) -> f32 {
+        if query_norm == 0.0 || code.norm == 0.0 {
+            return 0.0;
+        }
+        let dot: f32 = code.codes.iter().enumerate().map(|(j, &c)| table[j][c as usize]).sum();
+        dot / (query_norm * code.norm)
+    }
+}
+
+impl PqCode {
+    pub fn memory_bytes(&self) -> usize {
+        self.codes.len() + std::mem::size_of::<f32>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vectors(count: usize, dim: usize) -> Vec<Vec<f32>> {
+        (0..count)
+            .map(|i| (0..dim).map(|j| ((i * 31 + j * 17) as f32 * 0.37).sin()).collect())
+            .collect()
+    }
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        dot / (norm(a) * norm(b))
+    }
+
+    #[test]
+    fn test_int8_round_trip() {
+        for vector in vectors(20, 32) {
+            let code = Int8Code::encode(&vector);
+            let max = vector.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let min = vector.iter().copied().fold(f32::INFINITY, f32::min);
+            let half_step = (max - min) / 255.0 / 2.0 + 1e-6;
+            for (x, y) in vector.iter().zip(code.decode()) {
+                assert!((x - y).abs() <= half_step, "{} decoded as {}", x, y);
+            }
+            let query = &vectors(1, 32)[0];
+            let query_norm = query.iter().map(|x| x * x).sum::<f32>().sqrt();
+            assert!((code.cosine(query, query_norm) - cosine(query, &vector)).abs() < 0.01);
+            assert_eq!(code.memory_bytes(), 32 + 12);
+        }
+
+        // Constant, empty and non-finite vectors encode without dividing by zero
+        assert_eq!(Int8Code::encode(&[0.5; 4]).decode(), vec![0.5; 4]);
+        assert!(Int8Code::encode(&[]).decode().is_empty());
+        assert_eq!(Int8Code::encode(&[1.0, f32::INFINITY]).decode(), vec![0.0, 0.0]);
+        assert_eq!(Int8Code::encode(&[1.0, 2.0]).cosine(&[1.0], 1.0), 0.0);
+    }
+
+    #[test]
+    fn test_product_quantizer() {
+        let data = vectors(300, 16);
+        let slices: Vec<&[f32]> = data.iter().map(Vec::as_slice).collect();
+        let quantizer = ProductQuantizer::train(&slices, 4).unwrap();
+        let query = [0.3f32; 16];
+        let query_norm = 4.0 * 0.3;
+        let table = quantizer.query_table(&query).unwrap();
+        let mut worst = 0.0f32;
+        for vector in &data {
+            let code = quantizer.encode(vector).unwrap();
+            assert_eq!(code.memory_bytes(), 4 + 4);
+            worst = worst.max((ProductQuantizer::cosine(&table, &code, query_norm) - cosine(&query, vector)).abs());
+        }
+        assert!(worst < 0.1, "cosine off by {}", worst);
+
+        // Subspaces are capped at the dimension
+        let quantizer = ProductQuantizer::train(&slices, 64).unwrap();
+        assert_eq!(quantizer.encode(&data[0]).unwrap().memory_bytes(), 16 + 4);
+
+        assert!(ProductQuantizer::train(&[], 4).is_none());
+        assert!(ProductQuantizer::train(&[&[1.0, 2.0], &[1.0]], 1).is_none());
+        assert!(quantizer.encode(&[1.0; 3]).is_none());
+        assert!(quantizer.query_table(&[1.0; 3]).is_none());
+    }
+}
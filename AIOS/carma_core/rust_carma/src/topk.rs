@@ -0,0 +1,82 @@
+//! Bounded-heap top-k selection over streamed (position, score) pairs
+//!
+//! Keeps at most `k` entries in a min-heap keyed on score, so selecting from
+//! n candidates is O(n log k) time and O(k) memory instead of a full sort.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+/// Heap entry ordered by score, ties broken towards the lower position
+#[derive(Debug, Clone, Copy)]
+struct Scored(usize, f32);
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.1.total_cmp(&other.1).then(other.0.cmp(&self.0))
+    }
+}
+
+/// The `k` highest-scoring pairs, best first
+pub fn top_k(scores: impl IntoIterator<Item = (usize, f32)>, k: usize) -> Vec<(usize, f32)> {
+    if k == 0 {
+        return Vec::new();
+    }
+
+    let mut heap: BinaryHeap<Reverse<Scored>> = BinaryHeap::with_capacity(k + 1);
+    for (i, score) in scores {
+        let entry = Scored(i, score);
+        if heap.len() < k {
+            heap.push(Reverse(entry));
+        } else if heap.peek().is_some_and(|worst| entry > worst.0) {
+            heap.pop();
+            heap.push(Reverse(entry));
+        }
+    }
+
+    // Ascending order of Reverse is descending score
+    heap.into_sorted_vec().into_iter().map(|Reverse(Scored(i, score))| (i, score)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference: full sort by score, ties by position
+    fn sorted(scores: &[f32], k: usize) -> Vec<(usize, f32)> {
+        let mut all: Vec<(usize, f32)> = scores.iter().copied().enumerate().collect();
+        all.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        all.truncate(k);
+        all
+    }
+
+    #[test]
+    fn test_matches_full_sort() {
+        // Many repeated scores, so ties are exercised
+        let scores: Vec<f32> = (0..500).map(|i| ((i * 7919) % 101) as f32 / 10.0 - 5.0).collect();
+        for k in [0, 1, 5, 100, 499, 500, 1000] {
+            assert_eq!(top_k(scores.iter().copied().enumerate(), k), sorted(&scores, k), "k = {}", k);
+        }
+        assert!(top_k(std::iter::empty(), 3).is_empty());
+    }
+
+    #[test]
+    fn test_ties_keep_lower_positions() {
+        let scores = [1.0, 2.0, 2.0, 1.0, 2.0];
+        assert_eq!(top_k(scores.iter().copied().enumerate(), 2), vec![(1, 2.0), (2, 2.0)]);
+        assert_eq!(top_k(scores.iter().copied().enumerate(), 4), vec![(1, 2.0), (2, 2.0), (4, 2.0), (0, 1.0)]);
+    }
+}
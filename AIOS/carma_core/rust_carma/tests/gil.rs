@@ -0,0 +1,69 @@
+//! Long calls release the GIL, so other Python threads keep running
+
+use std::time::{Duration, Instant};
+
+use aios_carma_rust::RustCarmaCore;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// A Python thread counting every millisecond until `running` is cleared
+const TICKER: &str = r#"
+import threading, time
+ticks = 0
+running = True
+def tick():
+    global ticks
+    while running:
+        ticks += 1
+        time.sleep(0.001)
+ticker = threading.Thread(target=tick, daemon=True)
+ticker.start()
+"#;
+
+/// Ticks counted while `call` is repeated, holding the GIL in between, for
+/// `span`
+fn ticks_during(globals: &Bound<'_, PyDict>, span: Duration, mut call: impl FnMut() -> PyResult<()>) -> PyResult<u64> {
+    let ticks = || -> PyResult<u64> { globals.get_item("ticks")?.expect("ticker started").extract() };
+    let start = ticks()?;
+    let deadline = Instant::now() + span;
+    while Instant::now() < deadline {
+        call()?;
+    }
+    Ok(ticks()? - start)
+}
+
+#[test]
+fn test_clustering_and_export_release_gil() -> PyResult<()> {
+    let path = std::env::temp_dir().join(format!("aios_carma_gil_{}.jsonl", std::process::id()));
+    pyo3::prepare_freethreaded_python();
+    let result = Python::with_gil(|py| {
+        let core = py.get_type_bound::<RustCarmaCore>().call0()?;
+        let contents: Vec<String> = (0..2000).map(|i| format!("fragment {}", i)).collect();
+        let embeddings: Vec<Vec<f32>> =
+            (0..2000).map(|i| (0..32).map(|d| ((i * 32 + d) as f32 * 0.37).sin()).collect()).collect();
+        core.call_method1("add_fragments", (contents, embeddings))?;
+
+        let globals = PyDict::new_bound(py);
+        py.run_bound(TICKER, Some(&globals), None)?;
+        // The ticker stalls while the GIL is held...
+        let stalled = ticks_during(&globals, Duration::from_millis(200), || {
+            std::thread::sleep(Duration::from_millis(10));
+            Ok(())
+        })?;
+        // ...but not during calls that release it
+        let clustering = ticks_during(&globals, Duration::from_millis(200), || {
+            core.call_method1("cluster_fragments", (8,)).map(drop)
+        })?;
+        let exporting = ticks_during(&globals, Duration::from_millis(200), || {
+            core.call_method1("export_fragments", (path.to_str().unwrap(),)).map(drop)
+        })?;
+        globals.set_item("running", false)?;
+        py.run_bound("ticker.join()", Some(&globals), None)?;
+        assert!(stalled <= 2, "ticked {} times with the GIL held", stalled);
+        assert!(clustering >= 20, "ticked only {} times during clustering", clustering);
+        assert!(exporting >= 20, "ticked only {} times during exports", exporting);
+        Ok(())
+    });
+    let _ = std::fs::remove_file(&path);
+    result
+}
@@ -0,0 +1,52 @@
+//! Rerankers run without the store locked
+
+use aios_carma_rust::RustCarmaCore;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// A reranker that queries the same core before scoring, preferring
+/// fragments that mention "b"
+const RERANKER: &str = r#"
+nested = []
+def reranker(query, fragments):
+    nested.append(core.process_query("nested " + query, [1.0, 0.0, 0.0], 1)["fragments_found"])
+    return [1.0 if "b" in f.content else 0.0 for f in fragments]
+"#;
+
+#[test]
+fn test_reranker_calls_back_into_core() -> PyResult<()> {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let core = py.get_type_bound::<RustCarmaCore>().call0()?;
+        for (content, embedding) in [("a", [1.0, 0.0, 0.0]), ("b", [0.0, 1.0, 0.0]), ("c", [0.9, 0.1, 0.0])] {
+            core.call_method1("add_fragment", (content, embedding.to_vec()))?;
+        }
+        let globals = PyDict::new_bound(py);
+        globals.set_item("core", &core)?;
+        py.run_bound(RERANKER, Some(&globals), None)?;
+        let reranker = globals.get_item("reranker")?.expect("reranker defined");
+
+        let kwargs = PyDict::new_bound(py);
+        kwargs.set_item("reranker", &reranker)?;
+        kwargs.set_item("rerank_candidates", 3)?;
+        let result = core.call_method("process_query", ("q", vec![1.0f32, 0.0, 0.0], 1), Some(&kwargs))?;
+        let fragments = result.get_item("fragments")?;
+        assert_eq!(fragments.len()?, 1);
+        assert_eq!(fragments.get_item(0)?.getattr("content")?.extract::<String>()?, "b");
+        let nested: Vec<usize> = globals.get_item("nested")?.expect("nested defined").extract()?;
+        assert_eq!(nested, vec![1]);
+
+        // Both the nested and the outer query were counted
+        assert_eq!(result.get_item("total_queries")?.extract::<u64>()?, 2);
+
+        // Batches rerank each query, still unlocked
+        let queries = vec!["x".to_string(), "y".to_string()];
+        let embeddings = vec![vec![1.0f32, 0.0, 0.0], vec![0.0f32, 0.0, 1.0]];
+        let results = core.call_method("process_queries", (queries, embeddings, 2), Some(&kwargs))?;
+        for result in results.iter()? {
+            let fragments = result?.get_item("fragments")?;
+            assert_eq!(fragments.get_item(0)?.getattr("content")?.extract::<String>()?, "b");
+        }
+        Ok(())
+    })
+}
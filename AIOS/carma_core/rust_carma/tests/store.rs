@@ -0,0 +1,71 @@
+//! Fragment store behaviour through the Python API
+
+use aios_carma_rust::RustCarmaCore;
+use pyo3::prelude::*;
+
+fn embedding(i: usize) -> Vec<f32> {
+    (0..8).map(|d| ((i * 8 + d) as f32 * 0.37).sin()).collect()
+}
+
+fn tombstones(core: &Bound<'_, PyAny>) -> PyResult<usize> {
+    core.call_method0("get_stats")?.get_item("index_tombstones")?.extract()
+}
+
+/// Deletes tombstone graph nodes until they pass a quarter of the graph,
+/// then the index is rebuilt
+#[test]
+fn test_deletes_rebuild_graph_past_tombstone_share() -> PyResult<()> {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        // exact_threshold=0 keeps every search on the graph
+        let core = py.get_type_bound::<RustCarmaCore>().call1((8, 100, 50, 0))?;
+        let mut ids = Vec::new();
+        for i in 0..40 {
+            ids.push(core.call_method1("add_fragment", (format!("fragment {}", i), embedding(i)))?.extract::<String>()?);
+        }
+        assert_eq!(tombstones(&core)?, 0);
+
+        // 5 of 40 stays under the share: the nodes are only tombstoned
+        let first: Vec<String> = ids.drain(..5).collect();
+        assert_eq!(core.call_method1("delete_fragments", (first.clone(),))?.extract::<usize>()?, 5);
+        assert_eq!(tombstones(&core)?, 5);
+
+        // 11 of 40 passes it, so the graph is rebuilt without them
+        let second: Vec<String> = ids.drain(..6).collect();
+        assert_eq!(core.call_method1("delete_fragments", (second.clone(),))?.extract::<usize>()?, 6);
+        assert_eq!(tombstones(&core)?, 0);
+        assert_eq!(core.call_method0("get_stats")?.get_item("total_fragments")?.extract::<usize>()?, 29);
+
+        // Survivors are still found, the deleted never are
+        for (i, id) in ids.iter().enumerate() {
+            let result = core.call_method1("process_query", ("q", embedding(i + 11), 1))?;
+            let found: String = result.get_item("fragments")?.get_item(0)?.getattr("id")?.extract()?;
+            assert_eq!(&found, id);
+        }
+        let result = core.call_method1("process_query", ("q", embedding(0), 29))?;
+        for fragment in result.get_item("fragments")?.iter()? {
+            let id: String = fragment?.getattr("id")?.extract()?;
+            assert!(!first.contains(&id) && !second.contains(&id));
+        }
+        Ok(())
+    })
+}
+
+#[test]
+fn test_embedding_errors_are_validation_errors() -> PyResult<()> {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let core = py.get_type_bound::<RustCarmaCore>().call0()?;
+        core.call_method1("add_fragment", ("a", vec![1.0f32, 0.0]))?;
+        let error = core.call_method1("add_fragment", ("b", vec![1.0f32, 0.0, 0.0])).unwrap_err();
+        assert!(error.is_instance_of::<aios_carma_rust::EmbeddingError>(py));
+        let names: Vec<String> = error
+            .get_type_bound(py)
+            .getattr("__mro__")?
+            .iter()?
+            .map(|class| class?.getattr("__name__")?.extract())
+            .collect::<PyResult<_>>()?;
+        assert_eq!(names, ["EmbeddingError", "ValidationError", "AiosError", "RuntimeError", "Exception", "BaseException", "object"]);
+        Ok(())
+    })
+}
@@ -0,0 +1,63 @@
+//! Fragment candidates as an Arrow record batch for rust_carma
+//!
+//! Each readable, non-empty file becomes one row:
+//!
+//! ```text
+//! path: utf8, content: utf8, size: int64, features: fixed_size_list<float32>
+//! ```
+//!
+//! `features` is a hashed bag of words (FNV-1a over lowercased alphanumeric
+//! tokens), L2-normalized, so candidates can be stored and searched before a
+//! real embedding model has seen them.
+
+use aios_arrow::{Column, RecordBatch};
+
+/// One file selected for export
+pub struct Candidate {
+    pub path: String,
+    pub content: String,
+}
+
+/// Hashed, L2-normalized term counts of `text` in `dimension` buckets
+pub fn hashed_features(text: &str, dimension: usize) -> Vec<f32> {
+    let mut features = vec![0f32; dimension];
+    for token in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+    {
+        let hash = token
+            .chars()
+            .flat_map(char::to_lowercase)
+            .fold(0xcbf2_9ce4_8422_2325u64, |hash, c| {
+                let mut buf = [0u8; 4];
+                c.encode_utf8(&mut buf).bytes().fold(hash, |hash, byte| {
+                    (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+                })
+            });
+        features[(hash % dimension as u64) as usize] += 1.0;
+    }
+    let norm = features.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        features.iter_mut().for_each(|x| *x /= norm);
+    }
+    features
+}
+
+/// Build the candidate batch; `dimension` must be positive
+pub fn candidate_batch(candidates: Vec<Candidate>, dimension: usize) -> aios_errors::Result<RecordBatch> {
+    let features = candidates
+        .iter()
+        .map(|candidate| hashed_features(&candidate.content, dimension))
+        .collect();
+    let sizes = candidates.iter().map(|candidate| candidate.content.len() as i64).collect();
+    let (paths, contents) = candidates
+        .into_iter()
+        .map(|candidate| (candidate.path, candidate.content))
+        .unzip();
+    RecordBatch::new(vec![
+        ("path".to_string(), Column::Utf8(paths)),
+        ("content".to_string(), Column::Utf8(contents)),
+        ("size".to_string(), Column::Int64(sizes)),
+        ("features".to_string(), Column::FloatLists(features)),
+    ])
+}
@@ -1,3 +1,4 @@
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
@@ -6,75 +7,134 @@ use walkdir::WalkDir;
 use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 
+mod candidates;
+
+use aios_cancel::CancellationToken;
+use aios_errors::{AiosError, Result};
+use aios_store::{Namespace, Store};
+#[cfg(feature = "python")]
+use aios_config::AiosConfig;
+use candidates::Candidate;
+
+/// Default length of the hashed `features` vectors in candidate exports
+pub const DEFAULT_FEATURE_DIMENSION: usize = 256;
+
 /// Statistics for a directory
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all))]
 pub struct DirectoryStats {
-    #[pyo3(get)]
     pub total_files: u32,
-    #[pyo3(get)]
     pub total_dirs: u32,
-    #[pyo3(get)]
     pub total_size_bytes: u64,
-    #[pyo3(get)]
     pub total_size_mb: f64,
-    #[pyo3(get)]
     pub last_modified: Option<String>,
-    #[pyo3(get)]
     pub file_types: std::collections::HashMap<String, u32>,
 }
 
 /// Data pipeline statistics
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all))]
 pub struct PipelineStats {
-    #[pyo3(get)]
     pub total_ingestions: u32,
-    #[pyo3(get)]
     pub total_exports: u32,
-    #[pyo3(get)]
     pub last_ingestion: Option<String>,
-    #[pyo3(get)]
     pub last_export: Option<String>,
-    #[pyo3(get)]
     pub cache_hit_rate: f64,
 }
 
 /// Data export result
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all))]
 pub struct ExportResult {
-    #[pyo3(get)]
     pub success: bool,
-    #[pyo3(get)]
     pub files_processed: u32,
-    #[pyo3(get)]
     pub bytes_processed: u64,
-    #[pyo3(get)]
     pub export_path: String,
-    #[pyo3(get)]
     pub time_taken_ms: u64,
-    #[pyo3(get)]
     pub error_message: Option<String>,
+    /// True when the export stopped early through its cancellation token
+    pub cancelled: bool,
+}
+
+impl ExportResult {
+    /// Result of an export stopped by its cancellation token
+    fn cancelled(export_path: &str, files_processed: u32, bytes_processed: u64, start_time: std::time::Instant) -> Self {
+        Self {
+            success: false,
+            files_processed,
+            bytes_processed,
+            export_path: export_path.to_string(),
+            time_taken_ms: start_time.elapsed().as_millis() as u64,
+            error_message: Some("Export was cancelled".to_string()),
+            cancelled: true,
+        }
+    }
+}
+
+/// Serializes the items of an iterator as a JSON array as they are produced
+struct JsonArray<I>(std::cell::RefCell<Option<I>>);
+
+impl<I: Iterator<Item = serde_json::Value>> Serialize for JsonArray<I> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.borrow_mut().take().into_iter().flatten())
+    }
+}
+
+/// Lazy walk behind `cleanup_old_data`, yielding the path of each old file
+pub struct OldFiles {
+    walker: walkdir::IntoIter,
+    cutoff: DateTime<Utc>,
+    dry_run: bool,
+}
+
+impl Iterator for OldFiles {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        for entry in self.walker.by_ref().filter_map(|e| e.ok()).filter(|e| e.file_type().is_file()) {
+            let Some(modified) = entry.metadata().ok().and_then(|m| m.modified().ok()) else {
+                continue;
+            };
+            if DateTime::<Utc>::from(modified) >= self.cutoff {
+                continue;
+            }
+            if !self.dry_run {
+                if let Err(e) = fs::remove_file(entry.path()) {
+                    eprintln!("Failed to remove {}: {}", entry.path().display(), e);
+                }
+            }
+            return Some(entry.path().to_string_lossy().to_string());
+        }
+        None
+    }
 }
 
+/// Outcome of collecting fragment candidates
+enum Candidates {
+    Complete { ipc: Vec<u8>, files_processed: u32, bytes_processed: u64 },
+    Cancelled { files_processed: u32, bytes_processed: u64 },
+}
+
+/// Format version of `PyRustDataCore.export_state`
+#[cfg(feature = "python")]
+const DATA_STATE_VERSION: u32 = 1;
+
 /// Rust Data Core implementation
-#[pyclass]
 pub struct RustDataCore {
     data_dir: PathBuf,
     pipeline_stats: PipelineStats,
+    /// Keeps the pipeline metrics once attached
+    store: Option<Namespace>,
 }
 
-#[pymethods]
 impl RustDataCore {
     /// Initialize the Rust Data Core
-    #[new]
-    pub fn new(data_dir: &str) -> PyResult<Self> {
+    pub fn new(data_dir: &str) -> Result<Self> {
         let data_path = PathBuf::from(data_dir);
         
         // Ensure data directory exists
         if !data_path.exists() {
-            fs::create_dir_all(&data_path).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to create data directory: {}", e)))?;
+            fs::create_dir_all(&data_path).map_err(|e| AiosError::io(format!("Failed to create data directory: {}", e)))?;
         }
         
         // Initialize pipeline stats
@@ -89,11 +149,48 @@ impl RustDataCore {
         Ok(Self {
             data_dir: data_path,
             pipeline_stats,
+            store: None,
         })
     }
+
+    /// Keep the pipeline metrics in `store`, continuing from the ones saved
+    /// there for this data directory
+    pub fn attach_store(&mut self, store: &Store) -> Result<()> {
+        let namespace = store.namespace("data", &[])?;
+        if let Some(stats) = namespace.get(&self.store_key())? {
+            self.pipeline_stats = stats;
+        }
+        self.store = Some(namespace);
+        self.save_pipeline_metrics()
+    }
+
+    fn store_key(&self) -> String {
+        format!("pipeline_stats:{}", self.data_dir.to_string_lossy())
+    }
+
+    fn save_pipeline_metrics(&self) -> Result<()> {
+        match &self.store {
+            Some(store) => store.set(&self.store_key(), &self.pipeline_stats),
+            None => Ok(()),
+        }
+    }
+
+    /// Count a finished export; a failure to save the metrics does not fail it
+    fn record_export(&mut self) {
+        self.pipeline_stats.total_exports += 1;
+        self.pipeline_stats.last_export = Some(Utc::now().format("%Y-%m-%d %H:%M:%S").to_string());
+        if let Err(e) = self.save_pipeline_metrics() {
+            eprintln!("Failed to save pipeline metrics: {}", e);
+        }
+    }
+    
+    /// Root of the cache, conversation and database directories
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
     
     /// Get directory statistics using parallel processing
-    pub fn get_directory_stats(&self, directory_path: &str) -> PyResult<DirectoryStats> {
+    pub fn get_directory_stats(&self, directory_path: &str) -> Result<DirectoryStats> {
         let dir_path = Path::new(directory_path);
         
         if !dir_path.exists() {
@@ -117,7 +214,7 @@ impl RustDataCore {
         let entries: Vec<_> = WalkDir::new(dir_path)
             .into_iter()
             .collect::<std::result::Result<Vec<_>, _>>()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to traverse directory: {}", e)))?;
+            .map_err(|e| AiosError::io(format!("Failed to traverse directory: {}", e)))?;
         
         for entry in entries {
             if let Ok(metadata) = entry.metadata() {
@@ -154,32 +251,93 @@ impl RustDataCore {
     }
     
     /// Get fractal cache statistics
-    pub fn get_fractal_cache_stats(&self) -> PyResult<DirectoryStats> {
+    pub fn get_fractal_cache_stats(&self) -> Result<DirectoryStats> {
         let fractal_cache_path = self.data_dir.join("FractalCache");
         self.get_directory_stats(fractal_cache_path.to_str().unwrap_or(""))
     }
     
     /// Get arbiter cache statistics
-    pub fn get_arbiter_cache_stats(&self) -> PyResult<DirectoryStats> {
+    pub fn get_arbiter_cache_stats(&self) -> Result<DirectoryStats> {
         let arbiter_cache_path = self.data_dir.join("ArbiterCache");
         self.get_directory_stats(arbiter_cache_path.to_str().unwrap_or(""))
     }
     
     /// Get conversation statistics
-    pub fn get_conversation_stats(&self) -> PyResult<DirectoryStats> {
+    pub fn get_conversation_stats(&self) -> Result<DirectoryStats> {
         let conversations_path = self.data_dir.join("conversations");
         self.get_directory_stats(conversations_path.to_str().unwrap_or(""))
     }
     
     /// Get database statistics
-    pub fn get_database_stats(&self) -> PyResult<DirectoryStats> {
+    pub fn get_database_stats(&self) -> Result<DirectoryStats> {
         let database_path = self.data_dir.join("AIOS_Database").join("database");
         self.get_directory_stats(database_path.to_str().unwrap_or(""))
     }
     
+    /// Clean up old data files
+    pub fn cleanup_old_data(&self, days_old: u32, dry_run: bool) -> Result<Vec<String>> {
+        Ok(self.old_files(days_old, dry_run).collect())
+    }
+
+    /// Files not modified for `days_old` days, removed as the walk reaches
+    /// them unless `dry_run`
+    pub fn old_files(&self, days_old: u32, dry_run: bool) -> OldFiles {
+        OldFiles {
+            walker: WalkDir::new(&self.data_dir).into_iter(),
+            cutoff: Utc::now() - chrono::Duration::days(days_old as i64),
+            dry_run,
+        }
+    }
+    
+    /// Get comprehensive system overview
+    pub fn get_system_overview(&self) -> Result<String> {
+        let mut overview = HashMap::new();
+        
+        // Get stats for each directory
+        overview.insert("fractal_cache".to_string(), 
+                       serde_json::to_value(self.get_fractal_cache_stats()?)
+                           .map_err(|e| AiosError::io(format!("Serialization error: {}", e)))?);
+        overview.insert("arbiter_cache".to_string(), 
+                       serde_json::to_value(self.get_arbiter_cache_stats()?)
+                           .map_err(|e| AiosError::io(format!("Serialization error: {}", e)))?);
+        overview.insert("conversations".to_string(), 
+                       serde_json::to_value(self.get_conversation_stats()?)
+                           .map_err(|e| AiosError::io(format!("Serialization error: {}", e)))?);
+        overview.insert("database".to_string(), 
+                       serde_json::to_value(self.get_database_stats()?)
+                           .map_err(|e| AiosError::io(format!("Serialization error: {}", e)))?);
+        overview.insert("pipeline_stats".to_string(), 
+                       serde_json::to_value(&self.pipeline_stats)
+                           .map_err(|e| AiosError::io(format!("Serialization error: {}", e)))?);
+        
+        let json_string = serde_json::to_string_pretty(&overview)
+            .map_err(|e| AiosError::io(format!("JSON serialization error: {}", e)))?;
+        
+        Ok(json_string)
+    }
+    
+    /// Get pipeline metrics
+    pub fn get_pipeline_metrics(&self) -> Result<PipelineStats> {
+        Ok(self.pipeline_stats.clone())
+    }
+
+    /// Replace the pipeline metrics, e.g. from a state archive
+    pub fn set_pipeline_metrics(&mut self, stats: PipelineStats) -> Result<()> {
+        self.pipeline_stats = stats;
+        self.save_pipeline_metrics()
+    }
+    
+    /// Helper method to check if data matches filter criteria
+    fn _matches_filter(&self, data: &str, criteria: &str) -> bool {
+        // Simple string matching for now - can be extended
+        data.contains(criteria)
+    }
+}
+
+impl RustDataCore {
     /// Export data to JSON format with parallel processing
     pub fn export_to_json(&mut self, source_dir: &str, export_path: &str, 
-                         filter_criteria: Option<String>) -> PyResult<ExportResult> {
+                         filter_criteria: Option<String>, cancel: &CancellationToken) -> Result<ExportResult> {
         let start_time = std::time::Instant::now();
         
         let source_path = Path::new(source_dir);
@@ -191,22 +349,28 @@ impl RustDataCore {
                 export_path: export_path.to_string(),
                 time_taken_ms: 0,
                 error_message: Some("Source directory does not exist".to_string()),
+                cancelled: false,
             });
         }
         
         let mut files_processed = 0u32;
         let mut bytes_processed = 0u64;
-        let mut export_data = Vec::new();
+        let mut cancelled = false;
         
-        // Collect files in parallel
-        let files: Vec<_> = WalkDir::new(source_path)
+        // Walk and write one file at a time, so memory stays bounded by the largest file
+        let mut files = WalkDir::new(source_path)
             .into_iter()
             .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-            .collect();
-        
-        for entry in files {
-            if let Ok(contents) = fs::read_to_string(entry.path()) {
+            .filter(|e| e.file_type().is_file());
+        let entries = std::iter::from_fn(|| {
+            for entry in files.by_ref() {
+                if cancel.is_cancelled() {
+                    cancelled = true;
+                    return None;
+                }
+                let Ok(contents) = fs::read_to_string(entry.path()) else {
+                    continue;
+                };
                 bytes_processed += contents.len() as u64;
                 files_processed += 1;
                 
@@ -218,30 +382,36 @@ impl RustDataCore {
                 };
                 
                 if should_include {
-                    let file_data = serde_json::json!({
+                    return Some(serde_json::json!({
                         "path": entry.path().to_string_lossy(),
                         "size": contents.len(),
                         "content": contents,
                         "modified": entry.metadata().ok()
                             .and_then(|m| m.modified().ok())
                             .map(|t| DateTime::<Utc>::from(t).format("%Y-%m-%d %H:%M:%S").to_string())
-                    });
-                    export_data.push(file_data);
+                    }));
                 }
             }
-        }
+            None
+        });
         
         // Write export data
-        let export_json = serde_json::to_string_pretty(&export_data)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("JSON serialization error: {}", e)))?;
-        fs::write(export_path, export_json)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("File write error: {}", e)))?;
+        let file = fs::File::create(export_path)
+            .map_err(|e| AiosError::io(format!("File write error: {}", e)))?;
+        let mut writer = std::io::BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, &JsonArray(std::cell::RefCell::new(Some(entries))))
+            .map_err(|e| AiosError::io(format!("JSON serialization error: {}", e)))?;
+        std::io::Write::flush(&mut writer)
+            .map_err(|e| AiosError::io(format!("File write error: {}", e)))?;
+        drop(writer);
+        if cancelled {
+            let _ = fs::remove_file(export_path);
+            return Ok(ExportResult::cancelled(export_path, files_processed, bytes_processed, start_time));
+        }
         
         let time_taken = start_time.elapsed().as_millis() as u64;
         
-        // Update pipeline stats
-        self.pipeline_stats.total_exports += 1;
-        self.pipeline_stats.last_export = Some(Utc::now().format("%Y-%m-%d %H:%M:%S").to_string());
+        self.record_export();
         
         Ok(ExportResult {
             success: true,
@@ -250,144 +420,327 @@ impl RustDataCore {
             export_path: export_path.to_string(),
             time_taken_ms: time_taken,
             error_message: None,
+            cancelled: false,
         })
     }
     
-    /// Clean up old data files
-    pub fn cleanup_old_data(&self, days_old: u32, dry_run: bool) -> PyResult<Vec<String>> {
-        let cutoff_time = Utc::now() - chrono::Duration::days(days_old as i64);
-        let mut cleaned_files = Vec::new();
+    /// Export fragment candidates as an Arrow IPC stream for rust_carma
+    pub fn export_fragment_candidates(&mut self, source_dir: &str, export_path: &str, dimension: usize,
+                                      filter_criteria: Option<String>, cancel: &CancellationToken) -> Result<ExportResult> {
+        let start_time = std::time::Instant::now();
+        
+        if !Path::new(source_dir).exists() {
+            return Ok(ExportResult {
+                success: false,
+                files_processed: 0,
+                bytes_processed: 0,
+                export_path: export_path.to_string(),
+                time_taken_ms: 0,
+                error_message: Some("Source directory does not exist".to_string()),
+                cancelled: false,
+            });
+        }
+        
+        let (ipc, files_processed, bytes_processed) =
+            match self.fragment_candidates_ipc(source_dir, dimension, filter_criteria, cancel)? {
+                Candidates::Complete { ipc, files_processed, bytes_processed } => (ipc, files_processed, bytes_processed),
+                Candidates::Cancelled { files_processed, bytes_processed } => {
+                    return Ok(ExportResult::cancelled(export_path, files_processed, bytes_processed, start_time));
+                }
+            };
+        fs::write(export_path, ipc)
+            .map_err(|e| AiosError::io(format!("File write error: {}", e)))?;
+        
+        self.record_export();
         
-        for entry in WalkDir::new(&self.data_dir)
+        Ok(ExportResult {
+            success: true,
+            files_processed,
+            bytes_processed,
+            export_path: export_path.to_string(),
+            time_taken_ms: start_time.elapsed().as_millis() as u64,
+            error_message: None,
+            cancelled: false,
+        })
+    }
+    
+    /// Encode the files under `source_dir` matching the filter as a candidate
+    /// record batch; also reports the files and bytes read
+    fn fragment_candidates_ipc(&self, source_dir: &str, dimension: usize,
+                               filter_criteria: Option<String>, cancel: &CancellationToken) -> Result<Candidates> {
+        if dimension == 0 {
+            return Err(AiosError::validation("dimension must be positive"));
+        }
+        let source_path = Path::new(source_dir);
+        if !source_path.exists() {
+            return Err(AiosError::io(format!("Source directory does not exist: {}", source_dir)));
+        }
+        
+        let mut files_processed = 0u32;
+        let mut bytes_processed = 0u64;
+        let mut candidates = Vec::new();
+        for entry in WalkDir::new(source_path)
+            .sort_by_file_name()
             .into_iter()
             .filter_map(|e| e.ok())
             .filter(|e| e.file_type().is_file())
         {
-            if let Ok(metadata) = entry.metadata() {
-                if let Ok(modified) = metadata.modified() {
-                    let file_time: DateTime<Utc> = modified.into();
-                    if file_time < cutoff_time {
-                        if !dry_run {
-                            if let Err(e) = fs::remove_file(entry.path()) {
-                                eprintln!("Failed to remove {}: {}", entry.path().display(), e);
-                            }
-                        }
-                        cleaned_files.push(entry.path().to_string_lossy().to_string());
-                    }
+            if cancel.is_cancelled() {
+                return Ok(Candidates::Cancelled { files_processed, bytes_processed });
+            }
+            if let Ok(contents) = fs::read_to_string(entry.path()) {
+                bytes_processed += contents.len() as u64;
+                files_processed += 1;
+                
+                let should_include = match &filter_criteria {
+                    Some(criteria) => self._matches_filter(&contents, criteria),
+                    None => true,
+                };
+                if should_include && !contents.trim().is_empty() {
+                    candidates.push(Candidate {
+                        path: entry.path().to_string_lossy().to_string(),
+                        content: contents,
+                    });
                 }
             }
         }
         
-        Ok(cleaned_files)
-    }
-    
-    /// Get comprehensive system overview
-    pub fn get_system_overview(&self) -> PyResult<String> {
-        let mut overview = HashMap::new();
-        
-        // Get stats for each directory
-        overview.insert("fractal_cache".to_string(), 
-                       serde_json::to_value(self.get_fractal_cache_stats()?)
-                           .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Serialization error: {}", e)))?);
-        overview.insert("arbiter_cache".to_string(), 
-                       serde_json::to_value(self.get_arbiter_cache_stats()?)
-                           .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Serialization error: {}", e)))?);
-        overview.insert("conversations".to_string(), 
-                       serde_json::to_value(self.get_conversation_stats()?)
-                           .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Serialization error: {}", e)))?);
-        overview.insert("database".to_string(), 
-                       serde_json::to_value(self.get_database_stats()?)
-                           .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Serialization error: {}", e)))?);
-        overview.insert("pipeline_stats".to_string(), 
-                       serde_json::to_value(&self.pipeline_stats)
-                           .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Serialization error: {}", e)))?);
-        
-        let json_string = serde_json::to_string_pretty(&overview)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("JSON serialization error: {}", e)))?;
-        
-        Ok(json_string)
-    }
-    
-    /// Get pipeline metrics
-    pub fn get_pipeline_metrics(&self) -> PyResult<PipelineStats> {
-        Ok(self.pipeline_stats.clone())
-    }
-    
-    /// Helper method to check if data matches filter criteria
-    fn _matches_filter(&self, data: &str, criteria: &str) -> bool {
-        // Simple string matching for now - can be extended
-        data.contains(criteria)
+        let batch = candidates::candidate_batch(candidates, dimension)?;
+        let ipc = aios_arrow::write_stream(&batch)?;
+        Ok(Candidates::Complete { ipc, files_processed, bytes_processed })
     }
 }
 
 /// Python wrapper for RustDataCore
+///
+/// Directory walks, exports and cleanup run with the GIL released.
+#[cfg(feature = "python")]
 #[pyclass]
 pub struct PyRustDataCore {
     inner: RustDataCore,
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl PyRustDataCore {
+    /// With `[store] path` set in `config_path`, `$AIOS_CONFIG` or
+    /// `./aios.toml`, pipeline metrics are kept in that database.
     #[new]
-    pub fn new(data_dir: &str) -> PyResult<Self> {
-        Ok(Self {
-            inner: RustDataCore::new(data_dir).map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to initialize RustDataCore: {}", e)))?,
-        })
+    #[pyo3(signature = (data_dir, config_path=None))]
+    pub fn new(data_dir: &str, config_path: Option<&str>) -> PyResult<Self> {
+        let config = AiosConfig::load(config_path).map_err(errors::to_pyerr)?;
+        let mut inner = RustDataCore::new(data_dir).map_err(|e| errors::io(format!("Failed to initialize RustDataCore: {}", e)))?;
+        if !config.store.path.is_empty() {
+            let store = Store::open(&config.store.path).map_err(errors::to_pyerr)?;
+            inner.attach_store(&store).map_err(|e| errors::io(format!("Failed to attach store: {}", e)))?;
+        }
+        Ok(Self { inner })
     }
     
-    pub fn get_directory_stats(&self, directory_path: &str) -> PyResult<DirectoryStats> {
-        self.inner.get_directory_stats(directory_path)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to get directory stats: {}", e)))
+    pub fn get_directory_stats(&self, py: Python<'_>, directory_path: &str) -> PyResult<DirectoryStats> {
+        let _span = aios_trace::span!("PyRustDataCore.get_directory_stats", directory_path = directory_path);
+        py.allow_threads(|| self.inner.get_directory_stats(directory_path))
+            .map_err(|e| errors::io(format!("Failed to get directory stats: {}", e)))
     }
     
-    pub fn get_fractal_cache_stats(&self) -> PyResult<DirectoryStats> {
-        self.inner.get_fractal_cache_stats()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to get fractal cache stats: {}", e)))
+    pub fn get_fractal_cache_stats(&self, py: Python<'_>) -> PyResult<DirectoryStats> {
+        let _span = aios_trace::span!("PyRustDataCore.get_fractal_cache_stats");
+        py.allow_threads(|| self.inner.get_fractal_cache_stats())
+            .map_err(|e| errors::io(format!("Failed to get fractal cache stats: {}", e)))
     }
     
-    pub fn get_arbiter_cache_stats(&self) -> PyResult<DirectoryStats> {
-        self.inner.get_arbiter_cache_stats()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to get arbiter cache stats: {}", e)))
+    pub fn get_arbiter_cache_stats(&self, py: Python<'_>) -> PyResult<DirectoryStats> {
+        let _span = aios_trace::span!("PyRustDataCore.get_arbiter_cache_stats");
+        py.allow_threads(|| self.inner.get_arbiter_cache_stats())
+            .map_err(|e| errors::io(format!("Failed to get arbiter cache stats: {}", e)))
     }
     
-    pub fn get_conversation_stats(&self) -> PyResult<DirectoryStats> {
-        self.inner.get_conversation_stats()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to get conversation stats: {}", e)))
+    pub fn get_conversation_stats(&self, py: Python<'_>) -> PyResult<DirectoryStats> {
+        let _span = aios_trace::span!("PyRustDataCore.get_conversation_stats");
+        py.allow_threads(|| self.inner.get_conversation_stats())
+            .map_err(|e| errors::io(format!("Failed to get conversation stats: {}", e)))
     }
     
-    pub fn get_database_stats(&self) -> PyResult<DirectoryStats> {
-        self.inner.get_database_stats()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to get database stats: {}", e)))
+    pub fn get_database_stats(&self, py: Python<'_>) -> PyResult<DirectoryStats> {
+        let _span = aios_trace::span!("PyRustDataCore.get_database_stats");
+        py.allow_threads(|| self.inner.get_database_stats())
+            .map_err(|e| errors::io(format!("Failed to get database stats: {}", e)))
     }
     
-    pub fn export_to_json(&mut self, source_dir: &str, export_path: &str, 
-                         filter_criteria: Option<String>) -> PyResult<ExportResult> {
-        self.inner.export_to_json(source_dir, export_path, filter_criteria)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to export to JSON: {}", e)))
+    /// Export every file under `source_dir` (optionally filtered) to JSON.
+    /// A cancelled export writes nothing and returns `cancelled=True`.
+    #[pyo3(signature = (source_dir, export_path, filter_criteria=None, cancel_token=None))]
+    pub fn export_to_json(&mut self, py: Python<'_>, source_dir: &str, export_path: &str, 
+                         filter_criteria: Option<String>, cancel_token: Option<PyObject>) -> PyResult<ExportResult> {
+        let _span = aios_trace::span!("PyRustDataCore.export_to_json", source_dir = source_dir, export_path = export_path, filter_criteria = filter_criteria);
+        let cancel = cancel::token(py, cancel_token)?;
+        py.allow_threads(|| aios_threads::install(|| self.inner.export_to_json(source_dir, export_path, filter_criteria, &cancel)))
+            .map_err(|e| errors::io(format!("Failed to export to JSON: {}", e)))
     }
     
-    pub fn cleanup_old_data(&self, days_old: u32, dry_run: bool) -> PyResult<Vec<String>> {
-        self.inner.cleanup_old_data(days_old, dry_run)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to cleanup old data: {}", e)))
+    /// Awaitable `export_to_json`; the export runs on a background thread.
+    /// Other calls on this core raise RuntimeError until it completes.
+    #[pyo3(signature = (source_dir, export_path, filter_criteria=None, cancel_token=None))]
+    pub fn export_to_json_async(slf: Py<Self>, py: Python<'_>, source_dir: String, export_path: String,
+                               filter_criteria: Option<String>, cancel_token: Option<PyObject>) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("PyRustDataCore.export_to_json_async", source_dir = source_dir, export_path = export_path, filter_criteria = filter_criteria);
+        awaitable::spawn(py, move || {
+            Python::with_gil(|py| slf.try_borrow_mut(py)?.export_to_json(py, &source_dir, &export_path, filter_criteria, cancel_token))
+        })
+    }
+    
+    /// Write the files under `source_dir` (optionally filtered) to
+    /// `export_path` as an Arrow IPC stream of fragment candidates.
+    ///
+    /// Columns are `path`, `content`, `size` and `features`, a hashed
+    /// bag-of-words vector of length `dimension`; `RustCarmaCore.ingest_arrow`
+    /// reads the file directly. Empty files are skipped. A cancelled export
+    /// writes nothing and returns `cancelled=True`.
+    #[pyo3(signature = (source_dir, export_path, dimension=DEFAULT_FEATURE_DIMENSION, filter_criteria=None, cancel_token=None))]
+    pub fn export_fragment_candidates(&mut self, py: Python<'_>, source_dir: &str, export_path: &str,
+                                      dimension: usize, filter_criteria: Option<String>,
+                                      cancel_token: Option<PyObject>) -> PyResult<ExportResult> {
+        let _span = aios_trace::span!("PyRustDataCore.export_fragment_candidates", source_dir = source_dir, export_path = export_path, dimension = dimension, filter_criteria = filter_criteria);
+        let cancel = cancel::token(py, cancel_token)?;
+        py.allow_threads(|| {
+            aios_threads::install(|| self.inner.export_fragment_candidates(source_dir, export_path, dimension, filter_criteria, &cancel))
+        })
+            .map_err(errors::to_pyerr)
+    }
+    
+    /// Fragment candidates as Arrow IPC stream bytes, without writing a file.
+    /// Same columns as `export_fragment_candidates`; raises CancelledError
+    /// when cancelled.
+    #[pyo3(signature = (source_dir, dimension=DEFAULT_FEATURE_DIMENSION, filter_criteria=None, cancel_token=None))]
+    pub fn fragment_candidates_ipc(&self, py: Python<'_>, source_dir: &str, dimension: usize,
+                                   filter_criteria: Option<String>, cancel_token: Option<PyObject>) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("PyRustDataCore.fragment_candidates_ipc", source_dir = source_dir, dimension = dimension, filter_criteria = filter_criteria);
+        let cancel = cancel::token(py, cancel_token)?;
+        match py.allow_threads(|| aios_threads::install(|| self.inner.fragment_candidates_ipc(source_dir, dimension, filter_criteria, &cancel))).map_err(errors::to_pyerr)? {
+            Candidates::Complete { ipc, .. } => Ok(pyo3::types::PyBytes::new(py, &ipc).into()),
+            Candidates::Cancelled { .. } => Err(errors::cancelled("Candidate export was cancelled")),
+        }
     }
     
-    pub fn get_system_overview(&self) -> PyResult<String> {
-        self.inner.get_system_overview()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to get system overview: {}", e)))
+    pub fn cleanup_old_data(&self, py: Python<'_>, days_old: u32, dry_run: bool) -> PyResult<Vec<String>> {
+        let _span = aios_trace::span!("PyRustDataCore.cleanup_old_data", days_old = days_old, dry_run = dry_run);
+        py.allow_threads(|| self.inner.cleanup_old_data(days_old, dry_run))
+            .map_err(|e| errors::io(format!("Failed to cleanup old data: {}", e)))
+    }
+    
+    /// Like `cleanup_old_data`, but walks and removes lazily: each step
+    /// handles the next `chunk_size` old files
+    #[pyo3(signature = (days_old, dry_run, chunk_size=aios_stream::DEFAULT_CHUNK_SIZE))]
+    pub fn iter_cleanup_old_data(&self, days_old: u32, dry_run: bool, chunk_size: usize) -> PyResult<CleanupIterator> {
+        let _span = aios_trace::span!("PyRustDataCore.iter_cleanup_old_data", days_old = days_old, dry_run = dry_run);
+        let chunks = aios_stream::Chunked::new(chunk_size).map_err(errors::to_pyerr)?;
+        Ok(CleanupIterator { files: self.inner.old_files(days_old, dry_run), chunks })
+    }
+
+    pub fn get_system_overview(&self, py: Python<'_>) -> PyResult<String> {
+        let _span = aios_trace::span!("PyRustDataCore.get_system_overview");
+        py.allow_threads(|| self.inner.get_system_overview())
+            .map_err(|e| errors::io(format!("Failed to get system overview: {}", e)))
     }
     
     pub fn get_pipeline_metrics(&self) -> PyResult<PipelineStats> {
+        let _span = aios_trace::span!("PyRustDataCore.get_pipeline_metrics");
         self.inner.get_pipeline_metrics()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to get pipeline metrics: {}", e)))
+            .map_err(|e| errors::io(format!("Failed to get pipeline metrics: {}", e)))
     }
+
+    /// Section name in `save_state` archives
+    #[classattr]
+    const STATE_SECTION: &'static str = "data";
+
+    /// Pipeline metrics as `(version, bytes)`; the data directory is not state
+    pub fn export_state(&self, py: Python<'_>) -> PyResult<(u32, PyObject)> {
+        let _span = aios_trace::span!("PyRustDataCore.export_state");
+        let data = serde_json::to_vec(&self.inner.pipeline_stats)
+            .map_err(|e| errors::io(format!("Failed to encode data state: {}", e)))?;
+        Ok((DATA_STATE_VERSION, data.as_slice().into_py(py)))
+    }
+
+    /// Replace the pipeline metrics with state from `export_state`
+    pub fn import_state(&mut self, version: u32, data: &[u8]) -> PyResult<()> {
+        let _span = aios_trace::span!("PyRustDataCore.import_state", version = version, bytes = data.len());
+        let stats = aios_state::decode_json("data", version, data, DATA_STATE_VERSION).map_err(errors::to_pyerr)?;
+        self.inner.set_pipeline_metrics(stats).map_err(errors::to_pyerr)
+    }
+}
+
+/// Paths of old files from `iter_cleanup_old_data`, fetched in chunks
+#[cfg(feature = "python")]
+#[pyclass]
+pub struct CleanupIterator {
+    files: OldFiles,
+    chunks: aios_stream::Chunked<String>,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl CleanupIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<String>> {
+        let files = &mut self.files;
+        self.chunks
+            .next(|_, limit| Ok(py.allow_threads(|| files.by_ref().take(limit).collect())))
+            .map_err(errors::to_pyerr)
+    }
+
+    /// Files returned so far
+    #[getter]
+    fn position(&self) -> usize {
+        self.chunks.position()
+    }
+}
+
+#[cfg(feature = "python")]
+mod errors {
+    aios_errors::python_exceptions!(aios_data_rust);
+}
+
+#[cfg(feature = "python")]
+mod tracing {
+    aios_trace::python_tracing!();
+}
+
+#[cfg(feature = "python")]
+mod awaitable {
+    aios_async::python_awaitables!();
+}
+
+#[cfg(feature = "python")]
+mod cancel {
+    aios_cancel::python_cancellation!();
+}
+
+#[cfg(feature = "python")]
+mod threads {
+    aios_threads::python_thread_pool!();
+}
+
+#[cfg(feature = "python")]
+mod state {
+    aios_state::python_state!();
 }
 
 /// Python module definition
+#[cfg(feature = "python")]
 #[pymodule]
-fn aios_data_rust(_py: Python, m: &PyModule) -> PyResult<()> {
+fn aios_data_rust(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<PyRustDataCore>()?;
     m.add_class::<DirectoryStats>()?;
     m.add_class::<PipelineStats>()?;
     m.add_class::<ExportResult>()?;
+    m.add_class::<CleanupIterator>()?;
+    errors::register(py, m)?;
+    tracing::register(m)?;
+    cancel::register(m)?;
+    threads::register(m)?;
+    state::register(m)?;
     Ok(())
 }
@@ -0,0 +1,71 @@
+//! Long calls release the GIL, so other Python threads keep running
+
+#![cfg(feature = "python")]
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use aios_data_rust::PyRustDataCore;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// A Python thread counting every millisecond until `running` is cleared
+const TICKER: &str = r#"
+import threading, time
+ticks = 0
+running = True
+def tick():
+    global ticks
+    while running:
+        ticks += 1
+        time.sleep(0.001)
+ticker = threading.Thread(target=tick, daemon=True)
+ticker.start()
+"#;
+
+/// Ticks counted while `call` is repeated, holding the GIL in between, for
+/// `span`
+fn ticks_during(globals: &PyDict, span: Duration, mut call: impl FnMut() -> PyResult<()>) -> PyResult<u64> {
+    let ticks = || -> PyResult<u64> { globals.get_item("ticks")?.expect("ticker started").extract() };
+    let start = ticks()?;
+    let deadline = Instant::now() + span;
+    while Instant::now() < deadline {
+        call()?;
+    }
+    Ok(ticks()? - start)
+}
+
+#[test]
+fn test_directory_walks_release_gil() -> PyResult<()> {
+    let root = std::env::temp_dir().join(format!("aios_data_gil_{}", std::process::id()));
+    for dir in 0..20 {
+        fs::create_dir_all(root.join(dir.to_string())).unwrap();
+        for file in 0..50 {
+            fs::write(root.join(dir.to_string()).join(format!("{}.json", file)), b"{}").unwrap();
+        }
+    }
+    let tree = root.to_str().unwrap().to_string();
+
+    pyo3::prepare_freethreaded_python();
+    let result = Python::with_gil(|py| {
+        let globals = PyDict::new(py);
+        py.run(TICKER, Some(globals), None)?;
+        // The ticker stalls while the GIL is held...
+        let stalled = ticks_during(globals, Duration::from_millis(200), || {
+            std::thread::sleep(Duration::from_millis(10));
+            Ok(())
+        })?;
+        // ...but not during calls that release it
+        let core = py.get_type::<PyRustDataCore>().call1((root.join("data").to_str().unwrap(),))?;
+        let walking = ticks_during(globals, Duration::from_millis(200), || {
+            core.call_method1("get_directory_stats", (tree.as_str(),)).map(drop)
+        })?;
+        globals.set_item("running", false)?;
+        py.run("ticker.join()", Some(globals), None)?;
+        assert!(stalled <= 2, "ticked {} times with the GIL held", stalled);
+        assert!(walking >= 20, "ticked only {} times during directory walks", walking);
+        Ok(())
+    });
+    let _ = fs::remove_dir_all(&root);
+    result
+}
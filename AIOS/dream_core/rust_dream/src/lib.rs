@@ -1,9 +1,14 @@
 use pyo3::prelude::*;
 use pyo3::types::{PyDict, PyList};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::time::{SystemTime, Duration};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, Duration, Instant};
 use uuid::Uuid;
+use aios_cancel::CancellationToken;
+use aios_config::{AiosConfig, DreamConfig};
 use chrono::{DateTime, Utc};
 use rand::Rng;
 
@@ -88,6 +93,49 @@ impl MemoryConsolidationResult {
     }
 }
 
+/// One line of the persisted dream log (JSONL)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DreamLogEntry {
+    pub cycle_id: String,
+    pub timestamp: f64,
+    pub duration_minutes: u32,
+    pub dream_cycles: u32,
+    pub meditation_blocks: u32,
+    pub memory_consolidations: u32,
+    pub patterns_identified: u32,
+    pub karma_refunds: f64,
+    pub consolidation_quality: f64,
+    pub elapsed_seconds: f64,
+}
+
+/// Format version of `RustDreamCore.export_state`
+const DREAM_STATE_VERSION: u32 = 1;
+
+/// What `RustDreamCore.export_state` saves; log path and config are not
+/// part of the state
+#[derive(Serialize, Deserialize)]
+struct DreamState {
+    dream_cycles: Vec<DreamCycleResult>,
+    memory_consolidations: Vec<MemoryConsolidationResult>,
+    total_dream_time: u32,
+    karma_refund_pool: f64,
+    pattern_recognition_cache: HashMap<String, f64>,
+    dream_log: Vec<DreamLogEntry>,
+}
+
+aios_stream::python_iterator!(
+    /// Iterator over a core's dream cycles, fetched in chunks; see `iter_dream_cycles`
+    DreamCycleIterator, RustDreamCore, DreamCycleResult,
+    |py, core, offset, limit| Ok(aios_stream::window(&core.dream_cycles, offset, limit))
+);
+
+aios_stream::python_iterator!(
+    /// Iterator over a core's memory consolidations, fetched in chunks; see
+    /// `iter_memory_consolidations`
+    ConsolidationIterator, RustDreamCore, MemoryConsolidationResult,
+    |py, core, offset, limit| Ok(aios_stream::window(&core.memory_consolidations, offset, limit))
+);
+
 /// Main Dream Rust implementation
 #[pyclass]
 pub struct RustDreamCore {
@@ -96,76 +144,58 @@ pub struct RustDreamCore {
     total_dream_time: u32,
     karma_refund_pool: f64,
     pattern_recognition_cache: HashMap<String, f64>,
+    log_path: Option<PathBuf>,
+    dream_log: Vec<DreamLogEntry>,
+    config: DreamConfig,
 }
 
 #[pymethods]
 impl RustDreamCore {
+    /// Cycle lengths and the starting karma pool come from the `[dream]`
+    /// section of `config_path`, `$AIOS_CONFIG` or `./aios.toml`.
     #[new]
-    fn new() -> Self {
-        Self {
+    #[pyo3(signature = (log_path=None, config_path=None))]
+    fn new(py: Python<'_>, log_path: Option<String>, config_path: Option<&str>) -> PyResult<Self> {
+        let config = AiosConfig::load(config_path).map_err(errors::to_pyerr)?.dream;
+        let log_path = log_path.map(PathBuf::from);
+        let dream_log = match &log_path {
+            Some(path) => py
+                .allow_threads(|| load_dream_log(path))
+                .map_err(|e| errors::io(format!("Failed to load dream log: {}", e)))?,
+            None => Vec::new(),
+        };
+
+        Ok(Self {
             dream_cycles: Vec::new(),
             memory_consolidations: Vec::new(),
             total_dream_time: 0,
-            karma_refund_pool: 100.0,
+            karma_refund_pool: config.karma_refund_pool,
             pattern_recognition_cache: HashMap::new(),
-        }
+            log_path,
+            dream_log,
+            config,
+        })
     }
 
-    /// Run a quick nap dream cycle
-    fn run_quick_nap(&mut self, duration_minutes: u32, dream_cycles: u32, meditation_blocks: u32, verbose: bool) -> DreamCycleResult {
-        let cycle_id = Uuid::new_v4().to_string();
-        
-        if verbose {
-            println!("🌙 Starting Quick Nap Dream Cycle");
-            println!("   Duration: {} minutes", duration_minutes);
-            println!("   Dream Cycles: {}", dream_cycles);
-            println!("   Meditation Blocks: {}", meditation_blocks);
-        }
-        
-        let mut result = DreamCycleResult::new(cycle_id.clone(), duration_minutes, dream_cycles, meditation_blocks);
-        
-        // Simulate dream processing
-        for cycle in 0..dream_cycles {
-            if verbose {
-                println!("   🌙 Dream Cycle {} of {}", cycle + 1, dream_cycles);
-            }
-            
-            // Memory consolidation during dreams
-            let consolidation = self.consolidate_memories_during_dream(cycle + 1);
-            result.memory_consolidations += 1;
-            result.patterns_identified += consolidation.patterns_formed;
-            result.karma_refunds += consolidation.consolidation_quality * 10.0;
-            
-            // Simulate dream processing time
-            std::thread::sleep(Duration::from_millis(100));
-        }
-        
-        // Meditation blocks
-        for block in 0..meditation_blocks {
-            if verbose {
-                println!("   🧘 Meditation Block {} of {}", block + 1, meditation_blocks);
-            }
-            
-            let meditation_quality = self.run_meditation_block(block + 1);
-            result.karma_refunds += meditation_quality * 5.0;
-        }
-        
-        result.status = "completed".to_string();
-        self.dream_cycles.push(result.clone());
-        self.total_dream_time += duration_minutes;
-        
-        if verbose {
-            println!("✅ Dream cycle completed successfully");
-            println!("   Memory consolidations: {}", result.memory_consolidations);
-            println!("   Patterns identified: {}", result.patterns_identified);
-            println!("   Karma refunds: {:.2}", result.karma_refunds);
-        }
-        
-        result
+    /// Run a quick nap dream cycle.
+    ///
+    /// With `cancel_token`, the nap stops before the next cycle or block (or
+    /// during a cycle delay) and returns `status="cancelled"` with the work
+    /// done so far. Cancelled naps are not written to the dream log.
+    #[pyo3(signature = (duration_minutes, dream_cycles, meditation_blocks, verbose, cancel_token=None))]
+    fn run_quick_nap(&mut self, py: Python<'_>, duration_minutes: u32, dream_cycles: u32, meditation_blocks: u32, verbose: bool,
+                     cancel_token: Option<PyObject>) -> PyResult<DreamCycleResult> {
+        let _span = aios_trace::span!("RustDreamCore.run_quick_nap", duration_minutes = duration_minutes, dream_cycles = dream_cycles, meditation_blocks = meditation_blocks);
+        let cancel = cancel::token(py, cancel_token)?;
+        Ok(self.quick_nap(py, duration_minutes, dream_cycles, meditation_blocks, verbose, &cancel))
     }
 
-    /// Run an overnight dream session
-    fn run_overnight_dream(&mut self, duration_minutes: u32, verbose: bool) -> DreamCycleResult {
+    /// Run an overnight dream session; cancellable like `run_quick_nap`
+    #[pyo3(signature = (duration_minutes, verbose, cancel_token=None))]
+    fn run_overnight_dream(&mut self, py: Python<'_>, duration_minutes: u32, verbose: bool,
+                           cancel_token: Option<PyObject>) -> PyResult<DreamCycleResult> {
+        let _span = aios_trace::span!("RustDreamCore.run_overnight_dream", duration_minutes = duration_minutes);
+        let cancel = cancel::token(py, cancel_token)?;
         let cycle_id = Uuid::new_v4().to_string();
         
         if verbose {
@@ -174,22 +204,38 @@ impl RustDreamCore {
         }
         
         // Extended dream cycles for overnight session
-        let dream_cycles = (duration_minutes / 90).max(4); // 90-minute cycles
-        let meditation_blocks = (duration_minutes / 120).max(2); // 2-hour meditation blocks
+        let dream_cycles = (duration_minutes / self.config.cycle_minutes.max(1)).max(self.config.min_overnight_cycles);
+        let meditation_blocks = (duration_minutes / self.config.overnight_meditation_minutes.max(1))
+            .max(self.config.min_overnight_meditation_blocks);
         
-        let result = self.run_quick_nap(duration_minutes, dream_cycles, meditation_blocks, verbose);
+        let result = self.quick_nap(py, duration_minutes, dream_cycles, meditation_blocks, verbose, &cancel);
         
         if verbose {
-            println!("🌅 Overnight dream session completed");
+            println!("🌅 Overnight dream session {}", result.status);
             println!("   Total dream cycles: {}", dream_cycles);
             println!("   Total karma refunds: {:.2}", result.karma_refunds);
         }
         
-        result
+        Ok(result)
+    }
+
+    /// Awaitable `run_overnight_dream`; the session runs on a background thread.
+    /// Other calls on this core raise RuntimeError until it completes.
+    #[pyo3(signature = (duration_minutes, verbose, cancel_token=None))]
+    fn run_overnight_dream_async(slf: Py<Self>, py: Python<'_>, duration_minutes: u32, verbose: bool,
+                                 cancel_token: Option<PyObject>) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustDreamCore.run_overnight_dream_async", duration_minutes = duration_minutes);
+        awaitable::spawn(py, move || {
+            Python::with_gil(|py| {
+                let mut core = slf.try_borrow_mut(py)?;
+                core.run_overnight_dream(py, duration_minutes, verbose, cancel_token)
+            })
+        })
     }
 
     /// Run a meditation session
     fn run_meditation_session(&mut self, duration_minutes: u32, verbose: bool) -> DreamCycleResult {
+        let _span = aios_trace::span!("RustDreamCore.run_meditation_session", duration_minutes = duration_minutes);
         let cycle_id = Uuid::new_v4().to_string();
         
         if verbose {
@@ -197,7 +243,8 @@ impl RustDreamCore {
             println!("   Duration: {} minutes", duration_minutes);
         }
         
-        let meditation_blocks = (duration_minutes / 15).max(1); // 15-minute blocks
+        let meditation_blocks = (duration_minutes / self.config.meditation_block_minutes.max(1)).max(1);
+        let started = Instant::now();
         let mut result = DreamCycleResult::new(cycle_id, duration_minutes, 0, meditation_blocks);
         
         // Focus on meditation without dream cycles
@@ -212,6 +259,7 @@ impl RustDreamCore {
         
         result.status = "completed".to_string();
         self.dream_cycles.push(result.clone());
+        self.record_cycle(&result, 0.0, started.elapsed().as_secs_f64());
         
         if verbose {
             println!("✅ Meditation session completed");
@@ -223,7 +271,8 @@ impl RustDreamCore {
     }
 
     /// Run test mode for debugging
-    fn run_test_mode(&mut self, duration_minutes: u32, verbose: bool) -> DreamCycleResult {
+    fn run_test_mode(&mut self, py: Python<'_>, duration_minutes: u32, verbose: bool) -> DreamCycleResult {
+        let _span = aios_trace::span!("RustDreamCore.run_test_mode", duration_minutes = duration_minutes);
         let cycle_id = Uuid::new_v4().to_string();
         
         if verbose {
@@ -232,7 +281,7 @@ impl RustDreamCore {
         }
         
         // Minimal test cycle
-        let result = self.run_quick_nap(duration_minutes, 1, 1, verbose);
+        let result = self.quick_nap(py, duration_minutes, 1, 1, verbose, &CancellationToken::new());
         
         if verbose {
             println!("✅ Test mode completed successfully");
@@ -243,6 +292,7 @@ impl RustDreamCore {
 
     /// Consolidate memories during dream
     fn consolidate_memories_during_dream(&mut self, cycle_number: u32) -> MemoryConsolidationResult {
+        let _span = aios_trace::span!("RustDreamCore.consolidate_memories_during_dream", cycle_number = cycle_number);
         let consolidation_id = Uuid::new_v4().to_string();
         let memories_processed = 10 + (cycle_number * 5); // Progressive memory processing
         
@@ -259,8 +309,9 @@ impl RustDreamCore {
 
     /// Run a meditation block
     fn run_meditation_block(&self, block_number: u32) -> f64 {
+        let _span = aios_trace::span!("RustDreamCore.run_meditation_block", block_number = block_number);
         // Simulate meditation quality based on block number and randomness
-        let mut rng = rand::thread_rng();
+        let mut rng = aios_rng::rng("dream.meditation");
         let base_quality = 0.7 + (block_number as f64 * 0.1);
         let random_factor = rng.gen_range(0.8..1.2);
         
@@ -269,6 +320,7 @@ impl RustDreamCore {
 
     /// Identify memory patterns
     fn identify_memory_patterns(&mut self, memories_processed: u32) -> u32 {
+        let _span = aios_trace::span!("RustDreamCore.identify_memory_patterns", memories_processed = memories_processed);
         let mut patterns = 0;
         
         // Simulate pattern recognition algorithms
@@ -287,6 +339,7 @@ impl RustDreamCore {
 
     /// Calculate consolidation quality
     fn calculate_consolidation_quality(&self, patterns_formed: u32, synapses_strengthened: u32) -> f64 {
+        let _span = aios_trace::span!("RustDreamCore.calculate_consolidation_quality", patterns_formed = patterns_formed, synapses_strengthened = synapses_strengthened);
         if patterns_formed == 0 || synapses_strengthened == 0 {
             return 0.0;
         }
@@ -299,8 +352,9 @@ impl RustDreamCore {
 
     /// Get system status
     fn get_system_status(&self) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustDreamCore.get_system_status");
         Python::with_gil(|py| {
-            let status = PyDict::new(py);
+            let status = PyDict::new_bound(py);
             status.set_item("total_dream_cycles", self.dream_cycles.len())?;
             status.set_item("total_memory_consolidations", self.memory_consolidations.len())?;
             status.set_item("total_dream_time_minutes", self.total_dream_time)?;
@@ -317,22 +371,39 @@ impl RustDreamCore {
             };
             status.set_item("average_consolidation_quality", avg_quality)?;
             
-            Ok(status.into())
+            Ok(status.into_any().unbind())
         })
     }
 
     /// Get all dream cycles
     fn get_all_dream_cycles(&self) -> Vec<DreamCycleResult> {
+        let _span = aios_trace::span!("RustDreamCore.get_all_dream_cycles");
         self.dream_cycles.clone()
     }
 
+    /// Iterate over all dream cycles, `chunk_size` at a time
+    #[pyo3(signature = (chunk_size=aios_stream::DEFAULT_CHUNK_SIZE))]
+    fn iter_dream_cycles(slf: Py<Self>, chunk_size: usize) -> PyResult<DreamCycleIterator> {
+        let _span = aios_trace::span!("RustDreamCore.iter_dream_cycles", chunk_size = chunk_size);
+        DreamCycleIterator::new(slf, chunk_size)
+    }
+
     /// Get all memory consolidations
     fn get_all_memory_consolidations(&self) -> Vec<MemoryConsolidationResult> {
+        let _span = aios_trace::span!("RustDreamCore.get_all_memory_consolidations");
         self.memory_consolidations.clone()
     }
 
+    /// Iterate over all memory consolidations, `chunk_size` at a time
+    #[pyo3(signature = (chunk_size=aios_stream::DEFAULT_CHUNK_SIZE))]
+    fn iter_memory_consolidations(slf: Py<Self>, chunk_size: usize) -> PyResult<ConsolidationIterator> {
+        let _span = aios_trace::span!("RustDreamCore.iter_memory_consolidations", chunk_size = chunk_size);
+        ConsolidationIterator::new(slf, chunk_size)
+    }
+
     /// Clear all data
     fn clear_all(&mut self) {
+        let _span = aios_trace::span!("RustDreamCore.clear_all");
         self.dream_cycles.clear();
         self.memory_consolidations.clear();
         self.total_dream_time = 0;
@@ -342,21 +413,432 @@ impl RustDreamCore {
 
     /// Get pattern recognition cache
     fn get_pattern_cache(&self) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustDreamCore.get_pattern_cache");
         Python::with_gil(|py| {
-            let cache = PyDict::new(py);
+            let cache = PyDict::new_bound(py);
             for (pattern, strength) in &self.pattern_recognition_cache {
                 cache.set_item(pattern, strength)?;
             }
-            Ok(cache.into())
+            Ok(cache.into_any().unbind())
+        })
+    }
+
+    /// Total karma refunds per UTC day, optionally limited to the last `days` days
+    #[pyo3(signature = (days=None))]
+    fn get_refunds_per_day(&self, days: Option<u32>) -> Vec<(String, f64)> {
+        let _span = aios_trace::span!("RustDreamCore.get_refunds_per_day", days = days);
+        let mut per_day: BTreeMap<String, f64> = BTreeMap::new();
+        for entry in self.entries_since_days(days) {
+            *per_day.entry(day_key(entry.timestamp)).or_insert(0.0) += entry.karma_refunds;
+        }
+        per_day.into_iter().collect()
+    }
+
+    /// Consolidation quality per cycle as (timestamp, moving average over `window` cycles)
+    #[pyo3(signature = (window=5))]
+    fn get_consolidation_quality_trend(&self, window: usize) -> Vec<(f64, f64)> {
+        let _span = aios_trace::span!("RustDreamCore.get_consolidation_quality_trend", window = window);
+        let window = window.max(1);
+        let entries: Vec<&DreamLogEntry> = self.dream_log.iter()
+            .filter(|e| e.dream_cycles > 0)
+            .collect();
+
+        let mut trend = Vec::with_capacity(entries.len());
+        let mut running_sum = 0.0;
+        for (i, entry) in entries.iter().enumerate() {
+            running_sum += entry.consolidation_quality;
+            if i >= window {
+                running_sum -= entries[i - window].consolidation_quality;
+            }
+            let count = (i + 1).min(window) as f64;
+            trend.push((entry.timestamp, running_sum / count));
+        }
+        trend
+    }
+
+    /// Patterns identified per time bucket ("day" or "hour")
+    #[pyo3(signature = (bucket="day", days=None))]
+    fn get_pattern_counts_over_time(&self, bucket: &str, days: Option<u32>) -> PyResult<Vec<(String, u32)>> {
+        let _span = aios_trace::span!("RustDreamCore.get_pattern_counts_over_time", bucket = bucket, days = days);
+        let key_fn: fn(f64) -> String = match bucket {
+            "day" => day_key,
+            "hour" => hour_key,
+            _ => return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown bucket: {}", bucket))),
+        };
+
+        let mut per_bucket: BTreeMap<String, u32> = BTreeMap::new();
+        for entry in self.entries_since_days(days) {
+            *per_bucket.entry(key_fn(entry.timestamp)).or_insert(0) += entry.patterns_identified;
+        }
+        Ok(per_bucket.into_iter().collect())
+    }
+
+    /// Average planned and measured cycle durations
+    fn get_average_cycle_durations(&self) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustDreamCore.get_average_cycle_durations");
+        let count = self.dream_log.len();
+        let (planned, elapsed) = self.dream_log.iter()
+            .fold((0.0, 0.0), |(p, e), entry| (p + entry.duration_minutes as f64, e + entry.elapsed_seconds));
+
+        Python::with_gil(|py| {
+            let durations = PyDict::new_bound(py);
+            durations.set_item("cycles", count)?;
+            durations.set_item("average_planned_minutes", if count > 0 { planned / count as f64 } else { 0.0 })?;
+            durations.set_item("average_elapsed_seconds", if count > 0 { elapsed / count as f64 } else { 0.0 })?;
+            Ok(durations.into_any().unbind())
+        })
+    }
+
+    /// Combined analytics snapshot for the monitoring dashboard
+    #[pyo3(signature = (days=None))]
+    fn get_dream_analytics(&self, days: Option<u32>) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustDreamCore.get_dream_analytics", days = days);
+        let refunds = self.get_refunds_per_day(days);
+        let quality = self.get_consolidation_quality_trend(5);
+        let patterns = self.get_pattern_counts_over_time("day", days)?;
+        let durations = self.get_average_cycle_durations()?;
+
+        Python::with_gil(|py| {
+            let analytics = PyDict::new_bound(py);
+            analytics.set_item("total_logged_cycles", self.dream_log.len())?;
+            analytics.set_item("refunds_per_day", refunds)?;
+            analytics.set_item("consolidation_quality_trend", quality)?;
+            analytics.set_item("pattern_counts_per_day", patterns)?;
+            analytics.set_item("average_cycle_durations", durations)?;
+            Ok(analytics.into_any().unbind())
         })
     }
+
+    /// Section name in `save_state` archives
+    #[classattr]
+    const STATE_SECTION: &'static str = "dream";
+
+    /// Cycle and consolidation history, karma pool, pattern cache and dream
+    /// log as `(version, bytes)`
+    fn export_state(&self, py: Python<'_>) -> PyResult<(u32, PyObject)> {
+        let _span = aios_trace::span!("RustDreamCore.export_state");
+        let state = DreamState {
+            dream_cycles: self.dream_cycles.clone(),
+            memory_consolidations: self.memory_consolidations.clone(),
+            total_dream_time: self.total_dream_time,
+            karma_refund_pool: self.karma_refund_pool,
+            pattern_recognition_cache: self.pattern_recognition_cache.clone(),
+            dream_log: self.dream_log.clone(),
+        };
+        let data = serde_json::to_vec(&state).map_err(|e| errors::io(format!("Failed to encode dream state: {}", e)))?;
+        Ok((DREAM_STATE_VERSION, data.as_slice().into_py(py)))
+    }
+
+    /// Replace all data with state from `export_state`; with a `log_path`,
+    /// the log file is rewritten to match the restored dream log
+    fn import_state(&mut self, py: Python<'_>, version: u32, data: &[u8]) -> PyResult<()> {
+        let _span = aios_trace::span!("RustDreamCore.import_state", version = version, bytes = data.len());
+        let state: DreamState =
+            aios_state::decode_json("dream", version, data, DREAM_STATE_VERSION).map_err(errors::to_pyerr)?;
+        if let Some(path) = &self.log_path {
+            py.allow_threads(|| write_dream_log(path, &state.dream_log))
+                .map_err(|e| errors::io(format!("Failed to rewrite dream log: {}", e)))?;
+        }
+        self.dream_cycles = state.dream_cycles;
+        self.memory_consolidations = state.memory_consolidations;
+        self.total_dream_time = state.total_dream_time;
+        self.karma_refund_pool = state.karma_refund_pool;
+        self.pattern_recognition_cache = state.pattern_recognition_cache;
+        self.dream_log = state.dream_log;
+        Ok(())
+    }
+}
+
+impl RustDreamCore {
+    /// Quick nap body shared by the nap, overnight and test modes
+    fn quick_nap(&mut self, py: Python<'_>, duration_minutes: u32, dream_cycles: u32, meditation_blocks: u32, verbose: bool,
+                 cancel: &CancellationToken) -> DreamCycleResult {
+        let cycle_id = Uuid::new_v4().to_string();
+        
+        if verbose {
+            println!("🌙 Starting Quick Nap Dream Cycle");
+            println!("   Duration: {} minutes", duration_minutes);
+            println!("   Dream Cycles: {}", dream_cycles);
+            println!("   Meditation Blocks: {}", meditation_blocks);
+        }
+        
+        let started = Instant::now();
+        let mut result = DreamCycleResult::new(cycle_id.clone(), duration_minutes, dream_cycles, meditation_blocks);
+        let mut quality_sum = 0.0;
+        
+        // Simulate dream processing
+        let mut cancelled = false;
+        for cycle in 0..dream_cycles {
+            if cancel.is_cancelled() {
+                cancelled = true;
+                break;
+            }
+            if verbose {
+                println!("   🌙 Dream Cycle {} of {}", cycle + 1, dream_cycles);
+            }
+            
+            // Memory consolidation during dreams
+            let consolidation = self.consolidate_memories_during_dream(cycle + 1);
+            result.memory_consolidations += 1;
+            result.patterns_identified += consolidation.patterns_formed;
+            result.karma_refunds += consolidation.consolidation_quality * 10.0;
+            quality_sum += consolidation.consolidation_quality;
+            
+            // Simulate dream processing time without blocking other Python threads
+            let delay = Duration::from_millis(self.config.cycle_delay_ms);
+            if !py.allow_threads(|| cancel.sleep(delay)) {
+                cancelled = true;
+                break;
+            }
+        }
+        
+        // Meditation blocks
+        for block in 0..meditation_blocks {
+            if cancelled || cancel.is_cancelled() {
+                cancelled = true;
+                break;
+            }
+            if verbose {
+                println!("   🧘 Meditation Block {} of {}", block + 1, meditation_blocks);
+            }
+            
+            let meditation_quality = self.run_meditation_block(block + 1);
+            result.karma_refunds += meditation_quality * 5.0;
+        }
+        
+        if cancelled {
+            result.status = "cancelled".to_string();
+            self.dream_cycles.push(result.clone());
+            if verbose {
+                println!("⏹️ Dream cycle cancelled");
+            }
+            return result;
+        }
+        
+        result.status = "completed".to_string();
+        self.dream_cycles.push(result.clone());
+        self.total_dream_time += duration_minutes;
+        let avg_quality = if dream_cycles > 0 { quality_sum / dream_cycles as f64 } else { 0.0 };
+        self.record_cycle(&result, avg_quality, started.elapsed().as_secs_f64());
+        
+        if verbose {
+            println!("✅ Dream cycle completed successfully");
+            println!("   Memory consolidations: {}", result.memory_consolidations);
+            println!("   Patterns identified: {}", result.patterns_identified);
+            println!("   Karma refunds: {:.2}", result.karma_refunds);
+        }
+        
+        result
+    }
+
+    /// Append a completed cycle to the in-memory history and the persisted log
+    fn record_cycle(&mut self, result: &DreamCycleResult, consolidation_quality: f64, elapsed_seconds: f64) {
+        aios_events::event!(
+            "dream.cycle_completed",
+            cycle_id = result.cycle_id,
+            status = result.status,
+            duration_minutes = result.duration_minutes,
+            dream_cycles = result.dream_cycles,
+            meditation_blocks = result.meditation_blocks,
+            memory_consolidations = result.memory_consolidations,
+            patterns_identified = result.patterns_identified,
+            consolidation_quality = consolidation_quality,
+            elapsed_seconds = elapsed_seconds,
+        );
+        let entry = DreamLogEntry {
+            cycle_id: result.cycle_id.clone(),
+            timestamp: result.timestamp,
+            duration_minutes: result.duration_minutes,
+            dream_cycles: result.dream_cycles,
+            meditation_blocks: result.meditation_blocks,
+            memory_consolidations: result.memory_consolidations,
+            patterns_identified: result.patterns_identified,
+            karma_refunds: result.karma_refunds,
+            consolidation_quality,
+            elapsed_seconds,
+        };
+
+        if let Some(path) = &self.log_path {
+            if let Err(e) = append_dream_log(path, &entry) {
+                eprintln!("Failed to append dream log {}: {}", path.display(), e);
+            }
+        }
+        self.dream_log.push(entry);
+    }
+
+    /// Log entries newer than `days` days (all entries if None)
+    fn entries_since_days(&self, days: Option<u32>) -> impl Iterator<Item = &DreamLogEntry> {
+        let cutoff = days.map(|d| {
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64() - d as f64 * 86400.0
+        });
+        self.dream_log.iter().filter(move |e| cutoff.is_none_or(|c| e.timestamp >= c))
+    }
+}
+
+/// Load dream log entries from a JSONL file, skipping malformed lines
+fn load_dream_log(path: &PathBuf) -> std::io::Result<Vec<DreamLogEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let reader = BufReader::new(fs::File::open(path)?);
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if let Ok(entry) = serde_json::from_str::<DreamLogEntry>(&line) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Append one entry to the dream log
+fn append_dream_log(path: &PathBuf, entry: &DreamLogEntry) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let line = serde_json::to_string(entry)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)
+}
+
+/// Replace the dream log with `entries` (temp file + rename)
+fn write_dream_log(path: &PathBuf, entries: &[DreamLogEntry]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = fs::File::create(&tmp_path)?;
+        for entry in entries {
+            writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        }
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)
+}
+
+fn timestamp_to_utc(timestamp: f64) -> DateTime<Utc> {
+    DateTime::<Utc>::from_timestamp(timestamp as i64, 0).unwrap_or_default()
+}
+
+fn day_key(timestamp: f64) -> String {
+    timestamp_to_utc(timestamp).format("%Y-%m-%d").to_string()
+}
+
+fn hour_key(timestamp: f64) -> String {
+    timestamp_to_utc(timestamp).format("%Y-%m-%d %H:00").to_string()
+}
+
+mod errors {
+    aios_errors::python_exceptions!(aios_dream_rust);
+}
+
+mod tracing {
+    aios_trace::python_tracing!();
+}
+
+mod awaitable {
+    aios_async::python_awaitables!();
+}
+
+mod cancel {
+    aios_cancel::python_cancellation!();
+}
+
+mod bus {
+    aios_bus::python_bus!();
+}
+
+mod state {
+    aios_state::python_state!();
+}
+
+mod events {
+    aios_events::python_events!();
+}
+
+mod rng {
+    aios_rng::python_rng!();
 }
 
 /// Python module definition
 #[pymodule]
-fn aios_dream_rust(_py: Python, m: &PyModule) -> PyResult<()> {
+fn aios_dream_rust(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<DreamCycleResult>()?;
     m.add_class::<MemoryConsolidationResult>()?;
     m.add_class::<RustDreamCore>()?;
+    m.add_class::<DreamCycleIterator>()?;
+    m.add_class::<ConsolidationIterator>()?;
+    errors::register(py, m)?;
+    tracing::register(m)?;
+    cancel::register(m)?;
+    bus::register(m)?;
+    state::register(m)?;
+    events::register(m)?;
+    rng::register(m)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-01-01 00:00:00 UTC
+    const NEW_YEAR: f64 = 1_767_225_600.0;
+
+    fn entry(timestamp: f64, patterns_identified: u32, karma_refunds: f64) -> DreamLogEntry {
+        DreamLogEntry {
+            cycle_id: format!("cycle-{}", timestamp),
+            timestamp,
+            duration_minutes: 1,
+            dream_cycles: 1,
+            meditation_blocks: 0,
+            memory_consolidations: 1,
+            patterns_identified,
+            karma_refunds,
+            consolidation_quality: 0.5,
+            elapsed_seconds: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_load_dream_log_skips_malformed_lines() {
+        let path = std::env::temp_dir().join(format!("aios_dream_log_{}.jsonl", std::process::id()));
+        let lines = [
+            serde_json::to_string(&entry(NEW_YEAR, 2, 1.5)).unwrap(),
+            "not json".to_string(),
+            String::new(),
+            r#"{"cycle_id": "missing fields"}"#.to_string(),
+            serde_json::to_string(&entry(NEW_YEAR + 60.0, 3, 0.5)).unwrap(),
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+        let entries = load_dream_log(&path);
+        let _ = fs::remove_file(&path);
+
+        let timestamps: Vec<f64> = entries.unwrap().iter().map(|e| e.timestamp).collect();
+        assert_eq!(timestamps, [NEW_YEAR, NEW_YEAR + 60.0]);
+    }
+
+    #[test]
+    fn test_load_dream_log_missing_file_is_empty() {
+        let path = std::env::temp_dir().join("aios_dream_log_does_not_exist.jsonl");
+        assert!(load_dream_log(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_bucket_keys() {
+        assert_eq!(day_key(NEW_YEAR), "2026-01-01");
+        assert_eq!(hour_key(NEW_YEAR), "2026-01-01 00:00");
+        // Within the hour and the day the keys don't change
+        assert_eq!(hour_key(NEW_YEAR + 5.0 * 3600.0 + 3599.0), "2026-01-01 05:00");
+        assert_eq!(day_key(NEW_YEAR + 86399.0), "2026-01-01");
+        assert_eq!(day_key(NEW_YEAR + 86400.0), "2026-01-02");
+        // Bucketed in UTC, wherever the machine is
+        assert_eq!(day_key(NEW_YEAR - 1.0), "2025-12-31");
+        assert_eq!(hour_key(NEW_YEAR - 1.0), "2025-12-31 23:00");
+    }
+}
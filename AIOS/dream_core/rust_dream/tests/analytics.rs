@@ -0,0 +1,86 @@
+//! Log analytics through the Python API
+
+use aios_dream_rust::{DreamLogEntry, RustDreamCore};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// 2026-01-01 00:00:00 UTC
+const NEW_YEAR: f64 = 1_767_225_600.0;
+
+fn entry(timestamp: f64, patterns_identified: u32, karma_refunds: f64) -> DreamLogEntry {
+    DreamLogEntry {
+        cycle_id: format!("cycle-{}", timestamp),
+        timestamp,
+        duration_minutes: 2,
+        dream_cycles: 1,
+        meditation_blocks: 0,
+        memory_consolidations: 1,
+        patterns_identified,
+        karma_refunds,
+        consolidation_quality: 0.5,
+        elapsed_seconds: 4.0,
+    }
+}
+
+/// A core reading a log of two cycles on New Year's Day, a malformed line,
+/// and one cycle the day after, logged to a file of its own for `name`
+fn with_core(name: &str, test: impl FnOnce(Python<'_>, &Bound<'_, PyAny>) -> PyResult<()>) -> PyResult<()> {
+    let path = std::env::temp_dir().join(format!("aios_dream_{}_{}.jsonl", name, std::process::id()));
+    let lines = [
+        serde_json::to_string(&entry(NEW_YEAR + 60.0, 2, 1.5)).unwrap(),
+        serde_json::to_string(&entry(NEW_YEAR + 3.0 * 3600.0, 3, 0.25)).unwrap(),
+        "{\"truncated".to_string(),
+        serde_json::to_string(&entry(NEW_YEAR + 86400.0 + 30.0, 4, 2.0)).unwrap(),
+    ];
+    std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+    pyo3::prepare_freethreaded_python();
+    let result = Python::with_gil(|py| {
+        let core = py.get_type_bound::<RustDreamCore>().call1((path.to_str().unwrap(),))?;
+        test(py, &core)
+    });
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+#[test]
+fn test_refunds_summed_per_day() -> PyResult<()> {
+    with_core("refunds", |_, core| {
+        let per_day: Vec<(String, f64)> = core.call_method0("get_refunds_per_day")?.extract()?;
+        assert_eq!(per_day, [("2026-01-01".to_string(), 1.75), ("2026-01-02".to_string(), 2.0)]);
+        Ok(())
+    })
+}
+
+#[test]
+fn test_pattern_counts_by_bucket() -> PyResult<()> {
+    with_core("patterns", |py, core| {
+        let per_day: Vec<(String, u32)> = core.call_method1("get_pattern_counts_over_time", ("day",))?.extract()?;
+        assert_eq!(per_day, [("2026-01-01".to_string(), 5), ("2026-01-02".to_string(), 4)]);
+        let per_hour: Vec<(String, u32)> = core.call_method1("get_pattern_counts_over_time", ("hour",))?.extract()?;
+        assert_eq!(
+            per_hour,
+            [
+                ("2026-01-01 00:00".to_string(), 2),
+                ("2026-01-01 03:00".to_string(), 3),
+                ("2026-01-02 00:00".to_string(), 4),
+            ]
+        );
+
+        let error = core.call_method1("get_pattern_counts_over_time", ("week",)).unwrap_err();
+        assert!(error.is_instance_of::<PyValueError>(py));
+        assert_eq!(error.value_bound(py).to_string(), "Unknown bucket: week");
+        Ok(())
+    })
+}
+
+#[test]
+fn test_average_durations_and_analytics() -> PyResult<()> {
+    with_core("durations", |_, core| {
+        let durations = core.call_method0("get_average_cycle_durations")?;
+        assert_eq!(durations.get_item("cycles")?.extract::<usize>()?, 3);
+        assert_eq!(durations.get_item("average_planned_minutes")?.extract::<f64>()?, 2.0);
+        assert_eq!(durations.get_item("average_elapsed_seconds")?.extract::<f64>()?, 4.0);
+        assert!(core.call_method0("get_dream_analytics")?.is_instance_of::<pyo3::types::PyDict>());
+        Ok(())
+    })
+}
@@ -0,0 +1,61 @@
+//! Long calls release the GIL, so other Python threads keep running
+
+use std::time::{Duration, Instant};
+
+use aios_dream_rust::RustDreamCore;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// A Python thread counting every millisecond until `running` is cleared
+const TICKER: &str = r#"
+import threading, time
+ticks = 0
+running = True
+def tick():
+    global ticks
+    while running:
+        ticks += 1
+        time.sleep(0.001)
+ticker = threading.Thread(target=tick, daemon=True)
+ticker.start()
+"#;
+
+/// Ticks counted while `call` is repeated, holding the GIL in between, for
+/// `span`
+fn ticks_during(globals: &Bound<'_, PyDict>, span: Duration, mut call: impl FnMut() -> PyResult<()>) -> PyResult<u64> {
+    let ticks = || -> PyResult<u64> { globals.get_item("ticks")?.expect("ticker started").extract() };
+    let start = ticks()?;
+    let deadline = Instant::now() + span;
+    while Instant::now() < deadline {
+        call()?;
+    }
+    Ok(ticks()? - start)
+}
+
+#[test]
+fn test_dream_cycles_release_gil() -> PyResult<()> {
+    let config = std::env::temp_dir().join(format!("aios_dream_gil_{}.toml", std::process::id()));
+    std::fs::write(&config, "[dream]\ncycle_delay_ms = 20\n").unwrap();
+    pyo3::prepare_freethreaded_python();
+    let result = Python::with_gil(|py| {
+        let core = py.get_type_bound::<RustDreamCore>().call1((None::<String>, config.to_str().unwrap()))?;
+        let globals = PyDict::new_bound(py);
+        py.run_bound(TICKER, Some(&globals), None)?;
+        // The ticker stalls while the GIL is held...
+        let stalled = ticks_during(&globals, Duration::from_millis(200), || {
+            std::thread::sleep(Duration::from_millis(10));
+            Ok(())
+        })?;
+        // ...but not while cycles wait between consolidations
+        let dreaming = ticks_during(&globals, Duration::from_millis(200), || {
+            core.call_method1("run_quick_nap", (1, 3, 1, false)).map(drop)
+        })?;
+        globals.set_item("running", false)?;
+        py.run_bound("ticker.join()", Some(&globals), None)?;
+        assert!(stalled <= 2, "ticked {} times with the GIL held", stalled);
+        assert!(dreaming >= 20, "ticked only {} times during dream cycles", dreaming);
+        Ok(())
+    });
+    let _ = std::fs::remove_file(&config);
+    result
+}
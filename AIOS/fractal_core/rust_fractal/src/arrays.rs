@@ -0,0 +1,104 @@
+//! numpy-or-list array arguments
+//!
+//! numpy arrays are read through the buffer in one pass instead of being
+//! converted element by element; lists and other sequences still work. numpy
+//! is only touched for objects exposing `__array_interface__`, so the module
+//! keeps working where numpy is not installed.
+
+use numpy::{PyReadonlyArray1, PyReadonlyArray2};
+use pyo3::prelude::*;
+
+/// A 1-D float64 array or a sequence of floats
+pub enum Floats<'py> {
+    Array(PyReadonlyArray1<'py, f64>),
+    List(Vec<f64>),
+}
+
+impl<'py> FromPyObject<'py> for Floats<'py> {
+    fn extract(obj: &'py PyAny) -> PyResult<Self> {
+        if obj.hasattr("__array_interface__")? {
+            if let Ok(array) = obj.extract() {
+                return Ok(Self::Array(array));
+            }
+        }
+        Ok(Self::List(obj.extract()?))
+    }
+}
+
+impl Floats<'_> {
+    pub fn into_vec(self) -> Vec<f64> {
+        match self {
+            Self::Array(array) => match array.as_slice() {
+                Ok(slice) => slice.to_vec(),
+                Err(_) => array.as_array().to_vec(),
+            },
+            Self::List(values) => values,
+        }
+    }
+}
+
+/// A 1-D integer array (int64 or uint64) or a sequence of non-negative ints
+pub enum Counts<'py> {
+    I64(PyReadonlyArray1<'py, i64>),
+    U64(PyReadonlyArray1<'py, u64>),
+    List(Vec<usize>),
+}
+
+impl<'py> FromPyObject<'py> for Counts<'py> {
+    fn extract(obj: &'py PyAny) -> PyResult<Self> {
+        if obj.hasattr("__array_interface__")? {
+            if let Ok(array) = obj.extract() {
+                return Ok(Self::I64(array));
+            }
+            if let Ok(array) = obj.extract() {
+                return Ok(Self::U64(array));
+            }
+        }
+        Ok(Self::List(obj.extract()?))
+    }
+}
+
+impl Counts<'_> {
+    /// Raises ValueError for negative entries
+    pub fn into_vec(self) -> PyResult<Vec<usize>> {
+        match self {
+            Self::I64(array) => array
+                .as_array()
+                .iter()
+                .map(|&x| {
+                    usize::try_from(x).map_err(|_| {
+                        PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Expected non-negative values, got {}", x))
+                    })
+                })
+                .collect(),
+            Self::U64(array) => Ok(array.as_array().iter().map(|&x| x as usize).collect()),
+            Self::List(values) => Ok(values),
+        }
+    }
+}
+
+/// A 2-D float64 array or a sequence of float sequences, one row each
+pub enum Rows<'py> {
+    Array(PyReadonlyArray2<'py, f64>),
+    List(Vec<Vec<f64>>),
+}
+
+impl<'py> FromPyObject<'py> for Rows<'py> {
+    fn extract(obj: &'py PyAny) -> PyResult<Self> {
+        if obj.hasattr("__array_interface__")? {
+            if let Ok(array) = obj.extract() {
+                return Ok(Self::Array(array));
+            }
+        }
+        Ok(Self::List(obj.extract()?))
+    }
+}
+
+impl Rows<'_> {
+    pub fn into_rows(self) -> Vec<Vec<f64>> {
+        match self {
+            Self::Array(array) => array.as_array().rows().into_iter().map(|row| row.to_vec()).collect(),
+            Self::List(rows) => rows,
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Linear gain predictor for knapsack inputs
+//!
+//! gain = bias + Σ weight_i · feature_i over per-span features such as
+//! recency, similarity and length, scored for every span in one call.
+
+// `#[new]` under PyO3 0.20 expands to impls that newer rustc flags
+#![allow(unknown_lints, non_local_definitions)]
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+use crate::arrays::{Floats, Rows};
+
+/// Weights and bias of a linear model over span features
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GainModel {
+    weights: Vec<f64>,
+    bias: f64,
+    feature_names: Vec<String>,
+}
+
+impl GainModel {
+    /// `feature_names` default to `feature_0`, `feature_1`, ...
+    pub fn new(weights: Vec<f64>, bias: f64, feature_names: Option<Vec<String>>) -> Result<Self, String> {
+        if let Some(bad) = weights.iter().chain([&bias]).find(|w| !w.is_finite()) {
+            return Err(format!("Weights and bias must be finite, got {}", bad));
+        }
+        let feature_names = feature_names.unwrap_or_else(|| (0..weights.len()).map(|i| format!("feature_{}", i)).collect());
+        if feature_names.len() != weights.len() {
+            return Err(format!("Got {} weights and {} feature names", weights.len(), feature_names.len()));
+        }
+        Ok(Self { weights, bias, feature_names })
+    }
+
+    fn check_row(&self, row: &[f64]) -> Result<(), String> {
+        if row.len() != self.weights.len() {
+            return Err(format!("Expected {} features per span, got {}", self.weights.len(), row.len()));
+        }
+        match row.iter().find(|x| !x.is_finite()) {
+            Some(bad) => Err(format!("Features must be finite, got {}", bad)),
+            None => Ok(()),
+        }
+    }
+
+    fn score(&self, row: &[f64]) -> f64 {
+        self.bias + self.weights.iter().zip(row).map(|(w, x)| w * x).sum::<f64>()
+    }
+
+    /// Gain for each row of features; errors on the first bad row
+    pub fn predict(&self, rows: &[Vec<f64>]) -> Result<Vec<f64>, String> {
+        if let Some((i, err)) = rows.iter().enumerate().find_map(|(i, row)| self.check_row(row).err().map(|err| (i, err))) {
+            return Err(format!("Span {}: {}", i, err));
+        }
+        Ok(rows.par_iter().map(|row| self.score(row)).collect())
+    }
+}
+
+#[pymethods]
+impl GainModel {
+    #[new]
+    #[pyo3(signature = (weights, bias=0.0, feature_names=None))]
+    fn py_new(weights: Floats<'_>, bias: f64, feature_names: Option<Vec<String>>) -> PyResult<Self> {
+        Self::new(weights.into_vec(), bias, feature_names).map_err(PyValueError::new_err)
+    }
+
+    /// Gains for a spans × features array (or list of rows), ready to pass
+    /// to the knapsack solvers
+    #[pyo3(name = "predict")]
+    fn py_predict(&self, py: Python<'_>, features: Rows<'_>) -> PyResult<Vec<f64>> {
+        let rows = features.into_rows();
+        let _span = aios_trace::span!("GainModel.predict", spans = rows.len(), features = self.weights.len());
+        py.allow_threads(|| self.predict(&rows)).map_err(PyValueError::new_err)
+    }
+
+    /// `(feature name, weight × value)` for one span's features
+    fn contributions(&self, features: Floats<'_>) -> PyResult<Vec<(String, f64)>> {
+        let row = features.into_vec();
+        self.check_row(&row).map_err(PyValueError::new_err)?;
+        Ok(self.feature_names.iter().cloned().zip(self.weights.iter().zip(&row).map(|(w, x)| w * x)).collect())
+    }
+
+    #[getter]
+    fn weights(&self) -> Vec<f64> {
+        self.weights.clone()
+    }
+
+    #[getter]
+    fn bias(&self) -> f64 {
+        self.bias
+    }
+
+    #[getter]
+    fn feature_names(&self) -> Vec<String> {
+        self.feature_names.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        let terms: Vec<String> = self.feature_names.iter().zip(&self.weights).map(|(name, w)| format!("{}={}", name, w)).collect();
+        format!("GainModel({}, bias={})", terms.join(", "), self.bias)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_predict() {
+        let model = GainModel::new(vec![2.0, -1.0, 0.5], 1.0, None).unwrap();
+        assert_eq!(model.feature_names, vec!["feature_0", "feature_1", "feature_2"]);
+        let rows = vec![vec![1.0, 0.0, 2.0], vec![0.0, 3.0, 0.0]];
+        assert_eq!(model.predict(&rows), Ok(vec![4.0, -2.0]));
+        assert_eq!(model.predict(&[]), Ok(vec![]));
+        assert!(model.predict(&[vec![1.0, 2.0]]).is_err());
+        assert!(model.predict(&[vec![1.0, f64::NAN, 0.0]]).is_err());
+        assert!(GainModel::new(vec![1.0], 0.0, Some(vec![])).is_err());
+    }
+}
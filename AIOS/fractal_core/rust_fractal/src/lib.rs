@@ -4,24 +4,82 @@
 //! Week 3: Structure created, implementation pending
 //! Week 4-5: Implement + property tests
 
+use std::time::{Duration, Instant};
+
+use numpy::{PyArray1, PyArray2};
 use pyo3::prelude::*;
+use rayon::prelude::*;
+
+mod arrays;
+mod gain;
+mod link;
+mod merges;
+mod pareto;
+mod policy;
+mod refine;
+mod schedule;
+mod streaming;
+mod submodular;
+mod tokens;
+mod tree;
+
+use arrays::{Counts, Floats};
+use gain::GainModel;
+use link::{calibrate_temperature, calibration, Calibration};
+use merges::py_propose_merges;
+use pareto::{py_pareto_frontier, ParetoFrontier};
+use policy::Policy;
+use schedule::PolicySchedule;
+use streaming::StreamingKnapsack;
+use submodular::py_submodular_knapsack;
+use tokens::{estimate_tokens_batch, py_estimate_tokens, TokenVocab};
+use tree::FragmentTree;
 
 /// Fast split decision
 /// 
 /// τ_split = σ(a + b·entropy + c·error_density)
 /// 
-/// Returns true if fragment should be split
+/// Returns true if fragment should be split. `link` ("sigmoid", "probit" or
+/// "linear") replaces σ, and the linear term is divided by `temperature`
+/// first.
 #[pyfunction]
-fn should_split(entropy: f64, error_density: f64, params: Vec<f64>) -> bool {
-    if params.len() < 3 {
-        return false;
-    }
+#[pyo3(signature = (entropy, error_density, params, link="sigmoid", temperature=1.0))]
+fn should_split(entropy: f64, error_density: f64, params: Vec<f64>, link: &str, temperature: f64) -> PyResult<bool> {
+    let _span = aios_trace::span!("should_split", entropy = entropy, error_density = error_density);
+    let calibration = calibration(link, temperature)?;
+    check_params(&params, "a, b, c")
+        .and_then(|_| check_finite(&[("entropy", entropy), ("error_density", error_density)]))
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
     
+    Ok(split_decision(entropy, error_density, &params, calibration))
+}
+
+/// Decision parameters must be exactly the three named ones, all finite
+fn check_params(params: &[f64], names: &str) -> Result<(), String> {
+    if params.len() != 3 {
+        return Err(format!("Expected parameters {}; got {} values", names, params.len()));
+    }
+    match params.iter().find(|p| !p.is_finite()) {
+        Some(bad) => Err(format!("Parameters must be finite, got {}", bad)),
+        None => Ok(()),
+    }
+}
+
+/// Named feature values must be finite
+fn check_finite(values: &[(&str, f64)]) -> Result<(), String> {
+    match values.iter().find(|(_, value)| !value.is_finite()) {
+        Some((name, bad)) => Err(format!("{} must be finite, got {}", name, bad)),
+        None => Ok(()),
+    }
+}
+
+/// τ_split test with `params` known to hold at least a, b and c
+fn split_decision(entropy: f64, error_density: f64, params: &[f64], calibration: Calibration) -> bool {
     let a = params[0];
     let b = params[1];
     let c = params[2];
     
-    let threshold = sigmoid(a + b * entropy + c * error_density);
+    let threshold = calibration.apply(a + b * entropy + c * error_density);
     
     entropy > threshold
 }
@@ -30,22 +88,239 @@ fn should_split(entropy: f64, error_density: f64, params: Vec<f64>) -> bool {
 /// 
 /// τ_merge = σ(d + e·js_div + f·topic_shift)
 /// 
-/// Returns true if fragments should be merged
+/// Returns true if fragments should be merged; `link` and `temperature` as
+/// for `should_split`
 #[pyfunction]
-fn should_merge(js_div: f64, topic_shift: f64, params: Vec<f64>) -> bool {
-    if params.len() < 3 {
-        return false;
-    }
+#[pyo3(signature = (js_div, topic_shift, params, link="sigmoid", temperature=1.0))]
+fn should_merge(js_div: f64, topic_shift: f64, params: Vec<f64>, link: &str, temperature: f64) -> PyResult<bool> {
+    let _span = aios_trace::span!("should_merge", js_div = js_div, topic_shift = topic_shift);
+    let calibration = calibration(link, temperature)?;
+    check_params(&params, "d, e, f")
+        .and_then(|_| check_finite(&[("js_div", js_div), ("topic_shift", topic_shift)]))
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
     
+    Ok(merge_decision(js_div, topic_shift, &params, calibration))
+}
+
+/// τ_merge test with `params` known to hold at least d, e and f
+fn merge_decision(js_div: f64, topic_shift: f64, params: &[f64], calibration: Calibration) -> bool {
     let d = params[0];
     let e = params[1];
     let f = params[2];
     
-    let threshold = sigmoid(d + e * js_div + f * topic_shift);
+    let threshold = calibration.apply(d + e * js_div + f * topic_shift);
     
     js_div < threshold
 }
 
+/// Why a split or merge decision came out the way it did
+#[pyclass(get_all)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecisionScore {
+    /// The decision itself, as `should_split`/`should_merge` return it
+    pub decision: bool,
+    /// Linear term inside the link function: intercept + sum of
+    /// contributions, before dividing by the temperature
+    pub logit: f64,
+    /// link(logit / temperature); as a probability under fitted parameters, and as the τ
+    /// threshold the feature is compared with
+    pub probability: f64,
+    pub threshold: f64,
+    /// Distance of the compared feature from the threshold, positive when
+    /// the decision is true
+    pub margin: f64,
+    /// Intercept parameter (a or d)
+    pub intercept: f64,
+    /// (feature name, parameter × value) for each feature
+    pub contributions: Vec<(String, f64)>,
+}
+
+#[pymethods]
+impl DecisionScore {
+    fn __repr__(&self) -> String {
+        format!(
+            "DecisionScore(decision={}, probability={:.4}, margin={:.4}, contributions={:?})",
+            if self.decision { "True" } else { "False" }, self.probability, self.margin, self.contributions
+        )
+    }
+}
+
+/// Explain τ_split for one fragment
+fn split_explanation(entropy: f64, error_density: f64, params: &[f64], calibration: Calibration) -> Result<DecisionScore, String> {
+    check_params(params, "a, b, c")?;
+    check_finite(&[("entropy", entropy), ("error_density", error_density)])?;
+    let contributions = vec![("entropy".to_string(), params[1] * entropy), ("error_density".to_string(), params[2] * error_density)];
+    let logit = params[0] + contributions.iter().map(|(_, c)| c).sum::<f64>();
+    let threshold = calibration.apply(logit);
+    Ok(DecisionScore {
+        decision: split_decision(entropy, error_density, params, calibration),
+        logit,
+        probability: threshold,
+        threshold,
+        margin: entropy - threshold,
+        intercept: params[0],
+        contributions,
+    })
+}
+
+/// Explain τ_merge for one fragment pair
+fn merge_explanation(js_div: f64, topic_shift: f64, params: &[f64], calibration: Calibration) -> Result<DecisionScore, String> {
+    check_params(params, "d, e, f")?;
+    check_finite(&[("js_div", js_div), ("topic_shift", topic_shift)])?;
+    let contributions = vec![("js_div".to_string(), params[1] * js_div), ("topic_shift".to_string(), params[2] * topic_shift)];
+    let logit = params[0] + contributions.iter().map(|(_, c)| c).sum::<f64>();
+    let threshold = calibration.apply(logit);
+    Ok(DecisionScore {
+        decision: merge_decision(js_div, topic_shift, params, calibration),
+        logit,
+        probability: threshold,
+        threshold,
+        margin: threshold - js_div,
+        intercept: params[0],
+        contributions,
+    })
+}
+
+/// `should_split` with its reasoning: probability, threshold, margin and
+/// per-feature contributions
+#[pyfunction]
+#[pyo3(signature = (entropy, error_density, params, link="sigmoid", temperature=1.0))]
+fn split_score(entropy: f64, error_density: f64, params: Vec<f64>, link: &str, temperature: f64) -> PyResult<DecisionScore> {
+    let _span = aios_trace::span!("split_score", entropy = entropy, error_density = error_density);
+    split_explanation(entropy, error_density, &params, calibration(link, temperature)?).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+/// `should_merge` with its reasoning: probability, threshold, margin and
+/// per-feature contributions
+#[pyfunction]
+#[pyo3(signature = (js_div, topic_shift, params, link="sigmoid", temperature=1.0))]
+fn merge_score(js_div: f64, topic_shift: f64, params: Vec<f64>, link: &str, temperature: f64) -> PyResult<DecisionScore> {
+    let _span = aios_trace::span!("merge_score", js_div = js_div, topic_shift = topic_shift);
+    merge_explanation(js_div, topic_shift, &params, calibration(link, temperature)?).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+/// Apply `decide` to each pair of `xs` and `ys` in parallel; `names` are
+/// the parameter names for error messages
+fn batch_decisions(
+    xs: &[f64],
+    ys: &[f64],
+    params: &[f64],
+    names: &str,
+    calibration: Calibration,
+    decide: fn(f64, f64, &[f64], Calibration) -> bool,
+) -> Result<Vec<bool>, String> {
+    if xs.len() != ys.len() {
+        return Err(format!("Arrays have {} and {} elements", xs.len(), ys.len()));
+    }
+    check_params(params, names)?;
+    if let Some(bad) = xs.iter().chain(ys).find(|x| !x.is_finite()) {
+        return Err(format!("Features must be finite, got {}", bad));
+    }
+    Ok(xs.par_iter().zip(ys).map(|(&x, &y)| decide(x, y, params, calibration)).collect())
+}
+
+/// `should_split` for every fragment at once; returns a boolean numpy array
+#[pyfunction]
+#[pyo3(signature = (entropy, error_density, params, link="sigmoid", temperature=1.0))]
+fn should_split_batch<'py>(
+    py: Python<'py>,
+    entropy: Floats<'_>,
+    error_density: Floats<'_>,
+    params: Vec<f64>,
+    link: &str,
+    temperature: f64,
+) -> PyResult<&'py PyArray1<bool>> {
+    let (entropy, error_density) = (entropy.into_vec(), error_density.into_vec());
+    let _span = aios_trace::span!("should_split_batch", fragments = entropy.len());
+    let calibration = calibration(link, temperature)?;
+    let decisions = py
+        .allow_threads(|| batch_decisions(&entropy, &error_density, &params, "a, b, c", calibration, split_decision))
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    Ok(PyArray1::from_vec(py, decisions))
+}
+
+/// `should_merge` for every fragment pair at once; returns a boolean numpy array
+#[pyfunction]
+#[pyo3(signature = (js_div, topic_shift, params, link="sigmoid", temperature=1.0))]
+fn should_merge_batch<'py>(
+    py: Python<'py>,
+    js_div: Floats<'_>,
+    topic_shift: Floats<'_>,
+    params: Vec<f64>,
+    link: &str,
+    temperature: f64,
+) -> PyResult<&'py PyArray1<bool>> {
+    let (js_div, topic_shift) = (js_div.into_vec(), topic_shift.into_vec());
+    let _span = aios_trace::span!("should_merge_batch", pairs = js_div.len());
+    let calibration = calibration(link, temperature)?;
+    let decisions = py
+        .allow_threads(|| batch_decisions(&js_div, &topic_shift, &params, "d, e, f", calibration, merge_decision))
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    Ok(PyArray1::from_vec(py, decisions))
+}
+
+/// Outcome of a knapsack selection
+/// 
+/// Iterating over it, `len()` and indexing go over `selected`, so it can
+/// stand in for the plain index list the solvers used to return.
+#[pyclass(get_all)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnapsackResult {
+    /// Indices of the selected spans
+    pub selected: Vec<usize>,
+    pub total_gain: f64,
+    pub tokens_used: usize,
+    pub budget: usize,
+    /// tokens_used as a percentage of budget
+    pub utilization: f64,
+    /// Best gain/token ratio among spans left out, if any; a budget cut
+    /// should remove spans until the selection's worst ratio passes this
+    pub first_rejected_ratio: Option<f64>,
+}
+
+impl KnapsackResult {
+    fn new(selected: Vec<usize>, gains: &[f64], costs: &[usize], budget: usize) -> Self {
+        let total_gain = selected.iter().map(|&i| gains[i]).sum();
+        let tokens_used = selected.iter().map(|&i| costs[i]).sum();
+        let utilization = if budget > 0 { tokens_used as f64 / budget as f64 * 100.0 } else { 0.0 };
+        let mut chosen = vec![false; costs.len()];
+        selected.iter().for_each(|&i| chosen[i] = true);
+        let first_rejected_ratio = ratio_order(gains, costs)
+            .into_iter()
+            .find(|&i| !chosen[i])
+            .map(|i| gains[i] / costs[i] as f64);
+        Self { selected, total_gain, tokens_used, budget, utilization, first_rejected_ratio }
+    }
+}
+
+#[pymethods]
+impl KnapsackResult {
+    fn __len__(&self) -> usize {
+        self.selected.len()
+    }
+
+    fn __getitem__(&self, index: isize) -> PyResult<usize> {
+        let len = self.selected.len() as isize;
+        let position = if index < 0 { index + len } else { index };
+        if position < 0 || position >= len {
+            return Err(PyErr::new::<pyo3::exceptions::PyIndexError, _>("KnapsackResult index out of range"));
+        }
+        Ok(self.selected[position as usize])
+    }
+
+    fn __iter__(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let selected = pyo3::types::PyList::new(py, &self.selected);
+        Ok(selected.call_method0("__iter__")?.into())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "KnapsackResult(selected={:?}, total_gain={}, tokens_used={}, budget={}, utilization={:.1}%)",
+            self.selected, self.total_gain, self.tokens_used, self.budget, self.utilization
+        )
+    }
+}
+
 /// Fast greedy knapsack
 /// 
 /// Args:
@@ -54,19 +329,46 @@ fn should_merge(js_div: f64, topic_shift: f64, params: Vec<f64>) -> bool {
 ///   budget: Total token budget
 /// 
 /// Returns: Indices of selected spans
-#[pyfunction]
 fn greedy_knapsack(gains: Vec<f64>, costs: Vec<usize>, budget: usize) -> Vec<usize> {
     if gains.len() != costs.len() {
         return vec![];
     }
     
+    // Greedy selection
+    let mut selected = Vec::new();
+    let mut used = 0;
+    
+    for idx in ratio_order(&gains, &costs) {
+        if used + costs[idx] <= budget {
+            selected.push(idx);
+            used += costs[idx];
+        }
+    }
+    
+    selected
+}
+
+/// One finite gain per cost
+fn check_knapsack(gains: &[f64], costs: &[usize]) -> Result<(), String> {
+    if gains.len() != costs.len() {
+        return Err(format!("Got {} gains and {} costs", gains.len(), costs.len()));
+    }
+    match gains.iter().find(|gain| !gain.is_finite()) {
+        Some(bad) => Err(format!("Gains must be finite, got {}", bad)),
+        None => Ok(()),
+    }
+}
+
+/// Indices of positive-cost items by gain/cost ratio, best first; items
+/// with a NaN gain cannot be ranked and are left out
+fn ratio_order(gains: &[f64], costs: &[usize]) -> Vec<usize> {
     // Calculate ratios
     let mut items: Vec<(usize, f64)> = gains
         .iter()
         .zip(costs.iter())
         .enumerate()
         .filter_map(|(i, (&gain, &cost))| {
-            if cost > 0 {
+            if cost > 0 && !gain.is_nan() {
                 Some((i, gain / cost as f64))
             } else {
                 None
@@ -75,33 +377,769 @@ fn greedy_knapsack(gains: Vec<f64>, costs: Vec<usize>, budget: usize) -> Vec<usi
         .collect();
     
     // Sort by ratio descending
-    items.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    sort_by_ratio(&mut items, tie_seed());
+    items.into_iter().map(|(idx, _ratio)| idx).collect()
+}
+
+/// Stream the seeded tie-break keys are drawn from
+const TIE_STREAM: &str = "fractal.ties";
+
+/// The seed from `set_seed` or `AIOS_SEED`; a malformed `AIOS_SEED` counts
+/// as unseeded here and is reported by `get_seed`
+fn tie_seed() -> Option<u64> {
+    aios_rng::seed().unwrap_or(None)
+}
+
+/// Sort `(index, ratio)` pairs best ratio first, in a total order that
+/// never panics: NaN ratios go last, and equal ratios are ordered by index,
+/// or by a key derived from `seed` and the index when seeded
+fn sort_by_ratio(items: &mut [(usize, f64)], seed: Option<u64>) {
+    items.sort_by(|a, b| {
+        a.1.is_nan()
+            .cmp(&b.1.is_nan())
+            .then_with(|| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal))
+            .then_with(|| tie_key(seed, a.0).cmp(&tie_key(seed, b.0)))
+    });
+}
+
+/// Where item `idx` goes among equals: the index itself, or a key derived
+/// from `seed` and the index when seeded
+fn tie_key(seed: Option<u64>, idx: usize) -> u64 {
+    seed.map_or(idx as u64, |seed| aios_rng::key(seed, TIE_STREAM, idx as u64))
+}
+
+/// Greedy knapsack that always takes `required` and at most one item of
+/// each of `exclusive_groups`
+/// 
+/// Required items come first in the result, then the greedy picks. Errors
+/// when an index is out of range, the required items alone exceed the
+/// budget, or two required items share a group.
+fn constrained_knapsack(
+    gains: &[f64],
+    costs: &[usize],
+    budget: usize,
+    required: &[usize],
+    exclusive_groups: &[Vec<usize>],
+) -> Result<Vec<usize>, String> {
+    check_knapsack(gains, costs)?;
+    let n = costs.len();
+    if let Some(&idx) = required.iter().chain(exclusive_groups.iter().flatten()).find(|&&idx| idx >= n) {
+        return Err(format!("Item {} is out of range for {} items", idx, n));
+    }
     
-    // Greedy selection
+    // groups_of[i]: the exclusive groups item i belongs to
+    let mut groups_of = vec![Vec::new(); n];
+    for (group, members) in exclusive_groups.iter().enumerate() {
+        for &idx in members {
+            groups_of[idx].push(group);
+        }
+    }
+    let mut taken = vec![false; n];
+    let mut group_used = vec![false; exclusive_groups.len()];
     let mut selected = Vec::new();
-    let mut used = 0;
+    let mut used = 0usize;
+    
+    for &idx in required {
+        if taken[idx] {
+            continue;
+        }
+        if let Some(&group) = groups_of[idx].iter().find(|&&group| group_used[group]) {
+            return Err(format!("Required items conflict in exclusive group {}", group));
+        }
+        taken[idx] = true;
+        groups_of[idx].iter().for_each(|&group| group_used[group] = true);
+        selected.push(idx);
+        used += costs[idx];
+    }
+    if used > budget {
+        return Err(format!("Required items cost {}, more than the budget of {}", used, budget));
+    }
+    
+    for idx in ratio_order(gains, costs) {
+        if taken[idx] || used + costs[idx] > budget || groups_of[idx].iter().any(|&group| group_used[group]) {
+            continue;
+        }
+        taken[idx] = true;
+        groups_of[idx].iter().for_each(|&group| group_used[group] = true);
+        selected.push(idx);
+        used += costs[idx];
+    }
     
+    Ok(selected)
+}
+
+/// Greedy knapsack under a token budget and a latency budget at once
+/// 
+/// Items are ranked by gain per unit of combined cost, each cost taken as a
+/// fraction of its budget, and taken while both budgets hold. Items with no
+/// positive gain are never taken.
+fn two_budget_knapsack(
+    gains: &[f64],
+    token_costs: &[usize],
+    latency_costs: &[f64],
+    token_budget: usize,
+    latency_budget: f64,
+) -> Result<Vec<usize>, String> {
+    if gains.len() != token_costs.len() || gains.len() != latency_costs.len() {
+        return Err(format!(
+            "Got {} gains, {} token costs and {} latency costs",
+            gains.len(),
+            token_costs.len(),
+            latency_costs.len()
+        ));
+    }
+    check_knapsack(gains, token_costs)?;
+    if let Some(bad) = latency_costs.iter().chain([&latency_budget]).find(|x| !x.is_finite() || **x < 0.0) {
+        return Err(format!("Latencies must be finite and non-negative, got {}", bad));
+    }
+    
+    let share = |cost: f64, budget: f64| if budget > 0.0 { cost / budget } else if cost > 0.0 { f64::INFINITY } else { 0.0 };
+    let mut items: Vec<(usize, f64)> = (0..gains.len())
+        .filter(|&i| gains[i] > 0.0)
+        .map(|i| {
+            let weight = share(token_costs[i] as f64, token_budget as f64) + share(latency_costs[i], latency_budget);
+            (i, gains[i] / weight)
+        })
+        .collect();
+    sort_by_ratio(&mut items, tie_seed());
+    
+    let mut selected = Vec::new();
+    let (mut tokens, mut latency) = (0usize, 0.0f64);
     for (idx, _ratio) in items {
-        if used + costs[idx] <= budget {
+        if tokens + token_costs[idx] <= token_budget && latency + latency_costs[idx] <= latency_budget {
             selected.push(idx);
-            used += costs[idx];
+            tokens += token_costs[idx];
+            latency += latency_costs[idx];
+        }
+    }
+    Ok(selected)
+}
+
+/// Select spans within both a token budget and a latency budget (ms)
+#[pyfunction]
+#[pyo3(name = "two_budget_knapsack")]
+fn py_two_budget_knapsack(
+    gains: Floats<'_>,
+    token_costs: Counts<'_>,
+    latency_costs: Floats<'_>,
+    token_budget: usize,
+    latency_budget: f64,
+) -> PyResult<KnapsackResult> {
+    let gains = gains.into_vec();
+    let _span = aios_trace::span!("two_budget_knapsack", items = gains.len(), token_budget = token_budget, latency_budget = latency_budget);
+    let token_costs = token_costs.into_vec()?;
+    let selected = two_budget_knapsack(&gains, &token_costs, &latency_costs.into_vec(), token_budget, latency_budget)
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    Ok(KnapsackResult::new(selected, &gains, &token_costs, token_budget))
+}
+
+/// Split `total_budget` tokens across conversations in proportion to their
+/// predicted gains, by water-filling
+/// 
+/// A conversation never gets more than its cap (e.g. the tokens its spans
+/// cost in all); what a capped conversation cannot use is shared out among
+/// the rest by gain again. Conversations without positive gain get nothing.
+/// Shares are rounded down and the leftover tokens go to the largest
+/// remainders, so every token is handed out unless the caps run out first.
+fn allocate_budgets(gains: &[f64], total_budget: usize, caps: Option<&[usize]>) -> Result<Vec<usize>, String> {
+    if let Some(bad) = gains.iter().find(|gain| !gain.is_finite()) {
+        return Err(format!("Gains must be finite, got {}", bad));
+    }
+    if let Some(caps) = caps {
+        if caps.len() != gains.len() {
+            return Err(format!("Got {} gains and {} caps", gains.len(), caps.len()));
+        }
+    }
+    let cap = |i: usize| caps.map_or(usize::MAX, |caps| caps[i]);
+    
+    // Fill: conversations whose proportional share reaches their cap are
+    // pinned at it and the rest recomputed without them
+    let mut shares = vec![0.0f64; gains.len()];
+    let mut open: Vec<usize> = (0..gains.len()).filter(|&i| gains[i] > 0.0 && cap(i) > 0).collect();
+    let mut remaining = total_budget as f64;
+    loop {
+        let total_gain: f64 = open.iter().map(|&i| gains[i]).sum();
+        let (capped, free): (Vec<usize>, Vec<usize>) =
+            open.iter().partition(|&&i| remaining * gains[i] / total_gain >= cap(i) as f64);
+        if capped.is_empty() {
+            free.iter().for_each(|&i| shares[i] = remaining * gains[i] / total_gain);
+            break;
+        }
+        for &i in &capped {
+            shares[i] = cap(i) as f64;
+            remaining -= cap(i) as f64;
+        }
+        open = free;
+    }
+    
+    let mut budgets: Vec<usize> = shares.iter().map(|&share| share.floor() as usize).collect();
+    let handed_out: usize = budgets.iter().sum();
+    let mut by_remainder: Vec<usize> = (0..gains.len()).filter(|&i| budgets[i] < cap(i) && shares[i] > 0.0).collect();
+    by_remainder.sort_by(|&a, &b| (shares[b] - shares[b].floor()).total_cmp(&(shares[a] - shares[a].floor())).then(a.cmp(&b)));
+    for i in by_remainder.into_iter().take(total_budget.saturating_sub(handed_out)) {
+        budgets[i] += 1;
+    }
+    Ok(budgets)
+}
+
+/// Per-conversation token budgets from one global budget, proportional to
+/// each conversation's predicted gain
+/// 
+/// `caps` optionally bounds each conversation's budget; tokens beyond a cap
+/// go to the other conversations.
+#[pyfunction]
+#[pyo3(name = "allocate_budgets", signature = (conversation_gains, total_budget, caps=None))]
+fn py_allocate_budgets(conversation_gains: Floats<'_>, total_budget: usize, caps: Option<Counts<'_>>) -> PyResult<Vec<usize>> {
+    let gains = conversation_gains.into_vec();
+    let _span = aios_trace::span!("allocate_budgets", conversations = gains.len(), total_budget = total_budget);
+    let caps = caps.map(Counts::into_vec).transpose()?;
+    allocate_budgets(&gains, total_budget, caps.as_deref()).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+/// DP tables larger than this many cells fall back to `greedy_knapsack`
+const DEFAULT_MAX_DP_CELLS: usize = 10_000_000;
+
+/// Exact 0/1 knapsack by dynamic programming over the budget
+/// 
+/// Args:
+///   gains: Predicted decision gains per span
+///   costs: Token costs per span
+///   budget: Total token budget
+///   max_cells: Largest items × (budget + 1) table to build; bigger problems
+///              use `greedy_knapsack` instead
+/// 
+/// Returns: Indices of selected spans, ascending
+fn exact_knapsack(gains: Vec<f64>, costs: Vec<usize>, budget: usize, max_cells: usize) -> Vec<usize> {
+    if gains.is_empty() || gains.len() != costs.len() {
+        return vec![];
+    }
+    // No selection can cost more than every item together
+    let budget = budget.min(costs.iter().fold(0usize, |total, &cost| total.saturating_add(cost)));
+    let width = budget.saturating_add(1);
+    if width > max_cells || gains.len().saturating_mul(width) > max_cells {
+        let mut selected = greedy_knapsack(gains, costs, budget);
+        selected.sort_unstable();
+        return selected;
+    }
+    
+    // best[w]: highest gain within cost w; keep[i * width + w]: item i taken at w
+    let mut best = vec![0.0f64; width];
+    let mut keep = vec![false; gains.len() * width];
+    for (i, (&gain, &cost)) in gains.iter().zip(costs.iter()).enumerate() {
+        if gain <= 0.0 || cost > budget {
+            continue;
+        }
+        for w in (cost..width).rev() {
+            let candidate = best[w - cost] + gain;
+            if candidate > best[w] {
+                best[w] = candidate;
+                keep[i * width + w] = true;
+            }
         }
     }
     
+    // Walk back from the full budget
+    let mut selected = Vec::new();
+    let mut w = budget;
+    for i in (0..gains.len()).rev() {
+        if keep[i * width + w] {
+            selected.push(i);
+            w -= costs[i];
+        }
+    }
+    selected.reverse();
     selected
 }
 
+/// Python entry point for `exact_knapsack`; gains and costs may be numpy arrays
+#[pyfunction]
+#[pyo3(name = "exact_knapsack", signature = (gains, costs, budget, max_cells=DEFAULT_MAX_DP_CELLS))]
+fn py_exact_knapsack(gains: Floats<'_>, costs: Counts<'_>, budget: usize, max_cells: usize) -> PyResult<KnapsackResult> {
+    let gains = gains.into_vec();
+    let _span = aios_trace::span!("exact_knapsack", items = gains.len(), budget = budget);
+    let costs = costs.into_vec()?;
+    check_knapsack(&gains, &costs).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    let selected = exact_knapsack(gains.clone(), costs.clone(), budget, max_cells);
+    Ok(KnapsackResult::new(selected, &gains, &costs, budget))
+}
+
+/// Python entry point for `greedy_knapsack`; gains and costs may be numpy arrays
+/// 
+/// `required` spans are always selected and at most one span of each of
+/// `exclusive_groups` is; ValueError when they cannot be satisfied, when
+/// gains and costs differ in length, or when a gain is NaN or infinite.
+/// 
+/// With `refine_ms` > 0 a branch-and-bound search starts from the greedy
+/// selection and keeps the best selection it finds within that many
+/// milliseconds; it never does worse than greedy alone.
+#[pyfunction]
+#[pyo3(name = "greedy_knapsack", signature = (gains, costs, budget, required=None, exclusive_groups=None, refine_ms=0.0))]
+fn py_greedy_knapsack(
+    py: Python<'_>,
+    gains: Floats<'_>,
+    costs: Counts<'_>,
+    budget: usize,
+    required: Option<Vec<usize>>,
+    exclusive_groups: Option<Vec<Vec<usize>>>,
+    refine_ms: f64,
+) -> PyResult<KnapsackResult> {
+    let gains = gains.into_vec();
+    let _span = aios_trace::span!("greedy_knapsack", items = gains.len(), budget = budget, refine_ms = refine_ms);
+    let costs = costs.into_vec()?;
+    if !(refine_ms.is_finite() && refine_ms >= 0.0) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("refine_ms must be non-negative, got {}", refine_ms)));
+    }
+    check_knapsack(&gains, &costs).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    let (required, exclusive_groups) = (required.unwrap_or_default(), exclusive_groups.unwrap_or_default());
+    let mut selected = if required.is_empty() && exclusive_groups.is_empty() {
+        greedy_knapsack(gains.clone(), costs.clone(), budget)
+    } else {
+        constrained_knapsack(&gains, &costs, budget, &required, &exclusive_groups)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?
+    };
+    if refine_ms > 0.0 {
+        let deadline = Instant::now() + Duration::from_secs_f64(refine_ms / 1000.0);
+        selected = py
+            .allow_threads(|| refine::refine_knapsack(&gains, &costs, budget, &required, &exclusive_groups, selected, deadline))
+            .selected;
+    }
+    Ok(KnapsackResult::new(selected, &gains, &costs, budget))
+}
+
+/// Linear blend of two policies' parameters
+/// 
+/// policy = (1 - α)·policy_a + α·policy_b, with α in [0, 1]
+fn interpolate_policy(policy_a: &[f64], policy_b: &[f64], alpha: f64) -> Result<Vec<f64>, String> {
+    if policy_a.len() != policy_b.len() {
+        return Err(format!("Policies have {} and {} parameters", policy_a.len(), policy_b.len()));
+    }
+    if !(0.0..=1.0).contains(&alpha) {
+        return Err(format!("alpha must be between 0 and 1, got {}", alpha));
+    }
+    Ok(policy_a.iter().zip(policy_b).map(|(a, b)| a + alpha * (b - a)).collect())
+}
+
+/// Piecewise-linear policy over karma tiers
+/// 
+/// `tiers` are ascending karma levels and `policies[i]` applies exactly at
+/// `tiers[i]`; karma between two tiers blends their policies, and karma
+/// outside the tiers gets the nearest one's policy.
+fn interpolate_policy_tiers(karma: f64, tiers: &[f64], policies: &[Vec<f64>]) -> Result<Vec<f64>, String> {
+    if tiers.is_empty() || tiers.len() != policies.len() {
+        return Err(format!("Expected one policy per tier, got {} tiers and {} policies", tiers.len(), policies.len()));
+    }
+    if tiers.windows(2).any(|pair| pair[0].partial_cmp(&pair[1]) != Some(std::cmp::Ordering::Less)) {
+        return Err("Tiers must be strictly ascending".to_string());
+    }
+    if policies.iter().any(|policy| policy.len() != policies[0].len()) {
+        return Err("All policies must have the same number of parameters".to_string());
+    }
+    if karma.is_nan() {
+        return Err("karma must be a number".to_string());
+    }
+    
+    let upper = tiers.partition_point(|&tier| tier <= karma);
+    if upper == 0 {
+        return Ok(policies[0].clone());
+    }
+    if upper == tiers.len() {
+        return Ok(policies[upper - 1].clone());
+    }
+    let alpha = (karma - tiers[upper - 1]) / (tiers[upper] - tiers[upper - 1]);
+    interpolate_policy(&policies[upper - 1], &policies[upper], alpha)
+}
+
+/// Blend two decision-parameter vectors; `alpha=0` gives `policy_a`, `alpha=1` gives `policy_b`
+#[pyfunction]
+#[pyo3(name = "interpolate_policy")]
+fn py_interpolate_policy(policy_a: Floats<'_>, policy_b: Floats<'_>, alpha: f64) -> PyResult<Vec<f64>> {
+    let _span = aios_trace::span!("interpolate_policy", alpha = alpha);
+    interpolate_policy(&policy_a.into_vec(), &policy_b.into_vec(), alpha).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+/// Decision parameters for `karma`, interpolated between the policies of
+/// the surrounding karma tiers
+#[pyfunction]
+#[pyo3(name = "interpolate_policy_tiers")]
+fn py_interpolate_policy_tiers(karma: f64, tiers: Floats<'_>, policies: Vec<Vec<f64>>) -> PyResult<Vec<f64>> {
+    let _span = aios_trace::span!("interpolate_policy_tiers", karma = karma, tiers = policies.len());
+    interpolate_policy_tiers(karma, &tiers.into_vec(), &policies).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+/// Shannon entropy in bits of a distribution given by non-negative weights
+/// 
+/// Weights are normalized by their sum; with `normalized` the result is
+/// divided by log2 of the number of weights, giving a value in [0, 1].
+fn shannon_entropy(weights: &[f64], normalized: bool) -> Result<f64, String> {
+    check_weights(weights)?;
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        return Ok(0.0);
+    }
+    let entropy = -weights
+        .iter()
+        .filter(|&&w| w > 0.0)
+        .map(|&w| {
+            let p = w / total;
+            p * p.log2()
+        })
+        .sum::<f64>();
+    let entropy = entropy.max(0.0);
+    if normalized {
+        if weights.len() < 2 {
+            return Ok(0.0);
+        }
+        return Ok(entropy / (weights.len() as f64).log2());
+    }
+    Ok(entropy)
+}
+
+fn check_weights(weights: &[f64]) -> Result<(), String> {
+    match weights.iter().find(|w| !w.is_finite() || **w < 0.0) {
+        Some(bad) => Err(format!("Weights must be finite and non-negative, got {}", bad)),
+        None => Ok(()),
+    }
+}
+
+/// Entropy in bits of a fragment's token counts, e.g. to pass to `should_split`
+#[pyfunction]
+#[pyo3(signature = (token_counts, normalized=false))]
+fn entropy_from_counts(token_counts: Counts<'_>, normalized: bool) -> PyResult<f64> {
+    let counts: Vec<f64> = token_counts.into_vec()?.into_iter().map(|c| c as f64).collect();
+    let _span = aios_trace::span!("entropy_from_counts", tokens = counts.len());
+    shannon_entropy(&counts, normalized).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+/// Entropy in bits of a probability vector; it is renormalized if it does
+/// not sum to exactly 1
+#[pyfunction]
+#[pyo3(signature = (probs, normalized=false))]
+fn entropy_from_probs(probs: Floats<'_>, normalized: bool) -> PyResult<f64> {
+    let probs = probs.into_vec();
+    let _span = aios_trace::span!("entropy_from_probs", outcomes = probs.len());
+    shannon_entropy(&probs, normalized).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+/// Jensen–Shannon divergence in bits, in [0, 1]
+/// 
+/// JS(p, q) = ½·KL(p ‖ m) + ½·KL(q ‖ m), m = ½(p + q)
+/// 
+/// Both inputs are normalized by their sums. Bins where a distribution is
+/// zero add nothing to its KL term, and m is positive wherever either is,
+/// so disjoint supports give exactly 1 rather than infinity.
+fn js_divergence(p: &[f64], q: &[f64]) -> Result<f64, String> {
+    if p.len() != q.len() {
+        return Err(format!("Distributions have {} and {} bins", p.len(), q.len()));
+    }
+    let p_total = distribution_total(p)?;
+    let q_total = distribution_total(q)?;
+    let divergence: f64 = p
+        .iter()
+        .zip(q)
+        .map(|(&p, &q)| {
+            let (p, q) = (p / p_total, q / q_total);
+            let m = 0.5 * (p + q);
+            let term = |x: f64| if x > 0.0 { x * (x / m).log2() } else { 0.0 };
+            0.5 * (term(p) + term(q))
+        })
+        .sum();
+    Ok(divergence.clamp(0.0, 1.0))
+}
+
+/// Sum of a distribution's weights, checking they are valid and not all zero
+fn distribution_total(weights: &[f64]) -> Result<f64, String> {
+    check_weights(weights)?;
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        return Err("Distribution has no mass".to_string());
+    }
+    Ok(total)
+}
+
+/// Symmetric matrix of divergences between every pair of distributions
+fn js_divergence_matrix(distributions: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, String> {
+    let n = distributions.len();
+    let upper: Vec<Vec<f64>> = (0..n)
+        .into_par_iter()
+        .map(|i| (i + 1..n).map(|j| js_divergence(&distributions[i], &distributions[j])).collect())
+        .collect::<Result<_, _>>()?;
+    let mut matrix = vec![vec![0.0; n]; n];
+    for (i, row) in upper.iter().enumerate() {
+        for (offset, &value) in row.iter().enumerate() {
+            matrix[i][i + 1 + offset] = value;
+            matrix[i + 1 + offset][i] = value;
+        }
+    }
+    Ok(matrix)
+}
+
+/// Jensen–Shannon divergence in bits between two distributions, e.g. the
+/// topic distributions of two fragments, for `should_merge`
+#[pyfunction]
+#[pyo3(name = "js_divergence")]
+fn py_js_divergence(p: Floats<'_>, q: Floats<'_>) -> PyResult<f64> {
+    let (p, q) = (p.into_vec(), q.into_vec());
+    let _span = aios_trace::span!("js_divergence", bins = p.len());
+    js_divergence(&p, &q).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+/// Pairwise Jensen–Shannon divergences of a list of distributions (or the
+/// rows of a 2-D array) as an n×n numpy array
+#[pyfunction]
+fn js_divergence_pairwise(py: Python<'_>, distributions: Vec<Vec<f64>>) -> PyResult<&PyArray2<f64>> {
+    let _span = aios_trace::span!("js_divergence_pairwise", distributions = distributions.len());
+    let matrix = py
+        .allow_threads(|| js_divergence_matrix(&distributions))
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    PyArray2::from_vec2(py, &matrix).map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// Errors per unit length of each `[start, end)` span
+/// 
+/// Offsets are sorted once and counted per span by binary search, so spans
+/// may overlap or come in any order. Empty spans have density 0.
+fn error_densities(spans: &[(usize, usize)], mut error_offsets: Vec<usize>) -> Result<Vec<f64>, String> {
+    if let Some(&(start, end)) = spans.iter().find(|(start, end)| end < start) {
+        return Err(format!("Span ({}, {}) ends before it starts", start, end));
+    }
+    error_offsets.sort_unstable();
+    Ok(spans
+        .iter()
+        .map(|&(start, end)| {
+            if end == start {
+                return 0.0;
+            }
+            let errors = error_offsets.partition_point(|&o| o < end) - error_offsets.partition_point(|&o| o < start);
+            errors as f64 / (end - start) as f64
+        })
+        .collect())
+}
+
+/// Error density of each `(start, end)` span given the offsets of errors or
+/// annotations, e.g. to pass to `should_split`
+#[pyfunction]
+#[pyo3(name = "error_densities")]
+fn py_error_densities(py: Python<'_>, spans: Vec<(usize, usize)>, error_offsets: Counts<'_>) -> PyResult<Vec<f64>> {
+    let error_offsets = error_offsets.into_vec()?;
+    let _span = aios_trace::span!("error_densities", spans = spans.len(), errors = error_offsets.len());
+    py.allow_threads(|| error_densities(&spans, error_offsets)).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+/// Logistic regression P(y) = σ(w0 + w1·x1 + w2·x2) fitted by Newton's
+/// method, with an L2 penalty on w1 and w2
+/// 
+/// Returns the weights, the iterations run, and whether the steps became
+/// smaller than 1e-9 before `max_iterations`.
+fn fit_logistic(features: &[Vec<f64>], labels: &[bool], l2: f64, max_iterations: usize) -> Result<([f64; 3], usize, bool), String> {
+    if features.len() != labels.len() {
+        return Err(format!("Got {} feature rows and {} labels", features.len(), labels.len()));
+    }
+    if features.is_empty() {
+        return Err("Need at least one logged decision".to_string());
+    }
+    if let Some(row) = features.iter().find(|row| row.len() != 2 || row.iter().any(|x| !x.is_finite())) {
+        return Err(format!("Feature rows must be two finite numbers, got {:?}", row));
+    }
+    if !(l2 >= 0.0 && l2.is_finite()) {
+        return Err(format!("l2 must be non-negative, got {}", l2));
+    }
+    
+    let mut weights = [0.0f64; 3];
+    for iteration in 1..=max_iterations {
+        let mut gradient = [0.0f64; 3];
+        let mut hessian = [[0.0f64; 3]; 3];
+        for (row, &label) in features.iter().zip(labels) {
+            let x = [1.0, row[0], row[1]];
+            let p = sigmoid(weights[0] + weights[1] * x[1] + weights[2] * x[2]);
+            let y = if label { 1.0 } else { 0.0 };
+            for i in 0..3 {
+                gradient[i] += (p - y) * x[i];
+                for j in 0..3 {
+                    hessian[i][j] += p * (1.0 - p) * x[i] * x[j];
+                }
+            }
+        }
+        for i in 0..3 {
+            // A tiny ridge on the intercept keeps all-one-class data solvable
+            let penalty = if i == 0 { 1e-9 } else { l2 };
+            gradient[i] += penalty * weights[i];
+            hessian[i][i] += penalty;
+        }
+        let step = solve3(hessian, gradient).ok_or("Decisions do not determine the parameters; add l2 or more varied data")?;
+        for i in 0..3 {
+            weights[i] -= step[i];
+        }
+        if step.iter().all(|s| s.abs() < 1e-9) {
+            return Ok((weights, iteration, true));
+        }
+    }
+    Ok((weights, max_iterations, false))
+}
+
+/// Solve a 3×3 linear system by Gaussian elimination with partial pivoting
+fn solve3(mut a: [[f64; 3]; 3], mut b: [f64; 3]) -> Option<[f64; 3]> {
+    for col in 0..3 {
+        let pivot = (col..3).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..3 {
+            let factor = a[row][col] / a[col][col];
+            let pivot_row = a[col];
+            for (value, pivot_value) in a[row].iter_mut().zip(pivot_row).skip(col) {
+                *value -= factor * pivot_value;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = [0.0; 3];
+    for row in (0..3).rev() {
+        let tail: f64 = (row + 1..3).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - tail) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Fit `[a, b, c]` of P(split) = σ(a + b·entropy + c·error_density) to logged
+/// decisions; `features` rows are `(entropy, error_density)`
+/// 
+/// Returns `(params, iterations, converged)`.
+#[pyfunction]
+#[pyo3(signature = (features, labels, l2=1e-3, max_iterations=100))]
+fn fit_split_params(py: Python<'_>, features: Vec<Vec<f64>>, labels: Vec<bool>, l2: f64, max_iterations: usize) -> PyResult<(Vec<f64>, usize, bool)> {
+    let _span = aios_trace::span!("fit_split_params", decisions = labels.len());
+    let (weights, iterations, converged) = py
+        .allow_threads(|| fit_logistic(&features, &labels, l2, max_iterations))
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    Ok((weights.to_vec(), iterations, converged))
+}
+
+/// Fit `[d, e, f]` of P(merge) = σ(d + e·js_div + f·topic_shift) to logged
+/// decisions; `features` rows are `(js_div, topic_shift)`
+/// 
+/// Returns `(params, iterations, converged)`.
+#[pyfunction]
+#[pyo3(signature = (features, labels, l2=1e-3, max_iterations=100))]
+fn fit_merge_params(py: Python<'_>, features: Vec<Vec<f64>>, labels: Vec<bool>, l2: f64, max_iterations: usize) -> PyResult<(Vec<f64>, usize, bool)> {
+    let _span = aios_trace::span!("fit_merge_params", decisions = labels.len());
+    let (weights, iterations, converged) = py
+        .allow_threads(|| fit_logistic(&features, &labels, l2, max_iterations))
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    Ok((weights.to_vec(), iterations, converged))
+}
+
+/// Gradient of the decision probability link(w0 + w1·x1 + w2·x2) with
+/// respect to the three parameters, for each `features` row (x1, x2)
+fn policy_gradients(features: &[Vec<f64>], params: &[f64], calibration: Calibration) -> Result<Vec<Vec<f64>>, String> {
+    if params.len() < 3 {
+        return Err(format!("Expected three parameters, got {} values", params.len()));
+    }
+    if let Some(row) = features.iter().find(|row| row.len() != 2 || row.iter().any(|x| !x.is_finite())) {
+        return Err(format!("Feature rows must be two finite numbers, got {:?}", row));
+    }
+    Ok(features
+        .iter()
+        .map(|row| {
+            let slope = calibration.derivative(params[0] + params[1] * row[0] + params[2] * row[1]);
+            vec![slope, slope * row[0], slope * row[1]]
+        })
+        .collect())
+}
+
+/// ∂τ_split/∂(a, b, c) for each `features` row `(entropy, error_density)`,
+/// under the same `link` and `temperature` as `should_split`
+/// 
+/// Returns one `[da, db, dc]` row per feature row.
+#[pyfunction]
+#[pyo3(signature = (features, params, link="sigmoid", temperature=1.0))]
+fn split_policy_grad(features: Vec<Vec<f64>>, params: Vec<f64>, link: &str, temperature: f64) -> PyResult<Vec<Vec<f64>>> {
+    let _span = aios_trace::span!("split_policy_grad", rows = features.len());
+    policy_gradients(&features, &params, calibration(link, temperature)?).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+/// ∂τ_merge/∂(d, e, f) for each `features` row `(js_div, topic_shift)`,
+/// under the same `link` and `temperature` as `should_merge`
+/// 
+/// Returns one `[dd, de, df]` row per feature row.
+#[pyfunction]
+#[pyo3(signature = (features, params, link="sigmoid", temperature=1.0))]
+fn merge_policy_grad(features: Vec<Vec<f64>>, params: Vec<f64>, link: &str, temperature: f64) -> PyResult<Vec<Vec<f64>>> {
+    let _span = aios_trace::span!("merge_policy_grad", rows = features.len());
+    policy_gradients(&features, &params, calibration(link, temperature)?).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+/// Make tie-breaking in the knapsack solvers depend on `seed`, or return to
+/// index order with `seed=None`
+/// 
+/// Either way runs are reproducible. The seed applies to this core; an
+/// `AIOS_SEED` set before start applies to every core. ValueError while a
+/// malformed `AIOS_SEED` is set.
+#[pyfunction]
+#[pyo3(signature = (seed=None))]
+fn set_seed(seed: Option<u64>) -> PyResult<()> {
+    let _span = aios_trace::span!("set_seed");
+    aios_rng::set_seed(seed).map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// The seed in effect, or None when unseeded
+#[pyfunction]
+fn get_seed() -> PyResult<Option<u64>> {
+    aios_rng::seed().map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
 /// Sigmoid function
 fn sigmoid(x: f64) -> f64 {
     1.0 / (1.0 + (-x).exp())
 }
 
+mod tracing {
+    aios_trace::python_tracing!();
+}
+
 /// Python module
 #[pymodule]
 fn rust_fractal(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(should_split, m)?)?;
     m.add_function(wrap_pyfunction!(should_merge, m)?)?;
-    m.add_function(wrap_pyfunction!(greedy_knapsack, m)?)?;
+    m.add_function(wrap_pyfunction!(should_split_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(should_merge_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(split_score, m)?)?;
+    m.add_function(wrap_pyfunction!(merge_score, m)?)?;
+    m.add_class::<DecisionScore>()?;
+    m.add_function(wrap_pyfunction!(calibrate_temperature, m)?)?;
+    m.add_function(wrap_pyfunction!(entropy_from_counts, m)?)?;
+    m.add_function(wrap_pyfunction!(entropy_from_probs, m)?)?;
+    m.add_function(wrap_pyfunction!(py_js_divergence, m)?)?;
+    m.add_function(wrap_pyfunction!(js_divergence_pairwise, m)?)?;
+    m.add_function(wrap_pyfunction!(py_error_densities, m)?)?;
+    m.add_function(wrap_pyfunction!(fit_split_params, m)?)?;
+    m.add_function(wrap_pyfunction!(fit_merge_params, m)?)?;
+    m.add_function(wrap_pyfunction!(split_policy_grad, m)?)?;
+    m.add_function(wrap_pyfunction!(merge_policy_grad, m)?)?;
+    m.add_function(wrap_pyfunction!(py_propose_merges, m)?)?;
+    m.add_class::<Policy>()?;
+    m.add_class::<PolicySchedule>()?;
+    m.add_class::<FragmentTree>()?;
+    m.add_class::<GainModel>()?;
+    m.add_function(wrap_pyfunction!(py_greedy_knapsack, m)?)?;
+    m.add_class::<KnapsackResult>()?;
+    m.add_class::<StreamingKnapsack>()?;
+    m.add_function(wrap_pyfunction!(py_exact_knapsack, m)?)?;
+    m.add_function(wrap_pyfunction!(py_two_budget_knapsack, m)?)?;
+    m.add_function(wrap_pyfunction!(py_submodular_knapsack, m)?)?;
+    m.add_function(wrap_pyfunction!(py_allocate_budgets, m)?)?;
+    m.add_function(wrap_pyfunction!(py_pareto_frontier, m)?)?;
+    m.add_class::<ParetoFrontier>()?;
+    m.add_function(wrap_pyfunction!(py_estimate_tokens, m)?)?;
+    m.add_function(wrap_pyfunction!(estimate_tokens_batch, m)?)?;
+    m.add_class::<TokenVocab>()?;
+    m.add_function(wrap_pyfunction!(py_interpolate_policy, m)?)?;
+    m.add_function(wrap_pyfunction!(py_interpolate_policy_tiers, m)?)?;
+    m.add_function(wrap_pyfunction!(set_seed, m)?)?;
+    m.add_function(wrap_pyfunction!(get_seed, m)?)?;
+    tracing::register(m)?;
     Ok(())
 }
 
@@ -121,10 +1159,10 @@ mod tests {
         let params = vec![0.5, 0.1, 0.05];
         
         // High entropy should split
-        assert!(should_split(0.9, 0.5, params.clone()));
+        assert!(split_decision(0.9, 0.5, &params, Calibration::default()));
         
         // Low entropy should not split
-        assert!(!should_split(0.1, 0.1, params.clone()));
+        assert!(!split_decision(0.1, 0.1, &params, Calibration::default()));
     }
     
     #[test]
@@ -133,7 +1171,7 @@ mod tests {
         let costs = vec![300, 250, 200, 150];
         let budget = 600;
         
-        let selected = greedy_knapsack(gains, costs, budget);
+        let selected = greedy_knapsack(gains, costs.clone(), budget);
         
         // Should select items 0 and 1 (best ratios, fit in budget)
         assert!(selected.contains(&0));
@@ -143,5 +1181,277 @@ mod tests {
         let total_cost: usize = selected.iter().map(|&i| costs[i]).sum();
         assert!(total_cost <= budget);
     }
+    
+    #[test]
+    fn test_exact_knapsack_beats_greedy() {
+        // Greedy takes item 0 (best ratio) and then nothing else fits
+        let gains = vec![7.0, 5.0, 5.0];
+        let costs = vec![6, 5, 5];
+        assert_eq!(greedy_knapsack(gains.clone(), costs.clone(), 10), vec![0]);
+        assert_eq!(exact_knapsack(gains, costs, 10, DEFAULT_MAX_DP_CELLS), vec![1, 2]);
+    }
+    
+    #[test]
+    fn test_sort_by_ratio_is_total_and_stable() {
+        let mut items = vec![(0, 1.0), (1, f64::NAN), (2, 2.0), (3, 1.0), (4, 1.0)];
+        sort_by_ratio(&mut items, None);
+        let order: Vec<usize> = items.iter().map(|&(idx, _)| idx).collect();
+        assert_eq!(order, vec![2, 0, 3, 4, 1]);
+        
+        // A seed reorders the ties, the same way every time
+        let seeded = |seed| {
+            let mut items = vec![(0, 1.0), (1, 1.0), (2, 1.0), (3, 1.0), (4, 1.0), (5, 2.0)];
+            sort_by_ratio(&mut items, Some(seed));
+            items.into_iter().map(|(idx, _)| idx).collect::<Vec<usize>>()
+        };
+        assert_eq!(seeded(7), seeded(7));
+        assert_eq!(seeded(7)[0], 5);
+        assert!((0..20).any(|seed| seeded(seed) != seeded(7)));
+    }
+    
+    #[test]
+    fn test_greedy_knapsack_skips_nan_gains() {
+        assert_eq!(greedy_knapsack(vec![f64::NAN, 1.0], vec![1, 1], 10), vec![1]);
+    }
+    
+    #[test]
+    fn test_input_validation() {
+        assert!(check_params(&[0.5, 0.1, 0.05], "a, b, c").is_ok());
+        assert_eq!(check_params(&[0.5, 0.1], "a, b, c"), Err("Expected parameters a, b, c; got 2 values".to_string()));
+        assert!(check_params(&[0.5, 0.1, 0.05, 1.0], "a, b, c").is_err());
+        assert!(check_params(&[0.5, f64::INFINITY, 0.05], "a, b, c").is_err());
+        assert!(split_explanation(f64::NAN, 0.5, &[0.5, 0.1, 0.05], Calibration::default()).is_err());
+        assert!(check_knapsack(&[1.0, 2.0], &[1]).is_err());
+        assert!(check_knapsack(&[1.0, f64::NAN], &[1, 1]).is_err());
+        assert!(constrained_knapsack(&[f64::INFINITY], &[1], 10, &[], &[]).is_err());
+        assert!(two_budget_knapsack(&[f64::NAN], &[1], &[1.0], 10, 10.0).is_err());
+    }
+    
+    #[test]
+    fn test_exact_knapsack_falls_back_to_greedy() {
+        let gains = vec![7.0, 5.0, 5.0];
+        let costs = vec![6, 5, 5];
+        let mut greedy = greedy_knapsack(gains.clone(), costs.clone(), 10);
+        greedy.sort_unstable();
+        assert_eq!(exact_knapsack(gains, costs, 10, 10), greedy);
+        // Ascending, though greedy takes the better ratio first
+        assert_eq!(exact_knapsack(vec![1.0, 5.0], vec![1, 1], 10, 2), vec![0, 1]);
+    }
+    
+    #[test]
+    fn test_exact_knapsack_huge_budget() {
+        assert_eq!(exact_knapsack(vec![], vec![], usize::MAX, DEFAULT_MAX_DP_CELLS), Vec::<usize>::new());
+        assert_eq!(exact_knapsack(vec![3.0, 4.0], vec![2, 3], usize::MAX, DEFAULT_MAX_DP_CELLS), vec![0, 1]);
+    }
+    
+    #[test]
+    fn test_exact_knapsack_skips_useless_items() {
+        assert_eq!(exact_knapsack(vec![-1.0, 0.0, 2.0], vec![1, 1, 20], 10, DEFAULT_MAX_DP_CELLS), Vec::<usize>::new());
+        assert_eq!(exact_knapsack(vec![1.0], vec![1, 2], 10, DEFAULT_MAX_DP_CELLS), Vec::<usize>::new());
+    }
+    
+    #[quickcheck_macros::quickcheck]
+    fn prop_exact_knapsack_at_least_greedy(items: Vec<(u8, u8)>, budget: u8) -> bool {
+        let gains: Vec<f64> = items.iter().map(|&(g, _)| g as f64).collect();
+        let costs: Vec<usize> = items.iter().map(|&(_, c)| c as usize + 1).collect();
+        let total = |selected: &[usize]| -> (f64, usize) {
+            (selected.iter().map(|&i| gains[i]).sum(), selected.iter().map(|&i| costs[i]).sum())
+        };
+        let (exact_gain, exact_cost) = total(&exact_knapsack(gains.clone(), costs.clone(), budget as usize, DEFAULT_MAX_DP_CELLS));
+        let (greedy_gain, _) = total(&greedy_knapsack(gains.clone(), costs.clone(), budget as usize));
+        exact_cost <= budget as usize && exact_gain >= greedy_gain
+    }
+    
+    #[test]
+    fn test_interpolate_policy() {
+        assert_eq!(interpolate_policy(&[0.0, 2.0], &[1.0, 4.0], 0.5), Ok(vec![0.5, 3.0]));
+        assert_eq!(interpolate_policy(&[0.0, 2.0], &[1.0, 4.0], 0.0), Ok(vec![0.0, 2.0]));
+        assert!(interpolate_policy(&[0.0], &[1.0, 4.0], 0.5).is_err());
+        assert!(interpolate_policy(&[0.0], &[1.0], 1.5).is_err());
+    }
+    
+    #[test]
+    fn test_interpolate_policy_tiers() {
+        let tiers = [0.0, 50.0, 100.0];
+        let policies = vec![vec![0.0, 1.0], vec![1.0, 1.0], vec![3.0, 0.0]];
+        assert_eq!(interpolate_policy_tiers(-10.0, &tiers, &policies), Ok(vec![0.0, 1.0]));
+        assert_eq!(interpolate_policy_tiers(25.0, &tiers, &policies), Ok(vec![0.5, 1.0]));
+        assert_eq!(interpolate_policy_tiers(50.0, &tiers, &policies), Ok(vec![1.0, 1.0]));
+        assert_eq!(interpolate_policy_tiers(75.0, &tiers, &policies), Ok(vec![2.0, 0.5]));
+        assert_eq!(interpolate_policy_tiers(150.0, &tiers, &policies), Ok(vec![3.0, 0.0]));
+        assert!(interpolate_policy_tiers(10.0, &[50.0, 0.0], &policies[..2]).is_err());
+        assert!(interpolate_policy_tiers(10.0, &tiers, &policies[..2]).is_err());
+    }
+    
+    #[test]
+    fn test_batch_decisions_match_scalar() {
+        let params = vec![0.5, 0.1, 0.05];
+        let entropy = [0.9, 0.1, 0.7];
+        let density = [0.5, 0.1, 0.9];
+        let calibration = Calibration::default();
+        let expected: Vec<bool> = entropy.iter().zip(&density).map(|(&e, &d)| split_decision(e, d, &params, Calibration::default())).collect();
+        assert_eq!(batch_decisions(&entropy, &density, &params, "a, b, c", calibration, split_decision), Ok(expected));
+        let expected: Vec<bool> = entropy.iter().zip(&density).map(|(&j, &t)| merge_decision(j, t, &params, Calibration::default())).collect();
+        assert_eq!(batch_decisions(&entropy, &density, &params, "d, e, f", calibration, merge_decision), Ok(expected));
+        assert!(batch_decisions(&entropy, &density, &[0.5], "a, b, c", calibration, split_decision).is_err());
+        assert!(batch_decisions(&entropy, &density[..2], &params, "a, b, c", calibration, split_decision).is_err());
+        assert!(batch_decisions(&[f64::NAN], &[0.0], &params, "a, b, c", calibration, split_decision).is_err());
+    }
+    
+    #[test]
+    fn test_shannon_entropy() {
+        assert_eq!(shannon_entropy(&[1.0, 1.0], false), Ok(1.0));
+        assert_eq!(shannon_entropy(&[5.0, 0.0], false), Ok(0.0));
+        assert_eq!(shannon_entropy(&[], false), Ok(0.0));
+        assert!((shannon_entropy(&[0.25; 4], false).unwrap() - 2.0).abs() < 1e-12);
+        assert!((shannon_entropy(&[3.0, 3.0, 3.0], true).unwrap() - 1.0).abs() < 1e-12);
+        assert!(shannon_entropy(&[0.5, -0.1], false).is_err());
+        assert!(shannon_entropy(&[f64::NAN], false).is_err());
+    }
+    
+    #[test]
+    fn test_js_divergence() {
+        assert_eq!(js_divergence(&[0.5, 0.5], &[1.0, 1.0]), Ok(0.0));
+        assert_eq!(js_divergence(&[1.0, 0.0], &[0.0, 1.0]), Ok(1.0));
+        let d = js_divergence(&[0.9, 0.1, 0.0], &[0.1, 0.8, 0.1]).unwrap();
+        assert!(d > 0.0 && d < 1.0);
+        assert_eq!(Ok(d), js_divergence(&[0.1, 0.8, 0.1], &[0.9, 0.1, 0.0]));
+        assert!(js_divergence(&[0.0, 0.0], &[0.5, 0.5]).is_err());
+        assert!(js_divergence(&[1.0], &[0.5, 0.5]).is_err());
+    }
+    
+    #[test]
+    fn test_js_divergence_matrix() {
+        let distributions = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]];
+        let matrix = js_divergence_matrix(&distributions).unwrap();
+        assert_eq!(matrix[0][0], 0.0);
+        assert_eq!(matrix[0][1], 1.0);
+        assert_eq!(matrix[1][0], 1.0);
+        assert_eq!(matrix[0][2], matrix[2][0]);
+        assert_eq!(Ok(matrix[1][2]), js_divergence(&distributions[1], &distributions[2]));
+    }
+    
+    #[test]
+    fn test_error_densities() {
+        let spans = [(0, 10), (10, 20), (5, 15), (20, 20)];
+        let densities = error_densities(&spans, vec![12, 3, 9, 10, 25]).unwrap();
+        assert_eq!(densities, vec![0.2, 0.2, 0.3, 0.0]);
+        assert!(error_densities(&[(5, 4)], vec![]).is_err());
+    }
+    
+    #[test]
+    fn test_fit_logistic_recovers_weights() {
+        // Expected label frequencies from σ(-1 + 4·x1 - 2·x2) on a grid
+        let truth = [-1.0, 4.0, -2.0];
+        let mut features = Vec::new();
+        let mut labels = Vec::new();
+        for i in 0..=10 {
+            for j in 0..=10 {
+                let (x1, x2) = (i as f64 / 10.0, j as f64 / 10.0);
+                let positives = (sigmoid(truth[0] + truth[1] * x1 + truth[2] * x2) * 100.0).round() as usize;
+                for k in 0..100 {
+                    features.push(vec![x1, x2]);
+                    labels.push(k < positives);
+                }
+            }
+        }
+        let (weights, _, converged) = fit_logistic(&features, &labels, 0.0, 100).unwrap();
+        assert!(converged);
+        for (fitted, expected) in weights.iter().zip(truth) {
+            assert!((fitted - expected).abs() < 0.05, "{:?}", weights);
+        }
+    }
+    
+    #[test]
+    fn test_fit_logistic_rejects_bad_input() {
+        assert!(fit_logistic(&[vec![0.1, 0.2]], &[true, false], 1e-3, 10).is_err());
+        assert!(fit_logistic(&[vec![0.1]], &[true], 1e-3, 10).is_err());
+        assert!(fit_logistic(&[], &[], 1e-3, 10).is_err());
+        // One class only still gives finite parameters thanks to the penalty
+        let (weights, _, _) = fit_logistic(&[vec![0.1, 0.2], vec![0.3, 0.1]], &[true, true], 1e-3, 100).unwrap();
+        assert!(weights.iter().all(|w| w.is_finite()));
+    }
+    
+    #[test]
+    fn test_policy_gradients() {
+        let params = [0.5, 0.1, 0.05];
+        let features = vec![vec![0.9, 0.5], vec![0.1, 0.0]];
+        let gradients = policy_gradients(&features, &params, Calibration::default()).unwrap();
+        for (row, gradient) in features.iter().zip(&gradients) {
+            let p = sigmoid(params[0] + params[1] * row[0] + params[2] * row[1]);
+            let slope = p * (1.0 - p);
+            assert!((gradient[0] - slope).abs() < 1e-12);
+            assert!((gradient[1] - slope * row[0]).abs() < 1e-12);
+            assert!((gradient[2] - slope * row[1]).abs() < 1e-12);
+        }
+        assert!(policy_gradients(&features, &params[..2], Calibration::default()).is_err());
+        assert!(policy_gradients(&[vec![1.0]], &params, Calibration::default()).is_err());
+    }
+    
+    #[test]
+    fn test_constrained_knapsack() {
+        let gains = [10.0, 8.0, 5.0, 3.0];
+        let costs = [300, 250, 200, 150];
+        assert_eq!(constrained_knapsack(&gains, &costs, 600, &[], &[]), Ok(greedy_knapsack(gains.to_vec(), costs.to_vec(), 600)));
+        // Item 3 is forced in; 0 and 1 are alternatives
+        assert_eq!(constrained_knapsack(&gains, &costs, 600, &[3], &[vec![0, 1]]), Ok(vec![3, 0]));
+        assert!(constrained_knapsack(&gains, &costs, 600, &[0, 1], &[vec![0, 1]]).is_err());
+        assert!(constrained_knapsack(&gains, &costs, 400, &[0, 1], &[]).is_err());
+        assert!(constrained_knapsack(&gains, &costs, 600, &[4], &[]).is_err());
+    }
+    
+    #[test]
+    fn test_two_budget_knapsack() {
+        let gains = [10.0, 8.0, 5.0, 3.0];
+        let tokens = [300, 250, 200, 150];
+        let latency = [50.0, 400.0, 20.0, 20.0];
+        // Item 1 fits the token budget but not the latency budget
+        let selected = two_budget_knapsack(&gains, &tokens, &latency, 700, 100.0).unwrap();
+        assert_eq!(selected, vec![0, 2, 3]);
+        assert!(two_budget_knapsack(&gains, &tokens, &latency[..2], 700, 100.0).is_err());
+        assert!(two_budget_knapsack(&gains, &tokens, &latency, 700, f64::NAN).is_err());
+        assert_eq!(two_budget_knapsack(&gains, &tokens, &latency, 700, 0.0), Ok(vec![]));
+    }
+    
+    #[test]
+    fn test_allocate_budgets() {
+        assert_eq!(allocate_budgets(&[3.0, 1.0], 100, None), Ok(vec![75, 25]));
+        // Thirds: the leftover token goes to the first of the tied remainders
+        assert_eq!(allocate_budgets(&[1.0, 1.0, 1.0], 100, None), Ok(vec![34, 33, 33]));
+        assert_eq!(allocate_budgets(&[2.0, 0.0, -1.0], 10, None), Ok(vec![10, 0, 0]));
+        // The first conversation can only use 20 tokens; the rest spill over
+        assert_eq!(allocate_budgets(&[3.0, 1.0, 1.0], 100, Some(&[20, 100, 100])), Ok(vec![20, 40, 40]));
+        assert_eq!(allocate_budgets(&[1.0, 1.0], 100, Some(&[10, 20])), Ok(vec![10, 20]));
+        assert_eq!(allocate_budgets(&[], 100, None), Ok(vec![]));
+        assert!(allocate_budgets(&[f64::NAN], 100, None).is_err());
+        assert!(allocate_budgets(&[1.0], 100, Some(&[])).is_err());
+    }
+    
+    #[test]
+    fn test_knapsack_result() {
+        let gains = [10.0, 8.0, 5.0, 3.0];
+        let costs = [300, 250, 200, 150];
+        let result = KnapsackResult::new(greedy_knapsack(gains.to_vec(), costs.to_vec(), 600), &gains, &costs, 600);
+        assert_eq!(result.selected, vec![0, 1]);
+        assert_eq!(result.total_gain, 18.0);
+        assert_eq!(result.tokens_used, 550);
+        assert!((result.utilization - 550.0 / 6.0).abs() < 1e-9);
+        assert_eq!(result.first_rejected_ratio, Some(5.0 / 200.0));
+        let everything = KnapsackResult::new(vec![0, 1, 2, 3], &gains, &costs, 0);
+        assert_eq!((everything.first_rejected_ratio, everything.utilization), (None, 0.0));
+    }
+    
+    #[test]
+    fn test_decision_scores() {
+        let params = vec![0.5, 0.1, 0.05];
+        let score = split_explanation(0.9, 0.5, &params, Calibration::default()).unwrap();
+        assert_eq!(score.decision, split_decision(0.9, 0.5, &params, Calibration::default()));
+        assert!((score.logit - (0.5 + 0.09 + 0.025)).abs() < 1e-12);
+        assert_eq!(score.margin > 0.0, score.decision);
+        assert_eq!(score.contributions[0].0, "entropy");
+        let score = merge_explanation(0.9, 0.1, &params, Calibration::default()).unwrap();
+        assert_eq!(score.decision, merge_decision(0.9, 0.1, &params, Calibration::default()));
+        assert_eq!(score.margin > 0.0, score.decision);
+        assert!(split_explanation(0.9, 0.5, &params[..2], Calibration::default()).is_err());
+    }
 }
-
@@ -0,0 +1,234 @@
+//! Link functions mapping a decision's linear term to a probability
+//!
+//! τ_split and τ_merge pass `a + b·x + c·y` through a link function, the
+//! sigmoid unless callers pick another. Dividing the linear term by a
+//! temperature first flattens (T > 1) or sharpens (T < 1) the probabilities;
+//! `calibrate_temperature` picks T from logged outcomes.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// Shape of the link function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Link {
+    #[default]
+    Sigmoid,
+    /// Standard normal CDF
+    Probit,
+    /// 0.5 + x/4 clamped to [0, 1]; the sigmoid's tangent at 0
+    Linear,
+}
+
+impl Link {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sigmoid" => Some(Link::Sigmoid),
+            "probit" => Some(Link::Probit),
+            "linear" => Some(Link::Linear),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Link::Sigmoid => "sigmoid",
+            Link::Probit => "probit",
+            Link::Linear => "linear",
+        }
+    }
+
+    pub fn apply(self, x: f64) -> f64 {
+        match self {
+            Link::Sigmoid => crate::sigmoid(x),
+            Link::Probit => 0.5 * (1.0 + erf(x / std::f64::consts::SQRT_2)),
+            Link::Linear => (0.5 + x / 4.0).clamp(0.0, 1.0),
+        }
+    }
+
+    /// Slope of `apply` at `x`
+    pub fn derivative(self, x: f64) -> f64 {
+        match self {
+            Link::Sigmoid => {
+                let p = crate::sigmoid(x);
+                p * (1.0 - p)
+            }
+            Link::Probit => (-x * x / 2.0).exp() / (2.0 * std::f64::consts::PI).sqrt(),
+            Link::Linear => {
+                if x.abs() < 2.0 {
+                    0.25
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// Link function plus temperature
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub link: Link,
+    pub temperature: f64,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self { link: Link::Sigmoid, temperature: 1.0 }
+    }
+}
+
+impl Calibration {
+    pub fn new(link: Link, temperature: f64) -> Result<Self, String> {
+        if !(temperature.is_finite() && temperature > 0.0) {
+            return Err(format!("temperature must be positive, got {}", temperature));
+        }
+        Ok(Self { link, temperature })
+    }
+
+    pub fn apply(self, x: f64) -> f64 {
+        self.link.apply(x / self.temperature)
+    }
+
+    /// Slope of `apply` at `x`
+    pub fn derivative(self, x: f64) -> f64 {
+        self.link.derivative(x / self.temperature) / self.temperature
+    }
+}
+
+/// `Calibration` from the `link`/`temperature` arguments of a pyfunction
+pub fn calibration(link: &str, temperature: f64) -> PyResult<Calibration> {
+    let link = Link::parse(link)
+        .ok_or_else(|| PyValueError::new_err(format!("Unknown link {}; expected sigmoid, probit or linear", link)))?;
+    Calibration::new(link, temperature).map_err(PyValueError::new_err)
+}
+
+/// Error function, Abramowitz & Stegun 7.1.26 (absolute error below 1.5e-7)
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let poly = t * (0.254_829_592 + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let y = 1.0 - poly * (-x * x).exp();
+    if x < 0.0 {
+        -y
+    } else {
+        y
+    }
+}
+
+/// Mean negative log-likelihood of `outcomes` under `calibration(scores)`
+fn log_loss(scores: &[f64], outcomes: &[bool], calibration: Calibration) -> f64 {
+    const EPS: f64 = 1e-12;
+    let total: f64 = scores
+        .iter()
+        .zip(outcomes)
+        .map(|(&score, &outcome)| {
+            let p = calibration.apply(score).clamp(EPS, 1.0 - EPS);
+            if outcome {
+                -p.ln()
+            } else {
+                -(1.0 - p).ln()
+            }
+        })
+        .sum();
+    total / scores.len() as f64
+}
+
+/// Temperature in [1e-3, 1e3] minimizing the log loss, by golden-section
+/// search over log T; returns (temperature, log loss)
+pub fn fit_temperature(scores: &[f64], outcomes: &[bool], link: Link) -> Result<(f64, f64), String> {
+    if scores.len() != outcomes.len() {
+        return Err(format!("Got {} scores and {} outcomes", scores.len(), outcomes.len()));
+    }
+    if scores.is_empty() {
+        return Err("Need at least one scored outcome".to_string());
+    }
+    if let Some(bad) = scores.iter().find(|s| !s.is_finite()) {
+        return Err(format!("Scores must be finite, got {}", bad));
+    }
+
+    let loss = |log_t: f64| log_loss(scores, outcomes, Calibration { link, temperature: log_t.exp() });
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut lo, mut hi) = (1e-3f64.ln(), 1e3f64.ln());
+    let mut x1 = hi - ratio * (hi - lo);
+    let mut x2 = lo + ratio * (hi - lo);
+    let (mut f1, mut f2) = (loss(x1), loss(x2));
+    while hi - lo > 1e-6 {
+        if f1 <= f2 {
+            hi = x2;
+            x2 = x1;
+            f2 = f1;
+            x1 = hi - ratio * (hi - lo);
+            f1 = loss(x1);
+        } else {
+            lo = x1;
+            x1 = x2;
+            f1 = f2;
+            x2 = lo + ratio * (hi - lo);
+            f2 = loss(x2);
+        }
+    }
+    let log_t = (lo + hi) / 2.0;
+    Ok((log_t.exp(), loss(log_t)))
+}
+
+/// Temperature that best calibrates decision scores (the linear terms, as in
+/// `DecisionScore.logit`) against observed outcomes under `link`
+///
+/// Returns `(temperature, log_loss)`; pass the temperature to the decision
+/// functions.
+#[pyfunction]
+#[pyo3(signature = (scores, outcomes, link="sigmoid"))]
+pub fn calibrate_temperature(py: Python<'_>, scores: Vec<f64>, outcomes: Vec<bool>, link: &str) -> PyResult<(f64, f64)> {
+    let _span = aios_trace::span!("calibrate_temperature", samples = scores.len(), link = link);
+    let link = calibration(link, 1.0)?.link;
+    py.allow_threads(|| fit_temperature(&scores, &outcomes, link)).map_err(PyValueError::new_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_links() {
+        assert_eq!(Link::parse("logit"), None);
+        for link in [Link::Sigmoid, Link::Probit, Link::Linear] {
+            assert_eq!(Link::parse(link.name()), Some(link));
+            assert!((link.apply(0.0) - 0.5).abs() < 1e-7);
+            assert!(link.apply(2.0) > 0.5 && link.apply(-2.0) < 0.5);
+        }
+        assert!((Link::Probit.apply(1.0) - 0.841_344_746).abs() < 1e-6);
+        assert_eq!(Link::Linear.apply(5.0), 1.0);
+        assert!(Calibration::new(Link::Sigmoid, 0.0).is_err());
+        let flat = Calibration::new(Link::Sigmoid, 2.0).unwrap();
+        assert_eq!(flat.apply(2.0), crate::sigmoid(1.0));
+    }
+
+    #[test]
+    fn test_derivatives_match_finite_differences() {
+        let h = 1e-6;
+        for link in [Link::Sigmoid, Link::Probit, Link::Linear] {
+            let calibration = Calibration::new(link, 1.5).unwrap();
+            for x in [-1.0, 0.3, 2.0] {
+                let numeric = (calibration.apply(x + h) - calibration.apply(x - h)) / (2.0 * h);
+                assert!((calibration.derivative(x) - numeric).abs() < 1e-6, "{:?} at {}", link, x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fit_temperature_recovers_scale() {
+        // Outcome rates follow σ(score / 2), so the best temperature is about 2
+        let mut scores = Vec::new();
+        let mut outcomes = Vec::new();
+        for i in -20..=20 {
+            let score = i as f64 / 4.0;
+            let positives = (crate::sigmoid(score / 2.0) * 200.0).round() as usize;
+            for k in 0..200 {
+                scores.push(score);
+                outcomes.push(k < positives);
+            }
+        }
+        let (temperature, _) = fit_temperature(&scores, &outcomes, Link::Sigmoid).unwrap();
+        assert!((temperature - 2.0).abs() < 0.05, "{}", temperature);
+        assert!(fit_temperature(&[], &[], Link::Sigmoid).is_err());
+    }
+}
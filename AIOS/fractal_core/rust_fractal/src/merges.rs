@@ -0,0 +1,144 @@
+//! Merge proposals over many fragments at once
+//!
+//! Each fragment is an embedding. For a pair, `topic_shift` is one minus the
+//! cosine similarity of the embeddings and `js_div` the Jensen–Shannon
+//! divergence of the embeddings read as distributions: as they are when no
+//! entry is negative, through a softmax otherwise. Pairs passing τ_merge are
+//! taken by decreasing margin, each fragment joining at most one pair.
+
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+use crate::arrays::Rows;
+use crate::link::{calibration, Calibration};
+use crate::{check_params, js_divergence, merge_explanation};
+
+/// Row as a distribution for `js_divergence`
+fn as_distribution(row: &[f64]) -> Vec<f64> {
+    if row.iter().all(|&x| x >= 0.0) && row.iter().any(|&x| x > 0.0) {
+        return row.to_vec();
+    }
+    let max = row.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    row.iter().map(|&x| (x - max).exp()).collect()
+}
+
+/// 1 − cosine similarity; 1 when either vector is zero
+fn topic_shift(a: &[f64], b: &[f64], norm_a: f64, norm_b: f64) -> f64 {
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 1.0;
+    }
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    (1.0 - dot / (norm_a * norm_b)).clamp(0.0, 2.0)
+}
+
+/// Conflict-free merge pairs `(i, j)`, `i < j`, best margin first
+///
+/// With `adjacent_only` only neighbours `(i, i + 1)` are considered, as for
+/// fragments in reading order.
+pub fn propose_merges(
+    embeddings: &[Vec<f64>],
+    params: &[f64],
+    calibration: Calibration,
+    max_pairs: Option<usize>,
+    adjacent_only: bool,
+) -> Result<Vec<(usize, usize)>, String> {
+    check_params(params, "d, e, f")?;
+    let dims = embeddings.first().map_or(0, Vec::len);
+    if let Some(row) = embeddings.iter().find(|row| row.len() != dims || row.is_empty()) {
+        return Err(format!("Embeddings must all have the same non-zero length, got {} and {}", dims, row.len()));
+    }
+    if let Some(bad) = embeddings.iter().flatten().find(|x| !x.is_finite()) {
+        return Err(format!("Embeddings must be finite, got {}", bad));
+    }
+
+    let distributions: Vec<Vec<f64>> = embeddings.iter().map(|row| as_distribution(row)).collect();
+    let norms: Vec<f64> = embeddings.iter().map(|row| row.iter().map(|x| x * x).sum::<f64>().sqrt()).collect();
+    let n = embeddings.len();
+    let candidates: Vec<Vec<(usize, usize, f64)>> = (0..n)
+        .into_par_iter()
+        .map(|i| {
+            let last = if adjacent_only { (i + 2).min(n) } else { n };
+            (i + 1..last)
+                .map(|j| {
+                    let js_div = js_divergence(&distributions[i], &distributions[j])?;
+                    let shift = topic_shift(&embeddings[i], &embeddings[j], norms[i], norms[j]);
+                    let score = merge_explanation(js_div, shift, params, calibration)?;
+                    Ok((i, j, if score.decision { score.margin } else { f64::NAN }))
+                })
+                .filter(|candidate| !matches!(candidate, Ok((_, _, margin)) if margin.is_nan()))
+                .collect::<Result<Vec<_>, String>>()
+        })
+        .collect::<Result<_, _>>()?;
+    let mut candidates: Vec<(usize, usize, f64)> = candidates.into_iter().flatten().collect();
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2).then((a.0, a.1).cmp(&(b.0, b.1))));
+
+    let mut paired = vec![false; n];
+    let mut merges = Vec::new();
+    for (i, j, _margin) in candidates {
+        if merges.len() >= max_pairs.unwrap_or(usize::MAX) {
+            break;
+        }
+        if !paired[i] && !paired[j] {
+            paired[i] = true;
+            paired[j] = true;
+            merges.push((i, j));
+        }
+    }
+    Ok(merges)
+}
+
+/// Pairs of fragments to merge, from their embeddings, in one call
+///
+/// Applies `should_merge` with `params` to every pair (or every adjacent
+/// pair) and returns at most `max_pairs` pairs `(i, j)` with no fragment in
+/// two of them, most confident first.
+#[pyfunction]
+#[pyo3(
+    name = "propose_merges",
+    signature = (embeddings, params, max_pairs=None, adjacent_only=false, link="sigmoid", temperature=1.0)
+)]
+pub fn py_propose_merges(
+    py: Python<'_>,
+    embeddings: Rows<'_>,
+    params: Vec<f64>,
+    max_pairs: Option<usize>,
+    adjacent_only: bool,
+    link: &str,
+    temperature: f64,
+) -> PyResult<Vec<(usize, usize)>> {
+    let embeddings = embeddings.into_rows();
+    let _span = aios_trace::span!("propose_merges", fragments = embeddings.len(), adjacent_only = adjacent_only);
+    let calibration = calibration(link, temperature)?;
+    py.allow_threads(|| propose_merges(&embeddings, &params, calibration, max_pairs, adjacent_only))
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pairs_similar_fragments_once() {
+        // 0 and 1 are near-identical, 2 and 3 likewise, and 1 also resembles 2
+        let embeddings = vec![
+            vec![0.9, 0.1, 0.0],
+            vec![0.85, 0.15, 0.0],
+            vec![0.8, 0.0, 0.2],
+            vec![0.0, 0.1, 0.9],
+            vec![0.0, 0.12, 0.88],
+        ];
+        let params = [0.0, 0.0, -2.0];
+        let merges = propose_merges(&embeddings, &params, Calibration::default(), None, false).unwrap();
+        assert_eq!(merges, vec![(3, 4), (0, 1)]);
+        assert_eq!(propose_merges(&embeddings, &params, Calibration::default(), Some(1), false).unwrap(), vec![(3, 4)]);
+        assert_eq!(propose_merges(&embeddings, &params, Calibration::default(), None, true).unwrap(), vec![(3, 4), (0, 1)]);
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(propose_merges(&[vec![1.0], vec![1.0, 2.0]], &[0.0, 0.0, 0.0], Calibration::default(), None, false).is_err());
+        assert!(propose_merges(&[vec![f64::NAN]], &[0.0, 0.0, 0.0], Calibration::default(), None, false).is_err());
+        assert!(propose_merges(&[vec![1.0]], &[0.0], Calibration::default(), None, false).is_err());
+        assert_eq!(propose_merges(&[], &[0.0, 0.0, 0.0], Calibration::default(), None, false), Ok(vec![]));
+    }
+}
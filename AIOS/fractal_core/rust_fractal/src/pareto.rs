@@ -0,0 +1,100 @@
+//! Gain-versus-cost trade-off of a set of spans
+//!
+//! Two views: the spans no other span beats on both gain and cost, and the
+//! cumulative curve of taking spans in `greedy_knapsack`'s ratio order, whose
+//! prefix ending at a budget is what the greedy solver picks when nothing
+//! is skipped.
+
+use pyo3::prelude::*;
+
+use crate::arrays::{Counts, Floats};
+use crate::{check_knapsack, ratio_order};
+
+/// Non-dominated spans and the cumulative gain/cost curve
+#[pyclass(get_all)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParetoFrontier {
+    /// Spans that no other span matches or beats on gain at no more cost,
+    /// by ascending cost
+    pub frontier: Vec<usize>,
+    /// Spans with positive gain in ratio order, best first
+    pub curve_spans: Vec<usize>,
+    /// Tokens used after taking each of `curve_spans`
+    pub curve_costs: Vec<usize>,
+    /// Gain after taking each of `curve_spans`
+    pub curve_gains: Vec<f64>,
+}
+
+#[pymethods]
+impl ParetoFrontier {
+    fn __repr__(&self) -> String {
+        format!(
+            "ParetoFrontier(frontier={:?}, curve_points={}, max_cost={}, max_gain={})",
+            self.frontier,
+            self.curve_spans.len(),
+            self.curve_costs.last().copied().unwrap_or(0),
+            self.curve_gains.last().copied().unwrap_or(0.0)
+        )
+    }
+}
+
+/// Frontier and curve for `gains` and `costs`
+pub fn pareto_frontier(gains: &[f64], costs: &[usize]) -> Result<ParetoFrontier, String> {
+    check_knapsack(gains, costs)?;
+
+    // By cost, and by gain (best first) among equal costs; a span is on the
+    // frontier when it beats every cheaper span's gain, or ties the span
+    // that set the best gain exactly
+    let mut by_cost: Vec<usize> = (0..gains.len()).collect();
+    by_cost.sort_by(|&a, &b| costs[a].cmp(&costs[b]).then(gains[b].total_cmp(&gains[a])).then(a.cmp(&b)));
+    let mut frontier = Vec::new();
+    let mut best: Option<usize> = None;
+    for idx in by_cost {
+        match best {
+            Some(best) if gains[idx] == gains[best] && costs[idx] == costs[best] => {}
+            Some(best) if gains[idx] <= gains[best] => continue,
+            _ => best = Some(idx),
+        }
+        frontier.push(idx);
+    }
+
+    let curve_spans: Vec<usize> = ratio_order(gains, costs).into_iter().filter(|&idx| gains[idx] > 0.0).collect();
+    let (mut cost, mut gain) = (0usize, 0.0f64);
+    let (curve_costs, curve_gains) = curve_spans
+        .iter()
+        .map(|&idx| {
+            cost += costs[idx];
+            gain += gains[idx];
+            (cost, gain)
+        })
+        .unzip();
+    Ok(ParetoFrontier { frontier, curve_spans, curve_costs, curve_gains })
+}
+
+/// Non-dominated spans and the cumulative gain/cost curve of greedy
+/// selection, for plotting the budget trade-off
+#[pyfunction]
+#[pyo3(name = "pareto_frontier")]
+pub fn py_pareto_frontier(gains: Floats<'_>, costs: Counts<'_>) -> PyResult<ParetoFrontier> {
+    let gains = gains.into_vec();
+    let _span = aios_trace::span!("pareto_frontier", items = gains.len());
+    pareto_frontier(&gains, &costs.into_vec()?).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pareto_frontier() {
+        let gains = [10.0, 8.0, 5.0, 3.0, 9.0, 5.0, -1.0];
+        let costs = [300, 250, 200, 150, 400, 200, 10];
+        let result = pareto_frontier(&gains, &costs).unwrap();
+        // 4 costs more than 0 for less gain; 5 ties 2 exactly
+        assert_eq!(result.frontier, vec![6, 3, 2, 5, 1, 0]);
+        assert_eq!(result.curve_spans, vec![0, 1, 2, 5, 4, 3]);
+        assert_eq!(result.curve_costs, vec![300, 550, 750, 950, 1350, 1500]);
+        assert_eq!(result.curve_gains.last(), Some(&40.0));
+        assert!(pareto_frontier(&[1.0], &[]).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+//! Decision policies as one checkpointable object
+//!
+//! A `Policy` bundles the split and merge parameters with the link function,
+//! temperature and hysteresis they were tuned with, and round-trips through
+//! a versioned JSON document. Hysteresis keeps a decision from flapping: once
+//! a fragment has been split (or a pair merged), the margin has to drop below
+//! `-hysteresis` to undo it, and to reach `hysteresis` to make it.
+
+// `#[new]` under PyO3 0.20 expands to impls that newer rustc flags
+#![allow(unknown_lints, non_local_definitions)]
+
+use std::path::PathBuf;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::link::{calibration, Calibration};
+use crate::{merge_explanation, split_explanation, DecisionScore};
+
+/// Version written by `Policy.to_json`; documents from later versions are
+/// refused rather than half-read
+pub const POLICY_VERSION: u32 = 1;
+
+/// The JSON document
+#[derive(Debug, Serialize, Deserialize)]
+struct PolicyFile {
+    version: u32,
+    split_params: Vec<f64>,
+    merge_params: Vec<f64>,
+    link: String,
+    temperature: f64,
+    #[serde(default)]
+    hysteresis: f64,
+}
+
+/// Split/merge parameters with the calibration they were tuned under
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    split_params: Vec<f64>,
+    merge_params: Vec<f64>,
+    calibration: Calibration,
+    hysteresis: f64,
+}
+
+impl Policy {
+    pub fn new(split_params: Vec<f64>, merge_params: Vec<f64>, calibration: Calibration, hysteresis: f64) -> Result<Self, String> {
+        for (name, params) in [("split_params", &split_params), ("merge_params", &merge_params)] {
+            if params.len() != 3 || params.iter().any(|p| !p.is_finite()) {
+                return Err(format!("{} must be three finite numbers, got {:?}", name, params));
+            }
+        }
+        if !(hysteresis.is_finite() && hysteresis >= 0.0) {
+            return Err(format!("hysteresis must be non-negative, got {}", hysteresis));
+        }
+        Ok(Self { split_params, merge_params, calibration, hysteresis })
+    }
+
+    pub fn to_json(&self) -> String {
+        let file = PolicyFile {
+            version: POLICY_VERSION,
+            split_params: self.split_params.clone(),
+            merge_params: self.merge_params.clone(),
+            link: self.calibration.link.name().to_string(),
+            temperature: self.calibration.temperature,
+            hysteresis: self.hysteresis,
+        };
+        serde_json::to_string_pretty(&file).expect("policy fields serialize")
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let file: PolicyFile = serde_json::from_str(text).map_err(|e| format!("Invalid policy JSON: {}", e))?;
+        if file.version == 0 || file.version > POLICY_VERSION {
+            return Err(format!("Unsupported policy version {}; this build reads up to {}", file.version, POLICY_VERSION));
+        }
+        let link = crate::link::Link::parse(&file.link).ok_or_else(|| format!("Unknown link {}", file.link))?;
+        Self::new(file.split_params, file.merge_params, Calibration::new(link, file.temperature)?, file.hysteresis)
+    }
+
+    /// Linear blend towards `other`: parameters, temperature and hysteresis
+    /// are interpolated, and the link is whichever policy is nearer
+    pub fn blend(&self, other: &Self, alpha: f64) -> Self {
+        let mix = |a: f64, b: f64| a + alpha * (b - a);
+        let lerp = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(&a, &b)| mix(a, b)).collect();
+        let link = if alpha < 0.5 { self.calibration.link } else { other.calibration.link };
+        Self {
+            split_params: lerp(&self.split_params, &other.split_params),
+            merge_params: lerp(&self.merge_params, &other.merge_params),
+            calibration: Calibration { link, temperature: mix(self.calibration.temperature, other.calibration.temperature) },
+            hysteresis: mix(self.hysteresis, other.hysteresis),
+        }
+    }
+
+    /// Apply hysteresis to a decision margin given the previous decision
+    fn decide(&self, margin: f64, previous: Option<bool>) -> bool {
+        match previous {
+            None => margin > 0.0,
+            Some(true) => margin > -self.hysteresis,
+            Some(false) => margin > self.hysteresis,
+        }
+    }
+
+    /// Errors only for non-finite features
+    pub fn split_score(&self, entropy: f64, error_density: f64, previous: Option<bool>) -> Result<DecisionScore, String> {
+        let mut score = split_explanation(entropy, error_density, &self.split_params, self.calibration)?;
+        score.decision = self.decide(score.margin, previous);
+        Ok(score)
+    }
+
+    /// Errors only for non-finite features
+    pub fn merge_score(&self, js_div: f64, topic_shift: f64, previous: Option<bool>) -> Result<DecisionScore, String> {
+        let mut score = merge_explanation(js_div, topic_shift, &self.merge_params, self.calibration)?;
+        score.decision = self.decide(score.margin, previous);
+        Ok(score)
+    }
+}
+
+#[pymethods]
+impl Policy {
+    #[new]
+    #[pyo3(signature = (split_params, merge_params, link="sigmoid", temperature=1.0, hysteresis=0.0))]
+    fn py_new(split_params: Vec<f64>, merge_params: Vec<f64>, link: &str, temperature: f64, hysteresis: f64) -> PyResult<Self> {
+        Self::new(split_params, merge_params, calibration(link, temperature)?, hysteresis).map_err(PyValueError::new_err)
+    }
+
+    /// The policy as a versioned JSON document
+    #[pyo3(name = "to_json")]
+    fn py_to_json(&self) -> String {
+        self.to_json()
+    }
+
+    /// Read a document written by `to_json`; ValueError for malformed JSON,
+    /// bad parameters or a newer version
+    #[staticmethod]
+    #[pyo3(name = "from_json")]
+    fn py_from_json(text: &str) -> PyResult<Self> {
+        Self::from_json(text).map_err(PyValueError::new_err)
+    }
+
+    fn save(&self, path: PathBuf) -> PyResult<()> {
+        let _span = aios_trace::span!("Policy.save", path = path.display().to_string());
+        std::fs::write(&path, self.to_json())?;
+        Ok(())
+    }
+
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        let _span = aios_trace::span!("Policy.load", path = path.display().to_string());
+        Self::from_json(&std::fs::read_to_string(&path)?).map_err(PyValueError::new_err)
+    }
+
+    /// τ_split under this policy; pass the fragment's `previous` decision
+    /// to apply hysteresis
+    #[pyo3(signature = (entropy, error_density, previous=None))]
+    fn should_split(&self, entropy: f64, error_density: f64, previous: Option<bool>) -> PyResult<bool> {
+        Ok(self.py_split_score(entropy, error_density, previous)?.decision)
+    }
+
+    /// τ_merge under this policy; pass the pair's `previous` decision to
+    /// apply hysteresis
+    #[pyo3(signature = (js_div, topic_shift, previous=None))]
+    fn should_merge(&self, js_div: f64, topic_shift: f64, previous: Option<bool>) -> PyResult<bool> {
+        Ok(self.py_merge_score(js_div, topic_shift, previous)?.decision)
+    }
+
+    #[pyo3(name = "split_score", signature = (entropy, error_density, previous=None))]
+    fn py_split_score(&self, entropy: f64, error_density: f64, previous: Option<bool>) -> PyResult<DecisionScore> {
+        self.split_score(entropy, error_density, previous).map_err(PyValueError::new_err)
+    }
+
+    #[pyo3(name = "merge_score", signature = (js_div, topic_shift, previous=None))]
+    fn py_merge_score(&self, js_div: f64, topic_shift: f64, previous: Option<bool>) -> PyResult<DecisionScore> {
+        self.merge_score(js_div, topic_shift, previous).map_err(PyValueError::new_err)
+    }
+
+    #[getter]
+    fn version(&self) -> u32 {
+        POLICY_VERSION
+    }
+
+    #[getter]
+    fn split_params(&self) -> Vec<f64> {
+        self.split_params.clone()
+    }
+
+    #[getter]
+    fn merge_params(&self) -> Vec<f64> {
+        self.merge_params.clone()
+    }
+
+    #[getter]
+    fn link(&self) -> &'static str {
+        self.calibration.link.name()
+    }
+
+    #[getter]
+    fn temperature(&self) -> f64 {
+        self.calibration.temperature
+    }
+
+    #[getter]
+    fn hysteresis(&self) -> f64 {
+        self.hysteresis
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Policy(split_params={:?}, merge_params={:?}, link={:?}, temperature={}, hysteresis={})",
+            self.split_params,
+            self.merge_params,
+            self.calibration.link.name(),
+            self.calibration.temperature,
+            self.hysteresis
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link::Link;
+
+    fn policy() -> Policy {
+        Policy::new(vec![0.5, 0.1, 0.05], vec![0.3, -0.2, 0.1], Calibration::new(Link::Probit, 2.0).unwrap(), 0.05).unwrap()
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let policy = policy();
+        assert_eq!(Policy::from_json(&policy.to_json()), Ok(policy));
+        assert!(Policy::from_json(r#"{"version": 2, "split_params": [0,0,0], "merge_params": [0,0,0], "link": "sigmoid", "temperature": 1}"#).is_err());
+        assert!(Policy::from_json(r#"{"version": 1, "split_params": [0,0], "merge_params": [0,0,0], "link": "sigmoid", "temperature": 1}"#).is_err());
+        let old = Policy::from_json(r#"{"version": 1, "split_params": [0,0,0], "merge_params": [0,0,0], "link": "sigmoid", "temperature": 1}"#);
+        assert_eq!(old.map(|p| p.hysteresis), Ok(0.0));
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let policy = policy();
+        let decide = |entropy, previous| policy.split_score(entropy, 0.0, previous).unwrap().decision;
+        let entropy = policy.split_score(0.6, 0.0, None).unwrap().threshold + 0.02;
+        // Inside the band: the previous decision stands
+        assert!(decide(entropy, None));
+        assert!(!decide(entropy, Some(false)));
+        assert!(decide(entropy - 0.04, Some(true)));
+        assert!(decide(entropy + 0.1, Some(false)));
+        assert!(policy.merge_score(f64::NAN, 0.0, None).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//! Branch-and-bound refinement of a greedy knapsack selection
+//!
+//! The search walks items in gain/cost ratio order, taking each one before
+//! leaving it out, and prunes a branch once its fractional (LP) bound cannot
+//! beat the best selection so far. Starting from the greedy selection as that
+//! best, it only ever improves on it; when the deadline passes it stops and
+//! returns the best selection found.
+
+use std::time::Instant;
+
+use crate::ratio_order;
+
+/// Nodes visited between deadline checks
+const NODES_PER_CHECK: usize = 256;
+
+/// Tolerance below which a bound does not count as beating the incumbent
+const EPSILON: f64 = 1e-12;
+
+/// What the search visits next
+enum Step {
+    /// Branch on the item at this position of the ratio order
+    Enter(usize),
+    /// Put back an item taken on the way down
+    Undo(usize),
+}
+
+/// Best selection found by `refine_knapsack`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Refinement {
+    /// Required items first, then the rest in ratio order; `initial` as
+    /// given when nothing better turned up
+    pub selected: Vec<usize>,
+    pub total_gain: f64,
+    /// Whether the search finished, proving `selected` optimal
+    pub complete: bool,
+}
+
+/// Improve `initial`, a feasible selection for the same constraints, until
+/// `deadline`
+///
+/// Constraints are those of `constrained_knapsack`: `required` items are
+/// always taken and at most one item of each of `exclusive_groups` is.
+/// Items with no positive gain are never taken beyond `required`.
+pub fn refine_knapsack(
+    gains: &[f64],
+    costs: &[usize],
+    budget: usize,
+    required: &[usize],
+    exclusive_groups: &[Vec<usize>],
+    initial: Vec<usize>,
+    deadline: Instant,
+) -> Refinement {
+    let n = costs.len();
+    let mut groups_of = vec![Vec::new(); n];
+    for (group, members) in exclusive_groups.iter().enumerate() {
+        for &idx in members.iter().filter(|&&idx| idx < n) {
+            groups_of[idx].push(group);
+        }
+    }
+    let mut group_users = vec![0usize; exclusive_groups.len()];
+    let mut is_required = vec![false; n];
+    let mut fixed = Vec::new();
+    let (mut gain, mut used) = (0.0f64, 0usize);
+    for &idx in required.iter().filter(|&&idx| idx < n) {
+        if !is_required[idx] {
+            is_required[idx] = true;
+            groups_of[idx].iter().for_each(|&group| group_users[group] += 1);
+            fixed.push(idx);
+            gain += gains[idx];
+            used += costs[idx];
+        }
+    }
+    let order: Vec<usize> = ratio_order(gains, costs)
+        .into_iter()
+        .filter(|&idx| !is_required[idx] && gains[idx] > 0.0 && costs[idx] <= budget)
+        .collect();
+
+    let mut best = Refinement { total_gain: initial.iter().map(|&idx| gains[idx]).sum(), selected: initial, complete: false };
+    if used > budget {
+        return best;
+    }
+    let mut taken: Vec<usize> = Vec::new();
+    let mut stack = vec![Step::Enter(0)];
+    let mut nodes = 0usize;
+    while let Some(step) = stack.pop() {
+        let k = match step {
+            Step::Enter(k) => k,
+            Step::Undo(idx) => {
+                taken.pop();
+                groups_of[idx].iter().for_each(|&group| group_users[group] -= 1);
+                gain -= gains[idx];
+                used -= costs[idx];
+                continue;
+            }
+        };
+        if nodes.is_multiple_of(NODES_PER_CHECK) && Instant::now() >= deadline {
+            return best;
+        }
+        nodes += 1;
+        if gain > best.total_gain + EPSILON {
+            best.total_gain = gain;
+            best.selected = fixed.iter().chain(&taken).copied().collect();
+        }
+        if k == order.len() {
+            continue;
+        }
+
+        // Fractional bound over the items still open to this branch
+        let free = |idx: usize| groups_of[idx].iter().all(|&group| group_users[group] == 0);
+        let (mut bound, mut room) = (gain, budget - used);
+        for &idx in &order[k..] {
+            if room == 0 {
+                break;
+            }
+            if costs[idx] > budget - used || !free(idx) {
+                continue;
+            }
+            if costs[idx] <= room {
+                bound += gains[idx];
+                room -= costs[idx];
+            } else {
+                bound += gains[idx] * room as f64 / costs[idx] as f64;
+                room = 0;
+            }
+        }
+        if bound <= best.total_gain + EPSILON {
+            continue;
+        }
+
+        let idx = order[k];
+        stack.push(Step::Enter(k + 1));
+        if used + costs[idx] <= budget && free(idx) {
+            groups_of[idx].iter().for_each(|&group| group_users[group] += 1);
+            gain += gains[idx];
+            used += costs[idx];
+            taken.push(idx);
+            stack.push(Step::Undo(idx));
+            stack.push(Step::Enter(k + 1));
+        }
+    }
+    best.complete = true;
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{constrained_knapsack, exact_knapsack, greedy_knapsack, DEFAULT_MAX_DP_CELLS};
+
+    fn soon() -> Instant {
+        Instant::now() + Duration::from_secs(5)
+    }
+
+    #[test]
+    fn test_refine_beats_greedy() {
+        let gains = [7.0, 5.0, 5.0];
+        let costs = [6, 5, 5];
+        let greedy = greedy_knapsack(gains.to_vec(), costs.to_vec(), 10);
+        let refined = refine_knapsack(&gains, &costs, 10, &[], &[], greedy.clone(), soon());
+        assert_eq!((refined.selected, refined.total_gain, refined.complete), (vec![1, 2], 10.0, true));
+        // Out of time before the first check: the greedy selection stands
+        let stale = refine_knapsack(&gains, &costs, 10, &[], &[], greedy.clone(), Instant::now());
+        assert_eq!(stale.selected, greedy);
+    }
+
+    #[test]
+    fn test_refine_keeps_constraints() {
+        let gains = [7.0, 5.0, 5.0, 1.0];
+        let costs = [6, 5, 5, 1];
+        let groups = [vec![0, 1]];
+        let initial = constrained_knapsack(&gains, &costs, 11, &[3], &groups).unwrap();
+        assert_eq!(initial, vec![3, 0]);
+        let refined = refine_knapsack(&gains, &costs, 11, &[3], &groups, initial, soon());
+        assert_eq!(refined.selected, vec![3, 1, 2]);
+        assert_eq!(refined.total_gain, 11.0);
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn prop_refine_matches_exact(items: Vec<(u8, u8)>, budget: u8) -> bool {
+        let items = &items[..items.len().min(12)];
+        let gains: Vec<f64> = items.iter().map(|&(g, _)| g as f64).collect();
+        let costs: Vec<usize> = items.iter().map(|&(_, c)| c as usize + 1).collect();
+        let budget = budget as usize;
+        let greedy = greedy_knapsack(gains.clone(), costs.clone(), budget);
+        let refined = refine_knapsack(&gains, &costs, budget, &[], &[], greedy, soon());
+        let exact: f64 = exact_knapsack(gains.clone(), costs.clone(), budget, DEFAULT_MAX_DP_CELLS).iter().map(|&i| gains[i]).sum();
+        let cost: usize = refined.selected.iter().map(|&i| costs[i]).sum();
+        refined.complete && cost <= budget && (refined.total_gain - exact).abs() < 1e-9
+    }
+}
@@ -0,0 +1,177 @@
+//! Decision policies chosen by karma tier
+//!
+//! `boundaries` split the karma axis into tiers, and `policies[i]` applies
+//! below `boundaries[i]` and from `boundaries[i - 1]` on. Within `blend / 2`
+//! of a boundary the two neighbouring policies are mixed with a smoothstep,
+//! so parameters do not jump when karma crosses into another tier.
+
+// `#[new]` under PyO3 0.20 expands to impls that newer rustc flags
+#![allow(unknown_lints, non_local_definitions)]
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::policy::Policy;
+
+/// Karma tiers, each with its own `Policy`
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct PolicySchedule {
+    boundaries: Vec<f64>,
+    policies: Vec<Policy>,
+    names: Vec<String>,
+    blend: f64,
+}
+
+impl PolicySchedule {
+    /// `names` default to `tier_0`, `tier_1`, ...
+    pub fn new(boundaries: Vec<f64>, policies: Vec<Policy>, names: Option<Vec<String>>, blend: f64) -> Result<Self, String> {
+        if policies.len() != boundaries.len() + 1 {
+            return Err(format!("Expected {} policies for {} boundaries, got {}", boundaries.len() + 1, boundaries.len(), policies.len()));
+        }
+        if boundaries.iter().any(|b| !b.is_finite()) || boundaries.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err("Boundaries must be finite and strictly ascending".to_string());
+        }
+        let narrowest = boundaries.windows(2).map(|pair| pair[1] - pair[0]).fold(f64::INFINITY, f64::min);
+        if !(blend.is_finite() && blend >= 0.0 && blend <= narrowest) {
+            return Err(format!("blend must be between 0 and the narrowest tier width ({}), got {}", narrowest, blend));
+        }
+        let names = names.unwrap_or_else(|| (0..policies.len()).map(|i| format!("tier_{}", i)).collect());
+        if names.len() != policies.len() {
+            return Err(format!("Got {} policies and {} names", policies.len(), names.len()));
+        }
+        Ok(Self { boundaries, policies, names, blend })
+    }
+
+    /// Index of the tier whose range holds `karma`
+    pub fn tier(&self, karma: f64) -> usize {
+        self.boundaries.partition_point(|&boundary| boundary <= karma)
+    }
+
+    /// The policy in effect at `karma`, blended near a boundary
+    pub fn policy_at(&self, karma: f64) -> Policy {
+        let tier = self.tier(karma);
+        let half = self.blend / 2.0;
+        // Nearest boundary: the tier's lower one or its upper one
+        let near = [tier.checked_sub(1), Some(tier)]
+            .into_iter()
+            .flatten()
+            .filter(|&j| j < self.boundaries.len())
+            .find(|&j| (karma - self.boundaries[j]).abs() < half);
+        match near {
+            Some(j) => {
+                let t = (karma - (self.boundaries[j] - half)) / self.blend;
+                let alpha = t * t * (3.0 - 2.0 * t);
+                self.policies[j].blend(&self.policies[j + 1], alpha)
+            }
+            None => self.policies[tier].clone(),
+        }
+    }
+}
+
+fn check_karma(karma: f64) -> PyResult<()> {
+    if karma.is_finite() {
+        Ok(())
+    } else {
+        Err(PyValueError::new_err(format!("karma must be finite, got {}", karma)))
+    }
+}
+
+#[pymethods]
+impl PolicySchedule {
+    /// `policies` has one more entry than `boundaries`: the policy below the
+    /// first boundary, then one per boundary upwards
+    #[new]
+    #[pyo3(signature = (boundaries, policies, names=None, blend=0.0))]
+    fn py_new(boundaries: Vec<f64>, policies: Vec<Policy>, names: Option<Vec<String>>, blend: f64) -> PyResult<Self> {
+        Self::new(boundaries, policies, names, blend).map_err(PyValueError::new_err)
+    }
+
+    /// The (possibly blended) policy for `karma`
+    #[pyo3(name = "policy_at")]
+    fn py_policy_at(&self, karma: f64) -> PyResult<Policy> {
+        let _span = aios_trace::span!("PolicySchedule.policy_at", karma = karma);
+        check_karma(karma)?;
+        Ok(self.policy_at(karma))
+    }
+
+    /// Name of the tier `karma` falls in
+    #[pyo3(name = "tier")]
+    fn py_tier(&self, karma: f64) -> PyResult<String> {
+        check_karma(karma)?;
+        Ok(self.names[self.tier(karma)].clone())
+    }
+
+    /// τ_split under the policy for `karma`
+    #[pyo3(signature = (karma, entropy, error_density, previous=None))]
+    fn should_split(&self, karma: f64, entropy: f64, error_density: f64, previous: Option<bool>) -> PyResult<bool> {
+        check_karma(karma)?;
+        let score = self.policy_at(karma).split_score(entropy, error_density, previous).map_err(PyValueError::new_err)?;
+        Ok(score.decision)
+    }
+
+    /// τ_merge under the policy for `karma`
+    #[pyo3(signature = (karma, js_div, topic_shift, previous=None))]
+    fn should_merge(&self, karma: f64, js_div: f64, topic_shift: f64, previous: Option<bool>) -> PyResult<bool> {
+        check_karma(karma)?;
+        let score = self.policy_at(karma).merge_score(js_div, topic_shift, previous).map_err(PyValueError::new_err)?;
+        Ok(score.decision)
+    }
+
+    #[getter]
+    fn boundaries(&self) -> Vec<f64> {
+        self.boundaries.clone()
+    }
+
+    #[getter]
+    fn names(&self) -> Vec<String> {
+        self.names.clone()
+    }
+
+    #[getter]
+    fn blend(&self) -> f64 {
+        self.blend
+    }
+
+    fn __len__(&self) -> usize {
+        self.policies.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("PolicySchedule(names={:?}, boundaries={:?}, blend={})", self.names, self.boundaries, self.blend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link::Calibration;
+
+    fn policy(a: f64) -> Policy {
+        Policy::new(vec![a, 0.0, 0.0], vec![a, 0.0, 0.0], Calibration::default(), 0.0).unwrap()
+    }
+
+    fn intercept(policy: &Policy) -> f64 {
+        policy.split_score(0.0, 0.0, None).unwrap().intercept
+    }
+
+    #[test]
+    fn test_tiers_and_blending() {
+        let schedule = PolicySchedule::new(vec![30.0, 70.0], vec![policy(0.0), policy(1.0), policy(2.0)], None, 10.0).unwrap();
+        assert_eq!((schedule.tier(10.0), schedule.tier(30.0), schedule.tier(99.0)), (0, 1, 2));
+        assert_eq!(intercept(&schedule.policy_at(10.0)), 0.0);
+        assert_eq!(intercept(&schedule.policy_at(50.0)), 1.0);
+        // Halfway through the blend window, and continuous at its edges
+        assert!((intercept(&schedule.policy_at(70.0)) - 1.5).abs() < 1e-12);
+        assert!((intercept(&schedule.policy_at(64.999_999)) - 1.0).abs() < 1e-6);
+        assert!((intercept(&schedule.policy_at(75.000_001)) - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(PolicySchedule::new(vec![30.0], vec![policy(0.0)], None, 0.0).is_err());
+        assert!(PolicySchedule::new(vec![70.0, 30.0], vec![policy(0.0), policy(1.0), policy(2.0)], None, 0.0).is_err());
+        assert!(PolicySchedule::new(vec![30.0, 40.0], vec![policy(0.0), policy(1.0), policy(2.0)], None, 20.0).is_err());
+        assert!(PolicySchedule::new(vec![30.0], vec![policy(0.0), policy(1.0)], Some(vec!["low".to_string()]), 0.0).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! Knapsack selection over spans that arrive one at a time
+//!
+//! Each offered span gets the next id (0, 1, ...). A span is taken when it
+//! fits the remaining budget; with eviction on, a span that does not fit
+//! may push out selected spans with a lower gain/token ratio, lowest first,
+//! when that frees enough room.
+
+// `#[new]` under PyO3 0.20 expands to impls that newer rustc flags
+#![allow(unknown_lints, non_local_definitions)]
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::KnapsackResult;
+
+/// Running selection under a token budget
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct StreamingKnapsack {
+    budget: usize,
+    evict: bool,
+    gains: Vec<f64>,
+    costs: Vec<usize>,
+    chosen: Vec<bool>,
+    tokens_used: usize,
+}
+
+impl StreamingKnapsack {
+    pub fn new(budget: usize, evict: bool) -> Self {
+        Self { budget, evict, gains: Vec::new(), costs: Vec::new(), chosen: Vec::new(), tokens_used: 0 }
+    }
+
+    fn ratio(&self, id: usize) -> f64 {
+        if self.costs[id] == 0 {
+            f64::INFINITY
+        } else {
+            self.gains[id] / self.costs[id] as f64
+        }
+    }
+
+    /// Selected ids by ratio, lowest (and among equals, oldest) first: the
+    /// eviction order
+    fn eviction_order(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = (0..self.chosen.len()).filter(|&id| self.chosen[id]).collect();
+        ids.sort_by(|&a, &b| self.ratio(a).total_cmp(&self.ratio(b)).then(a.cmp(&b)));
+        ids
+    }
+
+    fn drop_selected(&mut self, id: usize) {
+        self.chosen[id] = false;
+        self.tokens_used -= self.costs[id];
+    }
+
+    /// Offer the next span; returns its id, whether it was taken and the
+    /// ids evicted to make room for it
+    pub fn offer(&mut self, gain: f64, cost: usize) -> (usize, bool, Vec<usize>) {
+        let id = self.gains.len();
+        self.gains.push(gain);
+        self.costs.push(cost);
+        self.chosen.push(false);
+        if gain.is_nan() || gain <= 0.0 || cost > self.budget {
+            return (id, false, Vec::new());
+        }
+
+        let mut evicted = Vec::new();
+        if self.tokens_used + cost > self.budget {
+            if !self.evict {
+                return (id, false, evicted);
+            }
+            let ratio = self.ratio(id);
+            let mut freed = 0;
+            for victim in self.eviction_order() {
+                if self.tokens_used - freed + cost <= self.budget || self.ratio(victim) >= ratio {
+                    break;
+                }
+                freed += self.costs[victim];
+                evicted.push(victim);
+            }
+            if self.tokens_used - freed + cost > self.budget {
+                return (id, false, Vec::new());
+            }
+            evicted.iter().for_each(|&victim| self.drop_selected(victim));
+        }
+        self.chosen[id] = true;
+        self.tokens_used += cost;
+        (id, true, evicted)
+    }
+
+    /// Change the budget, evicting lowest-ratio spans until the selection
+    /// fits; returns the evicted ids
+    pub fn set_budget(&mut self, budget: usize) -> Vec<usize> {
+        self.budget = budget;
+        let mut evicted = Vec::new();
+        for victim in self.eviction_order() {
+            if self.tokens_used <= budget {
+                break;
+            }
+            self.drop_selected(victim);
+            evicted.push(victim);
+        }
+        evicted
+    }
+
+    pub fn selected(&self) -> Vec<usize> {
+        (0..self.chosen.len()).filter(|&id| self.chosen[id]).collect()
+    }
+
+    pub fn result(&self) -> KnapsackResult {
+        KnapsackResult::new(self.selected(), &self.gains, &self.costs, self.budget)
+    }
+}
+
+#[pymethods]
+impl StreamingKnapsack {
+    /// With `evict=False` spans are only taken while they fit
+    #[new]
+    #[pyo3(signature = (budget, evict=true))]
+    fn py_new(budget: usize, evict: bool) -> Self {
+        Self::new(budget, evict)
+    }
+
+    /// Offer the next span; returns `(id, accepted, evicted_ids)`.
+    /// ValueError for a NaN or infinite gain, which gets no id
+    #[pyo3(name = "offer")]
+    fn py_offer(&mut self, gain: f64, cost: usize) -> PyResult<(usize, bool, Vec<usize>)> {
+        let _span = aios_trace::span!("StreamingKnapsack.offer", gain = gain, cost = cost);
+        if !gain.is_finite() {
+            return Err(PyValueError::new_err(format!("gain must be finite, got {}", gain)));
+        }
+        Ok(self.offer(gain, cost))
+    }
+
+    /// Drop a selected span, e.g. one that left the conversation window;
+    /// false if it was not selected
+    fn remove(&mut self, id: usize) -> bool {
+        if !self.chosen.get(id).copied().unwrap_or(false) {
+            return false;
+        }
+        self.drop_selected(id);
+        true
+    }
+
+    #[getter]
+    fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Shrinking the budget evicts lowest-ratio spans; returns their ids
+    #[pyo3(name = "set_budget")]
+    fn py_set_budget(&mut self, budget: usize) -> Vec<usize> {
+        self.set_budget(budget)
+    }
+
+    #[getter]
+    fn tokens_used(&self) -> usize {
+        self.tokens_used
+    }
+
+    /// Ids of the selected spans in arrival order
+    #[getter(selected)]
+    fn py_selected(&self) -> Vec<usize> {
+        self.selected()
+    }
+
+    /// The current selection as a `KnapsackResult` over every span offered
+    #[pyo3(name = "result")]
+    fn py_result(&self) -> KnapsackResult {
+        self.result()
+    }
+
+    fn __len__(&self) -> usize {
+        self.gains.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_while_it_fits() {
+        let mut knapsack = StreamingKnapsack::new(500, false);
+        assert_eq!(knapsack.offer(3.0, 300), (0, true, vec![]));
+        assert_eq!(knapsack.offer(10.0, 300), (1, false, vec![]));
+        assert_eq!(knapsack.offer(-1.0, 10), (2, false, vec![]));
+        assert_eq!(knapsack.offer(1.0, 200), (3, true, vec![]));
+        assert_eq!(knapsack.selected(), vec![0, 3]);
+        assert_eq!(knapsack.result().tokens_used, 500);
+    }
+
+    #[test]
+    fn test_evicts_lower_ratios() {
+        let mut knapsack = StreamingKnapsack::new(500, true);
+        knapsack.offer(3.0, 300);
+        knapsack.offer(4.0, 200);
+        // Better than span 0 but not span 1: only span 0 goes
+        assert_eq!(knapsack.offer(6.0, 300), (2, true, vec![0]));
+        // Worse than everything selected: rejected without evicting
+        assert_eq!(knapsack.offer(0.5, 100), (3, false, vec![]));
+        assert_eq!(knapsack.selected(), vec![1, 2]);
+        assert_eq!(knapsack.set_budget(300), vec![1]);
+        assert_eq!(knapsack.selected(), vec![2]);
+    }
+}
@@ -0,0 +1,169 @@
+//! Budgeted selection when overlapping spans have diminishing returns
+//!
+//! `redundancy[i][j]` in [0, 1] is how much of span i's content span j
+//! already covers. Once a set S is selected, span i is only worth
+//! `gain_i · (1 − max_{j∈S} redundancy[i][j])`, which can only shrink as S
+//! grows. Greedy by that marginal gain per token, evaluated lazily: a span's
+//! stale marginal is an upper bound on its current one, so only the top of
+//! the queue needs recomputing.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use pyo3::prelude::*;
+
+use crate::arrays::{Counts, Floats};
+use crate::{check_knapsack, tie_key, tie_seed, KnapsackResult};
+
+/// A span in the lazy queue with the marginal gain computed when `round`
+/// spans had been selected
+struct Candidate {
+    ratio: f64,
+    marginal: f64,
+    key: u64,
+    idx: usize,
+    round: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    /// Highest ratio first; equal ratios by ascending tie key
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.ratio.total_cmp(&other.ratio).then_with(|| other.key.cmp(&self.key))
+    }
+}
+
+/// Check `redundancy` is an n × n matrix of values in [0, 1]
+fn check_redundancy(redundancy: &[Vec<f64>], n: usize) -> Result<(), String> {
+    if redundancy.len() != n || redundancy.iter().any(|row| row.len() != n) {
+        return Err(format!("redundancy must be a {} × {} matrix", n, n));
+    }
+    match redundancy.iter().flatten().find(|r| !(0.0..=1.0).contains(*r)) {
+        Some(bad) => Err(format!("Redundancy values must be in [0, 1], got {}", bad)),
+        None => Ok(()),
+    }
+}
+
+/// Lazy-greedy selection under `budget` with redundancy-discounted gains
+///
+/// Returns the selected spans in the order picked and their combined
+/// discounted gain. As usual for budgeted submodular greedy, the single
+/// best span that fits is taken instead when it is worth more on its own.
+pub fn submodular_knapsack(
+    gains: &[f64],
+    costs: &[usize],
+    budget: usize,
+    redundancy: &[Vec<f64>],
+    seed: Option<u64>,
+) -> Result<(Vec<usize>, f64), String> {
+    check_knapsack(gains, costs)?;
+    check_redundancy(redundancy, gains.len())?;
+
+    let feasible = |i: usize| costs[i] > 0 && costs[i] <= budget && gains[i] > 0.0;
+    let mut queue: BinaryHeap<Candidate> = (0..gains.len())
+        .filter(|&i| feasible(i))
+        .map(|i| Candidate { ratio: gains[i] / costs[i] as f64, marginal: gains[i], key: tie_key(seed, i), idx: i, round: 0 })
+        .collect();
+
+    let mut selected: Vec<usize> = Vec::new();
+    let (mut used, mut total) = (0usize, 0.0f64);
+    while let Some(top) = queue.pop() {
+        let idx = top.idx;
+        if used + costs[idx] > budget {
+            // The remaining budget only shrinks, so it never fits again
+            continue;
+        }
+        if top.round == selected.len() {
+            selected.push(idx);
+            used += costs[idx];
+            total += top.marginal;
+            continue;
+        }
+        let covered = selected.iter().map(|&j| redundancy[idx][j]).fold(0.0, f64::max);
+        let marginal = gains[idx] * (1.0 - covered);
+        if marginal > 0.0 {
+            queue.push(Candidate { ratio: marginal / costs[idx] as f64, marginal, round: selected.len(), ..top });
+        }
+    }
+
+    let best_single = (0..gains.len()).filter(|&i| feasible(i)).max_by(|&a, &b| gains[a].total_cmp(&gains[b]).then(b.cmp(&a)));
+    match best_single {
+        Some(best) if gains[best] > total => Ok((vec![best], gains[best])),
+        _ => Ok((selected, total)),
+    }
+}
+
+/// Select a diverse set of spans under `budget` when overlapping spans have
+/// diminishing returns
+///
+/// `redundancy[i][j]` in [0, 1] is the share of span i's gain already
+/// covered once span j is selected. The result's `total_gain` is the
+/// discounted gain of the selection, not the sum of the raw gains.
+#[pyfunction]
+#[pyo3(name = "submodular_knapsack")]
+pub fn py_submodular_knapsack(
+    py: Python<'_>,
+    gains: Floats<'_>,
+    costs: Counts<'_>,
+    budget: usize,
+    redundancy: Vec<Vec<f64>>,
+) -> PyResult<KnapsackResult> {
+    let gains = gains.into_vec();
+    let _span = aios_trace::span!("submodular_knapsack", items = gains.len(), budget = budget);
+    let costs = costs.into_vec()?;
+    let seed = tie_seed();
+    let (selected, total_gain) = py
+        .allow_threads(|| submodular_knapsack(&gains, &costs, budget, &redundancy, seed))
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    Ok(KnapsackResult { total_gain, ..KnapsackResult::new(selected, &gains, &costs, budget) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::greedy_knapsack;
+
+    #[test]
+    fn test_prefers_diverse_spans() {
+        // Spans 0 and 1 say nearly the same thing
+        let gains = [10.0, 9.0, 6.0];
+        let costs = [10, 10, 10];
+        let redundancy = vec![vec![1.0, 0.9, 0.0], vec![0.9, 1.0, 0.0], vec![0.0, 0.0, 1.0]];
+        assert_eq!(greedy_knapsack(gains.to_vec(), costs.to_vec(), 20), vec![0, 1]);
+        let (selected, total) = submodular_knapsack(&gains, &costs, 20, &redundancy, None).unwrap();
+        assert_eq!(selected, vec![0, 2]);
+        assert_eq!(total, 16.0);
+    }
+
+    #[test]
+    fn test_without_redundancy_matches_greedy() {
+        let gains = [10.0, 8.0, 5.0, 3.0];
+        let costs = [300, 250, 200, 150];
+        let none = vec![vec![0.0; 4]; 4];
+        let (selected, total) = submodular_knapsack(&gains, &costs, 600, &none, None).unwrap();
+        assert_eq!(selected, greedy_knapsack(gains.to_vec(), costs.to_vec(), 600));
+        assert_eq!(total, 18.0);
+    }
+
+    #[test]
+    fn test_best_single_span_and_validation() {
+        // Ratio greedy would take the cheap span and then have no room
+        let (selected, _) = submodular_knapsack(&[1.0, 10.0], &[1, 10], 10, &[vec![0.0; 2], vec![0.0; 2]], None).unwrap();
+        assert_eq!(selected, vec![1]);
+        assert!(submodular_knapsack(&[1.0], &[1], 10, &[vec![0.0, 0.0]], None).is_err());
+        assert!(submodular_knapsack(&[1.0], &[1], 10, &[vec![1.5]], None).is_err());
+    }
+}
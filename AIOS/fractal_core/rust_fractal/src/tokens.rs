@@ -0,0 +1,211 @@
+//! Approximate token counts for span costs
+//!
+//! Text is cut into pieces the way BPE pre-tokenizers do: runs of letters,
+//! runs of digits, runs of other symbols and whitespace, with a single space
+//! riding along with the piece after it. Without a vocabulary each piece is
+//! costed by rule of thumb (about four ASCII letters, three digits or two
+//! symbols per token, one token per non-ASCII letter); with one, pieces are
+//! cut greedily into the longest vocabulary entries.
+
+// `#[new]` under PyO3 0.20 expands to impls that newer rustc flags
+#![allow(unknown_lints, non_local_definitions)]
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+/// Character class of a piece
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Letter,
+    Digit,
+    Space,
+    Symbol,
+}
+
+impl Kind {
+    fn of(c: char) -> Self {
+        if c.is_alphabetic() || c == '\'' {
+            Kind::Letter
+        } else if c.is_numeric() {
+            Kind::Digit
+        } else if c.is_whitespace() {
+            Kind::Space
+        } else {
+            Kind::Symbol
+        }
+    }
+}
+
+/// Token strings of a BPE vocabulary
+#[derive(Debug, Clone, Default)]
+pub struct Vocab {
+    pieces: HashSet<String>,
+    /// Longest piece, in chars
+    max_len: usize,
+}
+
+impl Vocab {
+    /// One token per line; anything after a tab is ignored, and GPT-2's `Ġ`
+    /// and `Ċ` stand for a space and a newline
+    pub fn parse(text: &str) -> Self {
+        let mut vocab = Self::default();
+        for line in text.lines() {
+            let piece = line.split('\t').next().unwrap_or_default().replace('Ġ', " ").replace('Ċ', "\n");
+            if !piece.is_empty() {
+                vocab.max_len = vocab.max_len.max(piece.chars().count());
+                vocab.pieces.insert(piece);
+            }
+        }
+        vocab
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?))
+    }
+
+    pub fn len(&self) -> usize {
+        self.pieces.len()
+    }
+
+    /// Tokens in `piece` by greedy longest match; a char no entry starts
+    /// with costs one token
+    fn segment(&self, piece: &str) -> usize {
+        let bounds: Vec<usize> = piece.char_indices().map(|(i, _)| i).chain([piece.len()]).collect();
+        let chars = bounds.len() - 1;
+        let (mut count, mut at) = (0, 0);
+        while at < chars {
+            let longest = (1..=self.max_len.min(chars - at)).rev().find(|&len| self.pieces.contains(&piece[bounds[at]..bounds[at + len]]));
+            at += longest.unwrap_or(1);
+            count += 1;
+        }
+        count
+    }
+}
+
+/// Rule-of-thumb tokens for a piece of one kind
+fn heuristic(kind: Kind, piece: &str) -> usize {
+    let chars = piece.chars().filter(|c| !c.is_whitespace()).count();
+    match kind {
+        Kind::Letter => {
+            let ascii = piece.chars().filter(char::is_ascii_alphabetic).count();
+            ascii.div_ceil(4) + (chars - ascii)
+        }
+        Kind::Digit => chars.div_ceil(3),
+        Kind::Space => 1,
+        Kind::Symbol => chars.div_ceil(2),
+    }
+}
+
+/// Approximate token count of `text`, using `vocab` when given
+pub fn estimate_tokens(text: &str, vocab: Option<&Vocab>) -> usize {
+    let mut count = 0;
+    let mut pieces = text.char_indices().peekable();
+    // Byte offset of a space waiting to join the next piece
+    let mut carried: Option<usize> = None;
+    while let Some((start, c)) = pieces.next() {
+        let kind = Kind::of(c);
+        let mut end = start + c.len_utf8();
+        while let Some(&(i, next)) = pieces.peek() {
+            if Kind::of(next) != kind {
+                break;
+            }
+            end = i + next.len_utf8();
+            pieces.next();
+        }
+        if kind == Kind::Space {
+            // The run's last space joins a following piece when it is a plain space
+            let joins = pieces.peek().is_some() && text[..end].ends_with(' ');
+            let own_end = if joins { end - 1 } else { end };
+            if own_end > start {
+                count += match vocab {
+                    Some(vocab) => vocab.segment(&text[start..own_end]),
+                    None => heuristic(kind, &text[start..own_end]),
+                };
+            }
+            carried = joins.then_some(own_end);
+            continue;
+        }
+        let piece_start = carried.take().unwrap_or(start);
+        count += match vocab {
+            Some(vocab) => vocab.segment(&text[piece_start..end]),
+            None => heuristic(kind, &text[start..end]),
+        };
+    }
+    count
+}
+
+/// A BPE vocabulary loaded for `estimate_tokens`
+#[pyclass(name = "TokenVocab")]
+#[derive(Debug, Clone)]
+pub struct TokenVocab {
+    vocab: Arc<Vocab>,
+}
+
+#[pymethods]
+impl TokenVocab {
+    /// Load a vocabulary file with one token per line (anything after a
+    /// tab ignored, `Ġ` read as a space)
+    #[new]
+    fn py_new(path: std::path::PathBuf) -> PyResult<Self> {
+        let _span = aios_trace::span!("TokenVocab.load", path = path.display().to_string());
+        Ok(Self { vocab: Arc::new(Vocab::load(&path)?) })
+    }
+
+    fn __len__(&self) -> usize {
+        self.vocab.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("TokenVocab(tokens={})", self.vocab.len())
+    }
+}
+
+/// Approximate number of BPE tokens in `text`, for span costs
+///
+/// Without `vocab` (a `TokenVocab`) the count is a rule-of-thumb estimate;
+/// with one, text is cut greedily into its longest tokens.
+#[pyfunction]
+#[pyo3(name = "estimate_tokens", signature = (text, vocab=None))]
+pub fn py_estimate_tokens(text: &str, vocab: Option<PyRef<'_, TokenVocab>>) -> usize {
+    let _span = aios_trace::span!("estimate_tokens", chars = text.len());
+    estimate_tokens(text, vocab.as_ref().map(|v| v.vocab.as_ref()))
+}
+
+/// `estimate_tokens` for many texts at once, in parallel; returns a list
+/// of counts ready to pass to the knapsack solvers as `costs`
+#[pyfunction]
+#[pyo3(signature = (texts, vocab=None))]
+pub fn estimate_tokens_batch(py: Python<'_>, texts: Vec<String>, vocab: Option<PyRef<'_, TokenVocab>>) -> Vec<usize> {
+    let _span = aios_trace::span!("estimate_tokens_batch", texts = texts.len());
+    let vocab = vocab.map(|v| Arc::clone(&v.vocab));
+    py.allow_threads(|| texts.par_iter().map(|text| estimate_tokens(text, vocab.as_deref())).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heuristic_estimate() {
+        assert_eq!(estimate_tokens("", None), 0);
+        assert_eq!(estimate_tokens("the cat sat", None), 3);
+        // 12 letters, 7 digits, "!!" and the newline
+        assert_eq!(estimate_tokens("tokenization 1234567!!\n", None), 3 + 3 + 1 + 1);
+        assert_eq!(estimate_tokens("日本語", None), 3);
+        assert_eq!(estimate_tokens("a  b", None), 3);
+    }
+
+    #[test]
+    fn test_vocab_estimate() {
+        let vocab = Vocab::parse("the\nĠthe\tx\nĠcat\ncat\ns\nĠ\n");
+        assert_eq!(vocab.len(), 6);
+        assert_eq!(estimate_tokens("the cats", Some(&vocab)), 3);
+        // "q" and "z" are not in the vocabulary: one token each
+        assert_eq!(estimate_tokens("the qz", Some(&vocab)), 4);
+        assert!(Vocab::load(Path::new("/nonexistent/vocab.txt")).is_err());
+    }
+}
@@ -0,0 +1,396 @@
+//! Fragment hierarchy kept on the Rust side
+//!
+//! A `FragmentTree` covers a text of `length` units with nested `[start, end)`
+//! spans: the root spans everything, and splitting a leaf gives it two
+//! children that partition its span. Node ids stay valid until the node is
+//! merged away and are never reused.
+
+// `#[new]` under PyO3 0.20 expands to impls that newer rustc flags
+#![allow(unknown_lints, non_local_definitions)]
+
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::link::Calibration;
+use crate::{merge_decision, split_decision};
+
+/// Traversal order of `FragmentTree.walk`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Pre,
+    Post,
+    Breadth,
+}
+
+impl Order {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "pre" => Some(Order::Pre),
+            "post" => Some(Order::Post),
+            "breadth" => Some(Order::Breadth),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Order::Pre => "pre",
+            Order::Post => "post",
+            Order::Breadth => "breadth",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    start: usize,
+    end: usize,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// Split features of a leaf
+    entropy: f64,
+    error_density: f64,
+    /// Merge features of a node whose children are leaves
+    js_div: f64,
+    topic_shift: f64,
+}
+
+/// Fragment hierarchy with split/merge applied in place
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct FragmentTree {
+    nodes: Vec<Option<Node>>,
+}
+
+impl FragmentTree {
+    pub fn new(length: usize) -> Self {
+        Self { nodes: vec![Some(Node { end: length, ..Node::default() })] }
+    }
+
+    fn node(&self, id: usize) -> Result<&Node, String> {
+        self.nodes.get(id).and_then(Option::as_ref).ok_or_else(|| format!("No fragment {}", id))
+    }
+
+    fn node_mut(&mut self, id: usize) -> Result<&mut Node, String> {
+        self.nodes.get_mut(id).and_then(Option::as_mut).ok_or_else(|| format!("No fragment {}", id))
+    }
+
+    /// Split leaf `id` at offset `at`; returns the ids of the two halves
+    pub fn split(&mut self, id: usize, at: usize) -> Result<(usize, usize), String> {
+        let node = self.node(id)?;
+        if !node.children.is_empty() {
+            return Err(format!("Fragment {} is already split", id));
+        }
+        if at <= node.start || at >= node.end {
+            return Err(format!("Split point {} is not inside fragment {} ({}, {})", at, id, node.start, node.end));
+        }
+        let (start, end) = (node.start, node.end);
+        let left = self.nodes.len();
+        self.nodes.push(Some(Node { start, end: at, parent: Some(id), ..Node::default() }));
+        self.nodes.push(Some(Node { start: at, end, parent: Some(id), ..Node::default() }));
+        self.node_mut(id)?.children = vec![left, left + 1];
+        Ok((left, left + 1))
+    }
+
+    /// Collapse everything below `id` back into one leaf; returns the
+    /// number of fragments removed
+    pub fn merge(&mut self, id: usize) -> Result<usize, String> {
+        let children = std::mem::take(&mut self.node_mut(id)?.children);
+        let mut removed = 0;
+        let mut stack = children;
+        while let Some(child) = stack.pop() {
+            if let Some(node) = self.nodes[child].take() {
+                stack.extend(node.children);
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Ids below `root` (inclusive) in the given order
+    pub fn walk(&self, root: usize, order: Order) -> Result<Vec<usize>, String> {
+        self.node(root)?;
+        let mut ids = Vec::new();
+        match order {
+            Order::Pre => {
+                let mut stack = vec![root];
+                while let Some(id) = stack.pop() {
+                    ids.push(id);
+                    stack.extend(self.children_of(id).iter().rev());
+                }
+            }
+            Order::Post => {
+                // Reverse of a pre-order that visits children right to left
+                let mut stack = vec![root];
+                while let Some(id) = stack.pop() {
+                    ids.push(id);
+                    stack.extend(self.children_of(id));
+                }
+                ids.reverse();
+            }
+            Order::Breadth => {
+                ids.push(root);
+                let mut next = 0;
+                while next < ids.len() {
+                    ids.extend(self.children_of(ids[next]).to_vec());
+                    next += 1;
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    fn children_of(&self, id: usize) -> &[usize] {
+        self.nodes[id].as_ref().map_or(&[], |node| &node.children)
+    }
+
+    /// Leaves in text order
+    pub fn leaves(&self) -> Vec<usize> {
+        self.walk(0, Order::Pre)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|&id| self.children_of(id).is_empty())
+            .collect()
+    }
+
+    pub fn depth(&self, id: usize) -> Result<usize, String> {
+        let mut depth = 0;
+        let mut current = self.node(id)?;
+        while let Some(parent) = current.parent {
+            depth += 1;
+            current = self.node(parent)?;
+        }
+        Ok(depth)
+    }
+
+    /// One decision cycle: merge every node whose children are all leaves
+    /// and pass `should_merge`, then split at the midpoint every leaf from
+    /// before the cycle that passes `should_split`. Decisions use the
+    /// features as they were before the cycle. Returns (splits, merges).
+    pub fn apply_decisions(&mut self, split_params: &[f64], merge_params: &[f64]) -> (usize, usize) {
+        let leaves = self.leaves();
+        let merges: Vec<usize> = if merge_params.len() < 3 {
+            Vec::new()
+        } else {
+            self.walk(0, Order::Post)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|&id| {
+                    let children = self.children_of(id);
+                    !children.is_empty() && children.iter().all(|&child| self.children_of(child).is_empty())
+                })
+                .filter(|&id| {
+                    let node = self.nodes[id].as_ref().expect("walked nodes exist");
+                    merge_decision(node.js_div, node.topic_shift, merge_params, Calibration::default())
+                })
+                .collect()
+        };
+        let splits: Vec<usize> = if split_params.len() < 3 {
+            Vec::new()
+        } else {
+            leaves
+                .into_iter()
+                .filter(|&id| {
+                    let node = self.nodes[id].as_ref().expect("leaves exist");
+                    node.end - node.start >= 2 && split_decision(node.entropy, node.error_density, split_params, Calibration::default())
+                })
+                .collect()
+        };
+
+        for &id in &merges {
+            let _ = self.merge(id);
+        }
+        let mut split_count = 0;
+        for id in splits {
+            // Leaves under a node merged above no longer exist
+            let Ok(node) = self.node(id) else {
+                continue;
+            };
+            let at = node.start + (node.end - node.start) / 2;
+            if self.split(id, at).is_ok() {
+                split_count += 1;
+            }
+        }
+        (split_count, merges.len())
+    }
+}
+
+fn value_error(message: String) -> PyErr {
+    PyValueError::new_err(message)
+}
+
+/// Unknown ids raise KeyError, everything else ValueError
+fn lookup_error(message: String) -> PyErr {
+    if message.starts_with("No fragment") {
+        PyKeyError::new_err(message)
+    } else {
+        value_error(message)
+    }
+}
+
+#[pymethods]
+impl FragmentTree {
+    /// A tree with one root fragment spanning `[0, length)`
+    #[new]
+    fn py_new(length: usize) -> Self {
+        Self::new(length)
+    }
+
+    /// Id of the root fragment
+    #[getter]
+    fn root(&self) -> usize {
+        0
+    }
+
+    fn __len__(&self) -> usize {
+        self.nodes.iter().flatten().count()
+    }
+
+    fn __contains__(&self, id: usize) -> bool {
+        self.node(id).is_ok()
+    }
+
+    /// Split leaf `id` at offset `at` (the midpoint by default); returns the
+    /// ids of the two halves
+    #[pyo3(name = "split", signature = (id, at=None))]
+    fn py_split(&mut self, id: usize, at: Option<usize>) -> PyResult<(usize, usize)> {
+        let _span = aios_trace::span!("FragmentTree.split", id = id);
+        let at = match at {
+            Some(at) => at,
+            None => {
+                let node = self.node(id).map_err(lookup_error)?;
+                node.start + (node.end - node.start) / 2
+            }
+        };
+        self.split(id, at).map_err(lookup_error)
+    }
+
+    /// Collapse the fragments below `id` into it; returns how many were removed
+    #[pyo3(name = "merge")]
+    fn py_merge(&mut self, id: usize) -> PyResult<usize> {
+        let _span = aios_trace::span!("FragmentTree.merge", id = id);
+        self.merge(id).map_err(lookup_error)
+    }
+
+    /// Set a fragment's decision features; omitted ones keep their value
+    #[pyo3(signature = (id, entropy=None, error_density=None, js_div=None, topic_shift=None))]
+    fn set_features(
+        &mut self,
+        id: usize,
+        entropy: Option<f64>,
+        error_density: Option<f64>,
+        js_div: Option<f64>,
+        topic_shift: Option<f64>,
+    ) -> PyResult<()> {
+        let node = self.node_mut(id).map_err(lookup_error)?;
+        node.entropy = entropy.unwrap_or(node.entropy);
+        node.error_density = error_density.unwrap_or(node.error_density);
+        node.js_div = js_div.unwrap_or(node.js_div);
+        node.topic_shift = topic_shift.unwrap_or(node.topic_shift);
+        Ok(())
+    }
+
+    /// Run one split/merge cycle with `should_split`/`should_merge`
+    /// parameters; returns (splits, merges)
+    #[pyo3(name = "apply_decisions")]
+    fn py_apply_decisions(&mut self, py: Python<'_>, split_params: Vec<f64>, merge_params: Vec<f64>) -> (usize, usize) {
+        let _span = aios_trace::span!("FragmentTree.apply_decisions", fragments = self.nodes.len());
+        py.allow_threads(|| self.apply_decisions(&split_params, &merge_params))
+    }
+
+    /// `(start, end)` of a fragment
+    fn span(&self, id: usize) -> PyResult<(usize, usize)> {
+        let node = self.node(id).map_err(lookup_error)?;
+        Ok((node.start, node.end))
+    }
+
+    fn parent(&self, id: usize) -> PyResult<Option<usize>> {
+        Ok(self.node(id).map_err(lookup_error)?.parent)
+    }
+
+    fn children(&self, id: usize) -> PyResult<Vec<usize>> {
+        Ok(self.node(id).map_err(lookup_error)?.children.clone())
+    }
+
+    #[pyo3(name = "depth")]
+    fn py_depth(&self, id: usize) -> PyResult<usize> {
+        self.depth(id).map_err(lookup_error)
+    }
+
+    /// Ids of the leaves in text order
+    #[pyo3(name = "leaves")]
+    fn py_leaves(&self) -> Vec<usize> {
+        self.leaves()
+    }
+
+    /// Ids below `root` (the tree's root by default) in "pre", "post" or
+    /// "breadth" order
+    #[pyo3(name = "walk", signature = (order="pre", root=0))]
+    fn py_walk(&self, order: &str, root: usize) -> PyResult<Vec<usize>> {
+        let order = Order::parse(order)
+            .ok_or_else(|| value_error(format!("Unknown order {}; expected pre, post or breadth", order)))?;
+        self.walk(root, order).map_err(lookup_error)
+    }
+
+    /// Every fragment as `(id, start, end, depth)` in pre-order
+    fn flatten(&self) -> Vec<(usize, usize, usize, usize)> {
+        let mut rows = Vec::new();
+        let mut stack = vec![(0, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            let Some(node) = self.nodes[id].as_ref() else {
+                continue;
+            };
+            rows.push((id, node.start, node.end, depth));
+            stack.extend(node.children.iter().rev().map(|&child| (child, depth + 1)));
+        }
+        rows
+    }
+
+    fn __repr__(&self) -> String {
+        format!("FragmentTree(length={}, fragments={}, leaves={})", self.nodes[0].as_ref().map_or(0, |n| n.end), self.__len__(), self.leaves().len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_merge() {
+        let mut tree = FragmentTree::new(100);
+        let (left, right) = tree.split(0, 40).unwrap();
+        let (a, b) = tree.split(right, 70).unwrap();
+        assert_eq!(tree.leaves(), vec![left, a, b]);
+        assert_eq!(tree.depth(b), Ok(2));
+        assert!(tree.split(0, 50).is_err());
+        assert!(tree.split(left, 40).is_err());
+        assert_eq!(tree.merge(0), Ok(4));
+        assert_eq!(tree.leaves(), vec![0]);
+        assert!(tree.node(a).is_err());
+    }
+
+    #[test]
+    fn test_walk_orders() {
+        let mut tree = FragmentTree::new(8);
+        let (l, r) = tree.split(0, 4).unwrap();
+        let (ll, lr) = tree.split(l, 2).unwrap();
+        assert_eq!(tree.walk(0, Order::Pre), Ok(vec![0, l, ll, lr, r]));
+        assert_eq!(tree.walk(0, Order::Post), Ok(vec![ll, lr, l, r, 0]));
+        assert_eq!(tree.walk(0, Order::Breadth), Ok(vec![0, l, r, ll, lr]));
+        assert_eq!(Order::parse(Order::Breadth.name()), Some(Order::Breadth));
+    }
+
+    #[test]
+    fn test_apply_decisions() {
+        let params = [0.5, 0.1, 0.05];
+        let mut tree = FragmentTree::new(10);
+        tree.node_mut(0).unwrap().entropy = 0.9;
+        assert_eq!(tree.apply_decisions(&params, &[]), (1, 0));
+        assert_eq!(tree.leaves().len(), 2);
+        // New halves start with zero features, so they stay as they are
+        assert_eq!(tree.apply_decisions(&params, &[]), (0, 0));
+        tree.node_mut(0).unwrap().js_div = 0.1;
+        assert_eq!(tree.apply_decisions(&params, &params), (0, 1));
+        assert_eq!(tree.leaves(), vec![0]);
+    }
+}
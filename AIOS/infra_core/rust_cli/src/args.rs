@@ -0,0 +1,160 @@
+//! Flag parsing for subcommands
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use aios_errors::{AiosError, Result};
+
+/// Parsed arguments of one subcommand
+pub struct Args {
+    positional: Vec<String>,
+    values: HashMap<&'static str, String>,
+    switches: Vec<&'static str>,
+}
+
+impl Args {
+    /// Split `raw` into positionals, `--name VALUE` / `--name=VALUE` options
+    /// listed in `options` and bare `switches`; anything after `--` is positional
+    pub fn parse(raw: Vec<String>, options: &[&'static str], switches: &[&'static str]) -> Result<Self> {
+        let mut args = Self { positional: Vec::new(), values: HashMap::new(), switches: Vec::new() };
+        let mut raw = raw.into_iter();
+        while let Some(arg) = raw.next() {
+            if arg == "--" {
+                args.positional.extend(raw.by_ref());
+                break;
+            }
+            if !arg.starts_with('-') || arg == "-" {
+                args.positional.push(arg);
+                continue;
+            }
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            if let Some(&option) = options.iter().find(|option| **option == name) {
+                let value = inline
+                    .or_else(|| raw.next())
+                    .ok_or_else(|| AiosError::validation(format!("{} needs a value", option)))?;
+                args.values.insert(option, value);
+            } else if let Some(&switch) = switches.iter().find(|switch| **switch == name) {
+                if inline.is_some() {
+                    return Err(AiosError::validation(format!("{} does not take a value", switch)));
+                }
+                args.switches.push(switch);
+            } else {
+                return Err(AiosError::validation(format!("Unknown option {}", name)));
+            }
+        }
+        Ok(args)
+    }
+
+    /// Positional arguments: all of `required`, then up to `optional.len()` more
+    pub fn positional(&self, required: &[&str], optional: &[&str]) -> Result<&[String]> {
+        let count = self.positional.len();
+        if count < required.len() || count > required.len() + optional.len() {
+            let mut usage: Vec<String> = required.iter().map(|name| name.to_string()).collect();
+            usage.extend(optional.iter().map(|name| format!("[{}]", name)));
+            let usage = if usage.is_empty() { "no arguments".to_string() } else { usage.join(" ") };
+            return Err(AiosError::validation(format!("Expected {}, got {} argument(s)", usage, count)));
+        }
+        Ok(&self.positional)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Option parsed as `T`, or `default` when absent
+    pub fn parsed<T: FromStr>(&self, name: &str, default: T) -> Result<T> {
+        match self.value(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| AiosError::validation(format!("Invalid value for {}: {}", name, value))),
+            None => Ok(default),
+        }
+    }
+
+    pub fn switch(&self, name: &str) -> bool {
+        self.switches.contains(&name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: &[&str] = &["--top-k", "--format"];
+    const SWITCHES: &[&str] = &["--json", "--dry-run"];
+
+    fn parse(raw: &[&str]) -> Result<Args> {
+        Args::parse(raw.iter().map(|arg| arg.to_string()).collect(), OPTIONS, SWITCHES)
+    }
+
+    #[test]
+    fn test_options_switches_and_positionals() {
+        let args = parse(&["snapshot.bin", "--top-k", "3", "--json", "query", "--format=csv"]).unwrap();
+        assert_eq!(args.positional(&["SNAPSHOT", "QUERY"], &[]).unwrap(), ["snapshot.bin", "query"]);
+        assert_eq!(args.parsed("--top-k", 5usize).unwrap(), 3);
+        assert_eq!(args.value("--format"), Some("csv"));
+        assert!(args.switch("--json"));
+        assert!(!args.switch("--dry-run"));
+
+        // Absent options fall back to their defaults
+        let args = parse(&[]).unwrap();
+        assert_eq!(args.parsed("--top-k", 5usize).unwrap(), 5);
+        assert_eq!(args.value("--format"), None);
+    }
+
+    #[test]
+    fn test_inline_values() {
+        // Everything after the first '=' is the value, which may be empty
+        let args = parse(&["--format=a=b", "--top-k="]).unwrap();
+        assert_eq!(args.value("--format"), Some("a=b"));
+        assert_eq!(args.value("--top-k"), Some(""));
+        assert!(args.parsed("--top-k", 5usize).is_err());
+        // A later occurrence wins
+        let args = parse(&["--top-k=1", "--top-k", "2"]).unwrap();
+        assert_eq!(args.parsed("--top-k", 0usize).unwrap(), 2);
+
+        let error = parse(&["--json=true"]).err().unwrap();
+        assert_eq!(error.to_string(), "--json does not take a value");
+    }
+
+    #[test]
+    fn test_unknown_options() {
+        for raw in [&["--topk", "3"][..], &["-k"], &["--unknown=1"], &["query", "---json"]] {
+            let error = parse(raw).err().expect("unknown option rejected");
+            assert!(error.to_string().starts_with("Unknown option "), "{:?}: {}", raw, error);
+        }
+        assert_eq!(parse(&["--unknown=1"]).err().unwrap().to_string(), "Unknown option --unknown");
+
+        // After `--` and as `-`, dashes are positional
+        let args = parse(&["-", "--", "--topk", "-k"]).unwrap();
+        assert_eq!(args.positional(&["A", "B", "C"], &[]).unwrap(), ["-", "--topk", "-k"]);
+    }
+
+    #[test]
+    fn test_missing_values() {
+        let error = parse(&["query", "--top-k"]).err().unwrap();
+        assert_eq!(error.to_string(), "--top-k needs a value");
+        assert!(parse(&["--format"]).is_err());
+        assert!(parse(&["--top-k", "many"]).unwrap().parsed("--top-k", 5usize).is_err());
+    }
+
+    #[test]
+    fn test_positional_count() {
+        let args = parse(&["a", "b"]).unwrap();
+        assert_eq!(args.positional(&["A"], &["B"]).unwrap().len(), 2);
+        assert_eq!(args.positional(&["A", "B"], &["C", "D"]).unwrap().len(), 2);
+
+        let error = args.positional(&["A", "B", "C"], &[]).err().unwrap();
+        assert_eq!(error.to_string(), "Expected A B C, got 2 argument(s)");
+        let error = args.positional(&["A"], &[]).err().unwrap();
+        assert_eq!(error.to_string(), "Expected A, got 2 argument(s)");
+        let error = args.positional(&[], &["X"]).err().unwrap();
+        assert_eq!(error.to_string(), "Expected [X], got 2 argument(s)");
+        let error = args.positional(&[], &[]).err().unwrap();
+        assert_eq!(error.to_string(), "Expected no arguments, got 2 argument(s)");
+        assert!(parse(&[]).unwrap().positional(&[], &[]).unwrap().is_empty());
+    }
+}
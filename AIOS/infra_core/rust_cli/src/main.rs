@@ -0,0 +1,263 @@
+//! aios-rs: the AIOS Rust cores from the command line
+//!
+//! Runs backups, data exports, health checks and CARMA snapshot searches
+//! without Python, for cron jobs and for debugging when the Python layer is
+//! broken. Results are printed to stdout as JSON; errors go to stderr.
+//!
+//! Exit status is 0 on success and 1 on errors or an unsuccessful (e.g.
+//! cancelled) operation. `health check` exits with 2 when the overall
+//! status is CRITICAL, so cron and monitoring wrappers can alert on it.
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use aios_backup_rust::RustBackupCore;
+use aios_cancel::CancellationToken;
+use aios_carma::keyword::Bm25Index;
+use aios_config::AiosConfig;
+use aios_data_rust::{RustDataCore, DEFAULT_FEATURE_DIMENSION};
+use aios_errors::{AiosError, Result};
+use aios_store::Store;
+use aios_support_rust::{ReportFormat, RustSupportCore};
+use serde::Serialize;
+use serde_json::json;
+
+mod args;
+
+use args::Args;
+
+const USAGE: &str = "\
+Usage: aios-rs [--config PATH] <COMMAND> [OPTIONS]
+
+Commands:
+  backup create   [--backup-dir DIR] [--data] [--logs] [--no-config]
+  backup restore  [--backup-dir DIR] [--target DIR] [--from-archive] [--dry-run]
+  data stats      [--data-dir DIR] [PATH]
+  data export     SOURCE OUTPUT [--format json|arrow] [--filter TEXT] [--dimension N]
+  health check    [--cache-dir DIR] [--thresholds JSON] [--quick]
+  health report   OUTPUT [--format json|markdown] [--cache-dir DIR] [--thresholds JSON]
+  carma search    SNAPSHOT QUERY [--top-k N]
+
+Directories default to the [server] section of the AIOS config (--config,
+$AIOS_CONFIG or ./aios.toml); the backup directory falls back to ./backups.
+Backups read the [backup] roots relative to the working directory. With
+[store] path set, backups, exports and health checks keep their state there.
+--thresholds names a JSON object overriding keys of the [support] section.";
+
+/// Exit status of a CRITICAL health check
+const EXIT_CRITICAL: u8 = 2;
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("aios-rs: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(mut raw: Vec<String>) -> Result<ExitCode> {
+    if raw.is_empty() || raw.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return Ok(ExitCode::SUCCESS);
+    }
+    let mut config_path = None;
+    if let Some(first) = raw.first().cloned() {
+        if first == "--config" {
+            raw.remove(0);
+            if raw.is_empty() {
+                return Err(AiosError::validation("--config needs a value"));
+            }
+            config_path = Some(raw.remove(0));
+        } else if let Some(path) = first.strip_prefix("--config=") {
+            config_path = Some(path.to_string());
+            raw.remove(0);
+        }
+    }
+    if raw.len() < 2 {
+        return Err(AiosError::validation(format!("Missing command\n\n{}", USAGE)));
+    }
+    let rest = raw.split_off(2);
+    let config = AiosConfig::load(config_path.as_deref())?;
+
+    match (raw[0].as_str(), raw[1].as_str()) {
+        ("backup", "create") => backup_create(config, rest),
+        ("backup", "restore") => backup_restore(config, rest),
+        ("data", "stats") => data_stats(config, rest),
+        ("data", "export") => data_export(config, rest),
+        ("health", "check") => health_check(config, rest),
+        ("health", "report") => health_report(config, rest),
+        ("carma", "search") => carma_search(rest),
+        (group, command) => Err(AiosError::validation(format!("Unknown command: {} {}\n\n{}", group, command, USAGE))),
+    }
+}
+
+fn backup_create(config: AiosConfig, raw: Vec<String>) -> Result<ExitCode> {
+    let args = Args::parse(raw, &["--backup-dir"], &["--data", "--logs", "--no-config"])?;
+    args.positional(&[], &[])?;
+    let mut core = backup_core(&config, &args)?;
+    let result = core
+        .create_backup(args.switch("--data"), args.switch("--logs"), !args.switch("--no-config"), &CancellationToken::new())
+        .map_err(|e| AiosError::io(format!("Backup failed: {}", e)))?;
+    print_json(&result)?;
+    Ok(status(result.success))
+}
+
+fn backup_restore(config: AiosConfig, raw: Vec<String>) -> Result<ExitCode> {
+    let args = Args::parse(raw, &["--backup-dir", "--target"], &["--from-archive", "--dry-run"])?;
+    args.positional(&[], &[])?;
+    let core = backup_core(&config, &args)?;
+    let target = Path::new(args.value("--target").unwrap_or("."));
+    let result = core
+        .restore_backup(target, args.switch("--from-archive"), args.switch("--dry-run"), &CancellationToken::new())
+        .map_err(|e| AiosError::io(format!("Restore failed: {}", e)))?;
+    print_json(&result)?;
+    Ok(status(result.success))
+}
+
+fn backup_core(config: &AiosConfig, args: &Args) -> Result<RustBackupCore> {
+    let backup_dir = match args.value("--backup-dir") {
+        Some(dir) => dir,
+        None if !config.server.backup_dir.is_empty() => &config.server.backup_dir,
+        None => "backups",
+    };
+    let mut core = RustBackupCore::new(backup_dir, config.backup.clone())
+        .map_err(|e| AiosError::io(format!("Failed to initialize backup core: {}", e)))?;
+    if let Some(store) = open_store(config)? {
+        core.attach_store(&store).map_err(|e| AiosError::io(format!("Failed to attach store: {}", e)))?;
+    }
+    Ok(core)
+}
+
+/// The `[store]` database, when one is configured
+fn open_store(config: &AiosConfig) -> Result<Option<Store>> {
+    if config.store.path.is_empty() {
+        return Ok(None);
+    }
+    Store::open(&config.store.path).map(Some)
+}
+
+fn data_stats(config: AiosConfig, raw: Vec<String>) -> Result<ExitCode> {
+    let args = Args::parse(raw, &["--data-dir"], &[])?;
+    let positional = args.positional(&[], &["PATH"])?;
+    let core = RustDataCore::new(args.value("--data-dir").unwrap_or(&config.server.data_dir))?;
+    let directory = match positional.first() {
+        Some(path) => path.clone(),
+        None => core.data_dir().to_string_lossy().to_string(),
+    };
+    print_json(&core.get_directory_stats(&directory)?)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn data_export(config: AiosConfig, raw: Vec<String>) -> Result<ExitCode> {
+    let args = Args::parse(raw, &["--format", "--filter", "--dimension"], &[])?;
+    let positional = args.positional(&["SOURCE", "OUTPUT"], &[])?;
+    let (source, output) = (&positional[0], &positional[1]);
+    let filter = args.value("--filter").map(str::to_string);
+    if !Path::new(source).is_dir() {
+        return Err(AiosError::io(format!("Source directory does not exist: {}", source)));
+    }
+    // Exports only read SOURCE; rooting the core there avoids creating a data dir
+    let mut core = RustDataCore::new(source)?;
+    if let Some(store) = open_store(&config)? {
+        core.attach_store(&store)?;
+    }
+    let cancel = CancellationToken::new();
+    let result = match args.value("--format").unwrap_or("json") {
+        "json" => core.export_to_json(source, output, filter, &cancel)?,
+        "arrow" => {
+            let dimension = args.parsed("--dimension", DEFAULT_FEATURE_DIMENSION)?;
+            core.export_fragment_candidates(source, output, dimension, filter, &cancel)?
+        }
+        format => return Err(AiosError::validation(format!("Unknown export format {}; expected json or arrow", format))),
+    };
+    print_json(&result)?;
+    Ok(status(result.success))
+}
+
+fn health_check(config: AiosConfig, raw: Vec<String>) -> Result<ExitCode> {
+    let args = Args::parse(raw, &["--cache-dir", "--thresholds"], &["--quick"])?;
+    args.positional(&[], &[])?;
+    let mut core = support_core(&config, &args)?;
+    let summary = core
+        .run_health_checks(args.switch("--quick"))
+        .map_err(|e| AiosError::io(format!("Health checks failed: {}", e)))?;
+    print_json(&summary)?;
+    if summary.overall_status == "CRITICAL" {
+        return Ok(ExitCode::from(EXIT_CRITICAL));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Full health checks written to OUTPUT as a report for bug reports
+fn health_report(config: AiosConfig, raw: Vec<String>) -> Result<ExitCode> {
+    let args = Args::parse(raw, &["--cache-dir", "--thresholds", "--format"], &[])?;
+    let positional = args.positional(&["OUTPUT"], &[])?;
+    let format = ReportFormat::parse(args.value("--format").unwrap_or("json")).map_err(AiosError::validation)?;
+    let mut core = support_core(&config, &args)?;
+    core.export_health_report(Path::new(&positional[0]), format)
+        .map_err(|e| AiosError::io(format!("Health report failed: {}", e)))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn support_core(config: &AiosConfig, args: &Args) -> Result<RustSupportCore> {
+    let cache_dir = args.value("--cache-dir").unwrap_or(&config.server.cache_dir);
+    let thresholds = match args.value("--thresholds") {
+        Some(path) => config.support.with_overrides_file(Path::new(path))?,
+        None => config.support.clone(),
+    };
+    let mut core = RustSupportCore::new(cache_dir, config.server.dimension, thresholds)
+        .map_err(|e| AiosError::io(format!("Failed to initialize support core: {}", e)))?;
+    if let Some(store) = open_store(config)? {
+        core.attach_store(&store).map_err(|e| AiosError::io(format!("Failed to attach store: {}", e)))?;
+    }
+    Ok(core)
+}
+
+/// BM25 keyword search over the fragments of a `RustCarmaCore.save` snapshot
+fn carma_search(raw: Vec<String>) -> Result<ExitCode> {
+    let args = Args::parse(raw, &["--top-k"], &[])?;
+    let positional = args.positional(&["SNAPSHOT", "QUERY"], &[])?;
+    let (snapshot, query) = (Path::new(&positional[0]), &positional[1]);
+    let top_k = args.parsed("--top-k", 5usize)?;
+
+    let (header, _) = aios_carma::snapshot::read_snapshot(snapshot)
+        .map_err(|e| AiosError::from(e).context(format!("Failed to read snapshot {}", snapshot.display())))?;
+    let mut index = Bm25Index::new(1.2, 0.75);
+    index.rebuild(header.fragments.iter().map(|fragment| fragment.content.as_str()));
+    let mut ranked: Vec<(usize, f32)> = index.scores(query).into_iter().collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let results: Vec<_> = ranked
+        .into_iter()
+        .take(top_k)
+        .map(|(position, score)| {
+            let fragment = &header.fragments[position];
+            json!({
+                "id": fragment.id,
+                "score": score,
+                "content": fragment.content,
+                "metadata": fragment.metadata,
+            })
+        })
+        .collect();
+    print_json(&json!({ "total_fragments": header.fragments.len(), "results": results }))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_json(value: &impl Serialize) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, value)?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+fn status(success: bool) -> ExitCode {
+    if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,315 @@
+//! Minimal HTTP/1.1 for JSON APIs
+//!
+//! One request per connection (`Connection: close`), bodies sized by
+//! `Content-Length` only; chunked uploads are rejected. Bodies must be sent
+//! as `application/json`, which browsers only allow cross-origin after a
+//! CORS preflight.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use aios_errors::{AiosError, ErrorKind, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Longest accepted request line or header line
+const MAX_LINE: usize = 8 * 1024;
+/// Most headers accepted per request
+const MAX_HEADERS: usize = 100;
+
+/// A parsed request
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// By lowercase name
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Read one request; `max_body` bounds `Content-Length`
+    pub fn read(stream: impl Read, max_body: usize) -> Result<Self> {
+        let mut reader = BufReader::new(stream);
+        let line = read_line(&mut reader)?;
+        let mut parts = line.split_whitespace();
+        let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version), None) => (method, target, version),
+            _ => return Err(AiosError::validation(format!("Malformed request line: {:?}", line))),
+        };
+        if !version.starts_with("HTTP/1.") {
+            return Err(AiosError::validation(format!("Unsupported protocol: {}", version)));
+        }
+
+        let mut headers = HashMap::new();
+        loop {
+            let line = read_line(&mut reader)?;
+            if line.is_empty() {
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err(AiosError::validation("Too many headers"));
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| AiosError::validation(format!("Malformed header: {:?}", line)))?;
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+        if headers.contains_key("transfer-encoding") {
+            return Err(AiosError::validation("Chunked request bodies are not supported; send Content-Length"));
+        }
+
+        let length = match headers.get("content-length") {
+            Some(value) => value
+                .parse::<usize>()
+                .map_err(|_| AiosError::validation(format!("Invalid Content-Length: {}", value)))?,
+            None => 0,
+        };
+        if length > max_body {
+            return Err(AiosError::validation(format!(
+                "Request body of {} bytes exceeds the {} byte limit",
+                length, max_body
+            )));
+        }
+        let mut body = vec![0u8; length];
+        reader.read_exact(&mut body)?;
+
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, parse_query(query)),
+            None => (target, HashMap::new()),
+        };
+        Ok(Self {
+            method: method.to_string(),
+            path: percent_decode(path),
+            query,
+            headers,
+            body,
+        })
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    /// Deserialize the JSON body, which must be sent as `application/json`;
+    /// an empty body reads as `{}`
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        if self.body.iter().all(u8::is_ascii_whitespace) {
+            return serde_json::from_slice(b"{}").map_err(|e| AiosError::from(e).context("Invalid JSON body"));
+        }
+        let media_type = self.header("content-type").map(|value| value.split(';').next().unwrap_or("").trim());
+        if !media_type.is_some_and(|media_type| media_type.eq_ignore_ascii_case("application/json")) {
+            return Err(AiosError::validation("Request bodies must be sent with Content-Type: application/json"));
+        }
+        serde_json::from_slice(&self.body).map_err(|e| AiosError::from(e).context("Invalid JSON body"))
+    }
+
+    /// Query parameter parsed as `T`, or `default` when absent
+    pub fn query_param<T: std::str::FromStr>(&self, name: &str, default: T) -> Result<T> {
+        match self.query.get(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| AiosError::validation(format!("Invalid query parameter {}={}", name, value))),
+            None => Ok(default),
+        }
+    }
+}
+
+/// A response ready to be written
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    /// Sent besides the status line and the content headers
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(status: u16, value: &impl Serialize) -> Self {
+        let body = serde_json::to_vec(value).unwrap_or_else(|e| {
+            format!("{{\"error\":{{\"kind\":\"IoError\",\"message\":\"Failed to encode response: {}\"}}}}", e).into_bytes()
+        });
+        Self { status, content_type: "application/json", headers: Vec::new(), body }
+    }
+
+    /// `body` as is, e.g. a Prometheus exposition
+    pub fn text(status: u16, content_type: &'static str, body: String) -> Self {
+        Self { status, content_type, headers: Vec::new(), body: body.into_bytes() }
+    }
+
+    pub fn ok(value: &impl Serialize) -> Self {
+        Self::json(200, value)
+    }
+
+    pub fn empty(status: u16) -> Self {
+        Self { status, content_type: "application/json", headers: Vec::new(), body: Vec::new() }
+    }
+
+    /// `{"error": {"kind": ..., "message": ...}}` with a status matching the kind
+    pub fn error(status: u16, kind: &str, message: impl Into<String>) -> Self {
+        let message: String = message.into();
+        Self::json(status, &json!({ "error": { "kind": kind, "message": message } }))
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::error(404, "NotFound", message)
+    }
+
+    pub fn method_not_allowed(method: &str, path: &str) -> Self {
+        Self::error(405, "MethodNotAllowed", format!("{} is not supported on {}", method, path))
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    pub fn write(&self, mut stream: impl Write) -> io::Result<()> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason(self.status),
+            self.body.len()
+        );
+        if !self.body.is_empty() {
+            head.push_str(&format!("Content-Type: {}\r\n", self.content_type));
+        }
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+impl From<AiosError> for Response {
+    fn from(error: AiosError) -> Self {
+        let status = match error.kind() {
+            ErrorKind::Validation => 400,
+            ErrorKind::Index => 404,
+            ErrorKind::Cancelled => 409,
+            ErrorKind::Io | ErrorKind::Config => 500,
+        };
+        Self::error(status, error.kind().name(), error.to_string())
+    }
+}
+
+impl From<Result<Value>> for Response {
+    fn from(result: Result<Value>) -> Self {
+        match result {
+            Ok(value) => Self::ok(&value),
+            Err(error) => error.into(),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Read a CRLF- or LF-terminated line without the terminator
+fn read_line(reader: &mut impl BufRead) -> Result<String> {
+    let mut line = Vec::new();
+    let read = reader.take(MAX_LINE as u64 + 1).read_until(b'\n', &mut line)?;
+    if read == 0 {
+        return Err(AiosError::validation("Connection closed before the request was complete"));
+    }
+    if line.last() != Some(&b'\n') {
+        return Err(AiosError::validation(format!("Request line or header longer than {} bytes", MAX_LINE)));
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line).map_err(|_| AiosError::validation("Request head is not valid UTF-8"))
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(&name.replace('+', " ")), percent_decode(&value.replace('+', " ")))
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes; malformed escapes are kept literally
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(content_type: Option<&str>, body: &str) -> Request {
+        let content_type = content_type.map_or(String::new(), |value| format!("Content-Type: {}\r\n", value));
+        let raw = format!("POST /vectors?k=2 HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n{}", content_type, body.len(), body);
+        Request::read(raw.as_bytes(), 1024).unwrap()
+    }
+
+    #[test]
+    fn test_json_content_type() {
+        let parsed: Value = request(Some("application/json"), r#"{"k": 1}"#).json().unwrap();
+        assert_eq!(parsed, json!({ "k": 1 }));
+        let parsed: Value = request(Some("Application/JSON; charset=utf-8"), "[]").json().unwrap();
+        assert_eq!(parsed, json!([]));
+        // An empty body needs no type
+        let parsed: Value = request(None, "").json().unwrap();
+        assert_eq!(parsed, json!({}));
+
+        for content_type in [None, Some("text/plain"), Some("application/x-www-form-urlencoded")] {
+            assert!(request(content_type, r#"{"k": 1}"#).json::<Value>().is_err(), "{:?}", content_type);
+        }
+    }
+
+    #[test]
+    fn test_headers_and_response() {
+        let request = request(Some("application/json"), "{}");
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.query_param("k", 0).unwrap(), 2);
+
+        let mut written = Vec::new();
+        Response::empty(204).with_header("Vary", "Origin").write(&mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(written.contains("\r\nVary: Origin\r\n"));
+        assert!(!written.contains("Access-Control"));
+    }
+}
@@ -0,0 +1,219 @@
+//! aios-server: the AIOS Rust cores over HTTP + JSON
+//!
+//! Serves health checks and metrics and vector search (rust_support),
+//! keyword retrieval over CARMA fragments, incremental backups (rust_backup)
+//! and data directory statistics (rust_data) to clients that cannot load the
+//! Python extension modules, e.g. remote dashboards. See `routes` for the
+//! endpoints.
+//!
+//! Settings come from the `[server]` section of the AIOS config (`--config`,
+//! `$AIOS_CONFIG` or `./aios.toml`); flags override them. Errors are returned
+//! as `{"error": {"kind": "ValidationError", "message": ...}}` with the kind
+//! names of the Python exceptions. gRPC is not offered: HTTP/1.1 keeps the
+//! binary free of an HTTP/2 stack and is reachable from `curl` and browsers.
+
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use aios_config::AiosConfig;
+use aios_errors::{AiosError, Result};
+
+mod http;
+mod retrieval;
+mod routes;
+
+use http::{Request, Response};
+use routes::State;
+
+/// Read and write timeout per connection
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+const USAGE: &str = "\
+Usage: aios-server [OPTIONS]
+
+Options:
+  --config PATH          AIOS config file (default: $AIOS_CONFIG or ./aios.toml)
+  --bind ADDR            address to listen on [server.bind]
+  --threads N            worker threads [server.threads]
+  --max-body-bytes N     largest accepted request body [server.max_body_bytes]
+  --cache-dir DIR        cache directory checked by /health [server.cache_dir]
+  --dimension N          vector dimension for /vectors [server.dimension]
+  --backup-dir DIR       enable POST /backup, writing to DIR [server.backup_dir]
+  --data-dir DIR         root of /data/stats paths [server.data_dir]
+  --carma-snapshot PATH  CARMA snapshot searched by /retrieval [server.carma_snapshot]
+  --trace-file PATH      append request spans to PATH as JSON Lines
+  --events-bind ADDR     serve health alerts and backup progress over WebSocket on ADDR
+  -h, --help             print this help";
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("aios-server: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<()> {
+    let Some(options) = Options::parse(args)? else {
+        println!("{}", USAGE);
+        return Ok(());
+    };
+    let mut config = AiosConfig::load(options.config.as_deref())?;
+    options.apply(&mut config)?;
+    if let Some(path) = &options.trace_file {
+        aios_trace::set_jsonl_file(Some(Path::new(path)))
+            .map_err(|e| AiosError::from(e).context(format!("Failed to open trace file {}", path)))?;
+    }
+    if let Some(bind) = &options.events_bind {
+        let addr = aios_events::start_server(bind)?;
+        eprintln!("aios-server streaming events on ws://{}/", addr);
+    }
+
+    let bind = config.server.bind.clone();
+    let threads = config.server.threads.max(1);
+    let max_body = config.server.max_body_bytes;
+    let state = Arc::new(State::new(config)?);
+    let listener = TcpListener::bind(&bind).map_err(|e| AiosError::from(e).context(format!("Failed to bind {}", bind)))?;
+    eprintln!("aios-server listening on http://{} ({} threads)", listener.local_addr()?, threads);
+
+    let (sender, receiver) = mpsc::channel::<TcpStream>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..threads {
+        let receiver = Arc::clone(&receiver);
+        let state = Arc::clone(&state);
+        thread::spawn(move || loop {
+            let stream = match receiver.lock().unwrap_or_else(|e| e.into_inner()).recv() {
+                Ok(stream) => stream,
+                Err(_) => return,
+            };
+            serve(&state, stream, max_body);
+        });
+    }
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if sender.send(stream).is_err() {
+                    break;
+                }
+            }
+            Err(e) => eprintln!("aios-server: failed to accept connection: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Answer one request and log it
+fn serve(state: &State, stream: TcpStream, max_body: usize) {
+    let start = Instant::now();
+    let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_else(|_| "-".to_string());
+    let _ = stream.set_read_timeout(Some(IO_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(IO_TIMEOUT));
+    let (line, response) = match Request::read(&stream, max_body) {
+        Ok(request) => (format!("{} {}", request.method, request.path), state.handle(&request)),
+        Err(error) => ("-".to_string(), Response::from(error)),
+    };
+    if let Err(e) = response.write(&stream) {
+        eprintln!("aios-server: {} {}: failed to write response: {}", peer, line, e);
+        return;
+    }
+    eprintln!("{} {} {} {:.1}ms", peer, line, response.status, start.elapsed().as_secs_f64() * 1000.0);
+}
+
+/// Command-line overrides of `[server]`
+#[derive(Default)]
+struct Options {
+    config: Option<String>,
+    bind: Option<String>,
+    threads: Option<usize>,
+    max_body_bytes: Option<usize>,
+    cache_dir: Option<String>,
+    dimension: Option<usize>,
+    backup_dir: Option<String>,
+    data_dir: Option<String>,
+    carma_snapshot: Option<String>,
+    trace_file: Option<String>,
+    events_bind: Option<String>,
+}
+
+impl Options {
+    /// None when help was requested
+    fn parse(args: Vec<String>) -> Result<Option<Self>> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            if flag == "-h" || flag == "--help" {
+                return Ok(None);
+            }
+            let (name, inline) = match flag.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (flag, None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| AiosError::validation(format!("{} needs a value\n\n{}", name, USAGE)))
+            };
+            match name.as_str() {
+                "--config" => options.config = Some(value()?),
+                "--bind" => options.bind = Some(value()?),
+                "--threads" => options.threads = Some(number(&name, value()?)?),
+                "--max-body-bytes" => options.max_body_bytes = Some(number(&name, value()?)?),
+                "--cache-dir" => options.cache_dir = Some(value()?),
+                "--dimension" => options.dimension = Some(number(&name, value()?)?),
+                "--backup-dir" => options.backup_dir = Some(value()?),
+                "--data-dir" => options.data_dir = Some(value()?),
+                "--carma-snapshot" => options.carma_snapshot = Some(value()?),
+                "--trace-file" => options.trace_file = Some(value()?),
+                "--events-bind" => options.events_bind = Some(value()?),
+                _ => return Err(AiosError::validation(format!("Unknown option {}\n\n{}", name, USAGE))),
+            }
+        }
+        Ok(Some(options))
+    }
+
+    fn apply(&self, config: &mut AiosConfig) -> Result<()> {
+        let server = &mut config.server;
+        if let Some(bind) = &self.bind {
+            server.bind = bind.clone();
+        }
+        if let Some(threads) = self.threads {
+            server.threads = threads;
+        }
+        if let Some(max_body_bytes) = self.max_body_bytes {
+            server.max_body_bytes = max_body_bytes;
+        }
+        if let Some(cache_dir) = &self.cache_dir {
+            server.cache_dir = cache_dir.clone();
+        }
+        if let Some(dimension) = self.dimension {
+            server.dimension = dimension;
+        }
+        if let Some(backup_dir) = &self.backup_dir {
+            server.backup_dir = backup_dir.clone();
+        }
+        if let Some(data_dir) = &self.data_dir {
+            server.data_dir = data_dir.clone();
+        }
+        if let Some(carma_snapshot) = &self.carma_snapshot {
+            server.carma_snapshot = carma_snapshot.clone();
+        }
+        if server.threads == 0 {
+            return Err(AiosError::config("server.threads must be positive"));
+        }
+        Ok(())
+    }
+}
+
+fn number(name: &str, value: String) -> Result<usize> {
+    value
+        .parse()
+        .map_err(|_| AiosError::validation(format!("{} expects a non-negative integer, got {}", name, value)))
+}
@@ -0,0 +1,156 @@
+//! In-memory document retrieval with BM25 keyword scoring
+//!
+//! Scoring is rust_carma's keyword index (`aios_carma::keyword`) with its
+//! default parameters, k1 = 1.2 and b = 0.75, so searches rank a CARMA
+//! snapshot's fragments as `aios-rs carma search` does.
+//!
+//! With `server.carma_snapshot` set, the index starts with the snapshot's
+//! fragments. Documents added or removed over HTTP change only the index in
+//! memory: the snapshot is never written (it holds embeddings the server
+//! does not have), so they are lost on restart.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use aios_carma::keyword::Bm25Index;
+use aios_errors::{AiosError, Result};
+use serde::{Deserialize, Serialize};
+
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// A document to index; the id is generated when missing
+#[derive(Debug, Deserialize)]
+pub struct NewDocument {
+    pub id: Option<String>,
+    pub content: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+/// A scored match
+#[derive(Debug, Serialize)]
+pub struct Hit {
+    pub id: String,
+    pub score: f32,
+    pub content: String,
+    pub metadata: HashMap<String, String>,
+}
+
+struct Document {
+    id: String,
+    content: String,
+    metadata: HashMap<String, String>,
+}
+
+pub struct RetrievalIndex {
+    documents: Vec<Document>,
+    /// id -> position in `documents`, which is the BM25 doc id
+    positions: HashMap<String, usize>,
+    keywords: Bm25Index,
+    next_id: u64,
+}
+
+impl RetrievalIndex {
+    pub fn new() -> Self {
+        Self {
+            documents: Vec::new(),
+            positions: HashMap::new(),
+            keywords: Bm25Index::new(K1, B),
+            next_id: 0,
+        }
+    }
+
+    /// An index of the fragments of a `RustCarmaCore.save` snapshot
+    pub fn from_snapshot(path: &Path) -> Result<Self> {
+        let (header, _) = aios_carma::snapshot::read_snapshot(path)
+            .map_err(|e| AiosError::from(e).context(format!("Failed to read snapshot {}", path.display())))?;
+        let mut index = Self::new();
+        let documents = header.fragments.into_iter().map(|fragment| NewDocument {
+            id: Some(fragment.id),
+            content: fragment.content,
+            metadata: fragment.metadata,
+        });
+        index.add(documents.collect());
+        Ok(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn vocabulary_size(&self) -> usize {
+        self.keywords.vocabulary_size()
+    }
+
+    /// Index `documents`, replacing any with the same id; returns their ids
+    pub fn add(&mut self, documents: Vec<NewDocument>) -> Vec<String> {
+        let mut ids = Vec::with_capacity(documents.len());
+        let mut replaced = false;
+        for document in documents {
+            let id = document.id.unwrap_or_else(|| loop {
+                self.next_id += 1;
+                let id = format!("doc_{}", self.next_id);
+                if !self.positions.contains_key(&id) {
+                    break id;
+                }
+            });
+            if let Some(&position) = self.positions.get(&id) {
+                self.documents[position].content = document.content;
+                self.documents[position].metadata = document.metadata;
+                replaced = true;
+            } else {
+                self.positions.insert(id.clone(), self.documents.len());
+                if !replaced {
+                    self.keywords.add(&document.content);
+                }
+                self.documents.push(Document {
+                    id: id.clone(),
+                    content: document.content,
+                    metadata: document.metadata,
+                });
+            }
+            ids.push(id);
+        }
+        if replaced {
+            self.reindex();
+        }
+        ids
+    }
+
+    /// Remove a document; returns false if the id is unknown
+    pub fn remove(&mut self, id: &str) -> bool {
+        let Some(position) = self.positions.remove(id) else {
+            return false;
+        };
+        self.documents.remove(position);
+        for (index, document) in self.documents.iter().enumerate().skip(position) {
+            self.positions.insert(document.id.clone(), index);
+        }
+        self.reindex();
+        true
+    }
+
+    /// Top `k` documents by BM25 score, best first; ties keep insertion order
+    pub fn search(&self, query: &str, k: usize) -> Vec<Hit> {
+        let mut ranked: Vec<(usize, f32)> = self.keywords.scores(query).into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+            .into_iter()
+            .take(k)
+            .map(|(position, score)| {
+                let document = &self.documents[position];
+                Hit {
+                    id: document.id.clone(),
+                    score,
+                    content: document.content.clone(),
+                    metadata: document.metadata.clone(),
+                }
+            })
+            .collect()
+    }
+
+    fn reindex(&mut self) {
+        self.keywords.rebuild(self.documents.iter().map(|document| document.content.as_str()));
+    }
+}
@@ -0,0 +1,521 @@
+//! Endpoints and the shared state behind them
+//!
+//! ```text
+//! GET    /                              service info and endpoint list
+//! GET    /health?quick=false            support core health summary
+//! GET    /metrics                       support core performance metrics
+//! GET    /metrics/prometheus?quick=true metrics and health checks for Prometheus
+//! POST   /vectors                       {"vectors": [[f32]], "metadata": [str]}
+//! POST   /vectors/search                {"vector": [f32], "k": 10}
+//! POST   /retrieval/documents           {"documents": [{"id"?, "content", "metadata"?}]}
+//! DELETE /retrieval/documents/{id}
+//! POST   /retrieval/search              {"query": str, "k": 5}
+//! POST   /backup                        {"include_data", "include_logs", "include_config"}
+//! GET    /data/stats?path=.             stats of a directory under the data dir
+//! ```
+//!
+//! The retrieval endpoints search the fragments of `server.carma_snapshot`
+//! by keyword; documents added or removed through them are kept in memory
+//! only (see `retrieval`).
+//!
+//! Browsers may call the API only from pages of `server.allowed_origins`;
+//! requests carrying any other `Origin` are refused. With `server.auth_token`
+//! set, the POST endpoints that add data or run backups and DELETE require
+//! `Authorization: Bearer <token>`.
+
+use std::path::{Component, Path};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, TryLockError};
+use std::time::Instant;
+
+use aios_backup_rust::RustBackupCore;
+use aios_cancel::CancellationToken;
+use aios_config::AiosConfig;
+use aios_data_rust::RustDataCore;
+use aios_errors::{AiosError, Result};
+use aios_store::Store;
+use aios_support_rust::{prometheus, RustSupportCore, VectorIndex};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::http::{Request, Response};
+use crate::retrieval::{NewDocument, RetrievalIndex};
+
+const ENDPOINTS: &[&str] = &[
+    "GET /health",
+    "GET /metrics",
+    "GET /metrics/prometheus",
+    "POST /vectors",
+    "POST /vectors/search",
+    "POST /retrieval/documents",
+    "DELETE /retrieval/documents/{id}",
+    "POST /retrieval/search",
+    "POST /backup",
+    "GET /data/stats",
+];
+
+/// The cores served, shared by all worker threads
+pub struct State {
+    support: Mutex<RustSupportCore>,
+    /// The support core's vector index, locked apart from it so vector
+    /// requests do not wait for health checks
+    vectors: Arc<RwLock<VectorIndex>>,
+    /// None when no backup directory is configured
+    backup: Option<Mutex<RustBackupCore>>,
+    retrieval: RwLock<RetrievalIndex>,
+    data: RustDataCore,
+    dimension: usize,
+    allowed_origins: Vec<String>,
+    /// Empty when none is required
+    auth_token: String,
+    started: Instant,
+}
+
+impl State {
+    pub fn new(config: AiosConfig) -> Result<Self> {
+        let server = config.server;
+        if server.dimension == 0 {
+            return Err(AiosError::config("server.dimension must be positive"));
+        }
+        let store = match config.store.path.as_str() {
+            "" => None,
+            path => Some(Store::open(path)?),
+        };
+        let mut support = RustSupportCore::new(&server.cache_dir, server.dimension, config.support)
+            .map_err(|e| AiosError::io(format!("Failed to initialize support core: {}", e)))?;
+        if let Some(store) = &store {
+            support.attach_store(store).map_err(|e| AiosError::io(format!("Failed to attach store: {}", e)))?;
+        }
+        // Runs the configured schedule_* health checks, if any, in the background
+        support
+            .start_scheduler(None, None, None)
+            .map_err(|e| AiosError::config(format!("Failed to start health check scheduler: {}", e)))?;
+        let backup = if server.backup_dir.is_empty() {
+            None
+        } else {
+            let mut core = RustBackupCore::new(&server.backup_dir, config.backup)
+                .map_err(|e| AiosError::io(format!("Failed to initialize backup core: {}", e)))?;
+            if let Some(store) = &store {
+                core.attach_store(store).map_err(|e| AiosError::io(format!("Failed to attach store: {}", e)))?;
+            }
+            Some(Mutex::new(core))
+        };
+        let retrieval = match server.carma_snapshot.as_str() {
+            "" => RetrievalIndex::new(),
+            path => RetrievalIndex::from_snapshot(Path::new(path))?,
+        };
+        let mut data = RustDataCore::new(&server.data_dir).map_err(|e| e.context("Failed to initialize data core"))?;
+        if let Some(store) = &store {
+            data.attach_store(store)?;
+        }
+        Ok(Self {
+            vectors: support.vector_index(),
+            support: Mutex::new(support),
+            backup,
+            retrieval: RwLock::new(retrieval),
+            data,
+            dimension: server.dimension,
+            allowed_origins: server.allowed_origins,
+            auth_token: server.auth_token,
+            started: Instant::now(),
+        })
+    }
+
+    /// Dispatch a request to its endpoint, answering CORS preflights for
+    /// the allowed origins
+    pub fn handle(&self, request: &Request) -> Response {
+        let _span = aios_trace::span!("aios-server.handle", method = request.method.as_str(), path = request.path.as_str());
+        let Some(origin) = request.header("origin") else {
+            return self.route(request);
+        };
+        if !self.allowed_origins.iter().any(|allowed| allowed == origin) {
+            return Response::error(403, "Forbidden", format!("Origin {} is not in server.allowed_origins", origin));
+        }
+        let response = if request.method == "OPTIONS" { Response::empty(204) } else { self.route(request) };
+        response
+            .with_header("Access-Control-Allow-Origin", origin)
+            .with_header("Access-Control-Allow-Methods", "GET, POST, DELETE")
+            .with_header("Access-Control-Allow-Headers", "Authorization, Content-Type")
+            .with_header("Vary", "Origin")
+    }
+
+    fn route(&self, request: &Request) -> Response {
+        let method = request.method.as_str();
+        let path = request.path.trim_end_matches('/');
+        let changes_state = matches!((method, path), ("POST", "/vectors" | "/retrieval/documents" | "/backup"))
+            || (method == "DELETE" && path.starts_with("/retrieval/documents/"));
+        if changes_state && !self.authorized(request) {
+            return Response::error(401, "Unauthorized", "This endpoint requires Authorization: Bearer <server.auth_token>")
+                .with_header("WWW-Authenticate", "Bearer");
+        }
+        match (method, path) {
+            ("GET", "") => self.info().into(),
+            ("GET", "/health") => self.health(request).into(),
+            ("GET", "/metrics") => self.metrics().into(),
+            ("GET", "/metrics/prometheus") => self.prometheus(request),
+            ("POST", "/vectors") => self.add_vectors(request).into(),
+            ("POST", "/vectors/search") => self.search_vectors(request).into(),
+            ("POST", "/retrieval/documents") => self.add_documents(request).into(),
+            ("POST", "/retrieval/search") => self.search_documents(request).into(),
+            ("POST", "/backup") => self.backup(request),
+            ("GET", "/data/stats") => self.data_stats(request).into(),
+            ("DELETE", _) if path.starts_with("/retrieval/documents/") => {
+                self.remove_document(&path["/retrieval/documents/".len()..]).into()
+            }
+            (_, "" | "/health" | "/metrics" | "/metrics/prometheus" | "/vectors" | "/vectors/search" | "/retrieval/documents"
+                | "/retrieval/search" | "/backup" | "/data/stats") => Response::method_not_allowed(method, &request.path),
+            _ => Response::not_found(format!("No endpoint at {}", request.path)),
+        }
+    }
+
+    /// The request carries the configured bearer token, if one is set
+    fn authorized(&self, request: &Request) -> bool {
+        if self.auth_token.is_empty() {
+            return true;
+        }
+        let token = request.header("authorization").and_then(|value| value.strip_prefix("Bearer ")).unwrap_or("");
+        // Compared in full, so the time taken does not reveal a matching prefix
+        token.len() == self.auth_token.len()
+            && token.bytes().zip(self.auth_token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+
+    fn info(&self) -> Result<Value> {
+        Ok(json!({
+            "service": "aios-server",
+            "version": env!("CARGO_PKG_VERSION"),
+            "uptime_seconds": self.started.elapsed().as_secs_f64(),
+            "dimension": self.dimension,
+            "backup_enabled": self.backup.is_some(),
+            "endpoints": ENDPOINTS,
+        }))
+    }
+
+    fn health(&self, request: &Request) -> Result<Value> {
+        let quick = request.query_param("quick", false)?;
+        let summary = lock(&self.support)
+            .run_health_checks(quick)
+            .map_err(|e| AiosError::io(format!("Health checks failed: {}", e)))?;
+        Ok(serde_json::to_value(summary)?)
+    }
+
+    fn metrics(&self) -> Result<Value> {
+        let metrics = lock(&self.support)
+            .get_performance_metrics()
+            .map_err(|e| AiosError::io(format!("Failed to get metrics: {}", e)))?;
+        Ok(serde_json::to_value(metrics)?)
+    }
+
+    fn prometheus(&self, request: &Request) -> Response {
+        let quick = match request.query_param("quick", true) {
+            Ok(quick) => quick,
+            Err(e) => return e.into(),
+        };
+        match lock(&self.support).render_prometheus(quick) {
+            Ok(text) => Response::text(200, prometheus::CONTENT_TYPE, text),
+            Err(e) => AiosError::io(format!("Failed to render metrics: {}", e)).into(),
+        }
+    }
+
+    fn add_vectors(&self, request: &Request) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Body {
+            vectors: Vec<Vec<f32>>,
+            #[serde(default)]
+            metadata: Vec<String>,
+        }
+        let Body { vectors, mut metadata } = request.json()?;
+        if let Some(vector) = vectors.iter().find(|vector| vector.len() != self.dimension) {
+            return Err(dimension_error(vector.len(), self.dimension));
+        }
+        if metadata.is_empty() {
+            metadata = vec![String::new(); vectors.len()];
+        } else if metadata.len() != vectors.len() {
+            return Err(AiosError::validation(format!(
+                "Got {} metadata entries for {} vectors",
+                metadata.len(),
+                vectors.len()
+            )));
+        }
+        let added = self
+            .vectors
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .add(vectors, metadata)
+            .map_err(|e| AiosError::index(format!("Failed to add vectors: {}", e)))?;
+        Ok(json!({ "added": added }))
+    }
+
+    fn search_vectors(&self, request: &Request) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Body {
+            vector: Vec<f32>,
+            #[serde(default = "default_vector_k")]
+            k: usize,
+        }
+        let Body { vector, k } = request.json()?;
+        if vector.len() != self.dimension {
+            return Err(dimension_error(vector.len(), self.dimension));
+        }
+        let results = self
+            .vectors
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .search(&vector, k)
+            .map_err(|e| AiosError::index(format!("Search failed: {}", e)))?;
+        Ok(json!({ "results": results }))
+    }
+
+    fn add_documents(&self, request: &Request) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Body {
+            documents: Vec<NewDocument>,
+        }
+        let Body { documents } = request.json()?;
+        let mut retrieval = self.retrieval.write().unwrap_or_else(|e| e.into_inner());
+        let ids = retrieval.add(documents);
+        Ok(json!({ "ids": ids, "total_documents": retrieval.len() }))
+    }
+
+    fn remove_document(&self, id: &str) -> Result<Value> {
+        let mut retrieval = self.retrieval.write().unwrap_or_else(|e| e.into_inner());
+        if !retrieval.remove(id) {
+            return Err(AiosError::index(format!("Unknown document: {}", id)));
+        }
+        Ok(json!({ "removed": id, "total_documents": retrieval.len() }))
+    }
+
+    fn search_documents(&self, request: &Request) -> Result<Value> {
+        #[derive(Deserialize)]
+        struct Body {
+            query: String,
+            #[serde(default = "default_retrieval_k")]
+            k: usize,
+        }
+        let Body { query, k } = request.json()?;
+        let retrieval = self.retrieval.read().unwrap_or_else(|e| e.into_inner());
+        Ok(json!({
+            "results": retrieval.search(&query, k),
+            "total_documents": retrieval.len(),
+            "vocabulary_size": retrieval.vocabulary_size(),
+        }))
+    }
+
+    /// 409 while another backup runs, 503 when backups are disabled
+    fn backup(&self, request: &Request) -> Response {
+        #[derive(Deserialize)]
+        #[serde(default)]
+        struct Body {
+            include_data: bool,
+            include_logs: bool,
+            include_config: bool,
+        }
+        impl Default for Body {
+            fn default() -> Self {
+                Self { include_data: false, include_logs: false, include_config: true }
+            }
+        }
+        let Some(backup) = &self.backup else {
+            return Response::error(503, "Unavailable", "Backups are disabled; set server.backup_dir or pass --backup-dir");
+        };
+        let body: Body = match request.json() {
+            Ok(body) => body,
+            Err(e) => return e.into(),
+        };
+        let mut core = match backup.try_lock() {
+            Ok(core) => core,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return Response::error(409, "Conflict", "A backup is already running"),
+        };
+        match core.create_backup(body.include_data, body.include_logs, body.include_config, &CancellationToken::new()) {
+            Ok(result) => Response::ok(&result),
+            Err(e) => AiosError::io(format!("Backup failed: {}", e)).into(),
+        }
+    }
+
+    /// `path` is relative to the data directory and may not leave it
+    fn data_stats(&self, request: &Request) -> Result<Value> {
+        let relative = Path::new(request.query.get("path").map(String::as_str).unwrap_or("."));
+        if relative
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(AiosError::validation(format!(
+                "Path must be relative to the data directory: {}",
+                relative.display()
+            )));
+        }
+        let directory = self.data.data_dir().join(relative);
+        let stats = self.data.get_directory_stats(&directory.to_string_lossy())?;
+        Ok(serde_json::to_value(stats)?)
+    }
+}
+
+fn default_vector_k() -> usize {
+    10
+}
+
+fn default_retrieval_k() -> usize {
+    5
+}
+
+fn dimension_error(got: usize, expected: usize) -> AiosError {
+    AiosError::validation(format!("Vector has dimension {}, expected {}", got, expected))
+}
+
+/// Lock a core, recovering from a panic in another request
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use aios_carma::snapshot::{self, FragmentRecord, SnapshotHeader};
+
+    const TOKEN: &str = "secret-token";
+    const ORIGIN: &str = "http://localhost:8501";
+
+    /// State over fresh directories under a temp dir named after `name`,
+    /// with retrieval seeded from a two-fragment CARMA snapshot
+    fn state(name: &str) -> (State, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("aios_server_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("data").join("logs")).unwrap();
+        let fragments = [("frag_a", "dream cycles consolidate memory"), ("frag_b", "karma refunds per day")];
+        let header = SnapshotHeader {
+            version: snapshot::FORMAT_VERSION,
+            fragments: fragments
+                .iter()
+                .map(|(id, content)| FragmentRecord {
+                    id: id.to_string(),
+                    content: content.to_string(),
+                    timestamp: 0.0,
+                    metadata: HashMap::from([("source".to_string(), "test".to_string())]),
+                    dim: 2,
+                })
+                .collect(),
+            clusters: HashMap::new(),
+            centroids: Vec::new(),
+            total_queries: 0,
+        };
+        let snapshot = dir.join("carma.snapshot");
+        snapshot::write_snapshot(&snapshot, &header, &[&[1.0, 0.0], &[0.0, 1.0]]).unwrap();
+
+        let mut config = AiosConfig::default();
+        config.server.cache_dir = dir.join("cache").to_string_lossy().into_owned();
+        config.server.data_dir = dir.join("data").to_string_lossy().into_owned();
+        config.server.carma_snapshot = snapshot.to_string_lossy().into_owned();
+        config.server.dimension = 2;
+        config.server.allowed_origins = vec![ORIGIN.to_string()];
+        config.server.auth_token = TOKEN.to_string();
+        (State::new(config).unwrap(), dir)
+    }
+
+    fn request(method: &str, target: &str, headers: &[(&str, &str)], body: &str) -> Request {
+        let mut raw = format!("{} {} HTTP/1.1\r\n", method, target);
+        for (name, value) in headers {
+            raw.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if !body.is_empty() {
+            raw.push_str(&format!("Content-Type: application/json\r\nContent-Length: {}\r\n", body.len()));
+        }
+        raw.push_str("\r\n");
+        raw.push_str(body);
+        Request::read(raw.as_bytes(), 1 << 20).unwrap()
+    }
+
+    fn body(response: &Response) -> Value {
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+        response.headers.iter().find(|(key, _)| *key == name).map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn test_state_changes_need_token() {
+        let (state, dir) = state("auth");
+        let documents = r#"{"documents": [{"id": "doc_x", "content": "new document"}]}"#;
+        let bearer = format!("Bearer {}", TOKEN);
+        for headers in [&[][..], &[("Authorization", "Bearer wrong-token")], &[("Authorization", TOKEN)]] {
+            let response = state.handle(&request("POST", "/retrieval/documents", headers, documents));
+            assert_eq!(response.status, 401, "{:?}", headers);
+            assert_eq!(header(&response, "WWW-Authenticate"), Some("Bearer"));
+            assert_eq!(body(&response)["error"]["kind"], "Unauthorized");
+        }
+        for (method, target) in [("POST", "/vectors"), ("POST", "/backup/"), ("DELETE", "/retrieval/documents/frag_a")] {
+            assert_eq!(state.handle(&request(method, target, &[], "")).status, 401, "{} {}", method, target);
+        }
+
+        let response = state.handle(&request("POST", "/retrieval/documents", &[("Authorization", &bearer)], documents));
+        assert_eq!(response.status, 200);
+        assert_eq!(body(&response)["total_documents"], 3);
+        // Reading needs no token
+        let search = r#"{"query": "new document"}"#;
+        let response = state.handle(&request("POST", "/retrieval/search", &[], search));
+        assert_eq!(response.status, 200);
+        assert_eq!(body(&response)["results"][0]["id"], "doc_x");
+        let response = state.handle(&request("DELETE", "/retrieval/documents/doc_x", &[("Authorization", &bearer)], ""));
+        assert_eq!((response.status, body(&response)["total_documents"].clone()), (200, json!(2)));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_foreign_origin_refused() {
+        let (state, dir) = state("origin");
+        let bearer = format!("Bearer {}", TOKEN);
+        for (method, target, body_text) in [("GET", "/", ""), ("POST", "/retrieval/search", r#"{"query": "karma"}"#)] {
+            for origin in ["http://evil.example", "http://localhost:8502", "null"] {
+                let headers = [("Origin", origin), ("Authorization", bearer.as_str())];
+                let response = state.handle(&request(method, target, &headers, body_text));
+                assert_eq!(response.status, 403, "{} {} from {}", method, target, origin);
+                assert_eq!(body(&response)["error"]["kind"], "Forbidden");
+                assert_eq!(header(&response, "Access-Control-Allow-Origin"), None);
+            }
+            let response = state.handle(&request(method, target, &[("Origin", ORIGIN)], body_text));
+            assert_eq!(response.status, 200);
+            assert_eq!(header(&response, "Access-Control-Allow-Origin"), Some(ORIGIN));
+        }
+        let preflight = state.handle(&request("OPTIONS", "/retrieval/documents", &[("Origin", ORIGIN)], ""));
+        assert_eq!(preflight.status, 204);
+        assert_eq!(state.handle(&request("OPTIONS", "/", &[("Origin", "http://evil.example")], "")).status, 403);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_data_stats_stays_in_data_dir() {
+        let (state, dir) = state("data_stats");
+        for path in ["..", "../data", "logs/../..", "%2E%2E", "/etc", "logs/../../cache"] {
+            let response = state.handle(&request("GET", &format!("/data/stats?path={}", path), &[], ""));
+            assert_eq!(response.status, 400, "{}", path);
+            assert_eq!(body(&response)["error"]["kind"], "ValidationError");
+        }
+        for path in [".", "logs", "./logs"] {
+            assert_eq!(state.handle(&request("GET", &format!("/data/stats?path={}", path), &[], "")).status, 200, "{}", path);
+        }
+        assert_eq!(state.handle(&request("GET", "/data/stats", &[], "")).status, 200);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_retrieval_searches_snapshot_fragments() {
+        let (state, dir) = state("snapshot");
+        let response = state.handle(&request("POST", "/retrieval/search", &[], r#"{"query": "karma refunds", "k": 5}"#));
+        assert_eq!(response.status, 200);
+        let found = body(&response);
+        assert_eq!(found["total_documents"], 2);
+        assert_eq!(found["results"].as_array().map(Vec::len), Some(1));
+        assert_eq!(found["results"][0]["id"], "frag_b");
+        assert_eq!(found["results"][0]["metadata"]["source"], "test");
+
+        let mut config = AiosConfig::default();
+        config.server.cache_dir = dir.join("cache").to_string_lossy().into_owned();
+        config.server.data_dir = dir.join("data").to_string_lossy().into_owned();
+        config.server.carma_snapshot = dir.join("missing.snapshot").to_string_lossy().into_owned();
+        let error = State::new(config).err().unwrap();
+        assert!(error.to_string().contains("missing.snapshot"), "{}", error);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,3 +1,4 @@
+use numpy::PyArray1;
 use pyo3::prelude::*;
 use pyo3::types::{PyDict, PyList};
 use serde::{Deserialize, Serialize};
@@ -6,6 +7,9 @@ use std::time::SystemTime;
 use uuid::Uuid;
 use regex::Regex;
 use chrono::{DateTime, Utc};
+use aios_config::{AiosConfig, ArbiterConfig};
+use aios_plugins::Hook;
+use aios_store::{Namespace, Store};
 
 /// Represents a Luna response with personality traits
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -70,6 +74,38 @@ impl LearningSessionResult {
     }
 }
 
+/// Format version of `RustLunaCore.export_state`
+const LUNA_STATE_VERSION: u32 = 1;
+
+/// Format version of `RustArbiter.export_state`
+const ARBITER_STATE_VERSION: u32 = 1;
+
+/// What `RustLunaCore.export_state` saves
+#[derive(Serialize, Deserialize)]
+struct LunaState {
+    responses: Vec<LunaResponse>,
+    total_interactions: u64,
+    karma_history: Vec<f64>,
+    personality_traits: HashMap<String, f64>,
+}
+
+/// Key of the arbiter's state in the store's "luna" namespace
+const ARBITER_STORE_KEY: &str = "arbiter";
+
+/// What `RustArbiter.export_state` saves; the karma rules come from the config
+#[derive(Serialize, Deserialize)]
+struct ArbiterState {
+    current_karma: f64,
+    total_assessments: u64,
+    lesson_count: usize,
+}
+
+aios_stream::python_iterator!(
+    /// Iterator over a core's responses, fetched in chunks; see `iter_responses`
+    ResponseIterator, RustLunaCore, LunaResponse,
+    |py, core, offset, limit| Ok(aios_stream::window(&core.responses, offset, limit))
+);
+
 /// Main Luna Rust implementation
 #[pyclass]
 pub struct RustLunaCore {
@@ -93,6 +129,7 @@ impl RustLunaCore {
 
     /// Generate a response with personality traits
     fn generate_response(&mut self, question: String, personality_trait: String, karma_score: f64) -> LunaResponse {
+        let _span = aios_trace::span!("RustLunaCore.generate_response", question_len = question.len(), personality_trait = personality_trait, karma_score = karma_score);
         self.total_interactions += 1;
         
         let response = LunaResponse::new(
@@ -115,6 +152,7 @@ impl RustLunaCore {
 
     /// Run a learning session with multiple questions
     fn run_learning_session(&mut self, questions: Vec<String>, traits: Vec<String>) -> LearningSessionResult {
+        let _span = aios_trace::span!("RustLunaCore.run_learning_session", questions = questions.len(), traits = traits.len());
         let start_time = SystemTime::now();
         
         if questions.len() != traits.len() {
@@ -153,6 +191,7 @@ impl RustLunaCore {
 
     /// Calculate karma score based on question analysis
     fn calculate_karma_score(&self, question: &str, personality_trait: &str) -> f64 {
+        let _span = aios_trace::span!("RustLunaCore.calculate_karma_score", question_len = question.len(), personality_trait = personality_trait);
         let mut score = 0.5; // Base score
         
         // Analyze question complexity
@@ -181,6 +220,7 @@ impl RustLunaCore {
 
     /// Analyze emotional tone of text
     fn analyze_emotional_tone(&self, text: &str) -> String {
+        let _span = aios_trace::span!("RustLunaCore.analyze_emotional_tone", text_len = text.len());
         let positive_words = ["happy", "good", "great", "wonderful", "amazing", "love", "joy"];
         let negative_words = ["sad", "bad", "terrible", "awful", "hate", "angry", "fear"];
         
@@ -202,6 +242,7 @@ impl RustLunaCore {
 
     /// Classify question type
     fn classify_question_type(&self, question: &str) -> String {
+        let _span = aios_trace::span!("RustLunaCore.classify_question_type", question_len = question.len());
         if question.contains("?") {
             "question".to_string()
         } else if question.contains("!") {
@@ -215,6 +256,7 @@ impl RustLunaCore {
 
     /// Get personality trait scores
     fn get_personality_traits(&self) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustLunaCore.get_personality_traits");
         Python::with_gil(|py| {
             let traits = PyDict::new(py);
             for (personality_trait, value) in &self.personality_traits {
@@ -226,6 +268,7 @@ impl RustLunaCore {
 
     /// Get system statistics
     fn get_stats(&self) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustLunaCore.get_stats");
         Python::with_gil(|py| {
             let stats = PyDict::new(py);
             stats.set_item("total_interactions", self.total_interactions)?;
@@ -238,6 +281,7 @@ impl RustLunaCore {
 
     /// Calculate average karma score
     fn calculate_average_karma(&self) -> f64 {
+        let _span = aios_trace::span!("RustLunaCore.calculate_average_karma");
         if self.karma_history.is_empty() {
             0.0
         } else {
@@ -245,18 +289,64 @@ impl RustLunaCore {
         }
     }
 
+    /// Karma score of every generated response, oldest first, as a float64 numpy array
+    fn get_karma_history<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray1<f64>>> {
+        let _span = aios_trace::span!("RustLunaCore.get_karma_history");
+        // Raise ImportError instead of panicking inside numpy when it is missing
+        py.import_bound("numpy")?;
+        Ok(PyArray1::from_slice_bound(py, &self.karma_history))
+    }
+
     /// Get all responses
     fn get_all_responses(&self) -> Vec<LunaResponse> {
+        let _span = aios_trace::span!("RustLunaCore.get_all_responses");
         self.responses.clone()
     }
 
+    /// Iterate over all responses, `chunk_size` at a time, without copying
+    /// the whole history into Python
+    #[pyo3(signature = (chunk_size=aios_stream::DEFAULT_CHUNK_SIZE))]
+    fn iter_responses(slf: Py<Self>, chunk_size: usize) -> PyResult<ResponseIterator> {
+        let _span = aios_trace::span!("RustLunaCore.iter_responses", chunk_size = chunk_size);
+        ResponseIterator::new(slf, chunk_size)
+    }
+
     /// Clear all data
     fn clear_all(&mut self) {
+        let _span = aios_trace::span!("RustLunaCore.clear_all");
         self.responses.clear();
         self.total_interactions = 0;
         self.karma_history.clear();
         self.personality_traits.clear();
     }
+
+    /// Section name in `save_state` archives
+    #[classattr]
+    const STATE_SECTION: &'static str = "luna";
+
+    /// Responses, karma history and personality traits as `(version, bytes)`
+    fn export_state(&self, py: Python<'_>) -> PyResult<(u32, PyObject)> {
+        let _span = aios_trace::span!("RustLunaCore.export_state");
+        let state = LunaState {
+            responses: self.responses.clone(),
+            total_interactions: self.total_interactions,
+            karma_history: self.karma_history.clone(),
+            personality_traits: self.personality_traits.clone(),
+        };
+        let data = serde_json::to_vec(&state).map_err(|e| errors::io(format!("Failed to encode Luna state: {}", e)))?;
+        Ok((LUNA_STATE_VERSION, data.as_slice().into_py(py)))
+    }
+
+    /// Replace all data with state from `export_state`
+    fn import_state(&mut self, version: u32, data: &[u8]) -> PyResult<()> {
+        let _span = aios_trace::span!("RustLunaCore.import_state", version = version, bytes = data.len());
+        let state: LunaState = aios_state::decode_json("luna", version, data, LUNA_STATE_VERSION).map_err(errors::to_pyerr)?;
+        self.responses = state.responses;
+        self.total_interactions = state.total_interactions;
+        self.karma_history = state.karma_history;
+        self.personality_traits = state.personality_traits;
+        Ok(())
+    }
 }
 
 /// Arbiter Assessment Result
@@ -295,21 +385,48 @@ pub struct RustArbiter {
     current_karma: f64,
     total_assessments: u64,
     lesson_count: usize,
+    rules: ArbiterConfig,
+    /// Keeps karma and counters across restarts when a store is configured
+    store: Option<Namespace>,
 }
 
 #[pymethods]
 impl RustArbiter {
+    /// Karma rules come from the `[arbiter]` section of `config_path`,
+    /// `$AIOS_CONFIG` or `./aios.toml`; `initial_karma` overrides the configured value.
+    ///
+    /// With `[store] path` set, karma and counters are saved there after
+    /// every assessment and picked up again by the next arbiter, unless
+    /// `initial_karma` is given.
     #[new]
-    fn new(initial_karma: f64) -> Self {
-        Self {
-            current_karma: initial_karma,
+    #[pyo3(signature = (initial_karma=None, config_path=None))]
+    fn new(initial_karma: Option<f64>, config_path: Option<&str>) -> PyResult<Self> {
+        let config = AiosConfig::load(config_path).map_err(errors::to_pyerr)?;
+        let rules = config.arbiter;
+        let mut arbiter = Self {
+            current_karma: initial_karma.unwrap_or(rules.initial_karma),
             total_assessments: 0,
             lesson_count: 0,
+            rules,
+            store: None,
+        };
+        if !config.store.path.is_empty() {
+            let store = Store::open(&config.store.path)
+                .and_then(|store| store.namespace("luna", &[]))
+                .map_err(errors::to_pyerr)?;
+            let saved: Option<ArbiterState> = store.get(ARBITER_STORE_KEY).map_err(errors::to_pyerr)?;
+            match saved {
+                Some(state) if initial_karma.is_none() => arbiter.restore(state),
+                _ => store.set(ARBITER_STORE_KEY, &arbiter.state()).map_err(errors::to_pyerr)?,
+            }
+            arbiter.store = Some(store);
         }
+        Ok(arbiter)
     }
 
     /// Fast utility score calculation
     fn calculate_utility_score(&self, luna_response: &str, gold_standard: &str) -> f64 {
+        let _span = aios_trace::span!("RustArbiter.calculate_utility_score", response_len = luna_response.len(), gold_len = gold_standard.len());
         // Word overlap similarity (fast approximation)
         let luna_words: Vec<&str> = luna_response.split_whitespace().collect();
         let gold_words: Vec<&str> = gold_standard.split_whitespace().collect();
@@ -335,15 +452,20 @@ impl RustArbiter {
         matches as f64 / total_unique
     }
 
-    /// Fast response quality assessment
+    /// Fast response quality assessment.
+    ///
+    /// `post_assessment` plugins are called with the assessment, prompt and
+    /// response before it is returned.
     fn assess_response_fast(
         &mut self,
+        py: Python<'_>,
         user_prompt: &str,
         luna_response: &str,
         tte_used: usize,
         max_tte: usize,
         rvc_grade: &str
     ) -> ArbiterAssessment {
+        let _span = aios_trace::span!("RustArbiter.assess_response_fast", prompt_len = user_prompt.len(), response_len = luna_response.len(), tte_used = tte_used, max_tte = max_tte, rvc_grade = rvc_grade);
         self.total_assessments += 1;
         
         // Calculate efficiency
@@ -357,21 +479,14 @@ impl RustArbiter {
         let mut utility_score = efficiency.clamp(0.0, 1.0);
         
         // Adjust for RVC grade
-        let grade_bonus = match rvc_grade {
-            "A" => 0.2,
-            "B" => 0.1,
-            "C" => 0.0,
-            "D" => -0.1,
-            "F" => -0.2,
-            _ => 0.0,
-        };
+        let grade_bonus = self.rules.grade_bonus.get(rvc_grade).copied().unwrap_or(0.0);
         utility_score = (utility_score + grade_bonus).clamp(0.0, 1.0);
         
         // Calculate karma delta based on performance
-        let karma_delta = if efficiency < 0.5 {
-            -0.1
-        } else if efficiency > 0.9 {
-            2.0
+        let karma_delta = if efficiency < self.rules.low_efficiency {
+            self.rules.low_efficiency_karma
+        } else if efficiency > self.rules.high_efficiency {
+            self.rules.high_efficiency_karma
         } else {
             efficiency * 2.0 - 1.0
         };
@@ -382,30 +497,48 @@ impl RustArbiter {
         let quality_gap = 1.0 - utility_score;
         
         // Reasoning
-        let reasoning = if efficiency < 0.5 {
+        let reasoning = if efficiency < self.rules.low_efficiency {
             format!("Poor response quality or efficiency. Karma changed by {:.1}. TTE efficiency: {:.1}%.", karma_delta, efficiency * 100.0)
-        } else if efficiency > 0.9 {
+        } else if efficiency > self.rules.high_efficiency {
             format!("Excellent response! Karma increased by {:.1}. TTE efficiency: {:.1}%.", karma_delta, efficiency * 100.0)
         } else {
             format!("Adequate response. Karma changed by {:.1}. TTE efficiency: {:.1}%.", karma_delta, efficiency * 100.0)
         };
         
-        ArbiterAssessment {
+        let assessment = ArbiterAssessment {
             utility_score,
             karma_delta,
             quality_gap,
             reasoning,
             lessons_generated: 0,
+        };
+        if let Err(e) = self.save() {
+            eprintln!("Failed to save arbiter state: {}", e);
         }
+        if aios_plugins::active(Hook::PostAssessment) {
+            let payload = serde_json::json!({
+                "user_prompt": user_prompt,
+                "luna_response": luna_response,
+                "tte_used": tte_used,
+                "max_tte": max_tte,
+                "rvc_grade": rvc_grade,
+                "assessment": assessment,
+                "current_karma": self.current_karma,
+            });
+            py.allow_threads(|| aios_plugins::notify(Hook::PostAssessment, &payload));
+        }
+        assessment
     }
 
     /// Get current karma
     fn get_current_karma(&self) -> f64 {
+        let _span = aios_trace::span!("RustArbiter.get_current_karma");
         self.current_karma
     }
 
     /// Get stats
     fn get_stats(&self) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustArbiter.get_stats");
         Python::with_gil(|py| {
             let stats = PyDict::new(py);
             stats.set_item("current_karma", self.current_karma)?;
@@ -414,15 +547,85 @@ impl RustArbiter {
             Ok(stats.into())
         })
     }
+
+    /// Section name in `save_state` archives
+    #[classattr]
+    const STATE_SECTION: &'static str = "arbiter";
+
+    /// Current karma and assessment counters as `(version, bytes)`
+    fn export_state(&self, py: Python<'_>) -> PyResult<(u32, PyObject)> {
+        let _span = aios_trace::span!("RustArbiter.export_state");
+        let data = serde_json::to_vec(&self.state()).map_err(|e| errors::io(format!("Failed to encode arbiter state: {}", e)))?;
+        Ok((ARBITER_STATE_VERSION, data.as_slice().into_py(py)))
+    }
+
+    /// Replace karma and counters with state from `export_state`
+    fn import_state(&mut self, version: u32, data: &[u8]) -> PyResult<()> {
+        let _span = aios_trace::span!("RustArbiter.import_state", version = version, bytes = data.len());
+        let state: ArbiterState =
+            aios_state::decode_json("arbiter", version, data, ARBITER_STATE_VERSION).map_err(errors::to_pyerr)?;
+        self.restore(state);
+        self.save().map_err(errors::to_pyerr)
+    }
+}
+
+impl RustArbiter {
+    fn state(&self) -> ArbiterState {
+        ArbiterState {
+            current_karma: self.current_karma,
+            total_assessments: self.total_assessments,
+            lesson_count: self.lesson_count,
+        }
+    }
+
+    fn restore(&mut self, state: ArbiterState) {
+        self.current_karma = state.current_karma;
+        self.total_assessments = state.total_assessments;
+        self.lesson_count = state.lesson_count;
+    }
+
+    /// Write karma and counters to the store, if one is configured
+    fn save(&self) -> aios_errors::Result<()> {
+        match &self.store {
+            Some(store) => store.set(ARBITER_STORE_KEY, &self.state()),
+            None => Ok(()),
+        }
+    }
+}
+
+mod errors {
+    aios_errors::python_exceptions!(aios_luna_rust);
+}
+
+mod tracing {
+    aios_trace::python_tracing!();
+}
+
+mod bus {
+    aios_bus::python_bus!();
+}
+
+mod state {
+    aios_state::python_state!();
+}
+
+mod plugins {
+    aios_plugins::python_plugins!(PostAssessment);
 }
 
 /// Python module definition
 #[pymodule]
-fn aios_luna_rust(_py: Python, m: &PyModule) -> PyResult<()> {
+fn aios_luna_rust(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<LunaResponse>()?;
     m.add_class::<LearningSessionResult>()?;
     m.add_class::<RustLunaCore>()?;
+    m.add_class::<ResponseIterator>()?;
     m.add_class::<ArbiterAssessment>()?;
     m.add_class::<RustArbiter>()?;
+    errors::register(py, m)?;
+    tracing::register(m)?;
+    bus::register(m)?;
+    state::register(m)?;
+    plugins::register(m)?;
     Ok(())
 }
@@ -0,0 +1,270 @@
+//! Minimal FlatBuffers encoding and decoding for Arrow IPC metadata
+//!
+//! The encoder writes each table before its children, so every offset points
+//! forward as the format requires. Alignment is computed from the start of
+//! the buffer, which the IPC framing keeps 8-byte aligned in the file.
+
+use aios_errors::{AiosError, Result};
+
+/// A field value in a table being encoded
+pub enum Value {
+    Bool(bool),
+    U8(u8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    String(String),
+    Table(Table),
+    Tables(Vec<Table>),
+    /// Vector of `count` inline structs of 8-byte aligned fields
+    Structs { bytes: Vec<u8>, count: usize },
+}
+
+impl Value {
+    fn inline_size(&self) -> usize {
+        match self {
+            Value::Bool(_) | Value::U8(_) => 1,
+            Value::I16(_) => 2,
+            Value::I64(_) => 8,
+            _ => 4,
+        }
+    }
+}
+
+/// A table being encoded, as (field id, value) pairs
+#[derive(Default)]
+pub struct Table {
+    fields: Vec<(u16, Value)>,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, id: u16, value: Value) -> Self {
+        self.fields.push((id, value));
+        self
+    }
+}
+
+/// Encode `root`, padded to a multiple of 8 bytes
+pub fn finish(root: &Table) -> Vec<u8> {
+    let mut buf = vec![0u8; 4];
+    let position = write_table(&mut buf, root);
+    buf[0..4].copy_from_slice(&(position as u32).to_le_bytes());
+    align(&mut buf, 8);
+    buf
+}
+
+fn align(buf: &mut Vec<u8>, alignment: usize) {
+    while !buf.len().is_multiple_of(alignment) {
+        buf.push(0);
+    }
+}
+
+fn patch_offset(buf: &mut [u8], at: usize, target: usize) {
+    buf[at..at + 4].copy_from_slice(&((target - at) as u32).to_le_bytes());
+}
+
+fn write_table(buf: &mut Vec<u8>, table: &Table) -> usize {
+    let slots = table.fields.iter().map(|(id, _)| *id as usize + 1).max().unwrap_or(0);
+    align(buf, 2);
+    let vtable = buf.len();
+    buf.resize(vtable + 4 + 2 * slots, 0);
+
+    align(buf, 8);
+    let start = buf.len();
+    buf.extend_from_slice(&((start - vtable) as i32).to_le_bytes());
+
+    // Largest fields first keeps padding small
+    let mut fields: Vec<&(u16, Value)> = table.fields.iter().collect();
+    fields.sort_by_key(|(_, value)| std::cmp::Reverse(value.inline_size()));
+
+    let mut children = Vec::new();
+    for (id, value) in fields {
+        align(buf, value.inline_size());
+        let at = buf.len();
+        match value {
+            Value::Bool(v) => buf.push(*v as u8),
+            Value::U8(v) => buf.push(*v),
+            Value::I16(v) => buf.extend_from_slice(&v.to_le_bytes()),
+            Value::I32(v) => buf.extend_from_slice(&v.to_le_bytes()),
+            Value::I64(v) => buf.extend_from_slice(&v.to_le_bytes()),
+            _ => {
+                buf.extend_from_slice(&[0; 4]);
+                children.push((at, value));
+            }
+        }
+        let slot = vtable + 4 + 2 * *id as usize;
+        buf[slot..slot + 2].copy_from_slice(&((at - start) as u16).to_le_bytes());
+    }
+
+    let table_size = buf.len() - start;
+    buf[vtable..vtable + 2].copy_from_slice(&((4 + 2 * slots) as u16).to_le_bytes());
+    buf[vtable + 2..vtable + 4].copy_from_slice(&(table_size as u16).to_le_bytes());
+
+    for (at, value) in children {
+        let child = write_child(buf, value);
+        patch_offset(buf, at, child);
+    }
+    start
+}
+
+fn write_child(buf: &mut Vec<u8>, value: &Value) -> usize {
+    match value {
+        Value::String(s) => {
+            align(buf, 4);
+            let at = buf.len();
+            buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            buf.extend_from_slice(s.as_bytes());
+            buf.push(0);
+            at
+        }
+        Value::Table(table) => write_table(buf, table),
+        Value::Tables(tables) => {
+            align(buf, 4);
+            let at = buf.len();
+            buf.extend_from_slice(&(tables.len() as u32).to_le_bytes());
+            buf.resize(at + 4 + 4 * tables.len(), 0);
+            for (i, table) in tables.iter().enumerate() {
+                let child = write_table(buf, table);
+                patch_offset(buf, at + 4 + 4 * i, child);
+            }
+            at
+        }
+        Value::Structs { bytes, count } => {
+            // The struct data after the length prefix must be 8-byte aligned
+            while !(buf.len() + 4).is_multiple_of(8) {
+                buf.push(0);
+            }
+            let at = buf.len();
+            buf.extend_from_slice(&(*count as u32).to_le_bytes());
+            buf.extend_from_slice(bytes);
+            at
+        }
+        _ => unreachable!("scalars are stored inline"),
+    }
+}
+
+fn malformed(what: &str) -> AiosError {
+    AiosError::validation(format!("Malformed Arrow metadata: {}", what))
+}
+
+fn read_bytes<const N: usize>(buf: &[u8], at: usize) -> Result<[u8; N]> {
+    buf.get(at..at + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| malformed("offset out of bounds"))
+}
+
+fn read_u32(buf: &[u8], at: usize) -> Result<usize> {
+    Ok(u32::from_le_bytes(read_bytes(buf, at)?) as usize)
+}
+
+/// A table inside an encoded buffer
+#[derive(Clone, Copy)]
+pub struct TableRef<'a> {
+    buf: &'a [u8],
+    position: usize,
+}
+
+/// Root table of an encoded buffer
+pub fn root(buf: &[u8]) -> Result<TableRef<'_>> {
+    Ok(TableRef { buf, position: read_u32(buf, 0)? })
+}
+
+impl<'a> TableRef<'a> {
+    /// Absolute position of field `id`, or None when absent
+    fn field(&self, id: u16) -> Result<Option<usize>> {
+        let soffset = i32::from_le_bytes(read_bytes(self.buf, self.position)?) as i64;
+        let vtable = usize::try_from(self.position as i64 - soffset).map_err(|_| malformed("bad vtable offset"))?;
+        let vtable_size = u16::from_le_bytes(read_bytes(self.buf, vtable)?) as usize;
+        let slot = 4 + 2 * id as usize;
+        if slot + 2 > vtable_size {
+            return Ok(None);
+        }
+        let offset = u16::from_le_bytes(read_bytes(self.buf, vtable + slot)?) as usize;
+        Ok((offset != 0).then_some(self.position + offset))
+    }
+
+    /// Target of the offset stored in field `id`
+    fn indirect(&self, id: u16) -> Result<Option<usize>> {
+        match self.field(id)? {
+            Some(at) => Ok(Some(at + read_u32(self.buf, at)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn u8(&self, id: u16, default: u8) -> Result<u8> {
+        match self.field(id)? {
+            Some(at) => Ok(read_bytes::<1>(self.buf, at)?[0]),
+            None => Ok(default),
+        }
+    }
+
+    pub fn bool(&self, id: u16, default: bool) -> Result<bool> {
+        Ok(self.u8(id, default as u8)? != 0)
+    }
+
+    pub fn i16(&self, id: u16, default: i16) -> Result<i16> {
+        match self.field(id)? {
+            Some(at) => Ok(i16::from_le_bytes(read_bytes(self.buf, at)?)),
+            None => Ok(default),
+        }
+    }
+
+    pub fn i32(&self, id: u16, default: i32) -> Result<i32> {
+        match self.field(id)? {
+            Some(at) => Ok(i32::from_le_bytes(read_bytes(self.buf, at)?)),
+            None => Ok(default),
+        }
+    }
+
+    pub fn i64(&self, id: u16, default: i64) -> Result<i64> {
+        match self.field(id)? {
+            Some(at) => Ok(i64::from_le_bytes(read_bytes(self.buf, at)?)),
+            None => Ok(default),
+        }
+    }
+
+    pub fn has(&self, id: u16) -> Result<bool> {
+        Ok(self.field(id)?.is_some())
+    }
+
+    pub fn table(&self, id: u16) -> Result<Option<TableRef<'a>>> {
+        Ok(self.indirect(id)?.map(|position| TableRef { buf: self.buf, position }))
+    }
+
+    pub fn string(&self, id: u16) -> Result<Option<&'a str>> {
+        let Some(at) = self.indirect(id)? else {
+            return Ok(None);
+        };
+        let len = read_u32(self.buf, at)?;
+        let bytes = self.buf.get(at + 4..at + 4 + len).ok_or_else(|| malformed("string out of bounds"))?;
+        std::str::from_utf8(bytes).map(Some).map_err(|_| malformed("string is not UTF-8"))
+    }
+
+    pub fn tables(&self, id: u16) -> Result<Vec<TableRef<'a>>> {
+        let Some(at) = self.indirect(id)? else {
+            return Ok(Vec::new());
+        };
+        let count = read_u32(self.buf, at)?;
+        (0..count)
+            .map(|i| {
+                let slot = at + 4 + 4 * i;
+                Ok(TableRef { buf: self.buf, position: slot + read_u32(self.buf, slot)? })
+            })
+            .collect()
+    }
+
+    /// Raw bytes of a vector of `size`-byte structs
+    pub fn structs(&self, id: u16, size: usize) -> Result<&'a [u8]> {
+        let Some(at) = self.indirect(id)? else {
+            return Ok(&[]);
+        };
+        let count = read_u32(self.buf, at)?;
+        self.buf
+            .get(at + 4..at + 4 + count * size)
+            .ok_or_else(|| malformed("struct vector out of bounds"))
+    }
+}
@@ -0,0 +1,589 @@
+//! Arrow IPC interchange between the AIOS Rust cores
+//!
+//! Writes and reads the Arrow IPC streaming format for the few column types
+//! the cores exchange, so record batches can move between cores (or to
+//! pyarrow) without JSON. Supported columns:
+//!
+//! - `Utf8` strings (read from `utf8` and `large_utf8`)
+//! - `Int64` integers (read from any integer width)
+//! - `Float64` numbers (read from `float32` and `float64`)
+//! - `FloatLists`, one float vector per row: written as
+//!   `fixed_size_list<float32>` when every row has the same length and as
+//!   `list<float32>` otherwise; read from either, with float32 or float64 items
+//!
+//! Null values, dictionary encoding and compressed bodies are rejected. The
+//! reader also accepts the IPC file format (`ARROW1` magic).
+
+use aios_errors::{AiosError, Result};
+
+mod flatbuf;
+
+use flatbuf::{TableRef, Value};
+
+const CONTINUATION: u32 = 0xFFFF_FFFF;
+const FILE_MAGIC: &[u8] = b"ARROW1";
+const METADATA_V5: i16 = 4;
+
+const HEADER_SCHEMA: u8 = 1;
+const HEADER_DICTIONARY_BATCH: u8 = 2;
+const HEADER_RECORD_BATCH: u8 = 3;
+
+const TYPE_INT: u8 = 2;
+const TYPE_FLOATING_POINT: u8 = 3;
+const TYPE_UTF8: u8 = 5;
+const TYPE_LIST: u8 = 12;
+const TYPE_FIXED_SIZE_LIST: u8 = 16;
+const TYPE_LARGE_UTF8: u8 = 20;
+
+const PRECISION_SINGLE: i16 = 1;
+const PRECISION_DOUBLE: i16 = 2;
+
+/// One column of a record batch
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    Utf8(Vec<String>),
+    Int64(Vec<i64>),
+    Float64(Vec<f64>),
+    FloatLists(Vec<Vec<f32>>),
+}
+
+impl Column {
+    pub fn len(&self) -> usize {
+        match self {
+            Column::Utf8(values) => values.len(),
+            Column::Int64(values) => values.len(),
+            Column::Float64(values) => values.len(),
+            Column::FloatLists(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Column::Utf8(_) => "utf8",
+            Column::Int64(_) => "int64",
+            Column::Float64(_) => "float64",
+            Column::FloatLists(_) => "list<float>",
+        }
+    }
+
+    /// Fixed row length when the lists can be written as a fixed-size list
+    fn fixed_list_size(rows: &[Vec<f32>]) -> Option<usize> {
+        let size = rows.first()?.len();
+        (size > 0 && rows.iter().all(|row| row.len() == size)).then_some(size)
+    }
+}
+
+/// Named, equal-length columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordBatch {
+    columns: Vec<(String, Column)>,
+}
+
+impl RecordBatch {
+    pub fn new(columns: Vec<(String, Column)>) -> Result<Self> {
+        if let Some((_, first)) = columns.first() {
+            if let Some((name, column)) = columns.iter().find(|(_, c)| c.len() != first.len()) {
+                return Err(AiosError::validation(format!(
+                    "Column '{}' has {} rows, expected {}",
+                    name,
+                    column.len(),
+                    first.len()
+                )));
+            }
+        }
+        Ok(Self { columns })
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.columns.first().map_or(0, |(_, column)| column.len())
+    }
+
+    pub fn columns(&self) -> &[(String, Column)] {
+        &self.columns
+    }
+
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|(n, _)| n == name).map(|(_, column)| column)
+    }
+
+    /// Remove and return the column `name`
+    pub fn take_column(&mut self, name: &str) -> Option<Column> {
+        let index = self.columns.iter().position(|(n, _)| n == name)?;
+        Some(self.columns.remove(index).1)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Writing
+
+/// Encode `batch` as an IPC stream: schema, one record batch, end marker
+pub fn write_stream(batch: &RecordBatch) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_message(&mut out, HEADER_SCHEMA, schema_table(batch), &[]);
+
+    let mut nodes = Vec::new();
+    let mut buffers = Vec::new();
+    let mut body = Vec::new();
+    for (name, column) in batch.columns() {
+        encode_column(name, column, &mut nodes, &mut buffers, &mut body)?;
+    }
+    let header = flatbuf::Table::new()
+        .with(0, Value::I64(batch.num_rows() as i64))
+        .with(1, Value::Structs { count: nodes.len() / 16, bytes: nodes })
+        .with(2, Value::Structs { count: buffers.len() / 16, bytes: buffers });
+    write_message(&mut out, HEADER_RECORD_BATCH, header, &body);
+
+    out.extend_from_slice(&CONTINUATION.to_le_bytes());
+    out.extend_from_slice(&0i32.to_le_bytes());
+    Ok(out)
+}
+
+fn write_message(out: &mut Vec<u8>, header_type: u8, header: flatbuf::Table, body: &[u8]) {
+    let message = flatbuf::Table::new()
+        .with(0, Value::I16(METADATA_V5))
+        .with(1, Value::U8(header_type))
+        .with(2, Value::Table(header))
+        .with(3, Value::I64(body.len() as i64));
+    let metadata = flatbuf::finish(&message);
+    out.extend_from_slice(&CONTINUATION.to_le_bytes());
+    out.extend_from_slice(&(metadata.len() as i32).to_le_bytes());
+    out.extend_from_slice(&metadata);
+    out.extend_from_slice(body);
+}
+
+fn schema_table(batch: &RecordBatch) -> flatbuf::Table {
+    let fields = batch
+        .columns()
+        .iter()
+        .map(|(name, column)| match column {
+            Column::Utf8(_) => field(name, TYPE_UTF8, flatbuf::Table::new(), Vec::new()),
+            Column::Int64(_) => field(
+                name,
+                TYPE_INT,
+                flatbuf::Table::new().with(0, Value::I32(64)).with(1, Value::Bool(true)),
+                Vec::new(),
+            ),
+            Column::Float64(_) => field(name, TYPE_FLOATING_POINT, float_type(PRECISION_DOUBLE), Vec::new()),
+            Column::FloatLists(rows) => {
+                let item = field("item", TYPE_FLOATING_POINT, float_type(PRECISION_SINGLE), Vec::new());
+                match Column::fixed_list_size(rows) {
+                    Some(size) => field(
+                        name,
+                        TYPE_FIXED_SIZE_LIST,
+                        flatbuf::Table::new().with(0, Value::I32(size as i32)),
+                        vec![item],
+                    ),
+                    None => field(name, TYPE_LIST, flatbuf::Table::new(), vec![item]),
+                }
+            }
+        })
+        .collect();
+    flatbuf::Table::new().with(1, Value::Tables(fields))
+}
+
+fn field(name: &str, type_type: u8, type_table: flatbuf::Table, children: Vec<flatbuf::Table>) -> flatbuf::Table {
+    flatbuf::Table::new()
+        .with(0, Value::String(name.to_string()))
+        .with(1, Value::Bool(false))
+        .with(2, Value::U8(type_type))
+        .with(3, Value::Table(type_table))
+        .with(5, Value::Tables(children))
+}
+
+fn float_type(precision: i16) -> flatbuf::Table {
+    flatbuf::Table::new().with(0, Value::I16(precision))
+}
+
+fn push_node(nodes: &mut Vec<u8>, length: usize) {
+    nodes.extend_from_slice(&(length as i64).to_le_bytes());
+    nodes.extend_from_slice(&0i64.to_le_bytes());
+}
+
+/// Append `data` to the body, 8-byte aligned, and record its location
+fn push_buffer(buffers: &mut Vec<u8>, body: &mut Vec<u8>, data: &[u8]) {
+    buffers.extend_from_slice(&(body.len() as i64).to_le_bytes());
+    buffers.extend_from_slice(&(data.len() as i64).to_le_bytes());
+    body.extend_from_slice(data);
+    while !body.len().is_multiple_of(8) {
+        body.push(0);
+    }
+}
+
+fn offsets_i32(name: &str, lengths: impl Iterator<Item = usize>) -> Result<Vec<u8>> {
+    let mut bytes = vec![0u8; 4];
+    let mut end = 0usize;
+    for length in lengths {
+        end += length;
+        let offset = i32::try_from(end)
+            .map_err(|_| AiosError::validation(format!("Column '{}' exceeds 2 GiB of values", name)))?;
+        bytes.extend_from_slice(&offset.to_le_bytes());
+    }
+    Ok(bytes)
+}
+
+fn encode_column(name: &str, column: &Column, nodes: &mut Vec<u8>, buffers: &mut Vec<u8>, body: &mut Vec<u8>) -> Result<()> {
+    push_node(nodes, column.len());
+    // No nulls, so every validity bitmap is empty
+    push_buffer(buffers, body, &[]);
+    match column {
+        Column::Utf8(values) => {
+            push_buffer(buffers, body, &offsets_i32(name, values.iter().map(String::len))?);
+            push_buffer(buffers, body, values.concat().as_bytes());
+        }
+        Column::Int64(values) => {
+            push_buffer(buffers, body, &values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>());
+        }
+        Column::Float64(values) => {
+            push_buffer(buffers, body, &values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>());
+        }
+        Column::FloatLists(rows) => {
+            if Column::fixed_list_size(rows).is_none() {
+                push_buffer(buffers, body, &offsets_i32(name, rows.iter().map(Vec::len))?);
+            }
+            let values: Vec<u8> = rows.iter().flatten().flat_map(|v| v.to_le_bytes()).collect();
+            push_node(nodes, values.len() / 4);
+            push_buffer(buffers, body, &[]);
+            push_buffer(buffers, body, &values);
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Reading
+
+fn invalid(message: impl Into<String>) -> AiosError {
+    AiosError::validation(format!("Invalid Arrow IPC data: {}", message.into()))
+}
+
+/// Column types the reader understands
+#[derive(Debug, Clone)]
+enum FieldType {
+    Utf8 { large: bool },
+    Int { bits: i32, signed: bool },
+    Float { double: bool },
+    List { fixed: Option<usize>, double: bool },
+}
+
+struct Field {
+    name: String,
+    data_type: FieldType,
+}
+
+/// Decode every record batch of an IPC stream (or file)
+pub fn read_stream(data: &[u8]) -> Result<Vec<RecordBatch>> {
+    let mut pos = if data.starts_with(FILE_MAGIC) { 8 } else { 0 };
+    let mut schema: Option<Vec<Field>> = None;
+    let mut batches = Vec::new();
+
+    while pos + 4 <= data.len() {
+        let mut length = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+        pos += 4;
+        // Streams written before Arrow 0.15 omit the continuation marker
+        if length == CONTINUATION {
+            length = u32::from_le_bytes(data.get(pos..pos + 4).ok_or_else(|| invalid("truncated message"))?.try_into().unwrap());
+            pos += 4;
+        }
+        if length == 0 {
+            break;
+        }
+        let metadata = data
+            .get(pos..pos + length as usize)
+            .ok_or_else(|| invalid("truncated message metadata"))?;
+        pos += length as usize;
+
+        let message = flatbuf::root(metadata)?;
+        let body_length = usize::try_from(message.i64(3, 0)?).map_err(|_| invalid("negative body length"))?;
+        let body = data.get(pos..pos + body_length).ok_or_else(|| invalid("truncated message body"))?;
+        pos += body_length;
+
+        let header = message.table(2)?.ok_or_else(|| invalid("message without header"))?;
+        match message.u8(1, 0)? {
+            HEADER_SCHEMA => schema = Some(read_schema(header)?),
+            HEADER_RECORD_BATCH => {
+                let fields = schema.as_ref().ok_or_else(|| invalid("record batch before schema"))?;
+                batches.push(read_record_batch(header, body, fields)?);
+            }
+            HEADER_DICTIONARY_BATCH => return Err(invalid("dictionary-encoded columns are not supported")),
+            other => return Err(invalid(format!("unsupported message type {}", other))),
+        }
+    }
+    if schema.is_none() {
+        return Err(invalid("no schema message"));
+    }
+    Ok(batches)
+}
+
+fn read_schema(schema: TableRef<'_>) -> Result<Vec<Field>> {
+    if schema.i16(0, 0)? != 0 {
+        return Err(invalid("big-endian data is not supported"));
+    }
+    schema
+        .tables(1)?
+        .into_iter()
+        .map(|field| {
+            let name = field.string(0)?.unwrap_or_default().to_string();
+            if field.has(4)? {
+                return Err(invalid(format!("column '{}' is dictionary-encoded", name)));
+            }
+            let data_type = read_type(&name, field)?;
+            Ok(Field { name, data_type })
+        })
+        .collect()
+}
+
+fn read_type(name: &str, field: TableRef<'_>) -> Result<FieldType> {
+    let type_table = field.table(3)?;
+    let unsupported = |kind: &str| invalid(format!("column '{}' has unsupported type {}", name, kind));
+    match field.u8(2, 0)? {
+        TYPE_UTF8 => Ok(FieldType::Utf8 { large: false }),
+        TYPE_LARGE_UTF8 => Ok(FieldType::Utf8 { large: true }),
+        TYPE_INT => {
+            let int = type_table.ok_or_else(|| unsupported("int without width"))?;
+            let bits = int.i32(0, 0)?;
+            if ![8, 16, 32, 64].contains(&bits) {
+                return Err(unsupported(&format!("int{}", bits)));
+            }
+            Ok(FieldType::Int { bits, signed: int.bool(1, false)? })
+        }
+        TYPE_FLOATING_POINT => Ok(FieldType::Float { double: float_is_double(name, type_table)? }),
+        kind @ (TYPE_LIST | TYPE_FIXED_SIZE_LIST) => {
+            let fixed = if kind == TYPE_FIXED_SIZE_LIST {
+                let size = type_table.ok_or_else(|| unsupported("fixed_size_list without size"))?.i32(0, 0)?;
+                Some(usize::try_from(size).map_err(|_| unsupported("negative list size"))?)
+            } else {
+                None
+            };
+            let children = field.tables(5)?;
+            let item = children.first().ok_or_else(|| unsupported("list without item field"))?;
+            if item.u8(2, 0)? != TYPE_FLOATING_POINT {
+                return Err(unsupported("list of non-float items"));
+            }
+            Ok(FieldType::List { fixed, double: float_is_double(name, item.table(3)?)? })
+        }
+        other => Err(unsupported(&format!("id {}", other))),
+    }
+}
+
+fn float_is_double(name: &str, float: Option<TableRef<'_>>) -> Result<bool> {
+    match float.map(|t| t.i16(0, 0)).transpose()?.unwrap_or(0) {
+        PRECISION_SINGLE => Ok(false),
+        PRECISION_DOUBLE => Ok(true),
+        _ => Err(invalid(format!("column '{}' has half-precision floats", name))),
+    }
+}
+
+/// Field nodes and buffers of a record batch, consumed in schema order
+struct BatchReader<'a> {
+    body: &'a [u8],
+    nodes: std::slice::ChunksExact<'a, u8>,
+    buffers: std::slice::ChunksExact<'a, u8>,
+}
+
+impl<'a> BatchReader<'a> {
+    /// Row count of the next node; nulls are rejected
+    fn node(&mut self, name: &str) -> Result<usize> {
+        let node = self.nodes.next().ok_or_else(|| invalid("missing field node"))?;
+        let length = i64::from_le_bytes(node[0..8].try_into().unwrap());
+        let null_count = i64::from_le_bytes(node[8..16].try_into().unwrap());
+        if null_count != 0 {
+            return Err(invalid(format!("column '{}' contains nulls", name)));
+        }
+        usize::try_from(length).map_err(|_| invalid("negative length"))
+    }
+
+    fn buffer(&mut self) -> Result<&'a [u8]> {
+        let buffer = self.buffers.next().ok_or_else(|| invalid("missing buffer"))?;
+        let offset = i64::from_le_bytes(buffer[0..8].try_into().unwrap());
+        let length = i64::from_le_bytes(buffer[8..16].try_into().unwrap());
+        let (offset, length) = (usize::try_from(offset), usize::try_from(length));
+        match (offset, length) {
+            (Ok(offset), Ok(length)) => self.body.get(offset..offset + length).ok_or_else(|| invalid("buffer out of bounds")),
+            _ => Err(invalid("negative buffer offset")),
+        }
+    }
+}
+
+fn read_record_batch(header: TableRef<'_>, body: &[u8], fields: &[Field]) -> Result<RecordBatch> {
+    if header.has(3)? {
+        return Err(invalid("compressed record batches are not supported"));
+    }
+    let mut reader = BatchReader {
+        body,
+        nodes: header.structs(1, 16)?.chunks_exact(16),
+        buffers: header.structs(2, 16)?.chunks_exact(16),
+    };
+    let columns = fields
+        .iter()
+        .map(|field| Ok((field.name.clone(), read_column(&mut reader, field)?)))
+        .collect::<Result<Vec<_>>>()?;
+    RecordBatch::new(columns)
+}
+
+fn values<const N: usize>(buffer: &[u8], count: usize) -> Result<impl Iterator<Item = [u8; N]> + '_> {
+    let bytes = count
+        .checked_mul(N)
+        .and_then(|size| buffer.get(..size))
+        .ok_or_else(|| invalid("value buffer too short"))?;
+    Ok(bytes.chunks_exact(N).map(|chunk| chunk.try_into().unwrap()))
+}
+
+fn offsets(buffer: &[u8], rows: usize, large: bool) -> Result<Vec<usize>> {
+    let offsets: Vec<i64> = if large {
+        values::<8>(buffer, rows + 1)?.map(i64::from_le_bytes).collect()
+    } else {
+        values::<4>(buffer, rows + 1)?.map(|b| i32::from_le_bytes(b) as i64).collect()
+    };
+    let offsets = offsets
+        .into_iter()
+        .map(|o| usize::try_from(o).map_err(|_| invalid("negative offset")))
+        .collect::<Result<Vec<usize>>>()?;
+    if offsets.windows(2).any(|w| w[0] > w[1]) {
+        return Err(invalid("offsets are not increasing"));
+    }
+    Ok(offsets)
+}
+
+fn floats(buffer: &[u8], count: usize, double: bool) -> Result<Vec<f32>> {
+    Ok(if double {
+        values::<8>(buffer, count)?.map(|b| f64::from_le_bytes(b) as f32).collect()
+    } else {
+        values::<4>(buffer, count)?.map(f32::from_le_bytes).collect()
+    })
+}
+
+fn read_column(reader: &mut BatchReader<'_>, field: &Field) -> Result<Column> {
+    let rows = reader.node(&field.name)?;
+    let _validity = reader.buffer()?;
+    match field.data_type {
+        FieldType::Utf8 { large } => {
+            let offsets = offsets(reader.buffer()?, rows, large)?;
+            let data = reader.buffer()?;
+            let strings = offsets
+                .windows(2)
+                .map(|w| {
+                    let bytes = data.get(w[0]..w[1]).ok_or_else(|| invalid("string data out of bounds"))?;
+                    String::from_utf8(bytes.to_vec()).map_err(|_| invalid(format!("column '{}' is not valid UTF-8", field.name)))
+                })
+                .collect::<Result<_>>()?;
+            Ok(Column::Utf8(strings))
+        }
+        FieldType::Int { bits, signed } => {
+            let buffer = reader.buffer()?;
+            let ints: Vec<i64> = match (bits, signed) {
+                (8, true) => values::<1>(buffer, rows)?.map(|b| i8::from_le_bytes(b) as i64).collect(),
+                (8, false) => values::<1>(buffer, rows)?.map(|b| b[0] as i64).collect(),
+                (16, true) => values::<2>(buffer, rows)?.map(|b| i16::from_le_bytes(b) as i64).collect(),
+                (16, false) => values::<2>(buffer, rows)?.map(|b| u16::from_le_bytes(b) as i64).collect(),
+                (32, true) => values::<4>(buffer, rows)?.map(|b| i32::from_le_bytes(b) as i64).collect(),
+                (32, false) => values::<4>(buffer, rows)?.map(|b| u32::from_le_bytes(b) as i64).collect(),
+                (_, true) => values::<8>(buffer, rows)?.map(i64::from_le_bytes).collect(),
+                (_, false) => values::<8>(buffer, rows)?
+                    .map(|b| i64::try_from(u64::from_le_bytes(b)).map_err(|_| invalid("uint64 value out of range")))
+                    .collect::<Result<_>>()?,
+            };
+            Ok(Column::Int64(ints))
+        }
+        FieldType::Float { double } => {
+            let buffer = reader.buffer()?;
+            Ok(Column::Float64(if double {
+                values::<8>(buffer, rows)?.map(f64::from_le_bytes).collect()
+            } else {
+                values::<4>(buffer, rows)?.map(|b| f32::from_le_bytes(b) as f64).collect()
+            }))
+        }
+        FieldType::List { fixed, double } => {
+            let offsets = match fixed {
+                Some(_) => None,
+                None => Some(offsets(reader.buffer()?, rows, false)?),
+            };
+            let item_count = reader.node(&field.name)?;
+            let _item_validity = reader.buffer()?;
+            let items = floats(reader.buffer()?, item_count, double)?;
+            let lists = match (fixed, offsets) {
+                (Some(size), _) => {
+                    if size == 0 || rows.checked_mul(size) != Some(item_count) {
+                        return Err(invalid(format!("column '{}' has inconsistent fixed-size lists", field.name)));
+                    }
+                    items.chunks_exact(size).map(<[f32]>::to_vec).collect()
+                }
+                (None, Some(offsets)) => offsets
+                    .windows(2)
+                    .map(|w| items.get(w[0]..w[1]).map(<[f32]>::to_vec).ok_or_else(|| invalid("list offsets out of bounds")))
+                    .collect::<Result<_>>()?,
+                (None, None) => unreachable!("variable-size lists always read offsets"),
+            };
+            Ok(Column::FloatLists(lists))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> RecordBatch {
+        RecordBatch::new(vec![
+            ("text".to_string(), Column::Utf8(vec!["".to_string(), "héllo".to_string(), "a".to_string()])),
+            ("count".to_string(), Column::Int64(vec![-1, 0, i64::MAX])),
+            ("score".to_string(), Column::Float64(vec![0.5, -2.0, f64::MAX])),
+            ("fixed".to_string(), Column::FloatLists(vec![vec![1.0, 2.0], vec![3.0, 4.0], vec![5.0, 6.0]])),
+            ("ragged".to_string(), Column::FloatLists(vec![vec![1.0], vec![], vec![2.0, 3.0, 4.0]])),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let batch = sample();
+        assert_eq!(read_stream(&write_stream(&batch).unwrap()).unwrap(), vec![batch]);
+
+        let empty = RecordBatch::new(vec![
+            ("text".to_string(), Column::Utf8(Vec::new())),
+            ("vectors".to_string(), Column::FloatLists(Vec::new())),
+        ])
+        .unwrap();
+        let read = read_stream(&write_stream(&empty).unwrap()).unwrap();
+        assert_eq!(read, vec![empty]);
+        assert_eq!(read[0].num_rows(), 0);
+    }
+
+    /// A stream of one utf8 column "s" with the given null count and buffers
+    fn utf8_stream(null_count: i64, validity: &[u8], offsets: &[i32], data: &[u8]) -> Vec<u8> {
+        let schema = RecordBatch::new(vec![("s".to_string(), Column::Utf8(Vec::new()))]).unwrap();
+        let mut out = Vec::new();
+        write_message(&mut out, HEADER_SCHEMA, schema_table(&schema), &[]);
+        let rows = offsets.len() as i64 - 1;
+        let nodes: Vec<u8> = rows.to_le_bytes().into_iter().chain(null_count.to_le_bytes()).collect();
+        let (mut buffers, mut body) = (Vec::new(), Vec::new());
+        push_buffer(&mut buffers, &mut body, validity);
+        push_buffer(&mut buffers, &mut body, &offsets.iter().flat_map(|o| o.to_le_bytes()).collect::<Vec<u8>>());
+        push_buffer(&mut buffers, &mut body, data);
+        let header = flatbuf::Table::new()
+            .with(0, Value::I64(rows))
+            .with(1, Value::Structs { count: 1, bytes: nodes })
+            .with(2, Value::Structs { count: 3, bytes: buffers });
+        write_message(&mut out, HEADER_RECORD_BATCH, header, &body);
+        out
+    }
+
+    #[test]
+    fn test_validity_and_offsets() {
+        // Writers such as pyarrow may send an all-valid bitmap
+        let batches = read_stream(&utf8_stream(0, &[0b111], &[0, 1, 3, 3], b"abc")).unwrap();
+        assert_eq!(batches[0].column("s"), Some(&Column::Utf8(vec!["a".to_string(), "bc".to_string(), String::new()])));
+
+        let error = read_stream(&utf8_stream(1, &[0b101], &[0, 1, 3, 3], b"abc")).unwrap_err();
+        assert!(error.to_string().contains("contains nulls"), "{}", error);
+        let error = read_stream(&utf8_stream(0, &[], &[0, 2, 1], b"ab")).unwrap_err();
+        assert!(error.to_string().contains("not increasing"), "{}", error);
+        let error = read_stream(&utf8_stream(0, &[], &[0, 5], b"ab")).unwrap_err();
+        assert!(error.to_string().contains("out of bounds"), "{}", error);
+        let error = read_stream(&utf8_stream(0, &[], &[0, 2], &[0xff, 0xfe])).unwrap_err();
+        assert!(error.to_string().contains("not valid UTF-8"), "{}", error);
+    }
+}
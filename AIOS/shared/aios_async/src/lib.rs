@@ -0,0 +1,80 @@
+//! asyncio awaitables for the AIOS Rust cores
+//!
+//! Like `aios-errors`, this crate does not depend on PyO3: extension modules
+//! expand `python_awaitables!` against their own PyO3 version. The generated
+//! `spawn` runs blocking work on the module's background pool from
+//! `aios-threads` and returns an asyncio future created on the caller's
+//! running event loop. The pool bounds how much work runs at once; the rest
+//! waits in its queue. The future is resolved from
+//! the loop thread via `call_soon_threadsafe`, so awaiting it never blocks
+//! the loop or an executor thread. Cancelling the future does not stop the
+//! work; its result is discarded.
+
+#[doc(hidden)]
+pub use aios_threads::spawn as spawn_background;
+
+/// Define `spawn` in the calling crate, against its own `pyo3`.
+///
+/// Expand it inside a module, e.g. `mod awaitable { aios_async::python_awaitables!(); }`,
+/// and call `awaitable::spawn(py, move || ...)` from an `async`-style pymethod.
+#[macro_export]
+macro_rules! python_awaitables {
+    () => {
+        /// Completes an asyncio future on its event loop, unless it was cancelled
+        #[::pyo3::pyclass]
+        struct Resolve {
+            future: ::pyo3::PyObject,
+            outcome: Option<::pyo3::PyResult<::pyo3::PyObject>>,
+        }
+
+        #[::pyo3::pymethods]
+        impl Resolve {
+            #[allow(deprecated)]
+            fn __call__(&mut self, py: ::pyo3::Python<'_>) -> ::pyo3::PyResult<()> {
+                if self.future.call_method0(py, "done")?.is_true(py)? {
+                    return Ok(());
+                }
+                match self.outcome.take() {
+                    Some(Ok(value)) => self.future.call_method1(py, "set_result", (value,))?,
+                    Some(Err(e)) => self.future.call_method1(py, "set_exception", (e.into_value(py),))?,
+                    None => return Ok(()),
+                };
+                Ok(())
+            }
+        }
+
+        /// Run `work` on the background pool and return an asyncio future for its result.
+        ///
+        /// Must be called from a coroutine (raises RuntimeError without a running
+        /// event loop). `work` runs without the GIL; use `Python::with_gil` inside
+        /// it to touch Python objects.
+        #[allow(deprecated)]
+        pub fn spawn<T, F>(py: ::pyo3::Python<'_>, work: F) -> ::pyo3::PyResult<::pyo3::PyObject>
+        where
+            T: ::pyo3::IntoPy<::pyo3::PyObject> + Send + 'static,
+            F: FnOnce() -> ::pyo3::PyResult<T> + Send + 'static,
+        {
+            let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+            let future: ::pyo3::PyObject = event_loop.call_method0("create_future")?.into();
+            let event_loop: ::pyo3::PyObject = event_loop.into();
+            let awaitable = future.clone_ref(py);
+
+            $crate::spawn_background(move || {
+                let outcome = work();
+                ::pyo3::Python::with_gil(|py| {
+                    let resolve = Resolve {
+                        future,
+                        outcome: Some(outcome.map(|value| value.into_py(py))),
+                    };
+                    // Fails only if the loop was closed in the meantime
+                    let scheduled = ::pyo3::Py::new(py, resolve)
+                        .and_then(|resolve| event_loop.call_method1(py, "call_soon_threadsafe", (resolve,)));
+                    if let Err(e) = scheduled {
+                        e.print(py);
+                    }
+                });
+            });
+            Ok(awaitable)
+        }
+    };
+}
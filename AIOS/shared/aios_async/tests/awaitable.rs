@@ -0,0 +1,71 @@
+//! Awaitables resolve on the event loop from the background pool
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+mod awaitable {
+    aios_async::python_awaitables!();
+}
+
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+static MOST_RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+/// Doubles `value` after a short sleep, on a thread of the background pool
+#[pyfunction]
+fn double(py: Python<'_>, value: i64) -> PyResult<PyObject> {
+    awaitable::spawn(py, move || {
+        let running = RUNNING.fetch_add(1, Ordering::SeqCst) + 1;
+        MOST_RUNNING.fetch_max(running, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(20));
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+        let name = thread::current().name().unwrap_or_default().to_string();
+        Ok((value * 2, name))
+    })
+}
+
+#[pyfunction]
+fn fail(py: Python<'_>, message: String) -> PyResult<PyObject> {
+    awaitable::spawn(py, move || Err::<(), _>(PyValueError::new_err(message)))
+}
+
+const SCRIPT: &str = r#"
+import asyncio
+
+async def main():
+    results = await asyncio.gather(*(double(i) for i in range(8)))
+    try:
+        await fail("boom")
+        error = None
+    except ValueError as e:
+        error = str(e)
+    return results, error
+
+results, error = asyncio.run(main())
+"#;
+
+#[test]
+fn test_futures_resolve_and_raise() -> PyResult<()> {
+    aios_threads::configure("awaitable-test", aios_threads::PoolConfig { threads: 2, ..Default::default() }).unwrap();
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| -> PyResult<()> {
+        let globals = PyDict::new(py);
+        globals.set_item("double", wrap_pyfunction!(double, py)?)?;
+        globals.set_item("fail", wrap_pyfunction!(fail, py)?)?;
+        py.run(SCRIPT, Some(globals), None)?;
+
+        let results: Vec<(i64, String)> = globals.get_item("results")?.expect("results set").extract()?;
+        assert_eq!(results.iter().map(|(value, _)| *value).collect::<Vec<_>>(), (0..8).map(|i| i * 2).collect::<Vec<_>>());
+        assert!(results.iter().all(|(_, name)| name.starts_with("awaitable-test-background-")), "{:?}", results);
+        let error: Option<String> = globals.get_item("error")?.expect("error set").extract()?;
+        assert_eq!(error.as_deref(), Some("boom"));
+        Ok(())
+    })?;
+    // Eight calls at once, but never more than the pool's two threads
+    assert_eq!(MOST_RUNNING.load(Ordering::SeqCst), 2);
+    Ok(())
+}
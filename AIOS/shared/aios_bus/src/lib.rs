@@ -0,0 +1,796 @@
+//! Message bus between the AIOS cores
+//!
+//! Messages are published to a topic, by convention the target core's name
+//! ("luna", "carma", "dream", "support", ...), and consumed from it in order.
+//! Each topic is a bounded in-memory queue. Once it holds `capacity`
+//! messages, new ones are appended to a spill file `<spill_dir>/<topic>.jsonl`
+//! and moved back into the queue, still in order, as consumers drain it.
+//! Without a spill directory a full topic makes `publish` wait for room.
+//!
+//! Delivery is at-least-once: a received message stays in flight until it
+//! is acknowledged and is delivered again if that does not happen within
+//! `redeliver_after`. When the bus is closed or dropped, queued and
+//! in-flight messages are written to the spill files and picked up by the
+//! next bus opened on the same directory. Messages still in memory are lost
+//! if the process crashes.
+//!
+//! Like `aios-errors`, this crate does not depend on PyO3: each extension
+//! module expands `python_bus!` to get its own `MessageBus` class. Extension
+//! modules do not share memory, so cores exchange messages by being handed
+//! the same Python `MessageBus` object; a spill directory can only be open in
+//! one bus at a time, which a `.lock` file enforces.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use aios_errors::{AiosError, Result};
+use serde::{Deserialize, Serialize};
+
+/// Longest accepted topic name
+const MAX_TOPIC_LEN: usize = 64;
+
+const LOCK_FILE: &str = ".lock";
+
+/// A message between cores; the field names match
+/// `RustUtilsCore.create_core_message`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub message_id: String,
+    pub source_core: String,
+    pub target_core: String,
+    pub message_type: String,
+    pub payload: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: f64,
+    /// Deliveries so far
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+impl Message {
+    /// A new message with a random id, addressed to `target_core`'s topic
+    pub fn new(source_core: &str, target_core: &str, message_type: &str, payload: impl Into<String>) -> Self {
+        Self {
+            message_id: uuid::Uuid::new_v4().to_string(),
+            source_core: source_core.to_string(),
+            target_core: target_core.to_string(),
+            message_type: message_type.to_string(),
+            payload: payload.into(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64(),
+            attempts: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BusConfig {
+    /// Messages kept in memory per topic
+    pub capacity: usize,
+    /// Where full topics overflow to and unfinished messages are kept on close
+    pub spill_dir: Option<PathBuf>,
+    /// How long a received message may stay unacknowledged
+    pub redeliver_after: Duration,
+}
+
+impl Default for BusConfig {
+    fn default() -> Self {
+        Self { capacity: 1024, spill_dir: None, redeliver_after: Duration::from_secs(30) }
+    }
+}
+
+/// Counters of one topic
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TopicStats {
+    pub topic: String,
+    /// In memory, waiting for a consumer
+    pub queued: usize,
+    /// Received but not yet acknowledged
+    pub in_flight: usize,
+    /// Waiting in the spill file
+    pub spilled: usize,
+    pub published: u64,
+    pub acked: u64,
+    pub redelivered: u64,
+    /// Unreadable spill file lines that were skipped
+    pub dropped: u64,
+}
+
+#[derive(Default)]
+struct Topic {
+    queue: VecDeque<Message>,
+    /// In publish order, with the instant each becomes due for redelivery
+    in_flight: Vec<(Message, Instant)>,
+    spilled: usize,
+    published: u64,
+    acked: u64,
+    redelivered: u64,
+    dropped: u64,
+}
+
+struct State {
+    topics: BTreeMap<String, Topic>,
+    closed: bool,
+}
+
+struct Inner {
+    config: BusConfig,
+    state: Mutex<State>,
+    /// Signalled when a topic gains a message or frees room
+    changed: Condvar,
+}
+
+/// Handle to a bus; clones share it
+#[derive(Clone)]
+pub struct MessageBus {
+    inner: Arc<Inner>,
+}
+
+impl MessageBus {
+    /// Open a bus, loading messages left in `config.spill_dir`
+    pub fn open(config: BusConfig) -> Result<Self> {
+        if config.capacity == 0 {
+            return Err(AiosError::config("Bus capacity must be positive"));
+        }
+        let mut topics = BTreeMap::new();
+        if let Some(dir) = &config.spill_dir {
+            fs::create_dir_all(dir)
+                .map_err(|e| AiosError::from(e).context(format!("Failed to create spill directory {}", dir.display())))?;
+            acquire_lock(dir)?;
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                let Some(name) = path.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_suffix(".jsonl")) else {
+                    continue;
+                };
+                if validate_topic(name).is_err() {
+                    continue;
+                }
+                let (count, dropped) = count_spilled(&path)?;
+                topics.insert(name.to_string(), Topic { spilled: count, dropped, ..Topic::default() });
+            }
+        }
+        let bus = Self {
+            inner: Arc::new(Inner {
+                config,
+                state: Mutex::new(State { topics, closed: false }),
+                changed: Condvar::new(),
+            }),
+        };
+        {
+            let mut state = bus.inner.lock();
+            let names: Vec<String> = state.topics.keys().cloned().collect();
+            for name in names {
+                bus.inner.refill(&mut state, &name)?;
+            }
+        }
+        Ok(bus)
+    }
+
+    pub fn config(&self) -> &BusConfig {
+        &self.inner.config
+    }
+
+    /// Publish to `message.target_core`'s topic; returns the message id.
+    ///
+    /// Without a spill directory, waits up to `timeout` for room in a full
+    /// topic and fails with an `Io` error after that.
+    pub fn publish(&self, mut message: Message, timeout: Duration) -> Result<String> {
+        let topic = message.target_core.clone();
+        validate_topic(&topic)?;
+        if message.message_id.is_empty() {
+            message.message_id = uuid::Uuid::new_v4().to_string();
+        }
+        let id = message.message_id.clone();
+        let capacity = self.inner.config.capacity;
+        let deadline = Instant::now() + timeout;
+        let mut state = self.inner.lock();
+        loop {
+            if state.closed {
+                return Err(AiosError::validation("Message bus is closed"));
+            }
+            let entry = state.topics.entry(topic.clone()).or_default();
+            let full = entry.queue.len() + entry.in_flight.len() >= capacity;
+            if entry.spilled == 0 && !full {
+                entry.queue.push_back(message);
+                entry.published += 1;
+                break;
+            }
+            if let Some(dir) = &self.inner.config.spill_dir {
+                // Behind already spilled messages, so order is kept
+                append_spilled(&spill_path(dir, &topic), std::slice::from_ref(&message))?;
+                entry.spilled += 1;
+                entry.published += 1;
+                break;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(AiosError::io(format!("Topic {} is full ({} messages)", topic, capacity)));
+            }
+            state = self.inner.changed.wait_timeout(state, deadline - now).unwrap_or_else(|e| e.into_inner()).0;
+        }
+        drop(state);
+        self.inner.changed.notify_all();
+        Ok(id)
+    }
+
+    /// Next message of `topic`, waiting up to `timeout`; None if there is none.
+    ///
+    /// The message must be acknowledged with `ack`, or it is delivered again
+    /// after `redeliver_after`.
+    pub fn receive(&self, topic: &str, timeout: Duration) -> Result<Option<Message>> {
+        validate_topic(topic)?;
+        let deadline = Instant::now() + timeout;
+        let redeliver_after = self.inner.config.redeliver_after;
+        let mut state = self.inner.lock();
+        loop {
+            if state.closed {
+                return Err(AiosError::validation("Message bus is closed"));
+            }
+            let now = Instant::now();
+            let mut next_due = None;
+            if let Some(entry) = state.topics.get_mut(topic) {
+                entry.requeue_expired(now);
+                if let Some(mut message) = entry.queue.pop_front() {
+                    if message.attempts > 0 {
+                        entry.redelivered += 1;
+                    }
+                    message.attempts += 1;
+                    entry.in_flight.push((message.clone(), now + redeliver_after));
+                    self.inner.refill(&mut state, topic)?;
+                    return Ok(Some(message));
+                }
+                next_due = entry.in_flight.iter().map(|(_, due)| *due).min();
+            }
+            if now >= deadline {
+                return Ok(None);
+            }
+            let wait = next_due.map_or(deadline - now, |due| (deadline - now).min(due.saturating_duration_since(now)));
+            state = self.inner.changed.wait_timeout(state, wait).unwrap_or_else(|e| e.into_inner()).0;
+        }
+    }
+
+    /// Mark an in-flight message as processed; false if it is not in flight
+    pub fn ack(&self, topic: &str, message_id: &str) -> Result<bool> {
+        let mut state = self.inner.lock();
+        let Some(entry) = state.topics.get_mut(topic) else {
+            return Ok(false);
+        };
+        let Some(position) = entry.in_flight.iter().position(|(message, _)| message.message_id == message_id) else {
+            return Ok(false);
+        };
+        entry.in_flight.remove(position);
+        entry.acked += 1;
+        self.inner.refill(&mut state, topic)?;
+        drop(state);
+        self.inner.changed.notify_all();
+        Ok(true)
+    }
+
+    /// Return an in-flight message to the front of its topic for immediate
+    /// redelivery; false if it is not in flight
+    pub fn nack(&self, topic: &str, message_id: &str) -> Result<bool> {
+        let mut state = self.inner.lock();
+        let Some(entry) = state.topics.get_mut(topic) else {
+            return Ok(false);
+        };
+        let Some(position) = entry.in_flight.iter().position(|(message, _)| message.message_id == message_id) else {
+            return Ok(false);
+        };
+        let (message, _) = entry.in_flight.remove(position);
+        entry.queue.push_front(message);
+        drop(state);
+        self.inner.changed.notify_all();
+        Ok(true)
+    }
+
+    /// Messages not yet acknowledged, in memory and spilled
+    pub fn pending(&self, topic: &str) -> usize {
+        let state = self.inner.lock();
+        state
+            .topics
+            .get(topic)
+            .map_or(0, |entry| entry.queue.len() + entry.in_flight.len() + entry.spilled)
+    }
+
+    /// Counters of every topic seen so far, sorted by name
+    pub fn stats(&self) -> Vec<TopicStats> {
+        let state = self.inner.lock();
+        state
+            .topics
+            .iter()
+            .map(|(name, entry)| TopicStats {
+                topic: name.clone(),
+                queued: entry.queue.len(),
+                in_flight: entry.in_flight.len(),
+                spilled: entry.spilled,
+                published: entry.published,
+                acked: entry.acked,
+                redelivered: entry.redelivered,
+                dropped: entry.dropped,
+            })
+            .collect()
+    }
+
+    /// Persist unfinished messages to the spill directory and reject further
+    /// use; waiting consumers and publishers fail. Idempotent.
+    pub fn close(&self) -> Result<()> {
+        let result = self.inner.close();
+        self.inner.changed.notify_all();
+        result
+    }
+}
+
+impl std::fmt::Debug for MessageBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MessageBus").field("config", &self.inner.config).finish()
+    }
+}
+
+impl Topic {
+    /// Move in-flight messages due for redelivery back to the front, oldest first
+    fn requeue_expired(&mut self, now: Instant) {
+        let mut expired = Vec::new();
+        self.in_flight.retain(|(message, due)| {
+            if *due <= now {
+                expired.push(message.clone());
+                false
+            } else {
+                true
+            }
+        });
+        for message in expired.into_iter().rev() {
+            self.queue.push_front(message);
+        }
+    }
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Move spilled messages of `topic` into the free part of its queue
+    fn refill(&self, state: &mut State, topic: &str) -> Result<()> {
+        let Some(dir) = &self.config.spill_dir else {
+            return Ok(());
+        };
+        let Some(entry) = state.topics.get_mut(topic) else {
+            return Ok(());
+        };
+        let used = entry.queue.len() + entry.in_flight.len();
+        // Refill in batches rather than per message to limit file rewrites
+        if entry.spilled == 0 || used > self.config.capacity / 2 {
+            return Ok(());
+        }
+        let (taken, remaining, dropped) = take_spilled(&spill_path(dir, topic), self.config.capacity - used)?;
+        entry.queue.extend(taken);
+        entry.spilled = remaining;
+        entry.dropped += dropped;
+        Ok(())
+    }
+
+    fn close(&self) -> Result<()> {
+        let mut state = self.lock();
+        if state.closed {
+            return Ok(());
+        }
+        state.closed = true;
+        let Some(dir) = &self.config.spill_dir else {
+            return Ok(());
+        };
+        let mut result = Ok(());
+        for (name, entry) in state.topics.iter_mut() {
+            let mut unfinished: Vec<Message> = entry.in_flight.drain(..).map(|(message, _)| message).collect();
+            unfinished.extend(entry.queue.drain(..));
+            if unfinished.is_empty() {
+                continue;
+            }
+            match prepend_spilled(&spill_path(dir, name), &unfinished) {
+                Ok(()) => entry.spilled += unfinished.len(),
+                Err(e) if result.is_ok() => result = Err(e.context(format!("Failed to persist topic {}", name))),
+                Err(_) => {}
+            }
+        }
+        let _ = fs::remove_file(dir.join(LOCK_FILE));
+        result
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+/// Topics name spill files: 1-64 ASCII letters, digits, `_`, `-` or `.`,
+/// not starting with `.`
+pub fn validate_topic(topic: &str) -> Result<()> {
+    let valid = !topic.is_empty()
+        && topic.len() <= MAX_TOPIC_LEN
+        && !topic.starts_with('.')
+        && topic.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid {
+        return Err(AiosError::validation(format!(
+            "Invalid topic {:?}: use 1-{} letters, digits, '_', '-' or '.'",
+            topic, MAX_TOPIC_LEN
+        )));
+    }
+    Ok(())
+}
+
+fn spill_path(dir: &Path, topic: &str) -> PathBuf {
+    dir.join(format!("{}.jsonl", topic))
+}
+
+/// Take the spill directory's lock file, replacing it if its process is gone
+fn acquire_lock(dir: &Path) -> Result<()> {
+    let path = dir.join(LOCK_FILE);
+    for _ in 0..2 {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                writeln!(file, "{}", std::process::id())?;
+                return Ok(());
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let owner = fs::read_to_string(&path).ok().and_then(|pid| pid.trim().parse::<u32>().ok());
+                if let Some(pid) = owner.filter(|pid| process_alive(*pid)) {
+                    return Err(AiosError::config(format!(
+                        "Spill directory {} is in use by process {}",
+                        dir.display(),
+                        pid
+                    )));
+                }
+                fs::remove_file(&path)?;
+            }
+            Err(e) => return Err(AiosError::from(e).context(format!("Failed to lock {}", dir.display()))),
+        }
+    }
+    Err(AiosError::config(format!("Failed to lock spill directory {}", dir.display())))
+}
+
+/// Whether `pid` is running; assumed not where `/proc` is unavailable
+fn process_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// Parsed messages of a spill file and the number of unreadable lines
+fn read_spilled(path: &Path) -> Result<(Vec<Message>, u64)> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => return Err(AiosError::from(e).context(format!("Failed to open {}", path.display()))),
+    };
+    let mut messages = Vec::new();
+    let mut dropped = 0;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(message) => messages.push(message),
+            Err(_) => dropped += 1,
+        }
+    }
+    Ok((messages, dropped))
+}
+
+fn count_spilled(path: &Path) -> Result<(usize, u64)> {
+    let (messages, dropped) = read_spilled(path)?;
+    Ok((messages.len(), dropped))
+}
+
+fn append_spilled(path: &Path, messages: &[Message]) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| AiosError::from(e).context(format!("Failed to open {}", path.display())))?;
+    let mut writer = BufWriter::new(file);
+    for message in messages {
+        serde_json::to_writer(&mut writer, message)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Replace the spill file with `messages`, through a temporary file so a
+/// crash leaves either the old or the new contents
+fn rewrite_spilled(path: &Path, messages: &[Message]) -> Result<()> {
+    if messages.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    }
+    let temporary = path.with_extension("jsonl.tmp");
+    let _ = fs::remove_file(&temporary);
+    append_spilled(&temporary, messages)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Remove up to `count` messages from the front of a spill file; returns
+/// them, the number left and the number of unreadable lines skipped
+fn take_spilled(path: &Path, count: usize) -> Result<(Vec<Message>, usize, u64)> {
+    let (mut messages, dropped) = read_spilled(path)?;
+    let remaining = messages.split_off(count.min(messages.len()));
+    rewrite_spilled(path, &remaining)?;
+    Ok((messages, remaining.len(), dropped))
+}
+
+fn prepend_spilled(path: &Path, messages: &[Message]) -> Result<()> {
+    let (existing, _) = read_spilled(path)?;
+    let mut all = messages.to_vec();
+    all.extend(existing);
+    rewrite_spilled(path, &all)
+}
+
+/// Define the `MessageBus` class in the calling crate, against its own `pyo3`.
+///
+/// Expand it inside a module, e.g. `mod bus { aios_bus::python_bus!(); }`,
+/// next to the `errors` module from `aios_errors::python_exceptions!`, and
+/// call `bus::register(m)` from the `#[pymodule]` function.
+// `crate::errors` deliberately names the calling crate's exceptions
+#[allow(clippy::crate_in_macro_def)]
+#[macro_export]
+macro_rules! python_bus {
+    () => {
+        // `#[new]` under PyO3 0.20 expands to impls that newer rustc flags
+        mod class {
+            #![allow(unknown_lints, non_local_definitions)]
+
+            use ::std::collections::HashMap;
+            use ::std::time::Duration;
+
+            use ::pyo3::prelude::*;
+
+            use crate::errors::{to_pyerr, validation};
+
+            /// Message bus between the AIOS cores.
+            ///
+            /// Topics are target core names. Hand the same bus object to every
+            /// core that should exchange messages; buses of different modules
+            /// have the same methods. Received messages must be acknowledged
+            /// with `ack`, or they are delivered again after `redeliver_after`
+            /// seconds. With `spill_dir`, full topics overflow to disk and
+            /// unfinished messages survive `close()` and restarts.
+            #[pyclass(name = "MessageBus")]
+            pub struct MessageBus {
+                bus: $crate::MessageBus,
+            }
+
+            fn seconds(name: &str, value: f64) -> PyResult<Duration> {
+                if !value.is_finite() || value < 0.0 {
+                    return Err(validation(format!("{} must be a non-negative number of seconds, got {}", name, value)));
+                }
+                Ok(Duration::from_secs_f64(value))
+            }
+
+            fn string_field(message: &HashMap<String, PyObject>, py: Python<'_>, name: &str) -> PyResult<Option<String>> {
+                match message.get(name) {
+                    None => Ok(None),
+                    Some(value) if value.is_none(py) => Ok(None),
+                    Some(value) => value
+                        .extract::<String>(py)
+                        .map(Some)
+                        .map_err(|_| validation(format!("Message field {} must be a str", name))),
+                }
+            }
+
+            #[allow(deprecated)]
+            fn to_dict(py: Python<'_>, message: $crate::Message) -> PyResult<PyObject> {
+                let dict = ::pyo3::types::PyDict::new(py);
+                dict.set_item("message_id", message.message_id)?;
+                dict.set_item("source_core", message.source_core)?;
+                dict.set_item("target_core", message.target_core)?;
+                dict.set_item("message_type", message.message_type)?;
+                dict.set_item("payload", message.payload)?;
+                dict.set_item("timestamp", message.timestamp)?;
+                dict.set_item("attempts", message.attempts)?;
+                dict.set_item("status", "delivered")?;
+                Ok(dict.to_object(py))
+            }
+
+            #[pymethods]
+            impl MessageBus {
+                #[new]
+                #[pyo3(signature = (spill_dir=None, capacity=1024, redeliver_after=30.0))]
+                fn new(spill_dir: Option<String>, capacity: usize, redeliver_after: f64) -> PyResult<Self> {
+                    let config = $crate::BusConfig {
+                        capacity,
+                        spill_dir: spill_dir.map(Into::into),
+                        redeliver_after: seconds("redeliver_after", redeliver_after)?,
+                    };
+                    Ok(Self { bus: $crate::MessageBus::open(config).map_err(to_pyerr)? })
+                }
+
+                /// Publish to `target_core`'s topic; returns the message id.
+                ///
+                /// Without `spill_dir`, a full topic is waited on for up to
+                /// `timeout` seconds before IoError is raised.
+                #[pyo3(signature = (target_core, message_type, payload, source_core="python", timeout=0.0))]
+                fn publish(
+                    &self,
+                    py: Python<'_>,
+                    target_core: &str,
+                    message_type: &str,
+                    payload: String,
+                    source_core: &str,
+                    timeout: f64,
+                ) -> PyResult<String> {
+                    let message = $crate::Message::new(source_core, target_core, message_type, payload);
+                    let timeout = seconds("timeout", timeout)?;
+                    py.allow_threads(|| self.bus.publish(message, timeout)).map_err(to_pyerr)
+                }
+
+                /// Publish a dict from `create_core_message`, keeping its id
+                #[pyo3(signature = (message, timeout=0.0))]
+                fn publish_message(&self, py: Python<'_>, message: HashMap<String, PyObject>, timeout: f64) -> PyResult<String> {
+                    let required = |name: &str| {
+                        string_field(&message, py, name)?.ok_or_else(|| validation(format!("Message is missing {}", name)))
+                    };
+                    let mut built = $crate::Message::new(
+                        &required("source_core")?,
+                        &required("target_core")?,
+                        &required("message_type")?,
+                        required("payload")?,
+                    );
+                    if let Some(id) = string_field(&message, py, "message_id")? {
+                        built.message_id = id;
+                    }
+                    if let Some(timestamp) = message.get("timestamp").and_then(|value| value.extract::<f64>(py).ok()) {
+                        built.timestamp = timestamp;
+                    }
+                    let timeout = seconds("timeout", timeout)?;
+                    py.allow_threads(|| self.bus.publish(built, timeout)).map_err(to_pyerr)
+                }
+
+                /// Next message of `topic` as a dict, waiting up to `timeout`
+                /// seconds; None if there is none
+                #[pyo3(signature = (topic, timeout=0.0))]
+                fn receive(&self, py: Python<'_>, topic: &str, timeout: f64) -> PyResult<Option<PyObject>> {
+                    let timeout = seconds("timeout", timeout)?;
+                    let message = py.allow_threads(|| self.bus.receive(topic, timeout)).map_err(to_pyerr)?;
+                    message.map(|message| to_dict(py, message)).transpose()
+                }
+
+                /// Mark a received message as processed; False if it is not in flight
+                fn ack(&self, topic: &str, message_id: &str) -> PyResult<bool> {
+                    self.bus.ack(topic, message_id).map_err(to_pyerr)
+                }
+
+                /// Put a received message back for immediate redelivery
+                fn nack(&self, topic: &str, message_id: &str) -> PyResult<bool> {
+                    self.bus.nack(topic, message_id).map_err(to_pyerr)
+                }
+
+                /// Messages of `topic` not yet acknowledged
+                fn pending(&self, topic: &str) -> usize {
+                    self.bus.pending(topic)
+                }
+
+                /// `{topic: {"queued", "in_flight", "spilled", "published", "acked", "redelivered", "dropped"}}`
+                #[allow(deprecated)]
+                fn stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+                    let result = ::pyo3::types::PyDict::new(py);
+                    for stats in self.bus.stats() {
+                        let entry = ::pyo3::types::PyDict::new(py);
+                        entry.set_item("queued", stats.queued)?;
+                        entry.set_item("in_flight", stats.in_flight)?;
+                        entry.set_item("spilled", stats.spilled)?;
+                        entry.set_item("published", stats.published)?;
+                        entry.set_item("acked", stats.acked)?;
+                        entry.set_item("redelivered", stats.redelivered)?;
+                        entry.set_item("dropped", stats.dropped)?;
+                        result.set_item(stats.topic, entry)?;
+                    }
+                    Ok(result.to_object(py))
+                }
+
+                /// Persist unfinished messages to `spill_dir` and stop the bus
+                fn close(&self, py: Python<'_>) -> PyResult<()> {
+                    py.allow_threads(|| self.bus.close()).map_err(to_pyerr)
+                }
+
+                fn __repr__(&self) -> String {
+                    let config = self.bus.config();
+                    format!(
+                        "MessageBus(spill_dir={}, capacity={}, redeliver_after={})",
+                        config.spill_dir.as_ref().map_or("None".to_string(), |dir| format!("'{}'", dir.display())),
+                        config.capacity,
+                        config.redeliver_after.as_secs_f64()
+                    )
+                }
+            }
+        }
+
+        pub use class::MessageBus;
+
+        /// Add the `MessageBus` class to the Python module
+        #[allow(deprecated)]
+        pub fn register(m: &::pyo3::types::PyModule) -> ::pyo3::PyResult<()> {
+            m.add_class::<MessageBus>()
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spill_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("aios_bus_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn config(dir: &Path) -> BusConfig {
+        BusConfig { capacity: 4, spill_dir: Some(dir.to_path_buf()), redeliver_after: Duration::from_secs(30) }
+    }
+
+    fn publish_all(bus: &MessageBus, range: std::ops::Range<usize>) {
+        for i in range {
+            bus.publish(Message::new("test", "luna", "note", i.to_string()), Duration::ZERO).unwrap();
+        }
+    }
+
+    fn drain(bus: &MessageBus) -> Vec<String> {
+        let mut payloads = Vec::new();
+        while let Some(message) = bus.receive("luna", Duration::ZERO).unwrap() {
+            assert!(bus.ack("luna", &message.message_id).unwrap());
+            payloads.push(message.payload);
+        }
+        payloads
+    }
+
+    #[test]
+    fn test_spill_and_replay() {
+        let dir = spill_dir("replay");
+        let bus = MessageBus::open(config(&dir)).unwrap();
+        publish_all(&bus, 0..10);
+        let stats = &bus.stats()[0];
+        assert_eq!((stats.queued, stats.spilled), (4, 6));
+
+        // One acknowledged, one left in flight, the rest queued or spilled
+        let first = bus.receive("luna", Duration::ZERO).unwrap().unwrap();
+        assert!(bus.ack("luna", &first.message_id).unwrap());
+        let second = bus.receive("luna", Duration::ZERO).unwrap().unwrap();
+        assert_eq!((first.payload.as_str(), second.payload.as_str()), ("0", "1"));
+        publish_all(&bus, 10..12);
+        drop(bus);
+        assert!(!dir.join(LOCK_FILE).exists());
+
+        let bus = MessageBus::open(config(&dir)).unwrap();
+        assert_eq!(bus.pending("luna"), 11);
+        let expected: Vec<String> = (1..12).map(|i| i.to_string()).collect();
+        assert_eq!(drain(&bus), expected);
+        assert_eq!(bus.pending("luna"), 0);
+        drop(bus);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_full_without_spill() {
+        let bus = MessageBus::open(BusConfig { capacity: 2, ..BusConfig::default() }).unwrap();
+        publish_all(&bus, 0..2);
+        let error = bus.publish(Message::new("test", "luna", "note", "2"), Duration::from_millis(10)).unwrap_err();
+        assert!(error.to_string().contains("is full"), "{}", error);
+        assert_eq!(drain(&bus), ["0", "1"]);
+    }
+
+    #[test]
+    fn test_lock() {
+        let dir = spill_dir("lock");
+        let bus = MessageBus::open(config(&dir)).unwrap();
+        assert!(MessageBus::open(config(&dir)).is_err());
+        bus.close().unwrap();
+        assert!(bus.publish(Message::new("test", "luna", "note", ""), Duration::ZERO).is_err());
+        MessageBus::open(config(&dir)).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
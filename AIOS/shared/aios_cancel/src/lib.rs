@@ -0,0 +1,292 @@
+//! Cooperative cancellation for long-running AIOS operations
+//!
+//! A `CancellationToken` is a shared flag that long loops (exports, backups,
+//! dream cycles, clustering) check between units of work. Cancelling never
+//! interrupts a step in progress; the operation stops at its next check.
+//!
+//! Like `aios-errors`, this crate does not depend on PyO3: each extension
+//! module expands `python_cancellation!` to get its own `CancellationToken`
+//! class. Long-running methods take a `cancel_token=None` argument and
+//! accept any core's token, or any object with an `is_cancelled()` or
+//! `is_set()` method (e.g. `threading.Event`), which is polled with the GIL.
+//!
+//! Cancellation is reported consistently: operations returning a result
+//! record (`ExportResult`, `BackupResult`, `DreamCycleResult`) return it with
+//! `cancelled=True` / `status="cancelled"` and the progress made so far;
+//! other operations raise the module's `CancelledError`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use aios_errors::{AiosError, Result};
+
+/// How often a foreign token (checked through Python) is polled at most
+pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Longest uninterrupted slice of `CancellationToken::sleep`
+const SLEEP_SLICE: Duration = Duration::from_millis(20);
+
+type Check = Box<dyn Fn() -> bool + Send + Sync>;
+
+/// External cancellation source, polled at most every `interval`
+struct Poll {
+    check: Check,
+    interval: Duration,
+    last: Mutex<Option<Instant>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    poll: Option<Poll>,
+}
+
+/// Shared cancellation flag; clones observe the same state
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+impl CancellationToken {
+    /// A token that is cancelled only through `cancel`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that is also cancelled once `check` returns true; `check` is
+    /// called at most every `interval`
+    pub fn polling(check: impl Fn() -> bool + Send + Sync + 'static, interval: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                cancelled: AtomicBool::new(false),
+                poll: Some(Poll { check: Box::new(check), interval, last: Mutex::new(None) }),
+            }),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        if self.inner.cancelled.load(Ordering::Acquire) {
+            return true;
+        }
+        let Some(poll) = &self.inner.poll else {
+            return false;
+        };
+        {
+            let mut last = poll.last.lock().unwrap_or_else(|e| e.into_inner());
+            if last.is_some_and(|at| at.elapsed() < poll.interval) {
+                return false;
+            }
+            *last = Some(Instant::now());
+        }
+        if (poll.check)() {
+            self.cancel();
+            return true;
+        }
+        false
+    }
+
+    /// `Err` with kind `Cancelled` once cancelled
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(AiosError::cancelled("Operation was cancelled"));
+        }
+        Ok(())
+    }
+
+    /// Sleep for `duration`, waking early on cancellation; returns false if cancelled
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        loop {
+            if self.is_cancelled() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            std::thread::sleep((deadline - now).min(SLEEP_SLICE));
+        }
+    }
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.inner.cancelled.load(Ordering::Acquire))
+            .finish()
+    }
+}
+
+/// Define the `CancellationToken` class and the `token` argument converter in
+/// the calling crate, against its own `pyo3`.
+///
+/// Expand it inside a module, e.g. `mod cancel { aios_cancel::python_cancellation!(); }`,
+/// call `cancel::register(m)` from the `#[pymodule]` function, and convert a
+/// method's `cancel_token: Option<PyObject>` with `cancel::token(py, cancel_token)?`.
+#[macro_export]
+macro_rules! python_cancellation {
+    () => {
+        // `#[new]` under PyO3 0.20 expands to impls that newer rustc flags
+        mod class {
+            #![allow(unknown_lints, non_local_definitions)]
+
+            /// Cooperative cancellation flag for long-running operations.
+            ///
+            /// Pass it as `cancel_token` and call `cancel()` from another thread or
+            /// task; the operation stops at its next checkpoint. Tokens from any
+            /// AIOS core are accepted by every core.
+            #[::pyo3::pyclass(name = "CancellationToken")]
+            #[derive(Clone)]
+            pub struct CancellationToken {
+                pub(super) token: $crate::CancellationToken,
+            }
+
+            #[::pyo3::pymethods]
+            impl CancellationToken {
+                #[new]
+                fn new() -> Self {
+                    Self { token: $crate::CancellationToken::new() }
+                }
+
+                /// Request cancellation; cannot be undone
+                fn cancel(&self) {
+                    self.token.cancel();
+                }
+
+                fn is_cancelled(&self) -> bool {
+                    self.token.is_cancelled()
+                }
+
+                fn __repr__(&self) -> String {
+                    format!("CancellationToken(cancelled={})", if self.token.is_cancelled() { "True" } else { "False" })
+                }
+            }
+        }
+
+        pub use class::CancellationToken;
+
+        /// Convert a `cancel_token` argument: None, this module's token, or any
+        /// object with `is_cancelled()` / `is_set()`
+        #[allow(dead_code, deprecated)]
+        pub fn token(py: ::pyo3::Python<'_>, token: Option<::pyo3::PyObject>) -> ::pyo3::PyResult<$crate::CancellationToken> {
+            let Some(token) = token else {
+                return Ok($crate::CancellationToken::new());
+            };
+            if let Ok(own) = token.extract::<CancellationToken>(py) {
+                return Ok(own.token);
+            }
+            let method = ["is_cancelled", "is_set"]
+                .into_iter()
+                .find(|name| token.getattr(py, *name).is_ok())
+                .ok_or_else(|| {
+                    ::pyo3::exceptions::PyTypeError::new_err(
+                        "cancel_token must be a CancellationToken or have an is_cancelled() or is_set() method",
+                    )
+                })?;
+            Ok($crate::CancellationToken::polling(
+                move || {
+                    ::pyo3::Python::with_gil(|py| {
+                        // A failing check counts as not cancelled
+                        token
+                            .call_method0(py, method)
+                            .and_then(|cancelled| cancelled.extract::<bool>(py))
+                            .unwrap_or(false)
+                    })
+                },
+                $crate::POLL_INTERVAL,
+            ))
+        }
+
+        /// Add the `CancellationToken` class to the Python module
+        #[allow(deprecated)]
+        pub fn register(m: &::pyo3::types::PyModule) -> ::pyo3::PyResult<()> {
+            m.add_class::<CancellationToken>()
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    use aios_errors::ErrorKind;
+
+    #[test]
+    fn test_cancel_reaches_clones_and_threads() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        assert!(clone.check().is_ok());
+
+        let worker = {
+            let token = token.clone();
+            std::thread::spawn(move || {
+                let mut steps = 0;
+                while token.check().is_ok() {
+                    steps += 1;
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                (steps, token.check().unwrap_err().kind())
+            })
+        };
+        std::thread::sleep(Duration::from_millis(20));
+        clone.cancel();
+        let (steps, kind) = worker.join().unwrap();
+        assert!(steps > 0);
+        assert_eq!(kind, ErrorKind::Cancelled);
+        assert!(token.is_cancelled());
+        assert!(format!("{:?}", token).contains("cancelled: true"));
+    }
+
+    #[test]
+    fn test_sleep_wakes_on_cancel() {
+        let token = CancellationToken::new();
+        assert!(token.sleep(Duration::from_millis(5)));
+
+        let canceller = token.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(30));
+            canceller.cancel();
+        });
+        let start = Instant::now();
+        assert!(!token.sleep(Duration::from_secs(10)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_polling_checks_at_most_every_interval() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let source = Arc::new(AtomicBool::new(false));
+        let token = {
+            let (calls, source) = (calls.clone(), source.clone());
+            CancellationToken::polling(
+                move || {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    source.load(Ordering::SeqCst)
+                },
+                Duration::from_secs(60),
+            )
+        };
+        for _ in 0..10 {
+            assert!(!token.is_cancelled());
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Cancelling directly needs no poll
+        source.store(true, Ordering::SeqCst);
+        token.clone().cancel();
+        assert!(token.is_cancelled());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Once the source reports it, the token stays cancelled
+        let token = CancellationToken::polling(|| true, Duration::ZERO);
+        assert!(token.is_cancelled());
+        assert!(token.clone().check().is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! BM25 keyword index over fragment content
+//!
+//! Tokens are lowercase runs of alphanumerics and underscores, so identifiers
+//! like `carma_core` or `RustCarmaCore` match as whole words.
+
+use std::collections::HashMap;
+
+/// Inverted index with Okapi BM25 scoring, doc ids are positions in the
+/// order documents were added (fragment positions, vector index nodes)
+#[derive(Debug, Clone)]
+pub struct Bm25Index {
+    k1: f32,
+    b: f32,
+    /// term -> (doc, term frequency)
+    postings: HashMap<String, Vec<(usize, u32)>>,
+    /// Token count of each document, None once removed
+    doc_lens: Vec<Option<u32>>,
+    /// Documents not removed
+    live: usize,
+    total_len: u64,
+}
+
+impl Bm25Index {
+    pub fn new(k1: f32, b: f32) -> Self {
+        Self {
+            k1,
+            b,
+            postings: HashMap::new(),
+            doc_lens: Vec::new(),
+            live: 0,
+            total_len: 0,
+        }
+    }
+
+    /// Index the next document; it gets doc id `len()`
+    pub fn add(&mut self, content: &str) {
+        self.doc_lens.push(None);
+        self.index(self.doc_lens.len() - 1, content);
+    }
+
+    /// Re-index document `doc`, indexed with content `old`, as `new`
+    pub fn replace(&mut self, doc: usize, old: &str, new: &str) {
+        self.remove(doc, old);
+        self.index(doc, new);
+    }
+
+    /// Drop document `doc`, indexed with content `content`, from scores and
+    /// statistics; its doc id is not reused
+    pub fn remove(&mut self, doc: usize, content: &str) {
+        let Some(len) = self.doc_lens[doc].take() else {
+            return;
+        };
+        let mut terms = tokenize(content);
+        terms.sort();
+        terms.dedup();
+        for term in terms {
+            if let Some(postings) = self.postings.get_mut(&term) {
+                postings.retain(|&(d, _)| d != doc);
+                if postings.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+        self.total_len -= len as u64;
+        self.live -= 1;
+    }
+
+    /// Index `content` under `doc`, which must not be indexed
+    fn index(&mut self, doc: usize, content: &str) {
+        let tokens = tokenize(content);
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for token in &tokens {
+            *counts.entry(token.clone()).or_insert(0) += 1;
+        }
+        for (term, tf) in counts {
+            self.postings.entry(term).or_default().push((doc, tf));
+        }
+        self.doc_lens[doc] = Some(tokens.len() as u32);
+        self.total_len += tokens.len() as u64;
+        self.live += 1;
+    }
+
+    /// Re-index from scratch, keeping the scoring parameters
+    pub fn rebuild<'a>(&mut self, contents: impl Iterator<Item = &'a str>) {
+        self.clear();
+        for content in contents {
+            self.add(content);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.postings.clear();
+        self.doc_lens.clear();
+        self.live = 0;
+        self.total_len = 0;
+    }
+
+    pub fn vocabulary_size(&self) -> usize {
+        self.postings.len()
+    }
+
+    /// BM25 score of every document matching at least one query term
+    pub fn scores(&self, query: &str) -> HashMap<usize, f32> {
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        let n = self.live as f32;
+        if n == 0.0 {
+            return scores;
+        }
+
+        let avg_len = (self.total_len as f32 / n).max(1.0);
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+        for term in terms {
+            let postings = match self.postings.get(&term) {
+                Some(postings) => postings,
+                None => continue,
+            };
+            let df = postings.len() as f32;
+            let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+            for &(doc, tf) in postings {
+                let tf = tf as f32;
+                let len_norm = 1.0 - self.b + self.b * self.doc_lens[doc].unwrap_or(0) as f32 / avg_len;
+                *scores.entry(doc).or_insert(0.0) += idf * tf * (self.k1 + 1.0) / (tf + self.k1 * len_norm);
+            }
+        }
+        scores
+    }
+}
+
+/// Lowercase alphanumeric/underscore tokens
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_matches_rebuild() {
+        let mut contents = ["carma core memory", "dream cycle", "memory memory fragments"];
+        let mut index = Bm25Index::new(1.2, 0.75);
+        index.rebuild(contents.iter().copied());
+        index.replace(2, contents[2], "longer dream of carma_core fragments");
+        contents[2] = "longer dream of carma_core fragments";
+        let mut rebuilt = Bm25Index::new(1.2, 0.75);
+        rebuilt.rebuild(contents.iter().copied());
+        for query in ["memory", "dream carma_core", "fragments cycle"] {
+            assert_eq!(index.scores(query), rebuilt.scores(query), "{}", query);
+        }
+        assert_eq!(index.vocabulary_size(), rebuilt.vocabulary_size());
+    }
+
+    #[test]
+    fn test_remove_matches_rebuild_without() {
+        let contents = ["carma core memory", "dream cycle memory", "memory memory fragments"];
+        let mut index = Bm25Index::new(1.2, 0.75);
+        index.rebuild(contents.iter().copied());
+        index.remove(1, contents[1]);
+        index.remove(1, contents[1]);
+        let mut rebuilt = Bm25Index::new(1.2, 0.75);
+        rebuilt.rebuild([contents[0], contents[2]].into_iter());
+        for query in ["memory", "dream cycle", "fragments carma"] {
+            let scores = index.scores(query);
+            assert!(!scores.contains_key(&1), "{}", query);
+            let expected: HashMap<usize, f32> =
+                rebuilt.scores(query).into_iter().map(|(doc, score)| (if doc == 1 { 2 } else { doc }, score)).collect();
+            assert_eq!(scores, expected, "{}", query);
+        }
+        assert_eq!(index.vocabulary_size(), rebuilt.vocabulary_size());
+
+        // A removed document can be indexed again under its id
+        index.replace(1, "", contents[1]);
+        let mut full = Bm25Index::new(1.2, 0.75);
+        full.rebuild(contents.iter().copied());
+        assert_eq!(index.scores("dream memory"), full.scores("dream memory"));
+    }
+}
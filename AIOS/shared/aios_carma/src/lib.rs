@@ -0,0 +1,9 @@
+//! PyO3-free parts of rust_carma
+//!
+//! The snapshot file format and the BM25 keyword index live here so tools
+//! that cannot load the extension module (the `aios-rs` CLI, `aios-server`)
+//! can read `RustCarmaCore.save` snapshots and score them the same way. The
+//! support core's vector index scores metadata with the same BM25 index.
+
+pub mod keyword;
+pub mod snapshot;
@@ -0,0 +1,132 @@
+//! On-disk snapshot format of `RustCarmaCore.save`
+//!
+//! Layout (all integers little-endian):
+//!
+//! ```text
+//! magic "CARMAFRG" | version u32 | reserved u32 | header_len u64 | header JSON
+//! | zero padding to a 64-byte boundary | embeddings as contiguous f32
+//! ```
+//!
+//! Embeddings are stored back to back in fragment order, so the block can be
+//! memory-mapped and sliced with the per-fragment `dim` from the header.
+//! Readers accept any version up to `FORMAT_VERSION`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+pub const FORMAT_VERSION: u32 = 1;
+const MAGIC: &[u8; 8] = b"CARMAFRG";
+const ALIGN: usize = 64;
+const PREAMBLE_LEN: usize = 8 + 4 + 4 + 8;
+
+/// Fragment fields stored in the JSON header (embedding lives in the binary block)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FragmentRecord {
+    pub id: String,
+    pub content: String,
+    pub timestamp: f64,
+    pub metadata: HashMap<String, String>,
+    pub dim: u32,
+}
+
+/// Snapshot header
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    pub version: u32,
+    pub fragments: Vec<FragmentRecord>,
+    /// Cluster id -> fragment ids
+    pub clusters: HashMap<i32, Vec<String>>,
+    pub centroids: Vec<Vec<f32>>,
+    pub total_queries: u64,
+}
+
+/// Write the header and one embedding per header fragment to `path`
+/// atomically (temp file + rename)
+pub fn write_snapshot(path: &Path, header: &SnapshotHeader, embeddings: &[&[f32]]) -> io::Result<()> {
+    let buffer = encode_snapshot(header, embeddings)?;
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(&buffer)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)
+}
+
+/// The snapshot file contents for the header and embeddings
+pub fn encode_snapshot(header: &SnapshotHeader, embeddings: &[&[f32]]) -> io::Result<Vec<u8>> {
+    let header_json = serde_json::to_vec(header)?;
+    let unpadded = PREAMBLE_LEN + header_json.len();
+    let padding = (ALIGN - unpadded % ALIGN) % ALIGN;
+
+    let total_floats: usize = embeddings.iter().map(|embedding| embedding.len()).sum();
+    let mut buffer = Vec::with_capacity(unpadded + padding + total_floats * 4);
+    buffer.extend_from_slice(MAGIC);
+    buffer.extend_from_slice(&header.version.to_le_bytes());
+    buffer.extend_from_slice(&0u32.to_le_bytes());
+    buffer.extend_from_slice(&(header_json.len() as u64).to_le_bytes());
+    buffer.extend_from_slice(&header_json);
+    buffer.resize(unpadded + padding, 0);
+    for embedding in embeddings {
+        for value in *embedding {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    Ok(buffer)
+}
+
+/// Read a snapshot, returning the header and the embedding of each fragment
+pub fn read_snapshot(path: &Path) -> io::Result<(SnapshotHeader, Vec<Vec<f32>>)> {
+    decode_snapshot(&fs::read(path)?)
+}
+
+/// Parse snapshot file contents into the header and one embedding per fragment
+pub fn decode_snapshot(data: &[u8]) -> io::Result<(SnapshotHeader, Vec<Vec<f32>>)> {
+    if data.len() < PREAMBLE_LEN || &data[..8] != MAGIC {
+        return Err(invalid("not a CARMA snapshot"));
+    }
+
+    let version = u32::from_le_bytes(data[8..12].try_into().unwrap());
+    if version > FORMAT_VERSION {
+        return Err(invalid(&format!(
+            "snapshot version {} is newer than supported version {}",
+            version, FORMAT_VERSION
+        )));
+    }
+
+    let header_len = u64::from_le_bytes(data[16..24].try_into().unwrap()) as usize;
+    let header_end = PREAMBLE_LEN
+        .checked_add(header_len)
+        .filter(|&end| end <= data.len())
+        .ok_or_else(|| invalid("truncated snapshot header"))?;
+    let header: SnapshotHeader = serde_json::from_slice(&data[PREAMBLE_LEN..header_end])?;
+
+    let mut offset = header_end + (ALIGN - header_end % ALIGN) % ALIGN;
+    let mut embeddings = Vec::with_capacity(header.fragments.len());
+    for record in &header.fragments {
+        let byte_len = record.dim as usize * 4;
+        let block = data
+            .get(offset..offset + byte_len)
+            .ok_or_else(|| invalid("truncated embedding block"))?;
+        let embedding = block
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        offset += byte_len;
+        embeddings.push(embedding);
+    }
+
+    Ok((header, embeddings))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
@@ -0,0 +1,504 @@
+//! Cross-core configuration loaded from one `aios.toml`
+//!
+//! Each core reads its own section; missing sections and keys fall back to
+//! the defaults below, which match the values previously hard-coded in the
+//! cores. Unknown keys inside a known section are rejected so typos surface
+//! as a `ConfigError` instead of being ignored.
+//!
+//! ```toml
+//! [support]
+//! memory_warning_percent = 75.0
+//!
+//! [arbiter.grade_bonus]
+//! A = 0.25
+//!
+//! [backup]
+//! roots = ["carma_core", "luna_core"]
+//!
+//! [store]
+//! path = "data/aios_state.db"
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use aios_errors::{AiosError, Result};
+
+mod toml;
+
+/// Environment variable naming the config file
+pub const CONFIG_ENV: &str = "AIOS_CONFIG";
+/// File looked up in the working directory when no path is given
+pub const CONFIG_FILE: &str = "aios.toml";
+
+/// All per-core sections
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiosConfig {
+    pub support: SupportConfig,
+    pub dream: DreamConfig,
+    pub arbiter: ArbiterConfig,
+    pub backup: BackupConfig,
+    pub server: ServerConfig,
+    pub store: StoreConfig,
+}
+
+/// Health check thresholds for the support core
+///
+/// Usage above a warning level makes its check WARNING, above the matching
+/// critical (or error) level CRITICAL.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SupportConfig {
+    pub memory_warning_percent: f64,
+    pub memory_critical_percent: f64,
+    /// Usage of the disk holding the cache directory
+    pub disk_warning_percent: f64,
+    pub disk_critical_percent: f64,
+    pub cpu_warning_percent: f64,
+    pub cpu_error_percent: f64,
+    pub process_warning_count: usize,
+    pub process_error_count: usize,
+    /// VRAM usage of the fullest GPU
+    pub gpu_vram_warning_percent: f64,
+    pub gpu_vram_critical_percent: f64,
+    /// Temperature of the hottest GPU, in °C
+    pub gpu_temperature_warning_c: f64,
+    pub gpu_temperature_critical_c: f64,
+    /// CPU package temperature (the hottest sensor when none is labelled as
+    /// the CPU), in °C
+    pub cpu_temperature_warning_c: f64,
+    pub cpu_temperature_critical_c: f64,
+    /// Processes watched from the start, by name (matched against the
+    /// executable name and command line) or PID
+    pub watched_processes: Vec<String>,
+    /// A watched process above either limit is runaway; CPU is a share of
+    /// all cores
+    pub process_runaway_cpu_percent: f64,
+    pub process_runaway_memory_mb: f64,
+    /// Python modules the dependency check imports, by import name
+    pub python_packages: Vec<String>,
+    /// Move cache files failing the integrity check to the cache's
+    /// quarantine directory
+    pub cache_quarantine: bool,
+    /// Base URL of the local OpenAI-compatible LLM server, e.g. LM Studio's
+    /// "http://localhost:1234"; empty skips the LLM check
+    pub llm_endpoint: String,
+    /// Model that must be listed by `/v1/models`; empty accepts any
+    pub llm_model: String,
+    pub llm_timeout_ms: u64,
+    /// An unavailable LLM endpoint is critical instead of a warning
+    pub llm_required: bool,
+    /// Endpoints probed by the network check: "host:port" for a TCP
+    /// connect, or an http:// URL for a HEAD request
+    pub network_endpoints: Vec<String>,
+    /// Endpoints (probed too) whose failure fails the network check; others
+    /// only warn
+    pub network_required: Vec<String>,
+    /// Timeout of each probe
+    pub network_timeout_ms: u64,
+    /// Probe latencies kept per endpoint for the latency metrics; 0 keeps
+    /// none
+    pub network_latency_window: usize,
+    /// Minutes between scheduled quick health checks; 0 turns them off
+    pub schedule_quick_minutes: u64,
+    /// Five-field cron expression (local time) for scheduled full health
+    /// checks, e.g. "0 */6 * * *"; empty turns them off
+    pub schedule_full_cron: String,
+    /// Directory the log check watches; empty skips the check
+    pub log_dir: String,
+    /// Total size of the log directory above which the check warns; 0
+    /// turns the quota off
+    pub log_quota_mb: f64,
+    /// Minutes without any log file being written after which the check
+    /// warns that the logger may have died; 0 turns this off
+    pub log_stale_minutes: u64,
+    /// Seconds a component may go without a heartbeat before it counts as
+    /// stalled, unless its heartbeats give their own interval
+    pub heartbeat_interval_secs: u64,
+    /// Performance metrics samples kept for `get_metrics_window`; 0 keeps
+    /// none
+    pub metrics_history_size: usize,
+    /// Per-check overrides by check name (e.g. "network_connectivity"),
+    /// from `[support.checks.<name>]` tables
+    pub checks: HashMap<String, CheckConfig>,
+}
+
+/// How one health check counts towards the summary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CheckConfig {
+    /// "default" keeps the check's own result, "warning" reports a failure
+    /// as WARNING, "critical" reports any result but PASS as CRITICAL
+    pub severity: String,
+    /// A disabled check is not run and not counted
+    pub enabled: bool,
+}
+
+impl Default for CheckConfig {
+    fn default() -> Self {
+        Self { severity: "default".to_string(), enabled: true }
+    }
+}
+
+/// Values of `CheckConfig::severity`
+pub const CHECK_SEVERITIES: &[&str] = &["default", "warning", "critical"];
+
+impl Default for SupportConfig {
+    fn default() -> Self {
+        Self {
+            memory_warning_percent: 80.0,
+            memory_critical_percent: 90.0,
+            disk_warning_percent: 85.0,
+            disk_critical_percent: 95.0,
+            cpu_warning_percent: 90.0,
+            cpu_error_percent: 95.0,
+            process_warning_count: 1000,
+            process_error_count: 2000,
+            gpu_vram_warning_percent: 90.0,
+            gpu_vram_critical_percent: 98.0,
+            gpu_temperature_warning_c: 80.0,
+            gpu_temperature_critical_c: 90.0,
+            cpu_temperature_warning_c: 85.0,
+            cpu_temperature_critical_c: 95.0,
+            watched_processes: Vec::new(),
+            process_runaway_cpu_percent: 90.0,
+            process_runaway_memory_mb: 16384.0,
+            python_packages: ["numpy", "requests", "pandas", "psutil"].iter().map(|s| s.to_string()).collect(),
+            cache_quarantine: false,
+            llm_endpoint: String::new(),
+            llm_model: String::new(),
+            llm_timeout_ms: 3000,
+            llm_required: false,
+            network_endpoints: Vec::new(),
+            network_required: Vec::new(),
+            network_timeout_ms: 2000,
+            network_latency_window: 60,
+            schedule_quick_minutes: 0,
+            schedule_full_cron: String::new(),
+            log_dir: String::new(),
+            log_quota_mb: 1024.0,
+            log_stale_minutes: 60,
+            heartbeat_interval_secs: 60,
+            metrics_history_size: 720,
+            checks: HashMap::new(),
+        }
+    }
+}
+
+impl SupportConfig {
+    /// Check every percentage is within 0–100 and no warning level is above
+    /// its critical level
+    pub fn validate(&self) -> Result<()> {
+        let levels = [
+            ("memory", self.memory_warning_percent, self.memory_critical_percent),
+            ("disk", self.disk_warning_percent, self.disk_critical_percent),
+            ("cpu", self.cpu_warning_percent, self.cpu_error_percent),
+            ("gpu_vram", self.gpu_vram_warning_percent, self.gpu_vram_critical_percent),
+        ];
+        for (name, warning, critical) in levels {
+            if !(0.0..=100.0).contains(&warning) || !(0.0..=100.0).contains(&critical) {
+                return Err(AiosError::config(format!(
+                    "support: {} thresholds must be percentages, got {} and {}",
+                    name, warning, critical
+                )));
+            }
+            if warning > critical {
+                return Err(AiosError::config(format!(
+                    "support: {} warning threshold {} is above its critical threshold {}",
+                    name, warning, critical
+                )));
+            }
+        }
+        if !(0.0..=100.0).contains(&self.process_runaway_cpu_percent) {
+            return Err(AiosError::config(format!(
+                "support: process_runaway_cpu_percent must be a percentage, got {}",
+                self.process_runaway_cpu_percent
+            )));
+        }
+        let temperatures = [
+            ("gpu", self.gpu_temperature_warning_c, self.gpu_temperature_critical_c),
+            ("cpu", self.cpu_temperature_warning_c, self.cpu_temperature_critical_c),
+        ];
+        for (name, warning, critical) in temperatures {
+            if warning > critical {
+                return Err(AiosError::config(format!(
+                    "support: {}_temperature_warning_c {} is above {}_temperature_critical_c {}",
+                    name, warning, name, critical
+                )));
+            }
+        }
+        if self.process_warning_count > self.process_error_count {
+            return Err(AiosError::config(format!(
+                "support: process_warning_count {} is above process_error_count {}",
+                self.process_warning_count, self.process_error_count
+            )));
+        }
+        if self.heartbeat_interval_secs == 0 {
+            return Err(AiosError::config("support: heartbeat_interval_secs must be positive"));
+        }
+        if self.log_quota_mb < 0.0 {
+            return Err(AiosError::config(format!("support: log_quota_mb must not be negative, got {}", self.log_quota_mb)));
+        }
+        if let Some((name, check)) = self.checks.iter().find(|(_, check)| !CHECK_SEVERITIES.contains(&check.severity.as_str())) {
+            return Err(AiosError::config(format!(
+                "support: checks.{}.severity must be one of {}, got {:?}",
+                name,
+                CHECK_SEVERITIES.join(", "),
+                check.severity
+            )));
+        }
+        Ok(())
+    }
+
+    /// These thresholds with the keys of the JSON object `overrides`
+    /// replaced, e.g. `{"memory_warning_percent": 70}`
+    pub fn with_overrides(&self, overrides: &serde_json::Value) -> Result<Self> {
+        let serde_json::Value::Object(overrides) = overrides else {
+            return Err(AiosError::config("support: threshold overrides must be a JSON object"));
+        };
+        let mut merged = serde_json::to_value(self).map_err(|e| AiosError::config(e.to_string()))?;
+        if let serde_json::Value::Object(fields) = &mut merged {
+            fields.extend(overrides.iter().map(|(key, value)| (key.clone(), value.clone())));
+        }
+        let config: Self =
+            serde_json::from_value(merged).map_err(|e| AiosError::config(format!("Invalid support thresholds: {}", e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// `with_overrides` from a JSON file
+    pub fn with_overrides_file(&self, path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| AiosError::config(format!("Failed to read {}: {}", path.display(), e)))?;
+        let overrides = serde_json::from_str(&text)
+            .map_err(|e| AiosError::config(format!("Invalid JSON in {}: {}", path.display(), e)))?;
+        self.with_overrides(&overrides).map_err(|e| e.context(path.display()))
+    }
+}
+
+/// Dream and meditation cycle settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DreamConfig {
+    pub karma_refund_pool: f64,
+    /// Length of one overnight dream cycle
+    pub cycle_minutes: u32,
+    /// Minimum dream cycles in an overnight session
+    pub min_overnight_cycles: u32,
+    /// Length of one overnight meditation block
+    pub overnight_meditation_minutes: u32,
+    /// Minimum meditation blocks in an overnight session
+    pub min_overnight_meditation_blocks: u32,
+    /// Length of one block in a meditation session
+    pub meditation_block_minutes: u32,
+    /// Simulated processing time per dream cycle
+    pub cycle_delay_ms: u64,
+}
+
+impl Default for DreamConfig {
+    fn default() -> Self {
+        Self {
+            karma_refund_pool: 100.0,
+            cycle_minutes: 90,
+            min_overnight_cycles: 4,
+            overnight_meditation_minutes: 120,
+            min_overnight_meditation_blocks: 2,
+            meditation_block_minutes: 15,
+            cycle_delay_ms: 100,
+        }
+    }
+}
+
+/// Karma rules for the Luna arbiter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArbiterConfig {
+    pub initial_karma: f64,
+    /// TTE efficiency below this is penalized
+    pub low_efficiency: f64,
+    /// TTE efficiency above this earns the full reward
+    pub high_efficiency: f64,
+    pub low_efficiency_karma: f64,
+    pub high_efficiency_karma: f64,
+    /// Utility bonus per RVC grade; unknown grades get 0. A configured table
+    /// replaces the defaults entirely
+    pub grade_bonus: HashMap<String, f64>,
+}
+
+impl Default for ArbiterConfig {
+    fn default() -> Self {
+        Self {
+            initial_karma: 100.0,
+            low_efficiency: 0.5,
+            high_efficiency: 0.9,
+            low_efficiency_karma: -0.1,
+            high_efficiency_karma: 2.0,
+            grade_bonus: [("A", 0.2), ("B", 0.1), ("C", 0.0), ("D", -0.1), ("F", -0.2)]
+                .into_iter()
+                .map(|(grade, bonus)| (grade.to_string(), bonus))
+                .collect(),
+        }
+    }
+}
+
+/// What the backup core copies, relative to the working directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Directories always backed up
+    pub roots: Vec<String>,
+    /// Top-level files always backed up
+    pub files: Vec<String>,
+    /// Directory names skipped anywhere below a root
+    pub exclude_dirs: Vec<String>,
+    pub config_dir: String,
+    pub log_dir: String,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        Self {
+            roots: strings(&[
+                "carma_core",
+                "data_core",
+                "dream_core",
+                "enterprise_core",
+                "luna_core",
+                "streamlit_core",
+                "support_core",
+                "utils_core",
+            ]),
+            files: strings(&["main.py", "requirements.txt", "README.md"]),
+            exclude_dirs: strings(&[".git", "__pycache__", ".pytest_cache", "node_modules"]),
+            config_dir: "config".to_string(),
+            log_dir: "log".to_string(),
+        }
+    }
+}
+
+/// Settings for the `aios-server` binary; command-line flags override them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address to listen on
+    pub bind: String,
+    /// Worker threads handling connections
+    pub threads: usize,
+    /// Largest accepted request body
+    pub max_body_bytes: usize,
+    /// Cache directory checked by the health endpoint
+    pub cache_dir: String,
+    /// Dimension of vectors accepted by the vector endpoints
+    pub dimension: usize,
+    /// Backup destination; empty disables the backup endpoint
+    pub backup_dir: String,
+    /// Root of the paths the data stats endpoint accepts
+    pub data_dir: String,
+    /// `RustCarmaCore.save` snapshot whose fragments the retrieval
+    /// endpoints search; empty starts them with no documents
+    pub carma_snapshot: String,
+    /// Origins (e.g. "http://localhost:8501") whose pages may call the API;
+    /// requests from any other page are refused
+    pub allowed_origins: Vec<String>,
+    /// Bearer token required by the endpoints that change state; empty
+    /// requires none
+    pub auth_token: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1:8700".to_string(),
+            threads: 4,
+            max_body_bytes: 16 * 1024 * 1024,
+            cache_dir: "cache".to_string(),
+            dimension: 384,
+            backup_dir: String::new(),
+            data_dir: "data".to_string(),
+            carma_snapshot: String::new(),
+            allowed_origins: Vec::new(),
+            auth_token: String::new(),
+        }
+    }
+}
+
+/// Shared SQLite store for durable core state
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreConfig {
+    /// Database file; empty keeps each core's state in its own files, or
+    /// in memory for cores that had none
+    pub path: String,
+}
+
+impl AiosConfig {
+    /// Parse a TOML document
+    pub fn from_toml_str(text: &str) -> Result<Self> {
+        let value = toml::parse(text)?;
+        let config: Self =
+            serde_json::from_value(value).map_err(|e| AiosError::config(format!("Invalid configuration: {}", e)))?;
+        config.support.validate()?;
+        Ok(config)
+    }
+
+    /// Read and parse `path`
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| AiosError::config(format!("Failed to read {}: {}", path.display(), e)))?;
+        Self::from_toml_str(&text).map_err(|e| e.context(path.display()))
+    }
+
+    /// Load the configuration for a core constructor.
+    ///
+    /// An explicit `path` must exist. Otherwise `$AIOS_CONFIG` is used if set,
+    /// then `./aios.toml` if present, and finally the defaults.
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        match Self::locate(path) {
+            Some(path) => Self::from_file(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Config file `load` would read, if any
+    pub fn locate(path: Option<&str>) -> Option<PathBuf> {
+        if let Some(path) = path {
+            return Some(PathBuf::from(path));
+        }
+        if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|p| !p.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+        let local = PathBuf::from(CONFIG_FILE);
+        local.is_file().then_some(local)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_precedence() {
+        let dir = std::env::temp_dir().join(format!("aios-config-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let explicit = dir.join("explicit.toml");
+        let from_env = dir.join("env.toml");
+        fs::write(&explicit, "[server]\nthreads = 3\n").unwrap();
+        fs::write(&from_env, "[server]\nthreads = 5\n").unwrap();
+
+        // The only test touching the environment, so nothing races it
+        std::env::set_var(CONFIG_ENV, &from_env);
+        assert_eq!(AiosConfig::load(Some(explicit.to_str().unwrap())).unwrap().server.threads, 3);
+        assert_eq!(AiosConfig::load(None).unwrap().server.threads, 5);
+        assert!(AiosConfig::load(Some(dir.join("missing.toml").to_str().unwrap())).is_err());
+        // An empty variable counts as unset; no aios.toml in the crate directory
+        std::env::set_var(CONFIG_ENV, "");
+        assert_eq!(AiosConfig::locate(None), None);
+        assert_eq!(AiosConfig::load(None).unwrap(), AiosConfig::default());
+        std::env::remove_var(CONFIG_ENV);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,379 @@
+//! Minimal TOML reader producing a `serde_json::Value` tree
+//!
+//! Supports comments, `[table]` headers, dotted and quoted keys, basic and
+//! literal strings (including multi-line), integers, floats, booleans,
+//! arrays and inline tables. Dates, arrays of tables and non-finite floats
+//! are rejected with an error, as are keys and tables defined twice.
+
+use serde_json::{Map, Number, Value};
+
+use aios_errors::{AiosError, Result};
+
+/// Parse a TOML document into a JSON object
+pub fn parse(input: &str) -> Result<Value> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+    };
+    parser.document().map_err(|message| {
+        let line = parser.chars[..parser.pos.min(parser.chars.len())]
+            .iter()
+            .filter(|&&c| c == '\n')
+            .count()
+            + 1;
+        AiosError::config(format!("TOML line {}: {}", line, message))
+    })
+}
+
+type ParseResult<T> = std::result::Result<T, String>;
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn document(&mut self) -> ParseResult<Value> {
+        let mut root = Map::new();
+        let mut table: Vec<String> = Vec::new();
+        let mut headers: Vec<Vec<String>> = Vec::new();
+
+        loop {
+            self.skip_whitespace_and_comments(true);
+            match self.peek() {
+                None => break,
+                Some('[') => {
+                    self.pos += 1;
+                    if self.peek() == Some('[') {
+                        return Err("arrays of tables are not supported".to_string());
+                    }
+                    self.skip_whitespace_and_comments(false);
+                    table = self.key()?;
+                    self.skip_whitespace_and_comments(false);
+                    self.expect(']')?;
+                    if headers.contains(&table) {
+                        return Err(format!("table '{}' defined twice", table.join(".")));
+                    }
+                    headers.push(table.clone());
+                    table_at(&mut root, &table)?;
+                }
+                Some(_) => {
+                    let key = self.key()?;
+                    self.skip_whitespace_and_comments(false);
+                    self.expect('=')?;
+                    self.skip_whitespace_and_comments(false);
+                    let value = self.value()?;
+                    let (last, parents) = key.split_last().expect("keys are non-empty");
+                    let path: Vec<String> = table.iter().chain(parents).cloned().collect();
+                    insert(table_at(&mut root, &path)?, last, value)?;
+                }
+            }
+            self.end_of_line()?;
+        }
+        Ok(Value::Object(root))
+    }
+
+    /// Dotted key: bare or quoted segments separated by `.`
+    fn key(&mut self) -> ParseResult<Vec<String>> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments(false);
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                        self.pos += 1;
+                    }
+                    if self.pos == start {
+                        return Err("expected a key".to_string());
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            parts.push(part);
+            self.skip_whitespace_and_comments(false);
+            if self.peek() == Some('.') {
+                self.pos += 1;
+            } else {
+                return Ok(parts);
+            }
+        }
+    }
+
+    fn value(&mut self) -> ParseResult<Value> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.basic_string()?)),
+            Some('\'') => Ok(Value::String(self.literal_string()?)),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some('t') if self.starts_with("true") => {
+                self.pos += 4;
+                Ok(Value::Bool(true))
+            }
+            Some('f') if self.starts_with("false") => {
+                self.pos += 5;
+                Ok(Value::Bool(false))
+            }
+            Some(c) if c.is_ascii_digit() || c == '+' || c == '-' || c == 'i' || c == 'n' => self.number(),
+            Some(c) => Err(format!("unexpected character '{}' in value", c)),
+            None => Err("expected a value".to_string()),
+        }
+    }
+
+    fn array(&mut self) -> ParseResult<Value> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments(true);
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_whitespace_and_comments(true);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {}
+                _ => return Err("expected ',' or ']' in array".to_string()),
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> ParseResult<Value> {
+        self.expect('{')?;
+        let mut table = Map::new();
+        self.skip_whitespace_and_comments(false);
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::Object(table));
+        }
+        loop {
+            let key = self.key()?;
+            self.skip_whitespace_and_comments(false);
+            self.expect('=')?;
+            self.skip_whitespace_and_comments(false);
+            let value = self.value()?;
+            let (last, parents) = key.split_last().expect("keys are non-empty");
+            insert(table_at(&mut table, parents)?, last, value)?;
+            self.skip_whitespace_and_comments(false);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(table));
+                }
+                _ => return Err("expected ',' or '}' in inline table".to_string()),
+            }
+        }
+    }
+
+    fn number(&mut self) -> ParseResult<Value> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_' | ':'))
+        {
+            self.pos += 1;
+        }
+        let raw: String = self.chars[start..self.pos].iter().collect();
+        let text = raw.replace('_', "");
+        if raw.contains(':') || (text.len() >= 10 && text.as_bytes().get(4) == Some(&b'-')) {
+            return Err(format!("dates are not supported: {}", raw));
+        }
+
+        let is_float = text.contains(['.', 'e', 'E']) || text.ends_with("inf") || text.ends_with("nan");
+        if !is_float {
+            if let Ok(n) = text.parse::<i64>() {
+                return Ok(Value::Number(n.into()));
+            }
+        }
+        let value: f64 = text.parse().map_err(|_| format!("invalid number: {}", raw))?;
+        Number::from_f64(value)
+            .map(Value::Number)
+            .ok_or_else(|| format!("non-finite floats are not supported: {}", raw))
+    }
+
+    fn basic_string(&mut self) -> ParseResult<String> {
+        let multiline = self.starts_with("\"\"\"");
+        self.pos += if multiline { 3 } else { 1 };
+        if multiline && self.peek() == Some('\n') {
+            self.pos += 1;
+        }
+
+        let mut out = String::new();
+        loop {
+            let c = self.next().ok_or("unterminated string")?;
+            match c {
+                '"' if !multiline => return Ok(out),
+                '"' if self.starts_with("\"\"") => {
+                    self.pos += 2;
+                    return Ok(out);
+                }
+                '\n' if !multiline => return Err("newline in single-line string".to_string()),
+                '\\' => {
+                    let escape = self.next().ok_or("unterminated escape")?;
+                    match escape {
+                        'n' => out.push('\n'),
+                        't' => out.push('\t'),
+                        'r' => out.push('\r'),
+                        'b' => out.push('\u{8}'),
+                        'f' => out.push('\u{c}'),
+                        '"' => out.push('"'),
+                        '\\' => out.push('\\'),
+                        'u' | 'U' => {
+                            let len = if escape == 'u' { 4 } else { 8 };
+                            let hex: String = (0..len).filter_map(|_| self.next()).collect();
+                            let code = u32::from_str_radix(&hex, 16).map_err(|_| format!("invalid escape \\{}{}", escape, hex))?;
+                            out.push(char::from_u32(code).ok_or_else(|| format!("invalid code point {:x}", code))?);
+                        }
+                        // Line-ending backslash trims the newline and leading whitespace
+                        c if multiline && c.is_whitespace() => {
+                            while self.peek().is_some_and(char::is_whitespace) {
+                                self.pos += 1;
+                            }
+                        }
+                        other => return Err(format!("invalid escape \\{}", other)),
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> ParseResult<String> {
+        let multiline = self.starts_with("'''");
+        self.pos += if multiline { 3 } else { 1 };
+        if multiline && self.peek() == Some('\n') {
+            self.pos += 1;
+        }
+
+        let mut out = String::new();
+        loop {
+            let c = self.next().ok_or("unterminated string")?;
+            match c {
+                '\'' if !multiline => return Ok(out),
+                '\'' if self.starts_with("''") => {
+                    self.pos += 2;
+                    return Ok(out);
+                }
+                '\n' if !multiline => return Err("newline in single-line string".to_string()),
+                c => out.push(c),
+            }
+        }
+    }
+
+    /// Skip spaces, tabs and comments, and newlines too if `newlines`
+    fn skip_whitespace_and_comments(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => self.pos += 1,
+                '\n' if newlines => self.pos += 1,
+                '#' => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> ParseResult<()> {
+        self.skip_whitespace_and_comments(false);
+        match self.peek() {
+            None | Some('\n') => Ok(()),
+            Some(c) => Err(format!("unexpected '{}' after value", c)),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> ParseResult<()> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected '{}', found '{}'", expected, c)),
+            None => Err(format!("expected '{}', found end of input", expected)),
+        }
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+}
+
+/// Table at `path` below `root`, creating intermediate tables
+fn table_at<'a>(root: &'a mut Map<String, Value>, path: &[String]) -> ParseResult<&'a mut Map<String, Value>> {
+    let mut table = root;
+    for part in path {
+        let entry = table.entry(part.clone()).or_insert_with(|| Value::Object(Map::new()));
+        table = match entry {
+            Value::Object(map) => map,
+            _ => return Err(format!("key '{}' is not a table", part)),
+        };
+    }
+    Ok(table)
+}
+
+fn insert(table: &mut Map<String, Value>, key: &str, value: Value) -> ParseResult<()> {
+    if table.contains_key(key) {
+        return Err(format!("duplicate key '{}'", key));
+    }
+    table.insert(key.to_string(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_keys() {
+        let value = parse("a.b = 1\n\"quoted key\" = 2\n'lit.eral' = 3\n[t.\"x y\"]\nz.w = true\n").unwrap();
+        assert_eq!(value, json!({"a": {"b": 1}, "quoted key": 2, "lit.eral": 3, "t": {"x y": {"z": {"w": true}}}}));
+    }
+
+    #[test]
+    fn test_redefinitions() {
+        assert!(parse("a = 1\na = 2\n").unwrap_err().to_string().contains("duplicate key 'a'"));
+        assert!(parse("a.b = 1\n[a]\nb = 2\n").unwrap_err().to_string().contains("duplicate key 'b'"));
+        assert!(parse("[t]\nx = 1\n[t]\ny = 2\n").unwrap_err().to_string().contains("table 't' defined twice"));
+        assert!(parse("t = 1\n[t]\n").unwrap_err().to_string().contains("not a table"));
+        // A parent may be defined after its child
+        assert!(parse("[a.b]\nx = 1\n[a]\ny = 2\n").is_ok());
+    }
+
+    #[test]
+    fn test_strings() {
+        let value = parse("s = \"\"\"\none \\\n    two \\\n\n  three\"\"\"\n").unwrap();
+        assert_eq!(value["s"], "one two three");
+        let value = parse(r#"s = "caf\u00e9 \U0001F600 tab\t""#).unwrap();
+        assert_eq!(value["s"], "caf\u{e9} \u{1F600} tab\t");
+        assert!(parse(r#"s = "\uZZZZ""#).is_err());
+        assert!(parse(r#"s = "\uD800""#).is_err());
+        let value = parse("s = '''\nC:\\raw\\path'''\n").unwrap();
+        assert_eq!(value["s"], r"C:\raw\path");
+    }
+
+    #[test]
+    fn test_rejected_values() {
+        for text in ["d = 1979-05-27\n", "t = 07:32:00\n", "d = 1979-05-27T07:32:00Z\n"] {
+            assert!(parse(text).unwrap_err().to_string().contains("dates are not supported"), "{}", text);
+        }
+        for text in ["f = inf\n", "f = +inf\n", "f = -inf\n", "f = nan\n"] {
+            assert!(parse(text).unwrap_err().to_string().contains("non-finite"), "{}", text);
+        }
+        assert!(parse("[[t]]\n").is_err());
+        assert_eq!(parse("n = 1_000\nf = -2.5e3\n").unwrap(), json!({"n": 1000, "f": -2500.0}));
+    }
+}
@@ -0,0 +1,208 @@
+//! Typed errors shared by the AIOS Rust cores
+//!
+//! `AiosError` carries an `ErrorKind` and a message. This crate does not
+//! depend on PyO3, since the cores build against different PyO3 versions;
+//! instead each extension module expands `python_exceptions!` to get its own
+//! exception hierarchy:
+//!
+//! ```text
+//! RuntimeError
+//! └── AiosError
+//!     ├── IoError
+//!     ├── ValidationError
+//!     ├── IndexError
+//!     ├── ConfigError
+//!     └── CancelledError
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Category of failure, mapped 1:1 to a Python exception class
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// Filesystem, network or serialization I/O failed
+    Io,
+    /// An argument or input record was rejected
+    Validation,
+    /// A search index or lookup failed
+    Index,
+    /// Configuration was missing or malformed
+    Config,
+    /// The operation was stopped through a cancellation token
+    Cancelled,
+}
+
+impl ErrorKind {
+    /// Python exception class name
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Io => "IoError",
+            ErrorKind::Validation => "ValidationError",
+            ErrorKind::Index => "IndexError",
+            ErrorKind::Config => "ConfigError",
+            ErrorKind::Cancelled => "CancelledError",
+        }
+    }
+}
+
+/// Error with a kind, a message and an optional underlying cause
+#[derive(Debug)]
+pub struct AiosError {
+    kind: ErrorKind,
+    message: String,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+pub type Result<T> = std::result::Result<T, AiosError>;
+
+impl AiosError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Io, message)
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Validation, message)
+    }
+
+    pub fn index(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Index, message)
+    }
+
+    pub fn config(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Config, message)
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Cancelled, message)
+    }
+
+    /// Attach the underlying cause
+    pub fn with_source(mut self, source: impl Error + Send + Sync + 'static) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Prefix the message, e.g. with the operation that failed
+    pub fn context(mut self, context: impl fmt::Display) -> Self {
+        self.message = format!("{}: {}", context, self.message);
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for AiosError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for AiosError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|e| e as &(dyn Error + 'static))
+    }
+}
+
+impl From<io::Error> for AiosError {
+    fn from(error: io::Error) -> Self {
+        Self::io(error.to_string()).with_source(error)
+    }
+}
+
+impl From<serde_json::Error> for AiosError {
+    /// I/O failures stay `Io`; syntax and data errors are `Validation`
+    fn from(error: serde_json::Error) -> Self {
+        let kind = if error.is_io() { ErrorKind::Io } else { ErrorKind::Validation };
+        Self::new(kind, error.to_string()).with_source(error)
+    }
+}
+
+/// Define the Python exception hierarchy and conversion helpers in the
+/// calling crate, against its own `pyo3` dependency.
+///
+/// Expand it inside a module, e.g. `mod errors { aios_errors::python_exceptions!(my_module); }`,
+/// and call `errors::register(py, m)` from the `#[pymodule]` function. The
+/// expansion provides `to_pyerr` plus `io`, `validation`, `index`,
+/// `config` and `cancelled` shortcuts that build a `PyErr` from a message.
+#[macro_export]
+macro_rules! python_exceptions {
+    ($module:ident) => {
+        ::pyo3::create_exception!(
+            $module,
+            AiosError,
+            ::pyo3::exceptions::PyRuntimeError,
+            "Base class for errors raised by the AIOS Rust cores."
+        );
+        ::pyo3::create_exception!($module, IoError, AiosError, "Filesystem, network or serialization I/O failed.");
+        ::pyo3::create_exception!($module, ValidationError, AiosError, "An argument or input record was rejected.");
+        ::pyo3::create_exception!($module, IndexError, AiosError, "A search index or lookup failed.");
+        ::pyo3::create_exception!($module, ConfigError, AiosError, "Configuration was missing or malformed.");
+        ::pyo3::create_exception!($module, CancelledError, AiosError, "The operation was stopped through a cancellation token.");
+
+        /// Convert into the Python exception matching the error kind
+        #[allow(dead_code)]
+        pub fn to_pyerr(error: $crate::AiosError) -> ::pyo3::PyErr {
+            let message = error.to_string();
+            match error.kind() {
+                $crate::ErrorKind::Io => IoError::new_err(message),
+                $crate::ErrorKind::Validation => ValidationError::new_err(message),
+                $crate::ErrorKind::Index => IndexError::new_err(message),
+                $crate::ErrorKind::Config => ConfigError::new_err(message),
+                $crate::ErrorKind::Cancelled => CancelledError::new_err(message),
+            }
+        }
+
+        #[allow(dead_code)]
+        pub fn io(message: impl Into<String>) -> ::pyo3::PyErr {
+            IoError::new_err(message.into())
+        }
+
+        #[allow(dead_code)]
+        pub fn validation(message: impl Into<String>) -> ::pyo3::PyErr {
+            ValidationError::new_err(message.into())
+        }
+
+        #[allow(dead_code)]
+        pub fn index(message: impl Into<String>) -> ::pyo3::PyErr {
+            IndexError::new_err(message.into())
+        }
+
+        #[allow(dead_code)]
+        pub fn config(message: impl Into<String>) -> ::pyo3::PyErr {
+            ConfigError::new_err(message.into())
+        }
+
+        #[allow(dead_code)]
+        pub fn cancelled(message: impl Into<String>) -> ::pyo3::PyErr {
+            CancelledError::new_err(message.into())
+        }
+
+        /// Add the exception classes to the Python module
+        #[allow(deprecated)]
+        pub fn register(py: ::pyo3::Python<'_>, m: &::pyo3::types::PyModule) -> ::pyo3::PyResult<()> {
+            m.add("AiosError", py.get_type::<AiosError>())?;
+            m.add("IoError", py.get_type::<IoError>())?;
+            m.add("ValidationError", py.get_type::<ValidationError>())?;
+            m.add("IndexError", py.get_type::<IndexError>())?;
+            m.add("ConfigError", py.get_type::<ConfigError>())?;
+            m.add("CancelledError", py.get_type::<CancelledError>())?;
+            Ok(())
+        }
+    };
+}
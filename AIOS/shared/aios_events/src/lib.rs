@@ -0,0 +1,340 @@
+//! Structured event stream of the AIOS cores
+//!
+//! Cores report notable moments (health alerts, backup progress, finished
+//! dream cycles, retrieval latency spikes) with `event!`. Each event is
+//! serialized once to a JSON object `{"kind", "source", "timestamp", "data"}`
+//! and handed to every subscriber: the clients of the embedded WebSocket
+//! server started with `start_server`, which get one text frame per event,
+//! and the callback set with `forward`. Without subscribers `event!` does
+//! not evaluate its fields.
+//!
+//! Subscribers have bounded queues. One that falls behind loses events
+//! instead of slowing the cores down; `info` counts the events lost.
+//!
+//! Like `aios-errors`, this crate does not depend on PyO3: extension modules
+//! expand `python_events!`. Each module keeps its own subscribers, so a
+//! dashboard either connects to every module's server, or one module runs
+//! the server and the others forward to it, e.g.
+//! `dream.forward_events(support.publish_event_json)`.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aios_errors::{AiosError, Result};
+use serde_json::{json, Map, Value};
+
+mod server;
+mod sha1;
+
+pub use server::EventServer;
+
+#[doc(hidden)]
+pub use serde_json;
+
+/// Events queued for the forwarding callback
+const FORWARD_QUEUE: usize = 4096;
+
+/// Set while anything is subscribed
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+static NEXT_SUBSCRIBER: AtomicU64 = AtomicU64::new(1);
+static SUBSCRIBERS: Mutex<Vec<(u64, SyncSender<Arc<str>>)>> = Mutex::new(Vec::new());
+static SERVER: Mutex<Option<EventServer>> = Mutex::new(None);
+/// Subscriber id of the forwarding thread
+static FORWARDER: Mutex<Option<u64>> = Mutex::new(None);
+
+/// Callback receiving every event as a JSON string
+pub type Callback = Box<dyn Fn(&str) + Send>;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// True while anything receives events
+pub fn enabled() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Send an event of `kind` from `source` to every subscriber
+pub fn emit(source: &str, kind: &str, data: Map<String, Value>) {
+    if !enabled() {
+        return;
+    }
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or(0.0);
+    broadcast(json!({ "kind": kind, "source": source, "timestamp": timestamp, "data": data }).to_string());
+}
+
+/// Send an already serialized event to every subscriber
+fn broadcast(event: String) {
+    let event: Arc<str> = Arc::from(event);
+    let mut subscribers = lock(&SUBSCRIBERS);
+    subscribers.retain(|(_, sender)| match sender.try_send(Arc::clone(&event)) {
+        Ok(()) => true,
+        Err(TrySendError::Full(_)) => {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+            true
+        }
+        Err(TrySendError::Disconnected(_)) => false,
+    });
+    ACTIVE.store(!subscribers.is_empty(), Ordering::Relaxed);
+}
+
+/// Re-send an event serialized by another module, unchanged.
+///
+/// `event` must be a JSON object with a string "kind".
+pub fn publish_json(event: &str) -> Result<()> {
+    let value: Value =
+        serde_json::from_str(event).map_err(|e| AiosError::validation(format!("Event is not valid JSON: {}", e)))?;
+    if !value.get("kind").is_some_and(Value::is_string) {
+        return Err(AiosError::validation("Event must be a JSON object with a string \"kind\""));
+    }
+    if enabled() {
+        broadcast(event.to_string());
+    }
+    Ok(())
+}
+
+/// A queue of events; unsubscribes when dropped
+pub struct Subscription {
+    id: u64,
+    receiver: Receiver<Arc<str>>,
+}
+
+impl Subscription {
+    pub fn recv_timeout(&self, timeout: Duration) -> std::result::Result<Arc<str>, RecvTimeoutError> {
+        self.receiver.recv_timeout(timeout)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        unsubscribe(self.id);
+    }
+}
+
+/// Receive every event from now on; up to `capacity` wait in the queue
+pub fn subscribe(capacity: usize) -> Subscription {
+    let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+    let id = NEXT_SUBSCRIBER.fetch_add(1, Ordering::Relaxed);
+    let mut subscribers = lock(&SUBSCRIBERS);
+    subscribers.push((id, sender));
+    ACTIVE.store(true, Ordering::Relaxed);
+    Subscription { id, receiver }
+}
+
+/// Disconnect subscriber `id`; its queued events can still be received
+fn unsubscribe(id: u64) {
+    let mut subscribers = lock(&SUBSCRIBERS);
+    subscribers.retain(|(subscriber, _)| *subscriber != id);
+    ACTIVE.store(!subscribers.is_empty(), Ordering::Relaxed);
+}
+
+/// Start the module's WebSocket server on `addr` and return the address it
+/// listens on
+pub fn start_server(addr: &str) -> Result<SocketAddr> {
+    let mut server = lock(&SERVER);
+    if let Some(running) = server.as_ref() {
+        return Err(AiosError::validation(format!("Event server is already running on {}", running.local_addr())));
+    }
+    let started = EventServer::bind(addr)?;
+    let addr = started.local_addr();
+    *server = Some(started);
+    Ok(addr)
+}
+
+/// Stop the module's WebSocket server; false when none was running
+pub fn stop_server() -> bool {
+    let stopped = lock(&SERVER).take();
+    stopped.is_some()
+}
+
+/// Call `callback` with every event on a background thread, or stop
+/// forwarding with `None`
+pub fn forward(callback: Option<Callback>) -> Result<()> {
+    let mut forwarder = lock(&FORWARDER);
+    // The previous thread finishes the events already queued for it, then exits
+    if let Some(id) = forwarder.take() {
+        unsubscribe(id);
+    }
+    let Some(callback) = callback else {
+        return Ok(());
+    };
+    let subscription = subscribe(FORWARD_QUEUE);
+    let id = subscription.id;
+    thread::Builder::new().name("aios-events-forward".to_string()).spawn(move || {
+        while let Ok(event) = subscription.receiver.recv() {
+            callback(&event);
+        }
+    })?;
+    *forwarder = Some(id);
+    Ok(())
+}
+
+/// State of the module's event stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamInfo {
+    pub server: Option<SocketAddr>,
+    /// Connected WebSocket clients
+    pub clients: usize,
+    pub forwarding: bool,
+    /// Events lost by subscribers that fell behind
+    pub dropped: u64,
+}
+
+pub fn info() -> StreamInfo {
+    let server = lock(&SERVER);
+    StreamInfo {
+        server: server.as_ref().map(EventServer::local_addr),
+        clients: server.as_ref().map_or(0, EventServer::clients),
+        forwarding: lock(&FORWARDER).is_some(),
+        dropped: DROPPED.load(Ordering::Relaxed),
+    }
+}
+
+/// Emit an event from the calling crate:
+/// `aios_events::event!("backup.progress", phase = "archiving", files = 12)`.
+///
+/// Fields become the event's `data` object and are only evaluated while
+/// something is subscribed.
+#[macro_export]
+macro_rules! event {
+    ($kind:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        if $crate::enabled() {
+            #[allow(unused_mut)]
+            let mut data = $crate::serde_json::Map::new();
+            $(data.insert(stringify!($key).to_string(), $crate::serde_json::json!($value));)*
+            $crate::emit(env!("CARGO_PKG_NAME"), $kind, data);
+        }
+    };
+}
+
+/// Define `start_event_server`, `stop_event_server`, `publish_event`,
+/// `publish_event_json`, `forward_events` and `event_stream_info` in the
+/// calling crate, against its own `pyo3`.
+///
+/// Expand it inside a module, e.g. `mod events { aios_events::python_events!(); }`,
+/// next to the `errors` module from `aios_errors::python_exceptions!`, and
+/// call `events::register(m)` from the `#[pymodule]` function.
+// `crate::errors` deliberately names the calling crate's exceptions
+#[allow(clippy::crate_in_macro_def)]
+#[macro_export]
+macro_rules! python_events {
+    () => {
+        /// Serve this module's events over WebSocket on `bind` ("host:port";
+        /// port 0 picks a free one) and return the address it listens on.
+        ///
+        /// Clients connect to `ws://<address>/` and get every event as a
+        /// JSON text message.
+        #[::pyo3::pyfunction]
+        #[pyo3(signature = (bind="127.0.0.1:8765"))]
+        pub fn start_event_server(py: ::pyo3::Python<'_>, bind: &str) -> ::pyo3::PyResult<String> {
+            py.allow_threads(|| $crate::start_server(bind))
+                .map(|addr| addr.to_string())
+                .map_err(crate::errors::to_pyerr)
+        }
+
+        /// Stop the event server, closing client connections; False when
+        /// none was running
+        #[::pyo3::pyfunction]
+        pub fn stop_event_server(py: ::pyo3::Python<'_>) -> bool {
+            py.allow_threads($crate::stop_server)
+        }
+
+        /// Send an event from Python to this module's subscribers.
+        ///
+        /// `data` is a JSON-serializable dict.
+        #[::pyo3::pyfunction]
+        #[pyo3(signature = (kind, data=None, source="python"))]
+        #[allow(deprecated)]
+        pub fn publish_event(
+            py: ::pyo3::Python<'_>,
+            kind: &str,
+            data: Option<::pyo3::PyObject>,
+            source: &str,
+        ) -> ::pyo3::PyResult<()> {
+            if kind.is_empty() {
+                return Err(crate::errors::validation("Event kind must not be empty"));
+            }
+            let data = match data {
+                None => $crate::serde_json::Map::new(),
+                Some(data) => {
+                    let text: String = py.import("json")?.call_method1("dumps", (data,))?.extract()?;
+                    match $crate::serde_json::from_str(&text) {
+                        Ok($crate::serde_json::Value::Object(data)) => data,
+                        _ => return Err(crate::errors::validation("Event data must be a dict")),
+                    }
+                }
+            };
+            $crate::emit(source, kind, data);
+            Ok(())
+        }
+
+        /// Re-send an event another module forwarded, unchanged
+        #[::pyo3::pyfunction]
+        pub fn publish_event_json(event: &str) -> ::pyo3::PyResult<()> {
+            $crate::publish_json(event).map_err(crate::errors::to_pyerr)
+        }
+
+        /// Call `callback` with every event of this module as a JSON string,
+        /// e.g. another module's `publish_event_json`; None stops forwarding.
+        ///
+        /// The callback runs on a background thread; exceptions are printed.
+        /// This module's own `publish_event_json` is rejected, as it would
+        /// forward every event back to itself forever.
+        #[::pyo3::pyfunction]
+        #[pyo3(signature = (callback=None))]
+        pub fn forward_events(py: ::pyo3::Python<'_>, callback: Option<::pyo3::PyObject>) -> ::pyo3::PyResult<()> {
+            if let Some(callback) = &callback {
+                // A built-in function's `__self__` is its module, named after
+                // the library crate, perhaps inside a package
+                let name = |object: &::pyo3::PyObject| -> Option<String> { object.getattr(py, "__name__").ok()?.extract(py).ok() };
+                let owner = callback.getattr(py, "__self__").ok().and_then(|module| name(&module));
+                let own_crate = module_path!().split("::").next().unwrap_or_default();
+                if name(callback).as_deref() == Some("publish_event_json")
+                    && owner.as_deref().and_then(|owner| owner.rsplit('.').next()) == Some(own_crate)
+                {
+                    return Err(crate::errors::validation("Cannot forward events to this module's own publish_event_json"));
+                }
+            }
+            let callback: Option<$crate::Callback> = callback.map(|callback| {
+                Box::new(move |event: &str| {
+                    ::pyo3::Python::with_gil(|py| {
+                        if let Err(e) = callback.call1(py, (event,)) {
+                            e.print(py);
+                        }
+                    })
+                }) as $crate::Callback
+            });
+            $crate::forward(callback).map_err(crate::errors::to_pyerr)
+        }
+
+        /// This module's event stream as `{"server", "clients", "forwarding", "dropped"}`
+        #[::pyo3::pyfunction]
+        #[allow(deprecated)]
+        pub fn event_stream_info(py: ::pyo3::Python<'_>) -> ::pyo3::PyResult<::pyo3::PyObject> {
+            use ::pyo3::ToPyObject;
+            let info = $crate::info();
+            let result = ::pyo3::types::PyDict::new(py);
+            result.set_item("server", info.server.map(|addr| addr.to_string()))?;
+            result.set_item("clients", info.clients)?;
+            result.set_item("forwarding", info.forwarding)?;
+            result.set_item("dropped", info.dropped)?;
+            Ok(result.to_object(py))
+        }
+
+        /// Add the event stream functions to the Python module
+        #[allow(deprecated)]
+        pub fn register(m: &::pyo3::types::PyModule) -> ::pyo3::PyResult<()> {
+            m.add_function(::pyo3::wrap_pyfunction!(start_event_server, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(stop_event_server, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(publish_event, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(publish_event_json, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(forward_events, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(event_stream_info, m)?)
+        }
+    };
+}
@@ -0,0 +1,295 @@
+//! Embedded WebSocket server broadcasting events
+//!
+//! A minimal RFC 6455 server on std networking: it answers the opening
+//! handshake on any path, then sends every event as one text frame. Client
+//! data frames are ignored; pings are answered and close frames echoed.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use aios_errors::{AiosError, Result};
+use base64::Engine;
+
+use crate::{lock, sha1, subscribe};
+
+/// Appended to the client's key before hashing it into the accept key
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest accepted handshake request
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// Largest accepted client frame; clients have nothing to send but control frames
+const MAX_CLIENT_FRAME: u64 = 64 * 1024;
+
+/// Events queued per client before it starts losing them
+const CLIENT_QUEUE: usize = 1024;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A client that cannot take a frame for this long is disconnected
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often idle loops check for shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Close status sent to clients when the server stops
+const CLOSE_GOING_AWAY: u16 = 1001;
+
+/// A running server; stops when dropped
+pub struct EventServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    clients: Arc<AtomicUsize>,
+    accept: Option<JoinHandle<()>>,
+}
+
+impl EventServer {
+    /// Listen on `addr` (port 0 picks a free port) and serve clients on
+    /// background threads
+    pub fn bind(addr: &str) -> Result<Self> {
+        let listener =
+            TcpListener::bind(addr).map_err(|e| AiosError::from(e).context(format!("Failed to bind {}", addr)))?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let clients = Arc::new(AtomicUsize::new(0));
+
+        let accept = {
+            let (stop, clients) = (Arc::clone(&stop), Arc::clone(&clients));
+            thread::Builder::new()
+                .name("aios-events".to_string())
+                .spawn(move || accept_loop(listener, stop, clients))?
+        };
+        Ok(Self { addr, stop, clients, accept: Some(accept) })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Clients currently connected
+    pub fn clients(&self) -> usize {
+        self.clients.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for EventServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Client threads notice within `POLL_INTERVAL` and close their connections
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+    }
+}
+
+fn accept_loop(listener: TcpListener, stop: Arc<AtomicBool>, clients: Arc<AtomicUsize>) {
+    static NEXT_CLIENT: AtomicU64 = AtomicU64::new(1);
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let id = NEXT_CLIENT.fetch_add(1, Ordering::Relaxed);
+                let (stop, clients) = (Arc::clone(&stop), Arc::clone(&clients));
+                let spawned = thread::Builder::new()
+                    .name(format!("aios-events-{}", id))
+                    .spawn(move || serve_client(stream, id, stop, clients));
+                if let Err(e) = spawned {
+                    eprintln!("aios-events: failed to start client thread: {}", e);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                eprintln!("aios-events: accept failed: {}", e);
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+fn serve_client(mut stream: TcpStream, id: u64, stop: Arc<AtomicBool>, clients: Arc<AtomicUsize>) {
+    let accepted = stream.set_nonblocking(false).and_then(|_| stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)));
+    if accepted.is_err() || !handshake(&mut stream).unwrap_or(false) {
+        let _ = stream.shutdown(Shutdown::Both);
+        return;
+    }
+    let Ok(reader) = stream.try_clone() else {
+        return;
+    };
+    let _ = stream.set_read_timeout(None);
+    let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+
+    let events = subscribe(CLIENT_QUEUE);
+    clients.fetch_add(1, Ordering::Relaxed);
+    let writer = Arc::new(Mutex::new(stream));
+    let closed = Arc::new(AtomicBool::new(false));
+    {
+        let (writer, closed) = (Arc::clone(&writer), Arc::clone(&closed));
+        let _ = thread::Builder::new()
+            .name(format!("aios-events-{}-read", id))
+            .spawn(move || read_loop(reader, writer, closed));
+    }
+
+    while !stop.load(Ordering::Relaxed) && !closed.load(Ordering::Relaxed) {
+        match events.recv_timeout(POLL_INTERVAL) {
+            Ok(event) => {
+                if write_frame(&mut *lock(&writer), OP_TEXT, event.as_bytes()).is_err() {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    clients.fetch_sub(1, Ordering::Relaxed);
+    let mut stream = lock(&writer);
+    if !closed.load(Ordering::Relaxed) {
+        let _ = write_frame(&mut *stream, OP_CLOSE, &CLOSE_GOING_AWAY.to_be_bytes());
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+/// Answer pings and close frames until the connection ends
+fn read_loop(mut reader: TcpStream, writer: Arc<Mutex<TcpStream>>, closed: Arc<AtomicBool>) {
+    while let Ok((opcode, payload)) = read_frame(&mut reader) {
+        match opcode {
+            OP_PING if write_frame(&mut *lock(&writer), OP_PONG, &payload).is_err() => break,
+            OP_CLOSE => {
+                // Echo the status code, as the protocol asks
+                let status = payload.get(..2).unwrap_or(&[]);
+                let _ = write_frame(&mut *lock(&writer), OP_CLOSE, status);
+                break;
+            }
+            // Data frames and pongs carry nothing the server needs
+            _ => {}
+        }
+    }
+    closed.store(true, Ordering::Relaxed);
+}
+
+/// Read the upgrade request and send the response; false when the request
+/// was not a WebSocket handshake (it then gets an HTTP error)
+fn handshake(stream: &mut TcpStream) -> io::Result<bool> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST_BYTES {
+            stream.write_all(b"HTTP/1.1 431 Request Header Fields Too Large\r\nConnection: close\r\nContent-Length: 0\r\n\r\n")?;
+            return Ok(false);
+        }
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(false);
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut lines = request.split("\r\n");
+    let is_get = lines.next().is_some_and(|line| line.starts_with("GET "));
+    let headers: HashMap<String, &str> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim()))
+        .collect();
+    let header = |name: &str| headers.get(name).copied().unwrap_or("");
+
+    let upgrade = header("upgrade").to_ascii_lowercase().contains("websocket");
+    let key = header("sec-websocket-key");
+    if !is_get || !upgrade || key.is_empty() {
+        stream.write_all(
+            b"HTTP/1.1 426 Upgrade Required\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        )?;
+        return Ok(false);
+    }
+    if header("sec-websocket-version") != "13" {
+        stream.write_all(b"HTTP/1.1 400 Bad Request\r\nSec-WebSocket-Version: 13\r\nConnection: close\r\nContent-Length: 0\r\n\r\n")?;
+        return Ok(false);
+    }
+
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    );
+    stream.write_all(response.as_bytes())?;
+    Ok(true)
+}
+
+fn accept_key(key: &str) -> String {
+    let digest = sha1::digest(format!("{}{}", key, HANDSHAKE_GUID).as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(digest)
+}
+
+/// Write one unmasked, unfragmented frame
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)
+}
+
+/// Read one masked client frame as (opcode, unmasked payload)
+fn read_frame(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    stream.read_exact(&mut head)?;
+    let opcode = head[0] & 0x0F;
+    if head[1] & 0x80 == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "client frames must be masked"));
+    }
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            stream.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_CLIENT_FRAME {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "client frame too large"));
+    }
+    let mut mask = [0u8; 4];
+    stream.read_exact(&mut mask)?;
+    let mut payload = vec![0u8; len as usize];
+    stream.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((opcode, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key_rfc6455() {
+        // RFC 6455 section 1.3
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+}
@@ -0,0 +1,368 @@
+//! Hierarchical Navigable Small World (HNSW) approximate nearest-neighbour index
+//!
+//! The index only stores the graph. Vectors stay in the owning store and are
+//! reached through distance callbacks, so the same graph works for full-precision
+//! and quantized embeddings. Shared by the CARMA fragment store and the support
+//! core's vector index; each draws node levels from its own `aios_rng` stream.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+/// Distance/id pair ordered by distance (ties broken by id)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    dist: f32,
+    id: usize,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.dist.total_cmp(&other.dist).then_with(|| self.id.cmp(&other.id))
+    }
+}
+
+/// Graph node: neighbour lists for every layer the node lives on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Node {
+    neighbors: Vec<Vec<usize>>,
+}
+
+/// HNSW graph over externally stored vectors
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HnswIndex {
+    /// `aios_rng` stream for level draws
+    stream: String,
+    m: usize,
+    m_max0: usize,
+    ef_construction: usize,
+    ef_search: usize,
+    level_mult: f64,
+    nodes: Vec<Node>,
+    entry_point: Option<usize>,
+    max_level: usize,
+}
+
+impl HnswIndex {
+    /// Create an empty index with `m` links per node and the given beam
+    /// widths, drawing levels from the `aios_rng` stream `stream`
+    pub fn new(stream: &str, m: usize, ef_construction: usize, ef_search: usize) -> Self {
+        let m = m.max(2);
+        Self {
+            stream: stream.to_string(),
+            m,
+            m_max0: m * 2,
+            ef_construction: ef_construction.max(m),
+            ef_search: ef_search.max(1),
+            level_mult: 1.0 / (m as f64).ln(),
+            nodes: Vec::new(),
+            entry_point: None,
+            max_level: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn ef_search(&self) -> usize {
+        self.ef_search
+    }
+
+    pub fn set_ef_search(&mut self, ef_search: usize) {
+        self.ef_search = ef_search.max(1);
+    }
+
+    /// Drop every node, keeping the configuration
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.entry_point = None;
+        self.max_level = 0;
+    }
+
+    /// Check that a deserialized graph only refers to its own nodes
+    pub fn validate(&self) -> Result<(), String> {
+        let len = self.nodes.len();
+        match self.entry_point {
+            None if len > 0 => return Err(format!("Graph of {} nodes has no entry point", len)),
+            Some(entry) if entry >= len => return Err(format!("Entry point {} is past the {} nodes", entry, len)),
+            Some(entry) if self.nodes[entry].neighbors.len() <= self.max_level => {
+                return Err(format!("Entry point {} is below the top layer {}", entry, self.max_level));
+            }
+            _ => {}
+        }
+        for (id, node) in self.nodes.iter().enumerate() {
+            if let Some(&neighbor) = node.neighbors.iter().flatten().find(|&&neighbor| neighbor >= len) {
+                return Err(format!("Node {} links to node {} of {}", id, neighbor, len));
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw a random level with the usual exponential decay
+    fn random_level(&self) -> usize {
+        let uniform: f64 = 1.0 - aios_rng::rng(&self.stream).gen::<f64>();
+        (-uniform.ln() * self.level_mult).floor() as usize
+    }
+
+    /// Insert the next node. Ids are dense: the new node gets id `len()`.
+    ///
+    /// `dist_to_new(id)` is the distance from the inserted vector to node `id`;
+    /// `pair_dist(a, b)` is the distance between two existing nodes.
+    pub fn insert<F, G>(&mut self, dist_to_new: F, pair_dist: G) -> usize
+    where
+        F: Fn(usize) -> f32,
+        G: Fn(usize, usize) -> f32,
+    {
+        let id = self.nodes.len();
+        let level = self.random_level();
+        self.nodes.push(Node {
+            neighbors: vec![Vec::new(); level + 1],
+        });
+
+        let entry = match self.entry_point {
+            Some(entry) => entry,
+            None => {
+                self.entry_point = Some(id);
+                self.max_level = level;
+                return id;
+            }
+        };
+
+        // Greedy descent through the layers above the new node's level
+        let mut current = Candidate { dist: dist_to_new(entry), id: entry };
+        let mut layer = self.max_level;
+        while layer > level {
+            current = self.greedy_closest(current, layer, &dist_to_new);
+            layer -= 1;
+        }
+
+        // Connect on every layer the new node shares with the graph
+        let mut entry_points = vec![current];
+        for layer in (0..=level.min(self.max_level)).rev() {
+            let candidates = self.search_layer(&entry_points, self.ef_construction, layer, &dist_to_new);
+            let max_links = if layer == 0 { self.m_max0 } else { self.m };
+            let selected: Vec<usize> = candidates.iter().take(self.m).map(|c| c.id).collect();
+
+            self.nodes[id].neighbors[layer] = selected.clone();
+            for &neighbor in &selected {
+                let links = &mut self.nodes[neighbor].neighbors[layer];
+                links.push(id);
+                if links.len() > max_links {
+                    let mut scored: Vec<Candidate> = links
+                        .iter()
+                        .map(|&other| Candidate { dist: pair_dist(neighbor, other), id: other })
+                        .collect();
+                    scored.sort();
+                    scored.truncate(max_links);
+                    *links = scored.into_iter().map(|c| c.id).collect();
+                }
+            }
+            entry_points = candidates;
+        }
+
+        if level > self.max_level {
+            self.max_level = level;
+            self.entry_point = Some(id);
+        }
+
+        id
+    }
+
+    /// Approximate k nearest neighbours as (id, distance), closest first
+    pub fn search<F>(&self, k: usize, dist_to_query: F) -> Vec<(usize, f32)>
+    where
+        F: Fn(usize) -> f32,
+    {
+        let entry = match self.entry_point {
+            Some(entry) => entry,
+            None => return Vec::new(),
+        };
+
+        let mut current = Candidate { dist: dist_to_query(entry), id: entry };
+        for layer in (1..=self.max_level).rev() {
+            current = self.greedy_closest(current, layer, &dist_to_query);
+        }
+
+        let ef = self.ef_search.max(k);
+        self.search_layer(&[current], ef, 0, &dist_to_query)
+            .into_iter()
+            .take(k)
+            .map(|c| (c.id, c.dist))
+            .collect()
+    }
+
+    /// Walk to the closest node on a single layer (beam width 1)
+    fn greedy_closest<F>(&self, start: Candidate, layer: usize, dist: &F) -> Candidate
+    where
+        F: Fn(usize) -> f32,
+    {
+        let mut best = start;
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for &neighbor in self.links(best.id, layer) {
+                let d = dist(neighbor);
+                if d < best.dist {
+                    best = Candidate { dist: d, id: neighbor };
+                    improved = true;
+                }
+            }
+        }
+        best
+    }
+
+    /// Beam search on one layer, returning up to `ef` candidates sorted by distance
+    fn search_layer<F>(&self, entry_points: &[Candidate], ef: usize, layer: usize, dist: &F) -> Vec<Candidate>
+    where
+        F: Fn(usize) -> f32,
+    {
+        let mut visited: HashSet<usize> = entry_points.iter().map(|c| c.id).collect();
+        // Min-heap of candidates to expand, max-heap of current results
+        let mut frontier: BinaryHeap<std::cmp::Reverse<Candidate>> =
+            entry_points.iter().copied().map(std::cmp::Reverse).collect();
+        let mut results: BinaryHeap<Candidate> = entry_points.iter().copied().collect();
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some(std::cmp::Reverse(candidate)) = frontier.pop() {
+            let worst = results.peek().map(|c| c.dist).unwrap_or(f32::INFINITY);
+            if candidate.dist > worst && results.len() >= ef {
+                break;
+            }
+
+            for &neighbor in self.links(candidate.id, layer) {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let d = dist(neighbor);
+                let worst = results.peek().map(|c| c.dist).unwrap_or(f32::INFINITY);
+                if results.len() < ef || d < worst {
+                    let next = Candidate { dist: d, id: neighbor };
+                    frontier.push(std::cmp::Reverse(next));
+                    results.push(next);
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        results.into_sorted_vec()
+    }
+
+    fn links(&self, id: usize, layer: usize) -> &[usize] {
+        self.nodes[id]
+            .neighbors
+            .get(layer)
+            .map(|links| links.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn random_vectors(count: usize, dimension: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count).map(|_| (0..dimension).map(|_| rng.gen_range(-1.0..1.0)).collect()).collect()
+    }
+
+    fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+    }
+
+    fn build(vectors: &[Vec<f32>]) -> HnswIndex {
+        let mut index = HnswIndex::new("hnsw.test", 16, 200, 64);
+        for (id, vector) in vectors.iter().enumerate() {
+            let inserted = index.insert(|i| squared_distance(vector, &vectors[i]), |a, b| squared_distance(&vectors[a], &vectors[b]));
+            assert_eq!(inserted, id);
+        }
+        index
+    }
+
+    #[test]
+    fn test_recall_against_brute_force() {
+        aios_rng::set_seed(Some(7)).unwrap();
+        let vectors = random_vectors(2000, 16, 1);
+        let index = build(&vectors);
+        assert_eq!(index.len(), 2000);
+        index.validate().unwrap();
+
+        let k = 10;
+        let queries = random_vectors(100, 16, 2);
+        let mut hits = 0;
+        for query in &queries {
+            let mut exact: Vec<(usize, f32)> =
+                vectors.iter().enumerate().map(|(i, v)| (i, squared_distance(query, v))).collect();
+            exact.sort_by(|a, b| a.1.total_cmp(&b.1));
+            let exact: HashSet<usize> = exact.iter().take(k).map(|&(i, _)| i).collect();
+            let found = index.search(k, |i| squared_distance(query, &vectors[i]));
+            assert_eq!(found.len(), k);
+            assert!(found.windows(2).all(|w| w[0].1 <= w[1].1), "results not sorted by distance");
+            hits += found.iter().filter(|(i, _)| exact.contains(i)).count();
+        }
+        let recall = hits as f64 / (k * queries.len()) as f64;
+        assert!(recall >= 0.95, "recall@{} is {}", k, recall);
+    }
+
+    #[test]
+    fn test_empty_and_single_node() {
+        let mut index = HnswIndex::new("hnsw.test", 16, 200, 64);
+        assert!(index.is_empty());
+        assert!(index.search(5, |_| unreachable!("no nodes to measure")).is_empty());
+        index.validate().unwrap();
+
+        let vector = [1.0, 2.0];
+        assert_eq!(index.insert(|_| unreachable!("no nodes to measure"), |_, _| unreachable!()), 0);
+        assert_eq!(index.len(), 1);
+        index.validate().unwrap();
+        assert_eq!(index.search(5, |_| squared_distance(&[0.0, 2.0], &vector)), vec![(0, 1.0)]);
+        assert!(index.search(0, |_| 0.0).is_empty());
+
+        index.clear();
+        assert!(index.is_empty());
+        assert!(index.search(1, |_| 0.0).is_empty());
+        index.validate().unwrap();
+    }
+
+    #[test]
+    fn test_validate_rejects_corrupt_graphs() {
+        let vectors = random_vectors(50, 4, 3);
+        let index = build(&vectors);
+        index.validate().unwrap();
+
+        let mut dangling = index.clone();
+        dangling.nodes[10].neighbors[0].push(50);
+        assert!(dangling.validate().unwrap_err().contains("links to node 50"));
+
+        let mut no_entry = index.clone();
+        no_entry.entry_point = None;
+        assert!(no_entry.validate().unwrap_err().contains("no entry point"));
+
+        let mut entry_past_end = index.clone();
+        entry_past_end.entry_point = Some(50);
+        assert!(entry_past_end.validate().unwrap_err().contains("past the 50 nodes"));
+
+        let mut entry_too_low = index.clone();
+        entry_too_low.max_level = entry_too_low.nodes[index.entry_point.unwrap()].neighbors.len();
+        assert!(entry_too_low.validate().unwrap_err().contains("below the top layer"));
+    }
+}
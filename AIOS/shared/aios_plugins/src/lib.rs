@@ -0,0 +1,478 @@
+//! Plugin callbacks invoked at extension points of the Rust pipelines
+//!
+//! Plugins are named callables registered for a `Hook`. The pipeline that
+//! owns the hook passes them a JSON payload:
+//!
+//! - `pre_retrieval` (carma) gets each query's text before it is searched
+//!   and may rewrite it (return a string) or reject it (return `false`);
+//!   rejected queries get no results.
+//! - `post_assessment` (luna) gets every arbiter assessment with the prompt
+//!   and response it scored; return values are ignored.
+//! - `pre_backup` (backup) gets the backup about to run and may veto it by
+//!   returning `false`.
+//!
+//! Returning null (or `true`) leaves the payload as it was. Plugins run in
+//! registration order, each seeing the previous one's result.
+//!
+//! Every call runs on its own thread and is waited for at most the plugin's
+//! timeout. A plugin that raises, times out or returns a value the hook does
+//! not accept is skipped, so the pipeline goes on as if it were not there.
+//! While a timed-out call is still running, the plugin is skipped instead
+//! of being called again, and after `max_failures` consecutive failures it
+//! is disabled until it is enabled again.
+//!
+//! Like `aios-errors`, this crate does not depend on PyO3: extension modules
+//! expand `python_plugins!` with the hooks they run. Each module keeps its
+//! own registry, so register plugins on the module that owns the hook.
+
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use aios_errors::{AiosError, Result};
+use serde_json::Value;
+
+#[doc(hidden)]
+pub use serde_json;
+
+/// Extension point in a pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PreRetrieval,
+    PostAssessment,
+    PreBackup,
+}
+
+impl Hook {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "pre_retrieval" => Some(Hook::PreRetrieval),
+            "post_assessment" => Some(Hook::PostAssessment),
+            "pre_backup" => Some(Hook::PreBackup),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PreRetrieval => "pre_retrieval",
+            Hook::PostAssessment => "post_assessment",
+            Hook::PreBackup => "pre_backup",
+        }
+    }
+}
+
+/// A plugin's code: payload in, result out, or an error message
+pub type Callable = Box<dyn Fn(Value) -> std::result::Result<Value, String> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PluginConfig {
+    /// Longest the pipeline waits for one call
+    pub timeout: Duration,
+    /// Consecutive failures that disable the plugin; 0 never disables it
+    pub max_failures: u32,
+}
+
+impl Default for PluginConfig {
+    fn default() -> Self {
+        Self { timeout: Duration::from_secs(1), max_failures: 3 }
+    }
+}
+
+/// A registered plugin and its call counters
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginInfo {
+    pub name: String,
+    pub hook: Hook,
+    pub config: PluginConfig,
+    pub enabled: bool,
+    pub calls: u64,
+    /// Calls that raised or returned an unusable value
+    pub failures: u64,
+    pub timeouts: u64,
+    /// Invocations skipped because the plugin was disabled or still busy
+    pub skipped: u64,
+    pub last_error: Option<String>,
+}
+
+/// A plugin returned `false`, stopping the pipeline step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    pub plugin: String,
+}
+
+struct Plugin {
+    name: String,
+    hook: Hook,
+    config: PluginConfig,
+    callable: Callable,
+    state: Mutex<PluginState>,
+}
+
+#[derive(Default)]
+struct PluginState {
+    disabled: bool,
+    /// A call is still running, possibly past its timeout
+    busy: bool,
+    calls: u64,
+    failures: u64,
+    timeouts: u64,
+    skipped: u64,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+}
+
+static REGISTRY: Mutex<Vec<Arc<Plugin>>> = Mutex::new(Vec::new());
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Add a plugin for `hook`, called after those already registered
+pub fn register(name: &str, hook: Hook, callable: Callable, config: PluginConfig) -> Result<()> {
+    if name.is_empty() {
+        return Err(AiosError::validation("Plugin name must not be empty"));
+    }
+    if config.timeout.is_zero() {
+        return Err(AiosError::validation("Plugin timeout must be positive"));
+    }
+    let mut registry = lock(&REGISTRY);
+    if registry.iter().any(|plugin| plugin.name == name) {
+        return Err(AiosError::validation(format!("Plugin {} is already registered", name)));
+    }
+    registry.push(Arc::new(Plugin { name: name.to_string(), hook, config, callable, state: Mutex::default() }));
+    Ok(())
+}
+
+/// Remove a plugin; false when none has that name. A call in progress
+/// still finishes.
+pub fn unregister(name: &str) -> bool {
+    let mut registry = lock(&REGISTRY);
+    let before = registry.len();
+    registry.retain(|plugin| plugin.name != name);
+    registry.len() != before
+}
+
+/// Enable or disable a plugin; enabling also clears its failure streak
+pub fn set_enabled(name: &str, enabled: bool) -> Result<()> {
+    let plugin = lock(&REGISTRY)
+        .iter()
+        .find(|plugin| plugin.name == name)
+        .cloned()
+        .ok_or_else(|| AiosError::validation(format!("No plugin named {}", name)))?;
+    let mut state = lock(&plugin.state);
+    state.disabled = !enabled;
+    if enabled {
+        state.consecutive_failures = 0;
+    }
+    Ok(())
+}
+
+/// Registered plugins in call order
+pub fn plugins() -> Vec<PluginInfo> {
+    lock(&REGISTRY)
+        .iter()
+        .map(|plugin| {
+            let state = lock(&plugin.state);
+            PluginInfo {
+                name: plugin.name.clone(),
+                hook: plugin.hook,
+                config: plugin.config,
+                enabled: !state.disabled,
+                calls: state.calls,
+                failures: state.failures,
+                timeouts: state.timeouts,
+                skipped: state.skipped,
+                last_error: state.last_error.clone(),
+            }
+        })
+        .collect()
+}
+
+/// True when any plugin is registered for `hook`, so callers can skip
+/// building its payload
+pub fn active(hook: Hook) -> bool {
+    lock(&REGISTRY).iter().any(|plugin| plugin.hook == hook)
+}
+
+fn registered(hook: Hook) -> Vec<Arc<Plugin>> {
+    lock(&REGISTRY).iter().filter(|plugin| plugin.hook == hook).cloned().collect()
+}
+
+/// Pass `payload` through `hook`'s plugins. Each may replace it with a
+/// value `accept` allows, or reject it with `false`, which skips the
+/// remaining plugins.
+pub fn run(hook: Hook, payload: Value, accept: impl Fn(&Value) -> bool) -> std::result::Result<Value, Rejection> {
+    let mut payload = payload;
+    for plugin in registered(hook) {
+        match call(&plugin, payload.clone()) {
+            Some(Value::Null | Value::Bool(true)) | None => {}
+            Some(Value::Bool(false)) => return Err(Rejection { plugin: plugin.name.clone() }),
+            Some(value) if accept(&value) => payload = value,
+            Some(_) => record_failure(&plugin, "returned a value this hook does not accept".to_string(), false),
+        }
+    }
+    Ok(payload)
+}
+
+/// Call `hook`'s plugins with `payload`, ignoring what they return
+pub fn notify(hook: Hook, payload: &Value) {
+    for plugin in registered(hook) {
+        call(&plugin, payload.clone());
+    }
+}
+
+/// One call with the plugin's timeout; None when it was skipped or failed
+fn call(plugin: &Arc<Plugin>, payload: Value) -> Option<Value> {
+    {
+        let mut state = lock(&plugin.state);
+        if state.disabled || state.busy {
+            state.skipped += 1;
+            return None;
+        }
+        state.busy = true;
+        state.calls += 1;
+    }
+
+    let (sender, receiver) = mpsc::sync_channel(1);
+    let worker = Arc::clone(plugin);
+    let spawned = thread::Builder::new().name(format!("aios-plugin-{}", plugin.name)).spawn(move || {
+        let result = (worker.callable)(payload);
+        lock(&worker.state).busy = false;
+        let _ = sender.send(result);
+    });
+    if let Err(e) = spawned {
+        lock(&plugin.state).busy = false;
+        record_failure(plugin, format!("failed to start: {}", e), false);
+        return None;
+    }
+
+    match receiver.recv_timeout(plugin.config.timeout) {
+        Ok(Ok(value)) => {
+            lock(&plugin.state).consecutive_failures = 0;
+            Some(value)
+        }
+        Ok(Err(message)) => {
+            record_failure(plugin, message, false);
+            None
+        }
+        Err(_) => {
+            let message = format!("timed out after {:.3}s", plugin.config.timeout.as_secs_f64());
+            record_failure(plugin, message, true);
+            None
+        }
+    }
+}
+
+fn record_failure(plugin: &Plugin, message: String, timed_out: bool) {
+    let mut state = lock(&plugin.state);
+    if timed_out {
+        state.timeouts += 1;
+    } else {
+        state.failures += 1;
+    }
+    state.consecutive_failures += 1;
+    state.last_error = Some(message);
+    if plugin.config.max_failures > 0 && state.consecutive_failures >= plugin.config.max_failures {
+        state.disabled = true;
+    }
+}
+
+/// Define `register_plugin`, `unregister_plugin`, `enable_plugin` and
+/// `list_plugins` in the calling crate, against its own `pyo3`, accepting
+/// the given hooks.
+///
+/// Expand it inside a module, e.g. `mod plugins { aios_plugins::python_plugins!(PreBackup); }`,
+/// next to the `errors` module from `aios_errors::python_exceptions!`, and
+/// call `plugins::register(m)` from the `#[pymodule]` function.
+// `crate::errors` deliberately names the calling crate's exceptions
+#[allow(clippy::crate_in_macro_def)]
+#[macro_export]
+macro_rules! python_plugins {
+    ($($hook:ident),+ $(,)?) => {
+        /// Hooks this module runs
+        const HOOKS: &[$crate::Hook] = &[$($crate::Hook::$hook),+];
+
+        /// Register `callback` under `name` for one of this module's hooks.
+        ///
+        /// The callback gets the hook's payload as Python objects and returns
+        /// None to keep it, False to reject it, or a replacement where the
+        /// hook allows one. It runs on a background thread; calls taking
+        /// longer than `timeout` seconds are abandoned, and after
+        /// `max_failures` consecutive errors or timeouts (0: never) the
+        /// plugin is disabled until `enable_plugin` is called.
+        #[::pyo3::pyfunction]
+        #[pyo3(signature = (name, hook, callback, timeout=1.0, max_failures=3))]
+        pub fn register_plugin(
+            name: &str,
+            hook: &str,
+            callback: ::pyo3::PyObject,
+            timeout: f64,
+            max_failures: u32,
+        ) -> ::pyo3::PyResult<()> {
+            let parsed = $crate::Hook::parse(hook).filter(|hook| HOOKS.contains(hook)).ok_or_else(|| {
+                let names: Vec<&str> = HOOKS.iter().map(|hook| hook.name()).collect();
+                crate::errors::validation(format!(
+                    "Hook {} is not run by this module; expected {}",
+                    hook,
+                    names.join(" or ")
+                ))
+            })?;
+            if !timeout.is_finite() || timeout <= 0.0 {
+                return Err(crate::errors::validation("timeout must be a positive number of seconds"));
+            }
+            let callable: $crate::Callable = Box::new(move |payload| {
+                ::pyo3::Python::with_gil(|py| {
+                    #[allow(deprecated)]
+                    let result = py.import("json").and_then(|json| {
+                        let argument = json.call_method1("loads", (payload.to_string(),))?;
+                        let returned = callback.call1(py, (argument,))?;
+                        json.call_method1("dumps", (returned,))?.extract::<String>()
+                    });
+                    let text = result.map_err(|e| e.to_string())?;
+                    $crate::serde_json::from_str(&text).map_err(|e| e.to_string())
+                })
+            });
+            let config = $crate::PluginConfig { timeout: ::std::time::Duration::from_secs_f64(timeout), max_failures };
+            $crate::register(name, parsed, callable, config).map_err(crate::errors::to_pyerr)
+        }
+
+        /// Remove a plugin; False when none has that name
+        #[::pyo3::pyfunction]
+        pub fn unregister_plugin(name: &str) -> bool {
+            $crate::unregister(name)
+        }
+
+        /// Enable (clearing its failure streak) or disable a plugin
+        #[::pyo3::pyfunction]
+        #[pyo3(signature = (name, enabled=true))]
+        pub fn enable_plugin(name: &str, enabled: bool) -> ::pyo3::PyResult<()> {
+            $crate::set_enabled(name, enabled).map_err(crate::errors::to_pyerr)
+        }
+
+        /// Registered plugins in call order, as dicts with their settings and
+        /// `calls`, `failures`, `timeouts`, `skipped` and `last_error`
+        #[::pyo3::pyfunction]
+        #[allow(deprecated)]
+        pub fn list_plugins(py: ::pyo3::Python<'_>) -> ::pyo3::PyResult<Vec<::pyo3::PyObject>> {
+            use ::pyo3::ToPyObject;
+            $crate::plugins()
+                .into_iter()
+                .map(|plugin| {
+                    let entry = ::pyo3::types::PyDict::new(py);
+                    entry.set_item("name", plugin.name)?;
+                    entry.set_item("hook", plugin.hook.name())?;
+                    entry.set_item("timeout", plugin.config.timeout.as_secs_f64())?;
+                    entry.set_item("max_failures", plugin.config.max_failures)?;
+                    entry.set_item("enabled", plugin.enabled)?;
+                    entry.set_item("calls", plugin.calls)?;
+                    entry.set_item("failures", plugin.failures)?;
+                    entry.set_item("timeouts", plugin.timeouts)?;
+                    entry.set_item("skipped", plugin.skipped)?;
+                    entry.set_item("last_error", plugin.last_error)?;
+                    Ok(entry.to_object(py))
+                })
+                .collect()
+        }
+
+        /// Add the plugin functions to the Python module
+        #[allow(deprecated)]
+        pub fn register(m: &::pyo3::types::PyModule) -> ::pyo3::PyResult<()> {
+            m.add_function(::pyo3::wrap_pyfunction!(register_plugin, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(unregister_plugin, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(enable_plugin, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(list_plugins, m)?)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // The registry is global: each test uses its own hook and plugin names
+
+    fn info(name: &str) -> PluginInfo {
+        plugins().into_iter().find(|plugin| plugin.name == name).expect("plugin registered")
+    }
+
+    fn config(timeout_ms: u64, max_failures: u32) -> PluginConfig {
+        PluginConfig { timeout: Duration::from_millis(timeout_ms), max_failures }
+    }
+
+    #[test]
+    fn test_timeout_counted_and_busy_plugin_skipped() {
+        register(
+            "slow",
+            Hook::PreBackup,
+            Box::new(|_| {
+                thread::sleep(Duration::from_millis(300));
+                Ok(Value::Bool(false))
+            }),
+            config(20, 0),
+        )
+        .unwrap();
+
+        // Its late veto is ignored
+        assert_eq!(run(Hook::PreBackup, json!({"backup": 1}), |_| true), Ok(json!({"backup": 1})));
+        let slow = info("slow");
+        assert_eq!((slow.calls, slow.timeouts, slow.failures), (1, 1, 0));
+        assert!(slow.last_error.unwrap().starts_with("timed out"));
+
+        // Still running, so not called again
+        assert!(run(Hook::PreBackup, json!({"backup": 2}), |_| true).is_ok());
+        let slow = info("slow");
+        assert_eq!((slow.calls, slow.skipped, slow.enabled), (1, 1, true));
+        assert!(unregister("slow"));
+        assert!(!unregister("slow"));
+        assert!(!active(Hook::PreBackup));
+    }
+
+    #[test]
+    fn test_disabled_after_max_failures() {
+        register("flaky", Hook::PostAssessment, Box::new(|_| Err("boom".to_string())), config(1000, 2)).unwrap();
+        notify(Hook::PostAssessment, &json!({}));
+        assert!(info("flaky").enabled);
+        notify(Hook::PostAssessment, &json!({}));
+        let flaky = info("flaky");
+        assert_eq!((flaky.calls, flaky.failures, flaky.enabled), (2, 2, false));
+        assert_eq!(flaky.last_error.as_deref(), Some("boom"));
+
+        notify(Hook::PostAssessment, &json!({}));
+        assert_eq!((info("flaky").calls, info("flaky").skipped), (2, 1));
+
+        // Enabling clears the streak: two more failures before it is disabled again
+        set_enabled("flaky", true).unwrap();
+        notify(Hook::PostAssessment, &json!({}));
+        assert!(info("flaky").enabled);
+        notify(Hook::PostAssessment, &json!({}));
+        assert!(!info("flaky").enabled);
+        assert!(set_enabled("missing", true).is_err());
+        unregister("flaky");
+    }
+
+    #[test]
+    fn test_plugins_chain_and_reject() {
+        let rewrite = |suffix: &'static str| -> Callable {
+            Box::new(move |payload| Ok(Value::String(format!("{}{}", payload.as_str().unwrap_or_default(), suffix))))
+        };
+        register("first", Hook::PreRetrieval, rewrite(" one"), config(1000, 3)).unwrap();
+        register("second", Hook::PreRetrieval, rewrite(" two"), config(1000, 3)).unwrap();
+        assert!(register("second", Hook::PreRetrieval, rewrite(""), config(1000, 3)).is_err());
+        assert!(register("", Hook::PreRetrieval, rewrite(""), config(1000, 3)).is_err());
+        assert!(register("instant", Hook::PreRetrieval, rewrite(""), config(0, 3)).is_err());
+
+        assert_eq!(run(Hook::PreRetrieval, json!("query"), Value::is_string), Ok(json!("query one two")));
+        // A value the hook does not accept is a failure and leaves the payload alone
+        assert_eq!(run(Hook::PreRetrieval, json!("query"), |value| value.as_str() != Some("query one")), Ok(json!("query two")));
+        assert_eq!(info("first").failures, 1);
+
+        register("veto", Hook::PreRetrieval, Box::new(|_| Ok(Value::Bool(false))), config(1000, 3)).unwrap();
+        assert_eq!(run(Hook::PreRetrieval, json!("query"), Value::is_string), Err(Rejection { plugin: "veto".to_string() }));
+        for name in ["first", "second", "veto"] {
+            unregister(name);
+        }
+    }
+}
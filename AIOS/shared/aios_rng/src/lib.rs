@@ -0,0 +1,169 @@
+//! Seedable randomness for the stochastic parts of the cores
+//!
+//! Dream simulations, k-means initialization and HNSW level draws take their
+//! generators from `rng`, named by a stream such as `"carma.kmeans"`. With no
+//! seed set each generator is seeded from the thread's entropy source, as
+//! `rand::thread_rng` was. With a seed, the n-th generator of a stream is
+//! derived from the seed, the stream name and n, so a run repeats exactly
+//! after the seed is set again, whatever other streams did in between.
+//!
+//! The seed is read from the `AIOS_SEED` environment variable once, on
+//! first use, and kept in this crate's state after that; `set_seed`
+//! replaces it there and restarts the stream counters, so setting the same
+//! seed twice replays the same sequences. Each core's module links its own
+//! copy of this crate, so `AIOS_SEED` in the environment seeds every core
+//! while a core's `set_seed` seeds that core. A malformed `AIOS_SEED` leaves
+//! the generators unseeded and makes `seed` and `set_seed` fail, so it is
+//! reported rather than silently ignored.
+//!
+//! Like `aios-errors`, this crate does not depend on PyO3: each extension
+//! module expands `python_rng!` to get `set_seed` and `get_seed`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use aios_errors::{AiosError, Result};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+pub use rand::Rng as RngExt;
+
+/// Generator handed out by `rng`
+pub type Rng = StdRng;
+
+/// Environment variable holding the seed
+pub const SEED_VAR: &str = "AIOS_SEED";
+
+/// The seed in effect and the generators handed out per stream since it
+/// was set
+struct Streams {
+    /// Error message for a malformed `AIOS_SEED`
+    seed: std::result::Result<Option<u64>, String>,
+    counters: HashMap<String, u64>,
+}
+
+/// None until first used, then initialized from `AIOS_SEED`
+static STREAMS: Mutex<Option<Streams>> = Mutex::new(None);
+
+/// Run `f` on the stream state, reading `AIOS_SEED` the first time
+fn with_streams<T>(f: impl FnOnce(&mut Streams) -> T) -> T {
+    let mut guard = STREAMS.lock().unwrap_or_else(|e| e.into_inner());
+    f(guard.get_or_insert_with(|| Streams { seed: env_seed(), counters: HashMap::new() }))
+}
+
+fn env_seed() -> std::result::Result<Option<u64>, String> {
+    match std::env::var(SEED_VAR) {
+        Ok(text) => text
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} must be an unsigned integer, got {:?}", SEED_VAR, text)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Seed this core's generators with `seed`, or return to entropy with
+/// None; fails, changing nothing, while a malformed `AIOS_SEED` is in
+/// effect
+pub fn set_seed(seed: Option<u64>) -> Result<()> {
+    with_streams(|streams| {
+        streams.seed.clone().map_err(AiosError::config)?;
+        *streams = Streams { seed: Ok(seed), counters: HashMap::new() };
+        Ok(())
+    })
+}
+
+/// The seed in effect; an `AIOS_SEED` that is not a u64 is an error
+pub fn seed() -> Result<Option<u64>> {
+    with_streams(|streams| streams.seed.clone().map_err(AiosError::config))
+}
+
+/// A generator for `stream`; reproducible when a seed is set
+pub fn rng(stream: &str) -> Rng {
+    let seeded = with_streams(|streams| {
+        let seed = streams.seed.clone().ok().flatten()?;
+        let counter = streams.counters.entry(stream.to_string()).or_insert(0);
+        let index = *counter;
+        *counter += 1;
+        Some(key(seed, stream, index))
+    });
+    match seeded {
+        Some(key) => StdRng::seed_from_u64(key),
+        None => StdRng::from_rng(rand::thread_rng()).expect("thread_rng does not fail"),
+    }
+}
+
+/// Pseudo-random key for `index` in `stream` under `seed`; the same inputs
+/// always give the same key, e.g. to break ties in a reproducible but
+/// seed-dependent order
+pub fn key(seed: u64, stream: &str, index: u64) -> u64 {
+    mix(mix(seed ^ fnv1a(stream)) ^ index)
+}
+
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// SplitMix64 finalizer, so nearby inputs give unrelated seeds
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Define `set_seed` and `get_seed` in the calling crate, against its own
+/// `pyo3`.
+///
+/// Expand it inside a module, e.g. `mod rng { aios_rng::python_rng!(); }`,
+/// next to the `errors` module from `aios_errors::python_exceptions!`, and
+/// call `rng::register(m)` from the `#[pymodule]` function.
+// `crate::errors` deliberately names the calling crate's exceptions
+#[allow(clippy::crate_in_macro_def)]
+#[macro_export]
+macro_rules! python_rng {
+    () => {
+        /// Make dream simulations, clustering and sampling in this core
+        /// reproducible; `seed=None` goes back to unseeded randomness.
+        /// Raises ConfigError while a malformed AIOS_SEED is set.
+        #[::pyo3::pyfunction]
+        #[pyo3(signature = (seed=None))]
+        pub fn set_seed(seed: Option<u64>) -> ::pyo3::PyResult<()> {
+            $crate::set_seed(seed).map_err(crate::errors::to_pyerr)
+        }
+
+        /// The seed in effect, or None when unseeded
+        #[::pyo3::pyfunction]
+        pub fn get_seed() -> ::pyo3::PyResult<Option<u64>> {
+            $crate::seed().map_err(crate::errors::to_pyerr)
+        }
+
+        /// Add the seeding functions to the Python module
+        #[allow(deprecated)]
+        pub fn register(m: &::pyo3::types::PyModule) -> ::pyo3::PyResult<()> {
+            m.add_function(::pyo3::wrap_pyfunction!(set_seed, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(get_seed, m)?)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_seed_replays_streams() {
+        set_seed(Some(7)).unwrap();
+        assert_eq!(seed().unwrap(), Some(7));
+        let first: Vec<u64> = (0..3).map(|_| rng("test.replay").gen()).collect();
+        let other: u64 = rng("test.other").gen();
+        set_seed(Some(7)).unwrap();
+        let _: u64 = rng("test.other").gen();
+        let again: Vec<u64> = (0..3).map(|_| rng("test.replay").gen()).collect();
+        assert_eq!(first, again);
+        set_seed(Some(8)).unwrap();
+        assert_ne!(rng("test.other").gen::<u64>(), other);
+        set_seed(None).unwrap();
+        assert_eq!(seed().unwrap(), None);
+    }
+}
@@ -0,0 +1,354 @@
+//! Versioned state archives across the AIOS cores
+//!
+//! One archive holds a section per core (luna karma, carma fragments, dream
+//! history, data pipeline stats, ...), each with its own format version.
+//! Archives are replaced atomically: the new file is written next to the
+//! old one, synced and renamed over it, so a crash leaves either the old or
+//! the new archive. A trailing SHA-256 rejects truncated or corrupted files.
+//!
+//! Layout (all integers little-endian):
+//!
+//! ```text
+//! magic "AIOSSTAT" | version u32 | section_count u32 | created f64
+//! | per section: name_len u16 | name | version u32 | data_len u64 | data
+//! | SHA-256 of everything before it
+//! ```
+//!
+//! Like `aios-errors`, this crate does not depend on PyO3: each extension
+//! module expands `python_state!` to get `save_state`, `restore_state` and
+//! `state_archive_info`. They accept the cores of any module: every core
+//! class names its section in `STATE_SECTION` and implements
+//! `export_state() -> (version, bytes)` and `import_state(version, bytes)`.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aios_errors::{AiosError, Result};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+
+pub const FORMAT_VERSION: u32 = 1;
+const MAGIC: &[u8; 8] = b"AIOSSTAT";
+const CHECKSUM_LEN: usize = 32;
+
+/// State of one core
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub name: String,
+    /// Format version of `data`, owned by the core
+    pub version: u32,
+    pub data: Vec<u8>,
+}
+
+/// Decode JSON state of core `name` written by a format version up to `supported`
+pub fn decode_json<T: DeserializeOwned>(name: &str, version: u32, data: &[u8], supported: u32) -> Result<T> {
+    check_version(name, version, supported)?;
+    serde_json::from_slice(data).map_err(|e| AiosError::validation(format!("Invalid {} state: {}", name, e)))
+}
+
+/// Reject state written by a newer format version
+pub fn check_version(name: &str, version: u32, supported: u32) -> Result<()> {
+    if version > supported {
+        return Err(AiosError::validation(format!(
+            "{} state version {} is newer than supported version {}",
+            name, version, supported
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Archive {
+    pub version: u32,
+    /// Seconds since the Unix epoch
+    pub created: f64,
+    pub sections: Vec<Section>,
+}
+
+impl Archive {
+    pub fn new(sections: Vec<Section>) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            created: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64(),
+            sections,
+        }
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let size: usize = self.sections.iter().map(|section| 14 + section.name.len() + section.data.len()).sum();
+        let mut buffer = Vec::with_capacity(24 + size + CHECKSUM_LEN);
+        buffer.extend_from_slice(MAGIC);
+        buffer.extend_from_slice(&self.version.to_le_bytes());
+        buffer.extend_from_slice(&(self.sections.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(&self.created.to_le_bytes());
+        for section in &self.sections {
+            let name_len = u16::try_from(section.name.len())
+                .map_err(|_| AiosError::validation(format!("Section name too long: {}", section.name)))?;
+            buffer.extend_from_slice(&name_len.to_le_bytes());
+            buffer.extend_from_slice(section.name.as_bytes());
+            buffer.extend_from_slice(&section.version.to_le_bytes());
+            buffer.extend_from_slice(&(section.data.len() as u64).to_le_bytes());
+            buffer.extend_from_slice(&section.data);
+        }
+        let checksum = Sha256::digest(&buffer);
+        buffer.extend_from_slice(&checksum);
+        Ok(buffer)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < MAGIC.len() + 16 + CHECKSUM_LEN || &data[..MAGIC.len()] != MAGIC {
+            return Err(AiosError::validation("Not an AIOS state archive"));
+        }
+        let (body, checksum) = data.split_at(data.len() - CHECKSUM_LEN);
+        if Sha256::digest(body).as_slice() != checksum {
+            return Err(AiosError::validation("State archive is corrupted (checksum mismatch)"));
+        }
+
+        let mut reader = Reader { data: body, offset: MAGIC.len() };
+        let version = u32::from_le_bytes(reader.array()?);
+        if version > FORMAT_VERSION {
+            return Err(AiosError::validation(format!(
+                "State archive version {} is newer than supported version {}",
+                version, FORMAT_VERSION
+            )));
+        }
+        let count = u32::from_le_bytes(reader.array()?);
+        let created = f64::from_le_bytes(reader.array()?);
+        let mut sections = Vec::new();
+        for _ in 0..count {
+            let name_len = u16::from_le_bytes(reader.array()?) as usize;
+            let name = String::from_utf8(reader.take(name_len)?.to_vec())
+                .map_err(|_| AiosError::validation("Invalid section name in state archive"))?;
+            let version = u32::from_le_bytes(reader.array()?);
+            let len = usize::try_from(u64::from_le_bytes(reader.array()?))
+                .map_err(|_| AiosError::validation("Truncated state archive"))?;
+            let data = reader.take(len)?.to_vec();
+            sections.push(Section { name, version, data });
+        }
+        Ok(Self { version, created, sections })
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| AiosError::validation("Truncated state archive"))?;
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("slice has N bytes"))
+    }
+}
+
+/// Replace the archive at `path` atomically (temp file, sync, rename)
+pub fn write_archive(path: &Path, archive: &Archive) -> Result<()> {
+    let buffer = archive.encode()?;
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty());
+    if let Some(parent) = parent {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = fs::File::create(&tmp_path)
+            .map_err(|e| AiosError::from(e).context(format!("Failed to write {}", tmp_path.display())))?;
+        file.write_all(&buffer)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path).map_err(|e| AiosError::from(e).context(format!("Failed to replace {}", path.display())))?;
+    // Persist the rename itself; not possible on every platform
+    if let Ok(directory) = fs::File::open(parent.unwrap_or(Path::new("."))) {
+        let _ = directory.sync_all();
+    }
+    Ok(())
+}
+
+pub fn read_archive(path: &Path) -> Result<Archive> {
+    let data = fs::read(path).map_err(|e| AiosError::from(e).context(format!("Failed to read {}", path.display())))?;
+    Archive::decode(&data).map_err(|e| e.context(path.display()))
+}
+
+/// Define `save_state`, `restore_state` and `state_archive_info` in the
+/// calling crate, against its own `pyo3`.
+///
+/// Expand it inside a module, e.g. `mod state { aios_state::python_state!(); }`,
+/// next to the `errors` module from `aios_errors::python_exceptions!`, and
+/// call `state::register(m)` from the `#[pymodule]` function.
+// `crate::errors` deliberately names the calling crate's exceptions
+#[allow(clippy::crate_in_macro_def)]
+#[macro_export]
+macro_rules! python_state {
+    () => {
+        /// Section name of a core, from its `STATE_SECTION` attribute
+        fn section_name(py: ::pyo3::Python<'_>, core: &::pyo3::PyObject) -> ::pyo3::PyResult<String> {
+            core.getattr(py, "STATE_SECTION")
+                .and_then(|name| name.extract::<String>(py))
+                .map_err(|_| ::pyo3::exceptions::PyTypeError::new_err("Expected an AIOS core with STATE_SECTION and export_state()"))
+        }
+
+        /// Write the state of `cores` to one archive at `path`.
+        ///
+        /// The archive is replaced atomically, so a crash during the save keeps
+        /// the previous one. Cores of any AIOS module are accepted, one per
+        /// section. Returns the section names written.
+        #[::pyo3::pyfunction]
+        pub fn save_state(py: ::pyo3::Python<'_>, path: &str, cores: Vec<::pyo3::PyObject>) -> ::pyo3::PyResult<Vec<String>> {
+            let mut sections: Vec<$crate::Section> = Vec::with_capacity(cores.len());
+            for core in &cores {
+                let name = section_name(py, core)?;
+                if sections.iter().any(|section| section.name == name) {
+                    return Err(crate::errors::validation(format!("More than one core with state section {}", name)));
+                }
+                let (version, data): (u32, ::pyo3::PyObject) = core.call_method0(py, "export_state")?.extract(py)?;
+                let data = data.extract::<&[u8]>(py)?.to_vec();
+                sections.push($crate::Section { name, version, data });
+            }
+            let names = sections.iter().map(|section| section.name.clone()).collect();
+            let archive = $crate::Archive::new(sections);
+            py.allow_threads(|| $crate::write_archive(::std::path::Path::new(path), &archive))
+                .map_err(crate::errors::to_pyerr)?;
+            Ok(names)
+        }
+
+        /// Restore `cores` from the archive at `path`; returns the sections restored.
+        ///
+        /// Cores without a section in the archive are skipped, or raise
+        /// ValidationError with `strict=True` before any core is changed.
+        /// Sections of cores not passed in are ignored.
+        #[::pyo3::pyfunction]
+        #[pyo3(signature = (path, cores, strict=false))]
+        pub fn restore_state(
+            py: ::pyo3::Python<'_>,
+            path: &str,
+            cores: Vec<::pyo3::PyObject>,
+            strict: bool,
+        ) -> ::pyo3::PyResult<Vec<String>> {
+            let archive = py
+                .allow_threads(|| $crate::read_archive(::std::path::Path::new(path)))
+                .map_err(crate::errors::to_pyerr)?;
+            let mut plan = Vec::with_capacity(cores.len());
+            for core in &cores {
+                let name = section_name(py, core)?;
+                match archive.section(&name) {
+                    Some(section) => plan.push((core, section)),
+                    None if strict => {
+                        return Err(crate::errors::validation(format!("State archive {} has no {} section", path, name)))
+                    }
+                    None => {}
+                }
+            }
+            let mut restored = Vec::with_capacity(plan.len());
+            for (core, section) in plan {
+                core.call_method1(py, "import_state", (section.version, section.data.as_slice()))?;
+                restored.push(section.name.clone());
+            }
+            Ok(restored)
+        }
+
+        /// `{"version", "created", "sections": {name: {"version", "bytes"}}}`
+        /// of the archive at `path`
+        #[::pyo3::pyfunction]
+        #[allow(deprecated)]
+        pub fn state_archive_info(py: ::pyo3::Python<'_>, path: &str) -> ::pyo3::PyResult<::pyo3::PyObject> {
+            use ::pyo3::ToPyObject;
+            let archive = py
+                .allow_threads(|| $crate::read_archive(::std::path::Path::new(path)))
+                .map_err(crate::errors::to_pyerr)?;
+            let sections = ::pyo3::types::PyDict::new(py);
+            for section in &archive.sections {
+                let entry = ::pyo3::types::PyDict::new(py);
+                entry.set_item("version", section.version)?;
+                entry.set_item("bytes", section.data.len())?;
+                sections.set_item(&section.name, entry)?;
+            }
+            let result = ::pyo3::types::PyDict::new(py);
+            result.set_item("version", archive.version)?;
+            result.set_item("created", archive.created)?;
+            result.set_item("sections", sections)?;
+            Ok(result.to_object(py))
+        }
+
+        /// Add the state archive functions to the Python module
+        #[allow(deprecated)]
+        pub fn register(m: &::pyo3::types::PyModule) -> ::pyo3::PyResult<()> {
+            m.add_function(::pyo3::wrap_pyfunction!(save_state, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(restore_state, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(state_archive_info, m)?)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aios_errors::ErrorKind;
+
+    fn archive() -> Archive {
+        Archive::new(vec![
+            Section { name: "luna".to_string(), version: 3, data: b"{\"karma\": 42.5}".to_vec() },
+            Section { name: "carma".to_string(), version: 1, data: (0..=255).collect() },
+            Section { name: "dream".to_string(), version: 2, data: Vec::new() },
+        ])
+    }
+
+    #[test]
+    fn test_archive_round_trip() {
+        let archive = archive();
+        assert_eq!(Archive::decode(&archive.encode().unwrap()).unwrap(), archive);
+        assert_eq!(archive.section("carma").map(|section| section.data.len()), Some(256));
+        assert!(archive.section("data").is_none());
+
+        let path = std::env::temp_dir().join(format!("aios_state_{}", std::process::id())).join("state.aios");
+        write_archive(&path, &archive).unwrap();
+        // Replacing keeps no temp file around
+        write_archive(&path, &archive).unwrap();
+        assert_eq!(read_archive(&path).unwrap(), archive);
+        assert!(!path.with_extension("tmp").exists());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_truncated_archive_rejected() {
+        let data = archive().encode().unwrap();
+        for len in [0, MAGIC.len(), data.len() / 2, data.len() - 1] {
+            let error = Archive::decode(&data[..len]).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::Validation, "{} bytes: {}", len, error);
+        }
+        // Cut short before its checksum was taken, so only the lengths tell
+        let mut short = data[..data.len() - CHECKSUM_LEN - 10].to_vec();
+        let checksum = Sha256::digest(&short);
+        short.extend_from_slice(&checksum);
+        assert_eq!(Archive::decode(&short).unwrap_err().to_string(), "Truncated state archive");
+
+        let mut corrupted = data.clone();
+        corrupted[30] ^= 1;
+        assert!(Archive::decode(&corrupted).unwrap_err().to_string().contains("checksum mismatch"));
+    }
+
+    #[test]
+    fn test_newer_versions_rejected() {
+        let mut archive = archive();
+        archive.version = FORMAT_VERSION + 1;
+        assert!(Archive::decode(&archive.encode().unwrap()).unwrap_err().to_string().contains("newer"));
+
+        assert_eq!(decode_json::<f64>("luna", 2, b"42.5", 2).unwrap(), 42.5);
+        assert!(decode_json::<f64>("luna", 3, b"42.5", 2).is_err());
+        assert_eq!(decode_json::<f64>("luna", 1, b"{", 2).unwrap_err().kind(), ErrorKind::Validation);
+    }
+}
@@ -0,0 +1,366 @@
+//! Shared SQLite store for durable core state
+//!
+//! One database file, set with `path` in the `[store]` section of the AIOS
+//! config, replaces the JSON files the cores kept their state in. Each core
+//! works in its own namespace: a row set in the shared key/value table
+//! `aios_kv` for small JSON documents (karma, pipeline statistics), plus
+//! tables of its own named `<namespace>_<table>`, created by migrations.
+//!
+//! A namespace's migrations are numbered from 1 and applied in order, each
+//! in its own transaction, when the namespace is opened; the versions
+//! applied are recorded in `aios_migrations`. A database migrated by a newer
+//! build is refused rather than used with a schema this build does not know.
+//!
+//! The database runs in WAL mode, so several cores and processes can use
+//! one file at once: readers do not block the writer, and writers wait up
+//! to `BUSY_TIMEOUT_MS` for each other.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aios_errors::{AiosError, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+mod sqlite;
+
+pub use sqlite::{Connection, Statement, Value};
+
+/// How long a write waits for another connection's transaction
+const BUSY_TIMEOUT_MS: u64 = 5000;
+
+/// Longest namespace name
+const MAX_NAMESPACE_LEN: usize = 32;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS aios_migrations (
+        namespace TEXT NOT NULL,
+        version INTEGER NOT NULL,
+        applied_at REAL NOT NULL,
+        PRIMARY KEY (namespace, version)
+    );
+    CREATE TABLE IF NOT EXISTS aios_kv (
+        namespace TEXT NOT NULL,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        updated_at REAL NOT NULL,
+        PRIMARY KEY (namespace, key)
+    );";
+
+/// One schema change of a namespace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    /// 1 for the first migration, then consecutive
+    pub version: u32,
+    pub sql: &'static str,
+}
+
+/// An open database; clones share the connection
+#[derive(Clone)]
+pub struct Store {
+    path: Arc<PathBuf>,
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl Store {
+    /// Open or create the database at `path` and its parent directories
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| AiosError::from(e).context(format!("Failed to create {}", parent.display())))?;
+        }
+        let connection = Connection::open(path)?;
+        connection.busy_timeout(BUSY_TIMEOUT_MS)?;
+        // journal_mode returns a row, so it goes through query rather than execute_batch
+        connection.query("PRAGMA journal_mode=WAL", &[])?;
+        connection.execute_batch("PRAGMA synchronous=NORMAL; PRAGMA foreign_keys=ON;")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self { path: Arc::new(path.to_path_buf()), connection: Arc::new(Mutex::new(connection)) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Open `name`'s namespace, first applying the migrations it has not had
+    pub fn namespace(&self, name: &str, migrations: &[Migration]) -> Result<Namespace> {
+        validate_namespace(name)?;
+        for (expected, migration) in (1..).zip(migrations) {
+            if migration.version != expected {
+                return Err(AiosError::config(format!(
+                    "Migrations of {} must be numbered 1, 2, ...; found {} at position {}",
+                    name, migration.version, expected
+                )));
+            }
+        }
+        let namespace = Namespace { store: self.clone(), name: name.to_string() };
+        let latest = migrations.len() as u32;
+        namespace.transaction(|connection| {
+            let current = schema_version(connection, name)?;
+            if current > latest {
+                return Err(AiosError::config(format!(
+                    "Store namespace {} is at schema version {}, newer than this build supports ({})",
+                    name, current, latest
+                )));
+            }
+            for migration in &migrations[current as usize..] {
+                connection
+                    .execute_batch(migration.sql)
+                    .map_err(|e| e.context(format!("Migration {} of {} failed", migration.version, name)))?;
+                connection.execute(
+                    "INSERT INTO aios_migrations (namespace, version, applied_at) VALUES (?, ?, ?)",
+                    &[name.into(), (migration.version as i64).into(), now().into()],
+                )?;
+            }
+            Ok(())
+        })?;
+        Ok(namespace)
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// One core's part of the store
+#[derive(Clone)]
+pub struct Namespace {
+    store: Store,
+    name: String,
+}
+
+impl Namespace {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Highest migration applied to this namespace (0 before any)
+    pub fn schema_version(&self) -> Result<u32> {
+        schema_version(&self.store.connection(), &self.name)
+    }
+
+    /// Value stored under `key`, decoded from JSON
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let rows = self.query(
+            "SELECT value FROM aios_kv WHERE namespace = ? AND key = ?",
+            &[self.name.as_str().into(), key.into()],
+        )?;
+        let Some(text) = rows.first().and_then(|row| row.first()).and_then(Value::as_str) else {
+            return Ok(None);
+        };
+        serde_json::from_str(text)
+            .map(Some)
+            .map_err(|e| AiosError::io(format!("Stored value {}/{} is not valid: {}", self.name, key, e)))
+    }
+
+    /// Store `value` as JSON under `key`, replacing any previous value
+    pub fn set<T: Serialize + ?Sized>(&self, key: &str, value: &T) -> Result<()> {
+        let text = serde_json::to_string(value)?;
+        self.execute(
+            "INSERT INTO aios_kv (namespace, key, value, updated_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT (namespace, key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+            &[self.name.as_str().into(), key.into(), text.into(), now().into()],
+        )?;
+        Ok(())
+    }
+
+    /// Remove `key`; false when it was not set
+    pub fn delete(&self, key: &str) -> Result<bool> {
+        let removed =
+            self.execute("DELETE FROM aios_kv WHERE namespace = ? AND key = ?", &[self.name.as_str().into(), key.into()])?;
+        Ok(removed > 0)
+    }
+
+    /// Run one statement; returns the rows it changed
+    pub fn execute(&self, sql: &str, params: &[Value]) -> Result<usize> {
+        self.store.connection().execute(sql, params)
+    }
+
+    /// Run one statement and collect its rows
+    pub fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Vec<Value>>> {
+        self.store.connection().query(sql, params)
+    }
+
+    /// Run `f` in a write transaction, committed if it returns Ok and rolled
+    /// back otherwise
+    pub fn transaction<R>(&self, f: impl FnOnce(&Connection) -> Result<R>) -> Result<R> {
+        let connection = self.store.connection();
+        connection.execute_batch("BEGIN IMMEDIATE")?;
+        match f(&connection) {
+            Ok(result) => {
+                if let Err(e) = connection.execute_batch("COMMIT") {
+                    let _ = connection.execute_batch("ROLLBACK");
+                    return Err(e);
+                }
+                Ok(result)
+            }
+            Err(e) => {
+                let _ = connection.execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
+    }
+}
+
+fn schema_version(connection: &Connection, namespace: &str) -> Result<u32> {
+    let rows = connection.query("SELECT MAX(version) FROM aios_migrations WHERE namespace = ?", &[namespace.into()])?;
+    Ok(rows.first().and_then(|row| row.first()).and_then(Value::as_i64).unwrap_or(0) as u32)
+}
+
+/// Namespaces are lowercase identifiers, since they prefix table names
+fn validate_namespace(name: &str) -> Result<()> {
+    let valid = name.len() <= MAX_NAMESPACE_LEN
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(AiosError::validation(format!(
+            "Invalid store namespace {:?}: expected a lowercase identifier of at most {} characters",
+            name, MAX_NAMESPACE_LEN
+        )))
+    }
+}
+
+fn now() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTES: &[Migration] = &[
+        Migration { version: 1, sql: "CREATE TABLE notes_items (id INTEGER PRIMARY KEY, body TEXT NOT NULL);" },
+        Migration { version: 2, sql: "ALTER TABLE notes_items ADD COLUMN score REAL;" },
+    ];
+
+    /// A fresh database file, removed with its directory by the caller
+    fn database(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("aios_store_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("nested").join("aios.db")
+    }
+
+    #[test]
+    fn test_key_values() {
+        let path = database("kv");
+        let store = Store::open(&path).unwrap();
+        let karma = store.namespace("luna", &[]).unwrap();
+        let other = store.namespace("dream", &[]).unwrap();
+
+        assert_eq!(karma.get::<f64>("karma").unwrap(), None);
+        karma.set("karma", &42.5).unwrap();
+        karma.set("karma", &43.5).unwrap();
+        other.set("karma", &vec!["a", "b"]).unwrap();
+        assert_eq!(karma.get::<f64>("karma").unwrap(), Some(43.5));
+        assert_eq!(other.get::<Vec<String>>("karma").unwrap(), Some(vec!["a".to_string(), "b".to_string()]));
+        assert!(karma.get::<String>("karma").is_err());
+
+        assert!(karma.delete("karma").unwrap());
+        assert!(!karma.delete("karma").unwrap());
+        assert_eq!(karma.get::<f64>("karma").unwrap(), None);
+        drop((karma, store));
+
+        // Kept across reopening
+        let store = Store::open(&path).unwrap();
+        assert_eq!(store.namespace("dream", &[]).unwrap().get::<Vec<String>>("karma").unwrap().map(|v| v.len()), Some(2));
+        drop(store);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap().parent().unwrap());
+    }
+
+    #[test]
+    fn test_migrations() {
+        let path = database("migrations");
+        let store = Store::open(&path).unwrap();
+        let notes = store.namespace("notes", &NOTES[..1]).unwrap();
+        assert_eq!(notes.schema_version().unwrap(), 1);
+        notes.execute("INSERT INTO notes_items (body) VALUES (?)", &["first".into()]).unwrap();
+
+        // Only the new migration runs
+        let notes = store.namespace("notes", NOTES).unwrap();
+        assert_eq!(notes.schema_version().unwrap(), 2);
+        let rows = notes.query("SELECT body, score FROM notes_items", &[]).unwrap();
+        assert_eq!(rows, vec![vec![Value::from("first"), Value::Null]]);
+
+        // Refused by an older build
+        let error = store.namespace("notes", &NOTES[..1]).err().unwrap();
+        assert!(error.to_string().contains("newer than this build"), "{}", error);
+
+        let misnumbered = [Migration { version: 2, sql: "" }];
+        assert!(store.namespace("other", &misnumbered).is_err());
+        assert!(store.namespace("Notes", &[]).is_err());
+        assert!(store.namespace("1notes", &[]).is_err());
+
+        // A failing migration leaves nothing behind
+        let failing = [
+            Migration { version: 1, sql: "CREATE TABLE broken_items (id INTEGER);" },
+            Migration { version: 2, sql: "CREATE TABLE broken_items (id INTEGER);" },
+        ];
+        assert!(store.namespace("broken", &failing).is_err());
+        let broken = store.namespace("broken", &failing[..1]).unwrap();
+        assert_eq!(broken.schema_version().unwrap(), 1);
+        drop((notes, broken, store));
+        let _ = std::fs::remove_dir_all(path.parent().unwrap().parent().unwrap());
+    }
+
+    #[test]
+    fn test_transactions() {
+        let path = database("transactions");
+        let store = Store::open(&path).unwrap();
+        let notes = store.namespace("notes", NOTES).unwrap();
+        let count = |notes: &Namespace| notes.query("SELECT COUNT(*) FROM notes_items", &[]).unwrap()[0][0].as_i64();
+
+        let inserted = notes
+            .transaction(|connection| {
+                let mut insert = connection.prepare("INSERT INTO notes_items (body, score) VALUES (?, ?)")?;
+                for i in 0..3 {
+                    insert.execute(&[format!("note {}", i).into(), Value::from(i as f64 / 2.0)])?;
+                }
+                Ok(3)
+            })
+            .unwrap();
+        assert_eq!((inserted, count(&notes)), (3, Some(3)));
+
+        let failed = notes.transaction(|connection| {
+            connection.execute("DELETE FROM notes_items", &[])?;
+            connection.execute("INSERT INTO missing_table VALUES (1)", &[])
+        });
+        assert!(failed.is_err());
+        assert_eq!(count(&notes), Some(3));
+
+        // Usable again after the rollback
+        notes.transaction(|connection| connection.execute("DELETE FROM notes_items WHERE score > ?", &[0.6.into()])).unwrap();
+        assert_eq!(count(&notes), Some(2));
+        drop((notes, store));
+        let _ = std::fs::remove_dir_all(path.parent().unwrap().parent().unwrap());
+    }
+
+    #[test]
+    fn test_values() {
+        let path = database("values");
+        let store = Store::open(&path).unwrap();
+        let values = store
+            .namespace("values", &[Migration { version: 1, sql: "CREATE TABLE values_all (a, b, c, d, e);" }])
+            .unwrap();
+        let row = vec![
+            Value::Null,
+            Value::Integer(i64::MIN),
+            Value::Real(-1.25),
+            Value::Text("héllo\0world".to_string()),
+            Value::Blob(vec![0, 255, 1]),
+        ];
+        values.execute("INSERT INTO values_all VALUES (?, ?, ?, ?, ?)", &row).unwrap();
+        assert_eq!(values.query("SELECT * FROM values_all", &[]).unwrap(), vec![row]);
+        assert_eq!(Value::Integer(2).as_f64(), Some(2.0));
+        assert!(values.execute("INSERT INTO values_all VALUES (?)", &[Value::Null]).is_err());
+        drop((values, store));
+        let _ = std::fs::remove_dir_all(path.parent().unwrap().parent().unwrap());
+    }
+}
@@ -0,0 +1,170 @@
+//! The store's SQLite connection, over rusqlite
+//!
+//! SQLite is compiled into the crate (rusqlite's `bundled` feature), so no
+//! system library is needed on any platform. Only what the store needs is
+//! exposed: running SQL with `Value` parameters and reading rows of `Value`s.
+//! Errors carry SQLite's message and become `AiosError::io`.
+
+use std::path::Path;
+use std::time::Duration;
+
+use aios_errors::{AiosError, Result};
+use rusqlite::types::{ToSqlOutput, ValueRef};
+use rusqlite::{params_from_iter, ToSql};
+
+/// A column value or statement parameter
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(value) => Some(*value as f64),
+            Value::Real(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Integer(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Real(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Text(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Text(value)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Value::Blob(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+impl ToSql for Value {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::Borrowed(match self {
+            Value::Null => ValueRef::Null,
+            Value::Integer(value) => ValueRef::Integer(*value),
+            Value::Real(value) => ValueRef::Real(*value),
+            Value::Text(value) => ValueRef::Text(value.as_bytes()),
+            Value::Blob(value) => ValueRef::Blob(value),
+        }))
+    }
+}
+
+impl From<ValueRef<'_>> for Value {
+    fn from(value: ValueRef<'_>) -> Self {
+        match value {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(value) => Value::Integer(value),
+            ValueRef::Real(value) => Value::Real(value),
+            ValueRef::Text(text) => Value::Text(String::from_utf8_lossy(text).into_owned()),
+            ValueRef::Blob(data) => Value::Blob(data.to_vec()),
+        }
+    }
+}
+
+/// An open database connection
+pub struct Connection {
+    inner: rusqlite::Connection,
+}
+
+impl Connection {
+    pub fn open(path: &Path) -> Result<Self> {
+        let inner = rusqlite::Connection::open(path).map_err(|e| error(&format!("Failed to open {}", path.display()), e))?;
+        Ok(Self { inner })
+    }
+
+    /// Wait up to `ms` for locks held by other connections
+    pub fn busy_timeout(&self, ms: u64) -> Result<()> {
+        self.inner.busy_timeout(Duration::from_millis(ms)).map_err(|e| error("Failed to set busy timeout", e))
+    }
+
+    /// Run one or more statements without parameters
+    pub fn execute_batch(&self, sql: &str) -> Result<()> {
+        self.inner.execute_batch(sql).map_err(|e| AiosError::io(format!("SQLite error: {}", e)))
+    }
+
+    pub fn prepare(&self, sql: &str) -> Result<Statement<'_>> {
+        let inner = self.inner.prepare(sql).map_err(|e| error("Failed to prepare statement", e))?;
+        Ok(Statement { inner })
+    }
+
+    /// Run one statement; returns the rows it changed
+    pub fn execute(&self, sql: &str, params: &[Value]) -> Result<usize> {
+        self.prepare(sql)?.execute(params)
+    }
+
+    /// Run one statement and collect its rows
+    pub fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Vec<Value>>> {
+        self.prepare(sql)?.query(params)
+    }
+}
+
+/// A prepared statement, reusable with different parameters
+pub struct Statement<'c> {
+    inner: rusqlite::Statement<'c>,
+}
+
+impl Statement<'_> {
+    pub fn execute(&mut self, params: &[Value]) -> Result<usize> {
+        self.inner.execute(params_from_iter(params)).map_err(|e| error("Statement failed", e))
+    }
+
+    pub fn query(&mut self, params: &[Value]) -> Result<Vec<Vec<Value>>> {
+        let columns = self.inner.column_count();
+        let mut rows = self.inner.query(params_from_iter(params)).map_err(|e| error("Query failed", e))?;
+        let mut result = Vec::new();
+        while let Some(row) = rows.next().map_err(|e| error("Query failed", e))? {
+            let values = (0..columns).map(|column| row.get_ref(column).map(Value::from));
+            result.push(values.collect::<rusqlite::Result<_>>().map_err(|e| error("Query failed", e))?);
+        }
+        Ok(result)
+    }
+}
+
+fn error(context: &str, e: rusqlite::Error) -> AiosError {
+    AiosError::io(format!("{}: SQLite error: {}", context, e))
+}
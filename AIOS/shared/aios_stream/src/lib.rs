@@ -0,0 +1,193 @@
+//! Chunked iteration over large result sets
+//!
+//! Methods such as `get_all_fragments` copy every item into one Python list.
+//! Their `iter_*` counterparts return an iterator that fetches `chunk_size`
+//! items at a time from the core, so a million-item collection can be
+//! walked with one chunk in memory. Between chunks the core is free: items
+//! added meanwhile are reached at the end, and removing items shifts the
+//! ones after them by the number removed.
+//!
+//! Like `aios-errors`, this crate does not depend on PyO3: `Chunked` holds
+//! the iteration state, and each extension module expands
+//! `python_iterator!` for its iterator classes.
+
+use std::collections::VecDeque;
+
+use aios_errors::{AiosError, Result};
+
+/// Items fetched per chunk unless a method is given `chunk_size`
+pub const DEFAULT_CHUNK_SIZE: usize = 1024;
+
+/// Position in a sequence read a chunk at a time
+///
+/// ```
+/// let items: Vec<u32> = (0..5).collect();
+/// let mut chunks = aios_stream::Chunked::new(2).unwrap();
+/// let mut seen = Vec::new();
+/// while let Some(item) = chunks.next(|offset, limit| Ok(aios_stream::window(&items, offset, limit))).unwrap() {
+///     seen.push(item);
+/// }
+/// assert_eq!(seen, items);
+/// assert_eq!(chunks.position(), 5);
+/// ```
+#[derive(Debug)]
+pub struct Chunked<T> {
+    offset: usize,
+    chunk_size: usize,
+    buffer: VecDeque<T>,
+    exhausted: bool,
+}
+
+impl<T> Chunked<T> {
+    pub fn new(chunk_size: usize) -> Result<Self> {
+        if chunk_size == 0 {
+            return Err(AiosError::validation("chunk_size must be at least 1"));
+        }
+        Ok(Self { offset: 0, chunk_size, buffer: VecDeque::new(), exhausted: false })
+    }
+
+    /// Items returned so far
+    pub fn position(&self) -> usize {
+        self.offset - self.buffer.len()
+    }
+
+    /// The next item, calling `fetch(offset, limit)` for the next chunk when
+    /// the current one is used up. A chunk shorter than `limit` ends the
+    /// sequence.
+    pub fn next(&mut self, fetch: impl FnOnce(usize, usize) -> Result<Vec<T>>) -> Result<Option<T>> {
+        if self.buffer.is_empty() && !self.exhausted {
+            let chunk = fetch(self.offset, self.chunk_size)?;
+            self.exhausted = chunk.len() < self.chunk_size;
+            self.offset += chunk.len();
+            self.buffer.extend(chunk);
+        }
+        Ok(self.buffer.pop_front())
+    }
+}
+
+/// Copies of `items[offset..offset + limit]`, clamped to the slice
+pub fn window<T: Clone>(items: &[T], offset: usize, limit: usize) -> Vec<T> {
+    items.iter().skip(offset).take(limit).cloned().collect()
+}
+
+/// Define a Python iterator class over items of an owning `#[pyclass]`, in
+/// the calling crate and against its own `pyo3`.
+///
+/// `fetch` gets the GIL token, a reference to the owner, the offset and the
+/// chunk size, and returns an `aios_errors::Result<Vec<Item>>`:
+///
+/// ```ignore
+/// aios_stream::python_iterator!(
+///     /// Iterator over the core's fragments
+///     FragmentIterator, RustCarmaCore, MemoryFragment,
+///     |py, core, offset, limit| Ok(core.fragment_window(py, offset, limit))
+/// );
+/// ```
+///
+/// The owner is borrowed only while a chunk is fetched. Create iterators
+/// with `Name::new(owner, chunk_size)`; errors go through the calling
+/// crate's `errors::to_pyerr`.
+// `crate::errors` deliberately names the calling crate's exceptions
+#[allow(clippy::crate_in_macro_def)]
+#[macro_export]
+macro_rules! python_iterator {
+    ($(#[$meta:meta])* $name:ident, $owner:ty, $item:ty, |$py:ident, $core:ident, $offset:ident, $limit:ident| $fetch:expr) => {
+        $(#[$meta])*
+        #[::pyo3::pyclass]
+        pub struct $name {
+            owner: ::pyo3::Py<$owner>,
+            chunks: $crate::Chunked<$item>,
+        }
+
+        impl $name {
+            pub fn new(owner: ::pyo3::Py<$owner>, chunk_size: usize) -> ::pyo3::PyResult<Self> {
+                let chunks = $crate::Chunked::new(chunk_size).map_err(crate::errors::to_pyerr)?;
+                Ok(Self { owner, chunks })
+            }
+        }
+
+        #[::pyo3::pymethods]
+        impl $name {
+            fn __iter__(slf: ::pyo3::PyRef<'_, Self>) -> ::pyo3::PyRef<'_, Self> {
+                slf
+            }
+
+            fn __next__(&mut self, $py: ::pyo3::Python<'_>) -> ::pyo3::PyResult<Option<$item>> {
+                let owner = self.owner.borrow($py);
+                let $core: &$owner = &owner;
+                self.chunks.next(|$offset, $limit| $fetch).map_err(crate::errors::to_pyerr)
+            }
+
+            /// Items returned so far
+            #[getter]
+            fn position(&self) -> usize {
+                self.chunks.position()
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every item of `items` through `chunk_size` chunks, and the fetches made
+    fn drain(items: &[u32], chunk_size: usize) -> (Vec<u32>, Vec<(usize, usize)>) {
+        let mut chunks = Chunked::new(chunk_size).unwrap();
+        let mut fetches = Vec::new();
+        let mut seen = Vec::new();
+        while let Some(item) = chunks
+            .next(|offset, limit| {
+                fetches.push((offset, limit));
+                Ok(window(items, offset, limit))
+            })
+            .unwrap()
+        {
+            seen.push(item);
+            assert_eq!(chunks.position(), seen.len());
+        }
+        // Exhausted: no more fetches
+        assert!(chunks.next(|_, _| panic!("fetched past the end")).unwrap().is_none());
+        (seen, fetches)
+    }
+
+    #[test]
+    fn test_chunking() {
+        let items: Vec<u32> = (0..10).collect();
+        assert_eq!(drain(&items, 4), (items.clone(), vec![(0, 4), (4, 4), (8, 4)]));
+        // A full last chunk takes one more, empty fetch to notice the end
+        assert_eq!(drain(&items, 5), (items.clone(), vec![(0, 5), (5, 5), (10, 5)]));
+        assert_eq!(drain(&items, 100), (items.clone(), vec![(0, 100)]));
+        assert_eq!(drain(&[], 3), (vec![], vec![(0, 3)]));
+        assert!(Chunked::<u32>::new(0).is_err());
+    }
+
+    #[test]
+    fn test_items_added_between_chunks_are_reached() {
+        let mut items: Vec<u32> = (0..4).collect();
+        let mut chunks = Chunked::new(3).unwrap();
+        let mut seen = Vec::new();
+        while let Some(item) = chunks.next(|offset, limit| Ok(window(&items, offset, limit))).unwrap() {
+            seen.push(item);
+            if item == 1 {
+                items.extend([4, 5]);
+            }
+        }
+        assert_eq!(seen, [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_fetch_error_propagates() {
+        let mut chunks = Chunked::<u32>::new(2).unwrap();
+        let error = chunks.next(|_, _| Err(AiosError::io("disk gone"))).unwrap_err();
+        assert_eq!(error.to_string(), "disk gone");
+        assert_eq!(chunks.position(), 0);
+    }
+
+    #[test]
+    fn test_window_clamps() {
+        let items = [1, 2, 3];
+        assert_eq!(window(&items, 1, 10), [2, 3]);
+        assert!(window(&items, 5, 2).is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+//! Python iterator classes from `python_iterator!`
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+mod errors {
+    aios_errors::python_exceptions!(iterator_test);
+}
+
+#[pyclass]
+struct Numbers {
+    items: Vec<u32>,
+}
+
+aios_stream::python_iterator!(
+    /// Iterator over `Numbers`
+    NumberIterator, Numbers, u32,
+    |py, core, offset, limit| Ok(aios_stream::window(&core.items, offset, limit))
+);
+
+#[pyfunction]
+fn iter_numbers(numbers: Py<Numbers>, chunk_size: usize) -> PyResult<NumberIterator> {
+    NumberIterator::new(numbers, chunk_size)
+}
+
+const SCRIPT: &str = r#"
+iterator = iter_numbers(numbers, 4)
+first = [next(iterator) for _ in range(6)]
+position = iterator.position
+numbers_after = list(iterator)
+try:
+    iter_numbers(numbers, 0)
+    error = None
+except Exception as e:
+    error = type(e).__name__
+"#;
+
+#[test]
+fn test_iterates_in_chunks() -> PyResult<()> {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| -> PyResult<()> {
+        let numbers = Py::new(py, Numbers { items: (0..10).collect() })?;
+        let globals = PyDict::new(py);
+        globals.set_item("numbers", numbers.clone_ref(py))?;
+        globals.set_item("iter_numbers", wrap_pyfunction!(iter_numbers, py)?)?;
+        py.run(SCRIPT, Some(globals), None)?;
+
+        let get = |name: &str| globals.get_item(name).map(|value| value.expect("set by the script"));
+        assert_eq!(get("first")?.extract::<Vec<u32>>()?, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(get("position")?.extract::<usize>()?, 6);
+        assert_eq!(get("numbers_after")?.extract::<Vec<u32>>()?, [6, 7, 8, 9]);
+        assert_eq!(get("error")?.extract::<String>()?, "ValidationError");
+        Ok(())
+    })
+}
@@ -0,0 +1,288 @@
+//! Per-core thread pools
+//!
+//! Every extension module links its own copy of rayon, so each core starts
+//! with a global pool of one thread per CPU, and running data exports,
+//! backups and clustering at the same time oversubscribes the machine. A
+//! core's heavy operations go through `install`, which runs them on the
+//! pool set up with `configure`, or on rayon's global pool until then.
+//! Sequential operations (exports, backups) run on a pool thread as well, so
+//! the pool size also bounds how many of them run at once.
+//!
+//! Work started with `spawn` (the asyncio awaitables of `aios-async`) runs on
+//! a second, background pool of the same size and priority. Its threads take
+//! the GIL to hand back results, so they are kept off the pool that a thread
+//! holding the GIL may be waiting on.
+//!
+//! A priority can only lower the scheduling priority of the pool threads
+//! (raise their nice value). It is applied on Linux and ignored elsewhere.
+//!
+//! Like `aios-errors`, this crate does not depend on PyO3: each extension
+//! module expands `python_thread_pool!` to get `configure_thread_pool`,
+//! `reset_thread_pool` and `thread_pool_info`. The pool is per module, so
+//! configure each core separately.
+
+use std::sync::{Arc, OnceLock, RwLock};
+
+use aios_errors::{AiosError, Result};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+/// Scheduling priority of pool threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Priority {
+    #[default]
+    Normal,
+    Low,
+    Idle,
+}
+
+impl Priority {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "normal" => Some(Priority::Normal),
+            "low" => Some(Priority::Low),
+            "idle" => Some(Priority::Idle),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Priority::Normal => "normal",
+            Priority::Low => "low",
+            Priority::Idle => "idle",
+        }
+    }
+
+    /// Nice value of the pool threads
+    pub fn nice(self) -> i32 {
+        match self {
+            Priority::Normal => 0,
+            Priority::Low => 10,
+            Priority::Idle => 19,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolConfig {
+    /// Worker threads; 0 means one per CPU
+    pub threads: usize,
+    pub priority: Priority,
+}
+
+/// The pool work currently runs on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolInfo {
+    /// False while rayon's global pool is used
+    pub configured: bool,
+    pub threads: usize,
+    pub priority: Priority,
+}
+
+struct Pool {
+    priority: Priority,
+    pool: Arc<ThreadPool>,
+    background: Arc<ThreadPool>,
+}
+
+static POOL: RwLock<Option<Pool>> = RwLock::new(None);
+
+/// Background pool used while none is configured, one thread per CPU
+static DEFAULT_BACKGROUND: OnceLock<ThreadPool> = OnceLock::new();
+
+/// Replace the module's pool; `label` prefixes the thread names.
+///
+/// Operations already running finish on the previous pool.
+pub fn configure(label: &str, config: PoolConfig) -> Result<()> {
+    let pool = build(label.to_string(), config)?;
+    let background = build(format!("{}-background", label), config)?;
+    *POOL.write().unwrap_or_else(|e| e.into_inner()) =
+        Some(Pool { priority: config.priority, pool: Arc::new(pool), background: Arc::new(background) });
+    Ok(())
+}
+
+fn build(label: String, config: PoolConfig) -> Result<ThreadPool> {
+    let nice = config.priority.nice();
+    ThreadPoolBuilder::new()
+        .num_threads(config.threads)
+        .thread_name(move |index| format!("{}-{}", label, index))
+        .start_handler(move |_| lower_priority(nice))
+        .build()
+        .map_err(|e| AiosError::config(format!("Failed to build thread pool: {}", e)))
+}
+
+/// Go back to rayon's global pool
+pub fn reset() {
+    POOL.write().unwrap_or_else(|e| e.into_inner()).take();
+}
+
+pub fn info() -> PoolInfo {
+    match POOL.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(pool) => PoolInfo { configured: true, threads: pool.pool.current_num_threads(), priority: pool.priority },
+        None => PoolInfo { configured: false, threads: rayon::current_num_threads(), priority: Priority::Normal },
+    }
+}
+
+/// Run `op` on the module's pool, so its parallel iterators use that pool;
+/// runs it directly while none is configured
+pub fn install<R, F>(op: F) -> R
+where
+    R: Send,
+    F: FnOnce() -> R + Send,
+{
+    let pool = POOL.read().unwrap_or_else(|e| e.into_inner()).as_ref().map(|pool| Arc::clone(&pool.pool));
+    match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
+/// Run `op` on the module's background pool without waiting for it; at
+/// most as many run at once as the pool has threads, the rest queue
+pub fn spawn<F>(op: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    let background = POOL.read().unwrap_or_else(|e| e.into_inner()).as_ref().map(|pool| Arc::clone(&pool.background));
+    match background {
+        Some(background) => background.spawn(op),
+        None => DEFAULT_BACKGROUND
+            .get_or_init(|| build("aios-background".to_string(), PoolConfig::default()).expect("Failed to build the background pool"))
+            .spawn(op),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn lower_priority(nice: i32) {
+    if nice == 0 {
+        return;
+    }
+    // With PRIO_PROCESS and a thread id, Linux changes only that thread.
+    // Failures (e.g. an already higher nice value) keep the current priority.
+    unsafe {
+        let thread_id = libc::syscall(libc::SYS_gettid) as libc::id_t;
+        libc::setpriority(libc::PRIO_PROCESS, thread_id, nice);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lower_priority(_nice: i32) {}
+
+/// Define `configure_thread_pool`, `reset_thread_pool` and
+/// `thread_pool_info` in the calling crate, against its own `pyo3`.
+///
+/// Expand it inside a module, e.g. `mod threads { aios_threads::python_thread_pool!(); }`,
+/// next to the `errors` module from `aios_errors::python_exceptions!`, and
+/// call `threads::register(m)` from the `#[pymodule]` function.
+// `crate::errors` deliberately names the calling crate's exceptions
+#[allow(clippy::crate_in_macro_def)]
+#[macro_export]
+macro_rules! python_thread_pool {
+    () => {
+        /// Run this module's parallel and long-running work on its own pool.
+        ///
+        /// `threads=0` uses one thread per CPU. `priority` is "normal", "low"
+        /// or "idle" and only takes effect on Linux. Operations already
+        /// running finish on the previous pool.
+        #[::pyo3::pyfunction]
+        #[pyo3(signature = (threads=0, priority="normal"))]
+        pub fn configure_thread_pool(threads: usize, priority: &str) -> ::pyo3::PyResult<()> {
+            let priority = $crate::Priority::parse(priority).ok_or_else(|| {
+                crate::errors::validation(format!("Unknown priority {}; expected normal, low or idle", priority))
+            })?;
+            $crate::configure(env!("CARGO_PKG_NAME"), $crate::PoolConfig { threads, priority })
+                .map_err(crate::errors::to_pyerr)
+        }
+
+        /// Drop this module's pool and use rayon's global pool again
+        #[::pyo3::pyfunction]
+        pub fn reset_thread_pool() {
+            $crate::reset();
+        }
+
+        /// The pool this module runs on as `{"configured", "threads", "priority"}`
+        #[::pyo3::pyfunction]
+        #[allow(deprecated)]
+        pub fn thread_pool_info(py: ::pyo3::Python<'_>) -> ::pyo3::PyResult<::pyo3::PyObject> {
+            use ::pyo3::ToPyObject;
+            let info = $crate::info();
+            let result = ::pyo3::types::PyDict::new(py);
+            result.set_item("configured", info.configured)?;
+            result.set_item("threads", info.threads)?;
+            result.set_item("priority", info.priority.name())?;
+            Ok(result.to_object(py))
+        }
+
+        /// Add the thread pool functions to the Python module
+        #[allow(deprecated)]
+        pub fn register(m: &::pyo3::types::PyModule) -> ::pyo3::PyResult<()> {
+            m.add_function(::pyo3::wrap_pyfunction!(configure_thread_pool, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(reset_thread_pool, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(thread_pool_info, m)?)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use rayon::prelude::*;
+
+    // One test, since the pool is global to the crate
+    #[test]
+    fn test_pool_size_honoured() {
+        assert!(!info().configured);
+        configure("threads-test", PoolConfig { threads: 3, priority: Priority::Low }).unwrap();
+        assert_eq!(info(), PoolInfo { configured: true, threads: 3, priority: Priority::Low });
+
+        let (threads, name) = install(|| (rayon::current_num_threads(), std::thread::current().name().map(String::from)));
+        assert_eq!(threads, 3);
+        assert!(name.unwrap().starts_with("threads-test-"));
+
+        // Parallel iterators never run more items at once than the pool has threads
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        install(|| {
+            (0..24).into_par_iter().for_each(|_| {
+                most.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(10));
+                running.fetch_sub(1, Ordering::SeqCst);
+            })
+        });
+        assert!((2..=3).contains(&most.swap(0, Ordering::SeqCst)));
+
+        // Background work queues behind the same number of threads
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..12 {
+            let (running, most, sender) = (running.clone(), most.clone(), sender.clone());
+            spawn(move || {
+                most.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(10));
+                running.fetch_sub(1, Ordering::SeqCst);
+                sender.send(std::thread::current().name().map(String::from)).unwrap();
+            });
+        }
+        drop(sender);
+        let names: Vec<_> = receiver.iter().collect();
+        assert_eq!(names.len(), 12);
+        assert!(names.iter().all(|name| name.as_deref().unwrap().starts_with("threads-test-background-")), "{:?}", names);
+        assert_eq!(most.load(Ordering::SeqCst), 3);
+
+        reset();
+        assert!(!info().configured);
+        assert_eq!(install(rayon::current_num_threads), rayon::current_num_threads());
+    }
+
+    #[test]
+    fn test_priority_names() {
+        for priority in [Priority::Normal, Priority::Low, Priority::Idle] {
+            assert_eq!(Priority::parse(priority.name()), Some(priority));
+        }
+        assert_eq!(Priority::parse("high"), None);
+        assert!(Priority::Normal.nice() < Priority::Low.nice() && Priority::Low.nice() < Priority::Idle.nice());
+    }
+}
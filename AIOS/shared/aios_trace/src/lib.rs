@@ -0,0 +1,432 @@
+//! Span tracing shared by the AIOS Rust cores
+//!
+//! Public API calls open a span with `span!`; when the guard drops, a
+//! `TraceEvent` with the call's timing, arguments and parent span is sent
+//! to every configured sink: a JSONL file and/or a callback (used to forward
+//! events to Python logging). Tracing is off until a sink is configured, and
+//! while it is off `span!` does not evaluate its arguments.
+//!
+//! Like `aios-errors`, this crate does not depend on PyO3; extension modules
+//! expand `python_tracing!` to get a `configure_tracing` Python function.
+//! Each extension module keeps its own sinks, so configure every module you
+//! want traced. JSONL files are opened in append mode and written one line
+//! per event, so several modules can share one file.
+//!
+//! Spans also feed an opt-in call statistics registry: with instrumentation
+//! on, every finished span adds to its name's call count, cumulative time and
+//! maximum latency, without building events or evaluating span arguments
+//! unless a sink is configured as well. Python gets `enable_instrumentation`,
+//! `get_call_stats` and `reset_call_stats`; like the sinks, the registry is
+//! per extension module, so query each core whose calls you want to compare.
+
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+#[doc(hidden)]
+pub use serde_json;
+
+/// Callback receiving every finished span
+pub type Callback = Box<dyn Fn(&TraceEvent) + Send + Sync>;
+type SharedCallback = Arc<dyn Fn(&TraceEvent) + Send + Sync>;
+
+/// Set while any sink is configured
+static SINKS_ENABLED: AtomicBool = AtomicBool::new(false);
+static INSTRUMENTED: AtomicBool = AtomicBool::new(false);
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static SINKS: Mutex<Sinks> = Mutex::new(Sinks { file: None, callback: None });
+static CALL_STATS: Mutex<BTreeMap<&'static str, CallStats>> = Mutex::new(BTreeMap::new());
+
+thread_local! {
+    /// Open span ids on this thread, innermost last
+    static STACK: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Sinks {
+    file: Option<LineWriter<File>>,
+    callback: Option<SharedCallback>,
+}
+
+/// A finished span
+#[derive(Debug, Clone)]
+pub struct TraceEvent {
+    /// Crate that recorded the span
+    pub target: &'static str,
+    pub name: &'static str,
+    pub span_id: u64,
+    pub parent_id: Option<u64>,
+    /// Unix time the span started, in seconds
+    pub start: f64,
+    pub duration_ms: f64,
+    pub thread: String,
+    pub args: Map<String, Value>,
+}
+
+impl TraceEvent {
+    pub fn to_json(&self) -> Value {
+        let mut object = Map::new();
+        object.insert("ts".to_string(), self.start.into());
+        object.insert("target".to_string(), self.target.into());
+        object.insert("name".to_string(), self.name.into());
+        object.insert("span_id".to_string(), self.span_id.into());
+        object.insert("parent_id".to_string(), self.parent_id.into());
+        object.insert("duration_ms".to_string(), self.duration_ms.into());
+        object.insert("thread".to_string(), self.thread.clone().into());
+        object.insert("args".to_string(), Value::Object(self.args.clone()));
+        Value::Object(object)
+    }
+}
+
+/// Aggregate timings of one span name
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CallStats {
+    pub calls: u64,
+    pub total_ms: f64,
+    pub max_ms: f64,
+}
+
+impl CallStats {
+    pub fn mean_ms(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.total_ms / self.calls as f64
+        }
+    }
+}
+
+/// Whether spans are recorded at all (a sink is configured or
+/// instrumentation is on)
+pub fn enabled() -> bool {
+    sinks_enabled() || INSTRUMENTED.load(Ordering::Relaxed)
+}
+
+/// Whether any sink is configured, i.e. span arguments are needed
+pub fn sinks_enabled() -> bool {
+    SINKS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Turn the call statistics registry on or off; collected stats are kept
+pub fn set_instrumentation(enabled: bool) {
+    INSTRUMENTED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn instrumentation_enabled() -> bool {
+    INSTRUMENTED.load(Ordering::Relaxed)
+}
+
+/// Statistics per span name, sorted by name
+pub fn call_stats() -> Vec<(&'static str, CallStats)> {
+    let stats = CALL_STATS.lock().unwrap_or_else(|e| e.into_inner());
+    stats.iter().map(|(name, stats)| (*name, *stats)).collect()
+}
+
+pub fn reset_call_stats() {
+    CALL_STATS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+fn record_call(name: &'static str, duration_ms: f64) {
+    let mut stats = CALL_STATS.lock().unwrap_or_else(|e| e.into_inner());
+    let entry = stats.entry(name).or_default();
+    entry.calls += 1;
+    entry.total_ms += duration_ms;
+    entry.max_ms = entry.max_ms.max(duration_ms);
+}
+
+/// Append events to `path` as JSON Lines, or stop writing a file with `None`
+pub fn set_jsonl_file(path: Option<&Path>) -> io::Result<()> {
+    let file = match path {
+        Some(path) => Some(LineWriter::new(OpenOptions::new().create(true).append(true).open(path)?)),
+        None => None,
+    };
+    let mut sinks = SINKS.lock().unwrap_or_else(|e| e.into_inner());
+    sinks.file = file;
+    update_enabled(&sinks);
+    Ok(())
+}
+
+/// Send events to `callback`, or remove the callback with `None`
+pub fn set_callback(callback: Option<Callback>) {
+    let mut sinks = SINKS.lock().unwrap_or_else(|e| e.into_inner());
+    sinks.callback = callback.map(Arc::from);
+    update_enabled(&sinks);
+}
+
+fn update_enabled(sinks: &Sinks) {
+    SINKS_ENABLED.store(sinks.file.is_some() || sinks.callback.is_some(), Ordering::Relaxed);
+}
+
+fn emit(event: &TraceEvent) {
+    // Run the callback outside the lock so it may itself be slow or reentrant
+    let callback = {
+        let mut sinks = SINKS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(file) = &mut sinks.file {
+            let mut line = event.to_json().to_string();
+            line.push('\n');
+            // Tracing must never fail the traced call
+            let _ = file.write_all(line.as_bytes());
+        }
+        sinks.callback.clone()
+    };
+
+    if let Some(callback) = callback {
+        callback(event);
+    }
+}
+
+/// Guard for an open span; records the event when dropped
+pub struct Span {
+    target: &'static str,
+    name: &'static str,
+    id: u64,
+    parent: Option<u64>,
+    start: f64,
+    started: Instant,
+    args: Map<String, Value>,
+}
+
+impl Span {
+    pub fn enter(target: &'static str, name: &'static str, args: Map<String, Value>) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let parent = STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            let parent = stack.last().copied();
+            stack.push(id);
+            parent
+        });
+        Self {
+            target,
+            name,
+            id,
+            parent,
+            start: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64()),
+            started: Instant::now(),
+            args,
+        }
+    }
+
+    /// Add an argument or result field after the span was opened
+    pub fn record(&mut self, key: &str, value: impl Into<Value>) {
+        self.args.insert(key.to_string(), value.into());
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            if let Some(position) = stack.iter().rposition(|&id| id == self.id) {
+                stack.remove(position);
+            }
+        });
+
+        let duration_ms = self.started.elapsed().as_secs_f64() * 1000.0;
+        if instrumentation_enabled() {
+            record_call(self.name, duration_ms);
+        }
+        if !sinks_enabled() {
+            return;
+        }
+
+        let thread = std::thread::current();
+        let event = TraceEvent {
+            target: self.target,
+            name: self.name,
+            span_id: self.id,
+            parent_id: self.parent,
+            start: self.start,
+            duration_ms,
+            thread: thread.name().map_or_else(|| format!("{:?}", thread.id()), str::to_string),
+            args: std::mem::take(&mut self.args),
+        };
+        emit(&event);
+    }
+}
+
+/// Open a span named `$name` with `key = value` arguments.
+///
+/// Returns `Option<Span>`, `None` while tracing and instrumentation are
+/// off; bind it to a named variable (`let _span = ...`) so it lives until the
+/// end of the call. Argument values must implement `serde::Serialize` and are
+/// only evaluated while a sink is configured.
+#[macro_export]
+macro_rules! span {
+    ($name:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        if $crate::enabled() {
+            #[allow(unused_mut)]
+            let mut args = $crate::serde_json::Map::new();
+            if $crate::sinks_enabled() {
+                $(
+                    args.insert(
+                        stringify!($key).to_string(),
+                        $crate::serde_json::to_value(&$value).unwrap_or($crate::serde_json::Value::Null),
+                    );
+                )*
+            }
+            Some($crate::Span::enter(env!("CARGO_PKG_NAME"), $name, args))
+        } else {
+            None
+        }
+    };
+}
+
+/// Define `configure_tracing` and the instrumentation functions in the
+/// calling crate, against its own `pyo3`.
+///
+/// Expand it inside a module, e.g. `mod tracing { aios_trace::python_tracing!(); }`,
+/// and call `tracing::register(m)` from the `#[pymodule]` function.
+#[macro_export]
+macro_rules! python_tracing {
+    () => {
+        /// Configure span tracing for this module.
+        ///
+        /// `path` appends one JSON object per finished call to a JSONL file.
+        /// `python_logging=True` forwards each event as a JSON message to the
+        /// `aios.trace` logger at `level` (default DEBUG). Calling with no
+        /// arguments turns tracing off.
+        #[::pyo3::pyfunction]
+        #[pyo3(signature = (path=None, python_logging=false, level=10))]
+        #[allow(deprecated)]
+        pub fn configure_tracing(path: Option<&str>, python_logging: bool, level: i32) -> ::pyo3::PyResult<()> {
+            $crate::set_jsonl_file(path.map(::std::path::Path::new)).map_err(|e| {
+                ::pyo3::exceptions::PyIOError::new_err(format!("Failed to open trace file: {}", e))
+            })?;
+
+            let callback: Option<$crate::Callback> = if python_logging {
+                Some(Box::new(move |event: &$crate::TraceEvent| {
+                    ::pyo3::Python::with_gil(|py| {
+                        let logged = py
+                            .import("logging")
+                            .and_then(|logging| logging.call_method1("getLogger", ("aios.trace",)))
+                            .and_then(|logger| logger.call_method1("log", (level, event.to_json().to_string())));
+                        if let Err(e) = logged {
+                            e.print(py);
+                        }
+                    })
+                }))
+            } else {
+                None
+            };
+            $crate::set_callback(callback);
+            Ok(())
+        }
+
+        /// Turn per-call statistics for this module on (default) or off.
+        ///
+        /// While on, every public call adds to its entry in `get_call_stats()`.
+        /// Turning it off keeps the statistics collected so far.
+        #[::pyo3::pyfunction]
+        #[pyo3(signature = (enabled=true))]
+        pub fn enable_instrumentation(enabled: bool) {
+            $crate::set_instrumentation(enabled);
+        }
+
+        /// Per-call statistics of this module as
+        /// `{name: {"calls", "total_ms", "mean_ms", "max_ms"}}`
+        #[::pyo3::pyfunction]
+        #[allow(deprecated)]
+        pub fn get_call_stats(py: ::pyo3::Python<'_>) -> ::pyo3::PyResult<::pyo3::PyObject> {
+            use ::pyo3::ToPyObject;
+            let result = ::pyo3::types::PyDict::new(py);
+            for (name, stats) in $crate::call_stats() {
+                let entry = ::pyo3::types::PyDict::new(py);
+                entry.set_item("calls", stats.calls)?;
+                entry.set_item("total_ms", stats.total_ms)?;
+                entry.set_item("mean_ms", stats.mean_ms())?;
+                entry.set_item("max_ms", stats.max_ms)?;
+                result.set_item(name, entry)?;
+            }
+            Ok(result.to_object(py))
+        }
+
+        /// Clear this module's per-call statistics
+        #[::pyo3::pyfunction]
+        pub fn reset_call_stats() {
+            $crate::reset_call_stats();
+        }
+
+        /// Add the tracing and instrumentation functions to the Python module
+        #[allow(deprecated)]
+        pub fn register(m: &::pyo3::types::PyModule) -> ::pyo3::PyResult<()> {
+            m.add_function(::pyo3::wrap_pyfunction!(configure_tracing, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(enable_instrumentation, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(get_call_stats, m)?)?;
+            m.add_function(::pyo3::wrap_pyfunction!(reset_call_stats, m)?)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn stats(name: &str) -> Option<CallStats> {
+        call_stats().into_iter().find(|(n, _)| *n == name).map(|(_, stats)| stats)
+    }
+
+    fn traced(value: &mut u32) -> u32 {
+        *value += 1;
+        *value
+    }
+
+    // One test, since sinks and the registry are global to the crate
+    #[test]
+    fn test_registry_counts_and_reset() {
+        // Off: no span, arguments not evaluated
+        let mut evaluated = 0;
+        assert!(span!("test.off", value = traced(&mut evaluated)).is_none());
+        assert_eq!(evaluated, 0);
+
+        // Instrumentation alone counts calls, still without evaluating arguments
+        set_instrumentation(true);
+        for sleep in [0, 5, 1] {
+            let _span = span!("test.call", value = traced(&mut evaluated));
+            std::thread::sleep(Duration::from_millis(sleep));
+        }
+        drop(span!("test.other"));
+        assert_eq!(evaluated, 0);
+        let call = stats("test.call").unwrap();
+        assert_eq!(call.calls, 3);
+        assert!(call.max_ms >= 5.0 && call.total_ms >= call.max_ms, "{:?}", call);
+        assert!((call.mean_ms() - call.total_ms / 3.0).abs() < 1e-9);
+        assert_eq!(stats("test.other").map(|stats| stats.calls), Some(1));
+        assert!(stats("test.off").is_none());
+        let names: Vec<_> = call_stats().into_iter().map(|(name, _)| name).collect();
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", names);
+
+        reset_call_stats();
+        assert!(call_stats().is_empty());
+        assert_eq!(CallStats::default().mean_ms(), 0.0);
+
+        // With a sink, events carry arguments and their parent span
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        set_callback(Some(Box::new(move |event: &TraceEvent| sink.lock().unwrap().push(event.clone()))));
+        {
+            let _outer = span!("test.outer", value = traced(&mut evaluated));
+            let mut inner = span!("test.inner");
+            inner.as_mut().unwrap().record("result", "ok");
+        }
+        set_callback(None);
+        set_instrumentation(false);
+        drop(span!("test.after"));
+
+        let events = events.lock().unwrap();
+        assert_eq!(evaluated, 1);
+        let [inner, outer] = &events[..] else { panic!("{:?}", events) };
+        assert_eq!((inner.name, outer.name), ("test.inner", "test.outer"));
+        assert_eq!(inner.parent_id, Some(outer.span_id));
+        assert_eq!(outer.parent_id, None);
+        assert_eq!(outer.args["value"], 1);
+        assert_eq!(inner.to_json()["args"]["result"], "ok");
+        assert_eq!(stats("test.outer").map(|stats| stats.calls), Some(1));
+        assert!(stats("test.after").is_none());
+    }
+}
@@ -0,0 +1,96 @@
+//! numpy-or-list vector arguments
+//!
+//! numpy arrays are read through the buffer in one pass instead of being
+//! converted element by element; lists and other sequences still work. numpy
+//! is only touched for objects exposing `__array_interface__`, so the module
+//! keeps working where numpy is not installed.
+
+use numpy::{PyReadonlyArray1, PyReadonlyArray2};
+use pyo3::prelude::*;
+
+/// One vector: a 1-D float32/float64 array or a sequence of floats
+pub enum Vector<'py> {
+    F32(PyReadonlyArray1<'py, f32>),
+    F64(PyReadonlyArray1<'py, f64>),
+    List(Vec<f32>),
+}
+
+impl<'py> FromPyObject<'py> for Vector<'py> {
+    fn extract(obj: &'py PyAny) -> PyResult<Self> {
+        if obj.hasattr("__array_interface__")? {
+            if let Ok(array) = obj.extract() {
+                return Ok(Self::F32(array));
+            }
+            if let Ok(array) = obj.extract() {
+                return Ok(Self::F64(array));
+            }
+        }
+        Ok(Self::List(obj.extract()?))
+    }
+}
+
+impl Vector<'_> {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::F32(array) => array.as_array().len(),
+            Self::F64(array) => array.as_array().len(),
+            Self::List(values) => values.len(),
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<f32> {
+        match self {
+            Self::F32(array) => match array.as_slice() {
+                Ok(slice) => slice.to_vec(),
+                Err(_) => array.as_array().iter().copied().collect(),
+            },
+            Self::F64(array) => array.as_array().iter().map(|&x| x as f32).collect(),
+            Self::List(values) => values,
+        }
+    }
+}
+
+/// Many vectors: a 2-D float32/float64 array (one row each) or a sequence of sequences
+pub enum Matrix<'py> {
+    F32(PyReadonlyArray2<'py, f32>),
+    F64(PyReadonlyArray2<'py, f64>),
+    List(Vec<Vec<f32>>),
+}
+
+impl<'py> FromPyObject<'py> for Matrix<'py> {
+    fn extract(obj: &'py PyAny) -> PyResult<Self> {
+        if obj.hasattr("__array_interface__")? {
+            if let Ok(array) = obj.extract() {
+                return Ok(Self::F32(array));
+            }
+            if let Ok(array) = obj.extract() {
+                return Ok(Self::F64(array));
+            }
+        }
+        Ok(Self::List(obj.extract()?))
+    }
+}
+
+impl Matrix<'_> {
+    /// Number of rows
+    pub fn len(&self) -> usize {
+        match self {
+            Self::F32(array) => array.as_array().nrows(),
+            Self::F64(array) => array.as_array().nrows(),
+            Self::List(rows) => rows.len(),
+        }
+    }
+
+    pub fn into_rows(self) -> Vec<Vec<f32>> {
+        match self {
+            Self::F32(array) => array.as_array().rows().into_iter().map(|row| row.to_vec()).collect(),
+            Self::F64(array) => array
+                .as_array()
+                .rows()
+                .into_iter()
+                .map(|row| row.iter().map(|&x| x as f32).collect())
+                .collect(),
+            Self::List(rows) => rows,
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Cache integrity: every JSON file under the cache directory is parsed and
+//! checked against the schema its name implies
+//!
+//! Files named `*registry*.json` are registries: an object of entries, each
+//! an object. Any other JSON file is a fragment: an object with a string
+//! `content`, a string `file_id` (or `id`), and when present a string or null
+//! `parent_id`, a non-negative integer `level`, string `tags` and a numeric
+//! `embedding`. A file that ends mid-document is reported as truncated.
+//!
+//! Corrupted files can be moved to `quarantine/` under the cache directory,
+//! keeping their relative path, with a JSON repair report beside them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use walkdir::WalkDir;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Directory under the cache directory that corrupted files are moved to
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// A corrupted cache file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct CacheIssue {
+    /// Relative to the cache directory
+    pub path: String,
+    /// "unreadable", "truncated", "invalid_json" or "schema"
+    pub kind: String,
+    pub detail: String,
+    /// Where the file was moved, relative to the cache directory
+    pub quarantined_to: Option<String>,
+}
+
+/// Outcome of verifying the cache directory
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct CacheReport {
+    pub files_checked: usize,
+    pub issues: Vec<CacheIssue>,
+    /// Repair report written when files were quarantined
+    pub report_path: Option<String>,
+}
+
+fn schema_error(detail: impl Into<String>) -> (&'static str, String) {
+    ("schema", detail.into())
+}
+
+fn check_registry(value: &Value) -> Result<(), (&'static str, String)> {
+    let Value::Object(entries) = value else {
+        return Err(schema_error("registry is not an object"));
+    };
+    match entries.iter().find(|(_, entry)| !entry.is_object()) {
+        Some((id, _)) => Err(schema_error(format!("registry entry {:?} is not an object", id))),
+        None => Ok(()),
+    }
+}
+
+fn check_fragment(value: &Value) -> Result<(), (&'static str, String)> {
+    let Value::Object(fields) = value else {
+        return Err(schema_error("fragment is not an object"));
+    };
+    if !fields.get("content").is_some_and(Value::is_string) {
+        return Err(schema_error("fragment has no string content"));
+    }
+    if !fields.get("file_id").or_else(|| fields.get("id")).is_some_and(Value::is_string) {
+        return Err(schema_error("fragment has no string file_id or id"));
+    }
+    if fields.get("parent_id").is_some_and(|parent| !parent.is_string() && !parent.is_null()) {
+        return Err(schema_error("fragment parent_id is not a string or null"));
+    }
+    if fields.get("level").is_some_and(|level| !level.is_u64()) {
+        return Err(schema_error("fragment level is not a non-negative integer"));
+    }
+    let all = |key: &str, predicate: fn(&Value) -> bool| match fields.get(key) {
+        None => true,
+        Some(Value::Array(items)) => items.iter().all(predicate),
+        Some(_) => false,
+    };
+    if !all("tags", Value::is_string) {
+        return Err(schema_error("fragment tags are not a list of strings"));
+    }
+    if !all("embedding", Value::is_number) {
+        return Err(schema_error("fragment embedding is not a list of numbers"));
+    }
+    Ok(())
+}
+
+fn check_file(path: &Path) -> Result<(), (&'static str, String)> {
+    let text = fs::read_to_string(path).map_err(|e| ("unreadable", e.to_string()))?;
+    let value: Value = serde_json::from_str(&text).map_err(|e| {
+        if e.is_eof() {
+            ("truncated", format!("ends mid-document ({} bytes)", text.len()))
+        } else {
+            ("invalid_json", e.to_string())
+        }
+    })?;
+    let name = path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if name.contains("registry") {
+        check_registry(&value)
+    } else {
+        check_fragment(&value)
+    }
+}
+
+fn relative(cache_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(cache_dir).unwrap_or(path).to_string_lossy().replace('\\', "/")
+}
+
+/// Verify every JSON file under `cache_dir` (outside the quarantine),
+/// moving corrupted ones to the quarantine when `quarantine` is set
+pub fn verify(cache_dir: &Path, quarantine: bool) -> anyhow::Result<CacheReport> {
+    let mut report = CacheReport { files_checked: 0, issues: Vec::new(), report_path: None };
+    if !cache_dir.is_dir() {
+        return Ok(report);
+    }
+    let quarantine_dir = cache_dir.join(QUARANTINE_DIR);
+    let files: Vec<PathBuf> = WalkDir::new(cache_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.path() != quarantine_dir)
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && entry.path().extension().is_some_and(|ext| ext == "json"))
+        .map(|entry| entry.into_path())
+        .collect();
+    report.files_checked = files.len();
+    for path in files {
+        if let Err((kind, detail)) = check_file(&path) {
+            report.issues.push(CacheIssue { path: relative(cache_dir, &path), kind: kind.to_string(), detail, quarantined_to: None });
+        }
+    }
+
+    if quarantine && !report.issues.is_empty() {
+        let stamp = Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string();
+        let batch = quarantine_dir.join(&stamp);
+        for issue in &mut report.issues {
+            let target = batch.join(&issue.path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            match fs::rename(cache_dir.join(&issue.path), &target) {
+                Ok(()) => issue.quarantined_to = Some(relative(cache_dir, &target)),
+                Err(e) => issue.detail = format!("{}; could not quarantine: {}", issue.detail, e),
+            }
+        }
+        let report_file = batch.join("repair_report.json");
+        report.report_path = Some(relative(cache_dir, &report_file));
+        fs::create_dir_all(&batch)?;
+        fs::write(&report_file, serde_json::to_string_pretty(&report)?)?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,214 @@
+//! Cron expressions for scheduled health checks
+//!
+//! Five fields in local time: minute, hour, day of month, month, day of week
+//! (0 or 7 is Sunday). Each field takes `*`, numbers, ranges `a-b`, steps
+//! `*/n` or `a-b/n`, and comma-separated lists of these. As in cron, when
+//! both day fields are restricted a day matching either one matches.
+//! `@hourly`, `@daily` (`@midnight`), `@weekly`, `@monthly` and `@yearly`
+//! (`@annually`) stand for their usual expressions.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone, Timelike};
+
+/// Furthest ahead `next_after` looks; a valid expression always matches
+/// within this many days, even Feb 29 in a leap year
+const SEARCH_DAYS: i64 = 366 * 8;
+
+/// A parsed five-field cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day of month and day of week fields were `*`
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// Bit set of the values `field` allows in `min..=max`
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| format!("invalid step in {:?}", part))?;
+                if step == 0 {
+                    return Err(format!("zero step in {:?}", part));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else {
+            let number = |text: &str| text.parse::<u32>().map_err(|_| format!("invalid value {:?}", text));
+            match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                // `n/step` runs from n to the end of the range
+                None if step > 1 => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            }
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("{:?} is outside {}-{}", part, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expanded = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("Cron expression {:?} needs 5 fields, got {}", expression, fields.len()));
+        };
+        let invalid = |e: String| format!("Invalid cron expression {:?}: {}", expression, e);
+        let mut weekdays = parse_field(weekday, 0, 7).map_err(invalid)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59).map_err(invalid)?,
+            hours: parse_field(hour, 0, 23).map_err(invalid)?,
+            days: parse_field(day, 1, 31).map_err(invalid)?,
+            months: parse_field(month, 1, 12).map_err(invalid)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first matching minute strictly after `time`; None when the
+    /// expression can never match (e.g. Feb 30)
+    pub fn next_after(&self, time: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = time.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut day = start.date_naive();
+        for _ in 0..SEARCH_DAYS {
+            if self.matches_day(day) {
+                for hour in (0..24).filter(|hour| self.hours & (1 << hour) != 0) {
+                    for minute in (0..60).filter(|minute| self.minutes & (1 << minute) != 0) {
+                        // Times skipped by a DST change do not exist and never match
+                        let Some(candidate) = Local.from_local_datetime(&day.and_hms_opt(hour, minute, 0)?).earliest() else {
+                            continue;
+                        };
+                        if candidate >= start {
+                            return Some(candidate);
+                        }
+                    }
+                }
+            }
+            day = day.succ_opt()?;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Local time; January 2026 has no DST change, and
+    /// 2026-01-01 is a Thursday
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(year, month, day, hour, minute, 0).single().unwrap()
+    }
+
+    fn next(expression: &str, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        Cron::parse(expression).unwrap().next_after(after)
+    }
+
+    #[test]
+    fn test_steps() {
+        let every_quarter = Cron::parse("*/15 * * * *").unwrap();
+        assert_eq!(every_quarter.minutes, 1 << 0 | 1 << 15 | 1 << 30 | 1 << 45);
+        assert_eq!(every_quarter.next_after(at(2026, 1, 1, 10, 7)), Some(at(2026, 1, 1, 10, 15)));
+        assert_eq!(every_quarter.next_after(at(2026, 1, 1, 10, 45)), Some(at(2026, 1, 1, 11, 0)));
+
+        assert_eq!(Cron::parse("1-5/2 * * * *").unwrap().minutes, 1 << 1 | 1 << 3 | 1 << 5);
+        assert_eq!(Cron::parse("10/20 * * * *").unwrap().minutes, 1 << 10 | 1 << 30 | 1 << 50);
+        assert_eq!(Cron::parse("0,30 9-10 * * *").unwrap().hours, 1 << 9 | 1 << 10);
+        // Monday, Wednesday and Friday
+        assert_eq!(next("0 9 * * 1-5/2", at(2026, 1, 1, 12, 0)), Some(at(2026, 1, 2, 9, 0)));
+        assert_eq!(next("0 9 * * 1-5/2", at(2026, 1, 2, 9, 0)), Some(at(2026, 1, 5, 9, 0)));
+    }
+
+    #[test]
+    fn test_restricted_day_fields_match_either() {
+        let start = at(2026, 1, 1, 0, 0);
+        // Friday the 2nd and 9th, then Tuesday the 13th
+        assert_eq!(next("0 0 13 * 5", start), Some(at(2026, 1, 2, 0, 0)));
+        assert_eq!(next("0 0 13 * 5", at(2026, 1, 2, 0, 0)), Some(at(2026, 1, 9, 0, 0)));
+        assert_eq!(next("0 0 13 * 5", at(2026, 1, 9, 0, 0)), Some(at(2026, 1, 13, 0, 0)));
+        // With one of them `*`, only the other counts
+        assert_eq!(next("0 0 13 * *", start), Some(at(2026, 1, 13, 0, 0)));
+        assert_eq!(next("0 0 * * 5", start), Some(at(2026, 1, 2, 0, 0)));
+        // Months still apply to both
+        assert_eq!(next("0 0 13 3 5", start), Some(at(2026, 3, 6, 0, 0)));
+    }
+
+    #[test]
+    fn test_seven_is_sunday() {
+        let start = at(2026, 1, 1, 0, 0);
+        assert_eq!(next("0 0 * * 7", start), Some(at(2026, 1, 4, 0, 0)));
+        assert_eq!(next("0 0 * * 0", start), next("0 0 * * 7", start));
+        assert_eq!(next("0 0 * * 5-7", at(2026, 1, 4, 0, 0)), Some(at(2026, 1, 9, 0, 0)));
+    }
+
+    #[test]
+    fn test_impossible_dates() {
+        let start = at(2026, 1, 1, 0, 0);
+        assert_eq!(next("0 0 30 2 *", start), None);
+        assert_eq!(next("0 0 31 4,6,9,11 *", start), None);
+        // Leap days come around
+        assert_eq!(next("0 0 29 2 *", start), Some(at(2028, 2, 29, 0, 0)));
+    }
+
+    #[test]
+    fn test_macros() {
+        for (macro_name, expression) in [
+            ("@yearly", "0 0 1 1 *"),
+            ("@annually", "0 0 1 1 *"),
+            ("@monthly", "0 0 1 * *"),
+            ("@weekly", "0 0 * * 0"),
+            ("@daily", "0 0 * * *"),
+            ("@midnight", "0 0 * * *"),
+            ("@hourly", "0 * * * *"),
+        ] {
+            assert_eq!(Cron::parse(macro_name).unwrap(), Cron::parse(expression).unwrap(), "{}", macro_name);
+        }
+        assert_eq!(next("@daily", at(2026, 1, 1, 0, 0)), Some(at(2026, 1, 2, 0, 0)));
+        assert!(Cron::parse("@sometimes").is_err());
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        for expression in ["* * * *", "* * * * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "* * * 13 *", "* * * * 8", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+            assert!(Cron::parse(expression).is_err(), "{}", expression);
+        }
+    }
+}
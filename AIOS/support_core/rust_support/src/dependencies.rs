@@ -0,0 +1,97 @@
+//! Python package verification
+//!
+//! Each package is imported for real and its version read from
+//! `__version__` or the installed distribution. Inside the Python module the
+//! embedded interpreter runs the probe (see `PythonRunner`); a core used from
+//! Rust runs it in a `python3` (or `python`) subprocess instead.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::Arc;
+
+/// Defines `probe(names) -> str`, the JSON object `{name: [imported,
+/// version or error]}`; run as a script it probes its arguments
+pub const PROBE_SCRIPT: &str = r#"
+import importlib
+import json
+import sys
+
+
+def probe(names):
+    results = {}
+    for name in names:
+        try:
+            module = importlib.import_module(name)
+        except BaseException as e:
+            results[name] = [False, f"{type(e).__name__}: {e}"]
+            continue
+        version = getattr(module, "__version__", None)
+        if version is None:
+            try:
+                from importlib.metadata import version as distribution_version
+                version = distribution_version(name)
+            except Exception:
+                pass
+        results[name] = [True, None if version is None else str(version)]
+    return json.dumps(results)
+
+
+if __name__ == "__main__":
+    print(probe(sys.argv[1:]))
+"#;
+
+/// Runs `probe` from `PROBE_SCRIPT` on the package names, returning its
+/// JSON output
+pub type PythonRunner = Arc<dyn Fn(&[String]) -> Result<String, String> + Send + Sync>;
+
+/// Interpreters tried, in order, without a `PythonRunner`
+const INTERPRETERS: &[&str] = &["python3", "python"];
+
+/// Outcome of importing one package
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub name: String,
+    /// None when the import failed
+    pub version: Option<String>,
+    pub imported: bool,
+    pub error: Option<String>,
+}
+
+fn run_subprocess(packages: &[String]) -> Result<String, String> {
+    let mut last_error = "no Python interpreter found".to_string();
+    for interpreter in INTERPRETERS {
+        match Command::new(interpreter).arg("-c").arg(PROBE_SCRIPT).args(packages).output() {
+            Ok(output) if output.status.success() => return Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+            Ok(output) => last_error = format!("{} failed: {}", interpreter, String::from_utf8_lossy(&output.stderr).trim()),
+            Err(_) => continue,
+        }
+    }
+    Err(last_error)
+}
+
+/// Import every package in `packages` with `runner`, or a subprocess
+/// interpreter without one
+pub fn check(packages: &[String], runner: Option<&PythonRunner>) -> Result<Vec<Dependency>, String> {
+    if packages.is_empty() {
+        return Ok(Vec::new());
+    }
+    let output = match runner {
+        Some(runner) => runner(packages)?,
+        None => run_subprocess(packages)?,
+    };
+    let results: HashMap<String, (bool, Option<String>)> =
+        serde_json::from_str(output.trim()).map_err(|e| format!("Unexpected probe output: {}", e))?;
+    Ok(packages
+        .iter()
+        .map(|name| match results.get(name) {
+            Some((true, version)) => Dependency { name: name.clone(), version: version.clone(), imported: true, error: None },
+            Some((false, error)) => Dependency { name: name.clone(), version: None, imported: false, error: error.clone() },
+            None => Dependency {
+                name: name.clone(),
+                version: None,
+                imported: false,
+                error: Some("not probed".to_string()),
+            },
+        })
+        .collect())
+}
@@ -0,0 +1,69 @@
+//! Per-disk metrics
+//!
+//! Each mounted disk is keyed by its mount point. I/O counters (totals
+//! since boot) are read from `/proc/diskstats` on Linux and matched to a
+//! disk by its device name; other platforms report none.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use sysinfo::Disk;
+
+/// `/proc/diskstats` counts sectors of 512 bytes, whatever the device's
+const SECTOR_BYTES: f64 = 512.0;
+
+/// Cumulative I/O of one block device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IoCounters {
+    pub reads: u64,
+    pub writes: u64,
+    pub read_bytes: f64,
+    pub written_bytes: f64,
+}
+
+/// Metric name fragment for a mount point: lowercased, with runs of
+/// anything but letters and digits as one `_`; "root" for `/`
+pub fn mount_key(mount_point: &Path) -> String {
+    let mut key = String::new();
+    for c in mount_point.to_string_lossy().chars() {
+        if c.is_ascii_alphanumeric() {
+            key.push(c.to_ascii_lowercase());
+        } else if !key.is_empty() && !key.ends_with('_') {
+            key.push('_');
+        }
+    }
+    let key = key.trim_end_matches('_');
+    if key.is_empty() { "root".to_string() } else { key.to_string() }
+}
+
+/// I/O counters of every block device, by kernel name (e.g. "sda1",
+/// "nvme0n1p2", "dm-0")
+pub fn io_counters() -> HashMap<String, IoCounters> {
+    let Ok(stats) = fs::read_to_string("/proc/diskstats") else {
+        return HashMap::new();
+    };
+    stats
+        .lines()
+        .filter_map(|line| {
+            // major minor name reads merged sectors_read ms writes merged sectors_written ...
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let number = |index: usize| fields.get(index)?.parse::<u64>().ok();
+            let counters = IoCounters {
+                reads: number(3)?,
+                writes: number(7)?,
+                read_bytes: number(5)? as f64 * SECTOR_BYTES,
+                written_bytes: number(9)? as f64 * SECTOR_BYTES,
+            };
+            Some((fields[2].to_string(), counters))
+        })
+        .collect()
+}
+
+/// Kernel name of the device behind `disk`, following symlinks such as
+/// `/dev/mapper/*` or `/dev/disk/by-uuid/*`
+pub fn device_name(disk: &Disk) -> String {
+    let device = Path::new(disk.name());
+    let device = fs::canonicalize(device).unwrap_or_else(|_| device.to_path_buf());
+    device.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
@@ -0,0 +1,301 @@
+//! Embedding cache
+//!
+//! Embeddings are kept by the SHA-256 of the text they were computed from,
+//! so identical fragments are embedded once. The cache holds at most
+//! `capacity` entries, evicting the least recently used, and with a TTL
+//! drops entries that old on access.
+
+// `#[new]` under PyO3 0.20 expands to impls that newer rustc flags
+#![allow(unknown_lints, non_local_definitions)]
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+#[cfg(feature = "python")]
+use crate::arrays::{Matrix, Vector};
+#[cfg(feature = "python")]
+use crate::errors;
+
+struct Entry {
+    embedding: Vec<f32>,
+    inserted: Instant,
+    last_used: u64,
+}
+
+/// Counters since the cache was created or last cleared
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct EmbeddingCacheStats {
+    pub capacity: usize,
+    pub size: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to make room
+    pub evictions: u64,
+    /// Entries dropped for outliving the TTL
+    pub expirations: u64,
+    /// hits / (hits + misses); 0 before any lookup
+    pub hit_rate: f64,
+}
+
+/// Bounded LRU cache of embeddings keyed by content hash
+pub struct EmbeddingCache {
+    capacity: usize,
+    ttl: Option<Duration>,
+    entries: HashMap<String, Entry>,
+    tick: u64,
+    stats: EmbeddingCacheStats,
+}
+
+impl EmbeddingCache {
+    /// Up to `capacity` embeddings (0 caches nothing), each kept at most
+    /// `ttl` when given
+    pub fn new(capacity: usize, ttl: Option<Duration>) -> Self {
+        Self { capacity, ttl, entries: HashMap::new(), tick: 0, stats: EmbeddingCacheStats::default() }
+    }
+
+    /// Hex SHA-256 of `content`
+    fn key(content: &str) -> String {
+        hex::encode(Sha256::digest(content.as_bytes()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The cached embedding of `content`, counting a hit or miss
+    pub fn get(&mut self, content: &str) -> Option<Vec<f32>> {
+        let key = Self::key(content);
+        self.tick += 1;
+        if let (Some(ttl), Some(entry)) = (self.ttl, self.entries.get(&key)) {
+            if entry.inserted.elapsed() >= ttl {
+                self.entries.remove(&key);
+                self.stats.expirations += 1;
+            }
+        }
+        match self.entries.get_mut(&key) {
+            Some(entry) => {
+                entry.last_used = self.tick;
+                self.stats.hits += 1;
+                Some(entry.embedding.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Cache `embedding` as the embedding of `content`, evicting the least
+    /// recently used entry when full
+    pub fn insert(&mut self, content: &str, embedding: Vec<f32>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let key = Self::key(content);
+        self.tick += 1;
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            if let Some(oldest) = self.entries.iter().min_by_key(|(_, entry)| entry.last_used).map(|(key, _)| key.clone()) {
+                self.entries.remove(&oldest);
+                self.stats.evictions += 1;
+            }
+        }
+        self.entries.insert(key, Entry { embedding, inserted: Instant::now(), last_used: self.tick });
+    }
+
+    /// Embeddings of `contents` in order, calling `embed` once with the
+    /// distinct contents not cached and caching what it returns
+    pub fn get_or_embed(
+        &mut self,
+        contents: &[String],
+        embed: impl FnOnce(&[String]) -> anyhow::Result<Vec<Vec<f32>>>,
+    ) -> anyhow::Result<Vec<Vec<f32>>> {
+        let mut found: Vec<Option<Vec<f32>>> = contents.iter().map(|content| self.get(content)).collect();
+        let mut seen = HashSet::new();
+        let missing: Vec<String> = contents
+            .iter()
+            .zip(&found)
+            .filter(|(content, embedding)| embedding.is_none() && seen.insert(*content))
+            .map(|(content, _)| content.clone())
+            .collect();
+        if !missing.is_empty() {
+            let embeddings = embed(&missing)?;
+            if embeddings.len() != missing.len() {
+                anyhow::bail!("Expected {} embeddings, got {}", missing.len(), embeddings.len());
+            }
+            let computed: HashMap<&String, &Vec<f32>> = missing.iter().zip(&embeddings).collect();
+            for (content, embedding) in contents.iter().zip(found.iter_mut()) {
+                if embedding.is_none() {
+                    *embedding = Some(computed[content].clone());
+                }
+            }
+            for (content, embedding) in missing.iter().zip(embeddings) {
+                self.insert(content, embedding);
+            }
+        }
+        Ok(found.into_iter().flatten().collect())
+    }
+
+    /// Drop every entry and reset the counters
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.stats = EmbeddingCacheStats::default();
+    }
+
+    pub fn stats(&self) -> EmbeddingCacheStats {
+        let lookups = self.stats.hits + self.stats.misses;
+        EmbeddingCacheStats {
+            capacity: self.capacity,
+            size: self.entries.len(),
+            hit_rate: if lookups == 0 { 0.0 } else { self.stats.hits as f64 / lookups as f64 },
+            ..self.stats.clone()
+        }
+    }
+}
+
+/// Python wrapper for EmbeddingCache
+///
+/// Embeddings of identical text are looked up by its SHA-256 instead of
+/// being recomputed. Vectors accept float32/float64 numpy arrays as well as
+/// lists and come back as lists.
+#[cfg(feature = "python")]
+#[pyclass(name = "EmbeddingCache")]
+pub struct PyEmbeddingCache {
+    cache: EmbeddingCache,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyEmbeddingCache {
+    /// Keeps up to `capacity` embeddings, evicting the least recently used;
+    /// with `ttl_secs`, entries older than that are recomputed
+    #[new]
+    #[pyo3(signature = (capacity=10000, ttl_secs=None))]
+    fn new(capacity: usize, ttl_secs: Option<f64>) -> PyResult<Self> {
+        let ttl = match ttl_secs {
+            Some(secs) if !(secs.is_finite() && secs > 0.0) => {
+                return Err(errors::validation(format!("ttl_secs must be positive, got {}", secs)));
+            }
+            secs => secs.map(Duration::from_secs_f64),
+        };
+        Ok(Self { cache: EmbeddingCache::new(capacity, ttl) })
+    }
+
+    /// The cached embedding of `content`, or None
+    fn get(&mut self, content: &str) -> Option<Vec<f32>> {
+        self.cache.get(content)
+    }
+
+    /// Cache `embedding` for `content`
+    fn put(&mut self, content: &str, embedding: Vector<'_>) {
+        self.cache.insert(content, embedding.into_vec())
+    }
+
+    /// Embeddings of `contents` in order. `embed(texts)` is called once with
+    /// the distinct texts not cached and must return one vector per text
+    /// (e.g. a model's `encode`); its results are cached. Exceptions it
+    /// raises propagate.
+    fn get_or_embed(&mut self, py: Python<'_>, contents: Vec<String>, embed: PyObject) -> PyResult<Vec<Vec<f32>>> {
+        let _span = aios_trace::span!("EmbeddingCache.get_or_embed", count = contents.len());
+        self.cache
+            .get_or_embed(&contents, |missing| {
+                let embeddings = embed.as_ref(py).call1((missing.to_vec(),))?;
+                Ok(embeddings.extract::<Matrix<'_>>()?.into_rows())
+            })
+            .map_err(|e| match e.downcast::<PyErr>() {
+                Ok(e) => e,
+                Err(e) => errors::validation(e.to_string()),
+            })
+    }
+
+    /// Hit, miss, eviction and expiry counts since creation or `clear`
+    fn stats(&self) -> EmbeddingCacheStats {
+        self.cache.stats()
+    }
+
+    /// Drop every entry and reset the counters
+    fn clear(&mut self) {
+        self.cache.clear()
+    }
+
+    fn __len__(&self) -> usize {
+        self.cache.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = EmbeddingCache::new(2, None);
+        cache.insert("a", vec![1.0]);
+        cache.insert("b", vec![2.0]);
+        assert_eq!(cache.get("a"), Some(vec![1.0]));
+        cache.insert("c", vec![3.0]);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(vec![1.0]));
+        assert_eq!(cache.get("c"), Some(vec![3.0]));
+        // Replacing a cached entry evicts nothing
+        cache.insert("c", vec![4.0]);
+        assert_eq!(cache.get("c"), Some(vec![4.0]));
+
+        let stats = cache.stats();
+        assert_eq!((stats.capacity, stats.size, stats.hits, stats.misses, stats.evictions), (2, 2, 4, 1, 1));
+        assert!((stats.hit_rate - 0.8).abs() < 1e-12);
+
+        let mut disabled = EmbeddingCache::new(0, None);
+        disabled.insert("a", vec![1.0]);
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn test_ttl_expires_on_access() {
+        let mut cache = EmbeddingCache::new(10, Some(Duration::from_millis(50)));
+        cache.insert("a", vec![1.0]);
+        assert_eq!(cache.get("a"), Some(vec![1.0]));
+        std::thread::sleep(Duration::from_millis(60));
+        cache.insert("b", vec![2.0]);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("b"), Some(vec![2.0]));
+        let stats = cache.stats();
+        assert_eq!((stats.size, stats.expirations, stats.evictions), (1, 1, 0));
+
+        cache.clear();
+        let stats = cache.stats();
+        assert_eq!((stats.size, stats.hits, stats.misses, stats.expirations, stats.hit_rate), (0, 0, 0, 0, 0.0));
+    }
+
+    #[test]
+    fn test_get_or_embed_computes_missing_once() {
+        let mut cache = EmbeddingCache::new(10, None);
+        cache.insert("x", vec![0.0]);
+        let contents: Vec<String> = ["x", "y", "z", "y"].iter().map(|s| s.to_string()).collect();
+        let embeddings = cache
+            .get_or_embed(&contents, |missing| {
+                assert_eq!(missing, ["y", "z"]);
+                Ok(missing.iter().map(|content| vec![content.len() as f32, content.as_bytes()[0] as f32]).collect())
+            })
+            .unwrap();
+        assert_eq!(embeddings, [vec![0.0], vec![1.0, 121.0], vec![1.0, 122.0], vec![1.0, 121.0]]);
+
+        // Everything is cached now
+        let again = cache.get_or_embed(&contents, |_| panic!("nothing to embed")).unwrap();
+        assert_eq!(again, embeddings);
+
+        let wrong = cache.get_or_embed(&["w".to_string()], |_| Ok(Vec::new()));
+        assert!(wrong.unwrap_err().to_string().contains("Expected 1 embeddings, got 0"));
+    }
+}
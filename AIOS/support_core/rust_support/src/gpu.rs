@@ -0,0 +1,131 @@
+//! GPU utilization, VRAM and temperature
+//!
+//! NVIDIA GPUs are read through `nvidia-smi`, which ships with the driver;
+//! when it is missing, AMD GPUs are read from the amdgpu driver's sysfs files
+//! on Linux. Any reading a GPU does not report is None.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// `nvidia-smi` is killed after this long; a wedged driver can hang it
+const NVIDIA_SMI_TIMEOUT: Duration = Duration::from_secs(5);
+
+const AMD_VENDOR_ID: &str = "0x1002";
+
+/// One GPU's readings
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuStats {
+    pub name: String,
+    pub utilization_percent: Option<f64>,
+    pub vram_used_mb: Option<f64>,
+    pub vram_total_mb: Option<f64>,
+    pub temperature_c: Option<f64>,
+}
+
+impl GpuStats {
+    pub fn vram_usage_percent(&self) -> Option<f64> {
+        match (self.vram_used_mb, self.vram_total_mb) {
+            (Some(used), Some(total)) if total > 0.0 => Some(used / total * 100.0),
+            _ => None,
+        }
+    }
+}
+
+/// Every GPU found, NVIDIA ones if `nvidia-smi` works, AMD ones otherwise
+pub fn gpus() -> Vec<GpuStats> {
+    match nvidia_smi() {
+        Some(gpus) => gpus,
+        None => amdgpu(Path::new("/sys/class/drm")),
+    }
+}
+
+fn nvidia_smi() -> Option<Vec<GpuStats>> {
+    let mut child = Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=utilization.gpu,memory.used,memory.total,temperature.gpu,name",
+            "--format=csv,noheader,nounits",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if start.elapsed() < NVIDIA_SMI_TIMEOUT => thread::sleep(Duration::from_millis(10)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).lines().filter_map(parse_nvidia_smi_line).collect())
+}
+
+/// "[N/A]" and "[Not Supported]" read as None
+fn reading(field: &str) -> Option<f64> {
+    field.trim().parse().ok()
+}
+
+fn parse_nvidia_smi_line(line: &str) -> Option<GpuStats> {
+    // The name comes last as it may itself contain commas
+    let fields: Vec<&str> = line.splitn(5, ',').collect();
+    if fields.len() != 5 {
+        return None;
+    }
+    Some(GpuStats {
+        name: fields[4].trim().to_string(),
+        utilization_percent: reading(fields[0]),
+        vram_used_mb: reading(fields[1]),
+        vram_total_mb: reading(fields[2]),
+        temperature_c: reading(fields[3]),
+    })
+}
+
+fn read_number(path: &Path) -> Option<f64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// amdgpu cards under `drm` (`/sys/class/drm`), skipping connector entries
+/// such as `card0-DP-1`
+fn amdgpu(drm: &Path) -> Vec<GpuStats> {
+    let Ok(entries) = fs::read_dir(drm) else {
+        return Vec::new();
+    };
+    let mut cards: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("card") && !name.contains('-'))
+        .collect();
+    cards.sort();
+    cards
+        .into_iter()
+        .filter_map(|card| {
+            let device = drm.join(&card).join("device");
+            if fs::read_to_string(device.join("vendor")).ok()?.trim() != AMD_VENDOR_ID {
+                return None;
+            }
+            let megabytes = |file: &str| read_number(&device.join(file)).map(|bytes| bytes / 1024.0 / 1024.0);
+            let temperature_c = fs::read_dir(device.join("hwmon"))
+                .ok()
+                .and_then(|hwmons| hwmons.flatten().find_map(|hwmon| read_number(&hwmon.path().join("temp1_input"))))
+                .map(|millidegrees| millidegrees / 1000.0);
+            Some(GpuStats {
+                name: format!("amdgpu {}", card),
+                utilization_percent: read_number(&device.join("gpu_busy_percent")),
+                vram_used_mb: megabytes("mem_info_vram_used"),
+                vram_total_mb: megabytes("mem_info_vram_total"),
+                temperature_c,
+            })
+        })
+        .collect()
+}
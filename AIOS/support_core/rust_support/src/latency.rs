@@ -0,0 +1,144 @@
+//! Probe latency history for the network health check
+//!
+//! Each endpoint keeps its most recent successful probe latencies in a
+//! fixed-size window. Failed probes are not recorded; they already show in
+//! the check's status. Jitter is the mean absolute difference between
+//! consecutive latencies, as in RFC 3550 but unsmoothed.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+/// Recent latencies per endpoint
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistory {
+    window: usize,
+    samples: BTreeMap<String, VecDeque<f64>>,
+}
+
+/// Summary of one endpoint's window, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub mean_ms: f64,
+    pub p95_ms: f64,
+    pub jitter_ms: f64,
+}
+
+impl LatencyHistory {
+    /// Keep up to `window` latencies per endpoint; 0 keeps none
+    pub fn new(window: usize) -> Self {
+        Self { window, samples: BTreeMap::new() }
+    }
+
+    /// Add a probe latency for `endpoint`, dropping its oldest beyond the
+    /// window
+    pub fn record(&mut self, endpoint: &str, latency: Duration) {
+        if self.window == 0 {
+            return;
+        }
+        let samples = self.samples.entry(endpoint.to_string()).or_default();
+        if samples.len() == self.window {
+            samples.pop_front();
+        }
+        samples.push_back(latency.as_secs_f64() * 1000.0);
+    }
+
+    /// Statistics of every endpoint with at least one latency, by endpoint
+    pub fn stats(&self) -> Vec<(&str, LatencyStats)> {
+        self.samples
+            .iter()
+            .filter(|(_, samples)| !samples.is_empty())
+            .map(|(endpoint, samples)| (endpoint.as_str(), summarize(samples)))
+            .collect()
+    }
+}
+
+fn summarize(samples: &VecDeque<f64>) -> LatencyStats {
+    let count = samples.len();
+    let mut sorted: Vec<f64> = samples.iter().copied().collect();
+    sorted.sort_by(f64::total_cmp);
+    // Nearest rank
+    let p95 = sorted[((count as f64 * 0.95).ceil() as usize).clamp(1, count) - 1];
+    let jitter = if count < 2 {
+        0.0
+    } else {
+        samples.iter().zip(samples.iter().skip(1)).map(|(a, b)| (b - a).abs()).sum::<f64>() / (count - 1) as f64
+    };
+    LatencyStats { samples: count, mean_ms: samples.iter().sum::<f64>() / count as f64, p95_ms: p95, jitter_ms: jitter }
+}
+
+/// Metric name fragment for an endpoint: lowercased, with runs of anything
+/// but letters and digits as one `_`, e.g. "localhost_1234" for
+/// "localhost:1234"
+pub fn metric_key(endpoint: &str) -> String {
+    let mut key = String::new();
+    for c in endpoint.chars() {
+        if c.is_ascii_alphanumeric() {
+            key.push(c.to_ascii_lowercase());
+        } else if !key.is_empty() && !key.ends_with('_') {
+            key.push('_');
+        }
+    }
+    key.trim_end_matches('_').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(endpoint: &str, latencies_ms: &[u64]) -> LatencyHistory {
+        let mut history = LatencyHistory::new(100);
+        for &ms in latencies_ms {
+            history.record(endpoint, Duration::from_millis(ms));
+        }
+        history
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn test_p95_is_nearest_rank() {
+        let latencies: Vec<u64> = (1..=20).rev().collect();
+        let stats = history("a", &latencies).stats()[0].1;
+        assert_eq!(stats.samples, 20);
+        assert_close(stats.mean_ms, 10.5);
+        assert_close(stats.p95_ms, 19.0);
+        assert_close(history("a", &[7]).stats()[0].1.p95_ms, 7.0);
+        assert_close(history("a", &[1, 2, 3, 100]).stats()[0].1.p95_ms, 100.0);
+    }
+
+    #[test]
+    fn test_jitter_is_mean_consecutive_difference() {
+        assert_close(history("a", &[10, 20, 10]).stats()[0].1.jitter_ms, 10.0);
+        assert_close(history("a", &[10, 12, 15, 15]).stats()[0].1.jitter_ms, 5.0 / 3.0);
+        // In arrival order, not sorted
+        assert_close(history("a", &[1, 3, 2]).stats()[0].1.jitter_ms, 1.5);
+        assert_close(history("a", &[10]).stats()[0].1.jitter_ms, 0.0);
+    }
+
+    #[test]
+    fn test_window_drops_oldest() {
+        let mut history = LatencyHistory::new(3);
+        for ms in [100, 1, 2, 3] {
+            history.record("a", Duration::from_millis(ms));
+        }
+        history.record("b", Duration::from_millis(5));
+        let stats = history.stats();
+        assert_eq!(stats.iter().map(|(endpoint, _)| *endpoint).collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(stats[0].1.samples, 3);
+        assert_close(stats[0].1.mean_ms, 2.0);
+
+        let mut none = LatencyHistory::new(0);
+        none.record("a", Duration::from_millis(1));
+        assert!(none.stats().is_empty());
+    }
+
+    #[test]
+    fn test_metric_key() {
+        assert_eq!(metric_key("localhost:1234"), "localhost_1234");
+        assert_eq!(metric_key("https://Example.com/"), "https_example_com");
+        assert_eq!(metric_key("--a--b--"), "a_b");
+    }
+}
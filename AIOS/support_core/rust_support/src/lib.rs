@@ -1,95 +1,428 @@
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
 use chrono::{DateTime, Utc};
 use rayon::prelude::*;
-use sysinfo::{System, CpuRefreshKind, MemoryRefreshKind, RefreshKind};
+use sysinfo::{Components, Disk, Disks, System, CpuRefreshKind, MemoryRefreshKind, RefreshKind};
 use anyhow::Result;
+#[cfg(feature = "python")]
+use aios_config::AiosConfig;
+use aios_config::SupportConfig;
+use aios_store::{Migration, Namespace, Store, Value as StoreValue};
+
+mod cache;
+mod cron;
+mod dependencies;
+mod disks;
+mod embedding_cache;
+mod gpu;
+mod latency;
+mod logs;
+mod metrics_history;
+mod monitor;
+mod network;
+mod processes;
+pub mod prometheus;
+mod quantization;
+mod report;
+mod sensors;
+mod vectors;
+mod watchdog;
+
+pub use cache::{CacheIssue, CacheReport};
+pub use dependencies::PythonRunner;
+pub use embedding_cache::{EmbeddingCache, EmbeddingCacheStats};
+#[cfg(feature = "python")]
+use embedding_cache::PyEmbeddingCache;
+pub use metrics_history::MetricsWindow;
+pub use monitor::{MonitorSample, TransitionCallback};
+pub use quantization::QuantizationMode;
+pub use report::ReportFormat;
+pub use vectors::{CompactionReport, Metric, VectorIndex};
+pub use watchdog::StallCallback;
+use cron::Cron;
+use latency::LatencyHistory;
+use logs::LogSample;
+use metrics_history::MetricsHistory;
+use monitor::{Monitor, Schedule};
+use processes::ProcessTarget;
+use report::HealthReport;
+use watchdog::{Heartbeats, Watchdog};
+
+#[cfg(feature = "python")]
+mod arrays;
+
+#[cfg(feature = "python")]
+use arrays::{Matrix, Vector};
 
 /// Health check result
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all))]
 pub struct HealthCheckResult {
-    #[pyo3(get)]
+    /// Which check this is, e.g. "disk_space"
+    pub name: String,
     pub status: String,
-    #[pyo3(get)]
     pub message: String,
-    #[pyo3(get)]
     pub critical: bool,
-    #[pyo3(get)]
     pub duration_ms: u64,
-    #[pyo3(get)]
     pub error: Option<String>,
 }
 
 /// System health summary
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all))]
 pub struct SystemHealthSummary {
-    #[pyo3(get)]
     pub overall_status: String,
-    #[pyo3(get)]
     pub total_checks: u32,
-    #[pyo3(get)]
     pub passed_checks: u32,
-    #[pyo3(get)]
     pub failed_checks: u32,
-    #[pyo3(get)]
     pub warnings: u32,
-    #[pyo3(get)]
     pub total_duration_ms: u64,
-    #[pyo3(get)]
     pub timestamp: String,
 }
 
-/// FAISS search result
+/// Vector search hit
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all))]
 pub struct FAISSSearchResult {
-    #[pyo3(get)]
     pub vector_id: String,
-    #[pyo3(get)]
     pub similarity_score: f32,
-    #[pyo3(get)]
     pub metadata: String,
 }
 
+/// Tables of the support core's store namespace
+const STORE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        sql: "CREATE TABLE support_health_history (
+                  id INTEGER PRIMARY KEY AUTOINCREMENT,
+                  overall_status TEXT NOT NULL,
+                  total_checks INTEGER NOT NULL,
+                  passed_checks INTEGER NOT NULL,
+                  failed_checks INTEGER NOT NULL,
+                  warnings INTEGER NOT NULL,
+                  total_duration_ms INTEGER NOT NULL,
+                  timestamp TEXT NOT NULL
+              );",
+    },
+    // Which checks ran ("quick" or "full") and their results as JSON; NULL
+    // in rows recorded before
+    Migration {
+        version: 2,
+        sql: "ALTER TABLE support_health_history ADD COLUMN mode TEXT;
+              ALTER TABLE support_health_history ADD COLUMN checks TEXT;",
+    },
+];
+
+/// Health checks in the order they run, by result name
+const FULL_CHECKS: &[&str] = &[
+    "python_environment",
+    "dependencies",
+    "file_system",
+    "memory_usage",
+    "disk_space",
+    "cpu_usage",
+    "gpu",
+    "thermal",
+    "network_connectivity",
+    "llm_endpoint",
+    "processes",
+    "watched_processes",
+    "cache_integrity",
+    "log_directory",
+    "heartbeats",
+];
+const QUICK_CHECKS: &[&str] = &["python_environment", "file_system", "memory_usage", "heartbeats"];
+
+/// Recorded summaries included in a health report
+const REPORT_HISTORY: usize = 20;
+
 /// Rust implementation of AIOS Support Core
 pub struct RustSupportCore {
     cache_dir: PathBuf,
     system: System,
-    faiss_index: Option<()>, // Placeholder for FAISS
-    dimension: usize,
+    disks: Disks,
+    components: Components,
+    /// Behind its own lock, so holders of `vector_index` search and add
+    /// without waiting on the core (e.g. on health checks in aios-server)
+    index: Arc<RwLock<VectorIndex>>,
+    thresholds: SupportConfig,
+    /// Keeps the health check history once attached
+    store: Option<Namespace>,
+    /// Background monitoring, while running
+    monitor: Option<Monitor>,
+    /// Shared with the monitoring core so watching takes effect there too
+    watched: Arc<Mutex<Vec<ProcessTarget>>>,
+    /// Imports packages for the dependency check; a subprocess without one
+    python: Option<PythonRunner>,
+    /// Log directory size at the previous log check
+    log_sample: Option<LogSample>,
+    /// Recent network probe latencies; shared with the monitoring core so
+    /// its probes show in the metrics
+    latency: Arc<Mutex<LatencyHistory>>,
+    /// Components' last heartbeats; shared with the monitoring core so its
+    /// checks see them
+    heartbeats: Arc<Mutex<Heartbeats>>,
+    /// Stall watchdog, while running
+    watchdog: Option<Watchdog>,
+    /// Recent metrics samples; shared with the monitoring core so its
+    /// samples are kept too
+    metrics_history: Arc<Mutex<MetricsHistory>>,
 }
 
 impl RustSupportCore {
-    /// Initialize the Rust support core
-    pub fn new(cache_dir: &str, dimension: usize) -> Result<Self> {
+    /// Initialize the Rust support core; fails on inconsistent `thresholds`
+    pub fn new(cache_dir: &str, dimension: usize, thresholds: SupportConfig) -> Result<Self> {
+        thresholds.validate()?;
+        let watched = thresholds
+            .watched_processes
+            .iter()
+            .map(|target| ProcessTarget::parse(target))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(anyhow::Error::msg)?;
+        if let Some(name) = thresholds.checks.keys().find(|name| !FULL_CHECKS.contains(&name.as_str())) {
+            anyhow::bail!("Unknown health check {:?} in support checks; expected one of {}", name, FULL_CHECKS.join(", "));
+        }
+        if !thresholds.schedule_full_cron.trim().is_empty() {
+            Cron::parse(&thresholds.schedule_full_cron).map_err(anyhow::Error::msg)?;
+        }
         let cache_path = PathBuf::from(cache_dir);
+        let latency = LatencyHistory::new(thresholds.network_latency_window);
+        let metrics_history = MetricsHistory::new(thresholds.metrics_history_size);
         let mut system = System::new_with_specifics(
             RefreshKind::new()
                 .with_cpu(CpuRefreshKind::everything())
                 .with_memory(MemoryRefreshKind::everything())
         );
         
-        // Initialize FAISS index (simplified for now)
-        let faiss_index = None; // Will implement FAISS integration later
-        
         Ok(Self {
             cache_dir: cache_path,
             system,
-            faiss_index,
-            dimension,
+            disks: Disks::new_with_refreshed_list(),
+            components: Components::new_with_refreshed_list(),
+            index: Arc::new(RwLock::new(VectorIndex::new(dimension, Metric::Cosine))),
+            thresholds,
+            store: None,
+            monitor: None,
+            watched: Arc::new(Mutex::new(watched)),
+            python: None,
+            log_sample: None,
+            latency: Arc::new(Mutex::new(latency)),
+            heartbeats: Arc::new(Mutex::new(Heartbeats::default())),
+            watchdog: None,
+            metrics_history: Arc::new(Mutex::new(metrics_history)),
         })
     }
+
+    /// Compare vectors under `metric` instead of cosine similarity; for a
+    /// freshly created core, before any vectors are added
+    pub fn with_metric(self, metric: Metric) -> Self {
+        let dimension = self.vectors().dimension();
+        *self.vectors_mut() = VectorIndex::new(dimension, metric);
+        self
+    }
+
+    /// Import the dependency check's packages with `runner`, e.g. in the
+    /// embedded interpreter, instead of a `python3` subprocess
+    pub fn with_python_runner(mut self, runner: PythonRunner) -> Self {
+        self.python = Some(runner);
+        self
+    }
+
+    /// Record every health check summary in `store`
+    pub fn attach_store(&mut self, store: &Store) -> Result<()> {
+        self.store = Some(store.namespace("support", STORE_MIGRATIONS)?);
+        Ok(())
+    }
+
+    /// Up to `limit` recorded health check summaries, newest first; None
+    /// without a store
+    pub fn health_history(&self, limit: usize) -> Result<Option<Vec<SystemHealthSummary>>> {
+        let Some(store) = &self.store else {
+            return Ok(None);
+        };
+        let rows = store.query(
+            "SELECT overall_status, total_checks, passed_checks, failed_checks, warnings, total_duration_ms, timestamp
+             FROM support_health_history ORDER BY id DESC LIMIT ?",
+            &[(limit.min(i64::MAX as usize) as i64).into()],
+        )?;
+        let count = |value: &StoreValue| value.as_i64().unwrap_or(0);
+        let history = rows
+            .iter()
+            .map(|row| SystemHealthSummary {
+                overall_status: row[0].as_str().unwrap_or_default().to_string(),
+                total_checks: count(&row[1]) as u32,
+                passed_checks: count(&row[2]) as u32,
+                failed_checks: count(&row[3]) as u32,
+                warnings: count(&row[4]) as u32,
+                total_duration_ms: count(&row[5]) as u64,
+                timestamp: row[6].as_str().unwrap_or_default().to_string(),
+            })
+            .collect();
+        Ok(Some(history))
+    }
+
+    /// A core for a background thread, sharing this one's settings, store,
+    /// watched processes and Python runner but not its vectors
+    fn sibling(&self) -> Result<Self> {
+        let mut core = Self::new(&self.cache_dir.to_string_lossy(), self.vectors().dimension(), self.thresholds.clone())?;
+        core.store = self.store.clone();
+        core.watched = Arc::clone(&self.watched);
+        core.latency = Arc::clone(&self.latency);
+        core.heartbeats = Arc::clone(&self.heartbeats);
+        core.metrics_history = Arc::clone(&self.metrics_history);
+        core.python = self.python.clone();
+        Ok(core)
+    }
+
+    /// Run the health checks and sample the metrics every `interval` on a
+    /// background thread, replacing any running monitor; `on_transition` is
+    /// called there whenever the overall status changes
+    pub fn start_monitoring(&mut self, interval: Duration, quick_mode: bool, on_transition: Option<TransitionCallback>) -> Result<()> {
+        if interval.is_zero() {
+            anyhow::bail!("Monitoring interval must be positive");
+        }
+        self.monitor = None;
+        let schedule = Schedule { every: Some((interval, quick_mode)), full_cron: None };
+        self.monitor = Some(Monitor::start(self.sibling()?, schedule, on_transition)?);
+        Ok(())
+    }
+
+    /// Run quick checks every `quick_every` and full checks whenever the
+    /// cron expression `full_cron` matches, on the monitoring thread in
+    /// place of any running monitor. None takes the `schedule_quick_minutes`
+    /// and `schedule_full_cron` settings; a zero interval or empty expression
+    /// turns that part off. False, starting nothing, when both are off.
+    pub fn start_scheduler(
+        &mut self,
+        quick_every: Option<Duration>,
+        full_cron: Option<&str>,
+        on_transition: Option<TransitionCallback>,
+    ) -> Result<bool> {
+        let quick_every = quick_every.unwrap_or(Duration::from_secs(self.thresholds.schedule_quick_minutes * 60));
+        let full_cron = full_cron.unwrap_or(&self.thresholds.schedule_full_cron).trim();
+        let full_cron = if full_cron.is_empty() { None } else { Some(Cron::parse(full_cron).map_err(anyhow::Error::msg)?) };
+        if quick_every.is_zero() && full_cron.is_none() {
+            return Ok(false);
+        }
+        self.monitor = None;
+        let schedule = Schedule { every: (!quick_every.is_zero()).then_some((quick_every, true)), full_cron };
+        self.monitor = Some(Monitor::start(self.sibling()?, schedule, on_transition)?);
+        Ok(true)
+    }
+
+    /// Stop background monitoring, waiting for a sample in progress; false
+    /// when none was running
+    pub fn stop_monitoring(&mut self) -> bool {
+        self.monitor.take().is_some()
+    }
+
+    /// The latest background monitoring sample; None when not monitoring or
+    /// before the first sample
+    pub fn latest_sample(&self) -> Option<MonitorSample> {
+        self.monitor.as_ref().and_then(Monitor::latest)
+    }
+
+    /// Follow a process, by name or PID, in the metrics and health checks;
+    /// false when it was already watched
+    pub fn watch_process(&mut self, target: &str) -> Result<bool> {
+        let target = ProcessTarget::parse(target).map_err(anyhow::Error::msg)?;
+        let mut watched = self.watched.lock().unwrap_or_else(|e| e.into_inner());
+        if watched.contains(&target) {
+            return Ok(false);
+        }
+        watched.push(target);
+        Ok(true)
+    }
+
+    /// Stop following a process; false when it was not watched
+    pub fn unwatch_process(&mut self, target: &str) -> Result<bool> {
+        let target = ProcessTarget::parse(target).map_err(anyhow::Error::msg)?;
+        let mut watched = self.watched.lock().unwrap_or_else(|e| e.into_inner());
+        let before = watched.len();
+        watched.retain(|watched| *watched != target);
+        Ok(watched.len() < before)
+    }
+
+    /// Watched processes, names lowercased
+    pub fn watched_processes(&self) -> Vec<String> {
+        self.watched.lock().unwrap_or_else(|e| e.into_inner()).iter().map(ProcessTarget::label).collect()
+    }
+
+    /// Record that `component` is alive; it counts as stalled when the next
+    /// heartbeat is not in within `interval`, by default the
+    /// `heartbeat_interval_secs` setting
+    pub fn heartbeat(&self, component: &str, interval: Option<Duration>) -> Result<()> {
+        if component.trim().is_empty() {
+            anyhow::bail!("Heartbeat component name must not be empty");
+        }
+        let interval = interval.unwrap_or(Duration::from_secs(self.thresholds.heartbeat_interval_secs));
+        if interval.is_zero() {
+            anyhow::bail!("Heartbeat interval must be positive");
+        }
+        if self.heartbeats.lock().unwrap_or_else(|e| e.into_inner()).beat(component, interval) {
+            aios_events::event!("health.recovered", component = component);
+        }
+        Ok(())
+    }
+
+    /// Stop expecting heartbeats from `component`; false when it sent none
+    pub fn forget_component(&self, component: &str) -> bool {
+        self.heartbeats.lock().unwrap_or_else(|e| e.into_inner()).remove(component)
+    }
+
+    /// Watch heartbeats on a background thread, replacing any running
+    /// watchdog; `on_stall` is called there once for each stall
+    pub fn start_watchdog(&mut self, on_stall: Option<StallCallback>) -> Result<()> {
+        self.watchdog = None;
+        self.watchdog = Some(Watchdog::start(Arc::clone(&self.heartbeats), on_stall)?);
+        Ok(())
+    }
+
+    /// Stop the stall watchdog; false when none was running
+    pub fn stop_watchdog(&mut self) -> bool {
+        self.watchdog.take().is_some()
+    }
+
+    fn record_health(&self, summary: &SystemHealthSummary, checks: &[HealthCheckResult], quick_mode: bool) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        store.execute(
+            "INSERT INTO support_health_history
+             (overall_status, total_checks, passed_checks, failed_checks, warnings, total_duration_ms, timestamp, mode, checks)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            &[
+                summary.overall_status.as_str().into(),
+                (summary.total_checks as i64).into(),
+                (summary.passed_checks as i64).into(),
+                (summary.failed_checks as i64).into(),
+                (summary.warnings as i64).into(),
+                (summary.total_duration_ms as i64).into(),
+                summary.timestamp.as_str().into(),
+                (if quick_mode { "quick" } else { "full" }).into(),
+                serde_json::to_string(checks)?.into(),
+            ],
+        )?;
+        Ok(())
+    }
     
     /// Run comprehensive health checks
     pub fn run_health_checks(&mut self, quick_mode: bool) -> Result<SystemHealthSummary> {
+        self.run_health_checks_detailed(quick_mode).map(|(summary, _)| summary)
+    }
+
+    /// Run health checks, returning each check's result with the summary
+    pub fn run_health_checks_detailed(&mut self, quick_mode: bool) -> Result<(SystemHealthSummary, Vec<HealthCheckResult>)> {
         let start_time = SystemTime::now();
         self.system.refresh_all();
+        self.disks.refresh_list();
+        self.components.refresh();
         
         let checks = if quick_mode {
             self.run_quick_health_checks()?
@@ -102,7 +435,7 @@ impl RustSupportCore {
         // Analyze results
         let total_checks = checks.len() as u32;
         let passed_checks = checks.iter().filter(|c| c.status == "PASS").count() as u32;
-        let failed_checks = checks.iter().filter(|c| c.status == "FAIL").count() as u32;
+        let failed_checks = checks.iter().filter(|c| c.status == "FAIL" || c.status == "CRITICAL").count() as u32;
         let warnings = checks.iter().filter(|c| c.status == "WARNING").count() as u32;
         
         let overall_status = if failed_checks > 0 {
@@ -112,8 +445,21 @@ impl RustSupportCore {
         } else {
             "HEALTHY"
         };
+        if overall_status != "HEALTHY" {
+            aios_events::event!(
+                "health.alert",
+                status = overall_status,
+                failed_checks = failed_checks,
+                warnings = warnings,
+                checks = checks
+                    .iter()
+                    .filter(|c| c.status != "PASS")
+                    .map(|c| serde_json::json!({ "name": c.name, "status": c.status, "message": c.message, "critical": c.critical }))
+                    .collect::<Vec<_>>(),
+            );
+        }
         
-        Ok(SystemHealthSummary {
+        let summary = SystemHealthSummary {
             overall_status: overall_status.to_string(),
             total_checks,
             passed_checks,
@@ -121,32 +467,63 @@ impl RustSupportCore {
             warnings,
             total_duration_ms: total_duration,
             timestamp: Utc::now().to_rfc3339(),
-        })
+        };
+        if let Err(e) = self.record_health(&summary, &checks, quick_mode) {
+            eprintln!("Failed to record health check history: {}", e);
+        }
+        Ok((summary, checks))
     }
     
     /// Run quick health checks (essential only)
     fn run_quick_health_checks(&mut self) -> Result<Vec<HealthCheckResult>> {
-        let checks = vec![
-            self.check_python_environment()?,
-            self.check_file_system()?,
-            self.check_memory_usage()?,
-        ];
-        Ok(checks)
+        self.run_checks(QUICK_CHECKS)
     }
     
     /// Run full health checks
     fn run_full_health_checks(&mut self) -> Result<Vec<HealthCheckResult>> {
-        let checks = vec![
-            self.check_python_environment()?,
-            self.check_dependencies()?,
-            self.check_file_system()?,
-            self.check_memory_usage()?,
-            self.check_disk_space()?,
-            self.check_cpu_usage()?,
-            self.check_network_connectivity()?,
-            self.check_processes()?,
-            self.check_cache_integrity()?,
-        ];
+        self.run_checks(FULL_CHECKS)
+    }
+
+    /// Run the enabled checks among `names`, in order, with their configured
+    /// severity applied
+    fn run_checks(&mut self, names: &[&str]) -> Result<Vec<HealthCheckResult>> {
+        let mut checks = Vec::with_capacity(names.len());
+        for &name in names {
+            let settings = self.thresholds.checks.get(name).cloned().unwrap_or_default();
+            if !settings.enabled {
+                continue;
+            }
+            let mut check = match name {
+                "python_environment" => self.check_python_environment()?,
+                "dependencies" => self.check_dependencies()?,
+                "file_system" => self.check_file_system()?,
+                "memory_usage" => self.check_memory_usage()?,
+                "disk_space" => self.check_disk_space()?,
+                "cpu_usage" => self.check_cpu_usage()?,
+                "gpu" => self.check_gpu()?,
+                "thermal" => self.check_thermal()?,
+                "network_connectivity" => self.check_network_connectivity()?,
+                "llm_endpoint" => self.check_llm_endpoint()?,
+                "processes" => self.check_processes()?,
+                "watched_processes" => self.check_watched_processes()?,
+                "cache_integrity" => self.check_cache_integrity()?,
+                "log_directory" => self.check_log_directory()?,
+                "heartbeats" => self.check_heartbeats()?,
+                other => anyhow::bail!("Unknown health check {:?}", other),
+            };
+            match settings.severity.as_str() {
+                "warning" if check.status == "FAIL" || check.status == "CRITICAL" => {
+                    check.status = "WARNING".to_string();
+                    check.critical = false;
+                }
+                "critical" if check.status != "PASS" => {
+                    check.status = "CRITICAL".to_string();
+                    check.critical = true;
+                }
+                _ => {}
+            }
+            checks.push(check);
+        }
         Ok(checks)
     }
     
@@ -161,6 +538,7 @@ impl RustSupportCore {
         let duration = start_time.elapsed()?.as_millis() as u64;
         
         Ok(HealthCheckResult {
+            name: "python_environment".to_string(),
             status: status.to_string(),
             message: format!("Python environment available: {}", python_version),
             critical: false,
@@ -169,26 +547,43 @@ impl RustSupportCore {
         })
     }
     
-    /// Check dependencies
+    /// Check every configured Python package imports, reporting versions
     fn check_dependencies(&self) -> Result<HealthCheckResult> {
         let start_time = SystemTime::now();
         
-        // Check if key dependencies are available
-        let mut missing_deps: Vec<String> = Vec::new();
-        let deps = vec!["numpy", "faiss", "serde", "chrono"];
-        
-        // This is a simplified check - in a real implementation,
-        // you'd check for actual Python packages
-        let status = if missing_deps.is_empty() { "PASS" } else { "WARNING" };
+        let packages = &self.thresholds.python_packages;
+        let (status, message, error) = match dependencies::check(packages, self.python.as_ref()) {
+            Err(e) => ("WARNING", "Dependencies: not checked".to_string(), Some(format!("Python unavailable: {}", e))),
+            Ok(results) => {
+                let missing: Vec<String> = results
+                    .iter()
+                    .filter(|dependency| !dependency.imported)
+                    .map(|dependency| format!("{} ({})", dependency.name, dependency.error.as_deref().unwrap_or("import failed")))
+                    .collect();
+                let found: Vec<String> = results
+                    .iter()
+                    .filter(|dependency| dependency.imported)
+                    .map(|dependency| format!("{} {}", dependency.name, dependency.version.as_deref().unwrap_or("(no version)")))
+                    .collect();
+                let message = if found.is_empty() {
+                    format!("Dependencies: {} of {} available", found.len(), packages.len())
+                } else {
+                    format!("Dependencies: {} of {} available: {}", found.len(), packages.len(), found.join(", "))
+                };
+                let error = (!missing.is_empty()).then(|| format!("Failed imports: {}", missing.join(", ")));
+                (if missing.is_empty() { "PASS" } else { "WARNING" }, message, error)
+            }
+        };
         
         let duration = start_time.elapsed()?.as_millis() as u64;
         
         Ok(HealthCheckResult {
+            name: "dependencies".to_string(),
             status: status.to_string(),
-            message: format!("Dependencies checked: {} available", deps.len()),
+            message,
             critical: false,
             duration_ms: duration,
-            error: None,
+            error,
         })
     }
     
@@ -216,6 +611,7 @@ impl RustSupportCore {
         let duration = start_time.elapsed()?.as_millis() as u64;
         
         Ok(HealthCheckResult {
+            name: "file_system".to_string(),
             status: status.to_string(),
             message: format!("Cache directory: exists={}, writable={}", cache_exists, cache_writable),
             critical: true,
@@ -232,38 +628,73 @@ impl RustSupportCore {
         let used_memory = self.system.used_memory();
         let memory_percent = (used_memory as f64 / total_memory as f64) * 100.0;
         
-        let status = if memory_percent > 90.0 { "CRITICAL" } else if memory_percent > 80.0 { "WARNING" } else { "PASS" };
+        let critical = memory_percent > self.thresholds.memory_critical_percent;
+        let status = if critical { "CRITICAL" } else if memory_percent > self.thresholds.memory_warning_percent { "WARNING" } else { "PASS" };
         
         let duration = start_time.elapsed()?.as_millis() as u64;
         
         Ok(HealthCheckResult {
+            name: "memory_usage".to_string(),
             status: status.to_string(),
             message: format!("Memory usage: {:.1}% ({}/{} MB)", memory_percent, used_memory / 1024 / 1024, total_memory / 1024 / 1024),
-            critical: memory_percent > 90.0,
+            critical,
             duration_ms: duration,
-            error: if memory_percent > 90.0 { Some("High memory usage detected".to_string()) } else { None },
+            error: if critical { Some("High memory usage detected".to_string()) } else { None },
         })
     }
     
-    /// Check disk space
+    /// The mounted disk holding the cache directory: the one whose mount
+    /// point is the longest prefix of its (nearest existing) path
+    fn cache_disk(&self) -> Option<&Disk> {
+        let absolute = self.cache_dir.ancestors().find_map(|dir| dir.canonicalize().ok())?;
+        self.disks
+            .list()
+            .iter()
+            .filter(|disk| absolute.starts_with(disk.mount_point()))
+            .max_by_key(|disk| disk.mount_point().components().count())
+    }
+
+    /// Check disk space on the disk holding the cache directory, listing
+    /// every mounted disk's usage in the message
     fn check_disk_space(&self) -> Result<HealthCheckResult> {
         let start_time = SystemTime::now();
         
-        // Simplified disk check for now
-        let total_space = 100_000_000_000u64; // 100GB placeholder
-        let total_available = 80_000_000_000u64; // 80GB placeholder
-        
-        let space_percent = ((total_space - total_available) as f64 / total_space as f64) * 100.0;
-        let status = if space_percent > 95.0 { "CRITICAL" } else if space_percent > 85.0 { "WARNING" } else { "PASS" };
+        let Some(disk) = self.cache_disk().filter(|disk| disk.total_space() > 0) else {
+            return Ok(HealthCheckResult {
+                name: "disk_space".to_string(),
+                status: "WARNING".to_string(),
+                message: format!("Disk usage: no mounted disk found for {}", self.cache_dir.display()),
+                critical: false,
+                duration_ms: start_time.elapsed()?.as_millis() as u64,
+                error: Some("Could not determine the cache directory's disk".to_string()),
+            });
+        };
+        let space_percent = disk_usage_percent(disk);
+        let critical = space_percent > self.thresholds.disk_critical_percent;
+        let status = if critical { "CRITICAL" } else if space_percent > self.thresholds.disk_warning_percent { "WARNING" } else { "PASS" };
+        let all_disks: Vec<String> = self
+            .disks
+            .list()
+            .iter()
+            .filter(|disk| disk.total_space() > 0)
+            .map(|disk| format!("{} {:.1}% of {} GB", disk.mount_point().display(), disk_usage_percent(disk), disk.total_space() / 1024 / 1024 / 1024))
+            .collect();
         
         let duration = start_time.elapsed()?.as_millis() as u64;
         
         Ok(HealthCheckResult {
+            name: "disk_space".to_string(),
             status: status.to_string(),
-            message: format!("Disk usage: {:.1}% ({} GB available)", space_percent, total_available / 1024 / 1024 / 1024),
-            critical: space_percent > 95.0,
+            message: format!(
+                "Disk usage: {:.1}% ({} GB available) on {}; disks: {}",
+                space_percent,
+                disk.available_space() / 1024 / 1024 / 1024,
+                disk.mount_point().display(),
+                all_disks.join(", ")
+            ),
+            critical,
             duration_ms: duration,
-            error: if space_percent > 95.0 { Some("Low disk space detected".to_string()) } else { None },
+            error: if critical { Some("Low disk space detected".to_string()) } else { None },
         })
     }
     
@@ -274,35 +705,208 @@ impl RustSupportCore {
         let cpus = self.system.cpus();
         let avg_cpu = cpus.iter().map(|cpu| cpu.cpu_usage()).sum::<f32>() / cpus.len() as f32;
         
-        let status = if avg_cpu > 90.0 { "WARNING" } else { "PASS" };
+        let avg_cpu = avg_cpu as f64;
+        let critical = avg_cpu > self.thresholds.cpu_error_percent;
+        let status = if critical { "CRITICAL" } else if avg_cpu > self.thresholds.cpu_warning_percent { "WARNING" } else { "PASS" };
         
         let duration = start_time.elapsed()?.as_millis() as u64;
         
         Ok(HealthCheckResult {
+            name: "cpu_usage".to_string(),
             status: status.to_string(),
             message: format!("CPU usage: {:.1}%", avg_cpu),
-            critical: false,
+            critical,
             duration_ms: duration,
-            error: if avg_cpu > 95.0 { Some("High CPU usage detected".to_string()) } else { None },
+            error: if critical { Some("High CPU usage detected".to_string()) } else { None },
         })
     }
     
-    /// Check network connectivity
+    /// Check network connectivity by probing the configured endpoints;
+    /// unreachable required endpoints fail the check, others only warn
     fn check_network_connectivity(&self) -> Result<HealthCheckResult> {
         let start_time = SystemTime::now();
         
-        // Simplified network check - in a real implementation,
-        // you'd ping specific endpoints
-        let status = "PASS";
+        let probes = network::probe_all(
+            &self.thresholds.network_endpoints,
+            &self.thresholds.network_required,
+            Duration::from_millis(self.thresholds.network_timeout_ms),
+        );
+        {
+            let mut history = self.latency.lock().unwrap_or_else(|e| e.into_inner());
+            for probe in &probes {
+                if let Ok(latency) = probe.latency {
+                    history.record(&probe.endpoint, latency);
+                }
+            }
+        }
+        let failed_required: Vec<&str> = probes.iter().filter(|p| p.required && p.latency.is_err()).map(|p| p.endpoint.as_str()).collect();
+        let any_failed = probes.iter().any(|p| p.latency.is_err());
+        let critical = !failed_required.is_empty();
+        let status = if critical { "FAIL" } else if any_failed { "WARNING" } else { "PASS" };
+        let message = if probes.is_empty() {
+            "Network connectivity: no endpoints configured".to_string()
+        } else {
+            let results: Vec<String> = probes
+                .iter()
+                .map(|p| match &p.latency {
+                    Ok(latency) => format!("{} {:.1} ms", p.endpoint, latency.as_secs_f64() * 1000.0),
+                    Err(reason) => format!("{} unreachable ({})", p.endpoint, reason),
+                })
+                .collect();
+            format!("Network connectivity: {}", results.join(", "))
+        };
+        
+        let duration = start_time.elapsed()?.as_millis() as u64;
+        
+        Ok(HealthCheckResult {
+            name: "network_connectivity".to_string(),
+            status: status.to_string(),
+            message,
+            critical,
+            duration_ms: duration,
+            error: if critical { Some(format!("Required endpoints unreachable: {}", failed_required.join(", "))) } else { None },
+        })
+    }
+    
+    /// Check VRAM usage and temperature of every GPU; passes when there is
+    /// none
+    fn check_gpu(&self) -> Result<HealthCheckResult> {
+        let start_time = SystemTime::now();
+        
+        let gpus = gpu::gpus();
+        let max = |reading: fn(&gpu::GpuStats) -> Option<f64>| gpus.iter().filter_map(reading).reduce(f64::max);
+        let vram_percent = max(gpu::GpuStats::vram_usage_percent);
+        let temperature = max(|gpu| gpu.temperature_c);
+        let above = |value: Option<f64>, limit: f64| value.is_some_and(|value| value > limit);
+        let critical = above(vram_percent, self.thresholds.gpu_vram_critical_percent)
+            || above(temperature, self.thresholds.gpu_temperature_critical_c);
+        let warning = above(vram_percent, self.thresholds.gpu_vram_warning_percent)
+            || above(temperature, self.thresholds.gpu_temperature_warning_c);
+        let status = if critical { "CRITICAL" } else if warning { "WARNING" } else { "PASS" };
+        let message = if gpus.is_empty() {
+            "GPU: none detected".to_string()
+        } else {
+            let reading = |value: Option<f64>, unit: &str| value.map_or("n/a".to_string(), |value| format!("{:.0}{}", value, unit));
+            let described: Vec<String> = gpus
+                .iter()
+                .map(|gpu| {
+                    format!(
+                        "{}: {} busy, VRAM {} of {}, {}",
+                        gpu.name,
+                        reading(gpu.utilization_percent, "%"),
+                        reading(gpu.vram_used_mb, " MB"),
+                        reading(gpu.vram_total_mb, " MB"),
+                        reading(gpu.temperature_c, "°C")
+                    )
+                })
+                .collect();
+            format!("GPU: {}", described.join("; "))
+        };
         
         let duration = start_time.elapsed()?.as_millis() as u64;
         
         Ok(HealthCheckResult {
+            name: "gpu".to_string(),
             status: status.to_string(),
-            message: "Network connectivity: OK".to_string(),
-            critical: false,
+            message,
+            critical,
             duration_ms: duration,
-            error: None,
+            error: if critical { Some("GPU VRAM or temperature above critical threshold".to_string()) } else { None },
+        })
+    }
+    
+    /// Check the CPU package temperature, reporting the hottest sensor and
+    /// fan speeds; passes when there are no temperature sensors
+    fn check_thermal(&self) -> Result<HealthCheckResult> {
+        let start_time = SystemTime::now();
+        
+        let cpu = sensors::cpu_temperature(&self.components);
+        let hottest = sensors::hottest(&self.components);
+        let fans = sensors::fans();
+        let celsius = cpu.as_ref().or(hottest.as_ref()).map(|reading| reading.celsius);
+        let critical = celsius.is_some_and(|c| c > self.thresholds.cpu_temperature_critical_c);
+        let status = if critical {
+            "CRITICAL"
+        } else if celsius.is_some_and(|c| c > self.thresholds.cpu_temperature_warning_c) {
+            "WARNING"
+        } else {
+            "PASS"
+        };
+        let mut parts = Vec::new();
+        match (&cpu, &hottest) {
+            (None, None) => parts.push("no temperature sensors".to_string()),
+            (Some(cpu), _) => parts.push(format!("CPU {:.1}°C ({})", cpu.celsius, cpu.label)),
+            (None, Some(_)) => {}
+        }
+        if let Some(hottest) = &hottest {
+            parts.push(format!("hottest {:.1}°C ({})", hottest.celsius, hottest.label));
+        }
+        if !fans.is_empty() {
+            let speeds: Vec<String> = fans.iter().map(|fan| format!("{} {:.0} RPM", fan.label, fan.rpm)).collect();
+            parts.push(format!("fans: {}", speeds.join(", ")));
+        }
+        
+        let duration = start_time.elapsed()?.as_millis() as u64;
+        
+        Ok(HealthCheckResult {
+            name: "thermal".to_string(),
+            status: status.to_string(),
+            message: format!("Thermal: {}", parts.join("; ")),
+            critical,
+            duration_ms: duration,
+            error: if critical { Some("CPU temperature above critical threshold".to_string()) } else { None },
+        })
+    }
+    
+    /// Check the local LLM server answers `GET /v1/models` listing a model
+    /// (the configured one, if any), reporting the models and latency
+    fn check_llm_endpoint(&self) -> Result<HealthCheckResult> {
+        let start_time = SystemTime::now();
+        
+        let endpoint = self.thresholds.llm_endpoint.trim_end_matches('/');
+        let outcome = if endpoint.is_empty() {
+            Ok("not configured".to_string())
+        } else {
+            let url = format!("{}/v1/models", endpoint);
+            network::http_get(&url, Duration::from_millis(self.thresholds.llm_timeout_ms)).and_then(|(status, body, latency)| {
+                if !(200..300).contains(&status) {
+                    return Err(format!("{} answered HTTP {}", url, status));
+                }
+                let listing: serde_json::Value = serde_json::from_str(&body).map_err(|e| format!("{} returned invalid JSON: {}", url, e))?;
+                let models: Vec<&str> = listing["data"]
+                    .as_array()
+                    .map(|models| models.iter().filter_map(|model| model["id"].as_str()).collect())
+                    .unwrap_or_default();
+                let wanted = &self.thresholds.llm_model;
+                if models.is_empty() {
+                    return Err(format!("{} lists no models", endpoint));
+                }
+                if !wanted.is_empty() && !models.contains(&wanted.as_str()) {
+                    return Err(format!("model {} not available at {} (has {})", wanted, endpoint, models.join(", ")));
+                }
+                Ok(format!("{} {:.1} ms, models: {}", endpoint, latency.as_secs_f64() * 1000.0, models.join(", ")))
+            })
+        };
+        let critical = outcome.is_err() && self.thresholds.llm_required;
+        let status = match &outcome {
+            Ok(_) => "PASS",
+            Err(_) if critical => "CRITICAL",
+            Err(_) => "WARNING",
+        };
+        let message = match &outcome {
+            Ok(description) => format!("LLM endpoint: {}", description),
+            Err(_) => format!("LLM endpoint: {} unavailable", endpoint),
+        };
+        
+        let duration = start_time.elapsed()?.as_millis() as u64;
+        
+        Ok(HealthCheckResult {
+            name: "llm_endpoint".to_string(),
+            status: status.to_string(),
+            message,
+            critical,
+            duration_ms: duration,
+            error: outcome.err(),
         })
     }
     
@@ -313,74 +917,309 @@ impl RustSupportCore {
         let processes = self.system.processes();
         let process_count = processes.len();
         
-        let status = if process_count > 1000 { "WARNING" } else { "PASS" };
+        let status = if process_count > self.thresholds.process_warning_count { "WARNING" } else { "PASS" };
         
         let duration = start_time.elapsed()?.as_millis() as u64;
         
         Ok(HealthCheckResult {
+            name: "processes".to_string(),
             status: status.to_string(),
             message: format!("Running processes: {}", process_count),
             critical: false,
             duration_ms: duration,
-            error: if process_count > 2000 { Some("High number of processes detected".to_string()) } else { None },
+            error: if process_count > self.thresholds.process_error_count { Some("High number of processes detected".to_string()) } else { None },
         })
     }
     
-    /// Check cache integrity
-    fn check_cache_integrity(&self) -> Result<HealthCheckResult> {
+    /// Check every watched process is running and within the runaway
+    /// limits; a missing one is critical
+    fn check_watched_processes(&self) -> Result<HealthCheckResult> {
         let start_time = SystemTime::now();
         
-        let mut corrupted_files = 0;
-        let mut total_files = 0;
-        
-        if self.cache_dir.exists() {
-            for entry in fs::read_dir(&self.cache_dir)? {
-                let entry = entry?;
-                if entry.path().extension().map_or(false, |ext| ext == "json") {
-                    total_files += 1;
-                    if let Err(_) = fs::read_to_string(&entry.path()) {
-                        corrupted_files += 1;
-                    }
-                }
+        let watched = self.watched.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let mut missing = Vec::new();
+        let mut runaway = Vec::new();
+        let mut described = Vec::new();
+        for target in &watched {
+            let usage = processes::usage(&self.system, target);
+            let label = target.label();
+            if usage.count == 0 {
+                described.push(format!("{} not running", label));
+                missing.push(label);
+                continue;
+            }
+            described.push(format!(
+                "{} ({} proc) {:.1}% CPU {:.0} MB up {}s",
+                label, usage.count, usage.cpu_percent, usage.rss_mb, usage.uptime_secs
+            ));
+            if usage.cpu_percent > self.thresholds.process_runaway_cpu_percent
+                || usage.rss_mb > self.thresholds.process_runaway_memory_mb
+            {
+                runaway.push(label);
             }
         }
+        let critical = !missing.is_empty();
+        let status = if critical { "CRITICAL" } else if !runaway.is_empty() { "WARNING" } else { "PASS" };
+        let message = if watched.is_empty() {
+            "Watched processes: none".to_string()
+        } else {
+            format!("Watched processes: {}", described.join(", "))
+        };
+        let mut errors = Vec::new();
+        if !missing.is_empty() {
+            errors.push(format!("Not running: {}", missing.join(", ")));
+        }
+        if !runaway.is_empty() {
+            errors.push(format!("Runaway: {}", runaway.join(", ")));
+        }
         
+        let duration = start_time.elapsed()?.as_millis() as u64;
+        
+        Ok(HealthCheckResult {
+            name: "watched_processes".to_string(),
+            status: status.to_string(),
+            message,
+            critical,
+            duration_ms: duration,
+            error: if errors.is_empty() { None } else { Some(errors.join("; ")) },
+        })
+    }
+    
+    /// Parse and schema-check every JSON file in the cache directory,
+    /// quarantining corrupted ones when configured to
+    fn check_cache_integrity(&self) -> Result<HealthCheckResult> {
+        let start_time = SystemTime::now();
+        
+        let report = self.verify_cache(self.thresholds.cache_quarantine)?;
+        let corrupted_files = report.issues.len();
+        let total_files = report.files_checked;
         let status = if corrupted_files > 0 { "WARNING" } else { "PASS" };
+        let mut message = format!("Cache integrity: {}/{} files OK", total_files - corrupted_files, total_files);
+        if corrupted_files > 0 {
+            let issues: Vec<String> = report.issues.iter().map(|issue| format!("{} ({}: {})", issue.path, issue.kind, issue.detail)).collect();
+            message.push_str(&format!("; corrupted: {}", issues.join(", ")));
+        }
+        if let Some(report_path) = &report.report_path {
+            message.push_str(&format!("; quarantined, report at {}", report_path));
+        }
         
         let duration = start_time.elapsed()?.as_millis() as u64;
         
         Ok(HealthCheckResult {
+            name: "cache_integrity".to_string(),
             status: status.to_string(),
-            message: format!("Cache integrity: {}/{} files OK", total_files - corrupted_files, total_files),
+            message,
             critical: false,
             duration_ms: duration,
             error: if corrupted_files > total_files / 2 { Some("High number of corrupted cache files".to_string()) } else { None },
         })
     }
+
+    /// Check every component that sent a heartbeat sent its latest in time;
+    /// stalled ones warn
+    fn check_heartbeats(&self) -> Result<HealthCheckResult> {
+        let start_time = SystemTime::now();
+        
+        let statuses = self.heartbeats.lock().unwrap_or_else(|e| e.into_inner()).statuses();
+        let stalled: Vec<&str> = statuses.iter().filter(|s| s.stalled).map(|s| s.name.as_str()).collect();
+        let message = if statuses.is_empty() {
+            "Heartbeats: no components".to_string()
+        } else {
+            let described: Vec<String> = statuses
+                .iter()
+                .map(|s| {
+                    let state = if s.stalled { "STALLED" } else { "alive" };
+                    format!("{} {} ({:.1}s ago, every {}s)", s.name, state, s.since_last.as_secs_f64(), s.interval.as_secs_f64())
+                })
+                .collect();
+            format!("Heartbeats: {}", described.join(", "))
+        };
+        
+        let duration = start_time.elapsed()?.as_millis() as u64;
+        
+        Ok(HealthCheckResult {
+            name: "heartbeats".to_string(),
+            status: if stalled.is_empty() { "PASS" } else { "WARNING" }.to_string(),
+            message,
+            critical: false,
+            duration_ms: duration,
+            error: (!stalled.is_empty()).then(|| format!("Stalled: {}", stalled.join(", "))),
+        })
+    }
+    
+    /// Check the log directory's size against its quota and that a log was
+    /// written recently, reporting growth since the previous check
+    fn check_log_directory(&mut self) -> Result<HealthCheckResult> {
+        let start_time = SystemTime::now();
+        
+        let dir = PathBuf::from(self.thresholds.log_dir.trim());
+        if dir.as_os_str().is_empty() || !dir.is_dir() {
+            let configured = !dir.as_os_str().is_empty();
+            return Ok(HealthCheckResult {
+                name: "log_directory".to_string(),
+                status: if configured { "WARNING" } else { "PASS" }.to_string(),
+                message: if configured {
+                    format!("Log directory: {} does not exist", dir.display())
+                } else {
+                    "Log directory: not configured".to_string()
+                },
+                critical: false,
+                duration_ms: start_time.elapsed()?.as_millis() as u64,
+                error: configured.then(|| "Log directory missing".to_string()),
+            });
+        }
+        let stats = logs::scan(&dir);
+        let current = LogSample { bytes: stats.bytes, timestamp: Utc::now() };
+        let store_key = format!("log_sample:{}", dir.display());
+        let previous = match (self.log_sample, &self.store) {
+            (Some(previous), _) => Some(previous),
+            (None, Some(store)) => store.get::<LogSample>(&store_key).unwrap_or_else(|e| {
+                eprintln!("Failed to read the previous log directory size: {}", e);
+                None
+            }),
+            (None, None) => None,
+        };
+        self.log_sample = Some(current);
+        if let Some(store) = &self.store {
+            if let Err(e) = store.set(&store_key, &current) {
+                eprintln!("Failed to record the log directory size: {}", e);
+            }
+        }
+        
+        let size_mb = stats.bytes as f64 / 1024.0 / 1024.0;
+        let age = stats.newest.map(|newest| SystemTime::now().duration_since(newest).unwrap_or_default());
+        let mut problems = Vec::new();
+        if self.thresholds.log_quota_mb > 0.0 && size_mb > self.thresholds.log_quota_mb {
+            problems.push(format!("logs exceed the {} MB quota", self.thresholds.log_quota_mb));
+        }
+        let stale_secs = self.thresholds.log_stale_minutes * 60;
+        if stale_secs > 0 && age.is_none_or(|age| age.as_secs() > stale_secs) {
+            problems.push(format!("nothing written in {} minutes", self.thresholds.log_stale_minutes));
+        }
+        let growth = match previous.and_then(|previous| logs::growth_mb_per_hour(&previous, &current)) {
+            Some(rate) => format!("{:+.2} MB/h", rate),
+            None => "unknown until the next check".to_string(),
+        };
+        let newest = match age {
+            Some(age) => format!("{} min ago", age.as_secs() / 60),
+            None => "never".to_string(),
+        };
+        
+        let duration = start_time.elapsed()?.as_millis() as u64;
+        
+        Ok(HealthCheckResult {
+            name: "log_directory".to_string(),
+            status: if problems.is_empty() { "PASS" } else { "WARNING" }.to_string(),
+            message: format!(
+                "Log directory: {:.1} MB in {} files at {}, growth {}, newest written {}",
+                size_mb,
+                stats.files,
+                dir.display(),
+                growth,
+                newest
+            ),
+            critical: false,
+            duration_ms: duration,
+            error: (!problems.is_empty()).then(|| problems.join("; ")),
+        })
+    }
+
+    /// Parse every JSON file under the cache directory and check it against
+    /// the fragment or registry schema, moving corrupted files to
+    /// `quarantine/` with a repair report when `quarantine` is set
+    pub fn verify_cache(&self, quarantine: bool) -> Result<CacheReport> {
+        cache::verify(&self.cache_dir, quarantine)
+    }
     
-    /// Add vectors (placeholder implementation)
+    /// The vector index, shared with this core; its lock is independent of
+    /// any lock around the core
+    pub fn vector_index(&self) -> Arc<RwLock<VectorIndex>> {
+        Arc::clone(&self.index)
+    }
+
+    fn vectors(&self) -> RwLockReadGuard<'_, VectorIndex> {
+        self.index.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn vectors_mut(&self) -> RwLockWriteGuard<'_, VectorIndex> {
+        self.index.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add vectors to the index, one metadata string per vector
     pub fn add_vectors(&mut self, vectors: Vec<Vec<f32>>, metadata: Vec<String>) -> Result<u32> {
-        // Placeholder implementation - will add FAISS integration later
-        Ok(vectors.len() as u32)
+        self.vectors_mut().add(vectors, metadata)
     }
     
-    /// Search similar vectors (placeholder implementation)
+    /// Search the `k` stored vectors nearest under the index metric
     pub fn search_vectors(&mut self, query_vector: Vec<f32>, k: usize) -> Result<Vec<FAISSSearchResult>> {
-        // Placeholder implementation - will add FAISS integration later
-        let mut results = Vec::new();
-        for i in 0..k {
-            results.push(FAISSSearchResult {
-                vector_id: format!("vector_{}", i),
-                similarity_score: 0.9 - (i as f32 * 0.1),
-                metadata: format!("metadata_{}", i),
-            });
+        self.vectors().search(&query_vector, k)
+    }
+
+    /// Search by keywords and vector at once; see `VectorIndex::hybrid_search`
+    pub fn hybrid_search(&mut self, query_text: &str, query_vector: Vec<f32>, k: usize, alpha: f32) -> Result<Vec<FAISSSearchResult>> {
+        self.vectors().hybrid_search(query_text, &query_vector, k, alpha)
+    }
+
+    /// Remove the vectors with these ids, e.g. when their fragments are
+    /// deleted; returns the number removed
+    pub fn remove_vectors(&mut self, ids: &[usize]) -> usize {
+        self.vectors_mut().remove(ids)
+    }
+
+    /// Replace the embedding and metadata stored under `id`
+    pub fn update_vector(&mut self, id: usize, vector: Vec<f32>, metadata: String) -> Result<()> {
+        self.vectors_mut().update(id, vector, metadata)
+    }
+
+    /// Reclaim removed vectors by rebuilding the index graph over the live
+    /// ones; ids are kept
+    pub fn compact_index(&mut self) -> CompactionReport {
+        self.vectors_mut().compact()
+    }
+
+    /// `compact_index`, also retraining product quantization codebooks on
+    /// the live vectors
+    pub fn rebuild_index(&mut self) -> Result<CompactionReport> {
+        self.vectors_mut().rebuild()
+    }
+
+    /// Compress the stored vectors; see `VectorIndex::enable_quantization`
+    pub fn enable_quantization(
+        &mut self,
+        mode: Option<QuantizationMode>,
+        rerank_candidates: usize,
+        pq_subspaces: usize,
+        keep_full_precision: bool,
+    ) -> Result<()> {
+        self.vectors_mut().enable_quantization(mode, rerank_candidates, pq_subspaces, keep_full_precision)
+    }
+
+    /// Write the vector index to `path`
+    pub fn save_index(&self, path: &Path) -> Result<()> {
+        self.vectors().save(path)
+    }
+
+    /// Replace the vector index with the one saved at `path`, which must
+    /// have this core's dimension and metric; returns the number of vectors
+    /// loaded
+    pub fn load_index(&mut self, path: &Path) -> Result<usize> {
+        let index = VectorIndex::load(path)?;
+        let mut current = self.vectors_mut();
+        if index.dimension() != current.dimension() {
+            anyhow::bail!("Index at {} has {} dimensions, expected {}", path.display(), index.dimension(), current.dimension());
         }
-        Ok(results)
+        if index.metric() != current.metric() {
+            anyhow::bail!("Index at {} uses the {} metric, expected {}", path.display(), index.metric().name(), current.metric().name());
+        }
+        *current = index;
+        Ok(current.len())
     }
     
     /// Get system performance metrics
     pub fn get_performance_metrics(&mut self) -> Result<HashMap<String, f64>> {
         self.system.refresh_all();
+        self.disks.refresh_list();
+        self.components.refresh();
         
         let mut metrics = HashMap::new();
         
@@ -396,70 +1235,625 @@ impl RustSupportCore {
         let avg_cpu = cpus.iter().map(|cpu| cpu.cpu_usage() as f64).sum::<f64>() / cpus.len() as f64;
         metrics.insert("cpu_usage_percent".to_string(), avg_cpu);
         
-        // Disk metrics (simplified)
-        let total_disk_space = 100_000_000_000u64 as f64; // 100GB placeholder
-        let total_disk_available = 80_000_000_000u64 as f64; // 80GB placeholder
-        metrics.insert("disk_total_gb".to_string(), total_disk_space / 1024.0 / 1024.0 / 1024.0);
-        metrics.insert("disk_available_gb".to_string(), total_disk_available / 1024.0 / 1024.0 / 1024.0);
-        metrics.insert("disk_usage_percent".to_string(), ((total_disk_space - total_disk_available) / total_disk_space) * 100.0);
+        // Disk metrics, for the disk holding the cache directory
+        if let Some(disk) = self.cache_disk().filter(|disk| disk.total_space() > 0) {
+            metrics.insert("disk_total_gb".to_string(), disk.total_space() as f64 / 1024.0 / 1024.0 / 1024.0);
+            metrics.insert("disk_available_gb".to_string(), disk.available_space() as f64 / 1024.0 / 1024.0 / 1024.0);
+            metrics.insert("disk_usage_percent".to_string(), disk_usage_percent(disk));
+        }
+
+        // Per-disk metrics, keyed by mount point
+        let io = disks::io_counters();
+        for disk in self.disks.list().iter().filter(|disk| disk.total_space() > 0) {
+            let key = disks::mount_key(disk.mount_point());
+            metrics.insert(format!("disk_{}_total_gb", key), disk.total_space() as f64 / 1024.0 / 1024.0 / 1024.0);
+            metrics.insert(format!("disk_{}_available_gb", key), disk.available_space() as f64 / 1024.0 / 1024.0 / 1024.0);
+            metrics.insert(format!("disk_{}_usage_percent", key), disk_usage_percent(disk));
+            if let Some(counters) = io.get(&disks::device_name(disk)) {
+                metrics.insert(format!("disk_{}_reads", key), counters.reads as f64);
+                metrics.insert(format!("disk_{}_writes", key), counters.writes as f64);
+                metrics.insert(format!("disk_{}_read_mb", key), counters.read_bytes / 1024.0 / 1024.0);
+                metrics.insert(format!("disk_{}_written_mb", key), counters.written_bytes / 1024.0 / 1024.0);
+            }
+        }
         
         // Process metrics
         metrics.insert("process_count".to_string(), self.system.processes().len() as f64);
+
+        // Watched process metrics
+        for target in self.watched.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+            let usage = processes::usage(&self.system, target);
+            let key = target.metric_key();
+            metrics.insert(format!("process_{}_running", key), usage.count as f64);
+            metrics.insert(format!("process_{}_cpu_percent", key), usage.cpu_percent);
+            metrics.insert(format!("process_{}_rss_mb", key), usage.rss_mb);
+            metrics.insert(format!("process_{}_uptime_seconds", key), usage.uptime_secs as f64);
+        }
+
+        // Network latency metrics, over each endpoint's recent probes
+        for (endpoint, stats) in self.latency.lock().unwrap_or_else(|e| e.into_inner()).stats() {
+            let key = latency::metric_key(endpoint);
+            metrics.insert(format!("network_{}_latency_samples", key), stats.samples as f64);
+            metrics.insert(format!("network_{}_latency_mean_ms", key), stats.mean_ms);
+            metrics.insert(format!("network_{}_latency_p95_ms", key), stats.p95_ms);
+            metrics.insert(format!("network_{}_jitter_ms", key), stats.jitter_ms);
+        }
+
+        // Thermal metrics
+        if let Some(cpu) = sensors::cpu_temperature(&self.components) {
+            metrics.insert("cpu_temperature_c".to_string(), cpu.celsius);
+        }
+        if let Some(hottest) = sensors::hottest(&self.components) {
+            metrics.insert("max_temperature_c".to_string(), hottest.celsius);
+        }
+        for (i, fan) in sensors::fans().iter().enumerate() {
+            metrics.insert(format!("fan{}_rpm", i), fan.rpm);
+        }
+
+        // GPU metrics, per GPU
+        let gpus = gpu::gpus();
+        metrics.insert("gpu_count".to_string(), gpus.len() as f64);
+        for (i, gpu) in gpus.iter().enumerate() {
+            let readings = [
+                ("utilization_percent", gpu.utilization_percent),
+                ("vram_used_mb", gpu.vram_used_mb),
+                ("vram_total_mb", gpu.vram_total_mb),
+                ("temperature_c", gpu.temperature_c),
+            ];
+            for (name, value) in readings {
+                if let Some(value) = value {
+                    metrics.insert(format!("gpu{}_{}", i, name), value);
+                }
+            }
+        }
+
+        // Vector index metrics
+        metrics.insert("vector_count".to_string(), self.vectors().len() as f64);
+        metrics.insert("vector_memory_mb".to_string(), self.vectors().memory_bytes() as f64 / 1024.0 / 1024.0);
+        metrics.insert("vector_removed_count".to_string(), self.vectors().removed() as f64);
         
+        self.metrics_history.lock().unwrap_or_else(|e| e.into_inner()).record(&metrics);
         Ok(metrics)
     }
+
+    /// Metrics sampled within the last `span`, as time series; samples are
+    /// taken by `get_performance_metrics` and the monitoring thread, up to
+    /// the `metrics_history_size` most recent
+    pub fn metrics_window(&self, span: Duration) -> MetricsWindow {
+        self.metrics_history.lock().unwrap_or_else(|e| e.into_inner()).window(span)
+    }
+
+    /// Performance metrics and health in Prometheus text format. Health
+    /// comes from the background monitor's latest sample while monitoring,
+    /// otherwise from running the health checks now.
+    pub fn render_prometheus(&mut self, quick_mode: bool) -> Result<String> {
+        let (summary, checks) = match self.latest_sample() {
+            Some(sample) => (sample.summary, sample.checks),
+            None => self.run_health_checks_detailed(quick_mode)?,
+        };
+        let metrics = self.get_performance_metrics()?;
+        Ok(prometheus::render(&metrics, &summary, &checks))
+    }
+
+    /// Write a health report to `path`: health as in `render_prometheus`
+    /// (full checks when not monitoring), the current metrics and up to
+    /// `REPORT_HISTORY` recorded summaries
+    pub fn export_health_report(&mut self, path: &Path, format: ReportFormat) -> Result<()> {
+        let (summary, checks) = match self.latest_sample() {
+            Some(sample) => (sample.summary, sample.checks),
+            None => self.run_health_checks_detailed(false)?,
+        };
+        let metrics = self.get_performance_metrics()?;
+        let history = self.health_history(REPORT_HISTORY)?.unwrap_or_default();
+        let report = HealthReport::new(summary, checks, metrics, history);
+        fs::write(path, report.render(format)?)
+            .map_err(|e| anyhow::anyhow!("Failed to write health report to {}: {}", path.display(), e))
+    }
+}
+
+/// Used share of a disk with non-zero capacity, in percent
+fn disk_usage_percent(disk: &Disk) -> f64 {
+    let total = disk.total_space() as f64;
+    (total - disk.available_space() as f64) / total * 100.0
+}
+
+#[cfg(feature = "python")]
+mod errors {
+    aios_errors::python_exceptions!(aios_support_rust);
+}
+
+#[cfg(feature = "python")]
+mod tracing {
+    aios_trace::python_tracing!();
+}
+
+#[cfg(feature = "python")]
+mod bus {
+    aios_bus::python_bus!();
+}
+
+#[cfg(feature = "python")]
+mod events {
+    aios_events::python_events!();
+}
+
+#[cfg(feature = "python")]
+mod awaitable {
+    aios_async::python_awaitables!();
 }
 
 /// Python module interface
+#[cfg(feature = "python")]
 #[pymodule]
-fn aios_support_rust(_py: Python, m: &PyModule) -> PyResult<()> {
+fn aios_support_rust(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<HealthCheckResult>()?;
     m.add_class::<SystemHealthSummary>()?;
     m.add_class::<FAISSSearchResult>()?;
+    m.add_class::<MonitorSample>()?;
+    m.add_class::<MetricsWindow>()?;
+    m.add_class::<CacheIssue>()?;
+    m.add_class::<CacheReport>()?;
+    m.add_class::<CompactionReport>()?;
     m.add_class::<PyRustSupportCore>()?;
+    m.add_class::<EmbeddingCacheStats>()?;
+    m.add_class::<PyEmbeddingCache>()?;
+    errors::register(py, m)?;
+    tracing::register(m)?;
+    bus::register(m)?;
+    events::register(m)?;
     Ok(())
 }
 
 /// Python wrapper for RustSupportCore
+///
+/// Health checks and vector operations run with the GIL released. Vector
+/// arguments accept float32/float64 numpy arrays as well as lists.
+#[cfg(feature = "python")]
 #[pyclass]
 pub struct PyRustSupportCore {
     core: RustSupportCore,
 }
 
+/// Dependency probe in this interpreter, so the packages checked are the
+/// ones the application imports
+#[cfg(feature = "python")]
+fn embedded_python_probe(packages: &[String]) -> std::result::Result<String, String> {
+    Python::with_gil(|py| {
+        let module = PyModule::from_code(py, dependencies::PROBE_SCRIPT, "aios_dependency_probe.py", "aios_dependency_probe")?;
+        module.getattr("probe")?.call1((packages.to_vec(),))?.extract::<String>()
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// Calls `callback(previous, current, sample)` under the GIL, printing
+/// exceptions
+#[cfg(feature = "python")]
+fn transition_callback(callback: PyObject) -> TransitionCallback {
+    Box::new(move |previous: &str, current: &str, sample: &MonitorSample| {
+        Python::with_gil(|py| {
+            if let Err(e) = callback.call1(py, (previous, current, sample.clone())) {
+                e.print(py);
+            }
+        })
+    })
+}
+
+/// A process name, or a PID given as an int
+#[cfg(feature = "python")]
+fn process_target(name_or_pid: &PyAny) -> PyResult<String> {
+    match name_or_pid.extract::<u32>() {
+        Ok(pid) => Ok(pid.to_string()),
+        Err(_) => name_or_pid.extract::<String>().map_err(|_| errors::validation("Expected a process name or PID")),
+    }
+}
+
+#[cfg(feature = "python")]
+impl Drop for PyRustSupportCore {
+    fn drop(&mut self) {
+        // The monitoring thread may be waiting for the GIL to run a callback
+        Python::with_gil(|py| {
+            py.allow_threads(|| {
+                self.core.stop_monitoring();
+                self.core.stop_watchdog();
+            })
+        });
+    }
+}
+
+#[cfg(feature = "python")]
 #[pymethods]
 impl PyRustSupportCore {
+    /// Health check thresholds come from the `[support]` section of
+    /// `config_path`, `$AIOS_CONFIG` or `./aios.toml` (defaults if none exist);
+    /// `thresholds`, a dict or the path of a JSON file such as
+    /// `{"memory_warning_percent": 70, "disk_critical_percent": 98}`,
+    /// overrides individual keys. Inconsistent thresholds raise `ConfigError`.
+    /// Its "checks" key (or `[support.checks.<name>]` tables) sets a check's
+    /// severity and whether it runs, e.g. `{"checks": {"network_connectivity":
+    /// {"severity": "warning"}, "cache_integrity": {"severity": "critical"}}}`.
+    /// With `[store] path` set, every health check summary is recorded there.
+    /// `metric` is how the vector index compares embeddings: "cosine"
+    /// (vectors are normalized), "l2" or "ip" (inner product), to match what
+    /// the embedding model was trained for.
     #[new]
-    fn new(cache_dir: &str, dimension: usize) -> PyResult<Self> {
-        let core = RustSupportCore::new(cache_dir, dimension)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to initialize support core: {}", e)))?;
+    #[pyo3(signature = (cache_dir, dimension, config_path=None, metric="cosine", thresholds=None))]
+    fn new(
+        py: Python<'_>,
+        cache_dir: &str,
+        dimension: usize,
+        config_path: Option<&str>,
+        metric: &str,
+        thresholds: Option<&PyAny>,
+    ) -> PyResult<Self> {
+        let metric = Metric::parse(metric).map_err(errors::validation)?;
+        let mut config = AiosConfig::load(config_path).map_err(errors::to_pyerr)?;
+        if let Some(thresholds) = thresholds {
+            config.support = if let Ok(path) = thresholds.extract::<&str>() {
+                config.support.with_overrides_file(Path::new(path))
+            } else {
+                let text: String = py.import("json")?.call_method1("dumps", (thresholds,))?.extract()?;
+                let overrides = serde_json::from_str(&text).map_err(|e| errors::validation(e.to_string()))?;
+                config.support.with_overrides(&overrides)
+            }
+            .map_err(errors::to_pyerr)?;
+        }
+        let mut core = py.allow_threads(|| RustSupportCore::new(cache_dir, dimension, config.support))
+            .map_err(|e| errors::io(format!("Failed to initialize support core: {}", e)))?
+            .with_metric(metric)
+            .with_python_runner(Arc::new(embedded_python_probe));
+        if !config.store.path.is_empty() {
+            let store = Store::open(&config.store.path).map_err(errors::to_pyerr)?;
+            core.attach_store(&store).map_err(|e| errors::io(format!("Failed to attach store: {}", e)))?;
+        }
         Ok(Self { core })
     }
 
-    fn run_health_checks(&mut self, quick_mode: bool) -> PyResult<SystemHealthSummary> {
-        match self.core.run_health_checks(quick_mode) {
+    /// Up to `limit` health check summaries recorded in the `[store]`
+    /// database, newest first. Raises `ConfigError` when no store is set.
+    #[pyo3(signature = (limit=100))]
+    fn get_health_history(&self, py: Python<'_>, limit: usize) -> PyResult<Vec<SystemHealthSummary>> {
+        let _span = aios_trace::span!("PyRustSupportCore.get_health_history", limit = limit);
+        match py.allow_threads(|| self.core.health_history(limit)) {
+            Ok(Some(history)) => Ok(history),
+            Ok(None) => Err(errors::config("Health history needs a [store] path in the AIOS config")),
+            Err(e) => Err(errors::io(format!("Failed to read health history: {}", e))),
+        }
+    }
+
+    fn run_health_checks(&mut self, py: Python<'_>, quick_mode: bool) -> PyResult<SystemHealthSummary> {
+        let _span = aios_trace::span!("PyRustSupportCore.run_health_checks", quick_mode = quick_mode);
+        match py.allow_threads(|| self.core.run_health_checks(quick_mode)) {
             Ok(result) => Ok(result),
-            Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Health checks failed: {}", e)))
+            Err(e) => Err(errors::io(format!("Health checks failed: {}", e)))
         }
     }
 
-    fn add_vectors(&mut self, vectors: Vec<Vec<f32>>, metadata: Vec<String>) -> PyResult<u32> {
-        match self.core.add_vectors(vectors, metadata) {
+    /// Awaitable `run_health_checks`, so event loops keep running through
+    /// slow checks (network probes, disk scans); the checks run on a
+    /// background thread with a core of their own, as the monitor's does,
+    /// so this core stays usable meanwhile.
+    fn run_health_checks_async(&self, py: Python<'_>, quick_mode: bool) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("PyRustSupportCore.run_health_checks_async", quick_mode = quick_mode);
+        let failed = |e: anyhow::Error| errors::io(format!("Health checks failed: {}", e));
+        let mut core = self.core.sibling().map_err(failed)?;
+        awaitable::spawn(py, move || core.run_health_checks(quick_mode).map_err(failed))
+    }
+
+    /// Like `run_health_checks`, also returning every check's result (name,
+    /// status, message, error) so callers can show which check failed and why
+    #[pyo3(signature = (quick_mode=false))]
+    fn run_health_checks_detailed(&mut self, py: Python<'_>, quick_mode: bool) -> PyResult<(SystemHealthSummary, Vec<HealthCheckResult>)> {
+        let _span = aios_trace::span!("PyRustSupportCore.run_health_checks_detailed", quick_mode = quick_mode);
+        py.allow_threads(|| self.core.run_health_checks_detailed(quick_mode))
+            .map_err(|e| errors::io(format!("Health checks failed: {}", e)))
+    }
+
+    /// Run the health checks and sample the performance metrics every
+    /// `interval_secs` on a background thread, replacing any running monitor.
+    ///
+    /// `on_transition(previous, current, sample)` is called on that thread
+    /// whenever the overall status changes (HEALTHY, WARNING, CRITICAL; the
+    /// status before the first sample counts as HEALTHY). Exceptions are
+    /// printed.
+    #[pyo3(signature = (interval_secs=60.0, quick_mode=false, on_transition=None))]
+    fn start_monitoring(&mut self, py: Python<'_>, interval_secs: f64, quick_mode: bool, on_transition: Option<PyObject>) -> PyResult<()> {
+        let _span = aios_trace::span!("PyRustSupportCore.start_monitoring", interval_secs = interval_secs);
+        if !(interval_secs.is_finite() && interval_secs > 0.0) {
+            return Err(errors::validation(format!("interval_secs must be positive, got {}", interval_secs)));
+        }
+        let on_transition = on_transition.map(transition_callback);
+        py.allow_threads(|| self.core.start_monitoring(Duration::from_secs_f64(interval_secs), quick_mode, on_transition))
+            .map_err(|e| errors::io(format!("Failed to start monitoring: {}", e)))
+    }
+
+    /// Run quick health checks every `quick_every_minutes` and full ones
+    /// whenever the cron expression `full_cron` (minute hour day month
+    /// weekday, local time, e.g. "0 */6 * * *" or "@daily") matches, on the
+    /// monitoring thread in place of any running monitor. Results are kept
+    /// in the attached store and by `get_latest`; `on_transition` is as for
+    /// `start_monitoring`.
+    ///
+    /// None takes the schedule_quick_minutes and schedule_full_cron
+    /// settings; 0 or "" turns that part off. Returns False, starting
+    /// nothing, when both are off.
+    #[pyo3(signature = (quick_every_minutes=None, full_cron=None, on_transition=None))]
+    fn start_scheduler(
+        &mut self,
+        py: Python<'_>,
+        quick_every_minutes: Option<f64>,
+        full_cron: Option<&str>,
+        on_transition: Option<PyObject>,
+    ) -> PyResult<bool> {
+        let _span = aios_trace::span!("PyRustSupportCore.start_scheduler", full_cron = full_cron.unwrap_or_default());
+        let quick_every = match quick_every_minutes {
+            Some(minutes) if !(minutes.is_finite() && minutes >= 0.0) => {
+                return Err(errors::validation(format!("quick_every_minutes must be non-negative, got {}", minutes)));
+            }
+            minutes => minutes.map(|minutes| Duration::from_secs_f64(minutes * 60.0)),
+        };
+        if let Some(expression) = full_cron.filter(|expression| !expression.trim().is_empty()) {
+            Cron::parse(expression).map_err(errors::validation)?;
+        }
+        let on_transition = on_transition.map(transition_callback);
+        py.allow_threads(|| self.core.start_scheduler(quick_every, full_cron, on_transition))
+            .map_err(|e| errors::io(format!("Failed to start health check scheduler: {}", e)))
+    }
+
+    /// Stop background monitoring; False when none was running
+    fn stop_monitoring(&mut self, py: Python<'_>) -> bool {
+        let _span = aios_trace::span!("PyRustSupportCore.stop_monitoring");
+        py.allow_threads(|| self.core.stop_monitoring())
+    }
+
+    /// The latest background monitoring sample (summary, per-check results
+    /// and metrics), or None before the first one
+    fn get_latest(&self) -> Option<MonitorSample> {
+        self.core.latest_sample()
+    }
+
+    /// Record that `component` (e.g. "luna") is alive. Once a component has
+    /// sent a heartbeat, the health checks report it STALLED whenever its
+    /// next is more than `interval_secs` late (the heartbeat_interval_secs
+    /// setting by default).
+    #[pyo3(signature = (component, interval_secs=None))]
+    fn heartbeat(&self, component: &str, interval_secs: Option<f64>) -> PyResult<()> {
+        let interval = match interval_secs {
+            Some(secs) if !(secs.is_finite() && secs > 0.0) => {
+                return Err(errors::validation(format!("interval_secs must be positive, got {}", secs)));
+            }
+            secs => secs.map(Duration::from_secs_f64),
+        };
+        self.core.heartbeat(component, interval).map_err(|e| errors::validation(e.to_string()))
+    }
+
+    /// Stop expecting heartbeats from `component`, e.g. on a clean
+    /// shutdown; False when it sent none
+    fn forget_component(&self, component: &str) -> bool {
+        self.core.forget_component(component)
+    }
+
+    /// Watch heartbeats on a background thread, replacing any running
+    /// watchdog. `on_stall(component, seconds_since_heartbeat)` is called on
+    /// that thread once when a component stalls, again only after it has
+    /// recovered and stalled anew. Exceptions are printed.
+    #[pyo3(signature = (on_stall=None))]
+    fn start_watchdog(&mut self, py: Python<'_>, on_stall: Option<PyObject>) -> PyResult<()> {
+        let _span = aios_trace::span!("PyRustSupportCore.start_watchdog");
+        let on_stall = on_stall.map(|callback| -> StallCallback {
+            Box::new(move |component: &str, since_last: Duration| {
+                Python::with_gil(|py| {
+                    if let Err(e) = callback.call1(py, (component, since_last.as_secs_f64())) {
+                        e.print(py);
+                    }
+                })
+            })
+        });
+        py.allow_threads(|| self.core.start_watchdog(on_stall))
+            .map_err(|e| errors::io(format!("Failed to start watchdog: {}", e)))
+    }
+
+    /// Stop the stall watchdog; False when none was running
+    fn stop_watchdog(&mut self, py: Python<'_>) -> bool {
+        let _span = aios_trace::span!("PyRustSupportCore.stop_watchdog");
+        py.allow_threads(|| self.core.stop_watchdog())
+    }
+
+    /// Follow a process, by name (matched, ignoring case, against the
+    /// executable name and command line, e.g. "streamlit") or PID, in
+    /// `get_performance_metrics` and the full health checks, which flag it
+    /// when it is not running or exceeds the runaway limits. False when it
+    /// was already watched.
+    fn watch_process(&mut self, name_or_pid: &PyAny) -> PyResult<bool> {
+        let target = process_target(name_or_pid)?;
+        let _span = aios_trace::span!("PyRustSupportCore.watch_process", target = target.as_str());
+        self.core.watch_process(&target).map_err(|e| errors::validation(e.to_string()))
+    }
+
+    /// Stop following a process; False when it was not watched
+    fn unwatch_process(&mut self, name_or_pid: &PyAny) -> PyResult<bool> {
+        let target = process_target(name_or_pid)?;
+        let _span = aios_trace::span!("PyRustSupportCore.unwatch_process", target = target.as_str());
+        self.core.unwatch_process(&target).map_err(|e| errors::validation(e.to_string()))
+    }
+
+    /// Parse and schema-check every JSON file in the cache directory. With
+    /// `quarantine`, corrupted files are moved to `quarantine/<timestamp>/`
+    /// under it, beside a `repair_report.json`.
+    #[pyo3(signature = (quarantine=false))]
+    fn verify_cache(&self, py: Python<'_>, quarantine: bool) -> PyResult<CacheReport> {
+        let _span = aios_trace::span!("PyRustSupportCore.verify_cache", quarantine = quarantine);
+        py.allow_threads(|| self.core.verify_cache(quarantine))
+            .map_err(|e| errors::io(format!("Failed to verify cache: {}", e)))
+    }
+
+    /// Watched processes, names lowercased and PIDs as strings
+    fn watched_processes(&self) -> Vec<String> {
+        self.core.watched_processes()
+    }
+
+    /// Store `vectors` (rows of `dimension` floats) with one metadata string
+    /// each; ids are assigned in insertion order
+    fn add_vectors(&mut self, py: Python<'_>, vectors: Matrix<'_>, metadata: Vec<String>) -> PyResult<u32> {
+        let _span = aios_trace::span!("PyRustSupportCore.add_vectors", count = vectors.len());
+        let vectors = vectors.into_rows();
+        match py.allow_threads(|| self.core.add_vectors(vectors, metadata)) {
             Ok(count) => Ok(count),
-            Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to add vectors: {}", e)))
+            Err(e) => Err(errors::index(format!("Failed to add vectors: {}", e)))
         }
     }
 
-    fn search_vectors(&mut self, query_vector: Vec<f32>, k: usize) -> PyResult<Vec<FAISSSearchResult>> {
-        match self.core.search_vectors(query_vector, k) {
+    /// The `k` stored vectors nearest to `query_vector` under the core's
+    /// metric, best first. `similarity_score` is the cosine similarity, the
+    /// inner product or the negated L2 distance.
+    fn search_vectors(&mut self, py: Python<'_>, query_vector: Vector<'_>, k: usize) -> PyResult<Vec<FAISSSearchResult>> {
+        let _span = aios_trace::span!("PyRustSupportCore.search_vectors", dimension = query_vector.len(), k = k);
+        let query_vector = query_vector.into_vec();
+        match py.allow_threads(|| self.core.search_vectors(query_vector, k)) {
             Ok(results) => Ok(results),
-            Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Search failed: {}", e)))
+            Err(e) => Err(errors::index(format!("Search failed: {}", e)))
         }
     }
 
-    fn get_performance_metrics(&mut self) -> PyResult<HashMap<String, f64>> {
-        match self.core.get_performance_metrics() {
+    /// The `k` stored vectors best matching both `query_text`, by BM25 over
+    /// their metadata, and `query_vector`, by similarity, best first; finds
+    /// exact identifiers that embeddings miss. `alpha` weighs the vector
+    /// side: 1.0 ranks by similarity alone, 0.0 by keywords alone. Each side
+    /// is scaled to 0..1 over the candidates, and `similarity_score` is
+    /// their weighted sum. Terms are runs of letters, digits and `_`,
+    /// matched ignoring case.
+    #[pyo3(signature = (query_text, query_vector, k=10, alpha=0.5))]
+    fn hybrid_search(
+        &mut self,
+        py: Python<'_>,
+        query_text: &str,
+        query_vector: Vector<'_>,
+        k: usize,
+        alpha: f32,
+    ) -> PyResult<Vec<FAISSSearchResult>> {
+        let _span = aios_trace::span!("PyRustSupportCore.hybrid_search", dimension = query_vector.len(), k = k, alpha = alpha);
+        if !(0.0..=1.0).contains(&alpha) {
+            return Err(errors::validation(format!("alpha must be between 0 and 1, got {}", alpha)));
+        }
+        let query_vector = query_vector.into_vec();
+        py.allow_threads(|| self.core.hybrid_search(query_text, query_vector, k, alpha))
+            .map_err(|e| errors::index(format!("Hybrid search failed: {}", e)))
+    }
+
+    /// Remove the vectors with these ids (the `vector_id`s of search
+    /// results); unknown ids are skipped. Returns the number removed. The
+    /// index compacts itself once a quarter of its nodes are removed.
+    fn remove_vectors(&mut self, py: Python<'_>, ids: Vec<usize>) -> usize {
+        let _span = aios_trace::span!("PyRustSupportCore.remove_vectors", count = ids.len());
+        py.allow_threads(|| self.core.remove_vectors(&ids))
+    }
+
+    /// Replace the embedding and metadata stored under `id`, keeping the id
+    fn update_vector(&mut self, py: Python<'_>, id: usize, embedding: Vector<'_>, metadata: String) -> PyResult<()> {
+        let _span = aios_trace::span!("PyRustSupportCore.update_vector", id = id);
+        let embedding = embedding.into_vec();
+        py.allow_threads(|| self.core.update_vector(id, embedding, metadata))
+            .map_err(|e| errors::index(format!("Failed to update vector: {}", e)))
+    }
+
+    /// Drop removed vectors from the index and rebuild its graph over the
+    /// live ones, which keep their ids. Removed and updated vectors
+    /// otherwise stay in the graph and slow searches down. The report gives
+    /// the bytes reclaimed and the estimated recall afterwards.
+    fn compact_index(&mut self, py: Python<'_>) -> CompactionReport {
+        let _span = aios_trace::span!("PyRustSupportCore.compact_index");
+        py.allow_threads(|| self.core.compact_index())
+    }
+
+    /// Like `compact_index`, also retraining the product quantization
+    /// codebooks (`mode="pq"`) on the vectors stored now, e.g. after the
+    /// data they were trained on has been replaced
+    fn rebuild_index(&mut self, py: Python<'_>) -> PyResult<CompactionReport> {
+        let _span = aios_trace::span!("PyRustSupportCore.rebuild_index");
+        py.allow_threads(|| self.core.rebuild_index())
+            .map_err(|e| errors::index(format!("Failed to rebuild index: {}", e)))
+    }
+
+    /// Compress stored vectors to cut index memory.
+    ///
+    /// `mode="int8"` keeps one int8 code per dimension (about 4x smaller);
+    /// `mode="pq"` additionally walks the graph with product-quantized codes
+    /// of `pq_subspaces` bytes, trained on the vectors stored now. Searches
+    /// re-rank the best `rerank_candidates`, exactly when
+    /// `keep_full_precision=True`. `mode="none"` restores (decoded) vectors.
+    #[pyo3(signature = (mode="int8", rerank_candidates=50, pq_subspaces=8, keep_full_precision=false))]
+    fn enable_quantization(
+        &mut self,
+        py: Python<'_>,
+        mode: &str,
+        rerank_candidates: usize,
+        pq_subspaces: usize,
+        keep_full_precision: bool,
+    ) -> PyResult<()> {
+        let _span = aios_trace::span!("PyRustSupportCore.enable_quantization", mode = mode, rerank_candidates = rerank_candidates, pq_subspaces = pq_subspaces, keep_full_precision = keep_full_precision);
+        let mode = QuantizationMode::parse(mode).map_err(errors::validation)?;
+        py.allow_threads(|| self.core.enable_quantization(mode, rerank_candidates, pq_subspaces, keep_full_precision))
+            .map_err(|e| errors::index(format!("Failed to enable quantization: {}", e)))
+    }
+
+    /// Write the vector index to `path` so `load_index` can restore it
+    /// without re-adding every vector
+    fn save_index(&self, py: Python<'_>, path: PathBuf) -> PyResult<()> {
+        let _span = aios_trace::span!("PyRustSupportCore.save_index", path = path.display().to_string());
+        py.allow_threads(|| self.core.save_index(&path))
+            .map_err(|e| errors::io(format!("Failed to save index: {}", e)))
+    }
+
+    /// Replace the vector index with one written by `save_index`; returns
+    /// the number of vectors loaded
+    fn load_index(&mut self, py: Python<'_>, path: PathBuf) -> PyResult<usize> {
+        let _span = aios_trace::span!("PyRustSupportCore.load_index", path = path.display().to_string());
+        py.allow_threads(|| self.core.load_index(&path))
+            .map_err(|e| errors::index(format!("Failed to load index: {}", e)))
+    }
+
+    fn get_performance_metrics(&mut self, py: Python<'_>) -> PyResult<HashMap<String, f64>> {
+        let _span = aios_trace::span!("PyRustSupportCore.get_performance_metrics");
+        match py.allow_threads(|| self.core.get_performance_metrics()) {
             Ok(metrics) => Ok(metrics),
-            Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to get metrics: {}", e)))
+            Err(e) => Err(errors::io(format!("Failed to get metrics: {}", e)))
+        }
+    }
+
+    /// Metrics sampled in the last `seconds` (by `get_performance_metrics`
+    /// and the monitoring thread) as time series for sparklines:
+    /// `timestamps` in Unix seconds, oldest first, and `series` mapping each
+    /// metric to its values at those times, None where a sample lacks it.
+    /// Holds at most the metrics_history_size latest samples.
+    #[pyo3(signature = (seconds=300.0))]
+    fn get_metrics_window(&self, py: Python<'_>, seconds: f64) -> PyResult<MetricsWindow> {
+        let _span = aios_trace::span!("PyRustSupportCore.get_metrics_window", seconds = seconds);
+        if seconds.is_nan() || seconds < 0.0 {
+            return Err(errors::validation(format!("seconds must not be negative, got {}", seconds)));
         }
+        let span = Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX);
+        Ok(py.allow_threads(|| self.core.metrics_window(span)))
+    }
+
+    /// Performance metrics and health check statuses in Prometheus text
+    /// format, to serve from a scrape endpoint. Uses the latest monitoring
+    /// sample while `start_monitoring` runs, otherwise runs the checks.
+    #[pyo3(signature = (quick_mode=true))]
+    fn render_prometheus(&mut self, py: Python<'_>, quick_mode: bool) -> PyResult<String> {
+        let _span = aios_trace::span!("PyRustSupportCore.render_prometheus", quick_mode = quick_mode);
+        py.allow_threads(|| self.core.render_prometheus(quick_mode))
+            .map_err(|e| errors::io(format!("Failed to render metrics: {}", e)))
+    }
+
+    /// Write a health report for attaching to bug reports: system details,
+    /// the health summary, every check's result, the performance metrics
+    /// and recent recorded summaries. `format` is "json" or "markdown".
+    /// Uses the latest monitoring sample while monitoring, otherwise runs
+    /// the full checks.
+    #[pyo3(signature = (path, format="json"))]
+    fn export_health_report(&mut self, py: Python<'_>, path: &str, format: &str) -> PyResult<()> {
+        let _span = aios_trace::span!("PyRustSupportCore.export_health_report", path = path, format = format);
+        let format = ReportFormat::parse(format).map_err(errors::validation)?;
+        py.allow_threads(|| self.core.export_health_report(Path::new(path), format))
+            .map_err(|e| errors::io(format!("Failed to export health report: {}", e)))
     }
 }
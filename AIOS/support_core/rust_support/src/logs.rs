@@ -0,0 +1,54 @@
+//! Log directory statistics for the log directory health check
+//!
+//! Every file under the directory counts, recursively. Growth is measured
+//! against the previous scan, which the core keeps in memory and, with a
+//! store attached, in the store so it survives restarts.
+
+use std::path::Path;
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+/// Size of the log directory at one point in time
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LogSample {
+    pub bytes: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// What a scan of the log directory found
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogDirStats {
+    pub files: usize,
+    pub bytes: u64,
+    /// Modification time of the most recently written file
+    pub newest: Option<SystemTime>,
+}
+
+/// Count the files under `dir`; unreadable entries are skipped
+pub fn scan(dir: &Path) -> LogDirStats {
+    let mut stats = LogDirStats { files: 0, bytes: 0, newest: None };
+    for entry in WalkDir::new(dir).into_iter().filter_map(|entry| entry.ok()) {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        stats.files += 1;
+        stats.bytes += metadata.len();
+        if let Ok(modified) = metadata.modified() {
+            stats.newest = Some(stats.newest.map_or(modified, |newest| newest.max(modified)));
+        }
+    }
+    stats
+}
+
+/// Growth in MB per hour from `previous` to `current`; None when no time
+/// has passed. Negative after rotation or cleanup.
+pub fn growth_mb_per_hour(previous: &LogSample, current: &LogSample) -> Option<f64> {
+    let hours = (current.timestamp - previous.timestamp).num_milliseconds() as f64 / 3_600_000.0;
+    (hours > 0.0).then(|| (current.bytes as f64 - previous.bytes as f64) / 1024.0 / 1024.0 / hours)
+}
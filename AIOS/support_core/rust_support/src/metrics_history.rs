@@ -0,0 +1,112 @@
+//! Recent performance metrics for sparklines
+//!
+//! Every metrics sample (each `get_performance_metrics` call, including the
+//! monitoring thread's) is kept in a fixed-size ring buffer, oldest dropped
+//! first. Only system metrics are kept, as in monitoring samples; the
+//! vector index's are left out.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Metrics over a span of time, one column per sample
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct MetricsWindow {
+    /// Unix time of each sample in seconds, oldest first
+    pub timestamps: Vec<f64>,
+    /// Each metric's value at every timestamp; None in samples without it
+    /// (e.g. before a disk was mounted)
+    pub series: HashMap<String, Vec<Option<f64>>>,
+}
+
+/// The last samples, up to a fixed number
+#[derive(Debug, Clone, Default)]
+pub struct MetricsHistory {
+    capacity: usize,
+    samples: VecDeque<(DateTime<Utc>, HashMap<String, f64>)>,
+}
+
+impl MetricsHistory {
+    /// Keep up to `capacity` samples; 0 keeps none
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, samples: VecDeque::with_capacity(capacity) }
+    }
+
+    /// Add a sample taken now, dropping the oldest when full
+    pub fn record(&mut self, metrics: &HashMap<String, f64>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        let metrics = metrics.iter().filter(|(name, _)| !name.starts_with("vector_")).map(|(k, v)| (k.clone(), *v));
+        self.samples.push_back((Utc::now(), metrics.collect()));
+    }
+
+    /// Samples taken within the last `span`
+    pub fn window(&self, span: Duration) -> MetricsWindow {
+        // A span beyond what chrono can represent covers everything
+        let since = chrono::Duration::from_std(span).ok().and_then(|span| Utc::now().checked_sub_signed(span));
+        let start = since.map_or(0, |since| self.samples.partition_point(|(taken, _)| *taken < since));
+        let samples: Vec<_> = self.samples.range(start..).collect();
+        let mut series: HashMap<String, Vec<Option<f64>>> = HashMap::new();
+        for (i, (_, metrics)) in samples.iter().enumerate() {
+            for (name, value) in metrics {
+                series.entry(name.clone()).or_insert_with(|| vec![None; samples.len()])[i] = Some(*value);
+            }
+        }
+        MetricsWindow {
+            timestamps: samples.iter().map(|(taken, _)| taken.timestamp_millis() as f64 / 1000.0).collect(),
+            series,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(pairs: &[(&str, f64)]) -> HashMap<String, f64> {
+        pairs.iter().map(|&(name, value)| (name.to_string(), value)).collect()
+    }
+
+    #[test]
+    fn test_keeps_last_samples_without_vector_metrics() {
+        let mut history = MetricsHistory::new(3);
+        history.record(&sample(&[("cpu", 1.0)]));
+        history.record(&sample(&[("cpu", 2.0), ("vector_count", 9.0)]));
+        history.record(&sample(&[("cpu", 3.0)]));
+        history.record(&sample(&[("cpu", 4.0), ("disk", 50.0)]));
+
+        let window = history.window(Duration::MAX);
+        assert_eq!(window.timestamps.len(), 3);
+        assert!(window.timestamps.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(window.series.len(), 2);
+        assert_eq!(window.series["cpu"], [Some(2.0), Some(3.0), Some(4.0)]);
+        assert_eq!(window.series["disk"], [None, None, Some(50.0)]);
+    }
+
+    #[test]
+    fn test_window_covers_recent_samples() {
+        let mut history = MetricsHistory::new(10);
+        history.record(&sample(&[("cpu", 1.0)]));
+        std::thread::sleep(Duration::from_millis(50));
+        history.record(&sample(&[("cpu", 2.0)]));
+        assert_eq!(history.window(Duration::from_millis(25)).series["cpu"], [Some(2.0)]);
+        assert_eq!(history.window(Duration::from_secs(60)).series["cpu"], [Some(1.0), Some(2.0)]);
+        std::thread::sleep(Duration::from_millis(5));
+        let empty = history.window(Duration::ZERO);
+        assert!(empty.timestamps.is_empty() && empty.series.is_empty());
+
+        let mut none = MetricsHistory::new(0);
+        none.record(&sample(&[("cpu", 1.0)]));
+        assert!(none.window(Duration::MAX).timestamps.is_empty());
+    }
+}
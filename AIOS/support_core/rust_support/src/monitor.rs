@@ -0,0 +1,140 @@
+//! Background health monitoring
+//!
+//! A monitoring thread runs the health checks and samples the performance
+//! metrics on a `Schedule` on its own core (same cache directory, thresholds
+//! and store, no vectors), keeps the latest sample, and reports every change
+//! of overall status.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use crate::cron::Cron;
+use crate::{HealthCheckResult, RustSupportCore, SystemHealthSummary};
+
+/// How often a sleeping monitor checks for shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// When the monitor samples
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    /// Checks every interval, quick ones when the flag is set; the first
+    /// sample is taken at once
+    pub every: Option<(Duration, bool)>,
+    /// Full checks at each time the expression matches
+    pub full_cron: Option<Cron>,
+}
+
+/// One round of monitoring
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct MonitorSample {
+    pub summary: SystemHealthSummary,
+    pub checks: Vec<HealthCheckResult>,
+    /// System metrics as in `get_performance_metrics`, without the vector
+    /// index's
+    pub metrics: HashMap<String, f64>,
+}
+
+/// Called with the previous and new overall status and the sample that
+/// changed it
+pub type TransitionCallback = Box<dyn Fn(&str, &str, &MonitorSample) + Send>;
+
+/// A running monitoring thread; stops when dropped
+pub struct Monitor {
+    stop: Arc<AtomicBool>,
+    latest: Arc<Mutex<Option<MonitorSample>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Monitor {
+    /// Sample with `core` on `schedule`. The status before the first sample
+    /// counts as HEALTHY, so starting on an unhealthy system reports a
+    /// transition.
+    pub fn start(core: RustSupportCore, schedule: Schedule, on_transition: Option<TransitionCallback>) -> anyhow::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let latest = Arc::new(Mutex::new(None));
+        let thread = {
+            let (stop, latest) = (Arc::clone(&stop), Arc::clone(&latest));
+            thread::Builder::new()
+                .name("aios-support-monitor".to_string())
+                .spawn(move || monitor_loop(core, schedule, on_transition, stop, latest))?
+        };
+        Ok(Self { stop, latest, thread: Some(thread) })
+    }
+
+    /// The most recent sample; None until the first one completes
+    pub fn latest(&self) -> Option<MonitorSample> {
+        self.latest.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl Drop for Monitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // A sample in progress finishes first
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn sample(core: &mut RustSupportCore, quick_mode: bool) -> anyhow::Result<MonitorSample> {
+    let (summary, checks) = core.run_health_checks_detailed(quick_mode)?;
+    let mut metrics = core.get_performance_metrics()?;
+    metrics.retain(|name, _| !name.starts_with("vector_"));
+    Ok(MonitorSample { summary, checks, metrics })
+}
+
+fn monitor_loop(
+    mut core: RustSupportCore,
+    schedule: Schedule,
+    on_transition: Option<TransitionCallback>,
+    stop: Arc<AtomicBool>,
+    latest: Arc<Mutex<Option<MonitorSample>>>,
+) {
+    let mut status = "HEALTHY".to_string();
+    let mut next_periodic = schedule.every.map(|_| Instant::now());
+    let mut next_full = schedule.full_cron.as_ref().and_then(|cron| cron.next_after(chrono::Local::now()));
+    while !stop.load(Ordering::Relaxed) {
+        let started = Instant::now();
+        let full_due = next_full.is_some_and(|due| due <= chrono::Local::now());
+        let periodic_due = next_periodic.is_some_and(|due| due <= started);
+        if !full_due && !periodic_due {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        // A full run covers a periodic one due at the same time
+        let quick_mode = !full_due && schedule.every.is_some_and(|(_, quick_mode)| quick_mode);
+        match sample(&mut core, quick_mode) {
+            Ok(sample) => {
+                if sample.summary.overall_status != status {
+                    aios_events::event!(
+                        "health.transition",
+                        from = status.as_str(),
+                        to = sample.summary.overall_status.as_str(),
+                    );
+                    if let Some(callback) = &on_transition {
+                        callback(&status, &sample.summary.overall_status, &sample);
+                    }
+                    status = sample.summary.overall_status.clone();
+                }
+                *latest.lock().unwrap_or_else(|e| e.into_inner()) = Some(sample);
+            }
+            Err(e) => eprintln!("aios-support: monitoring sample failed: {}", e),
+        }
+        if full_due {
+            next_full = schedule.full_cron.as_ref().and_then(|cron| cron.next_after(chrono::Local::now()));
+        }
+        if let Some((interval, _)) = schedule.every {
+            next_periodic = Some(started + interval);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Reachability probes for the network health check
+//!
+//! An endpoint is either "host:port", probed with a TCP connect, or an
+//! `http://` URL, probed with a HEAD request whose status line must arrive
+//! (any status below 500 counts as reachable). `https://` URLs get a TCP
+//! connect to their port, as there is no TLS client here. Latency is the time
+//! from starting the connect to the connection (TCP) or status line (HTTP).
+//! `http_get` fetches a body the same way, for probes that read responses.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// Where and how to probe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Tcp { host: String, port: u16 },
+    Http { host: String, port: u16, path: String },
+}
+
+impl Endpoint {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let (scheme, rest) = match text.split_once("://") {
+            Some((scheme, rest)) => (Some(scheme.to_ascii_lowercase()), rest),
+            None => (None, text),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        let default_port = match scheme.as_deref() {
+            None => None,
+            Some("http") => Some(80),
+            Some("https") => Some(443),
+            Some(other) => return Err(format!("Unsupported scheme {:?} in endpoint {:?}", other, text)),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port.parse().map_err(|_| format!("Invalid port in endpoint {:?}", text))?;
+                (host, port)
+            }
+            None => (authority, default_port.ok_or_else(|| format!("Endpoint {:?} needs a port", text))?),
+        };
+        if host.is_empty() {
+            return Err(format!("Endpoint {:?} has no host", text));
+        }
+        let host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+        Ok(match scheme.as_deref() {
+            Some("http") => Self::Http { host, port, path: path.to_string() },
+            _ => Self::Tcp { host, port },
+        })
+    }
+
+    fn connect(host: &str, port: u16, timeout: Duration) -> Result<TcpStream, String> {
+        let addresses = (host, port).to_socket_addrs().map_err(|e| format!("cannot resolve {}: {}", host, e))?;
+        let mut last_error = format!("no addresses for {}", host);
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = e.to_string(),
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Time until the endpoint answered, or why it did not
+    pub fn probe(&self, timeout: Duration) -> Result<Duration, String> {
+        let start = Instant::now();
+        match self {
+            Self::Tcp { host, port } => {
+                Self::connect(host, *port, timeout)?;
+                Ok(start.elapsed())
+            }
+            Self::Http { host, port, path } => {
+                let (status, _) = Self::request(host, *port, "HEAD", path, timeout, start)?;
+                if status >= 500 {
+                    return Err(format!("HTTP {}", status));
+                }
+                Ok(start.elapsed())
+            }
+        }
+    }
+
+    /// Send `method path` and read the status line, leaving the headers and
+    /// body unread; `timeout` counts from `start`
+    fn request(
+        host: &str,
+        port: u16,
+        method: &str,
+        path: &str,
+        timeout: Duration,
+        start: Instant,
+    ) -> Result<(u16, BufReader<TcpStream>), String> {
+        let mut stream = Self::connect(host, port, timeout)?;
+        let remaining = timeout.saturating_sub(start.elapsed()).max(Duration::from_millis(1));
+        stream.set_read_timeout(Some(remaining)).map_err(|e| e.to_string())?;
+        stream.set_write_timeout(Some(remaining)).map_err(|e| e.to_string())?;
+        let request = format!("{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", method, path, host);
+        stream.write_all(request.as_bytes()).map_err(|e| e.to_string())?;
+        let mut reader = BufReader::new(stream);
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line).map_err(|e| e.to_string())?;
+        let status = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| format!("not an HTTP response: {:?}", status_line.trim()))?;
+        Ok((status, reader))
+    }
+}
+
+/// Largest chunk accepted in a chunked body, whatever size the server claims
+const MAX_CHUNK_SIZE: usize = 1 << 20;
+
+/// Status, body and latency (to the status line) of `GET url` for an
+/// `http://` URL; the body is read to the end, chunked or not, each read
+/// waiting at most what is left of `timeout`
+pub fn http_get(url: &str, timeout: Duration) -> Result<(u16, String, Duration), String> {
+    let Endpoint::Http { host, port, path } = Endpoint::parse(url)? else {
+        return Err(format!("Not an http:// URL: {:?}", url));
+    };
+    let start = Instant::now();
+    let (status, mut reader) = Endpoint::request(&host, port, "GET", &path, timeout, start)?;
+    let latency = start.elapsed();
+    let mut chunked = false;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).map_err(|e| e.to_string())? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            chunked |= name.trim().eq_ignore_ascii_case("transfer-encoding") && value.to_ascii_lowercase().contains("chunked");
+        }
+    }
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            let mut size = String::new();
+            reader.read_line(&mut size).map_err(|e| e.to_string())?;
+            let size = usize::from_str_radix(size.trim().split(';').next().unwrap_or(""), 16)
+                .map_err(|_| format!("bad chunk size {:?}", size.trim()))?;
+            if size == 0 {
+                break;
+            }
+            if size > MAX_CHUNK_SIZE {
+                return Err(format!("chunk of {} bytes exceeds the {} byte limit", size, MAX_CHUNK_SIZE));
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..]).map_err(|e| e.to_string())?;
+            // The CRLF ending the chunk
+            reader.read_exact(&mut [0; 2]).map_err(|e| e.to_string())?;
+        }
+    } else {
+        reader.read_to_end(&mut body).map_err(|e| e.to_string())?;
+    }
+    Ok((status, String::from_utf8_lossy(&body).into_owned(), latency))
+}
+
+/// Outcome of probing one configured endpoint
+#[derive(Debug, Clone)]
+pub struct Probe {
+    pub endpoint: String,
+    pub required: bool,
+    pub latency: Result<Duration, String>,
+}
+
+/// Probe `endpoints` and `required` (duplicates once) in parallel
+pub fn probe_all(endpoints: &[String], required: &[String], timeout: Duration) -> Vec<Probe> {
+    use rayon::prelude::*;
+
+    let mut all: Vec<&String> = endpoints.iter().chain(required).collect();
+    let mut seen = std::collections::HashSet::new();
+    all.retain(|endpoint| seen.insert(endpoint.as_str()));
+    all.par_iter()
+        .map(|&endpoint| Probe {
+            endpoint: endpoint.clone(),
+            required: required.contains(endpoint),
+            latency: Endpoint::parse(endpoint).and_then(|parsed| parsed.probe(timeout)),
+        })
+        .collect()
+}
@@ -0,0 +1,81 @@
+//! Watched processes: AIOS components (Streamlit, LM Studio, ...) whose
+//! presence and resource use the health checks follow
+//!
+//! A process is watched by PID or by name. A name matches every process
+//! whose executable name or command line contains it, ignoring case, so
+//! "streamlit" finds `python -m streamlit run app.py`; their usage is summed.
+
+use sysinfo::{Pid, Process, System};
+
+/// What to look for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessTarget {
+    Pid(u32),
+    Name(String),
+}
+
+impl ProcessTarget {
+    /// A PID when `text` is all digits, a name otherwise
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("Process name or PID must not be empty".to_string());
+        }
+        match text.parse() {
+            Ok(pid) => Ok(Self::Pid(pid)),
+            Err(_) => Ok(Self::Name(text.to_lowercase())),
+        }
+    }
+
+    /// As given to `parse`, names lowercased
+    pub fn label(&self) -> String {
+        match self {
+            Self::Pid(pid) => pid.to_string(),
+            Self::Name(name) => name.clone(),
+        }
+    }
+
+    /// Metric name fragment: the label with anything but letters and digits
+    /// replaced by `_`
+    pub fn metric_key(&self) -> String {
+        self.label().chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+    }
+
+    fn matches(&self, pid: Pid, process: &Process) -> bool {
+        match self {
+            Self::Pid(target) => pid.as_u32() == *target,
+            Self::Name(name) => {
+                process.name().to_lowercase().contains(name.as_str())
+                    || process.cmd().iter().any(|arg| arg.to_lowercase().contains(name.as_str()))
+            }
+        }
+    }
+}
+
+/// Combined usage of the processes matching a target
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessUsage {
+    pub count: usize,
+    /// Share of all CPUs, in percent
+    pub cpu_percent: f64,
+    pub rss_mb: f64,
+    /// Run time of the oldest matching process
+    pub uptime_secs: u64,
+}
+
+/// Usage of `target` as of `system`'s last process refresh
+pub fn usage(system: &System, target: &ProcessTarget) -> ProcessUsage {
+    let cpus = system.cpus().len().max(1) as f64;
+    let mut usage = ProcessUsage { count: 0, cpu_percent: 0.0, rss_mb: 0.0, uptime_secs: 0 };
+    for (pid, process) in system.processes() {
+        // Threads show up as processes on Linux; count their process once
+        if process.thread_kind().is_some() || !target.matches(*pid, process) {
+            continue;
+        }
+        usage.count += 1;
+        usage.cpu_percent += process.cpu_usage() as f64 / cpus;
+        usage.rss_mb += process.memory() as f64 / 1024.0 / 1024.0;
+        usage.uptime_secs = usage.uptime_secs.max(process.run_time());
+    }
+    usage
+}
@@ -0,0 +1,156 @@
+//! Prometheus text exposition (format 0.0.4) of the support core's metrics
+//! and health
+//!
+//! Every performance metric becomes a gauge `aios_support_<name>`. Health is
+//! exposed as `aios_support_health_status` (overall) and
+//! `aios_support_check_status{check="..."}` (per check), both 0 for
+//! PASS/HEALTHY, 1 for WARNING and 2 for FAIL/CRITICAL.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::{HealthCheckResult, SystemHealthSummary};
+
+/// Content type of `render`'s output, for HTTP responses
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+const PREFIX: &str = "aios_support_";
+
+fn status_level(status: &str) -> u8 {
+    match status {
+        "PASS" | "HEALTHY" => 0,
+        "WARNING" => 1,
+        _ => 2,
+    }
+}
+
+/// Metric names may only contain `[a-zA-Z0-9_:]`
+fn metric_name(name: &str) -> String {
+    let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect();
+    format!("{}{}", PREFIX, name)
+}
+
+fn label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn value(x: f64) -> String {
+    if x.is_nan() {
+        "NaN".to_string()
+    } else if x.is_infinite() {
+        if x > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        x.to_string()
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+}
+
+/// `metrics` (sorted by name) and the health of `summary` and `checks`
+pub fn render(metrics: &HashMap<String, f64>, summary: &SystemHealthSummary, checks: &[HealthCheckResult]) -> String {
+    let mut out = String::new();
+    let mut names: Vec<&String> = metrics.keys().collect();
+    names.sort();
+    for name in names {
+        let metric = metric_name(name);
+        header(&mut out, &metric, &format!("Support core performance metric {}", name));
+        let _ = writeln!(out, "{} {}", metric, value(metrics[name]));
+    }
+
+    let overall = format!("{}health_status", PREFIX);
+    header(&mut out, &overall, "Overall health: 0 healthy, 1 warning, 2 critical");
+    let _ = writeln!(out, "{} {}", overall, status_level(&summary.overall_status));
+    for (name, count) in [
+        ("checks_passed", summary.passed_checks),
+        ("checks_failed", summary.failed_checks),
+        ("checks_warning", summary.warnings),
+    ] {
+        let metric = format!("{}{}", PREFIX, name);
+        header(&mut out, &metric, &format!("Health checks {} in the last run", name.trim_start_matches("checks_")));
+        let _ = writeln!(out, "{} {}", metric, count);
+    }
+
+    if !checks.is_empty() {
+        let status = format!("{}check_status", PREFIX);
+        header(&mut out, &status, "Health check status: 0 pass, 1 warning, 2 fail or critical");
+        for check in checks {
+            let _ = writeln!(out, "{}{{check=\"{}\"}} {}", status, label_value(&check.name), status_level(&check.status));
+        }
+        let duration = format!("{}check_duration_seconds", PREFIX);
+        header(&mut out, &duration, "Health check duration");
+        for check in checks {
+            let _ = writeln!(out, "{}{{check=\"{}\"}} {}", duration, label_value(&check.name), check.duration_ms as f64 / 1000.0);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(name: &str, status: &str, duration_ms: u64) -> HealthCheckResult {
+        HealthCheckResult {
+            name: name.to_string(),
+            status: status.to_string(),
+            message: String::new(),
+            critical: false,
+            duration_ms,
+            error: None,
+        }
+    }
+
+    fn summary(status: &str) -> SystemHealthSummary {
+        SystemHealthSummary {
+            overall_status: status.to_string(),
+            total_checks: 3,
+            passed_checks: 1,
+            failed_checks: 1,
+            warnings: 1,
+            total_duration_ms: 42,
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(metric_name("disk.usage-percent:/"), "aios_support_disk_usage_percent__");
+        assert_eq!(label_value("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+        assert_eq!((value(f64::NAN), value(f64::INFINITY), value(f64::NEG_INFINITY), value(1.5)), ("NaN".into(), "+Inf".into(), "-Inf".into(), "1.5".into()));
+    }
+
+    #[test]
+    fn test_render() {
+        let metrics: HashMap<String, f64> = [("memory.used", 2.5), ("cpu", 10.0)].iter().map(|&(k, v)| (k.to_string(), v)).collect();
+        let checks = [check("disk \"root\"", "PASS", 1500), check("network", "WARNING", 3), check("gpu", "FAIL", 0)];
+        let out = render(&metrics, &summary("WARNING"), &checks);
+        let samples: Vec<&str> = out.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(
+            samples,
+            [
+                "aios_support_cpu 10",
+                "aios_support_memory_used 2.5",
+                "aios_support_health_status 1",
+                "aios_support_checks_passed 1",
+                "aios_support_checks_failed 1",
+                "aios_support_checks_warning 1",
+                "aios_support_check_status{check=\"disk \\\"root\\\"\"} 0",
+                "aios_support_check_status{check=\"network\"} 1",
+                "aios_support_check_status{check=\"gpu\"} 2",
+                "aios_support_check_duration_seconds{check=\"disk \\\"root\\\"\"} 1.5",
+                "aios_support_check_duration_seconds{check=\"network\"} 0.003",
+                "aios_support_check_duration_seconds{check=\"gpu\"} 0",
+            ]
+        );
+        // One HELP and TYPE pair per metric family
+        assert_eq!(out.lines().filter(|line| line.starts_with("# TYPE")).count(), 8);
+        assert!(out.contains("# TYPE aios_support_check_status gauge\n"));
+
+        // Without checks there are no per-check families
+        let out = render(&HashMap::new(), &summary("CRITICAL"), &[]);
+        assert!(out.contains("aios_support_health_status 2\n") && !out.contains("check_status"));
+    }
+}
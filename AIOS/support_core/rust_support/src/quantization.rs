@@ -0,0 +1,295 @@
+//! Compressed vector storage for the vector index: int8 scalar and product
+//! quantization
+//!
+//! As in CARMA's fragment store, scoring is asymmetric: the query stays in
+//! full precision and only the stored vectors are compressed.
+
+use aios_rng::RngExt;
+use rayon::prelude::*;
+
+/// Lloyd iterations when training the product quantizer's codebooks
+const KMEANS_ITERATIONS: usize = 25;
+
+/// How stored vectors are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantizationMode {
+    /// Per-vector int8 scalar codes
+    Int8,
+    /// Product-quantized codes for graph traversal, int8 codes for re-ranking
+    Product,
+}
+
+impl QuantizationMode {
+    /// Parse "int8" or "pq"; "none" is None
+    pub fn parse(name: &str) -> Result<Option<Self>, String> {
+        match name {
+            "none" => Ok(None),
+            "int8" => Ok(Some(Self::Int8)),
+            "pq" => Ok(Some(Self::Product)),
+            _ => Err(format!("Unknown quantization mode: {}", name)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Int8 => "int8",
+            Self::Product => "pq",
+        }
+    }
+}
+
+/// Per-vector int8 scalar code: x ≈ min + code * scale
+#[derive(Debug, Clone)]
+pub struct Int8Code {
+    codes: Vec<u8>,
+    min: f32,
+    scale: f32,
+    /// L2 norm of the reconstructed vector
+    norm: f32,
+}
+
+impl Int8Code {
+    pub fn encode(vector: &[f32]) -> Self {
+        let min = vector.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = vector.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let (min, scale) = if vector.is_empty() { (0.0, 0.0) } else { (min, (max - min) / 255.0) };
+        let codes = vector
+            .iter()
+            .map(|&x| if scale > 0.0 { ((x - min) / scale).round().clamp(0.0, 255.0) as u8 } else { 0 })
+            .collect();
+        Self::from_parts(codes, min, scale)
+    }
+
+    /// Code with the given `parts`, as saved
+    pub fn from_parts(codes: Vec<u8>, min: f32, scale: f32) -> Self {
+        let mut code = Self { codes, min, scale, norm: 0.0 };
+        code.norm = code.decode().iter().map(|x| x * x).sum::<f32>().sqrt();
+        code
+    }
+
+    /// Codes, minimum and scale
+    pub fn parts(&self) -> (&[u8], f32, f32) {
+        (&self.codes, self.min, self.scale)
+    }
+
+    pub fn decode(&self) -> Vec<f32> {
+        self.codes.iter().map(|&c| self.min + c as f32 * self.scale).collect()
+    }
+
+    /// Dot product of a query (whose components sum to `query_sum`) with
+    /// this code
+    pub fn dot(&self, query: &[f32], query_sum: f32) -> f32 {
+        let weighted: f32 = query.iter().zip(&self.codes).map(|(q, &c)| q * c as f32).sum();
+        self.min * query_sum + self.scale * weighted
+    }
+
+    /// L2 norm of the reconstructed vector
+    pub fn norm(&self) -> f32 {
+        self.norm
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.codes.len() + 3 * std::mem::size_of::<f32>()
+    }
+}
+
+/// Product quantizer: the vector is split into subspaces, each encoded as the
+/// index of its nearest sub-centroid
+#[derive(Debug, Clone)]
+pub struct ProductQuantizer {
+    /// Subspace boundaries, one more than the number of subspaces
+    bounds: Vec<usize>,
+    /// codebooks[j][c] is sub-centroid c of subspace j
+    codebooks: Vec<Vec<Vec<f32>>>,
+}
+
+/// Product-quantized vector with the norm of its reconstruction
+#[derive(Debug, Clone)]
+pub struct PqCode {
+    codes: Vec<u8>,
+    norm: f32,
+}
+
+impl ProductQuantizer {
+    /// Train codebooks of up to 256 sub-centroids on `vectors`, which must
+    /// be non-empty and all `dimension` long
+    pub fn train(vectors: &[&[f32]], dimension: usize, subspaces: usize) -> Self {
+        let bounds = subspace_bounds(dimension, subspaces);
+        let codebooks = bounds
+            .windows(2)
+            .map(|bound| {
+                let slices: Vec<&[f32]> = vectors.iter().map(|v| &v[bound[0]..bound[1]]).collect();
+                kmeans(&slices, 256)
+            })
+            .collect();
+        Self { bounds, codebooks }
+    }
+
+    /// Quantizer with `centroids` sub-centroids per subspace read from
+    /// `floats`, laid out as `codebook_floats` writes them
+    pub fn from_codebooks(dimension: usize, subspaces: usize, centroids: usize, floats: &[f32]) -> Result<Self, String> {
+        if centroids == 0 || centroids > 256 || floats.len() != centroids * dimension {
+            return Err(format!(
+                "Expected {} sub-centroids of dimension {}, got {} floats",
+                centroids,
+                dimension,
+                floats.len()
+            ));
+        }
+        let bounds = subspace_bounds(dimension, subspaces);
+        let mut rest = floats;
+        let codebooks = bounds
+            .windows(2)
+            .map(|bound| {
+                let (codebook, tail) = rest.split_at(centroids * (bound[1] - bound[0]));
+                rest = tail;
+                match bound[1] - bound[0] {
+                    0 => vec![Vec::new(); centroids],
+                    width => codebook.chunks_exact(width).map(<[f32]>::to_vec).collect(),
+                }
+            })
+            .collect();
+        Ok(Self { bounds, codebooks })
+    }
+
+    /// Sub-centroids per subspace
+    pub fn centroids(&self) -> usize {
+        self.codebooks.first().map_or(0, Vec::len)
+    }
+
+    /// Every sub-centroid, subspace by subspace
+    pub fn codebook_floats(&self) -> impl Iterator<Item = f32> + '_ {
+        self.codebooks.iter().flatten().flatten().copied()
+    }
+
+    pub fn encode(&self, vector: &[f32]) -> PqCode {
+        let codes = self
+            .codebooks
+            .iter()
+            .enumerate()
+            .map(|(j, codebook)| nearest(&vector[self.bounds[j]..self.bounds[j + 1]], codebook) as u8)
+            .collect();
+        self.code_with_norm(codes)
+    }
+
+    /// Code made of one saved sub-centroid index per subspace
+    pub fn code(&self, codes: Vec<u8>) -> Result<PqCode, String> {
+        if codes.len() != self.subspaces() {
+            return Err(format!("Expected {} subspace codes, got {}", self.subspaces(), codes.len()));
+        }
+        if let Some(&bad) = codes.iter().find(|&&c| c as usize >= self.centroids()) {
+            return Err(format!("Code {} is past the last of {} sub-centroids", bad, self.centroids()));
+        }
+        Ok(self.code_with_norm(codes))
+    }
+
+    fn code_with_norm(&self, codes: Vec<u8>) -> PqCode {
+        let norm_sq: f32 = codes
+            .iter()
+            .zip(&self.codebooks)
+            .map(|(&c, codebook)| codebook[c as usize].iter().map(|x| x * x).sum::<f32>())
+            .sum();
+        PqCode { codes, norm: norm_sq.sqrt() }
+    }
+
+    pub fn subspaces(&self) -> usize {
+        self.codebooks.len()
+    }
+
+    /// Per-subspace dot products of the query with every sub-centroid
+    pub fn query_table(&self, query: &[f32]) -> Vec<Vec<f32>> {
+        self.codebooks
+            .iter()
+            .enumerate()
+            .map(|(j, codebook)| {
+                let sub = &query[self.bounds[j]..self.bounds[j + 1]];
+                codebook.iter().map(|c| c.iter().zip(sub).map(|(a, b)| a * b).sum()).collect()
+            })
+            .collect()
+    }
+
+    /// Approximate dot product of a query with `code`, through the query's
+    /// `query_table`
+    pub fn dot(table: &[Vec<f32>], code: &PqCode) -> f32 {
+        code.codes.iter().enumerate().map(|(j, &c)| table[j][c as usize]).sum()
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.codebooks.iter().flatten().map(|c| c.len() * std::mem::size_of::<f32>()).sum()
+    }
+}
+
+impl PqCode {
+    /// Sub-centroid index per subspace
+    pub fn codes(&self) -> &[u8] {
+        &self.codes
+    }
+
+    /// L2 norm of the reconstructed vector
+    pub fn norm(&self) -> f32 {
+        self.norm
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.codes.len() + std::mem::size_of::<f32>()
+    }
+}
+
+/// Boundaries of `subspaces` near-equal slices of `dimension`, clamped to
+/// between 1 and `dimension` slices
+fn subspace_bounds(dimension: usize, subspaces: usize) -> Vec<usize> {
+    let m = subspaces.clamp(1, dimension.max(1));
+    (0..=m).map(|j| j * dimension / m).collect()
+}
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+fn nearest(point: &[f32], centroids: &[Vec<f32>]) -> usize {
+    let mut best = (0, f32::INFINITY);
+    for (j, centroid) in centroids.iter().enumerate() {
+        let d = squared_distance(point, centroid);
+        if d < best.1 {
+            best = (j, d);
+        }
+    }
+    best.0
+}
+
+/// Lloyd's k-means from `k` distinct random points; a centroid that loses
+/// all its points stays where it was
+fn kmeans(points: &[&[f32]], k: usize) -> Vec<Vec<f32>> {
+    let k = k.min(points.len());
+    let mut rng = aios_rng::rng("support.pq");
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    for i in 0..k {
+        let j = rng.gen_range(i..order.len());
+        order.swap(i, j);
+    }
+    let mut centroids: Vec<Vec<f32>> = order[..k].iter().map(|&i| points[i].to_vec()).collect();
+
+    for _ in 0..KMEANS_ITERATIONS {
+        let assignments: Vec<usize> = points.par_iter().map(|p| nearest(p, &centroids)).collect();
+        let width = centroids.first().map_or(0, Vec::len);
+        let mut sums = vec![vec![0.0f32; width]; k];
+        let mut counts = vec![0usize; k];
+        for (point, &c) in points.iter().zip(&assignments) {
+            counts[c] += 1;
+            sums[c].iter_mut().zip(point.iter()).for_each(|(s, x)| *s += x);
+        }
+        let mut moved = false;
+        for ((centroid, sum), count) in centroids.iter_mut().zip(sums).zip(counts) {
+            if count == 0 {
+                continue;
+            }
+            let mean: Vec<f32> = sum.into_iter().map(|s| s / count as f32).collect();
+            moved |= squared_distance(centroid, &mean) > 1e-12;
+            *centroid = mean;
+        }
+        if !moved {
+            break;
+        }
+    }
+    centroids
+}
@@ -0,0 +1,251 @@
+//! Health reports for attaching to bug reports
+//!
+//! A report combines the system it was taken on, the health summary, every
+//! check's result, the performance metrics and the recent recorded
+//! summaries, as JSON (`HealthReport` serialized) or as Markdown.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use chrono::Utc;
+use serde::Serialize;
+use sysinfo::System;
+
+use crate::{HealthCheckResult, SystemHealthSummary};
+
+/// Report output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Markdown,
+}
+
+impl ReportFormat {
+    /// "json", or "markdown" / "md"
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "markdown" | "md" => Ok(Self::Markdown),
+            other => Err(format!("Unknown report format {:?}, expected json or markdown", other)),
+        }
+    }
+}
+
+/// Where the report was taken
+#[derive(Debug, Serialize, Clone)]
+pub struct ReportSystem {
+    pub os: Option<String>,
+    pub kernel: Option<String>,
+    pub host: Option<String>,
+    pub support_core_version: &'static str,
+}
+
+/// Everything a report holds; serialized as is for JSON
+#[derive(Debug, Serialize, Clone)]
+pub struct HealthReport {
+    pub generated_at: String,
+    pub system: ReportSystem,
+    pub summary: SystemHealthSummary,
+    pub checks: Vec<HealthCheckResult>,
+    /// Sorted by name
+    pub metrics: BTreeMap<String, f64>,
+    /// Recent recorded summaries, newest first; empty without a store
+    pub history: Vec<SystemHealthSummary>,
+}
+
+impl HealthReport {
+    pub fn new(
+        summary: SystemHealthSummary,
+        checks: Vec<HealthCheckResult>,
+        metrics: HashMap<String, f64>,
+        history: Vec<SystemHealthSummary>,
+    ) -> Self {
+        Self {
+            generated_at: Utc::now().to_rfc3339(),
+            system: ReportSystem {
+                os: System::long_os_version(),
+                kernel: System::kernel_version(),
+                host: System::host_name(),
+                support_core_version: env!("CARGO_PKG_VERSION"),
+            },
+            summary,
+            checks,
+            metrics: metrics.into_iter().collect(),
+            history,
+        }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            ReportFormat::Json => serde_json::to_string_pretty(self)?,
+            ReportFormat::Markdown => self.markdown(),
+        })
+    }
+
+    fn markdown(&self) -> String {
+        let unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".to_string());
+        let summary = &self.summary;
+        let mut out = String::new();
+        let _ = writeln!(out, "# AIOS health report\n");
+        let _ = writeln!(out, "Generated {}\n", self.generated_at);
+        let _ = writeln!(out, "- OS: {}", unknown(&self.system.os));
+        let _ = writeln!(out, "- Kernel: {}", unknown(&self.system.kernel));
+        let _ = writeln!(out, "- Host: {}", unknown(&self.system.host));
+        let _ = writeln!(out, "- Support core: {}\n", self.system.support_core_version);
+
+        let _ = writeln!(out, "## Summary\n");
+        let _ = writeln!(
+            out,
+            "**{}**: {} of {} checks passed, {} failed, {} warnings ({} ms, {})\n",
+            summary.overall_status,
+            summary.passed_checks,
+            summary.total_checks,
+            summary.failed_checks,
+            summary.warnings,
+            summary.total_duration_ms,
+            summary.timestamp
+        );
+
+        let _ = writeln!(out, "## Checks\n");
+        let _ = writeln!(out, "| Check | Status | Critical | Duration (ms) | Message | Error |");
+        let _ = writeln!(out, "|---|---|---|---|---|---|");
+        for check in &self.checks {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} |",
+                cell(&check.name),
+                check.status,
+                if check.critical { "yes" } else { "no" },
+                check.duration_ms,
+                cell(&check.message),
+                cell(check.error.as_deref().unwrap_or(""))
+            );
+        }
+
+        let _ = writeln!(out, "\n## Metrics\n");
+        let _ = writeln!(out, "| Metric | Value |");
+        let _ = writeln!(out, "|---|---|");
+        for (name, value) in &self.metrics {
+            let _ = writeln!(out, "| {} | {} |", name, format_value(*value));
+        }
+
+        if !self.history.is_empty() {
+            let _ = writeln!(out, "\n## Recent history\n");
+            let _ = writeln!(out, "| Time | Status | Passed | Failed | Warnings | Duration (ms) |");
+            let _ = writeln!(out, "|---|---|---|---|---|---|");
+            for past in &self.history {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {}/{} | {} | {} | {} |",
+                    past.timestamp,
+                    past.overall_status,
+                    past.passed_checks,
+                    past.total_checks,
+                    past.failed_checks,
+                    past.warnings,
+                    past.total_duration_ms
+                );
+            }
+        }
+        out
+    }
+}
+
+/// Text safe inside a Markdown table cell
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Whole numbers without decimals, others to two places
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(status: &str, timestamp: &str) -> SystemHealthSummary {
+        SystemHealthSummary {
+            overall_status: status.to_string(),
+            total_checks: 2,
+            passed_checks: 1,
+            failed_checks: 1,
+            warnings: 0,
+            total_duration_ms: 12,
+            timestamp: timestamp.to_string(),
+        }
+    }
+
+    fn report(history: Vec<SystemHealthSummary>) -> HealthReport {
+        let checks = vec![
+            HealthCheckResult {
+                name: "disk_space".to_string(),
+                status: "PASS".to_string(),
+                message: "ok".to_string(),
+                critical: true,
+                duration_ms: 3,
+                error: None,
+            },
+            HealthCheckResult {
+                name: "network".to_string(),
+                status: "FAIL".to_string(),
+                message: "a | b\nc".to_string(),
+                critical: false,
+                duration_ms: 9,
+                error: Some("timed out".to_string()),
+            },
+        ];
+        let metrics = [("memory_percent", 41.256), ("cpu_count", 8.0)].iter().map(|&(k, v)| (k.to_string(), v)).collect();
+        HealthReport::new(summary("CRITICAL", "2026-01-01T00:00:00Z"), checks, metrics, history)
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(ReportFormat::parse("JSON"), Ok(ReportFormat::Json));
+        assert_eq!(ReportFormat::parse(" md "), Ok(ReportFormat::Markdown));
+        assert_eq!(ReportFormat::parse("markdown"), Ok(ReportFormat::Markdown));
+        assert!(ReportFormat::parse("xml").unwrap_err().contains("\"xml\""));
+    }
+
+    #[test]
+    fn test_markdown() {
+        let out = report(Vec::new()).render(ReportFormat::Markdown).unwrap();
+        assert!(out.starts_with("# AIOS health report\n"));
+        assert!(out.contains("**CRITICAL**: 1 of 2 checks passed, 1 failed, 0 warnings (12 ms, 2026-01-01T00:00:00Z)"));
+        assert!(out.contains("| disk_space | PASS | yes | 3 | ok |  |\n"));
+        assert!(out.contains("| network | FAIL | no | 9 | a \\| b c | timed out |\n"));
+        // Metrics sorted by name, whole numbers without decimals
+        let cpu = out.find("| cpu_count | 8 |").unwrap();
+        assert!(cpu < out.find("| memory_percent | 41.26 |").unwrap());
+        assert!(!out.contains("Recent history"));
+
+        let out = report(vec![summary("HEALTHY", "2025-12-31T23:00:00Z")]).render(ReportFormat::Markdown).unwrap();
+        assert!(out.contains("## Recent history"));
+        assert!(out.contains("| 2025-12-31T23:00:00Z | HEALTHY | 1/2 | 1 | 0 | 12 |\n"));
+    }
+
+    #[test]
+    fn test_json() {
+        let out = report(vec![summary("HEALTHY", "2025-12-31T23:00:00Z")]).render(ReportFormat::Json).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(json["summary"]["overall_status"], "CRITICAL");
+        assert_eq!(json["checks"][1]["error"], "timed out");
+        assert_eq!(json["history"][0]["overall_status"], "HEALTHY");
+        assert_eq!(json["system"]["support_core_version"], env!("CARGO_PKG_VERSION"));
+        let metrics: Vec<&String> = json["metrics"].as_object().unwrap().keys().collect();
+        assert_eq!(metrics, ["cpu_count", "memory_percent"]);
+    }
+
+    #[test]
+    fn test_format_value() {
+        assert_eq!(format_value(3.0), "3");
+        assert_eq!(format_value(-2.0), "-2");
+        assert_eq!(format_value(1.5), "1.50");
+        assert_eq!(format_value(1e16), "10000000000000000.00");
+    }
+}
@@ -0,0 +1,89 @@
+//! Temperature and fan sensors
+//!
+//! Temperatures come from sysinfo's components. Fan speeds are read from
+//! the hwmon sysfs files on Linux; other platforms report no fans.
+
+use std::fs;
+use std::path::Path;
+
+use sysinfo::Components;
+
+/// Labels of CPU package sensors, most specific first: Intel coretemp,
+/// AMD k10temp, then anything naming the CPU
+const CPU_PACKAGE_LABELS: &[&str] = &["package", "tctl", "tdie", "cpu"];
+
+/// A temperature reading in °C
+#[derive(Debug, Clone, PartialEq)]
+pub struct Temperature {
+    pub label: String,
+    pub celsius: f64,
+}
+
+/// A fan speed reading
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fan {
+    pub label: String,
+    pub rpm: f64,
+}
+
+fn readings(components: &Components) -> impl Iterator<Item = Temperature> + '_ {
+    components
+        .list()
+        .iter()
+        .filter(|component| component.temperature().is_finite() && component.temperature() > 0.0)
+        .map(|component| Temperature { label: component.label().to_string(), celsius: component.temperature() as f64 })
+}
+
+/// The hottest CPU package sensor, by the first label pattern any sensor
+/// matches
+pub fn cpu_temperature(components: &Components) -> Option<Temperature> {
+    CPU_PACKAGE_LABELS.iter().find_map(|pattern| {
+        readings(components)
+            .filter(|reading| reading.label.to_lowercase().contains(pattern))
+            .max_by(|a, b| a.celsius.total_cmp(&b.celsius))
+    })
+}
+
+/// The hottest sensor of any kind
+pub fn hottest(components: &Components) -> Option<Temperature> {
+    readings(components).max_by(|a, b| a.celsius.total_cmp(&b.celsius))
+}
+
+/// Every fan reporting its speed
+pub fn fans() -> Vec<Fan> {
+    hwmon_fans(Path::new("/sys/class/hwmon"))
+}
+
+/// `fan*_input` files under each `hwmon` device of `root`, labelled by
+/// `fan*_label` or the device name
+fn hwmon_fans(root: &Path) -> Vec<Fan> {
+    let Ok(devices) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut devices: Vec<_> = devices.flatten().map(|entry| entry.path()).collect();
+    devices.sort();
+    let mut fans = Vec::new();
+    for device in devices {
+        let Ok(files) = fs::read_dir(&device) else {
+            continue;
+        };
+        let mut inputs: Vec<String> = files
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("fan") && name.ends_with("_input"))
+            .collect();
+        inputs.sort();
+        let read = |file: &str| fs::read_to_string(device.join(file)).ok().map(|text| text.trim().to_string());
+        for input in inputs {
+            let Some(rpm) = read(&input).and_then(|text| text.parse().ok()) else {
+                continue;
+            };
+            let fan = input.trim_end_matches("_input");
+            let label = read(&format!("{}_label", fan))
+                .or_else(|| read("name").map(|name| format!("{} {}", name, fan)))
+                .unwrap_or_else(|| fan.to_string());
+            fans.push(Fan { label, rpm });
+        }
+    }
+    fans
+}
@@ -0,0 +1,1237 @@
+//! Vector index behind `add_vectors`/`search_vectors`
+//!
+//! Vectors are kept next to their metadata and linked into an HNSW graph
+//! (`aios-hnsw`), so search is approximate nearest neighbour under the index's
+//! `Metric`; under cosine the vectors are stored unit-normalized. Search
+//! results carry a similarity where higher is better: the cosine, the inner
+//! product, or the negated L2 distance. Ids count up from 0 in insertion
+//! order and are never reused. A removed vector stays in the graph as a
+//! routing node but is left out of results, and an updated vector is removed
+//! and inserted again under the same id. `compact` rebuilds the graph over
+//! the live vectors only, reclaiming removed ones, and `rebuild` also
+//! retrains the product quantizer; both keep ids. The graph is compacted on
+//! its own once removed vectors make up `AUTO_COMPACT_SHARE` of its nodes.
+//!
+//! Metadata is also indexed by keyword for `hybrid_search`, which ranks by
+//! both vector similarity and BM25, scored by CARMA's keyword index.
+//!
+//! With quantization enabled the graph is walked with compressed codes
+//! (`quantization`) and the best `rerank_candidates` are scored again in full
+//! precision, or with int8 codes once full precision has been dropped.
+//!
+//! `save` writes the index in the layout of CARMA snapshots (integers
+//! little-endian):
+//!
+//! ```text
+//! magic "AIOSVIDX" | version u32 | reserved u32 | header_len u64 | header JSON
+//! | zero padding to a 64-byte boundary | vectors as contiguous f32
+//! | int8 codes | product quantizer codebooks as contiguous f32 | product codes
+//! ```
+//!
+//! Each part after the padding holds one entry per graph node and is only
+//! there when the index keeps it: the vectors unless quantized without full
+//! precision, int8 codes (min f32, scale f32 and a byte per dimension) when
+//! quantized, and in `pq` mode the trained codebooks followed by a byte per
+//! subspace. Codes are saved as they are, so reloading neither loses
+//! precision nor retrains anything.
+//!
+//! The header holds the dimension, the metric, the metadata, the id of every
+//! graph node, the quantization settings and the HNSW graph, so a loaded
+//! index answers searches without being rebuilt. `load` accepts any version
+//! up to `INDEX_FORMAT_VERSION`; version 1 files predate removal, so their
+//! ids are the node positions, and files without a metric are cosine. Files
+//! before version 3 hold only (decoded) vectors, from which the codes are
+//! encoded again and the product quantizer retrained on the live vectors,
+//! or left untrained (searching with int8 codes until `rebuild`) when there
+//! are none.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::Instant;
+
+use aios_carma::keyword::Bm25Index;
+use aios_hnsw::HnswIndex;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::quantization::{Int8Code, PqCode, ProductQuantizer, QuantizationMode};
+use crate::FAISSSearchResult;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+pub const INDEX_FORMAT_VERSION: u32 = 3;
+const MAGIC: &[u8; 8] = b"AIOSVIDX";
+const ALIGN: usize = 64;
+const PREAMBLE_LEN: usize = 8 + 4 + 4 + 8;
+
+/// Links per node and beam widths of the HNSW graph
+const HNSW_M: usize = 16;
+const EF_CONSTRUCTION: usize = 200;
+const EF_SEARCH: usize = 64;
+
+/// Extra search width at most for skipping removed nodes
+const MAX_REMOVED_BEAM: usize = 4 * EF_SEARCH;
+
+/// Share of graph nodes removed at which `remove` and `update` compact
+const AUTO_COMPACT_SHARE: f64 = 0.25;
+
+/// Neighbours compared, and stored vectors queried at most, when estimating
+/// recall
+const RECALL_K: usize = 10;
+const RECALL_SAMPLES: usize = 100;
+
+/// Nearest vectors and best keyword matches each considered by a hybrid
+/// search, when it asks for fewer results
+const HYBRID_CANDIDATES: usize = 50;
+
+/// BM25 term frequency saturation and length normalization, as in CARMA
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+/// Outcome of `compact` or `rebuild`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct CompactionReport {
+    /// Live vectors, all kept
+    pub vectors: usize,
+    /// Graph nodes of removed vectors dropped
+    pub removed_nodes: usize,
+    /// Bytes of stored vectors and codes before and after
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+    pub bytes_reclaimed: usize,
+    /// Share of the exact nearest neighbours search finds afterwards; None
+    /// with fewer than two vectors
+    pub recall_estimate: Option<f64>,
+    pub duration_ms: u64,
+}
+
+/// Header written by `save`
+#[derive(Serialize)]
+struct IndexHeader<'a> {
+    dimension: usize,
+    metric: &'static str,
+    metadata: &'a [String],
+    ids: &'a [Option<usize>],
+    next_id: usize,
+    quantization: Option<QuantizationSettings>,
+    graph: &'a HnswIndex,
+}
+
+/// Header read by `load`
+#[derive(Deserialize)]
+struct StoredHeader {
+    dimension: usize,
+    #[serde(default)]
+    metric: Option<String>,
+    metadata: Vec<String>,
+    #[serde(default)]
+    ids: Vec<Option<usize>>,
+    #[serde(default)]
+    next_id: usize,
+    #[serde(default)]
+    quantization: Option<QuantizationSettings>,
+    graph: HnswIndex,
+}
+
+/// Arguments of `enable_quantization`, as saved with the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QuantizationSettings {
+    mode: String,
+    rerank_candidates: usize,
+    pq_subspaces: usize,
+    keep_full_precision: bool,
+    /// Sub-centroids per subspace of the saved product quantizer; 0 when
+    /// there is none
+    #[serde(default)]
+    pq_centroids: usize,
+}
+
+/// How the index compares vectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Metric {
+    #[default]
+    Cosine,
+    L2,
+    InnerProduct,
+}
+
+impl Metric {
+    /// Parse "cosine", "l2" or "ip" (also "inner_product")
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "cosine" => Ok(Self::Cosine),
+            "l2" => Ok(Self::L2),
+            "ip" | "inner_product" => Ok(Self::InnerProduct),
+            _ => Err(format!("Unknown metric {:?}; expected cosine, l2 or ip", name)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cosine => "cosine",
+            Self::L2 => "l2",
+            Self::InnerProduct => "ip",
+        }
+    }
+
+    /// `vector` as stored and searched: unit length under cosine
+    fn prepare(self, vector: Vec<f32>) -> Vec<f32> {
+        match self {
+            Self::Cosine => normalized(vector),
+            Self::L2 | Self::InnerProduct => vector,
+        }
+    }
+
+    /// Graph distance between two full-precision vectors
+    fn distance(self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            Self::Cosine => 1.0 - dot(a, b),
+            Self::L2 => a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum(),
+            Self::InnerProduct => -dot(a, b),
+        }
+    }
+
+    /// Graph distance to a compressed vector of norm `norm` from its dot
+    /// product with a query of squared norm `query_norm_sq`
+    fn code_distance(self, dot: f32, query_norm_sq: f32, norm: f32) -> f32 {
+        match self {
+            Self::Cosine if norm == 0.0 => 1.0,
+            Self::Cosine => 1.0 - dot / norm,
+            Self::L2 => (query_norm_sq - 2.0 * dot + norm * norm).max(0.0),
+            Self::InnerProduct => -dot,
+        }
+    }
+
+    /// Reported similarity for a graph distance
+    fn similarity(self, distance: f32) -> f32 {
+        match self {
+            Self::Cosine => 1.0 - distance,
+            Self::L2 => -distance.sqrt(),
+            Self::InnerProduct => -distance,
+        }
+    }
+}
+
+/// Compressed copies of every node's vector
+struct Quantized {
+    mode: QuantizationMode,
+    int8: Vec<Int8Code>,
+    /// Trained quantizer and per-node codes in `pq` mode; None until trained
+    pq: Option<(ProductQuantizer, Vec<PqCode>)>,
+    pq_subspaces: usize,
+    rerank_candidates: usize,
+    keep_full_precision: bool,
+}
+
+impl Quantized {
+    /// No codes yet, compressing with `quantizer` in `pq` mode
+    fn new(mode: QuantizationMode, quantizer: Option<ProductQuantizer>, settings: &QuantizationSettings) -> Self {
+        Self {
+            mode,
+            int8: Vec::new(),
+            pq: quantizer.map(|quantizer| (quantizer, Vec::new())),
+            pq_subspaces: settings.pq_subspaces,
+            rerank_candidates: settings.rerank_candidates,
+            keep_full_precision: settings.keep_full_precision,
+        }
+    }
+
+    fn settings(&self) -> QuantizationSettings {
+        QuantizationSettings {
+            mode: self.mode.name().to_string(),
+            rerank_candidates: self.rerank_candidates,
+            pq_subspaces: self.pq_subspaces,
+            keep_full_precision: self.keep_full_precision,
+            pq_centroids: self.pq.as_ref().map_or(0, |(quantizer, _)| quantizer.centroids()),
+        }
+    }
+}
+
+/// A prepared query with what the compressed scorers need
+struct Query {
+    vector: Vec<f32>,
+    sum: f32,
+    norm_sq: f32,
+    table: Option<Vec<Vec<f32>>>,
+}
+
+/// Vectors by graph node: full precision (empty rows once dropped) and the
+/// compressed codes, if any
+struct Storage {
+    metric: Metric,
+    vectors: Vec<Vec<f32>>,
+    quantized: Option<Quantized>,
+}
+
+impl Storage {
+    /// Storage of `vectors`, one per node, compressed as `quantized` says
+    fn with_vectors(metric: Metric, vectors: Vec<Vec<f32>>, quantized: Option<Quantized>) -> Self {
+        let mut storage = Self { metric, vectors: Vec::with_capacity(vectors.len()), quantized };
+        for vector in vectors {
+            storage.push(vector);
+        }
+        storage
+    }
+
+    /// `vector` must already be `Metric::prepare`d
+    fn prepare(&self, vector: Vec<f32>) -> Query {
+        let table = match &self.quantized {
+            Some(Quantized { pq: Some((quantizer, _)), .. }) => Some(quantizer.query_table(&vector)),
+            _ => None,
+        };
+        Query { sum: vector.iter().sum(), norm_sq: dot(&vector, &vector), vector, table }
+    }
+
+    /// Distance used to walk the graph
+    fn scan_distance(&self, query: &Query, node: usize) -> f32 {
+        match (&self.quantized, &query.table) {
+            (None, _) => self.metric.distance(&query.vector, &self.vectors[node]),
+            (Some(Quantized { pq: Some((_, codes)), .. }), Some(table)) => {
+                let code = &codes[node];
+                self.metric.code_distance(ProductQuantizer::dot(table, code), query.norm_sq, code.norm())
+            }
+            (Some(quantized), _) => self.int8_distance(quantized, query, node),
+        }
+    }
+
+    /// Most precise distance available, for re-ranking
+    fn exact_distance(&self, query: &Query, node: usize) -> f32 {
+        match &self.quantized {
+            Some(quantized) if !quantized.keep_full_precision => self.int8_distance(quantized, query, node),
+            _ => self.metric.distance(&query.vector, &self.vectors[node]),
+        }
+    }
+
+    fn int8_distance(&self, quantized: &Quantized, query: &Query, node: usize) -> f32 {
+        let code = &quantized.int8[node];
+        self.metric.code_distance(code.dot(&query.vector, query.sum), query.norm_sq, code.norm())
+    }
+
+    /// Distance between two nodes' vectors as stored, for pruning links;
+    /// unlike `scan_distance` it needs no query table
+    fn pair_distance(&self, a: usize, b: usize) -> f32 {
+        self.metric.distance(&self.stored(a), &self.stored(b))
+    }
+
+    /// The node's vector, decoded when only codes are kept
+    fn stored(&self, node: usize) -> Cow<'_, [f32]> {
+        match &self.quantized {
+            Some(quantized) if !quantized.keep_full_precision => Cow::Owned(quantized.int8[node].decode()),
+            _ => Cow::Borrowed(&self.vectors[node]),
+        }
+    }
+
+    /// Append a prepared vector as the next node
+    fn push(&mut self, vector: Vec<f32>) {
+        match &mut self.quantized {
+            None => self.vectors.push(vector),
+            Some(quantized) => {
+                quantized.int8.push(Int8Code::encode(&vector));
+                if let Some((quantizer, codes)) = &mut quantized.pq {
+                    codes.push(quantizer.encode(&vector));
+                }
+                self.vectors.push(if quantized.keep_full_precision { vector } else { Vec::new() });
+            }
+        }
+    }
+
+    /// Append the vectors and codes in the layout `load` reads
+    fn write(&self, buffer: &mut Vec<u8>) {
+        fn put_floats(buffer: &mut Vec<u8>, floats: impl IntoIterator<Item = f32>) {
+            for value in floats {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        if self.quantized.as_ref().is_none_or(|quantized| quantized.keep_full_precision) {
+            self.vectors.iter().for_each(|vector| put_floats(buffer, vector.iter().copied()));
+        }
+        let Some(quantized) = &self.quantized else {
+            return;
+        };
+        for code in &quantized.int8 {
+            let (codes, min, scale) = code.parts();
+            put_floats(buffer, [min, scale]);
+            buffer.extend_from_slice(codes);
+        }
+        if let Some((quantizer, codes)) = &quantized.pq {
+            put_floats(buffer, quantizer.codebook_floats());
+            codes.iter().for_each(|code| buffer.extend_from_slice(code.codes()));
+        }
+    }
+
+    /// Read what `write` wrote for `nodes` vectors of `dimension`
+    fn read(
+        block: &mut Block<'_>,
+        nodes: usize,
+        dimension: usize,
+        metric: Metric,
+        quantization: Option<(QuantizationMode, &QuantizationSettings)>,
+    ) -> Result<Self> {
+        let Some((mode, settings)) = quantization else {
+            return Ok(Self { metric, vectors: block.vectors(nodes, dimension)?, quantized: None });
+        };
+        let vectors = match settings.keep_full_precision {
+            true => block.vectors(nodes, dimension)?,
+            false => vec![Vec::new(); nodes],
+        };
+        let mut quantized = Quantized::new(mode, None, settings);
+        quantized.int8 = (0..nodes)
+            .map(|_| {
+                let (min, scale) = (block.float()?, block.float()?);
+                Ok(Int8Code::from_parts(block.take(dimension)?.to_vec(), min, scale))
+            })
+            .collect::<Result<_>>()?;
+        if mode == QuantizationMode::Product && settings.pq_centroids > 0 {
+            let floats = block.floats(settings.pq_centroids.saturating_mul(dimension))?;
+            let quantizer = ProductQuantizer::from_codebooks(dimension, settings.pq_subspaces, settings.pq_centroids, &floats)
+                .map_err(|e| anyhow!("Corrupt quantizer: {}", e))?;
+            let codes = (0..nodes)
+                .map(|_| {
+                    let codes = block.take(quantizer.subspaces())?.to_vec();
+                    quantizer.code(codes).map_err(|e| anyhow!("Corrupt product code: {}", e))
+                })
+                .collect::<Result<_>>()?;
+            quantized.pq = Some((quantizer, codes));
+        }
+        Ok(Self { metric, vectors, quantized: Some(quantized) })
+    }
+
+    fn memory_bytes(&self) -> usize {
+        let full: usize = self.vectors.iter().map(|v| v.len() * std::mem::size_of::<f32>()).sum();
+        let codes = self.quantized.as_ref().map_or(0, |quantized| {
+            let pq = quantized.pq.as_ref().map_or(0, |(quantizer, codes)| {
+                quantizer.memory_bytes() + codes.iter().map(PqCode::memory_bytes).sum::<usize>()
+            });
+            quantized.int8.iter().map(Int8Code::memory_bytes).sum::<usize>() + pq
+        });
+        full + codes
+    }
+}
+
+/// Stored vectors, their metadata and the graph over them
+///
+/// `storage`, `metadata` and `ids` are indexed by graph node.
+pub struct VectorIndex {
+    dimension: usize,
+    storage: Storage,
+    metadata: Vec<String>,
+    /// Id of each node, None once removed
+    ids: Vec<Option<usize>>,
+    /// Node of each live id
+    nodes: HashMap<usize, usize>,
+    next_id: usize,
+    graph: HnswIndex,
+    /// Metadata of live nodes by keyword, doc ids being nodes; kept in step
+    /// with the graph and rebuilt from metadata on load, never saved
+    keywords: Bm25Index,
+}
+
+impl VectorIndex {
+    pub fn new(dimension: usize, metric: Metric) -> Self {
+        Self {
+            dimension,
+            storage: Storage { metric, vectors: Vec::new(), quantized: None },
+            metadata: Vec::new(),
+            ids: Vec::new(),
+            nodes: HashMap::new(),
+            next_id: 0,
+            graph: HnswIndex::new("support.hnsw", HNSW_M, EF_CONSTRUCTION, EF_SEARCH),
+            keywords: Bm25Index::new(BM25_K1, BM25_B),
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    pub fn metric(&self) -> Metric {
+        self.storage.metric
+    }
+
+    /// Number of live vectors
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Bytes held by stored vectors and codes
+    pub fn memory_bytes(&self) -> usize {
+        self.storage.memory_bytes()
+    }
+
+    /// Removed vectors still held as graph nodes
+    pub fn removed(&self) -> usize {
+        self.ids.len() - self.nodes.len()
+    }
+
+    fn check(&self, vector: &[f32]) -> Result<()> {
+        if vector.len() != self.dimension {
+            bail!("Expected {} dimensions, got {}", self.dimension, vector.len());
+        }
+        if let Some(bad) = vector.iter().find(|x| !x.is_finite()) {
+            bail!("Vector components must be finite, got {}", bad);
+        }
+        Ok(())
+    }
+
+    /// Add `vectors` with one metadata string each; nothing is added when
+    /// any vector is invalid
+    pub fn add(&mut self, vectors: Vec<Vec<f32>>, metadata: Vec<String>) -> Result<u32> {
+        if metadata.len() != vectors.len() {
+            bail!("Got {} metadata entries for {} vectors", metadata.len(), vectors.len());
+        }
+        for (i, vector) in vectors.iter().enumerate() {
+            self.check(vector).map_err(|e| anyhow!("Vector {}: {}", i, e))?;
+        }
+        let added = vectors.len() as u32;
+        for (vector, metadata) in vectors.into_iter().zip(metadata) {
+            let id = self.next_id;
+            self.next_id += 1;
+            self.insert(id, vector, metadata);
+        }
+        Ok(added)
+    }
+
+    /// Link a checked vector into the graph as a new node for `id`
+    fn insert(&mut self, id: usize, vector: Vec<f32>, metadata: String) {
+        let vector = self.storage.metric.prepare(vector);
+        let new = self.storage.prepare(vector.clone());
+        self.storage.push(vector);
+        self.keywords.add(&metadata);
+        self.metadata.push(metadata);
+        self.ids.push(Some(id));
+        self.nodes.insert(id, self.ids.len() - 1);
+        self.link(&new);
+    }
+
+    /// Add the next node, already stored, to the graph
+    fn link(&mut self, new: &Query) {
+        let storage = &self.storage;
+        self.graph.insert(
+            |i| storage.scan_distance(new, i),
+            |a, b| storage.pair_distance(a, b),
+        );
+    }
+
+    /// Drop removed vectors and rebuild the graph over the live ones, in
+    /// node order; codes are re-encoded with the current quantizer
+    pub fn compact(&mut self) -> CompactionReport {
+        let start = Instant::now();
+        let (removed_nodes, bytes_before) = (self.removed(), self.memory_bytes());
+        self.drop_removed();
+        self.relink();
+        self.report(start, removed_nodes, bytes_before)
+    }
+
+    /// `compact`, retraining the product quantizer on the live vectors
+    pub fn rebuild(&mut self) -> Result<CompactionReport> {
+        let start = Instant::now();
+        let (removed_nodes, bytes_before) = (self.removed(), self.memory_bytes());
+        self.drop_removed();
+        if let Some(quantized) = &self.storage.quantized {
+            if quantized.mode == QuantizationMode::Product && !self.nodes.is_empty() {
+                let settings = quantized.settings();
+                self.enable_quantization(Some(QuantizationMode::Product), settings.rerank_candidates, settings.pq_subspaces, settings.keep_full_precision)?;
+            }
+        }
+        self.relink();
+        Ok(self.report(start, removed_nodes, bytes_before))
+    }
+
+    /// Store only the live vectors, in node order, with an empty graph
+    fn drop_removed(&mut self) {
+        let mut live = Vec::with_capacity(self.nodes.len());
+        for node in 0..self.ids.len() {
+            if let Some(id) = self.ids[node] {
+                live.push((id, self.storage.stored(node).into_owned(), std::mem::take(&mut self.metadata[node])));
+            }
+        }
+        let quantized = self.storage.quantized.take().map(|mut quantized| {
+            quantized.int8.clear();
+            if let Some((_, codes)) = &mut quantized.pq {
+                codes.clear();
+            }
+            quantized
+        });
+        self.storage = Storage { metric: self.storage.metric, vectors: Vec::new(), quantized };
+        self.metadata.clear();
+        self.ids.clear();
+        self.nodes.clear();
+        self.graph.clear();
+        for (id, vector, metadata) in live {
+            self.storage.push(vector);
+            self.metadata.push(metadata);
+            self.ids.push(Some(id));
+            self.nodes.insert(id, self.ids.len() - 1);
+        }
+        self.keywords.rebuild(self.metadata.iter().map(String::as_str));
+    }
+
+    /// Link every stored node into an empty graph
+    fn relink(&mut self) {
+        for node in 0..self.ids.len() {
+            let new = self.storage.prepare(self.storage.stored(node).into_owned());
+            self.link(&new);
+        }
+    }
+
+    fn report(&self, start: Instant, removed_nodes: usize, bytes_before: usize) -> CompactionReport {
+        let bytes_after = self.memory_bytes();
+        CompactionReport {
+            vectors: self.len(),
+            removed_nodes,
+            bytes_before,
+            bytes_after,
+            bytes_reclaimed: bytes_before.saturating_sub(bytes_after),
+            recall_estimate: self.estimate_recall(),
+            duration_ms: start.elapsed().as_millis() as u64,
+        }
+    }
+
+    /// Recall@`RECALL_K` of search against an exact scan, averaged over up
+    /// to `RECALL_SAMPLES` evenly spaced stored vectors used as queries
+    /// (each leaving itself out); None with fewer than two live vectors
+    pub fn estimate_recall(&self) -> Option<f64> {
+        if self.nodes.len() < 2 {
+            return None;
+        }
+        let live: Vec<usize> = (0..self.ids.len()).filter(|&node| self.ids[node].is_some()).collect();
+        let k = RECALL_K.min(live.len() - 1);
+        let step = live.len().div_ceil(RECALL_SAMPLES);
+        let mut total = 0.0;
+        let mut samples = 0;
+        for &sample in live.iter().step_by(step) {
+            let query = self.storage.prepare(self.storage.stored(sample).into_owned());
+            let mut exact: Vec<(usize, f32)> = live
+                .iter()
+                .filter(|&&node| node != sample)
+                .map(|&node| (node, self.storage.exact_distance(&query, node)))
+                .collect();
+            exact.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+            let exact: Vec<usize> = exact.into_iter().take(k).map(|(node, _)| node).collect();
+            let found = self.nearest(&query, k + 1);
+            let hits = found.iter().filter(|(node, _)| *node != sample && exact.contains(node)).count();
+            total += hits.min(k) as f64 / k as f64;
+            samples += 1;
+        }
+        Some(total / samples as f64)
+    }
+
+    /// Compress stored vectors (`mode` None restores full precision)
+    ///
+    /// `Product` mode trains its codebooks on the live vectors, so needs at
+    /// least one. Searches re-score the best `rerank_candidates` with full
+    /// precision when `keep_full_precision`, else with the int8 codes.
+    pub fn enable_quantization(
+        &mut self,
+        mode: Option<QuantizationMode>,
+        rerank_candidates: usize,
+        pq_subspaces: usize,
+        keep_full_precision: bool,
+    ) -> Result<()> {
+        let vectors: Vec<Vec<f32>> = (0..self.ids.len()).map(|node| self.storage.stored(node).into_owned()).collect();
+        let Some(mode) = mode else {
+            self.storage = Storage { metric: self.storage.metric, vectors, quantized: None };
+            return Ok(());
+        };
+        let settings = QuantizationSettings {
+            mode: mode.name().to_string(),
+            rerank_candidates,
+            pq_subspaces: pq_subspaces.max(1),
+            keep_full_precision,
+            pq_centroids: 0,
+        };
+        let quantizer = match mode {
+            QuantizationMode::Int8 => None,
+            QuantizationMode::Product => {
+                if self.nodes.is_empty() {
+                    bail!("Product quantization needs stored vectors to train on");
+                }
+                let live: Vec<&[f32]> = self.nodes.values().map(|&node| vectors[node].as_slice()).collect();
+                Some(ProductQuantizer::train(&live, self.dimension, settings.pq_subspaces))
+            }
+        };
+        let quantized = Quantized::new(mode, quantizer, &settings);
+        self.storage = Storage::with_vectors(self.storage.metric, vectors, Some(quantized));
+        Ok(())
+    }
+
+    /// Drop `id` from results; false when it is not in the index
+    fn retire(&mut self, id: usize) -> bool {
+        let Some(node) = self.nodes.remove(&id) else {
+            return false;
+        };
+        self.ids[node] = None;
+        self.keywords.remove(node, &self.metadata[node]);
+        self.metadata[node].clear();
+        true
+    }
+
+    /// Remove the vectors with these ids; unknown ids are skipped. Returns
+    /// the number removed.
+    pub fn remove(&mut self, ids: &[usize]) -> usize {
+        let removed = ids.iter().filter(|&&id| self.retire(id)).count();
+        self.compact_if_sparse();
+        removed
+    }
+
+    /// Replace the vector and metadata stored under `id`
+    pub fn update(&mut self, id: usize, vector: Vec<f32>, metadata: String) -> Result<()> {
+        if !self.nodes.contains_key(&id) {
+            bail!("No vector with id {}", id);
+        }
+        self.check(&vector)?;
+        self.retire(id);
+        self.insert(id, vector, metadata);
+        self.compact_if_sparse();
+        Ok(())
+    }
+
+    /// Rebuild the graph once removed nodes reach `AUTO_COMPACT_SHARE`
+    fn compact_if_sparse(&mut self) {
+        if self.removed() as f64 >= self.ids.len() as f64 * AUTO_COMPACT_SHARE && self.removed() > 0 {
+            self.drop_removed();
+            self.relink();
+        }
+    }
+
+    /// Up to `k` stored vectors nearest to `query`, best first
+    pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<FAISSSearchResult>> {
+        self.check(query)?;
+        let metric = self.storage.metric;
+        let query = self.storage.prepare(metric.prepare(query.to_vec()));
+        Ok(self
+            .nearest(&query, k)
+            .into_iter()
+            .take(k)
+            .filter_map(|(node, distance)| {
+                Some(FAISSSearchResult {
+                    vector_id: self.ids[node]?.to_string(),
+                    similarity_score: metric.similarity(distance),
+                    metadata: self.metadata[node].clone(),
+                })
+            })
+            .collect())
+    }
+
+    /// Up to `k` stored vectors best by `alpha` times their similarity to
+    /// `query` plus `1 - alpha` times the BM25 score of their metadata
+    /// against `query_text`. Both are normalized over the candidates (the
+    /// `HYBRID_CANDIDATES`, or `k` if more, nearest vectors and best keyword
+    /// matches): similarities from the lowest to the highest to 0..1, BM25
+    /// scores as a fraction of the highest, so metadata without any query
+    /// term scores 0. The result's `similarity_score` is the combination.
+    pub fn hybrid_search(&self, query_text: &str, query: &[f32], k: usize, alpha: f32) -> Result<Vec<FAISSSearchResult>> {
+        self.check(query)?;
+        if !(0.0..=1.0).contains(&alpha) {
+            bail!("alpha must be between 0 and 1, got {}", alpha);
+        }
+        let metric = self.storage.metric;
+        let query = self.storage.prepare(metric.prepare(query.to_vec()));
+        let candidates = k.max(HYBRID_CANDIDATES);
+        let keyword_scores = self.keywords.scores(query_text);
+        let mut keyword_matches: Vec<(usize, f32)> = keyword_scores.iter().map(|(&node, &score)| (node, score)).collect();
+        keyword_matches.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        let mut nodes: Vec<usize> = self.nearest(&query, candidates).into_iter().take(candidates).map(|(node, _)| node).collect();
+        nodes.extend(keyword_matches.iter().take(candidates).map(|&(node, _)| node));
+        nodes.sort_unstable();
+        nodes.dedup();
+
+        let similarities: Vec<f32> =
+            nodes.iter().map(|&node| metric.similarity(self.storage.exact_distance(&query, node))).collect();
+        let (lowest, highest) = similarities.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &s| (lo.min(s), hi.max(s)));
+        let best_keyword = keyword_matches.first().map_or(0.0, |&(_, score)| score);
+        let mut ranked: Vec<(usize, f32)> = nodes
+            .iter()
+            .zip(&similarities)
+            .map(|(&node, &similarity)| {
+                let vector_score = if highest > lowest { (similarity - lowest) / (highest - lowest) } else { 1.0 };
+                let keyword_score = match keyword_scores.get(&node) {
+                    Some(&score) if best_keyword > 0.0 => score / best_keyword,
+                    _ => 0.0,
+                };
+                (node, alpha * vector_score + (1.0 - alpha) * keyword_score)
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        Ok(ranked
+            .into_iter()
+            .take(k)
+            .filter_map(|(node, score)| {
+                Some(FAISSSearchResult {
+                    vector_id: self.ids[node]?.to_string(),
+                    similarity_score: score,
+                    metadata: self.metadata[node].clone(),
+                })
+            })
+            .collect())
+    }
+
+    /// Live nodes nearest to `query` with their distances, best first: at
+    /// least `k` when there are that many, more when re-ranking
+    fn nearest(&self, query: &Query, k: usize) -> Vec<(usize, f32)> {
+        let wanted = match &self.storage.quantized {
+            Some(quantized) => k.max(quantized.rerank_candidates),
+            None => k,
+        };
+        // Ask for more nodes so that `wanted` live ones likely remain after
+        // dropping the removed ones
+        let extra = self.removed().min(MAX_REMOVED_BEAM);
+        let hits = self.graph.search(wanted.saturating_add(extra), |i| self.storage.scan_distance(query, i));
+        let mut hits: Vec<(usize, f32)> = hits.into_iter().filter(|&(node, _)| self.ids[node].is_some()).collect();
+        if self.storage.quantized.is_some() {
+            for (node, distance) in &mut hits {
+                *distance = self.storage.exact_distance(query, *node);
+            }
+            hits.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        }
+        hits
+    }
+
+    /// Write the index to `path` atomically (temp file + rename)
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let header = IndexHeader {
+            dimension: self.dimension,
+            metric: self.storage.metric.name(),
+            metadata: &self.metadata,
+            ids: &self.ids,
+            next_id: self.next_id,
+            quantization: self.storage.quantized.as_ref().map(Quantized::settings),
+            graph: &self.graph,
+        };
+        let header_json = serde_json::to_vec(&header)?;
+        let unpadded = PREAMBLE_LEN + header_json.len();
+        let padding = (ALIGN - unpadded % ALIGN) % ALIGN;
+        let mut buffer = Vec::with_capacity(unpadded + padding + self.memory_bytes());
+        buffer.extend_from_slice(MAGIC);
+        buffer.extend_from_slice(&INDEX_FORMAT_VERSION.to_le_bytes());
+        buffer.extend_from_slice(&0u32.to_le_bytes());
+        buffer.extend_from_slice(&(header_json.len() as u64).to_le_bytes());
+        buffer.extend_from_slice(&header_json);
+        buffer.resize(unpadded + padding, 0);
+        self.storage.write(&mut buffer);
+
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut file = fs::File::create(&tmp_path)?;
+            file.write_all(&buffer)?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Read an index written by `save`
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        if data.len() < PREAMBLE_LEN || &data[..8] != MAGIC {
+            bail!("{} is not a vector index", path.display());
+        }
+        let version = u32::from_le_bytes(data[8..12].try_into().unwrap());
+        if version > INDEX_FORMAT_VERSION {
+            bail!("Index version {} is newer than supported version {}", version, INDEX_FORMAT_VERSION);
+        }
+        let header_len = u64::from_le_bytes(data[16..24].try_into().unwrap()) as usize;
+        let header_end = PREAMBLE_LEN
+            .checked_add(header_len)
+            .filter(|&end| end <= data.len())
+            .ok_or_else(|| anyhow!("Truncated index header"))?;
+        let mut header: StoredHeader = serde_json::from_slice(&data[PREAMBLE_LEN..header_end])?;
+        let metric = match &header.metric {
+            Some(name) => Metric::parse(name).map_err(|e| anyhow!(e))?,
+            None => Metric::Cosine,
+        };
+        if version < 2 {
+            header.ids = (0..header.metadata.len()).map(Some).collect();
+            header.next_id = header.metadata.len();
+        }
+        if header.graph.len() != header.metadata.len() || header.ids.len() != header.metadata.len() {
+            bail!("Index graph has {} nodes for {} vectors and {} ids", header.graph.len(), header.metadata.len(), header.ids.len());
+        }
+        let nodes: HashMap<usize, usize> =
+            header.ids.iter().enumerate().filter_map(|(node, id)| id.map(|id| (id, node))).collect();
+        if nodes.keys().any(|&id| id >= header.next_id) {
+            bail!("Index ids run past next id {}", header.next_id);
+        }
+        header.graph.validate().map_err(|e| anyhow!("Corrupt index graph: {}", e))?;
+
+        let start = header_end + (ALIGN - header_end % ALIGN) % ALIGN;
+        let mut block = Block { data: &data, offset: start };
+        let (nodes_len, dimension) = (header.metadata.len(), header.dimension);
+        let quantization = match &header.quantization {
+            Some(settings) => QuantizationMode::parse(&settings.mode).map_err(|e| anyhow!(e))?.map(|mode| (mode, settings)),
+            None => None,
+        };
+        let storage = if version < 3 {
+            // Product codebooks are retrained below
+            let quantized = quantization.map(|(mode, settings)| Quantized::new(mode, None, settings));
+            Storage::with_vectors(metric, block.vectors(nodes_len, dimension)?, quantized)
+        } else {
+            Storage::read(&mut block, nodes_len, dimension, metric, quantization)?
+        };
+        if block.offset != data.len() {
+            bail!("{} bytes left over after the vector block", data.len() - block.offset);
+        }
+        let mut index = Self {
+            dimension: header.dimension,
+            storage,
+            metadata: header.metadata,
+            ids: header.ids,
+            nodes,
+            next_id: header.next_id,
+            graph: header.graph,
+            keywords: Bm25Index::new(BM25_K1, BM25_B),
+        };
+        // Removed nodes were saved with empty metadata
+        index.keywords.rebuild(index.metadata.iter().map(String::as_str));
+        for node in (0..index.ids.len()).filter(|&node| index.ids[node].is_none()) {
+            index.keywords.remove(node, "");
+        }
+        // Saved without codebooks: train them on the live vectors, if any
+        let untrained = matches!(&index.storage.quantized, Some(Quantized { mode: QuantizationMode::Product, pq: None, .. }));
+        if let Some(settings) = header.quantization.filter(|_| untrained && !index.nodes.is_empty()) {
+            index.enable_quantization(Some(QuantizationMode::Product), settings.rerank_candidates, settings.pq_subspaces, settings.keep_full_precision)?;
+        }
+        Ok(index)
+    }
+}
+
+/// Reads the part of a saved index after the header, in order
+struct Block<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Block<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| anyhow!("Index data truncated at offset {} of {}", self.offset, self.data.len()))?;
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn float(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn floats(&mut self, count: usize) -> Result<Vec<f32>> {
+        let bytes = self.take(count.saturating_mul(4))?;
+        Ok(bytes.chunks_exact(4).map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap())).collect())
+    }
+
+    /// `count` vectors of `dimension` floats
+    fn vectors(&mut self, count: usize, dimension: usize) -> Result<Vec<Vec<f32>>> {
+        let floats = self.floats(count.saturating_mul(dimension))?;
+        Ok(match dimension {
+            0 => vec![Vec::new(); count],
+            dimension => floats.chunks_exact(dimension).map(<[f32]>::to_vec).collect(),
+        })
+    }
+}
+
+/// `vector` scaled to unit length; the zero vector stays zero
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// `count` deterministic pseudo-random vectors with components in -1..1
+    fn vectors(count: usize, dimension: usize, seed: usize) -> Vec<Vec<f32>> {
+        (0..count)
+            .map(|i| {
+                (0..dimension)
+                    .map(|j| {
+                        let x = ((seed * 7919 + i * 104729 + j * 1299709 + 1) as f32).sin() * 43758.547;
+                        2.0 * (x - x.floor()) - 1.0
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn index(count: usize, dimension: usize, metric: Metric) -> VectorIndex {
+        let mut index = VectorIndex::new(dimension, metric);
+        let metadata = (0..count).map(|i| format!("vector {}", i)).collect();
+        index.add(vectors(count, dimension, 0), metadata).unwrap();
+        index
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("aios_vectors_{}_{}.idx", std::process::id(), name))
+    }
+
+    /// Save and load `index` through a temp file
+    fn reload(index: &VectorIndex, name: &str) -> VectorIndex {
+        let path = temp_path(name);
+        index.save(&path).unwrap();
+        let loaded = VectorIndex::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    fn ids(results: &[FAISSSearchResult]) -> Vec<String> {
+        results.iter().map(|r| r.vector_id.clone()).collect()
+    }
+
+    /// Both indexes give the same results for a few queries
+    fn assert_same_results(a: &VectorIndex, b: &VectorIndex) {
+        for query in vectors(5, a.dimension(), 1) {
+            let (expected, found) = (a.search(&query, 10).unwrap(), b.search(&query, 10).unwrap());
+            assert_eq!(ids(&expected), ids(&found));
+            for (expected, found) in expected.iter().zip(&found) {
+                assert!((expected.similarity_score - found.similarity_score).abs() < 1e-5);
+                assert_eq!(expected.metadata, found.metadata);
+            }
+        }
+    }
+
+    fn search_ids(index: &VectorIndex, query: &[f32], k: usize) -> Vec<String> {
+        ids(&index.search(query, k).unwrap())
+    }
+
+    #[test]
+    fn test_ids_are_stable_and_never_reused() {
+        let mut index = index(20, 4, Metric::L2);
+        let stored = vectors(20, 4, 0);
+        assert_eq!(index.remove(&[5, 5, 99]), 1);
+        assert_eq!((index.len(), index.removed()), (19, 1));
+        assert!(!search_ids(&index, &stored[5], 20).contains(&"5".to_string()));
+        assert!(index.update(5, vec![0.0; 4], String::new()).is_err());
+
+        // An updated vector keeps its id and is found at its new place
+        index.update(7, vec![9.0, 9.0, 9.0, 9.0], "moved".to_string()).unwrap();
+        let found = index.search(&[9.0, 9.0, 9.0, 9.0], 1).unwrap();
+        assert_eq!((found[0].vector_id.as_str(), found[0].metadata.as_str()), ("7", "moved"));
+        assert_eq!(index.len(), 19);
+
+        // New vectors get fresh ids, not removed ones
+        index.add(vectors(2, 4, 5), vec![String::new(); 2]).unwrap();
+        assert_eq!(search_ids(&index, &vectors(2, 4, 5)[1], 1), vec!["21"]);
+
+        // Removing past the compaction share drops the removed nodes but no ids
+        index.remove(&(0..5).collect::<Vec<_>>());
+        assert_eq!((index.len(), index.removed()), (16, 0));
+        for id in [6, 8, 19, 20] {
+            let query = if id < 20 { stored[id].clone() } else { vectors(2, 4, 5)[0].clone() };
+            assert_eq!(search_ids(&index, &query, 1), vec![id.to_string()]);
+        }
+    }
+
+    #[test]
+    fn test_metrics_rank_differently() {
+        // `a` is nearest, `b` has the largest inner product and `c` points
+        // the same way as the query
+        let query = [1.0, 0.1];
+        let mut expected = HashMap::new();
+        expected.insert("cosine", (["c", "a", "b"], 1.0));
+        expected.insert("l2", (["a", "c", "b"], -0.1));
+        expected.insert("ip", (["b", "a", "c"], 3.3));
+        for metric in [Metric::Cosine, Metric::L2, Metric::InnerProduct] {
+            let mut index = VectorIndex::new(2, metric);
+            let vectors = vec![vec![1.0, 0.0], vec![3.0, 3.0], vec![0.5, 0.05]];
+            index.add(vectors, vec!["a".to_string(), "b".to_string(), "c".to_string()]).unwrap();
+            let results = index.search(&query, 3).unwrap();
+            let (order, best) = expected[metric.name()];
+            assert_eq!(results.iter().map(|r| r.metadata.as_str()).collect::<Vec<_>>(), order, "{}", metric.name());
+            assert!((results[0].similarity_score - best).abs() < 1e-5, "{}: {}", metric.name(), results[0].similarity_score);
+        }
+
+        // Cosine stores unit vectors, so scale does not matter
+        let mut index = VectorIndex::new(2, Metric::Cosine);
+        index.add(vec![vec![100.0, 10.0]], vec![String::new()]).unwrap();
+        assert!((index.search(&query, 1).unwrap()[0].similarity_score - 1.0).abs() < 1e-5);
+        assert_eq!(Metric::parse("Inner_Product"), Ok(Metric::InnerProduct));
+        assert!(Metric::parse("manhattan").is_err());
+    }
+
+    #[test]
+    fn test_compact_and_rebuild_reclaim_removed() {
+        let queries = vectors(10, 16, 1);
+        for quantize in [false, true] {
+            let mut index = index(200, 16, Metric::Cosine);
+            if quantize {
+                index.enable_quantization(Some(QuantizationMode::Product), 20, 4, true).unwrap();
+            }
+            let removed: Vec<usize> = (0..200).step_by(10).collect();
+            index.remove(&removed);
+            let before: Vec<Vec<String>> = queries.iter().map(|q| search_ids(&index, q, 5)).collect();
+
+            let report = index.compact();
+            assert_eq!((report.vectors, report.removed_nodes, index.removed()), (180, 20, 0));
+            assert_eq!(report.bytes_before - report.bytes_after, report.bytes_reclaimed);
+            assert!(report.bytes_reclaimed > 0);
+            assert!(report.recall_estimate.unwrap() > 0.9);
+            let after: Vec<Vec<String>> = queries.iter().map(|q| search_ids(&index, q, 5)).collect();
+            let kept = before.iter().zip(&after).map(|(b, a)| b.iter().filter(|id| a.contains(id)).count()).sum::<usize>();
+            assert!(kept >= 45, "{} of 50 results kept", kept);
+
+            index.remove(&[1, 2]);
+            let report = index.rebuild().unwrap();
+            assert_eq!((report.vectors, report.removed_nodes), (178, 2));
+            assert!(report.bytes_reclaimed > 0);
+            assert_eq!(search_ids(&index, &vectors(4, 16, 0)[3], 1), vec!["3"]);
+        }
+    }
+
+    #[test]
+    fn test_hybrid_search_finds_identifiers() {
+        let count = 200;
+        let mut index = VectorIndex::new(16, Metric::Cosine);
+        let mut metadata: Vec<String> = (0..count).map(|i| format!("log line {}", i)).collect();
+        metadata[123] = "worker crashed in conv_1234 during sync".to_string();
+        metadata[150] = "conv_5678 retried".to_string();
+        index.add(vectors(count, 16, 0), metadata).unwrap();
+
+        // The query vector sits right on vector 7, far from 123
+        let query = vectors(8, 16, 0)[7].clone();
+        assert!(!search_ids(&index, &query, 5).contains(&"123".to_string()));
+        let results = index.hybrid_search("what happened to conv_1234?", &query, 5, 0.5).unwrap();
+        assert_eq!(results[0].vector_id, "123");
+        assert_eq!(results[1].vector_id, "7");
+        // With all the weight on vectors it is the plain search order again
+        let vector_only = index.hybrid_search("conv_1234", &query, 5, 1.0).unwrap();
+        assert_eq!(ids(&vector_only), search_ids(&index, &query, 5));
+        assert!(index.hybrid_search("conv_1234", &query, 5, 1.5).is_err());
+
+        // Removed vectors leave the keyword index too
+        index.remove(&[123]);
+        assert!(!ids(&index.hybrid_search("conv_1234", &query, 5, 0.0).unwrap()).contains(&"123".to_string()));
+        assert_eq!(index.hybrid_search("conv_5678", &query, 5, 0.0).unwrap()[0].vector_id, "150");
+        // and stay out after a reload; compaction moves the rest to new nodes
+        let loaded = reload(&index, "hybrid");
+        assert!(!ids(&loaded.hybrid_search("conv_1234", &query, 5, 0.0).unwrap()).contains(&"123".to_string()));
+        assert_eq!(loaded.hybrid_search("conv_5678", &query, 5, 0.0).unwrap()[0].vector_id, "150");
+        index.compact();
+        assert_eq!(index.hybrid_search("conv_5678", &query, 5, 0.0).unwrap()[0].vector_id, "150");
+    }
+
+    #[test]
+    fn test_save_load_round_trip() {
+        for metric in [Metric::Cosine, Metric::L2, Metric::InnerProduct] {
+            let mut index = index(100, 16, metric);
+            assert_eq!(index.remove(&[3, 50, 97]), 3);
+            let mut loaded = reload(&index, metric.name());
+            assert_eq!((loaded.len(), loaded.removed(), loaded.metric()), (97, 3, metric));
+            assert_same_results(&index, &loaded);
+            // Ids carry on after the saved ones rather than reusing removed ones
+            loaded.add(vectors(1, 16, 2), vec!["new".to_string()]).unwrap();
+            assert_eq!(loaded.search(&vectors(1, 16, 2)[0], 1).unwrap()[0].vector_id, "100");
+        }
+    }
+
+    #[test]
+    fn test_save_load_empty() {
+        let mut loaded = reload(&VectorIndex::new(8, Metric::L2), "empty");
+        assert_eq!((loaded.len(), loaded.dimension(), loaded.metric()), (0, 8, Metric::L2));
+        assert!(loaded.search(&[0.0; 8], 5).unwrap().is_empty());
+        loaded.add(vectors(3, 8, 0), vec![String::new(); 3]).unwrap();
+        assert_eq!(loaded.len(), 3);
+    }
+
+    #[test]
+    fn test_save_load_quantized() {
+        for mode in [QuantizationMode::Int8, QuantizationMode::Product] {
+            for keep_full_precision in [true, false] {
+                let mut index = index(300, 16, Metric::Cosine);
+                index.enable_quantization(Some(mode), 20, 4, keep_full_precision).unwrap();
+                index.remove(&[0, 1]);
+                let name = format!("{}_{}", mode.name(), keep_full_precision);
+                let loaded = reload(&index, &name);
+                assert_eq!(loaded.storage.quantized.as_ref().map(|q| q.mode), Some(mode));
+                assert_same_results(&index, &loaded);
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_keeps_codes() {
+        let mut index = index(300, 32, Metric::Cosine);
+        index.enable_quantization(Some(QuantizationMode::Product), 20, 8, false).unwrap();
+        let path = temp_path("codes");
+        index.save(&path).unwrap();
+        // The codes (34 bytes of int8 and 8 of product codes per vector) and
+        // codebooks, rather than 128 bytes of decoded floats per vector
+        let data_bytes = 300 * (8 + 32 + 8) + 256 * 32 * 4;
+        let file_bytes = fs::metadata(&path).unwrap().len() as usize;
+        assert!(file_bytes < data_bytes + 300 * 1024, "{} bytes saved", file_bytes);
+        fs::remove_file(&path).unwrap();
+
+        // Saving again and again changes nothing
+        let mut loaded = reload(&index, "codes_1");
+        for cycle in 2..5 {
+            loaded = reload(&loaded, &format!("codes_{}", cycle));
+        }
+        let (Some(before), Some(after)) = (&index.storage.quantized, &loaded.storage.quantized) else {
+            panic!("quantization dropped");
+        };
+        for (before, after) in before.int8.iter().zip(&after.int8) {
+            assert_eq!(before.parts(), after.parts());
+        }
+        let (Some((before_pq, before_codes)), Some((after_pq, after_codes))) = (&before.pq, &after.pq) else {
+            panic!("product quantizer dropped");
+        };
+        assert!(before_pq.codebook_floats().eq(after_pq.codebook_floats()));
+        assert!(before_codes.iter().zip(after_codes).all(|(a, b)| a.codes() == b.codes()));
+        assert!(loaded.storage.vectors.iter().all(Vec::is_empty));
+        assert_same_results(&index, &loaded);
+    }
+
+    #[test]
+    fn test_load_rejects_truncated() {
+        let mut index = index(20, 8, Metric::Cosine);
+        index.enable_quantization(Some(QuantizationMode::Int8), 10, 1, false).unwrap();
+        let path = temp_path("truncated");
+        index.save(&path).unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        assert!(VectorIndex::load(&path).err().unwrap().to_string().contains("truncated"));
+        fs::write(&path, [data.as_slice(), &[0]].concat()).unwrap();
+        assert!(VectorIndex::load(&path).err().unwrap().to_string().contains("left over"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_save_load_quantized_after_removing_everything() {
+        let mut index = index(50, 8, Metric::Cosine);
+        index.enable_quantization(Some(QuantizationMode::Product), 10, 2, true).unwrap();
+        index.remove(&(0..50).collect::<Vec<_>>());
+        assert!(index.is_empty());
+
+        // The quantizer trained before the removal is kept, so new vectors
+        // still get product codes
+        let mut loaded = reload(&index, "pq_emptied");
+        assert!(loaded.is_empty());
+        loaded.add(vectors(5, 8, 3), vec![String::new(); 5]).unwrap();
+        assert!(matches!(&loaded.storage.quantized, Some(Quantized { pq: Some((_, codes)), .. }) if codes.len() == 5));
+        assert_eq!(loaded.search(&vectors(1, 8, 3)[0], 1).unwrap()[0].vector_id, "50");
+    }
+}
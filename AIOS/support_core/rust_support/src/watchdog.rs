@@ -0,0 +1,183 @@
+//! Stall watchdog
+//!
+//! Components report that they are alive with heartbeats, each promising
+//! the next within its interval. One that misses it counts as stalled until
+//! its next heartbeat. The heartbeat check reads the same state, so stalls
+//! show in health output whether or not a watchdog thread runs; the thread
+//! reports each stall once, as a `health.stall` event and to its callback.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often the watchdog looks for stalls and for shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+struct Component {
+    interval: Duration,
+    last: Instant,
+    /// The current stall was reported by the watchdog
+    reported: bool,
+}
+
+/// Where a component stands
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentStatus {
+    pub name: String,
+    pub interval: Duration,
+    pub since_last: Duration,
+    pub stalled: bool,
+}
+
+/// Last heartbeat of every component, by name
+#[derive(Default)]
+pub struct Heartbeats {
+    components: BTreeMap<String, Component>,
+}
+
+impl Heartbeats {
+    /// Record a heartbeat from `component`, which promises the next within
+    /// `interval`; true when it was stalled
+    pub fn beat(&mut self, component: &str, interval: Duration) -> bool {
+        let now = Instant::now();
+        let previous = self.components.insert(component.to_string(), Component { interval, last: now, reported: false });
+        previous.is_some_and(|previous| now.duration_since(previous.last) > previous.interval)
+    }
+
+    /// Stop expecting heartbeats from `component`; false when it sent none
+    pub fn remove(&mut self, component: &str) -> bool {
+        self.components.remove(component).is_some()
+    }
+
+    pub fn statuses(&self) -> Vec<ComponentStatus> {
+        self.components.iter().map(|(name, component)| status(name, component)).collect()
+    }
+
+    /// Components stalled since the previous call
+    fn new_stalls(&mut self) -> Vec<ComponentStatus> {
+        let mut stalls = Vec::new();
+        for (name, component) in self.components.iter_mut() {
+            let status = status(name, component);
+            if status.stalled && !component.reported {
+                component.reported = true;
+                stalls.push(status);
+            }
+        }
+        stalls
+    }
+}
+
+fn status(name: &str, component: &Component) -> ComponentStatus {
+    let since_last = component.last.elapsed();
+    ComponentStatus {
+        name: name.to_string(),
+        interval: component.interval,
+        since_last,
+        stalled: since_last > component.interval,
+    }
+}
+
+/// Called with the stalled component and the time since its last heartbeat
+pub type StallCallback = Box<dyn Fn(&str, Duration) + Send>;
+
+/// A running watchdog thread; stops when dropped
+pub struct Watchdog {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub fn start(heartbeats: Arc<Mutex<Heartbeats>>, on_stall: Option<StallCallback>) -> anyhow::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            thread::Builder::new()
+                .name("aios-support-watchdog".to_string())
+                .spawn(move || watchdog_loop(heartbeats, on_stall, stop))?
+        };
+        Ok(Self { stop, thread: Some(thread) })
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // A callback in progress finishes first
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn watchdog_loop(heartbeats: Arc<Mutex<Heartbeats>>, on_stall: Option<StallCallback>, stop: Arc<AtomicBool>) {
+    while !stop.load(Ordering::Relaxed) {
+        // Not holding the lock while calling back, which may send a heartbeat
+        let stalls = heartbeats.lock().unwrap_or_else(|e| e.into_inner()).new_stalls();
+        for stall in stalls {
+            aios_events::event!(
+                "health.stall",
+                component = stall.name.as_str(),
+                since_last_secs = stall.since_last.as_secs_f64(),
+                interval_secs = stall.interval.as_secs_f64(),
+            );
+            if let Some(callback) = &on_stall {
+                callback(&stall.name, stall.since_last);
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_heartbeats_stall_until_next_beat() {
+        let mut heartbeats = Heartbeats::default();
+        assert!(!heartbeats.beat("fast", Duration::from_millis(20)));
+        assert!(!heartbeats.beat("slow", Duration::from_secs(60)));
+        assert!(heartbeats.statuses().iter().all(|status| !status.stalled));
+        assert!(heartbeats.new_stalls().is_empty());
+
+        thread::sleep(Duration::from_millis(40));
+        let statuses = heartbeats.statuses();
+        assert_eq!(statuses.iter().map(|s| (s.name.as_str(), s.stalled)).collect::<Vec<_>>(), [("fast", true), ("slow", false)]);
+        assert!(statuses[0].since_last >= Duration::from_millis(40));
+        // Reported once per stall
+        assert_eq!(heartbeats.new_stalls().iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["fast"]);
+        assert!(heartbeats.new_stalls().is_empty());
+
+        // The next heartbeat ends the stall and says there was one
+        assert!(heartbeats.beat("fast", Duration::from_millis(20)));
+        assert!(heartbeats.statuses().iter().all(|status| !status.stalled));
+        assert!(!heartbeats.beat("fast", Duration::from_millis(20)));
+
+        assert!(heartbeats.remove("fast"));
+        assert!(!heartbeats.remove("fast"));
+        assert_eq!(heartbeats.statuses().len(), 1);
+    }
+
+    #[test]
+    fn test_watchdog_reports_each_stall_once() {
+        let heartbeats = Arc::new(Mutex::new(Heartbeats::default()));
+        heartbeats.lock().unwrap().beat("worker", Duration::from_millis(10));
+        let (sender, stalls) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let watchdog = Watchdog::start(
+            Arc::clone(&heartbeats),
+            Some(Box::new(move |name, since_last| {
+                sender.lock().unwrap().send((name.to_string(), since_last)).unwrap();
+            })),
+        )
+        .unwrap();
+        let (name, since_last) = stalls.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(name, "worker");
+        assert!(since_last > Duration::from_millis(10));
+        assert!(stalls.recv_timeout(3 * POLL_INTERVAL).is_err());
+        drop(watchdog);
+    }
+}
@@ -0,0 +1,69 @@
+//! Long calls release the GIL, so other Python threads keep running
+
+#![cfg(feature = "python")]
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use aios_support_rust::PyRustSupportCore;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// A Python thread counting every millisecond until `running` is cleared
+const TICKER: &str = r#"
+import threading, time
+ticks = 0
+running = True
+def tick():
+    global ticks
+    while running:
+        ticks += 1
+        time.sleep(0.001)
+ticker = threading.Thread(target=tick, daemon=True)
+ticker.start()
+"#;
+
+/// Ticks counted while `call` is repeated, holding the GIL in between, for
+/// `span`
+fn ticks_during(globals: &PyDict, span: Duration, mut call: impl FnMut() -> PyResult<()>) -> PyResult<u64> {
+    let ticks = || -> PyResult<u64> { globals.get_item("ticks")?.expect("ticker started").extract() };
+    let start = ticks()?;
+    let deadline = Instant::now() + span;
+    while Instant::now() < deadline {
+        call()?;
+    }
+    Ok(ticks()? - start)
+}
+
+#[test]
+fn test_vector_operations_release_gil() -> PyResult<()> {
+    const DIMENSION: usize = 64;
+    let cache_dir = std::env::temp_dir().join(format!("aios_support_gil_{}", std::process::id()));
+    let vectors: Vec<Vec<f32>> =
+        (0..200).map(|i| (0..DIMENSION).map(|j| ((i * 31 + j * 7) % 101) as f32 / 101.0).collect()).collect();
+    let metadata: Vec<String> = (0..vectors.len()).map(|i| format!("vector {}", i)).collect();
+
+    pyo3::prepare_freethreaded_python();
+    let result = Python::with_gil(|py| {
+        let globals = PyDict::new(py);
+        py.run(TICKER, Some(globals), None)?;
+        // The ticker stalls while the GIL is held...
+        let stalled = ticks_during(globals, Duration::from_millis(200), || {
+            std::thread::sleep(Duration::from_millis(10));
+            Ok(())
+        })?;
+        // ...but not during calls that release it
+        let core = py.get_type::<PyRustSupportCore>().call1((cache_dir.to_str().unwrap(), DIMENSION))?;
+        let (vectors, metadata) = (vectors.to_object(py), metadata.to_object(py));
+        let adding = ticks_during(globals, Duration::from_millis(200), || {
+            core.call_method1("add_vectors", (&vectors, &metadata)).map(drop)
+        })?;
+        globals.set_item("running", false)?;
+        py.run("ticker.join()", Some(globals), None)?;
+        assert!(stalled <= 2, "ticked {} times with the GIL held", stalled);
+        assert!(adding >= 20, "ticked only {} times while adding vectors", adding);
+        Ok(())
+    });
+    let _ = fs::remove_dir_all(&cache_dir);
+    result
+}
@@ -1,5 +1,5 @@
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList};
+use pyo3::types::{PyBytes, PyDict, PyList, PyString};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::time::{SystemTime, Duration};
@@ -11,6 +11,12 @@ use regex::Regex;
 use std::fs;
 use std::path::Path;
 
+mod hashing;
+mod streaming;
+
+use hashing::Hasher;
+use streaming::{ChunkReader, ChunkWriter, DEFAULT_CHUNK_SIZE};
+
 /// Represents a validation result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[pyclass]
@@ -141,6 +147,7 @@ impl RustUtilsCore {
 
     /// Validate data based on type
     fn validate_data(&mut self, data: String, data_type: String) -> ValidationResult {
+        let _span = aios_trace::span!("RustUtilsCore.validate_data", data_len = data.len(), data_type = data_type);
         let cache_key = format!("{}:{}", data_type, data);
         
         // Check cache first
@@ -191,6 +198,7 @@ impl RustUtilsCore {
 
     /// Sanitize input data
     fn sanitize_input(&self, input_data: &str, max_length: usize) -> String {
+        let _span = aios_trace::span!("RustUtilsCore.sanitize_input", input_len = input_data.len(), max_length = max_length);
         let mut sanitized = input_data.to_string();
         
         // Remove null bytes
@@ -211,14 +219,18 @@ impl RustUtilsCore {
     }
 
     /// Safe file read operation
-    fn safe_file_read(&mut self, file_path: String, encoding: String) -> FileOperationResult {
+    fn safe_file_read(&mut self, py: Python<'_>, file_path: String, encoding: String) -> FileOperationResult {
+        let _span = aios_trace::span!("RustUtilsCore.safe_file_read", file_path = file_path, encoding = encoding);
         let mut result = FileOperationResult::new(false, file_path.clone(), "read".to_string());
         
-        match fs::read_to_string(&file_path) {
-            Ok(content) => {
+        let read = py.allow_threads(|| {
+            fs::read_to_string(&file_path).map(|content| (content.len() as u64, sha256_hex(content.as_bytes())))
+        });
+        match read {
+            Ok((bytes_processed, hash)) => {
                 result.success = true;
-                result.bytes_processed = content.len() as u64;
-                result.hash = self.generate_content_hash(&content);
+                result.bytes_processed = bytes_processed;
+                result.hash = hash;
             }
             Err(e) => {
                 // Log error but don't panic
@@ -231,21 +243,24 @@ impl RustUtilsCore {
     }
 
     /// Safe file write operation
-    fn safe_file_write(&mut self, file_path: String, content: String, encoding: String) -> FileOperationResult {
+    fn safe_file_write(&mut self, py: Python<'_>, file_path: String, content: String, encoding: String) -> FileOperationResult {
+        let _span = aios_trace::span!("RustUtilsCore.safe_file_write", file_path = file_path, content_len = content.len(), encoding = encoding);
         let mut result = FileOperationResult::new(false, file_path.clone(), "write".to_string());
         
         // Create directory if it doesn't exist
-        if let Some(parent) = Path::new(&file_path).parent() {
-            if let Err(_) = fs::create_dir_all(parent) {
-                return result;
-            }
+        let created = py.allow_threads(|| match Path::new(&file_path).parent() {
+            Some(parent) => fs::create_dir_all(parent).is_ok(),
+            None => true,
+        });
+        if !created {
+            return result;
         }
         
-        match fs::write(&file_path, &content) {
-            Ok(_) => {
+        match py.allow_threads(|| fs::write(&file_path, &content).map(|_| sha256_hex(content.as_bytes()))) {
+            Ok(hash) => {
                 result.success = true;
                 result.bytes_processed = content.len() as u64;
-                result.hash = self.generate_content_hash(&content);
+                result.hash = hash;
             }
             Err(e) => {
                 println!("File write error: {}", e);
@@ -256,47 +271,179 @@ impl RustUtilsCore {
         result
     }
 
-    /// Generate file hash
-    fn generate_file_hash(&self, file_path: String, algorithm: String) -> String {
-        match fs::read(&file_path) {
-            Ok(content) => self.generate_content_hash_bytes(&content, &algorithm),
-            Err(_) => String::new()
+    /// Read a file of any size `chunk_size` bytes at a time, calling
+    /// `on_chunk(bytes)` with each chunk; the result's hash is the SHA-256
+    /// of the whole file. Exceptions from `on_chunk` stop the read and
+    /// propagate.
+    #[pyo3(signature = (file_path, on_chunk, chunk_size=DEFAULT_CHUNK_SIZE))]
+    fn safe_file_read_stream(
+        &mut self,
+        py: Python<'_>,
+        file_path: String,
+        on_chunk: PyObject,
+        chunk_size: usize,
+    ) -> PyResult<FileOperationResult> {
+        let _span = aios_trace::span!("RustUtilsCore.safe_file_read_stream", file_path = file_path, chunk_size = chunk_size);
+        if chunk_size == 0 {
+            return Err(errors::validation("chunk_size must be positive"));
+        }
+        let mut result = FileOperationResult::new(false, file_path.clone(), "read_stream".to_string());
+        
+        let read = (|| -> PyResult<std::io::Result<(u64, String)>> {
+            let mut reader = match py.allow_threads(|| ChunkReader::open(Path::new(&file_path), chunk_size, Hasher::sha256())) {
+                Ok(reader) => reader,
+                Err(e) => return Ok(Err(e)),
+            };
+            loop {
+                let chunk = {
+                    let reader = &mut reader;
+                    py.allow_threads(move || reader.next_chunk())
+                };
+                match chunk {
+                    Ok(Some(chunk)) => {
+                        on_chunk.call1(py, (PyBytes::new_bound(py, chunk),))?;
+                    }
+                    Ok(None) => return Ok(Ok(reader.finish())),
+                    Err(e) => return Ok(Err(e)),
+                }
+            }
+        })()?;
+        match read {
+            Ok((bytes_processed, hash)) => {
+                result.success = true;
+                result.bytes_processed = bytes_processed;
+                result.hash = hash;
+            }
+            Err(e) => {
+                println!("File read error: {}", e);
+            }
         }
+        
+        self.file_operations.push(result.clone());
+        Ok(result)
     }
 
-    /// Generate content hash
-    fn generate_content_hash(&self, content: &str) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(content.as_bytes());
-        format!("{:x}", hasher.finalize())
+    /// Write a file of any size from `chunks`, an iterable of bytes or str
+    /// (written as UTF-8), buffering up to `chunk_size` bytes. The file is
+    /// written beside its target and moved into place once complete, so a
+    /// failed write keeps the previous file. Exceptions from the iterable
+    /// stop the write and propagate.
+    #[pyo3(signature = (file_path, chunks, chunk_size=DEFAULT_CHUNK_SIZE))]
+    fn safe_file_write_stream(
+        &mut self,
+        py: Python<'_>,
+        file_path: String,
+        chunks: &Bound<'_, PyAny>,
+        chunk_size: usize,
+    ) -> PyResult<FileOperationResult> {
+        let _span = aios_trace::span!("RustUtilsCore.safe_file_write_stream", file_path = file_path, chunk_size = chunk_size);
+        if chunk_size == 0 {
+            return Err(errors::validation("chunk_size must be positive"));
+        }
+        let mut result = FileOperationResult::new(false, file_path.clone(), "write_stream".to_string());
+        
+        let written = (|| -> PyResult<std::io::Result<(u64, String)>> {
+            let mut writer = match py.allow_threads(|| ChunkWriter::create(Path::new(&file_path), chunk_size)) {
+                Ok(writer) => writer,
+                Err(e) => return Ok(Err(e)),
+            };
+            for chunk in chunks.iter()? {
+                let chunk = chunk?;
+                let bytes: &[u8] = match (chunk.downcast::<PyBytes>(), chunk.downcast::<PyString>()) {
+                    (Ok(bytes), _) => bytes.as_bytes(),
+                    (_, Ok(text)) => text.to_str()?.as_bytes(),
+                    _ => return Err(errors::validation("chunks must be bytes or str")),
+                };
+                let writer = &mut writer;
+                if let Err(e) = py.allow_threads(move || writer.write_chunk(bytes)) {
+                    return Ok(Err(e));
+                }
+            }
+            Ok(py.allow_threads(|| writer.finish()))
+        })()?;
+        match written {
+            Ok((bytes_processed, hash)) => {
+                result.success = true;
+                result.bytes_processed = bytes_processed;
+                result.hash = hash;
+            }
+            Err(e) => {
+                println!("File write error: {}", e);
+            }
+        }
+        
+        self.file_operations.push(result.clone());
+        Ok(result)
     }
 
-    /// Generate content hash with specified algorithm
-    fn generate_content_hash_bytes(&self, content: &[u8], algorithm: &str) -> String {
-        match algorithm.to_lowercase().as_str() {
-            "md5" => {
-                // MD5 not available, use SHA256 instead
-                let mut hasher = Sha256::new();
-                hasher.update(content);
-                format!("{:x}", hasher.finalize())
-            }
-            "sha256" => {
-                let mut hasher = Sha256::new();
-                hasher.update(content);
-                format!("{:x}", hasher.finalize())
+    /// Copy a file of any size `chunk_size` bytes at a time, through a
+    /// temporary file as for `safe_file_write_stream`; the result's path is
+    /// the destination and its hash the SHA-256 of the contents
+    #[pyo3(signature = (source_path, destination_path, chunk_size=DEFAULT_CHUNK_SIZE))]
+    fn safe_file_copy(
+        &mut self,
+        py: Python<'_>,
+        source_path: String,
+        destination_path: String,
+        chunk_size: usize,
+    ) -> PyResult<FileOperationResult> {
+        let _span = aios_trace::span!(
+            "RustUtilsCore.safe_file_copy",
+            source_path = source_path,
+            destination_path = destination_path,
+            chunk_size = chunk_size
+        );
+        if chunk_size == 0 {
+            return Err(errors::validation("chunk_size must be positive"));
+        }
+        let mut result = FileOperationResult::new(false, destination_path.clone(), "copy".to_string());
+        
+        match py.allow_threads(|| streaming::copy(Path::new(&source_path), Path::new(&destination_path), chunk_size)) {
+            Ok((bytes_processed, hash)) => {
+                result.success = true;
+                result.bytes_processed = bytes_processed;
+                result.hash = hash;
             }
-            _ => {
-                // Default to SHA256
-                let mut hasher = Sha256::new();
-                hasher.update(content);
-                format!("{:x}", hasher.finalize())
+            Err(e) => {
+                println!("File copy error: {}", e);
             }
         }
+        
+        self.file_operations.push(result.clone());
+        Ok(result)
+    }
+
+    /// Generate file hash with "md5", "sha256" or "blake3", reading the
+    /// file in chunks; empty when it cannot be read. Unknown algorithms
+    /// raise ValueError.
+    fn generate_file_hash(&self, py: Python<'_>, file_path: String, algorithm: String) -> PyResult<String> {
+        let _span = aios_trace::span!("RustUtilsCore.generate_file_hash", file_path = file_path, algorithm = algorithm);
+        let hasher = Hasher::new(&algorithm).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(py.allow_threads(|| {
+            let mut reader = ChunkReader::open(Path::new(&file_path), DEFAULT_CHUNK_SIZE, hasher)?;
+            while reader.next_chunk()?.is_some() {}
+            Ok::<_, std::io::Error>(reader.finish().1)
+        })
+        .unwrap_or_default())
+    }
+
+    /// Generate content hash
+    fn generate_content_hash(&self, py: Python<'_>, content: &str) -> String {
+        let _span = aios_trace::span!("RustUtilsCore.generate_content_hash", content_len = content.len());
+        py.allow_threads(|| sha256_hex(content.as_bytes()))
+    }
+
+    /// Generate content hash with "md5", "sha256" or "blake3"; unknown
+    /// algorithms raise ValueError
+    fn generate_content_hash_bytes(&self, py: Python<'_>, content: &[u8], algorithm: &str) -> PyResult<String> {
+        let _span = aios_trace::span!("RustUtilsCore.generate_content_hash_bytes", content_len = content.len(), algorithm = algorithm);
+        py.allow_threads(|| hashing::hash_bytes(content, algorithm)).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
     }
 
     /// Generate content ID
     fn generate_content_id(&self, content: &str, prefix: &str) -> String {
-        let hash = self.generate_content_hash(content);
+        let _span = aios_trace::span!("RustUtilsCore.generate_content_id", content_len = content.len(), prefix = prefix);
+        let hash = sha256_hex(content.as_bytes());
         let timestamp = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap()
@@ -307,6 +454,7 @@ impl RustUtilsCore {
 
     /// Create core message
     fn create_core_message(&self, source_core: &str, target_core: &str, message_type: &str, payload: String) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustUtilsCore.create_core_message", source_core = source_core, target_core = target_core, message_type = message_type);
         Python::with_gil(|py| {
             let message = PyDict::new(py);
             message.set_item("message_id", Uuid::new_v4().to_string())?;
@@ -325,6 +473,7 @@ impl RustUtilsCore {
 
     /// Validate core message
     fn validate_core_message(&self, message: PyObject) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustUtilsCore.validate_core_message");
         Python::with_gil(|py| {
             let validation = PyDict::new(py);
             
@@ -354,6 +503,7 @@ impl RustUtilsCore {
 
     /// Get system metrics
     fn get_system_metrics(&self) -> SystemMetrics {
+        let _span = aios_trace::span!("RustUtilsCore.get_system_metrics");
         let mut metrics = SystemMetrics::new();
         
         // Calculate uptime
@@ -373,6 +523,7 @@ impl RustUtilsCore {
 
     /// Cleanup old data
     fn cleanup_old_data(&mut self, days_old: u32) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustUtilsCore.cleanup_old_data", days_old = days_old);
         Python::with_gil(|py| {
             let cutoff_time = SystemTime::now() - Duration::from_secs(days_old as u64 * 86400);
             let mut cleaned_count = 0;
@@ -405,18 +556,21 @@ impl RustUtilsCore {
 
     /// Register utility
     fn register_utility(&mut self, name: &str, description: &str) {
+        let _span = aios_trace::span!("RustUtilsCore.register_utility", name = name);
         self.utility_registry.insert(name.to_string(), description.to_string());
         self.usage_stats.insert(name.to_string(), 0);
     }
 
     /// Track utility usage
     fn track_utility_usage(&mut self, utility_name: &str, success: bool) {
+        let _span = aios_trace::span!("RustUtilsCore.track_utility_usage", utility_name = utility_name, success = success);
         let key = format!("{}:{}", utility_name, if success { "success" } else { "failure" });
         *self.usage_stats.entry(key).or_insert(0) += 1;
     }
 
     /// Get usage statistics
     fn get_usage_stats(&self) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustUtilsCore.get_usage_stats");
         Python::with_gil(|py| {
             let stats = PyDict::new(py);
             for (key, count) in &self.usage_stats {
@@ -428,6 +582,7 @@ impl RustUtilsCore {
 
     /// Get utility registry
     fn get_utility_registry(&self) -> PyResult<PyObject> {
+        let _span = aios_trace::span!("RustUtilsCore.get_utility_registry");
         Python::with_gil(|py| {
             let registry = PyDict::new(py);
             for (name, description) in &self.utility_registry {
@@ -439,11 +594,13 @@ impl RustUtilsCore {
 
     /// Get all file operations
     fn get_file_operations(&self) -> Vec<FileOperationResult> {
+        let _span = aios_trace::span!("RustUtilsCore.get_file_operations");
         self.file_operations.clone()
     }
 
     /// Clear all data
     fn clear_all(&mut self) {
+        let _span = aios_trace::span!("RustUtilsCore.clear_all");
         self.usage_stats.clear();
         self.utility_registry.clear();
         self.file_operations.clear();
@@ -451,12 +608,40 @@ impl RustUtilsCore {
     }
 }
 
+/// Hex SHA-256 digest of `content`
+fn sha256_hex(content: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content);
+    format!("{:x}", hasher.finalize())
+}
+
+mod errors {
+    aios_errors::python_exceptions!(aios_utils_rust);
+}
+
+mod tracing {
+    aios_trace::python_tracing!();
+}
+
+mod bus {
+    aios_bus::python_bus!();
+}
+
+mod rng {
+    aios_rng::python_rng!();
+}
+
 /// Python module definition
 #[pymodule]
-fn aios_utils_rust(_py: Python, m: &PyModule) -> PyResult<()> {
+fn aios_utils_rust(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<ValidationResult>()?;
     m.add_class::<FileOperationResult>()?;
     m.add_class::<SystemMetrics>()?;
     m.add_class::<RustUtilsCore>()?;
+    m.add("DEFAULT_CHUNK_SIZE", DEFAULT_CHUNK_SIZE)?;
+    errors::register(py, m)?;
+    tracing::register(m)?;
+    bus::register(m)?;
+    rng::register(m)?;
     Ok(())
 }
@@ -0,0 +1,221 @@
+//! Chunked file I/O for files too large to hold in memory
+//!
+//! Files are read and written `chunk_size` bytes at a time, hashed as the
+//! bytes go by: with SHA-256 when writing, with any `Hasher` when reading.
+//! Writes go to a temporary file beside the target (its name plus the process
+//! id, a per-process counter and ".tmp", so concurrent writers of the same
+//! target never share one), renamed over it once complete, so a failed write
+//! leaves any previous file intact.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::hashing::Hasher;
+
+/// Default size of each chunk: 1 MiB
+pub const DEFAULT_CHUNK_SIZE: usize = 1 << 20;
+
+/// Temporary files created by this process, to name the next one
+static TEMPORARY_FILES: AtomicU64 = AtomicU64::new(0);
+
+/// Reads a file chunk by chunk, hashing what it has read
+pub struct ChunkReader {
+    file: File,
+    buffer: Vec<u8>,
+    hasher: Hasher,
+    bytes: u64,
+}
+
+impl ChunkReader {
+    pub fn open(path: &Path, chunk_size: usize, hasher: Hasher) -> io::Result<Self> {
+        Ok(Self { file: File::open(path)?, buffer: vec![0; chunk_size.max(1)], hasher, bytes: 0 })
+    }
+
+    /// The next chunk, as full as the file allows; None at the end
+    pub fn next_chunk(&mut self) -> io::Result<Option<&[u8]>> {
+        let mut filled = 0;
+        while filled < self.buffer.len() {
+            match self.file.read(&mut self.buffer[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        if filled == 0 {
+            return Ok(None);
+        }
+        self.hasher.update(&self.buffer[..filled]);
+        self.bytes += filled as u64;
+        Ok(Some(&self.buffer[..filled]))
+    }
+
+    /// Bytes read and their hex digest
+    pub fn finish(self) -> (u64, String) {
+        (self.bytes, self.hasher.finalize_hex())
+    }
+}
+
+/// Writes a file chunk by chunk through a temporary file, hashing what it
+/// has written
+pub struct ChunkWriter {
+    path: PathBuf,
+    temporary: PathBuf,
+    /// Open until finished or dropped
+    file: Option<BufWriter<File>>,
+    hasher: Hasher,
+    bytes: u64,
+    /// Moved into place
+    finished: bool,
+}
+
+impl ChunkWriter {
+    /// Start writing `path`, creating its directory; output is buffered up
+    /// to `chunk_size` bytes
+    pub fn create(path: &Path, chunk_size: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(format!(".{}.{}.tmp", std::process::id(), TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed)));
+        let temporary = PathBuf::from(temporary);
+        let file = OpenOptions::new().write(true).create_new(true).open(&temporary)?;
+        let file = BufWriter::with_capacity(chunk_size.max(1), file);
+        Ok(Self { path: path.to_path_buf(), temporary, file: Some(file), hasher: Hasher::sha256(), bytes: 0, finished: false })
+    }
+
+    pub fn write_chunk(&mut self, chunk: &[u8]) -> io::Result<()> {
+        self.file.as_mut().expect("open until finished").write_all(chunk)?;
+        self.hasher.update(chunk);
+        self.bytes += chunk.len() as u64;
+        Ok(())
+    }
+
+    /// Flush to disk and move the file into place; bytes written and their
+    /// hex SHA-256
+    pub fn finish(mut self) -> io::Result<(u64, String)> {
+        if let Some(file) = self.file.take() {
+            file.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+        }
+        fs::rename(&self.temporary, &self.path)?;
+        self.finished = true;
+        Ok((self.bytes, std::mem::replace(&mut self.hasher, Hasher::sha256()).finalize_hex()))
+    }
+}
+
+impl Drop for ChunkWriter {
+    /// An unfinished write leaves no temporary file behind
+    fn drop(&mut self) {
+        // Closed first, as Windows cannot remove an open file
+        self.file = None;
+        if !self.finished {
+            let _ = fs::remove_file(&self.temporary);
+        }
+    }
+}
+
+/// Copy `source` to `destination` chunk by chunk; bytes copied and their
+/// hex SHA-256
+pub fn copy(source: &Path, destination: &Path, chunk_size: usize) -> io::Result<(u64, String)> {
+    let mut reader = ChunkReader::open(source, chunk_size, Hasher::sha256())?;
+    let mut writer = ChunkWriter::create(destination, chunk_size)?;
+    while let Some(chunk) = reader.next_chunk()? {
+        writer.write_chunk(chunk)?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::hash_bytes;
+
+    const CHUNK: usize = 1024;
+
+    /// Empty scratch directory for one test
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("aios_utils_streaming_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn content(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_write_read_copy_round_trip() {
+        let dir = scratch("round_trip");
+        let content = content(5 * CHUNK + 17);
+        let sha256 = hash_bytes(&content, "sha256").unwrap();
+
+        let mut writer = ChunkWriter::create(&dir.join("nested").join("a.bin"), CHUNK).unwrap();
+        for piece in content.chunks(700) {
+            writer.write_chunk(piece).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), (content.len() as u64, sha256.clone()));
+        assert_eq!(fs::read(dir.join("nested").join("a.bin")).unwrap(), content);
+
+        let mut reader = ChunkReader::open(&dir.join("nested").join("a.bin"), CHUNK, Hasher::new("blake3").unwrap()).unwrap();
+        let mut read = Vec::new();
+        while let Some(chunk) = reader.next_chunk().unwrap() {
+            read.extend_from_slice(chunk);
+        }
+        assert_eq!(read, content);
+        assert_eq!(reader.finish(), (content.len() as u64, hash_bytes(&content, "blake3").unwrap()));
+
+        assert_eq!(copy(&dir.join("nested").join("a.bin"), &dir.join("b.bin"), CHUNK).unwrap(), (content.len() as u64, sha256));
+        assert_eq!(fs::read(dir.join("b.bin")).unwrap(), content);
+        assert_eq!(entries(&dir), ["b.bin", "nested"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        let dir = scratch("boundaries");
+        for (len, chunks) in [(0, vec![]), (100, vec![100]), (CHUNK, vec![CHUNK]), (3 * CHUNK + 5, vec![CHUNK, CHUNK, CHUNK, 5])] {
+            let path = dir.join(format!("{}.bin", len));
+            fs::write(&path, content(len)).unwrap();
+            let mut reader = ChunkReader::open(&path, CHUNK, Hasher::sha256()).unwrap();
+            let mut sizes = Vec::new();
+            while let Some(chunk) = reader.next_chunk().unwrap() {
+                sizes.push(chunk.len());
+            }
+            assert_eq!(sizes, chunks, "{} bytes", len);
+            assert_eq!(reader.finish(), (len as u64, hash_bytes(&content(len), "sha256").unwrap()));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dropped_writer_leaves_target_untouched() {
+        let dir = scratch("dropped");
+        let target = dir.join("target.txt");
+        fs::write(&target, "previous").unwrap();
+
+        // Writers of the same target each get their own temporary file
+        let mut first = ChunkWriter::create(&target, CHUNK).unwrap();
+        let mut second = ChunkWriter::create(&target, CHUNK).unwrap();
+        assert_ne!(first.temporary, second.temporary);
+        first.write_chunk(&content(3 * CHUNK)).unwrap();
+        second.write_chunk(b"second").unwrap();
+        assert_eq!(entries(&dir).len(), 3);
+        drop(first);
+        assert_eq!(fs::read_to_string(&target).unwrap(), "previous");
+        assert_eq!(entries(&dir).len(), 2);
+
+        assert_eq!(second.finish().unwrap().0, 6);
+        assert_eq!(fs::read_to_string(&target).unwrap(), "second");
+        assert_eq!(entries(&dir), ["target.txt"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
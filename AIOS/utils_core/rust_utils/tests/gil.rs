@@ -0,0 +1,74 @@
+//! Long calls release the GIL, so other Python threads keep running
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use aios_utils_rust::RustUtilsCore;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+/// A Python thread counting every millisecond until `running` is cleared
+const TICKER: &str = r#"
+import threading, time
+ticks = 0
+running = True
+def tick():
+    global ticks
+    while running:
+        ticks += 1
+        time.sleep(0.001)
+ticker = threading.Thread(target=tick, daemon=True)
+ticker.start()
+"#;
+
+/// Ticks counted while `call` is repeated, holding the GIL in between, for
+/// `span`
+fn ticks_during(globals: &Bound<'_, PyDict>, span: Duration, mut call: impl FnMut() -> PyResult<()>) -> PyResult<u64> {
+    let ticks = || -> PyResult<u64> { globals.get_item("ticks")?.expect("ticker started").extract() };
+    let start = ticks()?;
+    let deadline = Instant::now() + span;
+    while Instant::now() < deadline {
+        call()?;
+    }
+    Ok(ticks()? - start)
+}
+
+#[test]
+fn test_hashing_and_streaming_release_gil() -> PyResult<()> {
+    let root = std::env::temp_dir().join(format!("aios_utils_gil_{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let source = root.join("source.bin");
+    let content: Vec<u8> = (0..8 * 1024 * 1024u32).map(|i| (i % 251) as u8).collect();
+    fs::write(&source, &content).unwrap();
+    let (source, destination) = (source.to_str().unwrap(), root.join("copy.bin"));
+    let destination = destination.to_str().unwrap();
+
+    pyo3::prepare_freethreaded_python();
+    let result = Python::with_gil(|py| {
+        let core = py.get_type_bound::<RustUtilsCore>().call0()?;
+        let bytes = PyBytes::new_bound(py, &content);
+        let globals = PyDict::new_bound(py);
+        py.run_bound(TICKER, Some(&globals), None)?;
+        // The ticker stalls while the GIL is held...
+        let stalled = ticks_during(&globals, Duration::from_millis(200), || {
+            std::thread::sleep(Duration::from_millis(10));
+            Ok(())
+        })?;
+        // ...but not during calls that release it
+        let hashing = ticks_during(&globals, Duration::from_millis(200), || {
+            core.call_method1("generate_content_hash_bytes", (&bytes, "sha256")).map(drop)?;
+            core.call_method1("generate_file_hash", (source, "blake3")).map(drop)
+        })?;
+        let streaming = ticks_during(&globals, Duration::from_millis(200), || {
+            core.call_method1("safe_file_copy", (source, destination)).map(drop)
+        })?;
+        globals.set_item("running", false)?;
+        py.run_bound("ticker.join()", Some(&globals), None)?;
+        assert!(stalled <= 2, "ticked {} times with the GIL held", stalled);
+        assert!(hashing >= 20, "ticked only {} times during hashing", hashing);
+        assert!(streaming >= 20, "ticked only {} times during copies", streaming);
+        Ok(())
+    });
+    let _ = fs::remove_dir_all(&root);
+    result
+}